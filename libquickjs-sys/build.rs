@@ -13,38 +13,159 @@ fn main() {
     panic!("Invalid config for crate libquickjs-sys: must enable either the 'bundled' or the 'system' feature");
 }
 
+#[cfg(feature = "bindgen")]
 extern crate bindgen;
 
+/// Whether the crate is built for a target using the MSVC toolchain.
+///
+/// The build script is compiled for the host, so `cfg(target_env)` would
+/// describe the host when cross-compiling.
+#[cfg(any(feature = "bundled", feature = "system"))]
+fn is_msvc() -> bool {
+    env::var("CARGO_CFG_TARGET_ENV").ok().as_deref() == Some("msvc")
+}
+
+#[cfg(any(feature = "bundled", feature = "system"))]
+fn is_wasi() -> bool {
+    env::var("CARGO_CFG_TARGET_OS").ok().as_deref() == Some("wasi")
+}
+
+/// Defines needed to compile quickjs for the target.
+///
+/// On 32 bit targets, quickjs represents `JSValue` with NaN boxing as a
+/// `uint64_t`, but the bindings require the struct representation used on
+/// 64 bit targets, which is selected with `JS_PTR64`, or by disabling
+/// `JS_NAN_BOXING` for quickjs-ng. MSVC builds of quickjs use their own
+/// representation.
+#[cfg(feature = "bundled")]
+fn target_defines() -> Vec<(&'static str, Option<&'static str>)> {
+    let pointer_width = env::var("CARGO_CFG_TARGET_POINTER_WIDTH").unwrap_or_default();
+    if pointer_width != "32" {
+        Vec::new()
+    } else if cfg!(feature = "ng") {
+        vec![("JS_NAN_BOXING", Some("0"))]
+    } else if !is_msvc() {
+        vec![("JS_PTR64", None)]
+    } else {
+        Vec::new()
+    }
+}
+
+/// The directory of the bundled quickjs sources in `embed`.
+#[cfg(feature = "bundled")]
+fn embedded_sources() -> &'static str {
+    if cfg!(feature = "ng") {
+        "quickjs-ng"
+    } else {
+        "quickjs"
+    }
+}
+
+#[cfg(feature = "bindgen")]
+fn is_cross_compiling() -> bool {
+    env::var("TARGET").ok() != env::var("HOST").ok()
+}
+
+/// The clang target for a Rust target triple.
+#[cfg(feature = "bindgen")]
+fn clang_target(target: &str) -> String {
+    let (arch, rest) = target.split_at(target.find('-').unwrap_or(target.len()));
+    // Rust includes the RISC-V extensions in the architecture.
+    let arch = if arch.starts_with("riscv64") {
+        "riscv64"
+    } else if arch.starts_with("riscv32") {
+        "riscv32"
+    } else {
+        arch
+    };
+    format!("{}{}", arch, rest)
+}
+
+/// The sysroot of the target, used by bindgen to find the C headers.
+///
+/// `QUICKJS_SYSROOT` takes precedence. Otherwise the C compiler of the
+/// target, as configured with `CC_<target>` or `CROSS_COMPILE`, is asked,
+/// which works for gcc cross toolchains. For WASI, the sysroot of the
+/// [wasi-sdk](https://github.com/WebAssembly/wasi-sdk) at `WASI_SDK_PATH` is
+/// used.
+#[cfg(any(feature = "bundled", feature = "system"))]
+fn sysroot() -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed=QUICKJS_SYSROOT");
+    if let Some(sysroot) = env::var_os("QUICKJS_SYSROOT") {
+        return Some(sysroot.into());
+    }
+    if is_wasi() {
+        println!("cargo:rerun-if-env-changed=WASI_SDK_PATH");
+        let sdk = env::var_os("WASI_SDK_PATH")?;
+        return Some(PathBuf::from(sdk).join("share").join("wasi-sysroot"));
+    }
+
+    let compiler = cc::Build::new().get_compiler();
+    if !compiler.is_like_gnu() {
+        return None;
+    }
+    let output = compiler.to_command().arg("-print-sysroot").output().ok()?;
+    let sysroot = String::from_utf8(output.stdout).ok()?;
+    let sysroot = sysroot.trim();
+    if !output.status.success() || sysroot.is_empty() {
+        return None;
+    }
+    Some(sysroot.into())
+}
+
+/// A bindgen builder for `wrapper.h`, configured for the target.
+#[cfg(feature = "bindgen")]
+fn bindings_builder() -> bindgen::Builder {
+    let mut builder = bindgen::Builder::default().header("wrapper.h");
+    if is_cross_compiling() {
+        let target = env::var("TARGET").unwrap();
+        builder = builder.clang_arg(format!("--target={}", clang_target(&target)));
+        if let Some(sysroot) = sysroot() {
+            builder = builder.clang_arg(format!("--sysroot={}", sysroot.display()));
+        }
+    }
+    builder
+}
+
 #[cfg(feature = "system")]
 fn main() {
-    #[cfg(not(feature = "bindgen"))]
-    panic!("Invalid configuration for libquickjs-sys: Must either enable the bundled or the bindgen feature");
-
     #[cfg(feature = "patched")]
     panic!("Invalid configuration for libquickjs-sys: the patched feature is incompatible with the system feature");
 
-    // compile statics
-    cc::Build::new()
-        .file("static-functions.c")
-        .compile("libquickjs-static-functions.a");
-
-    let lib: std::borrow::Cow<str> = if let Ok(lib) = env::var("QUICKJS_LIBRARY_PATH") {
-        lib.into()
-    } else if cfg!(unix) {
-        if exists(format!("/usr/lib/quickjs/{}.a", LIB_NAME)) {
-            "/usr/lib/quickjs".into()
-        } else if exists("/usr/local/lib/quickjs") {
-            "/usr/local/lib/quickjs".into()
-        } else {
-            panic!("quickjs library could not be found. Try setting the QUICKJS_LIBRARY_PATH env variable");
-        }
+    let mut include_paths = link_system_library();
+
+    println!("cargo:rerun-if-env-changed=QUICKJS_INCLUDE_PATH");
+    if let Some(path) = env::var_os("QUICKJS_INCLUDE_PATH") {
+        let path = PathBuf::from(path);
+        let dir = wrapper_include_dir(&path).unwrap_or_else(|| {
+            panic!(
+                "quickjs error: QUICKJS_INCLUDE_PATH {} does not contain quickjs.h",
+                path.display()
+            )
+        });
+        include_paths = vec![dir];
+    }
+
+    // compile statics against the headers of the system library
+    let mut statics = cc::Build::new();
+    statics.file("static-functions.c").includes(&include_paths);
+    if is_msvc() {
+        statics
+            // JS_STRICT_NAN_BOXING, as defined for MSVC in wrapper.h
+            .define("JS_STRICT_NAN_BOXING", None)
+            .define("_CRT_SECURE_NO_WARNINGS", None)
+            .compile("quickjs-static-functions.lib");
     } else {
-        panic!("quickjs error: Windows is not supported yet");
-    };
+        statics.compile("libquickjs-static-functions.a");
+    }
 
     // Generate bindings.
-    let bindings = bindgen::Builder::default()
-        .header("wrapper.h")
+    let bindings = bindings_builder()
+        .clang_args(
+            include_paths
+                .iter()
+                .map(|path| format!("-I{}", path.display())),
+        )
         .generate()
         .expect("Unable to generate bindings");
 
@@ -53,17 +174,123 @@ fn main() {
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
+}
+
+/// Find the quickjs library installed on the system and instruct cargo to
+/// link it.
+///
+/// `QUICKJS_LIBRARY_PATH` takes precedence. Otherwise quickjs is looked up
+/// with pkg-config, with vcpkg on Windows, and in the directories used by
+/// `make install` of quickjs.
+///
+/// Returns the include directories for `wrapper.h`, if known.
+#[cfg(feature = "system")]
+fn link_system_library() -> Vec<PathBuf> {
+    println!("cargo:rerun-if-env-changed=QUICKJS_LIBRARY_PATH");
+    if let Some(path) = env::var_os("QUICKJS_LIBRARY_PATH") {
+        link_library_dir(Path::new(&path));
+        return Vec::new();
+    }
+
+    let mut config = pkg_config::Config::new();
+    if let Some(statik) = prefer_static() {
+        config.statik(statik);
+    }
+    let pkg_config_error = match config.probe(LIB_NAME) {
+        Ok(library) => {
+            return library
+                .include_paths
+                .iter()
+                .filter_map(|path| wrapper_include_dir(path))
+                .collect();
+        }
+        Err(e) => e,
+    };
 
-    // Instruct cargo to statically link quickjs.
-    println!("cargo:rustc-link-search=native={}", lib);
-    println!("cargo:rustc-link-lib=static={}", LIB_NAME);
+    if env::var("CARGO_CFG_TARGET_OS").ok().as_deref() == Some("windows") {
+        if let Ok(library) = vcpkg::Config::new().find_package(LIB_NAME) {
+            return library
+                .include_paths
+                .iter()
+                .filter_map(|path| wrapper_include_dir(path))
+                .collect();
+        }
+    }
+
+    // The default prefix of `make install`.
+    if cfg!(unix) && !is_cross_compiling() {
+        for dir in &["/usr/lib/quickjs", "/usr/local/lib/quickjs"] {
+            if exists(dir) {
+                link_library_dir(Path::new(dir));
+                return Vec::new();
+            }
+        }
+    }
+
+    panic!(
+        "quickjs library could not be found. Try setting the QUICKJS_LIBRARY_PATH env variable.\n\
+         pkg-config: {}",
+        pkg_config_error
+    );
 }
 
-#[cfg(not(target_env = "msvc"))]
+/// Whether to link quickjs statically, as configured with `QUICKJS_STATIC`.
+#[cfg(feature = "system")]
+fn prefer_static() -> Option<bool> {
+    println!("cargo:rerun-if-env-changed=QUICKJS_STATIC");
+    match env::var("QUICKJS_STATIC").ok()?.as_str() {
+        "0" => Some(false),
+        _ => Some(true),
+    }
+}
+
+/// Instruct cargo to link quickjs from a directory.
+///
+/// The static library is preferred, unless `QUICKJS_STATIC=0` or the
+/// directory only contains a shared library.
+#[cfg(feature = "system")]
+fn link_library_dir(dir: &Path) {
+    let statik = prefer_static().unwrap_or_else(|| {
+        if is_msvc() {
+            // `quickjs.lib` is either the static library or the import
+            // library of `quickjs.dll`, which is installed next to it or in
+            // the sibling `bin` directory.
+            let dll = format!("{}.dll", LIB_NAME);
+            exists(dir.join(format!("{}.lib", LIB_NAME)))
+                && !exists(dir.join(&dll))
+                && !exists(dir.join("..").join("bin").join(&dll))
+        } else {
+            exists(dir.join(format!("lib{}.a", LIB_NAME)))
+        }
+    });
+
+    println!("cargo:rustc-link-search=native={}", dir.display());
+    if statik {
+        println!("cargo:rustc-link-lib=static={}", LIB_NAME);
+    } else {
+        println!("cargo:rustc-link-lib=dylib={}", LIB_NAME);
+    }
+}
+
+/// The include directory for the `#include <quickjs/quickjs.h>` of
+/// `wrapper.h`, given a directory containing `quickjs/quickjs.h`, or a
+/// `quickjs` directory containing `quickjs.h`.
+#[cfg(feature = "system")]
+fn wrapper_include_dir(dir: &Path) -> Option<PathBuf> {
+    if exists(dir.join("quickjs").join("quickjs.h")) {
+        Some(dir.to_path_buf())
+    } else if exists(dir.join("quickjs.h")) && dir.file_name() == Some("quickjs".as_ref()) {
+        dir.parent().map(Path::to_path_buf)
+    } else {
+        None
+    }
+}
+
+#[cfg(all(feature = "bundled", feature = "bindgen"))]
 #[derive(Debug)]
 struct IgnoreMacros(std::collections::HashSet<String>);
 
-#[cfg(not(target_env = "msvc"))]
+#[cfg(all(feature = "bundled", feature = "bindgen"))]
 impl bindgen::callbacks::ParseCallbacks for IgnoreMacros {
     fn will_parse_macro(&self, name: &str) -> bindgen::callbacks::MacroParsingBehavior {
         if self.0.contains(name) {
@@ -74,65 +301,128 @@ impl bindgen::callbacks::ParseCallbacks for IgnoreMacros {
     }
 }
 
-#[cfg(not(target_env = "msvc"))]
 #[cfg(feature = "bundled")]
 fn main() {
-    // compile statics
-    cc::Build::new()
-        .file("static-functions.c")
-        .compile("libquickjs-static-functions.a");
+    let msvc = is_msvc();
+    let wasi = is_wasi();
+    // Clang needs to be pointed to the libc of WASI, unless it comes with
+    // the wasi-sdk.
+    let wasi_sysroot = if wasi { sysroot() } else { None };
+    let configure = |build: &mut cc::Build| {
+        for (name, value) in target_defines() {
+            build.define(name, value);
+        }
+        if let Some(sysroot) = &wasi_sysroot {
+            build.flag(format!("--sysroot={}", sysroot.display()).as_str());
+        }
+    };
 
     let embed_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("embed");
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
 
+    // The sources are copied to `quickjs` for both quickjs and quickjs-ng,
+    // so `#include <quickjs/quickjs.h>` picks the bundled one.
     let code_dir = out_path.join("quickjs");
     if exists(&code_dir) {
         std::fs::remove_dir_all(&code_dir).unwrap();
     }
-    copy_dir::copy_dir(embed_path.join("quickjs"), &code_dir)
+    copy_dir::copy_dir(embed_path.join(embedded_sources()), &code_dir)
         .expect("Could not copy quickjs directory");
 
-    #[cfg(feature = "patched")]
-    apply_patches(&code_dir);
+    // quickjs-ng includes these fixes, and the patches are for quickjs.
+    if !cfg!(feature = "ng") {
+        if wasi {
+            fix_wasi_support(&code_dir);
+        }
+
+        apply_patches(&code_dir, cfg!(feature = "patched"));
+    }
+
+    // compile statics
+    let mut statics = cc::Build::new();
+    statics.file("static-functions.c").include(&out_path);
+    configure(&mut statics);
+    if msvc {
+        statics
+            // JS_STRICT_NAN_BOXING required for MSVC build
+            .define("JS_STRICT_NAN_BOXING", None)
+            .define("_CRT_SECURE_NO_WARNINGS", None)
+            .flag_if_supported(msvc_std_flag())
+            .compile("quickjs-static-functions.lib");
+    } else {
+        statics.compile("libquickjs-static-functions.a");
+    }
 
     eprintln!("Compiling quickjs...");
-    cc::Build::new()
-        .files(
-            [
-                "cutils.c",
-                "libbf.c",
-                "libregexp.c",
-                "libunicode.c",
-                "quickjs.c",
-            ]
-            .iter()
-            .map(|f| code_dir.join(f)),
-        )
-        .define("_GNU_SOURCE", None)
-        .define("CONFIG_BIGNUM", None)
-        // The below flags are used by the official Makefile.
-        .flag_if_supported("-Wchar-subscripts")
-        .flag_if_supported("-Wno-array-bounds")
-        .flag_if_supported("-Wno-format-truncation")
-        .flag_if_supported("-Wno-missing-field-initializers")
-        .flag_if_supported("-Wno-sign-compare")
-        .flag_if_supported("-Wno-unused-parameter")
-        .flag_if_supported("-Wundef")
-        .flag_if_supported("-Wuninitialized")
-        .flag_if_supported("-Wunused")
-        .flag_if_supported("-Wwrite-strings")
-        .flag_if_supported("-funsigned-char")
-        // Below flags are added to supress warnings that appear on some
-        // platforms.
-        .flag_if_supported("-Wno-cast-function-type")
-        .flag_if_supported("-Wno-implicit-fallthrough")
-        // cc uses the OPT_LEVEL env var by default, but we hardcode it to -O2
-        // since release builds use -O3 which might be problematic for quickjs,
-        // and debug builds only happen once anyway so the optimization slowdown
-        // is fine.
-        .opt_level(2)
-        .compile(LIB_NAME);
+    let mut build = cc::Build::new();
+    build.files(source_files().iter().map(|f| code_dir.join(f)));
+    configure(&mut build);
+    if cfg!(feature = "bignum") && !cfg!(feature = "ng") {
+        build.define("CONFIG_BIGNUM", None);
+    }
+    if msvc {
+        build
+            // JS_STRICT_NAN_BOXING required for MSVC build
+            .define("JS_STRICT_NAN_BOXING", None)
+            .define("_CRT_SECURE_NO_WARNINGS", None)
+            .flag_if_supported(msvc_std_flag())
+            // c-smile/quickjspp does not build with opt_level(2)!
+            .opt_level(1);
+        if cfg!(feature = "ng") {
+            build.flag_if_supported("/experimental:c11atomics");
+        }
+    } else {
+        // WASI has no GNU extensions.
+        if !wasi {
+            build.define("_GNU_SOURCE", None);
+        }
+        build
+            // The below flags are used by the official Makefile.
+            .flag_if_supported("-Wchar-subscripts")
+            .flag_if_supported("-Wno-array-bounds")
+            .flag_if_supported("-Wno-format-truncation")
+            .flag_if_supported("-Wno-missing-field-initializers")
+            .flag_if_supported("-Wno-sign-compare")
+            .flag_if_supported("-Wno-unused-parameter")
+            .flag_if_supported("-Wuninitialized")
+            .flag_if_supported("-Wunused")
+            .flag_if_supported("-Wwrite-strings")
+            .flag_if_supported("-funsigned-char")
+            // Below flags are added to supress warnings that appear on some
+            // platforms.
+            .flag_if_supported("-Wno-cast-function-type")
+            .flag_if_supported("-Wno-implicit-fallthrough")
+            // cc uses the OPT_LEVEL env var by default, but we hardcode it to -O2
+            // since release builds use -O3 which might be problematic for quickjs,
+            // and debug builds only happen once anyway so the optimization slowdown
+            // is fine.
+            .opt_level(2);
+        // quickjs-ng relies on undefined macros evaluating to 0.
+        if !cfg!(feature = "ng") {
+            build.flag_if_supported("-Wundef");
+        }
+    }
+    build.compile(LIB_NAME);
+
+    #[cfg(feature = "bindgen")]
+    generate_bindings(&out_path);
+    #[cfg(not(feature = "bindgen"))]
+    copy_pregenerated_bindings(&out_path);
+}
 
+/// The C standard flag for MSVC. quickjs-ng is written in C11.
+#[cfg(feature = "bundled")]
+fn msvc_std_flag() -> &'static str {
+    if cfg!(feature = "ng") {
+        "/std:c11"
+    } else {
+        "/std:c++latest"
+    }
+}
+
+/// Generate the bindings for the bundled quickjs with bindgen.
+#[cfg(all(feature = "bundled", feature = "bindgen"))]
+fn generate_bindings(out_path: &Path) {
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=wrapper.h");
 
@@ -151,15 +441,24 @@ fn main() {
     // The bindgen::Builder is the main entry point
     // to bindgen, and lets you build up options for
     // the resulting bindings.
-    let bindings = bindgen::Builder::default()
-        // The input header we would like to generate
-        // bindings for.
-        .header("wrapper.h")
+    let mut builder = bindings_builder()
         // Tell cargo to invalidate the built crate whenever any of the
         // included header files changed.
         .parse_callbacks(Box::new(bindgen::CargoCallbacks))
         .parse_callbacks(Box::new(ignored_macros))
         .clang_arg("-I".to_owned() + out_path.to_str().unwrap())
+        .clang_args(target_defines().iter().map(|define| match define {
+            (name, Some(value)) => format!("-D{}={}", name, value),
+            (name, None) => format!("-D{}", name),
+        }));
+    // These functions differ from quickjs, and are declared with the
+    // signatures of quickjs by the crate.
+    if cfg!(feature = "ng") {
+        for function in NG_COMPAT_FUNCTIONS {
+            builder = builder.blacklist_function(function);
+        }
+    }
+    let bindings = builder
         // Finish the builder and generate the bindings.
         .generate()
         // Unwrap the Result and panic on failure.
@@ -171,100 +470,183 @@ fn main() {
         .expect("Couldn't write bindings!");
 }
 
-#[cfg(target_env = "msvc")]
-#[cfg(feature = "bundled")]
-fn main() {
-    // compile statics
-    cc::Build::new()
-        .file("static-functions.c")
-        // JS_STRICT_NAN_BOXING required for MSVC build
-        .define("JS_STRICT_NAN_BOXING", None)
-        .define("_CRT_SECURE_NO_WARNINGS", None)
-        .flag_if_supported("/std:c++latest")
-        .compile("quickjs-static-functions.lib");
-
-    let embed_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("embed");
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-
-    let code_dir = out_path.join("quickjs");
-    if exists(&code_dir) {
-        std::fs::remove_dir_all(&code_dir).unwrap();
+/// Functions of quickjs-ng that are declared in `src/lib.rs` or `src/ng.rs`
+/// instead.
+#[cfg(all(feature = "bundled", feature = "bindgen"))]
+const NG_COMPAT_FUNCTIONS: &[&str] = &[
+    "JS_DupValue",
+    "JS_FreeValue",
+    "JS_IsArray",
+    "JS_IsError",
+    "JS_IsFunction",
+    "JS_IsJobPending",
+    "JS_IsRegisteredClass",
+    "JS_NewArrayBuffer",
+    "JS_NewClassID",
+    "JS_SetCanBlock",
+    "JS_SetConstructorBit",
+    "JS_ToCStringLen2",
+];
+
+/// Use the bindings generated for the target with `just generate-bindings`,
+/// which does not require libclang.
+#[cfg(all(feature = "bundled", not(feature = "bindgen")))]
+fn copy_pregenerated_bindings(out_path: &Path) {
+    if cfg!(feature = "ng") {
+        panic!("libquickjs-sys has no pre-generated bindings for quickjs-ng: enable the bindgen feature");
     }
-    copy_dir::copy_dir(embed_path.join("quickjs"), &code_dir)
-        .expect("Could not copy quickjs directory");
-
-    // Patch command generally unavailable on Windows
-    // #[cfg(feature = "patched")]
-    // apply_patches(&code_dir);
-
-    eprintln!("Compiling quickjs...");
-    cc::Build::new()
-        .files(
-            [
-                "cutils.c",
-                "libbf.c",
-                "libregexp.c",
-                "libunicode.c",
-                "quickjs.c",
-            ]
-            .iter()
-            .map(|f| code_dir.join(f)),
-        )
-        // JS_STRICT_NAN_BOXING required for MSVC build
-        .define("JS_STRICT_NAN_BOXING", None)
-        .define("_CRT_SECURE_NO_WARNINGS", None)
-        .define("CONFIG_BIGNUM", None)
-        .flag_if_supported("/std:c++latest")
-        // c-smile/quickjspp does not build with opt_level(2)!
-        .opt_level(1)
-        .compile(LIB_NAME);
+    let target = env::var("TARGET").unwrap();
+    let path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join("embed")
+        .join("bindings")
+        .join(format!("{}.rs", target));
+    println!("cargo:rerun-if-changed={}", path.display());
+    if !exists(&path) {
+        panic!(
+            "libquickjs-sys has no pre-generated bindings for {}: enable the bindgen feature",
+            target
+        );
+    }
+    std::fs::copy(&path, out_path.join("bindings.rs")).expect("Could not copy bindings");
+}
 
-    // Tell cargo to invalidate the built crate whenever the wrapper changes
-    println!("cargo:rerun-if-changed=wrapper.h");
+/// The C files of the bundled quickjs.
+///
+/// `libbf` implements `BigInt`, `BigFloat` and `BigDecimal`, and is only
+/// needed with the `bignum` feature. quickjs-ng implements `BigInt` itself.
+#[cfg(feature = "bundled")]
+fn source_files() -> Vec<&'static str> {
+    if cfg!(feature = "ng") {
+        return vec![
+            "cutils.c",
+            "dtoa.c",
+            "libregexp.c",
+            "libunicode.c",
+            "quickjs.c",
+        ];
+    }
+    let mut files = vec!["cutils.c", "libregexp.c", "libunicode.c", "quickjs.c"];
+    if cfg!(feature = "bignum") {
+        files.push("libbf.c");
+    }
+    files
+}
 
-    // The bindgen::Builder is the main entry point
-    // to bindgen, and lets you build up options for
-    // the resulting bindings.
-    let bindings = bindgen::Builder::default()
-        // The input header we would like to generate
-        // bindings for.
-        .header("wrapper.h")
-        // Tell cargo to invalidate the built crate whenever any of the
-        // included header files changed.
-        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
-        .clang_arg("-I".to_owned() + out_path.to_str().unwrap())
-        // Finish the builder and generate the bindings.
-        .generate()
-        // Unwrap the Result and panic on failure.
-        .expect("Unable to generate bindings");
+/// Make quickjs compile for WASI.
+///
+/// WebAssembly has no computed gotos, no threads for `Atomics`, and only
+/// supports rounding to nearest, so the corresponding features are disabled.
+/// `Number.prototype.toFixed` and `toPrecision` therefore round ties to even.
+/// The headers included on Linux are also available on WASI.
+#[cfg(feature = "bundled")]
+fn fix_wasi_support(code_dir: &Path) {
+    let path = code_dir.join("quickjs.c");
+    let mut code = std::fs::read_to_string(&path).expect("Could not read quickjs.c");
+
+    let fixes = [
+        (
+            "#elif defined(__linux__)\n#include <malloc.h>",
+            "#elif defined(__linux__) || defined(__wasi__)\n#include <malloc.h>",
+        ),
+        (
+            "#if defined(EMSCRIPTEN) || defined(_MSC_VER)\n#define DIRECT_DISPATCH  0",
+            "#if defined(EMSCRIPTEN) || defined(_MSC_VER) || defined(__wasi__)\n#define DIRECT_DISPATCH  0",
+        ),
+        (
+            "#if !defined(EMSCRIPTEN) && !defined(_MSC_VER)\n#define CONFIG_ATOMICS",
+            "#if !defined(EMSCRIPTEN) && !defined(_MSC_VER) && !defined(__wasi__)\n#define CONFIG_ATOMICS",
+        ),
+        (
+            "#if !defined(_WIN32)\n/* define it if printf uses the RNDN rounding mode instead of RNDNA */",
+            "#if !defined(_WIN32) && !defined(__wasi__)\n/* define it if printf uses the RNDN rounding mode instead of RNDNA */",
+        ),
+    ];
+    for (original, fixed) in fixes.iter() {
+        assert!(
+            code.contains(original),
+            "Could not find {:?} in quickjs.c",
+            original
+        );
+        code = code.replace(original, fixed);
+    }
 
-    // Write the bindings to the $OUT_DIR/bindings.rs file.
-    bindings
-        .write_to_file(out_path.join("bindings.rs"))
-        .expect("Couldn't write bindings!");
+    std::fs::write(&path, code).expect("Could not write quickjs.c");
 }
 
-#[cfg(feature = "patched")]
-fn apply_patches(code_dir: &PathBuf) {
+/// Patches in `embed/patches` that are applied without the `patched`
+/// feature.
+///
+/// Without `stack-overflow-signed.patch`, calling into quickjs from a stack
+/// frame above the one the runtime was created in fails with a spurious
+/// stack overflow error.
+#[cfg(feature = "bundled")]
+const REQUIRED_PATCHES: &[&str] = &["stack-overflow-signed.patch"];
+
+/// Apply the patches in `embed/patches` to the quickjs sources, or only the
+/// [REQUIRED_PATCHES] unless `all` is set.
+///
+/// The patches are applied in Rust, as the `patch` command is not available
+/// everywhere. Like `patch`, files are looked up by their name in
+/// `code_dir`, ignoring the directories of the paths in the patch.
+#[cfg(feature = "bundled")]
+fn apply_patches(code_dir: &Path, all: bool) {
     use std::fs;
 
     eprintln!("Applying patches...");
     let embed_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("embed");
     let patches_path = embed_path.join("patches");
-    for patch in fs::read_dir(patches_path).expect("Could not open patches directory") {
-        let patch = patch.expect("Could not open patch");
-        eprintln!("Applying {:?}...", patch.file_name());
-        let status = std::process::Command::new("patch")
-            .current_dir(&code_dir)
-            .arg("-i")
-            .arg(fs::canonicalize(patch.path()).expect("Cannot canonicalize patch path"))
-            .spawn()
-            .expect("Could not apply patches")
-            .wait()
-            .expect("Could not apply patches");
-        assert!(
-            status.success(),
-            "Patch command returned non-zero exit code"
-        );
+    let mut patches = fs::read_dir(patches_path)
+        .expect("Could not open patches directory")
+        .map(|entry| entry.expect("Could not open patch").path())
+        .filter(|path| all || REQUIRED_PATCHES.iter().any(|name| path.ends_with(name)))
+        .collect::<Vec<_>>();
+    patches.sort();
+    for patch in patches {
+        eprintln!("Applying {:?}...", patch.file_name().unwrap());
+        println!("cargo:rerun-if-changed={}", patch.display());
+        let content = fs::read_to_string(&patch).expect("Could not read patch");
+        for file_patch in split_patch(&content) {
+            let file_patch = diffy::Patch::from_str(file_patch)
+                .unwrap_or_else(|e| panic!("Could not parse patch {}: {}", patch.display(), e));
+            let name = file_patch
+                .modified()
+                .or_else(|| file_patch.original())
+                .and_then(|name| Path::new(name).file_name())
+                .unwrap_or_else(|| panic!("Patch {} has no file name", patch.display()));
+            let path = code_dir.join(name);
+            let code = fs::read_to_string(&path).expect("Could not read patched file");
+            let code = diffy::apply(&code, &file_patch).unwrap_or_else(|e| {
+                panic!(
+                    "Could not apply patch {} to {}: {}",
+                    patch.display(),
+                    path.display(),
+                    e
+                )
+            });
+            fs::write(&path, code).expect("Could not write patched file");
+        }
+    }
+}
+
+/// Split a patch of multiple files, as generated by `git diff`, into the
+/// patches of the single files.
+#[cfg(feature = "bundled")]
+fn split_patch(patch: &str) -> Vec<&str> {
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in patch.split_inclusive('\n') {
+        if line.starts_with("diff ") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
     }
+    starts.push(patch.len());
+    starts
+        .windows(2)
+        .map(|range| &patch[range[0]..range[1]])
+        .filter(|file_patch| file_patch.contains("\n@@ "))
+        .collect()
 }
@@ -0,0 +1,1392 @@
+/* automatically generated by rust-bindgen 0.55.1 */
+
+pub type FILE = ::std::os::raw::c_void;
+pub const JS_PROP_CONFIGURABLE: u32 = 1;
+pub const JS_PROP_WRITABLE: u32 = 2;
+pub const JS_PROP_ENUMERABLE: u32 = 4;
+pub const JS_PROP_C_W_E: u32 = 7;
+pub const JS_PROP_LENGTH: u32 = 8;
+pub const JS_PROP_TMASK: u32 = 48;
+pub const JS_PROP_NORMAL: u32 = 0;
+pub const JS_PROP_GETSET: u32 = 16;
+pub const JS_PROP_VARREF: u32 = 32;
+pub const JS_PROP_AUTOINIT: u32 = 48;
+pub const JS_PROP_HAS_SHIFT: u32 = 8;
+pub const JS_PROP_HAS_CONFIGURABLE: u32 = 256;
+pub const JS_PROP_HAS_WRITABLE: u32 = 512;
+pub const JS_PROP_HAS_ENUMERABLE: u32 = 1024;
+pub const JS_PROP_HAS_GET: u32 = 2048;
+pub const JS_PROP_HAS_SET: u32 = 4096;
+pub const JS_PROP_HAS_VALUE: u32 = 8192;
+pub const JS_PROP_THROW: u32 = 16384;
+pub const JS_PROP_THROW_STRICT: u32 = 32768;
+pub const JS_PROP_NO_ADD: u32 = 65536;
+pub const JS_PROP_NO_EXOTIC: u32 = 131072;
+pub const JS_DEFAULT_STACK_SIZE: u32 = 262144;
+pub const JS_EVAL_TYPE_GLOBAL: u32 = 0;
+pub const JS_EVAL_TYPE_MODULE: u32 = 1;
+pub const JS_EVAL_TYPE_DIRECT: u32 = 2;
+pub const JS_EVAL_TYPE_INDIRECT: u32 = 3;
+pub const JS_EVAL_TYPE_MASK: u32 = 3;
+pub const JS_EVAL_FLAG_STRICT: u32 = 8;
+pub const JS_EVAL_FLAG_STRIP: u32 = 16;
+pub const JS_EVAL_FLAG_COMPILE_ONLY: u32 = 32;
+pub const JS_EVAL_FLAG_BACKTRACE_BARRIER: u32 = 64;
+pub const JS_ATOM_NULL: u32 = 0;
+pub const JS_CALL_FLAG_CONSTRUCTOR: u32 = 1;
+pub const JS_GPN_STRING_MASK: u32 = 1;
+pub const JS_GPN_SYMBOL_MASK: u32 = 2;
+pub const JS_GPN_PRIVATE_MASK: u32 = 4;
+pub const JS_GPN_ENUM_ONLY: u32 = 16;
+pub const JS_GPN_SET_ENUM: u32 = 32;
+pub const JS_PARSE_JSON_EXT: u32 = 1;
+pub const JS_WRITE_OBJ_BYTECODE: u32 = 1;
+pub const JS_WRITE_OBJ_BSWAP: u32 = 2;
+pub const JS_WRITE_OBJ_SAB: u32 = 4;
+pub const JS_WRITE_OBJ_REFERENCE: u32 = 8;
+pub const JS_READ_OBJ_BYTECODE: u32 = 1;
+pub const JS_READ_OBJ_ROM_DATA: u32 = 2;
+pub const JS_READ_OBJ_SAB: u32 = 4;
+pub const JS_READ_OBJ_REFERENCE: u32 = 8;
+pub const JS_DEF_CFUNC: u32 = 0;
+pub const JS_DEF_CGETSET: u32 = 1;
+pub const JS_DEF_CGETSET_MAGIC: u32 = 2;
+pub const JS_DEF_PROP_STRING: u32 = 3;
+pub const JS_DEF_PROP_INT32: u32 = 4;
+pub const JS_DEF_PROP_INT64: u32 = 5;
+pub const JS_DEF_PROP_DOUBLE: u32 = 6;
+pub const JS_DEF_PROP_UNDEFINED: u32 = 7;
+pub const JS_DEF_OBJECT: u32 = 8;
+pub const JS_DEF_ALIAS: u32 = 9;
+pub type __darwin_size_t = ::std::os::raw::c_ulong;
+pub type size_t = __darwin_size_t;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSRuntime {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSContext {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSObject {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSClass {
+    _unused: [u8; 0],
+}
+pub type JSClassID = u32;
+pub type JSAtom = u32;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSRefCountHeader {
+    pub ref_count: ::std::os::raw::c_int,
+}
+pub const JS_TAG_FIRST: ::std::os::raw::c_int = -11;
+pub const JS_TAG_BIG_DECIMAL: ::std::os::raw::c_int = -11;
+pub const JS_TAG_BIG_INT: ::std::os::raw::c_int = -10;
+pub const JS_TAG_BIG_FLOAT: ::std::os::raw::c_int = -9;
+pub const JS_TAG_SYMBOL: ::std::os::raw::c_int = -8;
+pub const JS_TAG_STRING: ::std::os::raw::c_int = -7;
+pub const JS_TAG_MODULE: ::std::os::raw::c_int = -3;
+pub const JS_TAG_FUNCTION_BYTECODE: ::std::os::raw::c_int = -2;
+pub const JS_TAG_OBJECT: ::std::os::raw::c_int = -1;
+pub const JS_TAG_INT: ::std::os::raw::c_int = 0;
+pub const JS_TAG_BOOL: ::std::os::raw::c_int = 1;
+pub const JS_TAG_NULL: ::std::os::raw::c_int = 2;
+pub const JS_TAG_UNDEFINED: ::std::os::raw::c_int = 3;
+pub const JS_TAG_UNINITIALIZED: ::std::os::raw::c_int = 4;
+pub const JS_TAG_CATCH_OFFSET: ::std::os::raw::c_int = 5;
+pub const JS_TAG_EXCEPTION: ::std::os::raw::c_int = 6;
+pub const JS_TAG_FLOAT64: ::std::os::raw::c_int = 7;
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union JSValueUnion {
+    pub int32: i32,
+    pub float64: f64,
+    pub ptr: *mut ::std::os::raw::c_void,
+    _bindgen_union_align: u64,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct JSValue {
+    pub u: JSValueUnion,
+    pub tag: i64,
+}
+pub type JSCFunction = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut JSContext,
+        this_val: JSValue,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> JSValue,
+>;
+pub type JSCFunctionMagic = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut JSContext,
+        this_val: JSValue,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+        magic: ::std::os::raw::c_int,
+    ) -> JSValue,
+>;
+pub type JSCFunctionData = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut JSContext,
+        this_val: JSValue,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+        magic: ::std::os::raw::c_int,
+        func_data: *mut JSValue,
+    ) -> JSValue,
+>;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSMallocState {
+    pub malloc_count: size_t,
+    pub malloc_size: size_t,
+    pub malloc_limit: size_t,
+    pub opaque: *mut ::std::os::raw::c_void,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSMallocFunctions {
+    pub js_malloc: ::std::option::Option<
+        unsafe extern "C" fn(s: *mut JSMallocState, size: size_t) -> *mut ::std::os::raw::c_void,
+    >,
+    pub js_free: ::std::option::Option<
+        unsafe extern "C" fn(s: *mut JSMallocState, ptr: *mut ::std::os::raw::c_void),
+    >,
+    pub js_realloc: ::std::option::Option<
+        unsafe extern "C" fn(
+            s: *mut JSMallocState,
+            ptr: *mut ::std::os::raw::c_void,
+            size: size_t,
+        ) -> *mut ::std::os::raw::c_void,
+    >,
+    pub js_malloc_usable_size:
+        ::std::option::Option<unsafe extern "C" fn(ptr: *const ::std::os::raw::c_void) -> size_t>,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSGCObjectHeader {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn JS_NewRuntime() -> *mut JSRuntime;
+}
+extern "C" {
+    pub fn JS_SetRuntimeInfo(rt: *mut JSRuntime, info: *const ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn JS_SetMemoryLimit(rt: *mut JSRuntime, limit: size_t);
+}
+extern "C" {
+    pub fn JS_SetGCThreshold(rt: *mut JSRuntime, gc_threshold: size_t);
+}
+extern "C" {
+    pub fn JS_SetMaxStackSize(rt: *mut JSRuntime, stack_size: size_t);
+}
+extern "C" {
+    pub fn JS_NewRuntime2(
+        mf: *const JSMallocFunctions,
+        opaque: *mut ::std::os::raw::c_void,
+    ) -> *mut JSRuntime;
+}
+extern "C" {
+    pub fn JS_FreeRuntime(rt: *mut JSRuntime);
+}
+extern "C" {
+    pub fn JS_GetRuntimeOpaque(rt: *mut JSRuntime) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn JS_SetRuntimeOpaque(rt: *mut JSRuntime, opaque: *mut ::std::os::raw::c_void);
+}
+pub type JS_MarkFunc =
+    ::std::option::Option<unsafe extern "C" fn(rt: *mut JSRuntime, gp: *mut JSGCObjectHeader)>;
+extern "C" {
+    pub fn JS_MarkValue(rt: *mut JSRuntime, val: JSValue, mark_func: JS_MarkFunc);
+}
+extern "C" {
+    pub fn JS_RunGC(rt: *mut JSRuntime);
+}
+extern "C" {
+    pub fn JS_IsLiveObject(rt: *mut JSRuntime, obj: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_NewContext(rt: *mut JSRuntime) -> *mut JSContext;
+}
+extern "C" {
+    pub fn JS_FreeContext(s: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_DupContext(ctx: *mut JSContext) -> *mut JSContext;
+}
+extern "C" {
+    pub fn JS_GetContextOpaque(ctx: *mut JSContext) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn JS_SetContextOpaque(ctx: *mut JSContext, opaque: *mut ::std::os::raw::c_void);
+}
+extern "C" {
+    pub fn JS_GetRuntime(ctx: *mut JSContext) -> *mut JSRuntime;
+}
+extern "C" {
+    pub fn JS_SetClassProto(ctx: *mut JSContext, class_id: JSClassID, obj: JSValue);
+}
+extern "C" {
+    pub fn JS_GetClassProto(ctx: *mut JSContext, class_id: JSClassID) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewContextRaw(rt: *mut JSRuntime) -> *mut JSContext;
+}
+extern "C" {
+    pub fn JS_AddIntrinsicBaseObjects(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicDate(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicEval(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicStringNormalize(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicRegExpCompiler(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicRegExp(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicJSON(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicProxy(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicMapSet(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicTypedArrays(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicPromise(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicBigInt(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicBigFloat(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicBigDecimal(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_AddIntrinsicOperators(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_EnableBignumExt(ctx: *mut JSContext, enable: ::std::os::raw::c_int);
+}
+extern "C" {
+    pub fn js_string_codePointRange(
+        ctx: *mut JSContext,
+        this_val: JSValue,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn js_malloc_rt(rt: *mut JSRuntime, size: size_t) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn js_free_rt(rt: *mut JSRuntime, ptr: *mut ::std::os::raw::c_void);
+}
+extern "C" {
+    pub fn js_realloc_rt(
+        rt: *mut JSRuntime,
+        ptr: *mut ::std::os::raw::c_void,
+        size: size_t,
+    ) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn js_malloc_usable_size_rt(
+        rt: *mut JSRuntime,
+        ptr: *const ::std::os::raw::c_void,
+    ) -> size_t;
+}
+extern "C" {
+    pub fn js_mallocz_rt(rt: *mut JSRuntime, size: size_t) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn js_malloc(ctx: *mut JSContext, size: size_t) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn js_free(ctx: *mut JSContext, ptr: *mut ::std::os::raw::c_void);
+}
+extern "C" {
+    pub fn js_realloc(
+        ctx: *mut JSContext,
+        ptr: *mut ::std::os::raw::c_void,
+        size: size_t,
+    ) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn js_malloc_usable_size(ctx: *mut JSContext, ptr: *const ::std::os::raw::c_void)
+        -> size_t;
+}
+extern "C" {
+    pub fn js_realloc2(
+        ctx: *mut JSContext,
+        ptr: *mut ::std::os::raw::c_void,
+        size: size_t,
+        pslack: *mut size_t,
+    ) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn js_mallocz(ctx: *mut JSContext, size: size_t) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn js_strdup(
+        ctx: *mut JSContext,
+        str_: *const ::std::os::raw::c_char,
+    ) -> *mut ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn js_strndup(
+        ctx: *mut JSContext,
+        s: *const ::std::os::raw::c_char,
+        n: size_t,
+    ) -> *mut ::std::os::raw::c_char;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSMemoryUsage {
+    pub malloc_size: i64,
+    pub malloc_limit: i64,
+    pub memory_used_size: i64,
+    pub malloc_count: i64,
+    pub memory_used_count: i64,
+    pub atom_count: i64,
+    pub atom_size: i64,
+    pub str_count: i64,
+    pub str_size: i64,
+    pub obj_count: i64,
+    pub obj_size: i64,
+    pub prop_count: i64,
+    pub prop_size: i64,
+    pub shape_count: i64,
+    pub shape_size: i64,
+    pub js_func_count: i64,
+    pub js_func_size: i64,
+    pub js_func_code_size: i64,
+    pub js_func_pc2line_count: i64,
+    pub js_func_pc2line_size: i64,
+    pub c_func_count: i64,
+    pub array_count: i64,
+    pub fast_array_count: i64,
+    pub fast_array_elements: i64,
+    pub binary_object_count: i64,
+    pub binary_object_size: i64,
+}
+extern "C" {
+    pub fn JS_ComputeMemoryUsage(rt: *mut JSRuntime, s: *mut JSMemoryUsage);
+}
+extern "C" {
+    pub fn JS_DumpMemoryUsage(fp: *mut FILE, s: *const JSMemoryUsage, rt: *mut JSRuntime);
+}
+extern "C" {
+    pub fn JS_NewAtomLen(
+        ctx: *mut JSContext,
+        str_: *const ::std::os::raw::c_char,
+        len: size_t,
+    ) -> JSAtom;
+}
+extern "C" {
+    pub fn JS_NewAtom(ctx: *mut JSContext, str_: *const ::std::os::raw::c_char) -> JSAtom;
+}
+extern "C" {
+    pub fn JS_NewAtomUInt32(ctx: *mut JSContext, n: u32) -> JSAtom;
+}
+extern "C" {
+    pub fn JS_NewAtomLenRT(
+        rt: *mut JSRuntime,
+        str_: *const ::std::os::raw::c_char,
+        len: ::std::os::raw::c_int,
+    ) -> JSAtom;
+}
+extern "C" {
+    pub fn JS_AtomGetStrRT(
+        rt: *mut JSRuntime,
+        buf: *mut ::std::os::raw::c_char,
+        buf_size: ::std::os::raw::c_int,
+        atom: JSAtom,
+    ) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn JS_DupAtom(ctx: *mut JSContext, v: JSAtom) -> JSAtom;
+}
+extern "C" {
+    pub fn JS_FreeAtom(ctx: *mut JSContext, v: JSAtom);
+}
+extern "C" {
+    pub fn JS_FreeAtomRT(rt: *mut JSRuntime, v: JSAtom);
+}
+extern "C" {
+    pub fn JS_AtomToValue(ctx: *mut JSContext, atom: JSAtom) -> JSValue;
+}
+extern "C" {
+    pub fn JS_AtomToString(ctx: *mut JSContext, atom: JSAtom) -> JSValue;
+}
+extern "C" {
+    pub fn JS_AtomToCString(ctx: *mut JSContext, atom: JSAtom) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn JS_ValueToAtom(ctx: *mut JSContext, val: JSValue) -> JSAtom;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSPropertyEnum {
+    pub is_enumerable: ::std::os::raw::c_int,
+    pub atom: JSAtom,
+}
+extern "C" {
+    pub fn js_free_prop_enum(ctx: *mut JSContext, tab: *mut JSPropertyEnum, len: u32);
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct JSPropertyDescriptor {
+    pub flags: ::std::os::raw::c_int,
+    pub value: JSValue,
+    pub getter: JSValue,
+    pub setter: JSValue,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSClassExoticMethods {
+    pub get_own_property: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            desc: *mut JSPropertyDescriptor,
+            obj: JSValue,
+            prop: JSAtom,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub get_own_property_names: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            ptab: *mut *mut JSPropertyEnum,
+            plen: *mut u32,
+            obj: JSValue,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub delete_property: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            obj: JSValue,
+            prop: JSAtom,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub define_own_property: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            this_obj: JSValue,
+            prop: JSAtom,
+            val: JSValue,
+            getter: JSValue,
+            setter: JSValue,
+            flags: ::std::os::raw::c_int,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub has_property: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            obj: JSValue,
+            atom: JSAtom,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub get_property: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            obj: JSValue,
+            atom: JSAtom,
+            receiver: JSValue,
+        ) -> JSValue,
+    >,
+    pub set_property: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            obj: JSValue,
+            atom: JSAtom,
+            value: JSValue,
+            receiver: JSValue,
+            flags: ::std::os::raw::c_int,
+        ) -> ::std::os::raw::c_int,
+    >,
+}
+pub type JSClassFinalizer =
+    ::std::option::Option<unsafe extern "C" fn(rt: *mut JSRuntime, val: JSValue)>;
+pub type JSClassGCMark = ::std::option::Option<
+    unsafe extern "C" fn(rt: *mut JSRuntime, val: JSValue, mark_func: JS_MarkFunc),
+>;
+pub type JSClassCall = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut JSContext,
+        func_obj: JSValue,
+        this_val: JSValue,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> JSValue,
+>;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSClassDef {
+    pub class_name: *const ::std::os::raw::c_char,
+    pub finalizer: JSClassFinalizer,
+    pub gc_mark: JSClassGCMark,
+    pub call: JSClassCall,
+    pub exotic: *mut JSClassExoticMethods,
+}
+extern "C" {
+    pub fn JS_NewClassID(pclass_id: *mut JSClassID) -> JSClassID;
+}
+extern "C" {
+    pub fn JS_NewClass(
+        rt: *mut JSRuntime,
+        class_id: JSClassID,
+        class_def: *const JSClassDef,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_IsRegisteredClass(rt: *mut JSRuntime, class_id: JSClassID) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_NewBigInt64(ctx: *mut JSContext, v: i64) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewBigUint64(ctx: *mut JSContext, v: u64) -> JSValue;
+}
+extern "C" {
+    pub fn JS_Throw(ctx: *mut JSContext, obj: JSValue) -> JSValue;
+}
+extern "C" {
+    pub fn JS_GetException(ctx: *mut JSContext) -> JSValue;
+}
+extern "C" {
+    pub fn JS_IsError(ctx: *mut JSContext, val: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_ResetUncatchableError(ctx: *mut JSContext);
+}
+extern "C" {
+    pub fn JS_NewError(ctx: *mut JSContext) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ThrowSyntaxError(
+        ctx: *mut JSContext,
+        fmt: *const ::std::os::raw::c_char,
+        ...
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ThrowTypeError(
+        ctx: *mut JSContext,
+        fmt: *const ::std::os::raw::c_char,
+        ...
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ThrowReferenceError(
+        ctx: *mut JSContext,
+        fmt: *const ::std::os::raw::c_char,
+        ...
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ThrowRangeError(
+        ctx: *mut JSContext,
+        fmt: *const ::std::os::raw::c_char,
+        ...
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ThrowInternalError(
+        ctx: *mut JSContext,
+        fmt: *const ::std::os::raw::c_char,
+        ...
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ThrowOutOfMemory(ctx: *mut JSContext) -> JSValue;
+}
+extern "C" {
+    pub fn __JS_FreeValue(ctx: *mut JSContext, v: JSValue);
+}
+extern "C" {
+    pub fn __JS_FreeValueRT(rt: *mut JSRuntime, v: JSValue);
+}
+extern "C" {
+    pub fn JS_ToBool(ctx: *mut JSContext, val: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_ToInt32(ctx: *mut JSContext, pres: *mut i32, val: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_ToInt64(ctx: *mut JSContext, pres: *mut i64, val: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_ToIndex(ctx: *mut JSContext, plen: *mut u64, val: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_ToFloat64(ctx: *mut JSContext, pres: *mut f64, val: JSValue)
+        -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_ToBigInt64(
+        ctx: *mut JSContext,
+        pres: *mut i64,
+        val: JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_ToInt64Ext(
+        ctx: *mut JSContext,
+        pres: *mut i64,
+        val: JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_NewStringLen(
+        ctx: *mut JSContext,
+        str1: *const ::std::os::raw::c_char,
+        len1: size_t,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewString(ctx: *mut JSContext, str_: *const ::std::os::raw::c_char) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewAtomString(ctx: *mut JSContext, str_: *const ::std::os::raw::c_char) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ToString(ctx: *mut JSContext, val: JSValue) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ToPropertyKey(ctx: *mut JSContext, val: JSValue) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ToCStringLen2(
+        ctx: *mut JSContext,
+        plen: *mut size_t,
+        val1: JSValue,
+        cesu8: ::std::os::raw::c_int,
+    ) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn JS_FreeCString(ctx: *mut JSContext, ptr: *const ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn JS_NewObjectProtoClass(
+        ctx: *mut JSContext,
+        proto: JSValue,
+        class_id: JSClassID,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewObjectClass(ctx: *mut JSContext, class_id: ::std::os::raw::c_int) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewObjectProto(ctx: *mut JSContext, proto: JSValue) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewObject(ctx: *mut JSContext) -> JSValue;
+}
+extern "C" {
+    pub fn JS_IsFunction(ctx: *mut JSContext, val: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_IsConstructor(ctx: *mut JSContext, val: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_SetConstructorBit(
+        ctx: *mut JSContext,
+        func_obj: JSValue,
+        val: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_NewArray(ctx: *mut JSContext) -> JSValue;
+}
+extern "C" {
+    pub fn JS_IsArray(ctx: *mut JSContext, val: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_GetPropertyInternal(
+        ctx: *mut JSContext,
+        obj: JSValue,
+        prop: JSAtom,
+        receiver: JSValue,
+        throw_ref_error: ::std::os::raw::c_int,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_GetPropertyStr(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        prop: *const ::std::os::raw::c_char,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_GetPropertyUint32(ctx: *mut JSContext, this_obj: JSValue, idx: u32) -> JSValue;
+}
+extern "C" {
+    pub fn JS_SetPropertyInternal(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        prop: JSAtom,
+        val: JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_SetPropertyUint32(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        idx: u32,
+        val: JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_SetPropertyInt64(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        idx: i64,
+        val: JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_SetPropertyStr(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        prop: *const ::std::os::raw::c_char,
+        val: JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_HasProperty(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        prop: JSAtom,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_IsExtensible(ctx: *mut JSContext, obj: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_PreventExtensions(ctx: *mut JSContext, obj: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_DeleteProperty(
+        ctx: *mut JSContext,
+        obj: JSValue,
+        prop: JSAtom,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_SetPrototype(
+        ctx: *mut JSContext,
+        obj: JSValue,
+        proto_val: JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_GetPrototype(ctx: *mut JSContext, val: JSValue) -> JSValue;
+}
+extern "C" {
+    pub fn JS_GetOwnPropertyNames(
+        ctx: *mut JSContext,
+        ptab: *mut *mut JSPropertyEnum,
+        plen: *mut u32,
+        obj: JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_GetOwnProperty(
+        ctx: *mut JSContext,
+        desc: *mut JSPropertyDescriptor,
+        obj: JSValue,
+        prop: JSAtom,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_Call(
+        ctx: *mut JSContext,
+        func_obj: JSValue,
+        this_obj: JSValue,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_Invoke(
+        ctx: *mut JSContext,
+        this_val: JSValue,
+        atom: JSAtom,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_CallConstructor(
+        ctx: *mut JSContext,
+        func_obj: JSValue,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_CallConstructor2(
+        ctx: *mut JSContext,
+        func_obj: JSValue,
+        new_target: JSValue,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_DetectModule(
+        input: *const ::std::os::raw::c_char,
+        input_len: size_t,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_Eval(
+        ctx: *mut JSContext,
+        input: *const ::std::os::raw::c_char,
+        input_len: size_t,
+        filename: *const ::std::os::raw::c_char,
+        eval_flags: ::std::os::raw::c_int,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_Eval2(
+        ctx: *mut JSContext,
+        input: *const ::std::os::raw::c_char,
+        input_len: size_t,
+        filename: *const ::std::os::raw::c_char,
+        eval_flags: ::std::os::raw::c_int,
+        line_no: ::std::os::raw::c_int,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_EvalFunction(ctx: *mut JSContext, fun_obj: JSValue) -> JSValue;
+}
+extern "C" {
+    pub fn JS_GetGlobalObject(ctx: *mut JSContext) -> JSValue;
+}
+extern "C" {
+    pub fn JS_IsInstanceOf(
+        ctx: *mut JSContext,
+        val: JSValue,
+        obj: JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_DefineProperty(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        prop: JSAtom,
+        val: JSValue,
+        getter: JSValue,
+        setter: JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_DefinePropertyValue(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        prop: JSAtom,
+        val: JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_DefinePropertyValueUint32(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        idx: u32,
+        val: JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_DefinePropertyValueStr(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        prop: *const ::std::os::raw::c_char,
+        val: JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_DefinePropertyGetSet(
+        ctx: *mut JSContext,
+        this_obj: JSValue,
+        prop: JSAtom,
+        getter: JSValue,
+        setter: JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_SetOpaque(obj: JSValue, opaque: *mut ::std::os::raw::c_void);
+}
+extern "C" {
+    pub fn JS_GetOpaque(obj: JSValue, class_id: JSClassID) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn JS_GetOpaque2(
+        ctx: *mut JSContext,
+        obj: JSValue,
+        class_id: JSClassID,
+    ) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn JS_GetClassID(obj: JSValue, ppopaque: *mut *mut ::std::os::raw::c_void) -> JSClassID;
+}
+extern "C" {
+    pub fn JS_ParseJSON(
+        ctx: *mut JSContext,
+        buf: *const ::std::os::raw::c_char,
+        buf_len: size_t,
+        filename: *const ::std::os::raw::c_char,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ParseJSON2(
+        ctx: *mut JSContext,
+        buf: *const ::std::os::raw::c_char,
+        buf_len: size_t,
+        filename: *const ::std::os::raw::c_char,
+        flags: ::std::os::raw::c_int,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_JSONStringify(
+        ctx: *mut JSContext,
+        obj: JSValue,
+        replacer: JSValue,
+        space0: JSValue,
+    ) -> JSValue;
+}
+pub type JSFreeArrayBufferDataFunc = ::std::option::Option<
+    unsafe extern "C" fn(
+        rt: *mut JSRuntime,
+        opaque: *mut ::std::os::raw::c_void,
+        ptr: *mut ::std::os::raw::c_void,
+    ),
+>;
+extern "C" {
+    pub fn JS_NewArrayBuffer(
+        ctx: *mut JSContext,
+        buf: *mut u8,
+        len: size_t,
+        free_func: JSFreeArrayBufferDataFunc,
+        opaque: *mut ::std::os::raw::c_void,
+        is_shared: ::std::os::raw::c_int,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewArrayBufferCopy(ctx: *mut JSContext, buf: *const u8, len: size_t) -> JSValue;
+}
+extern "C" {
+    pub fn JS_DetachArrayBuffer(ctx: *mut JSContext, obj: JSValue);
+}
+extern "C" {
+    pub fn JS_GetArrayBuffer(ctx: *mut JSContext, psize: *mut size_t, obj: JSValue) -> *mut u8;
+}
+extern "C" {
+    pub fn JS_GetTypedArrayBuffer(
+        ctx: *mut JSContext,
+        obj: JSValue,
+        pbyte_offset: *mut size_t,
+        pbyte_length: *mut size_t,
+        pbytes_per_element: *mut size_t,
+    ) -> JSValue;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSSharedArrayBufferFunctions {
+    pub sab_alloc: ::std::option::Option<
+        unsafe extern "C" fn(
+            opaque: *mut ::std::os::raw::c_void,
+            size: size_t,
+        ) -> *mut ::std::os::raw::c_void,
+    >,
+    pub sab_free: ::std::option::Option<
+        unsafe extern "C" fn(opaque: *mut ::std::os::raw::c_void, ptr: *mut ::std::os::raw::c_void),
+    >,
+    pub sab_dup: ::std::option::Option<
+        unsafe extern "C" fn(opaque: *mut ::std::os::raw::c_void, ptr: *mut ::std::os::raw::c_void),
+    >,
+    pub sab_opaque: *mut ::std::os::raw::c_void,
+}
+extern "C" {
+    pub fn JS_SetSharedArrayBufferFunctions(
+        rt: *mut JSRuntime,
+        sf: *const JSSharedArrayBufferFunctions,
+    );
+}
+extern "C" {
+    pub fn JS_NewPromiseCapability(ctx: *mut JSContext, resolving_funcs: *mut JSValue) -> JSValue;
+}
+pub type JSHostPromiseRejectionTracker = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut JSContext,
+        promise: JSValue,
+        reason: JSValue,
+        is_handled: ::std::os::raw::c_int,
+        opaque: *mut ::std::os::raw::c_void,
+    ),
+>;
+extern "C" {
+    pub fn JS_SetHostPromiseRejectionTracker(
+        rt: *mut JSRuntime,
+        cb: JSHostPromiseRejectionTracker,
+        opaque: *mut ::std::os::raw::c_void,
+    );
+}
+pub type JSInterruptHandler = ::std::option::Option<
+    unsafe extern "C" fn(
+        rt: *mut JSRuntime,
+        opaque: *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int,
+>;
+extern "C" {
+    pub fn JS_SetInterruptHandler(
+        rt: *mut JSRuntime,
+        cb: JSInterruptHandler,
+        opaque: *mut ::std::os::raw::c_void,
+    );
+}
+extern "C" {
+    pub fn JS_SetCanBlock(rt: *mut JSRuntime, can_block: ::std::os::raw::c_int);
+}
+extern "C" {
+    pub fn JS_SetIsHTMLDDA(ctx: *mut JSContext, obj: JSValue);
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSModuleDef {
+    _unused: [u8; 0],
+}
+pub type JSModuleNormalizeFunc = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut JSContext,
+        module_base_name: *const ::std::os::raw::c_char,
+        module_name: *const ::std::os::raw::c_char,
+        opaque: *mut ::std::os::raw::c_void,
+    ) -> *mut ::std::os::raw::c_char,
+>;
+pub type JSModuleLoaderFunc = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut JSContext,
+        module_name: *const ::std::os::raw::c_char,
+        opaque: *mut ::std::os::raw::c_void,
+    ) -> *mut JSModuleDef,
+>;
+extern "C" {
+    pub fn JS_SetModuleLoaderFunc(
+        rt: *mut JSRuntime,
+        module_normalize: JSModuleNormalizeFunc,
+        module_loader: JSModuleLoaderFunc,
+        opaque: *mut ::std::os::raw::c_void,
+    );
+}
+extern "C" {
+    pub fn JS_GetImportMeta(ctx: *mut JSContext, m: *mut JSModuleDef) -> JSValue;
+}
+extern "C" {
+    pub fn JS_GetModuleName(ctx: *mut JSContext, m: *mut JSModuleDef) -> JSAtom;
+}
+pub type JSJobFunc = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut JSContext,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> JSValue,
+>;
+extern "C" {
+    pub fn JS_EnqueueJob(
+        ctx: *mut JSContext,
+        job_func: JSJobFunc,
+        argc: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_IsJobPending(rt: *mut JSRuntime) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_ExecutePendingJob(
+        rt: *mut JSRuntime,
+        pctx: *mut *mut JSContext,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_WriteObject(
+        ctx: *mut JSContext,
+        psize: *mut size_t,
+        obj: JSValue,
+        flags: ::std::os::raw::c_int,
+    ) -> *mut u8;
+}
+extern "C" {
+    pub fn JS_WriteObject2(
+        ctx: *mut JSContext,
+        psize: *mut size_t,
+        obj: JSValue,
+        flags: ::std::os::raw::c_int,
+        psab_tab: *mut *mut *mut u8,
+        psab_tab_len: *mut size_t,
+    ) -> *mut u8;
+}
+extern "C" {
+    pub fn JS_ReadObject(
+        ctx: *mut JSContext,
+        buf: *const u8,
+        buf_len: size_t,
+        flags: ::std::os::raw::c_int,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_ResolveModule(ctx: *mut JSContext, obj: JSValue) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_GetScriptOrModuleName(
+        ctx: *mut JSContext,
+        n_stack_levels: ::std::os::raw::c_int,
+    ) -> JSAtom;
+}
+extern "C" {
+    pub fn JS_RunModule(
+        ctx: *mut JSContext,
+        basename: *const ::std::os::raw::c_char,
+        filename: *const ::std::os::raw::c_char,
+    ) -> *mut JSModuleDef;
+}
+extern "C" {
+    pub fn JS_GetModuleExportItemStr(
+        ctx: *mut JSContext,
+        m: *mut JSModuleDef,
+        name: *const ::std::os::raw::c_char,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_GetModuleExportItem(
+        ctx: *mut JSContext,
+        m: *mut JSModuleDef,
+        atom: JSAtom,
+    ) -> JSValue;
+}
+pub const JSCFunctionEnum_JS_CFUNC_generic: JSCFunctionEnum = 0;
+pub const JSCFunctionEnum_JS_CFUNC_generic_magic: JSCFunctionEnum = 1;
+pub const JSCFunctionEnum_JS_CFUNC_constructor: JSCFunctionEnum = 2;
+pub const JSCFunctionEnum_JS_CFUNC_constructor_magic: JSCFunctionEnum = 3;
+pub const JSCFunctionEnum_JS_CFUNC_constructor_or_func: JSCFunctionEnum = 4;
+pub const JSCFunctionEnum_JS_CFUNC_constructor_or_func_magic: JSCFunctionEnum = 5;
+pub const JSCFunctionEnum_JS_CFUNC_f_f: JSCFunctionEnum = 6;
+pub const JSCFunctionEnum_JS_CFUNC_f_f_f: JSCFunctionEnum = 7;
+pub const JSCFunctionEnum_JS_CFUNC_getter: JSCFunctionEnum = 8;
+pub const JSCFunctionEnum_JS_CFUNC_setter: JSCFunctionEnum = 9;
+pub const JSCFunctionEnum_JS_CFUNC_getter_magic: JSCFunctionEnum = 10;
+pub const JSCFunctionEnum_JS_CFUNC_setter_magic: JSCFunctionEnum = 11;
+pub const JSCFunctionEnum_JS_CFUNC_iterator_next: JSCFunctionEnum = 12;
+pub type JSCFunctionEnum = ::std::os::raw::c_uint;
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union JSCFunctionType {
+    pub generic: JSCFunction,
+    pub generic_magic: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            this_val: JSValue,
+            argc: ::std::os::raw::c_int,
+            argv: *mut JSValue,
+            magic: ::std::os::raw::c_int,
+        ) -> JSValue,
+    >,
+    pub constructor: JSCFunction,
+    pub constructor_magic: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            new_target: JSValue,
+            argc: ::std::os::raw::c_int,
+            argv: *mut JSValue,
+            magic: ::std::os::raw::c_int,
+        ) -> JSValue,
+    >,
+    pub constructor_or_func: JSCFunction,
+    pub f_f: ::std::option::Option<unsafe extern "C" fn(arg1: f64) -> f64>,
+    pub f_f_f: ::std::option::Option<unsafe extern "C" fn(arg1: f64, arg2: f64) -> f64>,
+    pub getter: ::std::option::Option<
+        unsafe extern "C" fn(ctx: *mut JSContext, this_val: JSValue) -> JSValue,
+    >,
+    pub setter: ::std::option::Option<
+        unsafe extern "C" fn(ctx: *mut JSContext, this_val: JSValue, val: JSValue) -> JSValue,
+    >,
+    pub getter_magic: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            this_val: JSValue,
+            magic: ::std::os::raw::c_int,
+        ) -> JSValue,
+    >,
+    pub setter_magic: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            this_val: JSValue,
+            val: JSValue,
+            magic: ::std::os::raw::c_int,
+        ) -> JSValue,
+    >,
+    pub iterator_next: ::std::option::Option<
+        unsafe extern "C" fn(
+            ctx: *mut JSContext,
+            this_val: JSValue,
+            argc: ::std::os::raw::c_int,
+            argv: *mut JSValue,
+            pdone: *mut ::std::os::raw::c_int,
+            magic: ::std::os::raw::c_int,
+        ) -> JSValue,
+    >,
+    _bindgen_union_align: u64,
+}
+extern "C" {
+    pub fn JS_NewCFunction2(
+        ctx: *mut JSContext,
+        func: JSCFunction,
+        name: *const ::std::os::raw::c_char,
+        length: ::std::os::raw::c_int,
+        cproto: JSCFunctionEnum,
+        magic: ::std::os::raw::c_int,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_NewCFunctionData(
+        ctx: *mut JSContext,
+        func: JSCFunctionData,
+        length: ::std::os::raw::c_int,
+        magic: ::std::os::raw::c_int,
+        data_len: ::std::os::raw::c_int,
+        data: *mut JSValue,
+    ) -> JSValue;
+}
+extern "C" {
+    pub fn JS_SetConstructor(ctx: *mut JSContext, func_obj: JSValue, proto: JSValue);
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct JSCFunctionListEntry {
+    pub name: *const ::std::os::raw::c_char,
+    pub prop_flags: u8,
+    pub def_type: u8,
+    pub magic: i16,
+    pub u: JSCFunctionListEntry__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union JSCFunctionListEntry__bindgen_ty_1 {
+    pub func: JSCFunctionListEntry__bindgen_ty_1__bindgen_ty_1,
+    pub getset: JSCFunctionListEntry__bindgen_ty_1__bindgen_ty_2,
+    pub alias: JSCFunctionListEntry__bindgen_ty_1__bindgen_ty_3,
+    pub prop_list: JSCFunctionListEntry__bindgen_ty_1__bindgen_ty_4,
+    pub str_: *const ::std::os::raw::c_char,
+    pub i32_: i32,
+    pub i64_: i64,
+    pub f64_: f64,
+    _bindgen_union_align: [u64; 2usize],
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct JSCFunctionListEntry__bindgen_ty_1__bindgen_ty_1 {
+    pub length: u8,
+    pub cproto: u8,
+    pub cfunc: JSCFunctionType,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct JSCFunctionListEntry__bindgen_ty_1__bindgen_ty_2 {
+    pub get: JSCFunctionType,
+    pub set: JSCFunctionType,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSCFunctionListEntry__bindgen_ty_1__bindgen_ty_3 {
+    pub name: *const ::std::os::raw::c_char,
+    pub base: ::std::os::raw::c_int,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct JSCFunctionListEntry__bindgen_ty_1__bindgen_ty_4 {
+    pub tab: *const JSCFunctionListEntry,
+    pub len: ::std::os::raw::c_int,
+}
+extern "C" {
+    pub fn JS_SetPropertyFunctionList(
+        ctx: *mut JSContext,
+        obj: JSValue,
+        tab: *const JSCFunctionListEntry,
+        len: ::std::os::raw::c_int,
+    );
+}
+pub type JSModuleInitFunc = ::std::option::Option<
+    unsafe extern "C" fn(ctx: *mut JSContext, m: *mut JSModuleDef) -> ::std::os::raw::c_int,
+>;
+extern "C" {
+    pub fn JS_NewCModule(
+        ctx: *mut JSContext,
+        name_str: *const ::std::os::raw::c_char,
+        func: JSModuleInitFunc,
+    ) -> *mut JSModuleDef;
+}
+extern "C" {
+    pub fn JS_AddModuleExport(
+        ctx: *mut JSContext,
+        m: *mut JSModuleDef,
+        name_str: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_AddModuleExportList(
+        ctx: *mut JSContext,
+        m: *mut JSModuleDef,
+        tab: *const JSCFunctionListEntry,
+        len: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_SetModuleExport(
+        ctx: *mut JSContext,
+        m: *mut JSModuleDef,
+        export_name: *const ::std::os::raw::c_char,
+        val: JSValue,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn JS_SetModuleExportList(
+        ctx: *mut JSContext,
+        m: *mut JSModuleDef,
+        tab: *const JSCFunctionListEntry,
+        len: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
@@ -9,6 +9,11 @@
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+#[cfg(feature = "ng")]
+mod ng;
+#[cfg(feature = "ng")]
+pub use ng::*;
+
 // import the functions from static-functions.c
 
 extern "C" {
@@ -54,6 +59,33 @@ pub unsafe fn JS_NewFloat64(ctx: *mut JSContext, v: f64) -> JSValue {
     JS_NewFloat64_real(ctx, v)
 }
 
+/// get a property of an object
+/// # Safety
+/// be safe
+#[cfg(not(feature = "ng"))]
+pub unsafe fn JS_GetProperty(ctx: *mut JSContext, this_obj: JSValue, prop: JSAtom) -> JSValue {
+    JS_GetPropertyInternal(ctx, this_obj, prop, this_obj, 0)
+}
+
+/// set a property of an object, throwing on failure
+/// # Safety
+/// be safe
+#[cfg(not(feature = "ng"))]
+pub unsafe fn JS_SetProperty(
+    ctx: *mut JSContext,
+    this_obj: JSValue,
+    prop: JSAtom,
+    val: JSValue,
+) -> ::std::os::raw::c_int {
+    JS_SetPropertyInternal(
+        ctx,
+        this_obj,
+        prop,
+        val,
+        JS_PROP_THROW as ::std::os::raw::c_int,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use std::ffi::CStr;
@@ -87,7 +119,7 @@ mod tests {
             let ival = JS_NewInt32(ctx, 12);
             assert_eq!(ival.tag, 0);
             let fval = JS_NewFloat64(ctx, f64::MAX);
-            assert_eq!(fval.tag, 7);
+            assert_eq!(fval.tag, if cfg!(feature = "ng") { 8 } else { 7 });
             let bval = JS_NewBool(ctx, true);
             assert_eq!(bval.tag, 1);
         }
@@ -0,0 +1,140 @@
+//! The functions of quickjs-ng that differ from quickjs, declared with the
+//! signatures of quickjs.
+//!
+//! quickjs-ng uses `bool` instead of `int` for booleans, dropped the context
+//! argument of some functions, and allocates class ids per runtime.
+
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::{
+    size_t, JSClassID, JSContext, JSFreeArrayBufferDataFunc, JSRuntime, JSValue, JS_FreeRuntime,
+    JS_NewRuntime,
+};
+
+mod ffi {
+    use super::*;
+
+    extern "C" {
+        pub fn JS_IsArray(val: JSValue) -> bool;
+        pub fn JS_IsError(val: JSValue) -> bool;
+        pub fn JS_IsFunction(ctx: *mut JSContext, val: JSValue) -> bool;
+        pub fn JS_IsJobPending(rt: *mut JSRuntime) -> bool;
+        pub fn JS_IsRegisteredClass(rt: *mut JSRuntime, class_id: JSClassID) -> bool;
+        pub fn JS_NewArrayBuffer(
+            ctx: *mut JSContext,
+            buf: *mut u8,
+            len: size_t,
+            free_func: JSFreeArrayBufferDataFunc,
+            opaque: *mut c_void,
+            is_shared: bool,
+        ) -> JSValue;
+        pub fn JS_NewClassID(rt: *mut JSRuntime, pclass_id: *mut JSClassID) -> JSClassID;
+        pub fn JS_SetCanBlock(rt: *mut JSRuntime, can_block: bool);
+        pub fn JS_SetConstructorBit(ctx: *mut JSContext, func_obj: JSValue, val: bool) -> bool;
+        pub fn JS_ToCStringLen2(
+            ctx: *mut JSContext,
+            plen: *mut size_t,
+            val1: JSValue,
+            cesu8: bool,
+        ) -> *const c_char;
+    }
+}
+
+/// check if a value is an array
+/// # Safety
+/// be safe
+pub unsafe fn JS_IsArray(_ctx: *mut JSContext, val: JSValue) -> c_int {
+    ffi::JS_IsArray(val) as c_int
+}
+
+/// check if a value is an Error object
+/// # Safety
+/// be safe
+pub unsafe fn JS_IsError(_ctx: *mut JSContext, val: JSValue) -> c_int {
+    ffi::JS_IsError(val) as c_int
+}
+
+/// check if a value is a function
+/// # Safety
+/// be safe
+pub unsafe fn JS_IsFunction(ctx: *mut JSContext, val: JSValue) -> c_int {
+    ffi::JS_IsFunction(ctx, val) as c_int
+}
+
+/// check if there are pending jobs
+/// # Safety
+/// be safe
+pub unsafe fn JS_IsJobPending(rt: *mut JSRuntime) -> c_int {
+    ffi::JS_IsJobPending(rt) as c_int
+}
+
+/// check if a class is registered in a runtime
+/// # Safety
+/// be safe
+pub unsafe fn JS_IsRegisteredClass(rt: *mut JSRuntime, class_id: JSClassID) -> c_int {
+    ffi::JS_IsRegisteredClass(rt, class_id) as c_int
+}
+
+/// create an ArrayBuffer backed by `buf`
+/// # Safety
+/// be safe
+pub unsafe fn JS_NewArrayBuffer(
+    ctx: *mut JSContext,
+    buf: *mut u8,
+    len: size_t,
+    free_func: JSFreeArrayBufferDataFunc,
+    opaque: *mut c_void,
+    is_shared: c_int,
+) -> JSValue {
+    ffi::JS_NewArrayBuffer(ctx, buf, len, free_func, opaque, is_shared != 0)
+}
+
+/// allocate a class id, unique in the process as in quickjs
+///
+/// quickjs-ng allocates class ids per runtime, after its built-in classes.
+/// The ids are allocated from the first id of a runtime instead, so they can
+/// be registered in any runtime.
+/// # Safety
+/// be safe
+pub unsafe fn JS_NewClassID(pclass_id: *mut JSClassID) -> JSClassID {
+    static NEXT_CLASS_ID: AtomicU32 = AtomicU32::new(0);
+
+    if *pclass_id == 0 {
+        if NEXT_CLASS_ID.load(Ordering::SeqCst) == 0 {
+            let rt = JS_NewRuntime();
+            let mut first = 0;
+            ffi::JS_NewClassID(rt, &mut first);
+            JS_FreeRuntime(rt);
+            let _ = NEXT_CLASS_ID.compare_exchange(0, first, Ordering::SeqCst, Ordering::SeqCst);
+        }
+        *pclass_id = NEXT_CLASS_ID.fetch_add(1, Ordering::SeqCst);
+    }
+    *pclass_id
+}
+
+/// allow `Atomics.wait` to block
+/// # Safety
+/// be safe
+pub unsafe fn JS_SetCanBlock(rt: *mut JSRuntime, can_block: c_int) {
+    ffi::JS_SetCanBlock(rt, can_block != 0)
+}
+
+/// set whether a function can be called as a constructor
+/// # Safety
+/// be safe
+pub unsafe fn JS_SetConstructorBit(ctx: *mut JSContext, func_obj: JSValue, val: c_int) -> c_int {
+    ffi::JS_SetConstructorBit(ctx, func_obj, val != 0) as c_int
+}
+
+/// convert a value to a UTF-8 string, which must be freed with `JS_FreeCString`
+/// # Safety
+/// be safe
+pub unsafe fn JS_ToCStringLen2(
+    ctx: *mut JSContext,
+    plen: *mut size_t,
+    val1: JSValue,
+    cesu8: c_int,
+) -> *const c_char {
+    ffi::JS_ToCStringLen2(ctx, plen, val1, cesu8 != 0)
+}
@@ -1,4 +1,5 @@
-#include "embed/quickjs/quickjs.h"
+#include <stdbool.h>
+#include <quickjs/quickjs.h>
 
 // these are static inline functions in quickjs.h so bindgen does not pick them up
 // we impl them here as real functions so they can be added to lib.rs
@@ -19,6 +20,6 @@ JSValue JS_NewInt32_real(JSContext *ctx, int32_t val) {
     return JS_NewInt32(ctx, val);
 }
 
-JSValue JS_NewBool_real(JSContext *ctx, JS_BOOL val) {
+JSValue JS_NewBool_real(JSContext *ctx, bool val) {
     return JS_NewBool(ctx, val) ;
 }
\ No newline at end of file
@@ -0,0 +1,539 @@
+//! Derive macros for converting Rust structs and enums from and to
+//! Javascript values with [quick-js](https://docs.rs/quick-js).
+//!
+//! The macros are re-exported by `quick-js` with the `derive` feature, see
+//! the documentation of `quick_js::IntoJsValue` and `quick_js::FromJsValue`.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Fields, Generics, Ident, LitStr,
+    Type,
+};
+
+/// Implement `From<T> for JsValue`, which converts a struct to an object.
+///
+/// Each field becomes a property, converted with `Into<JsValue>`.
+///
+/// Field attributes:
+/// * `#[js(rename = "name")]`: use `name` as the property name
+/// * `#[js(skip)]`: do not convert the field
+///
+/// Enums are represented like with serde, depending on the attributes of
+/// the enum:
+/// * externally tagged by default: unit variants are strings with the name
+///   of the variant, and other variants objects with a single property named
+///   after the variant. It holds the value of newtype variants, an array for
+///   tuple variants, or an object for struct variants
+/// * `#[js(tag = "type")]`: internally tagged, as objects with the name of
+///   the variant in the `type` property, next to the fields of struct
+///   variants. Only unit and struct variants are supported
+/// * `#[js(untagged)]`: `null` for unit variants, the value of newtype
+///   variants, an array for tuple variants or an object for struct variants
+///
+/// Variants can be renamed with `#[js(rename = "name")]`.
+#[proc_macro_derive(IntoJsValue, attributes(js))]
+pub fn derive_into_js_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    into_js_value(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `TryFrom<JsValue>` for a struct, which converts an object.
+///
+/// Each field is converted from the property with the same name with
+/// `quick_js::FromJsValue`, so missing properties are converted like
+/// `undefined`.
+/// Other properties are ignored.
+///
+/// Field attributes:
+/// * `#[js(rename = "name")]`: use the property `name`
+/// * `#[js(default)]`: use `Default::default()` if the property is missing
+///   `null` or `undefined`
+/// * `#[js(skip)]`: do not convert the field, and use `Default::default()`
+///
+/// Enums are converted from the representations described for
+/// `IntoJsValue`. Untagged enums try the variants in order, and convert to
+/// the first one that matches.
+#[proc_macro_derive(FromJsValue, attributes(js))]
+pub fn derive_from_js_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_js_value(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A variable of the generated code, which can not clash with the fields.
+fn local(name: &str) -> Ident {
+    Ident::new(name, Span::mixed_site())
+}
+
+fn js_attributes(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("js"))
+}
+
+/// The representation of an enum.
+enum Tagging {
+    External,
+    /// The name of the tag property.
+    Internal(String),
+    Untagged,
+}
+
+impl Tagging {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut tagging = Tagging::External;
+        for attr in js_attributes(&input.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if !matches!(input.data, Data::Enum(_)) {
+                    return Err(meta.error("unknown js attribute for structs"));
+                }
+                if meta.path.is_ident("tag") {
+                    tagging = Tagging::Internal(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("untagged") {
+                    tagging = Tagging::Untagged;
+                } else {
+                    return Err(meta.error("unknown js attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(tagging)
+    }
+}
+
+/// A named field, with its attributes.
+struct Field {
+    ident: Ident,
+    ty: Type,
+    /// The name of the property.
+    name: String,
+    default: bool,
+    skip: bool,
+}
+
+impl Field {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.clone().expect("named field");
+        let mut parsed = Field {
+            name: ident.to_string(),
+            ident,
+            ty: field.ty.clone(),
+            default: false,
+            skip: false,
+        };
+        for attr in js_attributes(&field.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    parsed.name = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("default") {
+                    parsed.default = true;
+                } else if meta.path.is_ident("skip") {
+                    parsed.skip = true;
+                } else {
+                    return Err(meta.error("unknown js attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+
+    fn parse_named(ident: &Ident, fields: &Fields) -> syn::Result<Vec<Self>> {
+        match fields {
+            Fields::Named(fields) => fields.named.iter().map(Field::parse).collect(),
+            _ => Err(syn::Error::new_spanned(
+                ident,
+                "only structs with named fields are supported",
+            )),
+        }
+    }
+}
+
+/// The fields of an enum variant.
+enum VariantFields {
+    Unit,
+    /// The types of the fields of a newtype or tuple variant.
+    Tuple(Vec<Type>),
+    Struct(Vec<Field>),
+}
+
+/// An enum variant, with its attributes.
+struct Variant {
+    ident: Ident,
+    /// The name of the variant in Javascript.
+    name: String,
+    fields: VariantFields,
+}
+
+impl Variant {
+    fn parse(variant: &syn::Variant, tagging: &Tagging) -> syn::Result<Self> {
+        let mut name = variant.ident.to_string();
+        for attr in js_attributes(&variant.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown js attribute"))
+                }
+            })?;
+        }
+        let fields = match &variant.fields {
+            Fields::Unit => VariantFields::Unit,
+            Fields::Unnamed(fields) => {
+                VariantFields::Tuple(fields.unnamed.iter().map(|f| f.ty.clone()).collect())
+            }
+            Fields::Named(fields) => VariantFields::Struct(
+                fields
+                    .named
+                    .iter()
+                    .map(Field::parse)
+                    .collect::<syn::Result<_>>()?,
+            ),
+        };
+        if let (Tagging::Internal(_), VariantFields::Tuple(_)) = (tagging, &fields) {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                "internally tagged enums only support unit and struct variants",
+            ));
+        }
+        Ok(Variant {
+            ident: variant.ident.clone(),
+            name,
+            fields,
+        })
+    }
+}
+
+/// Add `bound` to all type parameters.
+fn add_bounds(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+fn parse_variants(input: &DeriveInput, tagging: &Tagging) -> syn::Result<Vec<Variant>> {
+    match &input.data {
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| Variant::parse(variant, tagging))
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+/// Build an object from the fields that are not skipped, with an optional
+/// tag property.
+fn object_expr(
+    fields: &[Field],
+    access: impl Fn(&Ident) -> TokenStream2,
+    tag: Option<(&str, &str)>,
+) -> TokenStream2 {
+    let object = local("object");
+    let tag = tag.map(|(tag, name)| {
+        quote! {
+            #object.insert(
+                ::std::string::String::from(#tag),
+                ::quick_js::JsValue::String(::std::string::String::from(#name)),
+            );
+        }
+    });
+    let properties = fields.iter().filter(|field| !field.skip).map(|field| {
+        let name = &field.name;
+        let value = access(&field.ident);
+        quote! {
+            #object.insert(
+                ::std::string::String::from(#name),
+                ::std::convert::Into::<::quick_js::JsValue>::into(#value),
+            );
+        }
+    });
+    quote!({
+        let mut #object = ::std::collections::HashMap::new();
+        #tag
+        #(#properties)*
+        ::quick_js::JsValue::Object(#object)
+    })
+}
+
+fn into_variant(enum_ident: &Ident, variant: &Variant, tagging: &Tagging) -> TokenStream2 {
+    let Variant {
+        ident,
+        name,
+        fields,
+    } = variant;
+    let (pattern, content) = match fields {
+        VariantFields::Unit => (quote!(#enum_ident::#ident), None),
+        VariantFields::Tuple(types) => {
+            let bindings = (0..types.len())
+                .map(|index| format_ident!("field{}", index, span = Span::mixed_site()))
+                .collect::<Vec<_>>();
+            let content = match bindings.as_slice() {
+                [binding] => quote!(::std::convert::Into::<::quick_js::JsValue>::into(#binding)),
+                _ => quote! {
+                    ::quick_js::JsValue::Array(::std::vec![
+                        #(::std::convert::Into::<::quick_js::JsValue>::into(#bindings)),*
+                    ])
+                },
+            };
+            (quote!(#enum_ident::#ident(#(#bindings),*)), Some(content))
+        }
+        VariantFields::Struct(fields) => {
+            let bindings = fields
+                .iter()
+                .filter(|field| !field.skip)
+                .map(|field| &field.ident);
+            let tag = match tagging {
+                Tagging::Internal(tag) => Some((tag.as_str(), name.as_str())),
+                _ => None,
+            };
+            let content = object_expr(fields, |field| quote!(#field), tag);
+            (
+                quote!(#enum_ident::#ident { #(#bindings,)* .. }),
+                Some(content),
+            )
+        }
+    };
+    let value = match (tagging, content) {
+        (Tagging::External, None) => {
+            quote!(::quick_js::JsValue::String(::std::string::String::from(#name)))
+        }
+        (Tagging::External, Some(content)) => quote!(::quick_js::derive::tagged(#name, #content)),
+        (Tagging::Internal(tag), None) => object_expr(&[], |_| quote!(), Some((tag, name))),
+        (Tagging::Untagged, None) => quote!(::quick_js::JsValue::Null),
+        (_, Some(content)) => content,
+    };
+    quote!(#pattern => #value,)
+}
+
+fn into_js_value(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let tagging = Tagging::parse(input)?;
+    let ident = &input.ident;
+    let value = local("value");
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = Field::parse_named(ident, &data.fields)?;
+            object_expr(&fields, |field| quote!(#value.#field), None)
+        }
+        Data::Enum(_) => {
+            let arms = parse_variants(input, &tagging)?
+                .iter()
+                .map(|variant| into_variant(ident, variant, &tagging))
+                .collect::<Vec<_>>();
+            quote!(match #value { #(#arms)* })
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(ident, "unions are not supported"));
+        }
+    };
+
+    let generics = add_bounds(
+        &input.generics,
+        quote!(::std::convert::Into<::quick_js::JsValue>),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::std::convert::From<#ident #ty_generics> for ::quick_js::JsValue
+            #where_clause
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn from(#value: #ident #ty_generics) -> Self {
+                #body
+            }
+        }
+    })
+}
+
+/// Construct `path` from the properties of `object`.
+fn struct_expr(path: TokenStream2, fields: &[Field], object: &Ident) -> TokenStream2 {
+    let values = fields.iter().map(|field| {
+        let Field {
+            ident, ty, name, ..
+        } = field;
+        let value = if field.skip {
+            quote!(::std::default::Default::default())
+        } else if field.default {
+            quote!(::quick_js::derive::property_or_default::<#ty>(&mut #object, #name)?)
+        } else {
+            quote!(::quick_js::derive::property::<#ty>(&mut #object, #name)?)
+        };
+        quote!(#ident: #value)
+    });
+    quote!(#path { #(#values,)* })
+}
+
+/// Construct `path` from the items of a tuple.
+fn tuple_expr(path: TokenStream2, types: &[Type], items: &Ident) -> TokenStream2 {
+    let values = types
+        .iter()
+        .map(|ty| quote!(::quick_js::derive::element::<#ty>(&mut #items)?));
+    quote!(#path(#(#values),*))
+}
+
+/// Convert `content` to a variant, for tagged enums.
+fn from_tagged_variant(variant: &Variant, content: &Ident) -> TokenStream2 {
+    let Variant { ident, fields, .. } = variant;
+    match fields {
+        VariantFields::Unit => quote!(::std::result::Result::Ok(Self::#ident)),
+        VariantFields::Tuple(types) if types.len() == 1 => {
+            quote!(::quick_js::FromJsValue::from_js_value(#content).map(Self::#ident))
+        }
+        VariantFields::Tuple(types) => {
+            let items = local("items");
+            let len = types.len();
+            let value = tuple_expr(quote!(Self::#ident), types, &items);
+            quote!({
+                let mut #items = ::quick_js::derive::tuple(#content, #len)?;
+                ::std::result::Result::Ok(#value)
+            })
+        }
+        VariantFields::Struct(fields) => {
+            let object = local("object");
+            let value = struct_expr(quote!(Self::#ident), fields, &object);
+            quote!({
+                let mut #object = ::quick_js::derive::object(#content)?;
+                ::std::result::Result::Ok(#value)
+            })
+        }
+    }
+}
+
+fn from_external(variants: &[Variant], value: &Ident) -> TokenStream2 {
+    let name = local("name");
+    let content = local("content");
+    let arms = variants.iter().map(|variant| {
+        let variant_name = &variant.name;
+        let convert = from_tagged_variant(variant, &content);
+        let pattern = match variant.fields {
+            VariantFields::Unit => quote!(::std::option::Option::None),
+            _ => quote!(::std::option::Option::Some(#content)),
+        };
+        quote! {
+            (#variant_name, #pattern) => #convert,
+            (#variant_name, _) => ::std::result::Result::Err(::quick_js::ValueError::UnexpectedType),
+        }
+    });
+    quote! {
+        let (#name, #content) = ::quick_js::derive::variant(#value)?;
+        match (#name.as_str(), #content) {
+            #(#arms)*
+            _ => ::std::result::Result::Err(::quick_js::derive::unknown_variant(&#name)),
+        }
+    }
+}
+
+fn from_internal(variants: &[Variant], tag: &str, value: &Ident) -> TokenStream2 {
+    let name = local("name");
+    let object = local("object");
+    let arms = variants.iter().map(|variant| {
+        let Variant {
+            ident,
+            name,
+            fields,
+        } = variant;
+        let value = match fields {
+            VariantFields::Struct(fields) => struct_expr(quote!(Self::#ident), fields, &object),
+            _ => quote!(Self::#ident),
+        };
+        quote!(#name => ::std::result::Result::Ok(#value),)
+    });
+    quote! {
+        let mut #object = ::quick_js::derive::object(#value)?;
+        let #name = ::quick_js::derive::tag(&mut #object, #tag)?;
+        match #name.as_str() {
+            #(#arms)*
+            _ => ::std::result::Result::Err(::quick_js::derive::unknown_variant(&#name)),
+        }
+    }
+}
+
+fn from_untagged(ident: &Ident, variants: &[Variant], value: &Ident) -> TokenStream2 {
+    let result = local("result");
+    let attempts = variants.iter().map(|variant| {
+        let variant_ident = &variant.ident;
+        let attempt = match &variant.fields {
+            VariantFields::Unit => {
+                quote!(::quick_js::derive::unit(#value.clone()).map(|()| Self::#variant_ident))
+            }
+            VariantFields::Tuple(types) if types.len() == 1 => quote! {
+                ::quick_js::FromJsValue::from_js_value(#value.clone()).map(Self::#variant_ident)
+            },
+            VariantFields::Tuple(types) => {
+                let items = local("items");
+                let len = types.len();
+                let tuple = tuple_expr(quote!(Self::#variant_ident), types, &items);
+                quote! {
+                    ::quick_js::derive::tuple(#value.clone(), #len)
+                        .and_then(|mut #items| ::std::result::Result::Ok(#tuple))
+                }
+            }
+            VariantFields::Struct(fields) => {
+                let object = local("object");
+                let value_struct = struct_expr(quote!(Self::#variant_ident), fields, &object);
+                quote! {
+                    ::quick_js::derive::object(#value.clone())
+                        .and_then(|mut #object| ::std::result::Result::Ok(#value_struct))
+                }
+            }
+        };
+        quote! {
+            if let ::std::result::Result::Ok(#result) = #attempt {
+                return ::std::result::Result::Ok(#result);
+            }
+        }
+    });
+    let name = ident.to_string();
+    quote! {
+        #(#attempts)*
+        ::std::result::Result::Err(::quick_js::derive::no_matching_variant(#name))
+    }
+}
+
+fn from_js_value(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let tagging = Tagging::parse(input)?;
+    let ident = &input.ident;
+    let value = local("value");
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = Field::parse_named(ident, &data.fields)?;
+            let object = local("object");
+            let value_struct = struct_expr(quote!(Self), &fields, &object);
+            quote! {
+                let mut #object = ::quick_js::derive::object(#value)?;
+                ::std::result::Result::Ok(#value_struct)
+            }
+        }
+        Data::Enum(_) => {
+            let variants = parse_variants(input, &tagging)?;
+            match &tagging {
+                Tagging::External => from_external(&variants, &value),
+                Tagging::Internal(tag) => from_internal(&variants, tag, &value),
+                Tagging::Untagged => from_untagged(ident, &variants, &value),
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(ident, "unions are not supported"));
+        }
+    };
+
+    let generics = add_bounds(&input.generics, quote!(::quick_js::FromJsValue));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::std::convert::TryFrom<::quick_js::JsValue> for #ident #ty_generics
+            #where_clause
+        {
+            type Error = ::quick_js::ValueError;
+
+            #[allow(unused_mut, unused_variables)]
+            fn try_from(#value: ::quick_js::JsValue) -> ::std::result::Result<Self, Self::Error> {
+                #body
+            }
+        }
+    })
+}
@@ -1,37 +1,97 @@
 use std::{
+    alloc::GlobalAlloc,
+    borrow::Cow,
+    cell::{Cell, RefCell},
     collections::HashMap,
     ffi::CString,
+    marker::PhantomData,
     os::raw::{c_char, c_int, c_void},
-    sync::Mutex,
+    panic::{AssertUnwindSafe, RefUnwindSafe},
+    rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use libquickjs_sys as q;
 
-#[cfg(feature = "bigint")]
-use crate::value::{bigint::BigIntOrI64, BigInt};
+mod allocator;
+mod class;
+mod dynamic;
+#[cfg(feature = "fetch")]
+mod fetch;
+mod finalizer;
+mod iterator;
+#[cfg(feature = "leak-tracking")]
+mod leaks;
+mod module;
+mod realm;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(feature = "atomics")]
+pub mod shared;
+mod snapshot;
+#[cfg(feature = "futures")]
+mod stream;
+mod task;
+mod time;
+mod timers;
+mod top_level_await;
+#[cfg(feature = "url")]
+mod url;
+
 use crate::{
-    callback::{Arguments, Callback},
+    callback::{Arguments, Callback, CallbackArgs, ErrorRepr, JsError},
     console::ConsoleBackend,
     droppable_value::DroppableValue,
-    ContextError, ExecutionError, JsValue, ValueError,
+    module::{BytecodeCache, Export, ModuleGraph, ModuleLoader, ModuleSource, ModuleState},
+    time::TimeSource,
+    value::bigint::BigIntOrI64,
+    AllocationStats, BigInt, ContextError, EvalOptions, ExecutionError, IntegerPolicy,
+    IntrinsicSet, JsException, JsFunction, JsPromise, JsStr, JsString, JsSymbol, JsType, JsValue,
+    MemoryUsage, NonePolicy, PromiseState, PropertyAttributes, ValueError,
 };
 
+pub use timers::TimerTime;
+
 // JS_TAG_* constants from quickjs.
 // For some reason bindgen does not pick them up.
-#[cfg(feature = "bigint")]
+#[cfg(not(feature = "ng"))]
 const TAG_BIG_INT: i64 = -10;
+#[cfg(feature = "ng")]
+const TAG_BIG_INT: i64 = -9;
+const TAG_SYMBOL: i64 = -8;
 const TAG_STRING: i64 = -7;
+const TAG_MODULE: i64 = -3;
 const TAG_OBJECT: i64 = -1;
 const TAG_INT: i64 = 0;
 const TAG_BOOL: i64 = 1;
 const TAG_NULL: i64 = 2;
 const TAG_UNDEFINED: i64 = 3;
 const TAG_EXCEPTION: i64 = 6;
+#[cfg(not(feature = "ng"))]
 const TAG_FLOAT64: i64 = 7;
+// quickjs-ng stores BigInts in the 32 bit range in the value itself.
+#[cfg(feature = "ng")]
+const TAG_SHORT_BIG_INT: i64 = 7;
+#[cfg(feature = "ng")]
+const TAG_FLOAT64: i64 = 8;
+
+/// Whether a tag is the tag of a BigInt.
+fn is_big_int_tag(tag: i64) -> bool {
+    #[cfg(feature = "ng")]
+    if tag == TAG_SHORT_BIG_INT {
+        return true;
+    }
+    tag == TAG_BIG_INT
+}
 
 /// Free a JSValue.
 /// This function is the equivalent of JS_FreeValue from quickjs, which can not
 /// be used due to being `static inline`.
+#[cfg(not(feature = "ng"))]
 unsafe fn free_value(context: *mut q::JSContext, value: q::JSValue) {
     // All tags < 0 are garbage collected and need to be freed.
     if value.tag < 0 {
@@ -46,42 +106,240 @@ unsafe fn free_value(context: *mut q::JSContext, value: q::JSValue) {
     }
 }
 
-#[cfg(feature = "chrono")]
-fn js_date_constructor(context: *mut q::JSContext) -> q::JSValue {
+/// Free a JSValue.
+/// quickjs-ng exports JS_FreeValue, but not `__JS_FreeValue`.
+#[cfg(feature = "ng")]
+unsafe fn free_value(context: *mut q::JSContext, value: q::JSValue) {
+    q::JS_FreeValue(context, value);
+}
+
+/// The header of reference counted values, which quickjs-ng does not export.
+#[cfg(feature = "ng")]
+#[repr(C)]
+struct JSRefCountHeader {
+    ref_count: c_int,
+}
+
+/// Increment the refcount of a JSValue and return it.
+/// This function is the equivalent of JS_DupValue from quickjs, which can not
+/// be used due to being `static inline`.
+unsafe fn dup_value(value: q::JSValue) -> q::JSValue {
+    #[cfg(not(feature = "ng"))]
+    use q::JSRefCountHeader;
+
+    if value.tag < 0 {
+        let ptr = value.u.ptr as *mut JSRefCountHeader;
+        (*ptr).ref_count += 1;
+    }
+    value
+}
+
+/// Get a property of the global object, usually a constructor.
+///
+/// The property is `undefined` if the constructor was removed, or is not
+/// one of the [intrinsics](crate::IntrinsicSet) of the context. `name` must
+/// be nul-terminated.
+fn js_global_property(context: *mut q::JSContext, name: &[u8]) -> q::JSValue {
     let global = unsafe { q::JS_GetGlobalObject(context) };
     assert_eq!(global.tag, TAG_OBJECT);
 
-    let date_constructor = unsafe {
+    let property = unsafe {
         q::JS_GetPropertyStr(
             context,
             global,
-            std::ffi::CStr::from_bytes_with_nul(b"Date\0")
-                .unwrap()
-                .as_ptr(),
+            std::ffi::CStr::from_bytes_with_nul(name).unwrap().as_ptr(),
         )
     };
-    assert_eq!(date_constructor.tag, TAG_OBJECT);
     unsafe { free_value(context, global) };
-    date_constructor
+    property
 }
 
-#[cfg(feature = "bigint")]
-fn js_create_bigint_function(context: *mut q::JSContext) -> q::JSValue {
-    let global = unsafe { q::JS_GetGlobalObject(context) };
-    assert_eq!(global.tag, TAG_OBJECT);
+/// Check if a value is an instance of the global constructor with the given
+/// (nul-terminated) name.
+fn js_is_instance_of(context: *mut q::JSContext, value: &q::JSValue, name: &[u8]) -> bool {
+    let constructor = js_global_property(context, name);
+    if constructor.tag != TAG_OBJECT {
+        unsafe { free_value(context, constructor) };
+        return false;
+    }
+    let is_instance = unsafe { q::JS_IsInstanceOf(context, *value, constructor) };
+    unsafe { free_value(context, constructor) };
+    // The check can throw, eg when the stack is exhausted by deeply nested
+    // values.
+    if is_instance < 0 {
+        js_clear_exception(context);
+    }
+    is_instance > 0
+}
 
-    let bigint_function = unsafe {
-        q::JS_GetPropertyStr(
-            context,
-            global,
-            std::ffi::CStr::from_bytes_with_nul(b"BigInt\0")
-                .unwrap()
-                .as_ptr(),
-        )
+/// Check if a value is a typed array, an instance of the `%TypedArray%`
+/// constructor all typed array constructors inherit from.
+fn js_is_typed_array(context: *mut q::JSContext, value: &q::JSValue) -> bool {
+    let constructor = js_global_property(context, b"Uint8Array\0");
+    let typed_array = unsafe { q::JS_GetPrototype(context, constructor) };
+    unsafe { free_value(context, constructor) };
+    let is_instance = if typed_array.tag == TAG_OBJECT {
+        unsafe { q::JS_IsInstanceOf(context, *value, typed_array) }
+    } else {
+        0
     };
-    assert_eq!(bigint_function.tag, TAG_OBJECT);
-    unsafe { free_value(context, global) };
-    bigint_function
+    unsafe { free_value(context, typed_array) };
+    if is_instance < 0 {
+        js_clear_exception(context);
+    }
+    is_instance > 0
+}
+
+/// Determine the type of a value, distinguishing objects by their built-in
+/// class.
+fn js_type_of(context: *mut q::JSContext, value: &q::JSValue) -> JsType {
+    match value.tag {
+        TAG_UNDEFINED => JsType::Undefined,
+        TAG_NULL => JsType::Null,
+        TAG_BOOL => JsType::Boolean,
+        TAG_INT | TAG_FLOAT64 => JsType::Number,
+        TAG_STRING => JsType::String,
+        TAG_SYMBOL => JsType::Symbol,
+        tag if is_big_int_tag(tag) => JsType::BigInt,
+        TAG_OBJECT => {
+            if unsafe { q::JS_IsArray(context, *value) } > 0 {
+                JsType::Array
+            } else if unsafe { q::JS_IsFunction(context, *value) } != 0 {
+                JsType::Function
+            } else if unsafe { q::JS_IsError(context, *value) } != 0 {
+                JsType::Error
+            } else if js_is_instance_of(context, value, b"Promise\0") {
+                JsType::Promise
+            } else if js_is_instance_of(context, value, b"Date\0") {
+                JsType::Date
+            } else if js_is_instance_of(context, value, b"RegExp\0") {
+                JsType::RegExp
+            } else if js_is_instance_of(context, value, b"Map\0") {
+                JsType::Map
+            } else if js_is_instance_of(context, value, b"Set\0") {
+                JsType::Set
+            } else if js_is_instance_of(context, value, b"ArrayBuffer\0") {
+                JsType::ArrayBuffer
+            } else if js_is_typed_array(context, value) {
+                JsType::TypedArray
+            } else {
+                JsType::Object
+            }
+        }
+        // Internal values, like modules, are never exposed.
+        _ => JsType::Undefined,
+    }
+}
+
+/// Call the global constructor with the given (nul-terminated) name with a
+/// single argument, which is consumed.
+fn js_construct_global(
+    context: *mut q::JSContext,
+    name: &[u8],
+    arg: q::JSValue,
+) -> Result<q::JSValue, ValueError> {
+    let constructor = js_global_property(context, name);
+    let mut args = [arg];
+    let value = unsafe { q::JS_CallConstructor(context, constructor, 1, args.as_mut_ptr()) };
+    unsafe {
+        free_value(context, constructor);
+        free_value(context, arg);
+    }
+
+    if value.tag != TAG_OBJECT {
+        js_clear_exception(context);
+        return Err(ValueError::Internal(format!(
+            "Could not construct {} object",
+            String::from_utf8_lossy(&name[..name.len() - 1])
+        )));
+    }
+    Ok(value)
+}
+
+/// Clear the pending exception of a context.
+fn js_clear_exception(context: *mut q::JSContext) {
+    unsafe {
+        let exception = q::JS_GetException(context);
+        free_value(context, exception);
+    }
+}
+
+/// Throw the error of the engine for exceeding the maximum stack size.
+///
+/// Unlike [ContextWrapper::create_error], this does not call any Javascript,
+/// so it also works when the stack is exhausted.
+fn js_throw_stack_overflow(context: *mut q::JSContext) -> q::JSValue {
+    let format = b"%s\0".as_ptr() as *const c_char;
+    unsafe {
+        if cfg!(feature = "ng") {
+            let message = b"Maximum call stack size exceeded\0".as_ptr() as *const c_char;
+            q::JS_ThrowRangeError(context, format, message)
+        } else {
+            q::JS_ThrowInternalError(
+                context,
+                format,
+                b"stack overflow\0".as_ptr() as *const c_char,
+            )
+        }
+    }
+}
+
+/// Create a JS string.
+fn js_new_string(context: *mut q::JSContext, value: &str) -> Result<q::JSValue, ValueError> {
+    js_new_string_wtf8(context, value.as_bytes())
+}
+
+/// Create a JS string from WTF-8, which may contain lone surrogates.
+fn js_new_string_wtf8(context: *mut q::JSContext, value: &[u8]) -> Result<q::JSValue, ValueError> {
+    let qval =
+        unsafe { q::JS_NewStringLen(context, value.as_ptr() as *const c_char, value.len() as _) };
+
+    if qval.tag == TAG_EXCEPTION {
+        return Err(ValueError::Internal(
+            "Could not create string in runtime".into(),
+        ));
+    }
+
+    Ok(qval)
+}
+
+/// Create a Uint8Array holding a copy of the given bytes.
+fn js_new_uint8_array(context: *mut q::JSContext, bytes: &[u8]) -> Result<q::JSValue, ValueError> {
+    let buffer = unsafe { q::JS_NewArrayBufferCopy(context, bytes.as_ptr(), bytes.len() as _) };
+    if buffer.tag == TAG_EXCEPTION {
+        return Err(ValueError::Internal(
+            "Could not create ArrayBuffer in runtime".into(),
+        ));
+    }
+    let buffer = DroppableValue::new(buffer, |&mut buffer| unsafe {
+        free_value(context, buffer);
+    });
+
+    let constructor = js_global_property(context, b"Uint8Array\0");
+    let mut args = vec![*buffer];
+    let value = unsafe {
+        q::JS_CallConstructor(context, constructor, args.len() as i32, args.as_mut_ptr())
+    };
+    unsafe {
+        free_value(context, constructor);
+    }
+
+    if value.tag != TAG_OBJECT {
+        js_clear_exception(context);
+        return Err(ValueError::Internal(
+            "Could not construct Uint8Array".into(),
+        ));
+    }
+    Ok(value)
+}
+
+/// Drops the Rust buffer backing an external ArrayBuffer.
+unsafe extern "C" fn free_array_buffer<B>(
+    _runtime: *mut q::JSRuntime,
+    opaque: *mut c_void,
+    _data: *mut c_void,
+) {
+    drop(Box::from_raw(opaque as *mut B));
 }
 
 /// Serialize a Rust value into a quickjs runtime value.
@@ -91,6 +349,10 @@ fn serialize_value(context: *mut q::JSContext, value: JsValue) -> Result<q::JSVa
             u: q::JSValueUnion { int32: 0 },
             tag: TAG_NULL,
         },
+        JsValue::Undefined => q::JSValue {
+            u: q::JSValueUnion { int32: 0 },
+            tag: TAG_UNDEFINED,
+        },
         JsValue::Bool(flag) => q::JSValue {
             u: q::JSValueUnion {
                 int32: if flag { 1 } else { 0 },
@@ -105,19 +367,8 @@ fn serialize_value(context: *mut q::JSContext, value: JsValue) -> Result<q::JSVa
             u: q::JSValueUnion { float64: val },
             tag: TAG_FLOAT64,
         },
-        JsValue::String(val) => {
-            let qval = unsafe {
-                q::JS_NewStringLen(context, val.as_ptr() as *const c_char, val.len() as _)
-            };
-
-            if qval.tag == TAG_EXCEPTION {
-                return Err(ValueError::Internal(
-                    "Could not create string in runtime".into(),
-                ));
-            }
-
-            qval
-        }
+        JsValue::String(val) => js_new_string(context, &val)?,
+        JsValue::Utf16(val) => js_new_string_wtf8(context, &val.to_wtf8())?,
         JsValue::Array(values) => {
             // Allocate a new array in the runtime.
             let arr = unsafe { q::JS_NewArray(context) };
@@ -201,11 +452,10 @@ fn serialize_value(context: *mut q::JSContext, value: JsValue) -> Result<q::JSVa
 
             obj
         }
-        #[cfg(feature = "chrono")]
-        JsValue::Date(datetime) => {
-            let date_constructor = js_date_constructor(context);
+        JsValue::Date(time) => {
+            let date_constructor = js_global_property(context, b"Date\0");
 
-            let f = datetime.timestamp_millis() as f64;
+            let f = system_time_to_millis(time);
 
             let timestamp = q::JSValue {
                 u: q::JSValueUnion { float64: f },
@@ -227,17 +477,34 @@ fn serialize_value(context: *mut q::JSContext, value: JsValue) -> Result<q::JSVa
             }
 
             if value.tag != TAG_OBJECT {
+                js_clear_exception(context);
                 return Err(ValueError::Internal(
                     "Could not construct Date object".into(),
                 ));
             }
             value
         }
-        #[cfg(feature = "bigint")]
+        // quickjs is built without BigInt support unless the `bignum`
+        // feature is enabled.
+        JsValue::BigInt(_) if !cfg!(any(feature = "bignum", feature = "ng")) => {
+            return Err(ValueError::Custom(
+                "BigInt values require the bignum feature".into(),
+            ));
+        }
         JsValue::BigInt(int) => match int.inner {
-            BigIntOrI64::Int(int) => unsafe { q::JS_NewBigInt64(context, int) },
-            BigIntOrI64::BigInt(bigint) => {
-                let bigint_string = bigint.to_str_radix(10);
+            BigIntOrI64::Int(int) => {
+                let js_bigint = unsafe { q::JS_NewBigInt64(context, int) };
+                // Fails if quickjs was built without the `bignum` feature.
+                if !is_big_int_tag(js_bigint.tag) {
+                    js_clear_exception(context);
+                    return Err(ValueError::Internal(
+                        "Could not construct BigInt object".into(),
+                    ));
+                }
+                js_bigint
+            }
+            _ => {
+                let bigint_string = int.to_string();
                 let s = unsafe {
                     q::JS_NewStringLen(
                         context,
@@ -256,7 +523,7 @@ fn serialize_value(context: *mut q::JSContext, value: JsValue) -> Result<q::JSVa
 
                 let mut args = vec![*s];
 
-                let bigint_function = js_create_bigint_function(context);
+                let bigint_function = js_global_property(context, b"BigInt\0");
                 let bigint_function =
                     DroppableValue::new(bigint_function, |&mut bigint_function| unsafe {
                         free_value(context, bigint_function);
@@ -271,7 +538,8 @@ fn serialize_value(context: *mut q::JSContext, value: JsValue) -> Result<q::JSVa
                     )
                 };
 
-                if js_bigint.tag != TAG_BIG_INT {
+                if !is_big_int_tag(js_bigint.tag) {
+                    js_clear_exception(context);
                     return Err(ValueError::Internal(
                         "Could not construct BigInt object".into(),
                     ));
@@ -280,6 +548,23 @@ fn serialize_value(context: *mut q::JSContext, value: JsValue) -> Result<q::JSVa
                 js_bigint
             }
         },
+        JsValue::Map(entries) => {
+            let entries = entries
+                .into_iter()
+                .map(|(key, value)| JsValue::Array(vec![key, value]))
+                .collect();
+            let entries = serialize_value(context, JsValue::Array(entries))?;
+            js_construct_global(context, b"Map\0", entries)?
+        }
+        JsValue::Set(values) => {
+            let values = serialize_value(context, JsValue::Array(values))?;
+            js_construct_global(context, b"Set\0", values)?
+        }
+        JsValue::Bytes(bytes) => js_new_uint8_array(context, &bytes)?,
+        JsValue::Promise(promise) => promise.persistent().dup_for(context)?,
+        JsValue::Function(function) => function.persistent().dup_for(context)?,
+        JsValue::Symbol(symbol) => symbol.persistent().dup_for(context)?,
+        JsValue::Ref(value) => value.persistent().dup_for(context)?,
         JsValue::__NonExhaustive => unreachable!(),
     };
     Ok(v)
@@ -353,7 +638,7 @@ fn deserialize_object(context: *mut q::JSContext, obj: &q::JSValue) -> Result<Js
     let mut map = HashMap::new();
     for index in 0..count {
         let prop = unsafe { (*properties).offset(index as isize) };
-        let raw_value = unsafe { q::JS_GetPropertyInternal(context, *obj, (*prop).atom, *obj, 0) };
+        let raw_value = unsafe { q::JS_GetProperty(context, *obj, (*prop).atom) };
         if raw_value.tag == TAG_EXCEPTION {
             return Err(ValueError::Internal("Could not get object property".into()));
         }
@@ -377,6 +662,7 @@ fn deserialize_object(context: *mut q::JSContext, obj: &q::JSValue) -> Result<Js
         }
         let key = match key_res? {
             JsValue::String(s) => s,
+            JsValue::Utf16(s) => return Err(s.not_unicode_error()),
             _ => {
                 return Err(ValueError::Internal("Could not get property name".into()));
             }
@@ -387,10 +673,229 @@ fn deserialize_object(context: *mut q::JSContext, obj: &q::JSValue) -> Result<Js
     Ok(JsValue::Object(map))
 }
 
-fn deserialize_value(
+/// Convert a SystemTime to a JS timestamp in milliseconds.
+fn system_time_to_millis(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as f64,
+        Err(e) => -(e.duration().as_millis() as f64),
+    }
+}
+
+/// Convert a JS timestamp in milliseconds to a SystemTime.
+///
+/// Returns `None` for invalid dates (NaN timestamp).
+fn millis_to_system_time(millis: f64) -> Option<SystemTime> {
+    if !millis.is_finite() {
+        None
+    } else if millis >= 0.0 {
+        UNIX_EPOCH.checked_add(Duration::from_millis(millis as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_millis(-millis as u64))
+    }
+}
+
+/// Convert an iterable, like a `Map` or a `Set`, with `Array.from`.
+fn deserialize_iterable(
+    context: *mut q::JSContext,
+    iterable: &q::JSValue,
+) -> Result<Vec<JsValue>, ValueError> {
+    let array_constructor = js_global_property(context, b"Array\0");
+    let from = unsafe {
+        q::JS_GetPropertyStr(
+            context,
+            array_constructor,
+            std::ffi::CStr::from_bytes_with_nul(b"from\0")
+                .unwrap()
+                .as_ptr(),
+        )
+    };
+    let mut args = [*iterable];
+    let array = unsafe { q::JS_Call(context, from, array_constructor, 1, args.as_mut_ptr()) };
+    unsafe {
+        free_value(context, from);
+        free_value(context, array_constructor);
+    }
+
+    if array.tag != TAG_OBJECT {
+        js_clear_exception(context);
+        return Err(ValueError::Internal(
+            "Could not convert iterable to array".into(),
+        ));
+    }
+    let values = deserialize_array(context, &array);
+    unsafe { free_value(context, array) };
+    match values? {
+        JsValue::Array(values) => Ok(values),
+        _ => unreachable!(),
+    }
+}
+
+fn deserialize_map(context: *mut q::JSContext, map: &q::JSValue) -> Result<JsValue, ValueError> {
+    let entries = deserialize_iterable(context, map)?
+        .into_iter()
+        .map(|entry| match entry {
+            JsValue::Array(entry) if entry.len() == 2 => {
+                let mut entry = entry.into_iter();
+                Ok((entry.next().unwrap(), entry.next().unwrap()))
+            }
+            _ => Err(ValueError::Internal("Invalid Map entry".into())),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(JsValue::Map(entries))
+}
+
+fn deserialize_date(context: *mut q::JSContext, date: &q::JSValue) -> Result<JsValue, ValueError> {
+    let getter = unsafe {
+        q::JS_GetPropertyStr(
+            context,
+            *date,
+            std::ffi::CStr::from_bytes_with_nul(b"getTime\0")
+                .unwrap()
+                .as_ptr(),
+        )
+    };
+    assert_eq!(getter.tag, TAG_OBJECT);
+
+    let timestamp_raw = unsafe { q::JS_Call(context, getter, *date, 0, std::ptr::null_mut()) };
+    unsafe {
+        free_value(context, getter);
+    };
+
+    let millis = match timestamp_raw.tag {
+        TAG_FLOAT64 => unsafe { timestamp_raw.u.float64 },
+        TAG_INT => f64::from(unsafe { timestamp_raw.u.int32 }),
+        _ => {
+            unsafe { free_value(context, timestamp_raw) };
+            return Err(ValueError::Internal(
+                "Could not convert 'Date' instance to timestamp".into(),
+            ));
+        }
+    };
+    millis_to_system_time(millis)
+        .map(JsValue::Date)
+        .ok_or_else(|| ValueError::Internal("Invalid Date".into()))
+}
+
+/// Copy `length` bytes starting at `offset` out of an ArrayBuffer.
+/// If `length` is `None`, the remainder of the buffer is copied.
+fn deserialize_array_buffer(
+    context: *mut q::JSContext,
+    buffer: &q::JSValue,
+    offset: usize,
+    length: Option<usize>,
+) -> Result<JsValue, ValueError> {
+    let mut size = 0;
+    let ptr = unsafe { q::JS_GetArrayBuffer(context, &mut size, *buffer) };
+    if ptr.is_null() {
+        js_clear_exception(context);
+        return Err(ValueError::Internal(
+            "Could not access ArrayBuffer: buffer is detached".into(),
+        ));
+    }
+    let size = size as usize;
+    let end = length.map_or(size, |length| offset + length);
+    if offset > end || end > size {
+        return Err(ValueError::Internal("Invalid ArrayBuffer range".into()));
+    }
+
+    let data = unsafe { std::slice::from_raw_parts(ptr, size) };
+    Ok(JsValue::Bytes(data[offset..end].to_vec()))
+}
+
+fn deserialize_uint8_array(
+    context: *mut q::JSContext,
+    array: &q::JSValue,
+) -> Result<JsValue, ValueError> {
+    let mut offset = 0;
+    let mut length = 0;
+    let mut bytes_per_element = 0;
+    let buffer = unsafe {
+        q::JS_GetTypedArrayBuffer(
+            context,
+            *array,
+            &mut offset,
+            &mut length,
+            &mut bytes_per_element,
+        )
+    };
+    if buffer.tag == TAG_EXCEPTION {
+        js_clear_exception(context);
+        return Err(ValueError::Internal(
+            "Could not get buffer of Uint8Array".into(),
+        ));
+    }
+    let buffer = DroppableValue::new(buffer, |&mut buffer| unsafe {
+        free_value(context, buffer);
+    });
+
+    deserialize_array_buffer(context, &buffer, offset as usize, Some(length as usize))
+}
+
+fn deserialize_promise(
+    context: *mut q::JSContext,
+    promise: &q::JSValue,
+) -> Result<JsValue, ValueError> {
+    let wrapper = unsafe { context_wrapper(context) };
+    let promise = unsafe { PersistentValue::new(wrapper.clone(), dup_value(*promise)) };
+    let tracker = wrapper.track_promise(&promise.as_value_ref())?;
+    let tracker = unsafe { PersistentValue::new(wrapper.clone(), dup_value(tracker.value.value)) };
+    Ok(JsValue::Promise(JsPromise::new(promise, tracker)))
+}
+
+fn deserialize_function(
+    context: *mut q::JSContext,
+    function: &q::JSValue,
+) -> Result<JsValue, ValueError> {
+    let wrapper = unsafe { context_wrapper(context) };
+    let function = unsafe { PersistentValue::new(wrapper, dup_value(*function)) };
+    Ok(JsValue::Function(JsFunction::new(function)))
+}
+
+fn deserialize_symbol(
+    context: *mut q::JSContext,
+    symbol: &q::JSValue,
+) -> Result<JsValue, ValueError> {
+    let wrapper = unsafe { context_wrapper(context) };
+    let symbol = unsafe { PersistentValue::new(wrapper, dup_value(*symbol)) };
+    Ok(JsValue::Symbol(JsSymbol::new(symbol)))
+}
+
+/// The maximum nesting of values converted to a [JsValue], which protects
+/// the native stack from deeply nested and cyclic values.
+const MAX_DESERIALIZE_DEPTH: usize = 512;
+
+thread_local! {
+    static DESERIALIZE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the nesting of [deserialize_value] while alive.
+struct DeserializeDepth;
+
+impl DeserializeDepth {
+    fn enter() -> Result<Self, ValueError> {
+        DESERIALIZE_DEPTH.with(|depth| {
+            if depth.get() >= MAX_DESERIALIZE_DEPTH {
+                return Err(ValueError::Internal(
+                    "Value is nested too deeply, or cyclic".into(),
+                ));
+            }
+            depth.set(depth.get() + 1);
+            Ok(DeserializeDepth)
+        })
+    }
+}
+
+impl Drop for DeserializeDepth {
+    fn drop(&mut self) {
+        DESERIALIZE_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+pub(crate) fn deserialize_value(
     context: *mut q::JSContext,
     value: &q::JSValue,
 ) -> Result<JsValue, ValueError> {
+    let _depth = DeserializeDepth::enter()?;
     let r = value;
 
     match r.tag {
@@ -408,113 +913,50 @@ fn deserialize_value(
         // Null.
         TAG_NULL => Ok(JsValue::Null),
         // Undefined.
-        TAG_UNDEFINED => Ok(JsValue::Null),
+        TAG_UNDEFINED => Ok(JsValue::Undefined),
+        // Symbol.
+        TAG_SYMBOL => deserialize_symbol(context, r),
         // Float.
         TAG_FLOAT64 => {
             let val = unsafe { r.u.float64 };
             Ok(JsValue::Float(val))
         }
         // String.
-        TAG_STRING => {
-            let ptr = unsafe { q::JS_ToCStringLen2(context, std::ptr::null_mut(), *r, 0) };
-
-            if ptr.is_null() {
-                return Err(ValueError::Internal(
-                    "Could not convert string: got a null pointer".into(),
-                ));
-            }
-
-            let cstr = unsafe { std::ffi::CStr::from_ptr(ptr) };
-
-            let s = cstr
-                .to_str()
-                .map_err(ValueError::InvalidString)?
-                .to_string();
-
-            // Free the c string.
-            unsafe { q::JS_FreeCString(context, ptr) };
-
-            Ok(JsValue::String(s))
-        }
+        TAG_STRING => deserialize_string(context, r),
         // Object.
         TAG_OBJECT => {
             let is_array = unsafe { q::JS_IsArray(context, *r) } > 0;
             if is_array {
                 deserialize_array(context, r)
             } else {
-                #[cfg(feature = "chrono")]
-                {
-                    use chrono::offset::TimeZone;
-
-                    let date_constructor = js_date_constructor(context);
-                    let is_date = unsafe { q::JS_IsInstanceOf(context, *r, date_constructor) > 0 };
-
-                    if is_date {
-                        let getter = unsafe {
-                            q::JS_GetPropertyStr(
-                                context,
-                                *r,
-                                std::ffi::CStr::from_bytes_with_nul(b"getTime\0")
-                                    .unwrap()
-                                    .as_ptr(),
-                            )
-                        };
-                        assert_eq!(getter.tag, TAG_OBJECT);
-
-                        let timestamp_raw =
-                            unsafe { q::JS_Call(context, getter, *r, 0, std::ptr::null_mut()) };
-                        unsafe {
-                            free_value(context, getter);
-                            free_value(context, date_constructor);
-                        };
-
-                        let res = if timestamp_raw.tag != TAG_FLOAT64 {
-                            Err(ValueError::Internal(
-                                "Could not convert 'Date' instance to timestamp".into(),
-                            ))
-                        } else {
-                            let f = unsafe { timestamp_raw.u.float64 } as i64;
-                            let datetime = chrono::Utc.timestamp_millis(f);
-                            Ok(JsValue::Date(datetime))
-                        };
-                        return res;
-                    } else {
-                        unsafe { free_value(context, date_constructor) };
-                    }
+                if unsafe { q::JS_IsFunction(context, *r) } != 0 {
+                    return deserialize_function(context, r);
                 }
-
-                deserialize_object(context, r)
-            }
-        }
-        // BigInt
-        #[cfg(feature = "bigint")]
-        TAG_BIG_INT => {
-            let mut int: i64 = 0;
-            let ret = unsafe { q::JS_ToBigInt64(context, &mut int, *r) };
-            if ret == 0 {
-                Ok(JsValue::BigInt(BigInt {
-                    inner: BigIntOrI64::Int(int),
-                }))
-            } else {
-                let ptr = unsafe { q::JS_ToCStringLen2(context, std::ptr::null_mut(), *r, 0) };
-
-                if ptr.is_null() {
-                    return Err(ValueError::Internal(
-                        "Could not convert BigInt to string: got a null pointer".into(),
-                    ));
+                if js_is_instance_of(context, r, b"Promise\0") {
+                    return deserialize_promise(context, r);
+                }
+                if js_is_instance_of(context, r, b"Uint8Array\0") {
+                    return deserialize_uint8_array(context, r);
+                }
+                if js_is_instance_of(context, r, b"ArrayBuffer\0") {
+                    return deserialize_array_buffer(context, r, 0, None);
                 }
 
-                let cstr = unsafe { std::ffi::CStr::from_ptr(ptr) };
-                let bigint = num_bigint::BigInt::parse_bytes(cstr.to_bytes(), 10).unwrap();
-
-                // Free the c string.
-                unsafe { q::JS_FreeCString(context, ptr) };
+                if js_is_instance_of(context, r, b"Date\0") {
+                    return deserialize_date(context, r);
+                }
+                if js_is_instance_of(context, r, b"Map\0") {
+                    return deserialize_map(context, r);
+                }
+                if js_is_instance_of(context, r, b"Set\0") {
+                    return deserialize_iterable(context, r).map(JsValue::Set);
+                }
 
-                Ok(JsValue::BigInt(BigInt {
-                    inner: BigIntOrI64::BigInt(bigint),
-                }))
+                deserialize_object(context, r)
             }
         }
+        // BigInt
+        tag if is_big_int_tag(tag) => deserialize_big_int(context, r),
         x => Err(ValueError::Internal(format!(
             "Unhandled JS_TAG value: {}",
             x
@@ -522,54 +964,201 @@ fn deserialize_value(
     }
 }
 
-/// Helper for creating CStrings.
-fn make_cstring(value: impl Into<Vec<u8>>) -> Result<CString, ValueError> {
-    CString::new(value).map_err(ValueError::StringWithZeroBytes)
-}
-
-/// Helper to construct null JsValue
-fn js_null_value() -> q::JSValue {
-    q::JSValue {
-        u: q::JSValueUnion { int32: 0 },
-        tag: TAG_NULL,
+fn deserialize_string(context: *mut q::JSContext, r: &q::JSValue) -> Result<JsValue, ValueError> {
+    let string = unsafe { CStringRef::new(context, r)? };
+    let bytes = string.as_bytes();
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Ok(JsValue::String(s.to_string())),
+        Err(e) => JsString::from_wtf8(bytes)
+            .map(JsValue::Utf16)
+            .map_err(|_| ValueError::InvalidString(e)),
     }
 }
 
-type WrappedCallback = dyn Fn(c_int, *mut q::JSValue) -> q::JSValue;
+fn deserialize_big_int(context: *mut q::JSContext, r: &q::JSValue) -> Result<JsValue, ValueError> {
+    // Converting via a string is required to detect values outside
+    // of the i64 range, since JS_ToBigInt64 silently truncates.
+    let ptr = unsafe { q::JS_ToCStringLen2(context, std::ptr::null_mut(), *r, 0) };
 
-/// Taken from: https://s3.amazonaws.com/temp.michaelfbryan.com/callbacks/index.html
-///
-/// Create a C wrapper function for a Rust closure to enable using it as a
-/// callback function in the Quickjs runtime.
-///
-/// Both the boxed closure and the boxed data are returned and must be stored
-/// by the caller to guarantee they stay alive.
-unsafe fn build_closure_trampoline<F>(
-    closure: F,
-) -> ((Box<WrappedCallback>, Box<q::JSValue>), q::JSCFunctionData)
-where
-    F: Fn(c_int, *mut q::JSValue) -> q::JSValue + 'static,
-{
-    unsafe extern "C" fn trampoline<F>(
-        _ctx: *mut q::JSContext,
-        _this: q::JSValue,
-        argc: c_int,
-        argv: *mut q::JSValue,
-        _magic: c_int,
-        data: *mut q::JSValue,
-    ) -> q::JSValue
-    where
-        F: Fn(c_int, *mut q::JSValue) -> q::JSValue,
-    {
-        let closure_ptr = (*data).u.ptr;
-        let closure: &mut F = &mut *(closure_ptr as *mut F);
-        (*closure)(argc, argv)
+    if ptr.is_null() {
+        return Err(ValueError::Internal(
+            "Could not convert BigInt to string: got a null pointer".into(),
+        ));
     }
 
-    let boxed_f = Box::new(closure);
+    let cstr = unsafe { std::ffi::CStr::from_ptr(ptr) };
+    let bigint = cstr
+        .to_str()
+        .ok()
+        .and_then(BigInt::parse_decimal)
+        .ok_or_else(|| {
+            ValueError::Internal(format!(
+                "BigInt value out of range: {}",
+                cstr.to_string_lossy()
+            ))
+        });
 
-    let data = Box::new(q::JSValue {
-        u: q::JSValueUnion {
+    // Free the c string.
+    unsafe { q::JS_FreeCString(context, ptr) };
+
+    Ok(JsValue::BigInt(bigint?))
+}
+
+/// A string value converted to WTF-8 by quickjs, which is freed on drop.
+///
+/// ASCII strings are not copied, quickjs returns their storage directly.
+pub struct CStringRef<'a> {
+    context: *mut q::JSContext,
+    ptr: *const c_char,
+    len: usize,
+    _context: PhantomData<&'a ContextWrapper>,
+}
+
+impl<'a> CStringRef<'a> {
+    /// Convert a string value.
+    ///
+    /// Unsafe because the context must outlive the returned string.
+    pub(crate) unsafe fn new(
+        context: *mut q::JSContext,
+        value: &q::JSValue,
+    ) -> Result<Self, ValueError> {
+        let mut len = 0;
+        let ptr = q::JS_ToCStringLen2(context, &mut len, *value, 0);
+        if ptr.is_null() {
+            js_clear_exception(context);
+            return Err(ValueError::Internal(
+                "Could not convert string: got a null pointer".into(),
+            ));
+        }
+        Ok(CStringRef {
+            context,
+            ptr,
+            len: len as usize,
+            _context: PhantomData,
+        })
+    }
+
+    /// The WTF-8 bytes of the string, which may contain \0 bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl<'a> Drop for CStringRef<'a> {
+    fn drop(&mut self) {
+        unsafe { q::JS_FreeCString(self.context, self.ptr) };
+    }
+}
+
+/// Whether a value is `null` or `undefined`.
+pub(crate) fn is_nullish(value: &q::JSValue) -> bool {
+    matches!(value.tag, TAG_NULL | TAG_UNDEFINED)
+}
+
+/// Convert a string value to a [JsStr], which keeps its context alive.
+///
+/// Unsafe because the value must belong to the given context.
+pub(crate) unsafe fn borrow_string(
+    context: *mut q::JSContext,
+    value: &q::JSValue,
+) -> Result<JsStr<'static>, ValueError> {
+    if value.tag != TAG_STRING {
+        return Err(ValueError::UnexpectedType);
+    }
+    let string = CStringRef::new(context, value)?;
+    JsStr::with_context(string, context_wrapper(context))
+}
+
+/// Check if an exception is the error thrown by the engine when the maximum
+/// stack size is exceeded.
+fn is_stack_overflow(exception: &JsException) -> bool {
+    if cfg!(feature = "ng") {
+        exception.name == "RangeError" && exception.message == "Maximum call stack size exceeded"
+    } else {
+        // The parser throws a SyntaxError instead.
+        matches!(exception.name.as_str(), "InternalError" | "SyntaxError")
+            && exception.message == "stack overflow"
+    }
+}
+
+/// The error thrown by the engine when the maximum stack size is exceeded,
+/// see [is_stack_overflow].
+pub(crate) fn stack_overflow_error() -> JsError {
+    if cfg!(feature = "ng") {
+        JsError::range_error("Maximum call stack size exceeded")
+    } else {
+        JsError::new("InternalError", "stack overflow")
+    }
+}
+
+/// Helper for creating CStrings.
+fn make_cstring(value: impl Into<Vec<u8>>) -> Result<CString, ValueError> {
+    CString::new(value).map_err(ValueError::StringWithZeroBytes)
+}
+
+/// Helper to construct null JsValue
+fn js_null_value() -> q::JSValue {
+    q::JSValue {
+        u: q::JSValueUnion { int32: 0 },
+        tag: TAG_NULL,
+    }
+}
+
+fn js_undefined_value() -> q::JSValue {
+    q::JSValue {
+        u: q::JSValueUnion { int32: 0 },
+        tag: TAG_UNDEFINED,
+    }
+}
+
+type WrappedCallback = dyn Fn(q::JSValue, c_int, *mut q::JSValue) -> q::JSValue;
+
+/// Taken from: https://s3.amazonaws.com/temp.michaelfbryan.com/callbacks/index.html
+///
+/// Create a C wrapper function for a Rust closure to enable using it as a
+/// callback function in the Quickjs runtime.
+///
+/// Both the boxed closure and the boxed data are returned and must be stored
+/// by the caller to guarantee they stay alive.
+unsafe fn build_closure_trampoline<F>(
+    closure: F,
+) -> ((Box<WrappedCallback>, Box<q::JSValue>), q::JSCFunctionData)
+where
+    F: Fn(q::JSValue, c_int, *mut q::JSValue) -> q::JSValue + 'static,
+{
+    unsafe extern "C" fn trampoline<F>(
+        ctx: *mut q::JSContext,
+        this: q::JSValue,
+        argc: c_int,
+        argv: *mut q::JSValue,
+        _magic: c_int,
+        data: *mut q::JSValue,
+    ) -> q::JSValue
+    where
+        F: Fn(q::JSValue, c_int, *mut q::JSValue) -> q::JSValue,
+    {
+        let closure_ptr = (*data).u.ptr;
+        let closure: &mut F = &mut *(closure_ptr as *mut F);
+        // Callbacks catch panics themselves, this only covers the conversion
+        // of their results.
+        match std::panic::catch_unwind(AssertUnwindSafe(|| (*closure)(this, argc, argv))) {
+            Ok(value) => value,
+            Err(_) => {
+                context_wrapper(ctx).caught_panic("Callback panicked!");
+                let format = b"%s\0".as_ptr() as *const c_char;
+                q::JS_ThrowInternalError(
+                    ctx,
+                    format,
+                    b"Callback panicked!\0".as_ptr() as *const c_char,
+                )
+            }
+        }
+    }
+
+    let boxed_f = Box::new(closure);
+
+    let data = Box::new(q::JSValue {
+        u: q::JSValueUnion {
             ptr: (&*boxed_f) as *const F as *mut c_void,
         },
         tag: TAG_NULL,
@@ -617,21 +1206,18 @@ impl<'a> OwnedValueRef<'a> {
     /// Get the inner JSValue without freeing in drop.
     ///
     /// Unsafe because the caller is responsible for freeing the value.
-    //unsafe fn into_inner(mut self) -> q::JSValue {
-    //let v = self.value;
-    //self.value = q::JSValue {
-    //u: q::JSValueUnion { int32: 0 },
-    //tag: TAG_NULL,
-    //};
-    //v
-    //}
+    unsafe fn into_inner(self) -> q::JSValue {
+        let v = self.value;
+        std::mem::forget(self);
+        v
+    }
 
     pub fn is_null(&self) -> bool {
         self.value.tag == TAG_NULL
     }
 
-    pub fn is_bool(&self) -> bool {
-        self.value.tag == TAG_BOOL
+    pub fn is_undefined(&self) -> bool {
+        self.value.tag == TAG_UNDEFINED
     }
 
     pub fn is_exception(&self) -> bool {
@@ -646,6 +1232,10 @@ impl<'a> OwnedValueRef<'a> {
         self.value.tag == TAG_STRING
     }
 
+    pub fn type_of(&self) -> JsType {
+        js_type_of(self.context.context, &self.value)
+    }
+
     pub fn to_string(&self) -> Result<String, ExecutionError> {
         let value = if self.is_string() {
             self.to_value()?
@@ -667,13 +1257,6 @@ impl<'a> OwnedValueRef<'a> {
     pub fn to_value(&self) -> Result<JsValue, ValueError> {
         self.context.to_value(&self.value)
     }
-
-    pub fn to_bool(&self) -> Result<bool, ValueError> {
-        match self.to_value()? {
-            JsValue::Bool(b) => Ok(b),
-            _ => Err(ValueError::UnexpectedType),
-        }
-    }
 }
 
 /// Wraps an object from the quickjs runtime.
@@ -691,7 +1274,7 @@ impl<'a> OwnedObjectRef<'a> {
         }
     }
 
-    fn into_value(self) -> OwnedValueRef<'a> {
+    pub fn into_value(self) -> OwnedValueRef<'a> {
         self.value
     }
 
@@ -739,6 +1322,23 @@ impl<'a> OwnedObjectRef<'a> {
         }
     }
 
+    /// Get a property, treating `undefined` as a regular value.
+    fn property_or_undefined(&self, name: &str) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        let cname = make_cstring(name)?;
+        let raw = unsafe {
+            q::JS_GetPropertyStr(self.value.context.context, self.value.value, cname.as_ptr())
+        };
+
+        if raw.tag == TAG_EXCEPTION {
+            Err(ExecutionError::Internal(format!(
+                "Exception while getting property '{}'",
+                name
+            )))
+        } else {
+            Ok(OwnedValueRef::new(self.value.context, raw))
+        }
+    }
+
     unsafe fn set_property_raw(&self, name: &str, value: q::JSValue) -> Result<(), ExecutionError> {
         let cname = make_cstring(name)?;
         let ret = q::JS_SetPropertyStr(
@@ -754,165 +1354,1657 @@ impl<'a> OwnedObjectRef<'a> {
         }
     }
 
-    // pub fn set_property(&self, name: &str, value: JsValue) -> Result<(), ExecutionError> {
-    //     let qval = self.value.context.serialize_value(value)?;
-    //     unsafe { self.set_property_raw(name, qval.value) }
-    // }
-}
-
-/*
-type ModuleInit = dyn Fn(*mut q::JSContext, *mut q::JSModuleDef);
+    /// Define a writable, configurable and non-enumerable property, like the
+    /// methods of built-in classes.
+    ///
+    /// Unsafe because the value is consumed.
+    unsafe fn define_property_raw(
+        &self,
+        name: &str,
+        value: q::JSValue,
+    ) -> Result<(), ExecutionError> {
+        let cname = match make_cstring(name) {
+            Ok(cname) => cname,
+            Err(e) => {
+                free_value(self.value.context.context, value);
+                return Err(e.into());
+            }
+        };
+        let ret = q::JS_DefinePropertyValueStr(
+            self.value.context.context,
+            self.value.value,
+            cname.as_ptr(),
+            value,
+            (q::JS_PROP_CONFIGURABLE | q::JS_PROP_WRITABLE) as i32,
+        );
+        if ret < 0 {
+            Err(ExecutionError::Exception(
+                "Could not define property".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
 
-thread_local! {
-    static NATIVE_MODULE_INIT: RefCell<Option<Box<ModuleInit>>> = RefCell::new(None);
-}
+    /// Define a configurable and non-enumerable accessor property.
+    ///
+    /// Unsafe because the getter and setter are consumed.
+    unsafe fn define_accessor_raw(
+        &self,
+        name: &str,
+        getter: q::JSValue,
+        setter: q::JSValue,
+    ) -> Result<(), ExecutionError> {
+        let context = self.value.context.context;
+        let cname = match make_cstring(name) {
+            Ok(cname) => cname,
+            Err(e) => {
+                free_value(context, getter);
+                free_value(context, setter);
+                return Err(e.into());
+            }
+        };
+        let atom = q::JS_NewAtom(context, cname.as_ptr());
+        let ret = q::JS_DefinePropertyGetSet(
+            context,
+            self.value.value,
+            atom,
+            getter,
+            setter,
+            q::JS_PROP_CONFIGURABLE as i32,
+        );
+        q::JS_FreeAtom(context, atom);
+        if ret < 0 {
+            Err(ExecutionError::Exception(
+                "Could not define property".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
 
-unsafe extern "C" fn native_module_init(
-    ctx: *mut q::JSContext,
-    m: *mut q::JSModuleDef,
-) -> ::std::os::raw::c_int {
-    NATIVE_MODULE_INIT.with(|init| {
-        let init = init.replace(None).unwrap();
-        init(ctx, m);
-    });
-    0
-}
-*/
+    /// Take the pending exception of the context.
+    fn exception(&self) -> ExecutionError {
+        self.value
+            .context
+            .get_exception()
+            .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()))
+    }
 
-/// Wraps a quickjs context.
-///
-/// Cleanup of the context happens in drop.
-pub struct ContextWrapper {
-    runtime: *mut q::JSRuntime,
-    context: *mut q::JSContext,
-    /// Stores callback closures and quickjs data pointers.
-    /// This array is write-only and only exists to ensure the lifetime of
-    /// the closure.
-    // A Mutex is used over a RefCell because it needs to be unwind-safe.
-    callbacks: Mutex<Vec<(Box<WrappedCallback>, Box<q::JSValue>)>>,
-}
+    /// Call `f` with an atom for the given property name.
+    fn with_atom<R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(q::JSAtom) -> R,
+    ) -> Result<R, ExecutionError> {
+        let context = self.value.context.context;
+        let cname = make_cstring(name)?;
+        let atom = unsafe { q::JS_NewAtom(context, cname.as_ptr()) };
+        let result = f(atom);
+        unsafe { q::JS_FreeAtom(context, atom) };
+        Ok(result)
+    }
 
-impl Drop for ContextWrapper {
-    fn drop(&mut self) {
-        unsafe {
-            q::JS_FreeContext(self.context);
-            q::JS_FreeRuntime(self.runtime);
+    /// Get the value of a property, including inherited ones.
+    ///
+    /// Missing properties are returned as `JsValue::Undefined`.
+    pub fn get_property(&self, name: &str) -> Result<JsValue, ExecutionError> {
+        let cname = make_cstring(name)?;
+        let raw = unsafe {
+            q::JS_GetPropertyStr(self.value.context.context, self.value.value, cname.as_ptr())
+        };
+        let value = OwnedValueRef::new(self.value.context, raw);
+        if value.is_exception() {
+            return Err(self.exception());
         }
+        Ok(value.to_value()?)
     }
-}
 
-impl ContextWrapper {
-    /// Initialize a wrapper by creating a JSRuntime and JSContext.
-    pub fn new(memory_limit: Option<usize>) -> Result<Self, ContextError> {
-        let runtime = unsafe { q::JS_NewRuntime() };
-        if runtime.is_null() {
-            return Err(ContextError::RuntimeCreationFailed);
+    pub fn set_property(&self, name: &str, value: JsValue) -> Result<(), ExecutionError> {
+        let cname = make_cstring(name)?;
+        let value = self.value.context.serialize_value(value)?;
+        let ret = unsafe {
+            q::JS_SetPropertyStr(
+                self.value.context.context,
+                self.value.value,
+                cname.as_ptr(),
+                value.into_inner(),
+            )
+        };
+        if ret < 0 {
+            Err(self.exception())
+        } else {
+            Ok(())
         }
+    }
 
-        // Configure memory limit if specified.
-        if let Some(limit) = memory_limit {
-            unsafe {
-                q::JS_SetMemoryLimit(runtime, limit as _);
-            }
+    /// Call `f` with an atom for the given symbol.
+    fn with_symbol_atom<R>(
+        &self,
+        symbol: &OwnedValueRef,
+        f: impl FnOnce(q::JSAtom) -> R,
+    ) -> Result<R, ExecutionError> {
+        let context = self.value.context.context;
+        let atom = unsafe { q::JS_ValueToAtom(context, symbol.value) };
+        if atom == 0 {
+            return Err(self.exception());
         }
+        let result = f(atom);
+        unsafe { q::JS_FreeAtom(context, atom) };
+        Ok(result)
+    }
 
-        let context = unsafe { q::JS_NewContext(runtime) };
-        if context.is_null() {
-            unsafe {
-                q::JS_FreeRuntime(runtime);
-            }
-            return Err(ContextError::ContextCreationFailed);
+    /// Get the value of a symbol keyed property, including inherited ones.
+    pub fn get_symbol_property(&self, symbol: &OwnedValueRef) -> Result<JsValue, ExecutionError> {
+        let raw = self.with_symbol_atom(symbol, |atom| unsafe {
+            q::JS_GetProperty(self.value.context.context, self.value.value, atom)
+        })?;
+        let value = OwnedValueRef::new(self.value.context, raw);
+        if value.is_exception() {
+            return Err(self.exception());
         }
-
-        // Initialize the promise resolver helper code.
-        // This code is needed by Self::resolve_value
-        let wrapper = Self {
-            runtime,
-            context,
-            callbacks: Mutex::new(Vec::new()),
-        };
-
-        Ok(wrapper)
+        Ok(value.to_value()?)
     }
 
-    // See console standard: https://console.spec.whatwg.org
-    pub fn set_console(&self, backend: Box<dyn ConsoleBackend>) -> Result<(), ExecutionError> {
-        use crate::console::Level;
-
-        self.add_callback("__console_write", move |args: Arguments| {
-            let mut args = args.into_vec();
-
-            if args.len() > 1 {
-                let level_raw = args.remove(0);
+    pub fn set_symbol_property(
+        &self,
+        symbol: &OwnedValueRef,
+        value: JsValue,
+    ) -> Result<(), ExecutionError> {
+        let value = self.value.context.serialize_value(value)?;
+        let ret = self.with_symbol_atom(symbol, |atom| unsafe {
+            q::JS_SetProperty(
+                self.value.context.context,
+                self.value.value,
+                atom,
+                value.into_inner(),
+            )
+        })?;
+        if ret < 0 {
+            Err(self.exception())
+        } else {
+            Ok(())
+        }
+    }
 
-                let level_opt = level_raw.as_str().and_then(|v| match v {
-                    "trace" => Some(Level::Trace),
-                    "debug" => Some(Level::Debug),
-                    "log" => Some(Level::Log),
-                    "info" => Some(Level::Info),
-                    "warn" => Some(Level::Warn),
-                    "error" => Some(Level::Error),
-                    _ => None,
-                });
+    /// Check if the object has a property, including inherited ones.
+    pub fn has_property(&self, name: &str) -> Result<bool, ExecutionError> {
+        let ret = self.with_atom(name, |atom| unsafe {
+            q::JS_HasProperty(self.value.context.context, self.value.value, atom)
+        })?;
+        if ret < 0 {
+            Err(self.exception())
+        } else {
+            Ok(ret > 0)
+        }
+    }
 
-                if let Some(level) = level_opt {
-                    backend.log(level, args);
-                }
-            }
+    /// Delete an own property.
+    ///
+    /// Returns `false` if the property can not be deleted.
+    pub fn delete_property(&self, name: &str) -> Result<bool, ExecutionError> {
+        let ret = self.with_atom(name, |atom| unsafe {
+            q::JS_DeleteProperty(self.value.context.context, self.value.value, atom, 0)
         })?;
+        if ret < 0 {
+            Err(self.exception())
+        } else {
+            Ok(ret > 0)
+        }
+    }
 
-        self.eval(
-            r#"
-            globalThis.console = {
-                trace: (...args) => {
-                    globalThis.__console_write("trace", ...args);
-                },
-                debug: (...args) => {
-                    globalThis.__console_write("debug", ...args);
-                },
-                log: (...args) => {
-                    globalThis.__console_write("log", ...args);
-                },
-                info: (...args) => {
-                    globalThis.__console_write("info", ...args);
-                },
-                warn: (...args) => {
-                    globalThis.__console_write("warn", ...args);
-                },
-                error: (...args) => {
-                    globalThis.__console_write("error", ...args);
-                },
-            };
-        "#,
-        )?;
+    pub fn is_array(&self) -> bool {
+        unsafe { q::JS_IsArray(self.value.context.context, self.value.value) > 0 }
+    }
 
-        Ok(())
+    /// Get the `length` of an array.
+    pub fn array_length(&self) -> Result<u32, ExecutionError> {
+        match self.get_property("length")? {
+            JsValue::Int(len) if len >= 0 => Ok(len as u32),
+            JsValue::Float(len) if len >= 0.0 && len <= u32::MAX as f64 => Ok(len as u32),
+            _ => Err(ExecutionError::Internal(
+                "Could not determine array length".into(),
+            )),
+        }
     }
 
-    /// Reset the wrapper by creating a new context.
-    pub fn reset(self) -> Result<Self, ContextError> {
-        unsafe {
-            q::JS_FreeContext(self.context);
+    /// Get the value of an indexed property.
+    pub fn get_index(&self, index: u32) -> Result<JsValue, ExecutionError> {
+        let raw =
+            unsafe { q::JS_GetPropertyUint32(self.value.context.context, self.value.value, index) };
+        let value = OwnedValueRef::new(self.value.context, raw);
+        if value.is_exception() {
+            return Err(self.exception());
+        }
+        Ok(value.to_value()?)
+    }
+
+    /// Set the value of an indexed property.
+    pub fn set_index(&self, index: u32, value: JsValue) -> Result<(), ExecutionError> {
+        let value = self.value.context.serialize_value(value)?;
+        let ret = unsafe {
+            q::JS_SetPropertyUint32(
+                self.value.context.context,
+                self.value.value,
+                index,
+                value.into_inner(),
+            )
         };
-        self.callbacks.lock().unwrap().clear();
-        let context = unsafe { q::JS_NewContext(self.runtime) };
-        if context.is_null() {
-            return Err(ContextError::ContextCreationFailed);
+        if ret < 0 {
+            Err(self.exception())
+        } else {
+            Ok(())
         }
+    }
 
-        let mut s = self;
-        s.context = context;
-        Ok(s)
+    /// Get the prototype of the object, like `Object.getPrototypeOf`.
+    pub fn prototype(&self) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        let raw = unsafe { q::JS_GetPrototype(self.value.context.context, self.value.value) };
+        let prototype = OwnedValueRef::new(self.value.context, raw);
+        if prototype.is_exception() {
+            Err(self.exception())
+        } else {
+            Ok(prototype)
+        }
     }
 
-    pub fn serialize_value(&self, value: JsValue) -> Result<OwnedValueRef<'_>, ExecutionError> {
-        let serialized = serialize_value(self.context, value)?;
-        Ok(OwnedValueRef::new(self, serialized))
+    /// Check if the object is an instance of the given constructor, like the
+    /// `instanceof` operator.
+    pub fn is_instance_of(&self, constructor: &OwnedValueRef) -> Result<bool, ExecutionError> {
+        let ret = unsafe {
+            q::JS_IsInstanceOf(
+                self.value.context.context,
+                self.value.value,
+                constructor.value,
+            )
+        };
+        if ret < 0 {
+            Err(self.exception())
+        } else {
+            Ok(ret > 0)
+        }
     }
 
-    // Deserialize a quickjs runtime value into a Rust value.
-    fn to_value(&self, value: &q::JSValue) -> Result<JsValue, ValueError> {
-        deserialize_value(self.context, value)
+    /// Convert the own enumerable properties into a map.
+    pub fn to_map(&self) -> Result<HashMap<String, JsValue>, ExecutionError> {
+        match deserialize_object(self.value.context.context, &self.value.value)? {
+            JsValue::Object(map) => Ok(map),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Get the names of the own string keyed properties.
+    pub fn property_names(&self, enumerable_only: bool) -> Result<Vec<String>, ExecutionError> {
+        let mut flags = q::JS_GPN_STRING_MASK as i32;
+        if enumerable_only {
+            flags |= q::JS_GPN_ENUM_ONLY as i32;
+        }
+        let context = self.value.context.context;
+        self.with_own_properties(flags, |properties| {
+            properties
+                .iter()
+                .map(|prop| {
+                    let name = unsafe { q::JS_AtomToString(context, prop.atom) };
+                    OwnedValueRef::new(self.value.context, name).to_string()
+                })
+                .collect()
+        })?
+    }
+
+    /// Call `f` with the own properties selected by the `JS_GPN_*` flags.
+    fn with_own_properties<R>(
+        &self,
+        flags: i32,
+        f: impl FnOnce(&[q::JSPropertyEnum]) -> R,
+    ) -> Result<R, ExecutionError> {
+        let context = self.value.context.context;
+        let mut properties: *mut q::JSPropertyEnum = std::ptr::null_mut();
+        let mut count: u32 = 0;
+
+        let ret = unsafe {
+            q::JS_GetOwnPropertyNames(
+                context,
+                &mut properties,
+                &mut count,
+                self.value.value,
+                flags,
+            )
+        };
+        if ret != 0 {
+            return Err(self.exception());
+        }
+
+        let properties = DroppableValue::new(properties, |&mut properties| {
+            for index in 0..count {
+                let prop = unsafe { properties.offset(index as isize) };
+                unsafe {
+                    q::JS_FreeAtom(context, (*prop).atom);
+                }
+            }
+            unsafe {
+                q::js_free(context, properties as *mut std::ffi::c_void);
+            }
+        });
+
+        let properties = if count == 0 {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(*properties, count as usize) }
+        };
+        Ok(f(properties))
+    }
+
+    /// Define a data property with the given attributes.
+    ///
+    /// Fails if the property exists and is not configurable.
+    pub fn define_property(
+        &self,
+        name: &str,
+        value: JsValue,
+        attributes: PropertyAttributes,
+    ) -> Result<(), ExecutionError> {
+        let mut flags = q::JS_PROP_THROW as i32;
+        if attributes.configurable {
+            flags |= q::JS_PROP_CONFIGURABLE as i32;
+        }
+        if attributes.writable {
+            flags |= q::JS_PROP_WRITABLE as i32;
+        }
+        if attributes.enumerable {
+            flags |= q::JS_PROP_ENUMERABLE as i32;
+        }
+        let value = self.value.context.serialize_value(value)?;
+        let ret = self.with_atom(name, |atom| unsafe {
+            q::JS_DefinePropertyValue(
+                self.value.context.context,
+                self.value.value,
+                atom,
+                value.into_inner(),
+                flags,
+            )
+        })?;
+        if ret < 0 {
+            Err(self.exception())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the flags of an own property, or `None` if it is missing.
+    fn own_property_flags(&self, atom: q::JSAtom) -> Result<Option<i32>, ExecutionError> {
+        let context = self.value.context.context;
+        let mut desc = std::mem::MaybeUninit::<q::JSPropertyDescriptor>::uninit();
+        let ret =
+            unsafe { q::JS_GetOwnProperty(context, desc.as_mut_ptr(), self.value.value, atom) };
+        if ret < 0 {
+            return Err(self.exception());
+        }
+        if ret == 0 {
+            return Ok(None);
+        }
+        let desc = unsafe { desc.assume_init() };
+        unsafe {
+            free_value(context, desc.value);
+            free_value(context, desc.getter);
+            free_value(context, desc.setter);
+        }
+        Ok(Some(desc.flags))
+    }
+
+    /// Prevent adding properties, and make all own properties
+    /// non-configurable, like `Object.seal`. If `frozen`, data properties
+    /// are made non-writable as well, like `Object.freeze`.
+    pub fn set_integrity_level(&self, frozen: bool) -> Result<(), ExecutionError> {
+        let context = self.value.context.context;
+        if unsafe { q::JS_PreventExtensions(context, self.value.value) } < 0 {
+            return Err(self.exception());
+        }
+        let flags = (q::JS_GPN_STRING_MASK | q::JS_GPN_SYMBOL_MASK) as i32;
+        self.with_own_properties(flags, |properties| {
+            for prop in properties {
+                let is_accessor = match self.own_property_flags(prop.atom)? {
+                    Some(flags) => flags & q::JS_PROP_GETSET as i32 != 0,
+                    None => continue,
+                };
+
+                // Accessors stay accessors, they have no writable attribute.
+                let mut flags = (q::JS_PROP_HAS_CONFIGURABLE | q::JS_PROP_THROW) as i32;
+                if frozen && !is_accessor {
+                    flags |= q::JS_PROP_HAS_WRITABLE as i32;
+                }
+                let ret = unsafe {
+                    q::JS_DefineProperty(
+                        context,
+                        self.value.value,
+                        prop.atom,
+                        js_undefined_value(),
+                        js_undefined_value(),
+                        js_undefined_value(),
+                        flags,
+                    )
+                };
+                if ret < 0 {
+                    return Err(self.exception());
+                }
+            }
+            Ok(())
+        })?
+    }
+
+    /// Check if no properties can be added and all own properties are
+    /// non-configurable, like `Object.isSealed`. If `frozen`, data
+    /// properties must be non-writable as well, like `Object.isFrozen`.
+    pub fn test_integrity_level(&self, frozen: bool) -> Result<bool, ExecutionError> {
+        let ret = unsafe { q::JS_IsExtensible(self.value.context.context, self.value.value) };
+        if ret < 0 {
+            return Err(self.exception());
+        }
+        if ret > 0 {
+            return Ok(false);
+        }
+        let flags = (q::JS_GPN_STRING_MASK | q::JS_GPN_SYMBOL_MASK) as i32;
+        self.with_own_properties(flags, |properties| {
+            for prop in properties {
+                let flags = match self.own_property_flags(prop.atom)? {
+                    Some(flags) => flags,
+                    None => continue,
+                };
+                let is_writable = flags & q::JS_PROP_GETSET as i32 == 0
+                    && flags & q::JS_PROP_WRITABLE as i32 != 0;
+                if flags & q::JS_PROP_CONFIGURABLE as i32 != 0 || (frozen && is_writable) {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        })?
+    }
+}
+
+/// PersistentValue wraps a Javascript value and keeps its context alive.
+///
+/// Unlike [OwnedValueRef], it is not bound to a borrow of the context, so it
+/// can be stored in a [JsValue] and outlive the call that produced it.
+pub struct PersistentValue {
+    context: Rc<ContextWrapper>,
+    value: q::JSValue,
+    /// The id of the handle in the tracker of the context.
+    #[cfg(feature = "leak-tracking")]
+    handle: u64,
+}
+
+impl Drop for PersistentValue {
+    fn drop(&mut self) {
+        #[cfg(feature = "leak-tracking")]
+        self.context.handles.untrack(self.handle);
+        unsafe {
+            free_value(self.context.context, self.value);
+        }
+    }
+}
+
+impl Clone for PersistentValue {
+    fn clone(&self) -> Self {
+        unsafe { Self::new(self.context.clone(), dup_value(self.value)) }
+    }
+}
+
+impl PartialEq for PersistentValue {
+    fn eq(&self, other: &Self) -> bool {
+        if self.value.tag != other.value.tag {
+            return false;
+        }
+        // Reference types are compared by identity.
+        match self.value.tag {
+            tag if tag < 0 => unsafe { self.value.u.ptr == other.value.u.ptr },
+            TAG_INT | TAG_BOOL => unsafe { self.value.u.int32 == other.value.u.int32 },
+            TAG_FLOAT64 => unsafe { self.value.u.float64 == other.value.u.float64 },
+            #[cfg(feature = "ng")]
+            TAG_SHORT_BIG_INT => unsafe {
+                self.value.u.short_big_int == other.value.u.short_big_int
+            },
+            _ => true,
+        }
+    }
+}
+
+impl PersistentValue {
+    /// Takes ownership of the given value.
+    ///
+    /// Unsafe because the value must belong to the given context.
+    unsafe fn new(context: Rc<ContextWrapper>, value: q::JSValue) -> Self {
+        Self {
+            #[cfg(feature = "leak-tracking")]
+            handle: context.handles.track(),
+            context,
+            value,
+        }
+    }
+
+    /// Keep a value alive beyond the borrow of its context.
+    pub fn from_ref(value: OwnedValueRef<'_>) -> Self {
+        unsafe {
+            let context = context_wrapper(value.context.context);
+            Self::new(context, value.into_inner())
+        }
+    }
+
+    /// The context this value belongs to.
+    pub fn context(&self) -> &Rc<ContextWrapper> {
+        &self.context
+    }
+
+    /// Get a new reference to the value.
+    pub fn as_value_ref(&self) -> OwnedValueRef<'_> {
+        OwnedValueRef::new(&self.context, unsafe { dup_value(self.value) })
+    }
+
+    /// Convert a string value to WTF-8, without copying ASCII strings.
+    pub fn to_cstring(&self) -> Result<CStringRef<'_>, ValueError> {
+        if self.value.tag != TAG_STRING {
+            return Err(ValueError::UnexpectedType);
+        }
+        unsafe { CStringRef::new(self.context.context, &self.value) }
+    }
+
+    /// Get a new reference to the raw value, for use in the given context.
+    ///
+    /// Values can only be shared between contexts of the same runtime, and
+    /// only with their own context if the runtime isolates its contexts.
+    /// The caller is responsible for freeing the value.
+    fn dup_for(&self, context: *mut q::JSContext) -> Result<q::JSValue, ValueError> {
+        let runtime = unsafe { q::JS_GetRuntime(context) };
+        if self.context.runtime.runtime != runtime {
+            return Err(ValueError::Internal(
+                "Can not move a value to a different runtime".into(),
+            ));
+        }
+        if self.context.context != context && self.context.runtime.isolated.get() {
+            return Err(ValueError::Internal(
+                "Can not move a value to a different context of an isolated runtime".into(),
+            ));
+        }
+        Ok(unsafe { dup_value(self.value) })
+    }
+
+    /// Get a new reference to the value, for use in the given context.
+    ///
+    /// See [dup_for](PersistentValue::dup_for).
+    pub fn value_ref_for<'a>(
+        &self,
+        context: &'a ContextWrapper,
+    ) -> Result<OwnedValueRef<'a>, ValueError> {
+        let value = self.dup_for(context.context)?;
+        Ok(OwnedValueRef::new(context, value))
+    }
+}
+
+/// Module loader callback for quickjs.
+///
+/// Native modules registered in the context are loaded first. Otherwise the
+/// opaque pointer points to the `Box<dyn ModuleLoader>` stored in the
+/// RuntimeWrapper, or is null if no loader is set.
+unsafe extern "C" fn module_loader_trampoline(
+    ctx: *mut q::JSContext,
+    module_name: *const c_char,
+    opaque: *mut c_void,
+) -> *mut q::JSModuleDef {
+    let wrapper = context_wrapper(ctx);
+    let name = std::ffi::CStr::from_ptr(module_name).to_string_lossy();
+    let name = wrapper.unversioned_module_name(&name).to_string();
+    let (module, state) = match module::load_native_module(ctx, module_name, &name) {
+        Some(module) => (module, ModuleState::Native),
+        None => (
+            load_module(ctx, module_name, &name, opaque),
+            ModuleState::Loaded,
+        ),
+    };
+    let state = if module.is_null() {
+        ModuleState::Failed
+    } else {
+        state
+    };
+    wrapper.module_graph.lock().unwrap().set_state(&name, state);
+    module
+}
+
+/// Load a module with the module loader the opaque pointer points to.
+unsafe fn load_module(
+    ctx: *mut q::JSContext,
+    module_name: *const c_char,
+    name: &str,
+    opaque: *mut c_void,
+) -> *mut q::JSModuleDef {
+    if opaque.is_null() {
+        throw_reference_error(ctx, &format!("could not load module '{}'", name));
+        return std::ptr::null_mut();
+    }
+    let loader = &*(opaque as *const Box<dyn ModuleLoader>);
+
+    let result = std::panic::catch_unwind(|| loader.load(name)).unwrap_or_else(|_| {
+        context_wrapper(ctx).caught_panic("Module loader panicked!");
+        Err("Module loader panicked!".to_string())
+    });
+    let code = match result {
+        Ok(ModuleSource::Code(code)) => code,
+        Ok(ModuleSource::Json(json)) => {
+            return module::load_data_module(ctx, module_name, name, Export::Json(json));
+        }
+        Ok(ModuleSource::Bytes(bytes)) => {
+            let bytes = Export::Value(JsValue::Bytes(bytes));
+            return module::load_data_module(ctx, module_name, name, bytes);
+        }
+        Ok(ModuleSource::__NonExhaustive) => unreachable!(),
+        Err(e) => {
+            throw_reference_error(ctx, &format!("could not load module '{}': {}", name, e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let module = match module::compile_module(ctx, module_name, name, &code) {
+        Some(module) => module,
+        None => return std::ptr::null_mut(),
+    };
+    if let Err(e) = module::set_import_meta(ctx, module, Some(&**loader)) {
+        throw_reference_error(ctx, &format!("could not load module '{}': {}", name, e));
+        return std::ptr::null_mut();
+    }
+    module
+}
+
+/// Module name normalization callback for quickjs, which resolves import
+/// specifiers with the module loader, and records the imports in the module
+/// graph of the context.
+///
+/// The opaque pointer is the same as for the module loader callback.
+unsafe extern "C" fn module_normalize_trampoline(
+    ctx: *mut q::JSContext,
+    base: *const c_char,
+    specifier: *const c_char,
+    opaque: *mut c_void,
+) -> *mut c_char {
+    let wrapper = context_wrapper(ctx);
+    let base = std::ffi::CStr::from_ptr(base).to_string_lossy();
+    let base = wrapper.unversioned_module_name(&base);
+    let specifier = std::ffi::CStr::from_ptr(specifier).to_string_lossy();
+
+    let result = if opaque.is_null() {
+        Ok(crate::module::resolve(base, &specifier))
+    } else {
+        let loader = &*(opaque as *const Box<dyn ModuleLoader>);
+        std::panic::catch_unwind(|| loader.resolve(base, &specifier)).unwrap_or_else(|_| {
+            wrapper.caught_panic("Module loader panicked!");
+            Err("Module loader panicked!".to_string())
+        })
+    };
+    let result = result.map(|name| {
+        let mut graph = wrapper.module_graph.lock().unwrap();
+        graph.add_import(base, &specifier, &name);
+        wrapper.versioned_module_name(&name)
+    });
+    let name = match result.map(make_cstring) {
+        Ok(Ok(name)) => name,
+        Ok(Err(_)) => {
+            throw_reference_error(
+                ctx,
+                &format!(
+                    "could not resolve module '{}': name contains zero byte",
+                    specifier
+                ),
+            );
+            return std::ptr::null_mut();
+        }
+        Err(e) => {
+            throw_reference_error(
+                ctx,
+                &format!("could not resolve module '{}': {}", specifier, e),
+            );
+            return std::ptr::null_mut();
+        }
+    };
+    // Freed by quickjs.
+    q::js_strdup(ctx, name.as_ptr())
+}
+
+/// Throw the given error as a Javascript exception, and return the exception
+/// value for returning it from a C function.
+fn throw_error(context: *mut q::JSContext, error: &JsError) -> q::JSValue {
+    let wrapper = unsafe { context_wrapper(context) };
+    let js_exception = match wrapper.create_error(error) {
+        Ok(value) => unsafe { value.into_inner() },
+        Err(ExecutionError::StackOverflow) => {
+            return js_throw_stack_overflow(context);
+        }
+        // Fall back to throwing the reason of the failure.
+        Err(e) => serialize_value(context, e.to_string().into()).unwrap(),
+    };
+    unsafe {
+        q::JS_Throw(context, js_exception);
+    }
+
+    q::JSValue {
+        u: q::JSValueUnion { int32: 0 },
+        tag: TAG_EXCEPTION,
+    }
+}
+
+/// Throw a JS ReferenceError with the given message.
+unsafe fn throw_reference_error(ctx: *mut q::JSContext, message: &str) {
+    // Zero bytes are not allowed in C strings.
+    let message = make_cstring(message.replace('\0', "")).unwrap();
+    q::JS_ThrowReferenceError(ctx, b"%s\0".as_ptr() as *const c_char, message.as_ptr());
+}
+
+/// The default maximum stack size of runtimes (in bytes).
+const DEFAULT_MAX_STACK_SIZE: usize = 256 * 1024;
+
+/// Wraps a quickjs runtime.
+///
+/// The runtime is shared between all contexts created in it and is freed
+/// once the last context referencing it is dropped.
+pub struct RuntimeWrapper {
+    runtime: *mut q::JSRuntime,
+    /// The module loader. Double boxed to get a thin pointer that can be
+    /// passed to quickjs as opaque data.
+    module_loader: Mutex<Option<Box<Box<dyn ModuleLoader>>>>,
+    /// The cache for the bytecode of loaded modules.
+    bytecode_cache: Mutex<Option<Arc<dyn BytecodeCache>>>,
+    /// Boxed to get a stable pointer that can be passed to quickjs as opaque
+    /// data.
+    interrupt: Box<InterruptState>,
+    /// The contexts created in this runtime, including dropped ones until
+    /// they are pruned.
+    contexts: Mutex<Vec<Weak<ContextWrapper>>>,
+    /// Whether handles can only be used in the context they belong to.
+    isolated: Cell<bool>,
+    /// The allocator of the runtime, dropped after the runtime was freed.
+    allocator: Box<allocator::AllocatorState>,
+    /// Finalizers whose target was collected.
+    finalizers: finalizer::FinalizerQueue,
+    /// Futures settling promises.
+    tasks: task::TaskQueue,
+}
+
+impl Drop for RuntimeWrapper {
+    fn drop(&mut self) {
+        // All contexts were freed, so the objects still alive are leaked.
+        #[cfg(feature = "leak-tracking")]
+        if let Some(report) = self.leaked_objects() {
+            eprintln!("{}", report);
+        }
+        unsafe {
+            q::JS_FreeRuntime(self.runtime);
+        }
+        // Freeing the runtime collects all remaining objects.
+        while let Some(finalizer) = self.take_finalizer() {
+            finalizer();
+        }
+    }
+}
+
+impl RuntimeWrapper {
+    /// Initialize a new JSRuntime, allocating its memory with `allocator`.
+    pub fn new(allocator: Box<dyn GlobalAlloc>) -> Result<Self, ContextError> {
+        let allocator = allocator::AllocatorState::new(allocator);
+        let runtime = allocator::new_runtime(&allocator);
+        if runtime.is_null() {
+            return Err(ContextError::RuntimeCreationFailed);
+        }
+        // quickjs-ng defaults to 1 MiB, which can overflow the 2 MiB stack of
+        // spawned threads together with the frames of the host.
+        unsafe {
+            q::JS_SetMaxStackSize(runtime, DEFAULT_MAX_STACK_SIZE as _);
+        }
+        let wrapper = Self {
+            runtime,
+            module_loader: Mutex::new(None),
+            bytecode_cache: Mutex::new(None),
+            interrupt: Box::new(InterruptState {
+                handler: Mutex::new(None),
+                deadline: Cell::new(None),
+                depth: Cell::new(0),
+                ticks: Cell::new(0),
+                fuel_limit: Cell::new(None),
+                cancelled: Arc::new(AtomicBool::new(false)),
+                interrupted: Cell::new(None),
+            }),
+            contexts: Mutex::new(Vec::new()),
+            isolated: Cell::new(false),
+            allocator,
+            finalizers: Default::default(),
+            tasks: Default::default(),
+        };
+        #[cfg(feature = "atomics")]
+        wrapper.enable_atomics();
+        Ok(wrapper)
+    }
+
+    /// Register a context created in this runtime.
+    fn add_context(&self, context: &Rc<ContextWrapper>) {
+        let mut contexts = self.contexts.lock().unwrap();
+        contexts.retain(|context| context.strong_count() > 0);
+        contexts.push(Rc::downgrade(context));
+    }
+
+    /// The contexts of this runtime that are still alive, in the order they
+    /// were created.
+    pub fn contexts(&self) -> Vec<Rc<ContextWrapper>> {
+        let mut contexts = self.contexts.lock().unwrap();
+        contexts.retain(|context| context.strong_count() > 0);
+        contexts.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Only allow handles to be used in the context they belong to.
+    pub fn set_isolated(&self, isolated: bool) {
+        self.isolated.set(isolated);
+    }
+
+    /// Set the memory limit of the runtime (in bytes).
+    pub fn set_memory_limit(&self, limit: usize) {
+        unsafe {
+            q::JS_SetMemoryLimit(self.runtime, limit as _);
+        }
+    }
+
+    /// Set the maximum stack size of the runtime (in bytes).
+    pub fn set_max_stack_size(&self, size: usize) {
+        unsafe {
+            q::JS_SetMaxStackSize(self.runtime, size as _);
+        }
+    }
+
+    /// Set the allocation threshold (in bytes) that triggers the garbage
+    /// collector.
+    pub fn set_gc_threshold(&self, threshold: usize) {
+        unsafe {
+            q::JS_SetGCThreshold(self.runtime, threshold as _);
+        }
+    }
+
+    /// Run the garbage collector.
+    pub fn run_gc(&self) {
+        unsafe {
+            q::JS_RunGC(self.runtime);
+        }
+    }
+
+    /// Compute the current memory usage of the runtime.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut raw = std::mem::MaybeUninit::<q::JSMemoryUsage>::uninit();
+        let raw = unsafe {
+            q::JS_ComputeMemoryUsage(self.runtime, raw.as_mut_ptr());
+            raw.assume_init()
+        };
+        MemoryUsage {
+            malloc_size: raw.malloc_size,
+            malloc_limit: raw.malloc_limit,
+            memory_used_size: raw.memory_used_size,
+            malloc_count: raw.malloc_count,
+            memory_used_count: raw.memory_used_count,
+            atom_count: raw.atom_count,
+            atom_size: raw.atom_size,
+            str_count: raw.str_count,
+            str_size: raw.str_size,
+            obj_count: raw.obj_count,
+            obj_size: raw.obj_size,
+            prop_count: raw.prop_count,
+            prop_size: raw.prop_size,
+            shape_count: raw.shape_count,
+            shape_size: raw.shape_size,
+            js_func_count: raw.js_func_count,
+            js_func_size: raw.js_func_size,
+            js_func_code_size: raw.js_func_code_size,
+            js_func_pc2line_count: raw.js_func_pc2line_count,
+            js_func_pc2line_size: raw.js_func_pc2line_size,
+            c_func_count: raw.c_func_count,
+            array_count: raw.array_count,
+            fast_array_count: raw.fast_array_count,
+            fast_array_elements: raw.fast_array_elements,
+            binary_object_count: raw.binary_object_count,
+            binary_object_size: raw.binary_object_size,
+        }
+    }
+
+    /// The allocation statistics of the runtime.
+    pub fn allocation_stats(&self) -> AllocationStats {
+        self.allocator.stats()
+    }
+
+    /// Run `f`, and return the statistics of the allocations of the runtime
+    /// while it ran.
+    pub fn measure_allocations<T>(&self, f: impl FnOnce() -> T) -> (T, AllocationStats) {
+        self.allocator.measure(f)
+    }
+
+    /// Check if there are pending jobs in the job queue.
+    pub fn has_pending_jobs(&self) -> bool {
+        if self.has_woken_tasks() {
+            return true;
+        }
+        unsafe { q::JS_IsJobPending(self.runtime) > 0 || self.has_pending_finalizers() }
+    }
+
+    /// Set the loader used to resolve module imports.
+    pub fn set_module_loader(&self, loader: Box<dyn ModuleLoader>) {
+        let loader = Box::new(loader);
+        let opaque = (&*loader) as *const Box<dyn ModuleLoader> as *mut c_void;
+        unsafe {
+            q::JS_SetModuleLoaderFunc(
+                self.runtime,
+                Some(module_normalize_trampoline),
+                Some(module_loader_trampoline),
+                opaque,
+            );
+        }
+        // The previous loader is dropped only after quickjs was pointed to
+        // the new one.
+        *self.module_loader.lock().unwrap() = Some(loader);
+    }
+
+    /// Set the cache for the bytecode of modules loaded by the module loader.
+    ///
+    /// Unsafe because the cached bytecode is not validated.
+    pub unsafe fn set_bytecode_cache(&self, cache: Arc<dyn BytecodeCache>) {
+        *self.bytecode_cache.lock().unwrap() = Some(cache);
+    }
+
+    fn bytecode_cache(&self) -> Option<Arc<dyn BytecodeCache>> {
+        self.bytecode_cache.lock().unwrap().clone()
+    }
+
+    /// Resolve an import specifier of the module named `base` with the
+    /// module loader.
+    fn resolve_module(&self, base: &str, specifier: &str) -> Result<String, String> {
+        match &*self.module_loader.lock().unwrap() {
+            Some(loader) => loader.resolve(base, specifier),
+            None => Ok(crate::module::resolve(base, specifier)),
+        }
+    }
+
+    /// Install the module loader callback without a loader, for loading
+    /// native modules, if no loader is set.
+    fn enable_module_loader(&self) {
+        let loader = self.module_loader.lock().unwrap();
+        if loader.is_none() {
+            unsafe {
+                q::JS_SetModuleLoaderFunc(
+                    self.runtime,
+                    Some(module_normalize_trampoline),
+                    Some(module_loader_trampoline),
+                    std::ptr::null_mut(),
+                );
+            }
+        }
+    }
+
+    /// Set the handler that is periodically called during execution.
+    pub fn set_interrupt_handler(&self, handler: Box<InterruptHandler>) {
+        *self.interrupt.handler.lock().unwrap() = Some(handler);
+        self.enable_interrupt_trampoline();
+    }
+
+    fn enable_interrupt_trampoline(&self) {
+        let opaque = (&*self.interrupt) as *const InterruptState as *mut c_void;
+        unsafe {
+            q::JS_SetInterruptHandler(self.runtime, Some(interrupt_handler_trampoline), opaque);
+        }
+    }
+
+    /// Get the flag that cancels the running execution when set.
+    ///
+    /// The interrupt handler is enabled, so the flag is checked during
+    /// execution.
+    pub fn cancellation_flag(&self) -> Arc<AtomicBool> {
+        self.enable_interrupt_trampoline();
+        self.interrupt.cancelled.clone()
+    }
+
+    /// Start an execution, until the returned guard is dropped.
+    ///
+    /// Executions started from a callback are nested in the outer
+    /// execution. The outermost execution fails with
+    /// `ExecutionError::Cancelled` without running if a cancellation was
+    /// requested before it started, which consumes the request.
+    ///
+    /// If a timeout is given, execution is aborted with
+    /// `ExecutionError::TimedOut` once it has passed. If a deadline is
+    /// already active, the outer deadline stays in effect.
+    fn start_execution(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<ExecutionGuard<'_>, ExecutionError> {
+        let depth = self.interrupt.depth.get();
+        if depth == 0 {
+            self.check_cancelled()?;
+        }
+        self.interrupt.depth.set(depth + 1);
+
+        let deadline = match timeout {
+            Some(timeout) if self.interrupt.deadline.get().is_none() => {
+                self.interrupt.deadline.set(Some(Instant::now() + timeout));
+                self.enable_interrupt_trampoline();
+                true
+            }
+            _ => false,
+        };
+        Ok(ExecutionGuard {
+            runtime: self,
+            deadline,
+        })
+    }
+
+    /// Abort execution with `ExecutionError::OutOfFuel` after `fuel` more
+    /// interrupt checks, until the returned guard is dropped.
+    ///
+    /// A limit that is already active stays in effect if it is lower.
+    fn limit_fuel(&self, fuel: u64) -> FuelGuard<'_> {
+        self.enable_interrupt_trampoline();
+        let previous = self.interrupt.fuel_limit.get();
+        let limit = self.interrupt.ticks.get().saturating_add(fuel);
+        let limit = previous.map_or(limit, |previous| previous.min(limit));
+        self.interrupt.fuel_limit.set(Some(limit));
+        FuelGuard {
+            runtime: self,
+            previous,
+        }
+    }
+
+    /// The number of interrupt checks made so far, used as fuel.
+    ///
+    /// The interrupt handler is enabled, so following checks are counted.
+    pub fn interrupt_ticks(&self) -> u64 {
+        self.enable_interrupt_trampoline();
+        self.interrupt.ticks.get()
+    }
+
+    /// Return `ExecutionError::Cancelled` if the running execution was
+    /// cancelled, for checks outside of the interrupt handler.
+    pub fn check_cancelled(&self) -> Result<(), ExecutionError> {
+        if self.interrupt.cancelled.swap(false, Ordering::SeqCst) {
+            Err(ExecutionError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check whether execution was aborted by an interrupt since the last
+    /// call, and reset the flag.
+    ///
+    /// Returns the error to report for the aborted execution.
+    pub fn take_interrupt(&self) -> Option<ExecutionError> {
+        self.interrupt
+            .interrupted
+            .take()
+            .map(|reason| match reason {
+                InterruptReason::Handler => ExecutionError::Interrupted,
+                InterruptReason::Timeout => ExecutionError::TimedOut,
+                InterruptReason::Cancelled => ExecutionError::Cancelled,
+                InterruptReason::OutOfFuel => ExecutionError::OutOfFuel,
+            })
+    }
+}
+
+/// Ends an execution started by [RuntimeWrapper::start_execution] on drop,
+/// clearing its deadline.
+struct ExecutionGuard<'a> {
+    runtime: &'a RuntimeWrapper,
+    deadline: bool,
+}
+
+/// Restores the previous fuel limit on drop.
+struct FuelGuard<'a> {
+    runtime: &'a RuntimeWrapper,
+    previous: Option<u64>,
+}
+
+impl<'a> Drop for FuelGuard<'a> {
+    fn drop(&mut self) {
+        self.runtime.interrupt.fuel_limit.set(self.previous);
+    }
+}
+
+impl<'a> Drop for ExecutionGuard<'a> {
+    fn drop(&mut self) {
+        let interrupt = &self.runtime.interrupt;
+        interrupt.depth.set(interrupt.depth.get() - 1);
+        if self.deadline {
+            interrupt.deadline.set(None);
+        }
+    }
+}
+
+type InterruptHandler = dyn Fn() -> bool + std::panic::RefUnwindSafe;
+
+#[derive(Clone, Copy)]
+enum InterruptReason {
+    Handler,
+    Timeout,
+    Cancelled,
+    OutOfFuel,
+}
+
+struct InterruptState {
+    handler: Mutex<Option<Box<InterruptHandler>>>,
+    /// The deadline of the current execution, if it has a timeout.
+    deadline: Cell<Option<Instant>>,
+    /// The number of nested executions.
+    depth: Cell<usize>,
+    /// The number of calls of the interrupt handler.
+    ticks: Cell<u64>,
+    /// The tick count at which execution runs out of fuel.
+    fuel_limit: Cell<Option<u64>>,
+    /// Set from any thread by a `CancellationHandle`.
+    cancelled: Arc<AtomicBool>,
+    /// Set when execution was aborted, so the resulting exception can be
+    /// reported as the matching `ExecutionError`.
+    interrupted: Cell<Option<InterruptReason>>,
+}
+
+/// Interrupt handler callback for quickjs.
+///
+/// The opaque pointer points to the InterruptState of the RuntimeWrapper.
+unsafe extern "C" fn interrupt_handler_trampoline(
+    _runtime: *mut q::JSRuntime,
+    opaque: *mut c_void,
+) -> c_int {
+    let state = &*(opaque as *const InterruptState);
+    let ticks = state.ticks.get() + 1;
+    state.ticks.set(ticks);
+
+    if state.cancelled.swap(false, Ordering::SeqCst) {
+        state.interrupted.set(Some(InterruptReason::Cancelled));
+        return 1;
+    }
+    if matches!(state.fuel_limit.get(), Some(limit) if ticks > limit) {
+        state.interrupted.set(Some(InterruptReason::OutOfFuel));
+        return 1;
+    }
+    if matches!(state.deadline.get(), Some(deadline) if Instant::now() >= deadline) {
+        state.interrupted.set(Some(InterruptReason::Timeout));
+        return 1;
+    }
+
+    let handler = state.handler.lock().unwrap();
+    let interrupt = match handler.as_ref() {
+        // A panicking handler interrupts execution.
+        Some(handler) => std::panic::catch_unwind(handler).unwrap_or(true),
+        None => false,
+    };
+    if interrupt {
+        state.interrupted.set(Some(InterruptReason::Handler));
+    }
+    interrupt as c_int
+}
+
+/// Get a new reference to the ContextWrapper owning a raw context.
+///
+/// Unsafe because the context must have been created by
+/// [ContextWrapper::new] and must not be freed yet.
+unsafe fn context_wrapper(context: *mut q::JSContext) -> Rc<ContextWrapper> {
+    let ptr = q::JS_GetContextOpaque(context) as *const ContextWrapper;
+    assert!(!ptr.is_null());
+    Rc::increment_strong_count(ptr);
+    Rc::from_raw(ptr)
+}
+
+/// Add the selected built-in objects to a context created with
+/// `JS_NewContextRaw`, in the same order as `JS_NewContext`.
+///
+/// The base objects, like `Object` and `Error`, and the `eval` machinery are
+/// always added, since `JS_Eval` requires them. `Promise` is always added,
+/// since async functions leak without it.
+unsafe fn add_intrinsics(context: *mut q::JSContext, intrinsics: &IntrinsicSet) {
+    q::JS_AddIntrinsicBaseObjects(context);
+    if intrinsics.date {
+        q::JS_AddIntrinsicDate(context);
+    }
+    q::JS_AddIntrinsicEval(context);
+    // quickjs-ng always adds `String.prototype.normalize`.
+    #[cfg(not(feature = "ng"))]
+    if intrinsics.string_normalize {
+        q::JS_AddIntrinsicStringNormalize(context);
+    }
+    if intrinsics.regexp {
+        q::JS_AddIntrinsicRegExp(context);
+    }
+    if intrinsics.json {
+        q::JS_AddIntrinsicJSON(context);
+    }
+    if intrinsics.proxy {
+        q::JS_AddIntrinsicProxy(context);
+    }
+    if intrinsics.map_set {
+        q::JS_AddIntrinsicMapSet(context);
+    }
+    if intrinsics.typed_arrays {
+        q::JS_AddIntrinsicTypedArrays(context);
+    }
+    q::JS_AddIntrinsicPromise(context);
+    #[cfg(any(feature = "bignum", feature = "ng"))]
+    if intrinsics.big_int {
+        q::JS_AddIntrinsicBigInt(context);
+    }
+    // The additional intrinsics of `JS_NewContext` in quickjs-ng.
+    #[cfg(feature = "ng")]
+    {
+        q::JS_AddIntrinsicWeakRef(context);
+        q::JS_AddIntrinsicDOMException(context);
+        q::JS_AddPerformance(context);
+    }
+    // quickjs-ng has a built-in `queueMicrotask`.
+    #[cfg(not(feature = "ng"))]
+    add_queue_microtask(context);
+}
+
+/// Add the global `queueMicrotask` function.
+///
+/// Microtasks are enqueued as jobs of the runtime, like promise reactions,
+/// so both run in the order they were enqueued.
+#[cfg(not(feature = "ng"))]
+unsafe fn add_queue_microtask(context: *mut q::JSContext) {
+    unsafe extern "C" fn run_microtask(
+        ctx: *mut q::JSContext,
+        _argc: c_int,
+        argv: *mut q::JSValue,
+    ) -> q::JSValue {
+        let this = q::JSValue {
+            u: q::JSValueUnion { int32: 0 },
+            tag: TAG_UNDEFINED,
+        };
+        q::JS_Call(ctx, *argv, this, 0, std::ptr::null_mut())
+    }
+
+    unsafe extern "C" fn queue_microtask(
+        ctx: *mut q::JSContext,
+        _this: q::JSValue,
+        _argc: c_int,
+        argv: *mut q::JSValue,
+    ) -> q::JSValue {
+        // Missing arguments are `undefined`, up to the declared length of 1.
+        // The message matches quickjs-ng.
+        if q::JS_IsFunction(ctx, *argv) == 0 {
+            return q::JS_ThrowTypeError(ctx, b"not a function\0".as_ptr() as *const c_char);
+        }
+        let tag = if q::JS_EnqueueJob(ctx, Some(run_microtask), 1, argv) < 0 {
+            TAG_EXCEPTION
+        } else {
+            TAG_UNDEFINED
+        };
+        q::JSValue {
+            u: q::JSValueUnion { int32: 0 },
+            tag,
+        }
+    }
+
+    let function = q::JS_NewCFunction2(
+        context,
+        Some(queue_microtask),
+        b"queueMicrotask\0".as_ptr() as *const c_char,
+        1,
+        q::JSCFunctionEnum_JS_CFUNC_generic,
+        0,
+    );
+    let global = q::JS_GetGlobalObject(context);
+    q::JS_DefinePropertyValueStr(
+        context,
+        global,
+        b"queueMicrotask\0".as_ptr() as *const c_char,
+        function,
+        (q::JS_PROP_WRITABLE | q::JS_PROP_CONFIGURABLE) as i32,
+    );
+    free_value(context, global);
+}
+
+/// JS helper that records the outcome of a promise on a tracker object,
+/// since quickjs does not expose the state of a promise.
+const PROMISE_TRACKER: &str = r#"
+    (promise) => {
+        const tracker = { state: 0, value: undefined };
+        promise.then(
+            (value) => {
+                tracker.state = 1;
+                tracker.value = value;
+            },
+            (error) => {
+                tracker.state = 2;
+                tracker.value = error;
+            },
+        );
+        return tracker;
+    }
+"#;
+
+/// Function creating the `Error` instance for a [JsError].
+///
+/// Global constructors are looked up with an indirect eval, since classes
+/// declared by scripts are not properties of the global object. The
+/// built-ins used are captured when the function is created, so it keeps
+/// working if scripts or a [Realm](crate::realm::Realm) remove them.
+const ERROR_FACTORY: &str = r#"
+    (() => {
+        const lookup = eval;
+        const BaseError = Error;
+        const { assign, defineProperty } = Object;
+        // Not a regular expression, since `RegExp` may not be available.
+        const isIdentifier = (name) => {
+            for (let i = 0; i < name.length; i++) {
+                const c = name[i];
+                const letter = (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z');
+                const digit = c >= '0' && c <= '9';
+                if (!letter && c !== '_' && c !== '$' && !(digit && i > 0)) {
+                    return false;
+                }
+            }
+            return name.length > 0;
+        };
+        return (name, message, properties) => {
+            let ctor;
+            if (isIdentifier(name)) {
+                try {
+                    ctor = lookup(name);
+                } catch (e) {}
+            }
+            const isErrorClass =
+                typeof ctor === 'function' &&
+                (ctor === BaseError || ctor.prototype instanceof BaseError);
+            const error = isErrorClass ? new ctor(message) : new BaseError(message);
+            if (error.name !== name) {
+                defineProperty(error, 'name', {
+                    value: name,
+                    writable: true,
+                    configurable: true,
+                });
+            }
+            return assign(error, properties);
+        };
+    })()
+"#;
+
+/// A step of the setup of a context, eg adding a callback, which is applied
+/// again when the context is reset.
+pub type SetupStep = Rc<dyn Fn(&ContextWrapper) -> Result<(), ExecutionError>>;
+
+/// Wraps a quickjs context.
+///
+/// The wrapper is always stored in an `Rc`, which is referenced by the
+/// context opaque pointer. This allows [PersistentValue]s to keep the context
+/// alive.
+///
+/// Cleanup of the context happens in drop.
+///
+/// Callbacks may execute code in the context again, so locks and borrows of
+/// its state must not be held while Javascript code runs.
+pub struct ContextWrapper {
+    runtime: Rc<RuntimeWrapper>,
+    context: *mut q::JSContext,
+    /// Stores callback closures and quickjs data pointers.
+    /// This array is write-only and only exists to ensure the lifetime of
+    /// the closure.
+    // A Mutex is used over a RefCell because it needs to be unwind-safe.
+    callbacks: Mutex<Vec<(Box<WrappedCallback>, Box<q::JSValue>)>>,
+    /// The lazily compiled [PROMISE_TRACKER] function.
+    promise_tracker: Mutex<Option<q::JSValue>>,
+    /// The lazily compiled [ERROR_FACTORY] function.
+    error_factory: Mutex<Option<q::JSValue>>,
+    /// The lazily compiled function registering finalizers.
+    finalizer_registry: Mutex<Option<q::JSValue>>,
+    /// The function wrapping dynamic objects, created on first use.
+    dynamic_object_factory: Mutex<Option<q::JSValue>>,
+    /// The function wrapping Rust iterators, created on first use.
+    iterator_factory: Mutex<Option<q::JSValue>>,
+    /// The lazily compiled function opening Javascript iterators.
+    iterator_stepper: Mutex<Option<q::JSValue>>,
+    /// The function wrapping Rust streams, created on first use.
+    #[cfg(feature = "futures")]
+    async_iterator_factory: Mutex<Option<q::JSValue>>,
+    /// The lazily compiled function opening Javascript async iterators.
+    #[cfg(feature = "futures")]
+    async_iterator_stepper: Mutex<Option<q::JSValue>>,
+    /// Maximum duration of a single `eval` or `call_function`.
+    execution_timeout: Cell<Option<Duration>>,
+    /// Whether panics of Rust code called from Javascript abort the process.
+    abort_on_panic: Cell<bool>,
+    /// How serde converts integers beyond the safe integer range.
+    integer_policy: Cell<IntegerPolicy>,
+    /// Which value serde converts `None` to.
+    none_policy: Cell<NonePolicy>,
+    /// The live handles to values of this context.
+    #[cfg(feature = "leak-tracking")]
+    handles: leaks::HandleTracker,
+    /// The function running timer callbacks, if timers are enabled.
+    timer_runner: Mutex<Option<q::JSValue>>,
+    timer_queue: timers::SharedTimerQueue,
+    /// The clock used for `Date` and timers, if it is not the system clock.
+    time_source: RefCell<Option<Rc<dyn TimeSource>>>,
+    /// The steps applied by [setup](ContextWrapper::setup), in order.
+    setup_steps: Mutex<Vec<SetupStep>>,
+    /// The built-in objects the context was created with.
+    intrinsics: IntrinsicSet,
+    /// The finalizers of the classes registered in this context.
+    class_finalizers: class::ClassFinalizers,
+    /// The native modules registered in this context.
+    native_modules: module::NativeModules,
+    /// The JSON and binary modules loaded in this context, until they are
+    /// evaluated.
+    data_modules: module::NativeModules,
+    /// The modules loaded in this context and their imports.
+    module_graph: Mutex<ModuleGraph>,
+    /// The number of times modules were invalidated in this context.
+    module_versions: module::ModuleVersions,
+}
+
+impl Drop for ContextWrapper {
+    fn drop(&mut self) {
+        // The runtime itself is freed when the last reference to it is
+        // dropped, which always happens after the context is freed.
+        self.drop_tasks();
+        unsafe {
+            if let Some(tracker) = self.promise_tracker.lock().unwrap().take() {
+                free_value(self.context, tracker);
+            }
+            if let Some(factory) = self.error_factory.lock().unwrap().take() {
+                free_value(self.context, factory);
+            }
+            if let Some(registry) = self.finalizer_registry.lock().unwrap().take() {
+                free_value(self.context, registry);
+            }
+            if let Some(factory) = self.dynamic_object_factory.lock().unwrap().take() {
+                free_value(self.context, factory);
+            }
+            if let Some(factory) = self.iterator_factory.lock().unwrap().take() {
+                free_value(self.context, factory);
+            }
+            if let Some(stepper) = self.iterator_stepper.lock().unwrap().take() {
+                free_value(self.context, stepper);
+            }
+            #[cfg(feature = "futures")]
+            if let Some(factory) = self.async_iterator_factory.lock().unwrap().take() {
+                free_value(self.context, factory);
+            }
+            #[cfg(feature = "futures")]
+            if let Some(stepper) = self.async_iterator_stepper.lock().unwrap().take() {
+                free_value(self.context, stepper);
+            }
+            if let Some(runner) = self.timer_runner.lock().unwrap().take() {
+                free_value(self.context, runner);
+            }
+            q::JS_SetContextOpaque(self.context, std::ptr::null_mut());
+            q::JS_FreeContext(self.context);
+        }
+    }
+}
+
+impl ContextWrapper {
+    /// Initialize a wrapper by creating a JSContext in the given runtime.
+    pub fn new(
+        runtime: Rc<RuntimeWrapper>,
+        intrinsics: IntrinsicSet,
+    ) -> Result<Rc<Self>, ContextError> {
+        let context = unsafe { q::JS_NewContextRaw(runtime.runtime) };
+        if context.is_null() {
+            return Err(ContextError::ContextCreationFailed);
+        }
+        unsafe { add_intrinsics(context, &intrinsics) };
+
+        let wrapper = Rc::new(Self {
+            runtime,
+            context,
+            callbacks: Mutex::new(Vec::new()),
+            promise_tracker: Mutex::new(None),
+            error_factory: Mutex::new(None),
+            finalizer_registry: Mutex::new(None),
+            dynamic_object_factory: Mutex::new(None),
+            iterator_factory: Mutex::new(None),
+            iterator_stepper: Mutex::new(None),
+            #[cfg(feature = "futures")]
+            async_iterator_factory: Mutex::new(None),
+            #[cfg(feature = "futures")]
+            async_iterator_stepper: Mutex::new(None),
+            execution_timeout: Cell::new(None),
+            abort_on_panic: Cell::new(false),
+            integer_policy: Cell::new(IntegerPolicy::default()),
+            none_policy: Cell::new(NonePolicy::default()),
+            #[cfg(feature = "leak-tracking")]
+            handles: Default::default(),
+            timer_runner: Mutex::new(None),
+            timer_queue: Default::default(),
+            time_source: RefCell::new(None),
+            setup_steps: Mutex::new(Vec::new()),
+            intrinsics,
+            class_finalizers: Default::default(),
+            native_modules: Default::default(),
+            data_modules: Default::default(),
+            module_graph: Default::default(),
+            module_versions: Default::default(),
+        });
+        unsafe {
+            q::JS_SetContextOpaque(context, Rc::as_ptr(&wrapper) as *mut c_void);
+        }
+        wrapper.runtime.add_context(&wrapper);
+        wrapper
+            .set_console(crate::console::default_backend().into())
+            .map_err(ContextError::Execution)?;
+        // The `URL` glue wraps the classes with a `Proxy`.
+        #[cfg(feature = "url")]
+        if intrinsics.proxy {
+            wrapper.enable_url().map_err(ContextError::Execution)?;
+        }
+
+        Ok(wrapper)
+    }
+
+    /// The runtime this context belongs to.
+    pub fn runtime(&self) -> &Rc<RuntimeWrapper> {
+        &self.runtime
+    }
+
+    /// The built-in objects the context was created with.
+    pub fn intrinsics(&self) -> IntrinsicSet {
+        self.intrinsics
+    }
+
+    /// Apply a setup step, and record it so it can be applied to the
+    /// replacement of this context with [replay_setup](ContextWrapper::replay_setup).
+    pub fn setup<S>(&self, step: S) -> Result<(), ExecutionError>
+    where
+        S: Fn(&ContextWrapper) -> Result<(), ExecutionError> + 'static,
+    {
+        step(self)?;
+        self.setup_steps.lock().unwrap().push(Rc::new(step));
+        Ok(())
+    }
+
+    /// The setup steps applied to this context.
+    pub fn setup_steps(&self) -> Vec<SetupStep> {
+        self.setup_steps.lock().unwrap().clone()
+    }
+
+    /// Apply and record the setup steps of another context.
+    pub fn replay_setup(&self, steps: Vec<SetupStep>) -> Result<(), ExecutionError> {
+        for step in &steps {
+            step(self)?;
+        }
+        self.setup_steps.lock().unwrap().extend(steps);
+        Ok(())
+    }
+
+    /// Set the maximum duration of a single `eval` or `call_function`.
+    pub fn set_execution_timeout(&self, timeout: Option<Duration>) {
+        self.execution_timeout.set(timeout);
+    }
+
+    /// Abort the process on panics of Rust code called from Javascript,
+    /// instead of throwing an exception.
+    pub fn set_abort_on_panic(&self, abort: bool) {
+        self.abort_on_panic.set(abort);
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn integer_policy(&self) -> IntegerPolicy {
+        self.integer_policy.get()
+    }
+
+    /// Set how serde converts integers beyond the safe integer range.
+    pub fn set_integer_policy(&self, policy: IntegerPolicy) {
+        self.integer_policy.set(policy);
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn none_policy(&self) -> NonePolicy {
+        self.none_policy.get()
+    }
+
+    /// Set which value serde converts `None` to.
+    pub fn set_none_policy(&self, policy: NonePolicy) {
+        self.none_policy.set(policy);
+    }
+
+    /// Handle a caught panic of Rust code called from Javascript, which must
+    /// not unwind into quickjs.
+    ///
+    /// Aborts the process if requested, and returns the error that is thrown
+    /// instead otherwise.
+    fn caught_panic(&self, message: &str) -> ExecutionError {
+        if self.abort_on_panic.get() {
+            std::process::abort();
+        }
+        ExecutionError::Internal(message.to_string())
+    }
+
+    /// Start an execution, with the execution timeout if one is configured.
+    fn start_execution(&self) -> Result<ExecutionGuard<'_>, ExecutionError> {
+        self.runtime.start_execution(self.execution_timeout.get())
+    }
+
+    // See console standard: https://console.spec.whatwg.org
+    pub fn set_console(&self, backend: Rc<dyn ConsoleBackend>) -> Result<(), ExecutionError> {
+        use crate::console::Level;
+
+        for &level in &[
+            Level::Trace,
+            Level::Debug,
+            Level::Log,
+            Level::Info,
+            Level::Warn,
+            Level::Error,
+        ] {
+            let backend = backend.clone();
+            // Callbacks are kept in an `Arc` to be `RefUnwindSafe`, they are
+            // not shared between threads.
+            #[allow(clippy::arc_with_non_send_sync)]
+            let log = Arc::new(move |args: Arguments| backend.log(level, args.into_vec()));
+            self.add_callback_at(&format!("console.{}", level), log)?;
+        }
+        Ok(())
+    }
+
+    pub fn serialize_value(&self, value: JsValue) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let serialized = serialize_value(self.context, value)?;
+        Ok(OwnedValueRef::new(self, serialized))
+    }
+
+    // Deserialize a quickjs runtime value into a Rust value.
+    fn to_value(&self, value: &q::JSValue) -> Result<JsValue, ValueError> {
+        deserialize_value(self.context, value)
     }
 
     /// Get the global object.
@@ -923,29 +3015,414 @@ impl ContextWrapper {
         Ok(global)
     }
 
-    /// Get the last exception from the runtime, and if present, convert it to a ExceptionError.
-    fn get_exception(&self) -> Option<ExecutionError> {
-        let raw = unsafe { q::JS_GetException(self.context) };
-        let value = OwnedValueRef::new(self, raw);
+    /// Get the global `Symbol` function.
+    fn symbol_function(&self) -> Result<OwnedObjectRef<'_>, ExecutionError> {
+        let symbol = self.global()?.property("Symbol")?;
+        Ok(OwnedObjectRef::new(symbol)?)
+    }
+
+    /// Create a new unique symbol, like `Symbol(description)`.
+    pub fn new_symbol(&self, description: Option<&str>) -> Result<JsValue, ExecutionError> {
+        let args = match description {
+            Some(description) => vec![self.serialize_value(JsValue::String(description.into()))?],
+            None => Vec::new(),
+        };
+        let function = self.symbol_function()?.into_value();
+        Ok(self.call_function(function, args)?.to_value()?)
+    }
+
+    /// Get the symbol for a key from the global registry, like `Symbol.for(key)`.
+    pub fn symbol_for(&self, key: &str) -> Result<JsValue, ExecutionError> {
+        let key = self.serialize_value(JsValue::String(key.into()))?;
+        let function = self.symbol_function()?.property("for")?;
+        Ok(self.call_function(function, vec![key])?.to_value()?)
+    }
+
+    /// Get a well-known symbol, like `Symbol.iterator`.
+    pub fn well_known_symbol(&self, name: &str) -> Result<JsValue, ExecutionError> {
+        Ok(self.symbol_function()?.property(name)?.to_value()?)
+    }
+
+    /// Get the description of a symbol.
+    pub fn symbol_description(
+        &self,
+        symbol: &OwnedValueRef,
+    ) -> Result<Option<String>, ExecutionError> {
+        let raw = unsafe {
+            q::JS_GetPropertyStr(
+                self.context,
+                symbol.value,
+                b"description\0".as_ptr() as *const _,
+            )
+        };
+        let description = OwnedValueRef::new(self, raw);
+        if description.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        if description.is_undefined() {
+            Ok(None)
+        } else {
+            Ok(Some(description.to_string()?))
+        }
+    }
+
+    /// Create an ArrayBuffer backed by the memory of `buffer`, without copying.
+    ///
+    /// The buffer is dropped when the ArrayBuffer is garbage collected.
+    pub fn new_array_buffer<B>(&self, buffer: B) -> Result<OwnedValueRef<'_>, ExecutionError>
+    where
+        B: AsMut<[u8]> + 'static,
+    {
+        let mut buffer = Box::new(buffer);
+        let data = (*buffer).as_mut();
+        let (ptr, len) = (data.as_mut_ptr(), data.len());
+        let opaque = Box::into_raw(buffer);
+
+        let raw = unsafe {
+            q::JS_NewArrayBuffer(
+                self.context,
+                ptr,
+                len as _,
+                Some(free_array_buffer::<B>),
+                opaque as *mut c_void,
+                0,
+            )
+        };
+        let value = OwnedValueRef::new(self, raw);
+        if value.is_exception() {
+            // The free function is only called for successfully created buffers.
+            drop(unsafe { Box::from_raw(opaque) });
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        Ok(value)
+    }
+
+    /// Parse a JSON string, like `JSON.parse`.
+    pub fn json_parse(&self, json: &str) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let json_c = make_cstring(json)?;
+        let filename_c = make_cstring("input.json")?;
+
+        let _execution = self.start_execution()?;
+        let raw = unsafe {
+            q::JS_ParseJSON(
+                self.context,
+                json_c.as_ptr(),
+                json.len() as _,
+                filename_c.as_ptr(),
+            )
+        };
+        let value = OwnedValueRef::new(self, raw);
+        if value.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        Ok(value)
+    }
+
+    /// Convert a value to a JSON string, like `JSON.stringify`.
+    ///
+    /// Returns `None` for values without a JSON representation, like
+    /// `undefined` or functions.
+    pub fn json_stringify(&self, value: &OwnedValueRef) -> Result<Option<String>, ExecutionError> {
+        let _execution = self.start_execution()?;
+        let raw = unsafe {
+            q::JS_JSONStringify(
+                self.context,
+                value.value,
+                js_undefined_value(),
+                js_undefined_value(),
+            )
+        };
+        let json = OwnedValueRef::new(self, raw);
+        if json.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        if json.is_undefined() {
+            Ok(None)
+        } else {
+            Ok(Some(json.to_string()?))
+        }
+    }
+
+    /// Get the last exception from the runtime, and if present, convert it to a ExceptionError.
+    fn get_exception(&self) -> Option<ExecutionError> {
+        let raw = unsafe { q::JS_GetException(self.context) };
+        let value = OwnedValueRef::new(self, raw);
+
+        if value.is_null() {
+            None
+        } else if let Some(err) = self.runtime.take_interrupt() {
+            Some(err)
+        } else {
+            let err = if value.is_exception() {
+                ExecutionError::Internal("Could get exception from runtime".into())
+            } else {
+                match self.to_exception(value) {
+                    Ok(exception) => {
+                        if exception.message.contains("out of memory") {
+                            ExecutionError::OutOfMemory
+                        } else if is_stack_overflow(&exception) {
+                            ExecutionError::StackOverflow
+                        } else {
+                            ExecutionError::Exception(Box::new(exception))
+                        }
+                    }
+                    Err(_) => ExecutionError::Internal("Unknown exception".into()),
+                }
+            };
+            Some(err)
+        }
+    }
+
+    /// Convert a thrown value to a [JsException].
+    ///
+    /// The value is converted to a string, and for errors the name, message
+    /// and stack trace are captured.
+    fn to_exception(&self, value: OwnedValueRef<'_>) -> Result<JsException, ExecutionError> {
+        let string = value.to_string();
+        if !value.is_object() || unsafe { q::JS_IsError(self.context, value.value) } == 0 {
+            return Ok(JsException::from(string?));
+        }
+        if string.is_err() {
+            // Converting an error calls its `toString` method, which fails
+            // when the stack is exhausted, eg by a stack overflow in a
+            // nested execution.
+            js_clear_exception(self.context);
+        }
+
+        let error = OwnedObjectRef::new(value)?;
+        let string_property = |name: &str| -> Result<Option<String>, ExecutionError> {
+            let value = error.property_or_undefined(name)?;
+            if value.is_string() {
+                value.to_string().map(Some)
+            } else {
+                Ok(None)
+            }
+        };
+        let name = string_property("name")?.unwrap_or_default();
+        let message = string_property("message")?.unwrap_or_default();
+        let mut exception = JsException::from(string.unwrap_or_else(|_| match message.as_str() {
+            "" => name.clone(),
+            _ => format!("{}: {}", name, message),
+        }));
+        exception.name = name;
+        exception.message = message;
+        if let Some(stack) = string_property("stack")? {
+            exception.stack = JsException::parse_stack(&stack);
+        }
+        Ok(exception)
+    }
+
+    /// Execute a single pending job of the runtime.
+    ///
+    /// Finalizers whose target was collected run before other jobs, and
+    /// woken tasks are polled before the jobs of Javascript.
+    /// Returns `false` if there were no pending jobs.
+    pub fn execute_pending_job(&self) -> Result<bool, ExecutionError> {
+        if let Some(finalizer) = self.runtime.take_finalizer() {
+            finalizer();
+            return Ok(true);
+        }
+        if self.poll_task()? {
+            return Ok(true);
+        }
+        // The job may belong to any context in the runtime.
+        let mut job_context = std::ptr::null_mut();
+        let flag = unsafe { q::JS_ExecutePendingJob(self.runtime.runtime, &mut job_context) };
+        if flag < 0 {
+            let err = if job_context == self.context {
+                self.get_exception()
+            } else {
+                unsafe { context_wrapper(job_context) }.get_exception()
+            };
+            Err(err.unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())))
+        } else {
+            Ok(flag > 0)
+        }
+    }
+
+    /// Execute pending jobs until the queue is empty, or `limit` jobs were
+    /// executed.
+    ///
+    /// Returns the number of executed jobs.
+    pub fn execute_pending_jobs(&self, limit: Option<usize>) -> Result<usize, ExecutionError> {
+        let mut count = 0;
+        while limit != Some(count) && self.execute_pending_job()? {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Get a helper function implemented in Javascript, compiling it on first
+    /// use.
+    fn helper_function(
+        &self,
+        cache: &Mutex<Option<q::JSValue>>,
+        code: &str,
+        filename: &str,
+    ) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let mut cached = cache.lock().unwrap();
+        if cached.is_none() {
+            let code_c = make_cstring(code)?;
+            let filename_c = make_cstring(filename)?;
+            let value = unsafe {
+                q::JS_Eval(
+                    self.context,
+                    code_c.as_ptr(),
+                    code.len() as _,
+                    filename_c.as_ptr(),
+                    q::JS_EVAL_TYPE_GLOBAL as i32,
+                )
+            };
+            if value.tag != TAG_OBJECT {
+                unsafe { free_value(self.context, value) };
+                // Compiling fails when the stack is exhausted.
+                return Err(self.get_exception().unwrap_or_else(|| {
+                    ExecutionError::Internal(format!("Could not compile {}", filename))
+                }));
+            }
+            *cached = Some(value);
+        }
+        // The lock is released before the function is called, since it may
+        // run arbitrary code, like `then` of a thenable.
+        Ok(OwnedValueRef::new(self, unsafe {
+            dup_value(cached.unwrap())
+        }))
+    }
+
+    /// Create the value that is thrown for a [JsError].
+    pub fn create_error(&self, error: &JsError) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let (name, message, properties) = match error.repr() {
+            ErrorRepr::Value(value) => return self.serialize_value(value.clone()),
+            ErrorRepr::Error {
+                name,
+                message,
+                properties,
+            } => (name, message, properties),
+        };
+
+        let factory = self.helper_function(&self.error_factory, ERROR_FACTORY, "error.js")?;
+        let args = [
+            self.serialize_value(JsValue::String(name.clone()))?,
+            self.serialize_value(JsValue::String(message.clone()))?,
+            self.serialize_value(JsValue::Object(properties.clone()))?,
+        ];
+        let mut qargs = args.iter().map(|arg| arg.value).collect::<Vec<_>>();
+        let value = unsafe {
+            q::JS_Call(
+                self.context,
+                factory.value,
+                js_null_value(),
+                qargs.len() as i32,
+                qargs.as_mut_ptr(),
+            )
+        };
+        let value = OwnedValueRef::new(self, value);
+        if value.is_exception() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+            Err(err)
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Create a tracker object that records the outcome of the given
+    /// promise once it is settled.
+    pub fn track_promise<'a>(
+        &'a self,
+        promise: &OwnedValueRef<'_>,
+    ) -> Result<OwnedObjectRef<'a>, ValueError> {
+        let tracker_fn = self
+            .helper_function(&self.promise_tracker, PROMISE_TRACKER, "promise_tracker.js")
+            .map_err(|e| match e {
+                ExecutionError::Conversion(e) => e,
+                e => ValueError::Internal(e.to_string()),
+            })?;
+
+        let mut args = vec![promise.value];
+        let tracker_raw = unsafe {
+            q::JS_Call(
+                self.context,
+                tracker_fn.value,
+                js_null_value(),
+                1,
+                args.as_mut_ptr(),
+            )
+        };
+        OwnedObjectRef::new(OwnedValueRef::new(self, tracker_raw))
+    }
 
-        if value.is_null() {
-            None
-        } else {
-            let err = if value.is_exception() {
-                ExecutionError::Internal("Could get exception from runtime".into())
-            } else {
-                match value.to_string() {
-                    Ok(strval) => {
-                        if strval.contains("out of memory") {
-                            ExecutionError::OutOfMemory
-                        } else {
-                            ExecutionError::Exception(JsValue::String(strval))
-                        }
-                    }
-                    Err(_) => ExecutionError::Internal("Unknown exception".into()),
+    /// Get the state of a promise tracked with [track_promise].
+    pub fn tracked_promise_state(
+        &self,
+        tracker: &OwnedObjectRef<'_>,
+    ) -> Result<PromiseState, ExecutionError> {
+        match tracker.property("state")?.to_value()? {
+            JsValue::Int(0) => Ok(PromiseState::Pending),
+            JsValue::Int(1) => Ok(PromiseState::Fulfilled),
+            JsValue::Int(2) => Ok(PromiseState::Rejected),
+            _ => Err(ExecutionError::Internal("Invalid promise state".into())),
+        }
+    }
+
+    /// Get the result of a settled promise tracked with [track_promise].
+    ///
+    /// Returns `None` if the promise is still pending.
+    pub fn tracked_promise_result<'a>(
+        &self,
+        tracker: &OwnedObjectRef<'a>,
+    ) -> Result<Option<OwnedValueRef<'a>>, ExecutionError> {
+        match self.tracked_promise_state(tracker)? {
+            PromiseState::Pending => Ok(None),
+            PromiseState::Fulfilled => tracker.property_or_undefined("value").map(Some),
+            PromiseState::Rejected => {
+                // Interrupts in promise reactions reject the promise instead
+                // of aborting the job.
+                if let Some(err) = self.runtime.take_interrupt() {
+                    return Err(err);
                 }
-            };
-            Some(err)
+                let value = tracker.property_or_undefined("value")?;
+                Err(ExecutionError::Exception(Box::new(
+                    self.to_exception(value)?,
+                )))
+            }
+        }
+    }
+
+    /// Run the event loop until a promise tracked with [track_promise] is
+    /// settled, and return its result.
+    pub fn await_tracked_promise<'a>(
+        &self,
+        tracker: &OwnedObjectRef<'a>,
+    ) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        loop {
+            if let Some(value) = self.tracked_promise_result(tracker)? {
+                return Ok(value);
+            }
+            self.runtime.check_cancelled()?;
+            if !self.execute_pending_job()? {
+                if self.runtime.has_pending_tasks() {
+                    self.wait_for_tasks()?;
+                    continue;
+                }
+                // Wait for the next timer, which may settle the promise.
+                let deadline = self.next_timer_due().ok_or_else(|| {
+                    ExecutionError::Internal(
+                        "Promise can not be settled: there are no pending jobs left".into(),
+                    )
+                })?;
+                self.wait_for_timer(deadline)?;
+                self.run_due_timers(self.timer_now())?;
+            }
         }
     }
 
@@ -963,63 +3440,9 @@ impl ContextWrapper {
         } else if value.is_object() {
             let obj = OwnedObjectRef::new(value)?;
             if obj.is_promise()? {
-                self.eval(
-                    r#"
-                    // Values:
-                    //   - undefined: promise not finished
-                    //   - false: error ocurred, __promiseError is set.
-                    //   - true: finished, __promiseSuccess is set.
-                    var __promiseResult = 0;
-                    var __promiseValue = 0;
-
-                    var __resolvePromise = function(p) {
-                        p
-                            .then(value => {
-                                __promiseResult = true;
-                                __promiseValue = value;
-                            })
-                            .catch(e => {
-                                __promiseResult = false;
-                                __promiseValue = e;
-                            });
-                    }
-                "#,
-                )?;
-
-                let global = self.global()?;
-                let resolver = global.property("__resolvePromise")?;
-
-                // Call the resolver code that sets the result values once
-                // the promise resolves.
-                self.call_function(resolver, vec![obj.into_value()])?;
-
-                loop {
-                    let flag = unsafe {
-                        let wrapper_mut = self as *const Self as *mut Self;
-                        let ctx_mut = &mut (*wrapper_mut).context;
-                        q::JS_ExecutePendingJob(self.runtime, ctx_mut)
-                    };
-                    if flag < 0 {
-                        let e = self.get_exception().unwrap_or_else(|| {
-                            ExecutionError::Exception("Unknown exception".into())
-                        });
-                        return Err(e);
-                    }
-
-                    // Check if promise is finished.
-                    let res_val = global.property("__promiseResult")?;
-                    if res_val.is_bool() {
-                        let ok = res_val.to_bool()?;
-                        let value = global.property("__promiseValue")?;
-
-                        if ok {
-                            return self.resolve_value(value);
-                        } else {
-                            let err_msg = value.to_string()?;
-                            return Err(ExecutionError::Exception(JsValue::String(err_msg)));
-                        }
-                    }
-                }
+                let tracker = self.track_promise(&obj.value)?;
+                let value = self.await_tracked_promise(&tracker)?;
+                self.resolve_value(value)
             } else {
                 Ok(obj.into_value())
             }
@@ -1028,25 +3451,307 @@ impl ContextWrapper {
         }
     }
 
-    /// Evaluate javascript code.
-    pub fn eval<'a>(&'a self, code: &str) -> Result<OwnedValueRef<'a>, ExecutionError> {
-        let filename = "script.js";
+    /// Evaluate javascript code with the given JS_EVAL_* flags.
+    fn eval_raw<'a>(
+        &'a self,
+        code: &str,
+        filename: &str,
+        flags: u32,
+    ) -> Result<OwnedValueRef<'a>, ExecutionError> {
         let filename_c = make_cstring(filename)?;
         let code_c = make_cstring(code)?;
 
+        // Discard interrupts of earlier executions that were not reported.
+        self.runtime.take_interrupt();
+
+        // Modules are compiled first, to set up `import.meta` before they
+        // are evaluated.
+        let is_module = flags & q::JS_EVAL_TYPE_MASK == q::JS_EVAL_TYPE_MODULE;
+        let compile_flags = if is_module {
+            flags | q::JS_EVAL_FLAG_COMPILE_ONLY
+        } else {
+            flags
+        };
         let value_raw = unsafe {
             q::JS_Eval(
                 self.context,
                 code_c.as_ptr(),
                 code.len() as _,
                 filename_c.as_ptr(),
-                q::JS_EVAL_TYPE_GLOBAL as i32,
+                compile_flags as i32,
             )
         };
         let value = OwnedValueRef::new(self, value_raw);
+        if !is_module {
+            return Ok(value);
+        }
+        let state = if value.value.tag == TAG_MODULE {
+            ModuleState::Loaded
+        } else {
+            ModuleState::Failed
+        };
+        self.module_graph.lock().unwrap().set_state(filename, state);
+        if state == ModuleState::Failed {
+            return Ok(value);
+        }
+
+        let m = unsafe { value.value.u.ptr } as *mut q::JSModuleDef;
+        let loader = self.runtime.module_loader.lock().unwrap();
+        unsafe { module::set_import_meta(self.context, m, loader.as_ref().map(|l| &***l)) }?;
+        drop(loader);
+        if flags & q::JS_EVAL_FLAG_COMPILE_ONLY != 0 {
+            return Ok(value);
+        }
+        // Takes ownership of the module.
+        let value_raw = unsafe { q::JS_EvalFunction(self.context, value.into_inner()) };
+        Ok(OwnedValueRef::new(self, value_raw))
+    }
+
+    /// Evaluate javascript code.
+    pub fn eval<'a>(&'a self, code: &str) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        let _execution = self.start_execution()?;
+        let value = self.eval_raw(code, "script.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        self.resolve_value(value)
+    }
+
+    /// Evaluate javascript code with the given options.
+    pub fn eval_with_options<'a>(
+        &'a self,
+        code: &str,
+        options: &EvalOptions,
+    ) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        let _execution = self.start_execution()?;
+        let _fuel = options
+            .max_instructions
+            .map(|fuel| self.runtime.limit_fuel(fuel));
+        let mut flags = if options.module {
+            q::JS_EVAL_TYPE_MODULE
+        } else {
+            q::JS_EVAL_TYPE_GLOBAL
+        };
+        if options.strict {
+            flags |= q::JS_EVAL_FLAG_STRICT;
+        }
+        #[cfg(not(feature = "ng"))]
+        if options.strip {
+            flags |= q::JS_EVAL_FLAG_STRIP;
+        }
+        if options.backtrace_barrier {
+            flags |= q::JS_EVAL_FLAG_BACKTRACE_BARRIER;
+        }
+        if options.compile_only {
+            flags |= q::JS_EVAL_FLAG_COMPILE_ONLY;
+        }
+        // QuickJS always starts counting at line 1, so the offset is applied
+        // by prepending empty lines.
+        let code = if options.line_offset > 0 {
+            Cow::Owned("\n".repeat(options.line_offset as usize) + code)
+        } else {
+            Cow::Borrowed(code)
+        };
+        #[cfg(not(feature = "ng"))]
+        let value = self.eval_raw(&code, &options.filename, flags)?;
+        #[cfg(feature = "ng")]
+        let value = if options.strip && !options.compile_only {
+            self.eval_stripped(&code, &options.filename, flags)?
+        } else {
+            self.eval_raw(&code, &options.filename, flags)?
+        };
+        if options.compile_only {
+            // The compiled function or module is discarded.
+            if value.is_exception() {
+                let err = self
+                    .get_exception()
+                    .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+                return Err(err);
+            }
+            return Ok(OwnedValueRef::new(self, js_null_value()));
+        }
         self.resolve_value(value)
     }
 
+    /// Evaluate javascript code without source and debug information.
+    ///
+    /// quickjs-ng can only strip them when writing bytecode, so the code is
+    /// compiled, written without them and read back before evaluating it.
+    #[cfg(feature = "ng")]
+    fn eval_stripped<'a>(
+        &'a self,
+        code: &str,
+        filename: &str,
+        flags: u32,
+    ) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        let function = self.eval_raw(code, filename, flags | q::JS_EVAL_FLAG_COMPILE_ONLY)?;
+        if function.is_exception() {
+            return Ok(function);
+        }
+        let bytecode = self.write_bytecode(
+            &function,
+            q::JS_WRITE_OBJ_BYTECODE | q::JS_WRITE_OBJ_STRIP_SOURCE | q::JS_WRITE_OBJ_STRIP_DEBUG,
+        )?;
+        unsafe {
+            let function = q::JS_ReadObject(
+                self.context,
+                bytecode.as_ptr(),
+                bytecode.len() as _,
+                q::JS_READ_OBJ_BYTECODE as i32,
+            );
+            if function.tag == TAG_MODULE && q::JS_ResolveModule(self.context, function) < 0 {
+                let exception = q::JSValue {
+                    u: q::JSValueUnion { int32: 0 },
+                    tag: TAG_EXCEPTION,
+                };
+                return Ok(OwnedValueRef::new(self, exception));
+            }
+            // Takes ownership of the function.
+            let value = if function.tag == TAG_EXCEPTION {
+                function
+            } else {
+                q::JS_EvalFunction(self.context, function)
+            };
+            Ok(OwnedValueRef::new(self, value))
+        }
+    }
+
+    /// Evaluate javascript code without running the event loop.
+    ///
+    /// A returned promise (or thenable) is converted to a
+    /// `JsValue::Promise`.
+    pub fn eval_unresolved(&self, code: &str) -> Result<JsValue, ExecutionError> {
+        let _execution = self.start_execution()?;
+        let value = self.eval_raw(code, "script.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        if value.is_exception() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+            Err(err)
+        } else if value.is_object() {
+            let obj = OwnedObjectRef::new(value)?;
+            if obj.is_promise()? {
+                Ok(deserialize_promise(self.context, &obj.value.value)?)
+            } else {
+                Ok(obj.value.to_value()?)
+            }
+        } else {
+            Ok(value.to_value()?)
+        }
+    }
+
+    /// Evaluate javascript code as an ES module.
+    pub fn eval_module(&self, code: &str, name: &str) -> Result<(), ExecutionError> {
+        let _execution = self.start_execution()?;
+        let value = self.eval_raw(code, name, q::JS_EVAL_TYPE_MODULE)?;
+        self.resolve_value(value)?;
+        Ok(())
+    }
+
+    /// Compile javascript code to bytecode, without executing it.
+    pub fn compile(&self, code: &str, filename: &str) -> Result<Vec<u8>, ExecutionError> {
+        let function = self.eval_raw(
+            code,
+            filename,
+            q::JS_EVAL_TYPE_GLOBAL | q::JS_EVAL_FLAG_COMPILE_ONLY,
+        )?;
+        if function.is_exception() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+            return Err(err);
+        }
+        self.write_bytecode(&function, q::JS_WRITE_OBJ_BYTECODE)
+    }
+
+    /// Serialize a compiled function or module to bytecode.
+    fn write_bytecode(
+        &self,
+        function: &OwnedValueRef<'_>,
+        flags: u32,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        let mut size = 0;
+        let data =
+            unsafe { q::JS_WriteObject(self.context, &mut size, function.value, flags as i32) };
+        if data.is_null() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Internal("Could not write bytecode".into()));
+            return Err(err);
+        }
+        let bytecode = unsafe { std::slice::from_raw_parts(data, size as usize) }.to_vec();
+        unsafe { q::js_free(self.context, data as *mut c_void) };
+        Ok(bytecode)
+    }
+
+    /// Execute bytecode created by [ContextWrapper::compile].
+    ///
+    /// Unsafe because quickjs does not validate the bytecode.
+    pub unsafe fn eval_compiled<'a>(
+        &'a self,
+        bytecode: &[u8],
+    ) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        let _execution = self.start_execution()?;
+        // Discard interrupts of earlier executions that were not reported.
+        self.runtime.take_interrupt();
+
+        let function = q::JS_ReadObject(
+            self.context,
+            bytecode.as_ptr(),
+            bytecode.len() as _,
+            q::JS_READ_OBJ_BYTECODE as i32,
+        );
+        if function.tag == TAG_EXCEPTION {
+            return self.resolve_value(OwnedValueRef::new(self, function));
+        }
+        // The imports of a module are loaded when it is resolved. On failure,
+        // QuickJS frees the unresolved module itself.
+        if function.tag == TAG_MODULE && q::JS_ResolveModule(self.context, function) < 0 {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+            return Err(err);
+        }
+        // Takes ownership of the function.
+        let value = q::JS_EvalFunction(self.context, function);
+        self.resolve_value(OwnedValueRef::new(self, value))
+    }
+
+    /// Copy a value into another context with the QuickJS serializer.
+    ///
+    /// The target context can belong to a different runtime.
+    pub fn transfer<'t>(
+        &self,
+        value: &OwnedValueRef,
+        target: &'t ContextWrapper,
+    ) -> Result<OwnedValueRef<'t>, ExecutionError> {
+        let mut write_flags = q::JS_WRITE_OBJ_REFERENCE;
+        let mut read_flags = q::JS_READ_OBJ_REFERENCE;
+        // SharedArrayBuffers are only shareable with reference counted memory.
+        if cfg!(feature = "atomics") {
+            write_flags |= q::JS_WRITE_OBJ_SAB;
+            read_flags |= q::JS_READ_OBJ_SAB;
+        }
+
+        let mut size = 0;
+        let data =
+            unsafe { q::JS_WriteObject(self.context, &mut size, value.value, write_flags as i32) };
+        if data.is_null() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Internal("Could not serialize value".into()));
+            return Err(err);
+        }
+        let raw = unsafe { q::JS_ReadObject(target.context, data, size, read_flags as i32) };
+        unsafe { q::js_free(self.context, data as *mut c_void) };
+
+        let value = OwnedValueRef::new(target, raw);
+        if value.is_exception() {
+            let err = target
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Internal("Could not deserialize value".into()));
+            return Err(err);
+        }
+        Ok(value)
+    }
+
     /*
     /// Call a constructor function.
     fn call_constructor<'a>(
@@ -1084,6 +3789,7 @@ impl ContextWrapper {
     ) -> Result<OwnedValueRef<'a>, ExecutionError> {
         let mut qargs = args.iter().map(|arg| arg.value).collect::<Vec<_>>();
 
+        let _execution = self.start_execution()?;
         let qres_raw = unsafe {
             q::JS_Call(
                 self.context,
@@ -1100,62 +3806,144 @@ impl ContextWrapper {
     /// Helper for executing a callback closure.
     fn exec_callback<F>(
         context: *mut q::JSContext,
+        this: q::JSValue,
         argc: c_int,
         argv: *mut q::JSValue,
-        callback: &impl Callback<F>,
-    ) -> Result<q::JSValue, ExecutionError> {
+        callback: &F,
+    ) -> Result<q::JSValue, JsError>
+    where
+        F: Fn(q::JSValue, &[q::JSValue]) -> Result<q::JSValue, JsError> + RefUnwindSafe,
+    {
         let result = std::panic::catch_unwind(|| {
-            let arg_slice = unsafe { std::slice::from_raw_parts(argv, argc as usize) };
-
-            let args = arg_slice
-                .iter()
-                .map(|raw| deserialize_value(context, raw))
-                .collect::<Result<Vec<_>, _>>()?;
+            // Accessors are called without an argument array.
+            let arg_slice = if argv.is_null() {
+                &[]
+            } else {
+                unsafe { std::slice::from_raw_parts(argv, argc as usize) }
+            };
 
-            match callback.call(args) {
-                Ok(Ok(result)) => {
-                    let serialized = serialize_value(context, result)?;
-                    Ok(serialized)
-                }
-                // TODO: better error reporting.
-                Ok(Err(e)) => Err(ExecutionError::Exception(JsValue::String(e))),
-                Err(e) => Err(e.into()),
-            }
+            callback(this, arg_slice)
         });
 
         match result {
             Ok(r) => r,
-            Err(_e) => Err(ExecutionError::Internal("Callback panicked!".to_string())),
+            Err(_e) => {
+                let wrapper = unsafe { context_wrapper(context) };
+                Err(wrapper.caught_panic("Callback panicked!").into())
+            }
         }
     }
 
-    /// Add a global JS function that is backed by a Rust function or closure.
+    /// Create a JS function that is backed by a Rust function or closure.
     pub fn create_callback<'a, F>(
         &'a self,
         callback: impl Callback<F> + 'static,
     ) -> Result<q::JSValue, ExecutionError> {
+        self.create_shared_callback(Arc::new(callback))
+    }
+
+    /// Create a JS function that is backed by a shared Rust function or
+    /// closure, which can also back functions in other contexts.
+    pub fn create_shared_callback<F, C>(
+        &self,
+        callback: Arc<C>,
+    ) -> Result<q::JSValue, ExecutionError>
+    where
+        C: Callback<F> + 'static,
+    {
         let argcount = callback.argument_count() as i32;
+        self.create_args_callback_function(argcount, move |args| match callback.call(args) {
+            Ok(result) => result,
+            Err(e) => Err(ExecutionError::from(e).into()),
+        })
+    }
 
+    /// Create a JS function that is backed by a Rust closure, which converts
+    /// the arguments itself.
+    fn create_args_callback_function<F>(
+        &self,
+        argcount: i32,
+        callback: F,
+    ) -> Result<q::JSValue, ExecutionError>
+    where
+        F: Fn(CallbackArgs<'_>) -> Result<JsValue, JsError> + RefUnwindSafe + 'static,
+    {
         let context = self.context;
-        let wrapper = move |argc: c_int, argv: *mut q::JSValue| -> q::JSValue {
-            match Self::exec_callback(context, argc, argv, &callback) {
-                Ok(value) => value,
-                // TODO: better error reporting.
-                Err(e) => {
-                    let js_exception_value = match e {
-                        ExecutionError::Exception(e) => e,
-                        other => other.to_string().into(),
-                    };
-                    let js_exception = serialize_value(context, js_exception_value).unwrap();
-                    unsafe {
-                        q::JS_Throw(context, js_exception);
-                    }
+        self.create_native_function(argcount, move |_, args| {
+            let result = callback(CallbackArgs::new(context, args))?;
+            let serialized = serialize_value(context, result).map_err(ExecutionError::from)?;
+            Ok(serialized)
+        })
+    }
 
-                    q::JSValue {
-                        u: q::JSValueUnion { int32: 0 },
-                        tag: TAG_EXCEPTION,
-                    }
-                }
+    /// Create a JS function that is backed by a Rust closure, which receives
+    /// the context it is called in.
+    pub fn create_context_callback<F>(&self, callback: F) -> Result<q::JSValue, ExecutionError>
+    where
+        F: Fn(Rc<ContextWrapper>, Vec<JsValue>) -> Result<JsValue, ExecutionError>
+            + RefUnwindSafe
+            + 'static,
+    {
+        let context = self.context;
+        self.create_callback_function(0, move |_, args| {
+            callback(unsafe { context_wrapper(context) }, args).map_err(JsError::from)
+        })
+    }
+
+    /// Create a JS function that is backed by a Rust closure, which receives
+    /// the `this` value of the call and the arguments.
+    fn create_callback_function<F>(
+        &self,
+        argcount: i32,
+        callback: F,
+    ) -> Result<q::JSValue, ExecutionError>
+    where
+        F: Fn(q::JSValue, Vec<JsValue>) -> Result<JsValue, JsError> + RefUnwindSafe + 'static,
+    {
+        let context = self.context;
+        self.create_raw_callback_function(argcount, move |this, args| {
+            let result = callback(this, args)?;
+            let serialized = serialize_value(context, result).map_err(ExecutionError::from)?;
+            Ok(serialized)
+        })
+    }
+
+    /// Like [create_callback_function](ContextWrapper::create_callback_function),
+    /// but the closure returns the raw result, which it owns.
+    fn create_raw_callback_function<F>(
+        &self,
+        argcount: i32,
+        callback: F,
+    ) -> Result<q::JSValue, ExecutionError>
+    where
+        F: Fn(q::JSValue, Vec<JsValue>) -> Result<q::JSValue, JsError> + RefUnwindSafe + 'static,
+    {
+        let context = self.context;
+        self.create_native_function(argcount, move |this, args| {
+            let args = args
+                .iter()
+                .map(|raw| deserialize_value(context, raw))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(ExecutionError::from)?;
+            callback(this, args)
+        })
+    }
+
+    /// Create a JS function that is backed by a Rust closure, which receives
+    /// the raw arguments and returns the raw result, which it owns.
+    fn create_native_function<F>(
+        &self,
+        argcount: i32,
+        callback: F,
+    ) -> Result<q::JSValue, ExecutionError>
+    where
+        F: Fn(q::JSValue, &[q::JSValue]) -> Result<q::JSValue, JsError> + RefUnwindSafe + 'static,
+    {
+        let context = self.context;
+        let wrapper = move |this: q::JSValue, argc: c_int, argv: *mut q::JSValue| -> q::JSValue {
+            match Self::exec_callback(context, this, argc, argv, &callback) {
+                Ok(value) => value,
+                Err(error) => throw_error(context, &error),
             }
         };
 
@@ -1172,16 +3960,124 @@ impl ContextWrapper {
         Ok(cfunc)
     }
 
-    pub fn add_callback<'a, F>(
+    pub fn add_callback<F, C>(&self, name: &str, callback: Arc<C>) -> Result<(), ExecutionError>
+    where
+        C: Callback<F> + 'static,
+    {
+        let cfunc = self.create_shared_callback(callback)?;
+        let global = self.global()?;
+        unsafe {
+            global.set_property_raw(name, cfunc)?;
+        }
+        Ok(())
+    }
+
+    /// Add a JS function that is backed by a Rust function or closure at the
+    /// given dot-separated path, like `api.fs.readFile`.
+    ///
+    /// Missing objects along the path are created.
+    pub fn add_callback_at<F, C>(&self, path: &str, callback: Arc<C>) -> Result<(), ExecutionError>
+    where
+        C: Callback<F> + 'static,
+    {
+        if path.split('.').any(str::is_empty) {
+            return Err(ExecutionError::Internal(format!(
+                "Invalid callback path '{}'",
+                path
+            )));
+        }
+        let (namespace, name) = match path.rfind('.') {
+            Some(index) => (Some(&path[..index]), &path[index + 1..]),
+            None => (None, path),
+        };
+        let mut object = self.global()?;
+        for segment in namespace.into_iter().flat_map(|ns| ns.split('.')) {
+            object = self.namespace_object(object, segment)?;
+        }
+
+        let cfunc = self.create_shared_callback(callback)?;
+        unsafe { object.set_property_raw(name, cfunc) }
+    }
+
+    /// Get the object stored in a property of `parent`, creating it if the
+    /// property does not exist.
+    fn namespace_object<'a>(
         &'a self,
+        parent: OwnedObjectRef<'a>,
         name: &str,
-        callback: impl Callback<F> + 'static,
-    ) -> Result<(), ExecutionError> {
-        let cfunc = self.create_callback(callback)?;
+    ) -> Result<OwnedObjectRef<'a>, ExecutionError> {
+        let value = parent.property_or_undefined(name)?;
+        if value.is_undefined() {
+            let object = OwnedValueRef::new(self, unsafe { q::JS_NewObject(self.context) });
+            unsafe {
+                parent.set_property_raw(name, dup_value(object.value))?;
+            }
+            Ok(OwnedObjectRef::new(object)?)
+        } else if value.is_object() {
+            Ok(OwnedObjectRef::new(value)?)
+        } else {
+            Err(ExecutionError::Internal(format!(
+                "Invalid namespace: property '{}' is not an object",
+                name
+            )))
+        }
+    }
+
+    pub fn add_context_callback<F>(&self, name: &str, callback: F) -> Result<(), ExecutionError>
+    where
+        F: Fn(Rc<ContextWrapper>, Vec<JsValue>) -> Result<JsValue, ExecutionError>
+            + RefUnwindSafe
+            + 'static,
+    {
+        let cfunc = self.create_context_callback(callback)?;
         let global = self.global()?;
         unsafe {
             global.set_property_raw(name, cfunc)?;
         }
         Ok(())
     }
+
+    /// Get a property of the global object.
+    ///
+    /// Missing properties are returned as `JsValue::Undefined`.
+    pub fn get_global(&self, name: &str) -> Result<JsValue, ExecutionError> {
+        let global = self.global()?;
+        let value = global.property_or_undefined(name)?.to_value()?;
+        Ok(value)
+    }
+
+    /// Set a property of the global object.
+    pub fn set_global(&self, name: &str, value: JsValue) -> Result<(), ExecutionError> {
+        let global = self.global()?;
+        let value = self.serialize_value(value)?;
+        unsafe { global.set_property_raw(name, value.into_inner()) }
+    }
+
+    /// Define a property of the global object that is backed by a Rust
+    /// getter and setter.
+    pub fn define_global_accessor<G, S>(
+        &self,
+        name: &str,
+        getter: G,
+        setter: S,
+    ) -> Result<(), ExecutionError>
+    where
+        G: Fn() -> JsValue + RefUnwindSafe + 'static,
+        S: Fn(JsValue) + RefUnwindSafe + 'static,
+    {
+        let global = self.global()?;
+        let getter = self.create_callback_function(0, move |_, _| Ok(getter()))?;
+        let setter = self.create_callback_function(1, move |_, args| {
+            setter(args.into_iter().next().unwrap_or(JsValue::Undefined));
+            Ok(JsValue::Null)
+        });
+        let setter = match setter {
+            Ok(setter) => setter,
+            Err(e) => {
+                unsafe { free_value(self.context, getter) };
+                return Err(e);
+            }
+        };
+        unsafe { global.define_accessor_raw(name, getter, setter) }
+    }
 }
@@ -0,0 +1,291 @@
+//! Runtimes backed by a [GlobalAlloc], which also keep [AllocationStats].
+//!
+//! `js_malloc_usable_size` does not get the opaque pointer of the runtime, so
+//! each block starts with a header storing its size.
+
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    cell::Cell,
+    os::raw::c_void,
+    ptr,
+};
+
+use libquickjs_sys as q;
+
+use crate::AllocationStats;
+
+/// The alignment of the blocks, which matches `malloc`.
+const ALIGN: usize = 16;
+/// The size of the header storing the size of a block. Keeps the returned
+/// pointers aligned to `ALIGN`.
+const HEADER_SIZE: usize = ALIGN;
+/// The bookkeeping overhead per block assumed by quickjs.
+#[cfg(not(feature = "ng"))]
+const MALLOC_OVERHEAD: usize = 8;
+
+/// The allocator of a runtime and its statistics. Boxed to get a stable
+/// pointer that can be passed to quickjs as opaque data.
+pub struct AllocatorState {
+    allocator: Box<dyn GlobalAlloc>,
+    current_bytes: Cell<usize>,
+    peak_bytes: Cell<usize>,
+    total_bytes: Cell<u64>,
+    allocation_count: Cell<u64>,
+    /// The peak since the start of the innermost [measure](Self::measure).
+    scope_peak_bytes: Cell<usize>,
+}
+
+impl AllocatorState {
+    pub fn new(allocator: Box<dyn GlobalAlloc>) -> Box<Self> {
+        Box::new(Self {
+            allocator,
+            current_bytes: Cell::new(0),
+            peak_bytes: Cell::new(0),
+            total_bytes: Cell::new(0),
+            allocation_count: Cell::new(0),
+            scope_peak_bytes: Cell::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> AllocationStats {
+        AllocationStats {
+            current_bytes: self.current_bytes.get(),
+            peak_bytes: self.peak_bytes.get(),
+            total_bytes: self.total_bytes.get(),
+            allocation_count: self.allocation_count.get(),
+        }
+    }
+
+    /// Run `f`, and return the statistics of the allocations made while it
+    /// ran. The peak is the highest number of bytes allocated during `f`.
+    pub fn measure<T>(&self, f: impl FnOnce() -> T) -> (T, AllocationStats) {
+        let start = self.stats();
+        let outer_peak = self.scope_peak_bytes.replace(start.current_bytes);
+        let result = f();
+        let peak = self.scope_peak_bytes.get();
+        self.scope_peak_bytes.set(outer_peak.max(peak));
+        let end = self.stats();
+        let stats = AllocationStats {
+            current_bytes: end.current_bytes,
+            peak_bytes: peak,
+            total_bytes: end.total_bytes - start.total_bytes,
+            allocation_count: end.allocation_count - start.allocation_count,
+        };
+        (result, stats)
+    }
+
+    /// Record that a block of `old_size` bytes was replaced by a block of
+    /// `new_size` bytes, where a size of 0 means no block.
+    fn record(&self, old_size: usize, new_size: usize) {
+        let current = self.current_bytes.get() - old_size + new_size;
+        self.current_bytes.set(current);
+        self.peak_bytes.set(self.peak_bytes.get().max(current));
+        self.scope_peak_bytes
+            .set(self.scope_peak_bytes.get().max(current));
+        let allocated = new_size.saturating_sub(old_size) as u64;
+        self.total_bytes.set(self.total_bytes.get() + allocated);
+        if new_size > 0 {
+            self.allocation_count.set(self.allocation_count.get() + 1);
+        }
+    }
+}
+
+fn layout(size: usize) -> Option<Layout> {
+    Layout::from_size_align(size.checked_add(HEADER_SIZE)?, ALIGN).ok()
+}
+
+/// Store the size in the header of `base`, and return the pointer to the
+/// data.
+unsafe fn finish_block(base: *mut u8, size: usize) -> *mut c_void {
+    if base.is_null() {
+        return ptr::null_mut();
+    }
+    (base as *mut usize).write(size);
+    base.add(HEADER_SIZE) as *mut c_void
+}
+
+unsafe fn base_ptr(ptr: *const c_void) -> *mut u8 {
+    (ptr as *mut u8).sub(HEADER_SIZE)
+}
+
+unsafe fn block_size(ptr: *const c_void) -> usize {
+    (base_ptr(ptr) as *const usize).read()
+}
+
+unsafe fn alloc_block(state: &AllocatorState, size: usize, zeroed: bool) -> *mut c_void {
+    let layout = match layout(size) {
+        Some(layout) => layout,
+        None => return ptr::null_mut(),
+    };
+    let base = if zeroed {
+        state.allocator.alloc_zeroed(layout)
+    } else {
+        state.allocator.alloc(layout)
+    };
+    if !base.is_null() {
+        state.record(0, layout.size());
+    }
+    finish_block(base, size)
+}
+
+unsafe fn free_block(state: &AllocatorState, ptr: *mut c_void) {
+    let layout = layout(block_size(ptr)).unwrap();
+    state.allocator.dealloc(base_ptr(ptr), layout);
+    state.record(layout.size(), 0);
+}
+
+unsafe fn realloc_block(state: &AllocatorState, ptr: *mut c_void, size: usize) -> *mut c_void {
+    let new_layout = match layout(size) {
+        Some(layout) => layout,
+        None => return ptr::null_mut(),
+    };
+    let old_layout = layout(block_size(ptr)).unwrap();
+    let base = state
+        .allocator
+        .realloc(base_ptr(ptr), old_layout, new_layout.size());
+    if !base.is_null() {
+        state.record(old_layout.size(), new_layout.size());
+    }
+    finish_block(base, size)
+}
+
+unsafe extern "C" fn usable_size(ptr: *const c_void) -> q::size_t {
+    if ptr.is_null() {
+        0
+    } else {
+        block_size(ptr) as _
+    }
+}
+
+/// The malloc functions of quickjs, which also keep the statistics and
+/// enforce the memory limit, like the default functions of quickjs.
+#[cfg(not(feature = "ng"))]
+mod functions {
+    use super::*;
+
+    unsafe fn allocator<'a>(state: *mut q::JSMallocState) -> &'a AllocatorState {
+        &*((*state).opaque as *const AllocatorState)
+    }
+
+    unsafe extern "C" fn malloc(state: *mut q::JSMallocState, size: q::size_t) -> *mut c_void {
+        let state = &mut *state;
+        let size = size as usize;
+        if state.malloc_size as usize + size > state.malloc_limit as usize {
+            return ptr::null_mut();
+        }
+        let ptr = alloc_block(allocator(state), size, false);
+        if !ptr.is_null() {
+            state.malloc_count += 1;
+            state.malloc_size += (size + MALLOC_OVERHEAD) as q::size_t;
+        }
+        ptr
+    }
+
+    unsafe extern "C" fn free(state: *mut q::JSMallocState, ptr: *mut c_void) {
+        if ptr.is_null() {
+            return;
+        }
+        let state = &mut *state;
+        state.malloc_count -= 1;
+        state.malloc_size -= (block_size(ptr) + MALLOC_OVERHEAD) as q::size_t;
+        free_block(allocator(state), ptr);
+    }
+
+    unsafe extern "C" fn realloc(
+        state: *mut q::JSMallocState,
+        ptr: *mut c_void,
+        size: q::size_t,
+    ) -> *mut c_void {
+        if ptr.is_null() {
+            if size == 0 {
+                return ptr::null_mut();
+            }
+            return malloc(state, size);
+        }
+        if size == 0 {
+            free(state, ptr);
+            return ptr::null_mut();
+        }
+        let state = &mut *state;
+        let size = size as usize;
+        let old_size = block_size(ptr);
+        if state.malloc_size as usize + size - old_size > state.malloc_limit as usize {
+            return ptr::null_mut();
+        }
+        let ptr = realloc_block(allocator(state), ptr, size);
+        if !ptr.is_null() {
+            state.malloc_size = (state.malloc_size as usize + size - old_size) as q::size_t;
+        }
+        ptr
+    }
+
+    pub const MALLOC_FUNCTIONS: q::JSMallocFunctions = q::JSMallocFunctions {
+        js_malloc: Some(malloc),
+        js_free: Some(free),
+        js_realloc: Some(realloc),
+        js_malloc_usable_size: Some(usable_size),
+    };
+}
+
+/// The malloc functions of quickjs-ng, which keeps the statistics and
+/// enforces the memory limit itself.
+#[cfg(feature = "ng")]
+mod functions {
+    use super::*;
+
+    unsafe fn allocator<'a>(opaque: *mut c_void) -> &'a AllocatorState {
+        &*(opaque as *const AllocatorState)
+    }
+
+    unsafe extern "C" fn calloc(
+        opaque: *mut c_void,
+        count: q::size_t,
+        size: q::size_t,
+    ) -> *mut c_void {
+        match (count as usize).checked_mul(size as usize) {
+            Some(size) => alloc_block(allocator(opaque), size, true),
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe extern "C" fn malloc(opaque: *mut c_void, size: q::size_t) -> *mut c_void {
+        alloc_block(allocator(opaque), size as usize, false)
+    }
+
+    unsafe extern "C" fn free(opaque: *mut c_void, ptr: *mut c_void) {
+        if !ptr.is_null() {
+            free_block(allocator(opaque), ptr);
+        }
+    }
+
+    unsafe extern "C" fn realloc(
+        opaque: *mut c_void,
+        ptr: *mut c_void,
+        size: q::size_t,
+    ) -> *mut c_void {
+        if ptr.is_null() {
+            return malloc(opaque, size);
+        }
+        if size == 0 {
+            free(opaque, ptr);
+            return ptr::null_mut();
+        }
+        realloc_block(allocator(opaque), ptr, size as usize)
+    }
+
+    pub const MALLOC_FUNCTIONS: q::JSMallocFunctions = q::JSMallocFunctions {
+        js_calloc: Some(calloc),
+        js_malloc: Some(malloc),
+        js_free: Some(free),
+        js_realloc: Some(realloc),
+        js_malloc_usable_size: Some(usable_size),
+    };
+}
+
+/// Create a runtime that allocates its memory with the allocator of `state`.
+///
+/// The state must outlive the runtime.
+pub fn new_runtime(state: &AllocatorState) -> *mut q::JSRuntime {
+    let opaque = state as *const AllocatorState as *mut c_void;
+    unsafe { q::JS_NewRuntime2(&functions::MALLOC_FUNCTIONS, opaque) }
+}
@@ -0,0 +1,286 @@
+//! Registration of Rust types as Javascript classes.
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    os::raw::c_void,
+    sync::Mutex,
+};
+
+use libquickjs_sys as q;
+use once_cell::sync::Lazy;
+
+use super::{
+    context_wrapper, dup_value, finalizer::FinalizerQueue, make_cstring, ContextWrapper,
+    OwnedObjectRef, OwnedValueRef, PersistentValue, TAG_OBJECT, TAG_UNDEFINED,
+};
+use crate::{
+    callback::{Arguments, JsError},
+    class::{ClassBuilder, Finalizer},
+    ExecutionError, JsValue, OwnedJsValue,
+};
+
+/// Class ids of registered Rust types.
+///
+/// Class ids are global to the process in QuickJS, so every type gets a
+/// single id, which is registered with each runtime the type is used in.
+/// The lock also guards the id allocation, which is not thread safe.
+static CLASS_IDS: Lazy<Mutex<HashMap<TypeId, q::JSClassID>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn class_id<T: 'static>() -> q::JSClassID {
+    let mut ids = CLASS_IDS.lock().unwrap();
+    *ids.entry(TypeId::of::<T>()).or_insert_with(|| {
+        let mut id = 0;
+        unsafe { q::JS_NewClassID(&mut id) }
+    })
+}
+
+/// The finalizers of the classes registered in a context, by the type of
+/// the class. The values are [Finalizer]s of the type.
+pub type ClassFinalizers = Mutex<HashMap<TypeId, Box<dyn Any>>>;
+
+/// The opaque data of a class instance.
+struct Instance<T> {
+    value: RefCell<T>,
+    /// The finalizer of the class, and the queue of the runtime it runs
+    /// from.
+    finalizer: Option<(Finalizer<T>, FinalizerQueue)>,
+}
+
+/// Drops the Rust value owned by a class instance, or passes it to the
+/// finalizer of the class.
+unsafe extern "C" fn finalize<T: 'static>(_runtime: *mut q::JSRuntime, value: q::JSValue) {
+    let opaque = q::JS_GetOpaque(value, class_id::<T>());
+    if opaque.is_null() {
+        return;
+    }
+    let instance = *Box::from_raw(opaque as *mut Instance<T>);
+    if let Some((finalizer, queue)) = instance.finalizer {
+        // The runtime can not be used during garbage collection, so the
+        // finalizer runs as a job.
+        let value = instance.value.into_inner();
+        queue
+            .lock()
+            .unwrap()
+            .push_back(Box::new(move || finalizer(value)));
+    }
+}
+
+/// Get the Rust value of the instance a method is called on.
+///
+/// Unsafe because the returned reference is only valid while `this` is alive.
+pub(super) unsafe fn instance<'a, T: 'static>(this: q::JSValue) -> Result<&'a RefCell<T>, JsError> {
+    let opaque = q::JS_GetOpaque(this, class_id::<T>());
+    if opaque.is_null() {
+        Err(JsError::type_error("Illegal invocation"))
+    } else {
+        Ok(&(*(opaque as *const Instance<T>)).value)
+    }
+}
+
+pub(super) fn borrow_error() -> JsError {
+    JsError::type_error("Instance is already in use")
+}
+
+pub(super) const UNDEFINED: q::JSValue = q::JSValue {
+    u: q::JSValueUnion { int32: 0 },
+    tag: TAG_UNDEFINED,
+};
+
+impl ContextWrapper {
+    /// Define the class of `T` in the runtime, if it is not defined yet, and
+    /// return its class id.
+    ///
+    /// Instances own a value of type `T`, which is dropped when they are
+    /// garbage collected.
+    pub(super) fn define_class<T: 'static>(
+        &self,
+        name: &str,
+    ) -> Result<q::JSClassID, ExecutionError> {
+        let class_id = class_id::<T>();
+        let class_name = make_cstring(name)?;
+
+        unsafe {
+            let runtime = self.runtime.runtime;
+            if q::JS_IsRegisteredClass(runtime, class_id) == 0 {
+                let definition = q::JSClassDef {
+                    class_name: class_name.as_ptr(),
+                    finalizer: Some(finalize::<T>),
+                    gc_mark: None,
+                    call: None,
+                    exotic: std::ptr::null_mut(),
+                };
+                if q::JS_NewClass(runtime, class_id, &definition) < 0 {
+                    return Err(ExecutionError::Internal(format!(
+                        "Could not register class {}",
+                        name
+                    )));
+                }
+            }
+        }
+        Ok(class_id)
+    }
+
+    /// Get the class id of `T`, if the class is registered in this context.
+    fn registered_class_id<T: 'static>(&self) -> Option<q::JSClassID> {
+        let class_id = class_id::<T>();
+        if unsafe { q::JS_IsRegisteredClass(self.runtime.runtime, class_id) } == 0 {
+            return None;
+        }
+        let proto =
+            OwnedValueRef::new(self, unsafe { q::JS_GetClassProto(self.context, class_id) });
+        if proto.is_object() {
+            Some(class_id)
+        } else {
+            None
+        }
+    }
+
+    /// Register a Rust type as a Javascript class, and add its constructor
+    /// to the global object.
+    pub fn register_class<T: 'static>(&self, class: ClassBuilder<T>) -> Result<(), ExecutionError> {
+        if self.registered_class_id::<T>().is_some() {
+            return Err(ExecutionError::Internal(format!(
+                "Class {} is already registered",
+                class.name
+            )));
+        }
+        let class_id = self.define_class::<T>(&class.name)?;
+        if let Some(finalizer) = class.finalizer {
+            self.class_finalizers
+                .lock()
+                .unwrap()
+                .insert(TypeId::of::<T>(), Box::new(finalizer));
+        }
+
+        let proto = OwnedValueRef::new(self, unsafe { q::JS_NewObject(self.context) });
+        let proto = OwnedObjectRef::new(proto)?;
+
+        for (name, method) in class.methods {
+            let function = self.create_callback_function(0, move |this, args| {
+                let instance = unsafe { instance::<T>(this)? };
+                let mut value = instance.try_borrow_mut().map_err(|_| borrow_error())?;
+                method(&mut value, Arguments::new(args))
+            })?;
+            unsafe { proto.define_property_raw(&name, function)? };
+        }
+
+        for property in class.properties {
+            let getter = property.getter;
+            let getter = self.create_callback_function(0, move |this, _args| {
+                let instance = unsafe { instance::<T>(this)? };
+                let value = instance.try_borrow().map_err(|_| borrow_error())?;
+                getter(&value)
+            })?;
+            let setter = match property.setter {
+                Some(setter) => self.create_callback_function(1, move |this, args| {
+                    let instance = unsafe { instance::<T>(this)? };
+                    let mut value = instance.try_borrow_mut().map_err(|_| borrow_error())?;
+                    let arg = args.into_iter().next().unwrap_or(JsValue::Undefined);
+                    setter(&mut value, arg)?;
+                    Ok(JsValue::Null)
+                })?,
+                None => UNDEFINED,
+            };
+            unsafe { proto.define_accessor_raw(&property.name, getter, setter)? };
+        }
+
+        let context = self.context;
+        let name = class.name.clone();
+        let constructor = class.constructor;
+        let function = self.create_callback_function(0, move |new_target, args| {
+            let constructor = constructor
+                .as_ref()
+                .ok_or_else(|| JsError::type_error(format!("{} is not a constructor", name)))?;
+            if new_target.tag != TAG_OBJECT {
+                return Err(JsError::type_error(format!(
+                    "Class constructor {} cannot be invoked without 'new'",
+                    name
+                )));
+            }
+            let value = constructor(Arguments::new(args))?;
+
+            // Use the prototype of `new.target`, to support subclasses.
+            let wrapper = unsafe { context_wrapper(context) };
+            let target = OwnedValueRef::new(&wrapper, unsafe { dup_value(new_target) });
+            let target = OwnedObjectRef::new(target).map_err(ExecutionError::from)?;
+            let target_proto = target.property_or_undefined("prototype")?;
+            let target_proto = if target_proto.is_object() {
+                Some(target_proto.value)
+            } else {
+                None
+            };
+
+            let instance = wrapper.new_instance(class_id, target_proto, value)?;
+            let instance = OwnedJsValue::new(PersistentValue::from_ref(instance));
+            Ok(JsValue::Ref(instance))
+        })?;
+        let function = OwnedObjectRef::new(OwnedValueRef::new(self, function))?;
+
+        unsafe {
+            let name = self.serialize_value(JsValue::String(class.name.clone()))?;
+            function.define_property_raw("name", name.into_inner())?;
+
+            q::JS_SetConstructorBit(self.context, function.value.value, 1);
+            q::JS_SetConstructor(self.context, function.value.value, proto.value.value);
+
+            let global = self.global()?;
+            global.define_property_raw(&class.name, dup_value(function.value.value))?;
+            q::JS_SetClassProto(self.context, class_id, proto.into_value().into_inner());
+        }
+        Ok(())
+    }
+
+    /// Create an instance of the class with the given id, which owns `value`.
+    ///
+    /// If `proto` is `None`, the prototype of the class is used.
+    pub(super) fn new_instance<T: 'static>(
+        &self,
+        class_id: q::JSClassID,
+        proto: Option<q::JSValue>,
+        value: T,
+    ) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let raw = unsafe {
+            match proto {
+                Some(proto) => q::JS_NewObjectProtoClass(self.context, proto, class_id),
+                None => q::JS_NewObjectClass(self.context, class_id as _),
+            }
+        };
+        let instance = OwnedValueRef::new(self, raw);
+        if instance.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+
+        let finalizer = self
+            .class_finalizers
+            .lock()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .and_then(|finalizer| finalizer.downcast_ref::<Finalizer<T>>())
+            .map(|finalizer| (finalizer.clone(), self.runtime.finalizers.clone()));
+        let opaque = Box::into_raw(Box::new(Instance {
+            value: RefCell::new(value),
+            finalizer,
+        }));
+        unsafe { q::JS_SetOpaque(instance.value, opaque as *mut c_void) };
+        Ok(instance)
+    }
+
+    /// Create an instance of a registered class, which owns `value`.
+    pub fn create_instance<T: 'static>(
+        &self,
+        value: T,
+    ) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let class_id = self.registered_class_id::<T>().ok_or_else(|| {
+            ExecutionError::Internal(format!(
+                "Class {} is not registered",
+                std::any::type_name::<T>()
+            ))
+        })?;
+        self.new_instance(class_id, None, value)
+    }
+}
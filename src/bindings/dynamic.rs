@@ -0,0 +1,181 @@
+//! Host objects backed by a [DynamicObject], wrapped in a `Proxy`.
+
+use libquickjs_sys as q;
+
+use super::{
+    class::{borrow_error, instance},
+    dup_value, free_value, js_null_value, ContextWrapper, OwnedValueRef,
+};
+use crate::{DynamicObject, ExecutionError, JsError, JsValue};
+
+/// Creates the function wrapping a host in a `Proxy`, from the methods of
+/// the host.
+const DYNAMIC_OBJECT: &str = r#"
+    (get, set, has, keys, remove) => {
+        const { apply } = Reflect;
+        const ProxyConstructor = Proxy;
+        const lookup = (host, name) => apply(get, host, [name]);
+        return (host) => new ProxyConstructor({}, {
+            get(target, name) {
+                return typeof name === 'string' ? lookup(host, name) : undefined;
+            },
+            set(target, name, value) {
+                if (typeof name !== 'string') {
+                    return false;
+                }
+                apply(set, host, [name, value]);
+                return true;
+            },
+            has(target, name) {
+                return typeof name === 'string' && apply(has, host, [name]);
+            },
+            deleteProperty(target, name) {
+                return typeof name !== 'string' || apply(remove, host, [name]);
+            },
+            ownKeys(target) {
+                return apply(keys, host, []);
+            },
+            getOwnPropertyDescriptor(target, name) {
+                if (typeof name !== 'string' || !apply(has, host, [name])) {
+                    return undefined;
+                }
+                const value = lookup(host, name);
+                return { value, writable: true, enumerable: true, configurable: true };
+            },
+            defineProperty(target, name, descriptor) {
+                if (typeof name !== 'string' || !('value' in descriptor)) {
+                    return false;
+                }
+                apply(set, host, [name, descriptor.value]);
+                return true;
+            },
+        });
+    }
+"#;
+
+/// The Rust value of a host.
+struct Host(Box<dyn DynamicObject>);
+
+/// Check the property name passed to a method of a host.
+fn property_name(name: Option<JsValue>) -> Result<String, JsError> {
+    match name {
+        Some(JsValue::String(name)) => Ok(name),
+        _ => Err(JsError::type_error("Property name must be a string")),
+    }
+}
+
+impl ContextWrapper {
+    /// Get the function wrapping hosts, creating it on first use.
+    fn dynamic_object_factory(&self) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        if let Some(factory) = *self.dynamic_object_factory.lock().unwrap() {
+            return Ok(OwnedValueRef::new(self, unsafe { dup_value(factory) }));
+        }
+
+        let make = self.eval_raw(DYNAMIC_OBJECT, "dynamic_object.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        let get = OwnedValueRef::new(
+            self,
+            self.create_callback_function(1, |this, args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let host = host.try_borrow().map_err(|_| borrow_error())?;
+                let value = host.0.get(&property_name(args.into_iter().next())?);
+                Ok(value.unwrap_or(JsValue::Undefined))
+            })?,
+        );
+        let set = OwnedValueRef::new(
+            self,
+            self.create_callback_function(2, |this, args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let mut host = host.try_borrow_mut().map_err(|_| borrow_error())?;
+                let mut args = args.into_iter();
+                let name = property_name(args.next())?;
+                host.0
+                    .set(&name, args.next().unwrap_or(JsValue::Undefined))?;
+                Ok(JsValue::Null)
+            })?,
+        );
+        let has = OwnedValueRef::new(
+            self,
+            self.create_callback_function(1, |this, args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let host = host.try_borrow().map_err(|_| borrow_error())?;
+                Ok(JsValue::Bool(
+                    host.0.has(&property_name(args.into_iter().next())?),
+                ))
+            })?,
+        );
+        let keys = OwnedValueRef::new(
+            self,
+            self.create_callback_function(0, |this, _args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let host = host.try_borrow().map_err(|_| borrow_error())?;
+                let keys = host.0.keys().into_iter().map(JsValue::String).collect();
+                Ok(JsValue::Array(keys))
+            })?,
+        );
+        let remove = OwnedValueRef::new(
+            self,
+            self.create_callback_function(1, |this, args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let mut host = host.try_borrow_mut().map_err(|_| borrow_error())?;
+                Ok(JsValue::Bool(
+                    host.0.delete(&property_name(args.into_iter().next())?)?,
+                ))
+            })?,
+        );
+
+        let mut args = vec![get.value, set.value, has.value, keys.value, remove.value];
+        let factory = unsafe {
+            q::JS_Call(
+                self.context,
+                make.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let factory = OwnedValueRef::new(self, factory);
+        if factory.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+
+        let previous = self
+            .dynamic_object_factory
+            .lock()
+            .unwrap()
+            .replace(unsafe { dup_value(factory.value) });
+        if let Some(previous) = previous {
+            unsafe { free_value(self.context, previous) };
+        }
+        Ok(factory)
+    }
+
+    /// Create a `Proxy` backed by `object`.
+    pub fn create_dynamic_object(
+        &self,
+        object: Box<dyn DynamicObject>,
+    ) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let factory = self.dynamic_object_factory()?;
+        let class_id = self.define_class::<Host>("DynamicObject")?;
+        let host = self.new_instance(class_id, None, Host(object))?;
+
+        let mut args = vec![host.value];
+        let proxy = unsafe {
+            q::JS_Call(
+                self.context,
+                factory.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let proxy = OwnedValueRef::new(self, proxy);
+        if proxy.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        Ok(proxy)
+    }
+}
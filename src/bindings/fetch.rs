@@ -0,0 +1,251 @@
+//! `fetch`, `Headers` and `Response`, backed by an [HttpHandler].
+
+use std::{collections::HashMap, rc::Rc};
+
+use libquickjs_sys as q;
+
+use super::{js_null_value, ContextWrapper, OwnedValueRef};
+use crate::{
+    fetch::{HttpHandler, Request},
+    ExecutionError, JsValue,
+};
+
+/// Installs the globals.
+///
+/// The host provides `send(method, url, headers, body)`, which returns a
+/// `{ status, statusText, headers, body }` object, and `encode`/`decode` for
+/// converting between strings and UTF-8 bytes. Headers are passed as a flat
+/// `[name, value, ...]` array.
+const FETCH: &str = r#"
+    (send, encode, decode) => {
+        const internal = Symbol('internal');
+        const normalizeName = (name) => {
+            name = String(name).toLowerCase();
+            if (!/^[!#$%&'*+\-.^_`|~0-9a-z]+$/.test(name)) {
+                throw new TypeError(`Invalid header name: ${name}`);
+            }
+            return name;
+        };
+        const toBytes = (body) => {
+            if (body === undefined || body === null) {
+                return null;
+            } else if (body instanceof Uint8Array) {
+                return body;
+            } else if (body instanceof ArrayBuffer) {
+                return new Uint8Array(body);
+            } else if (ArrayBuffer.isView(body)) {
+                return new Uint8Array(body.buffer, body.byteOffset, body.byteLength);
+            }
+            return encode(String(body));
+        };
+
+        class Headers {
+            constructor(init) {
+                this[internal] = new Map();
+                if (init instanceof Headers) {
+                    init.forEach((value, name) => this.append(name, value));
+                } else if (Array.isArray(init)) {
+                    for (const [name, value] of init) {
+                        this.append(name, value);
+                    }
+                } else if (init !== undefined && init !== null) {
+                    for (const name of Object.keys(init)) {
+                        this.append(name, init[name]);
+                    }
+                }
+            }
+            append(name, value) {
+                name = normalizeName(name);
+                const current = this[internal].get(name);
+                value = String(value);
+                this[internal].set(name, current === undefined ? value : `${current}, ${value}`);
+            }
+            delete(name) {
+                this[internal].delete(normalizeName(name));
+            }
+            get(name) {
+                const value = this[internal].get(normalizeName(name));
+                return value === undefined ? null : value;
+            }
+            has(name) {
+                return this[internal].has(normalizeName(name));
+            }
+            set(name, value) {
+                this[internal].set(normalizeName(name), String(value));
+            }
+            forEach(callback, thisArg) {
+                for (const [name, value] of this) {
+                    callback.call(thisArg, value, name, this);
+                }
+            }
+            entries() {
+                return [...this[internal]].sort(([a], [b]) => (a < b ? -1 : a > b ? 1 : 0)).values();
+            }
+            keys() {
+                return [...this.entries()].map(([name]) => name).values();
+            }
+            values() {
+                return [...this.entries()].map(([, value]) => value).values();
+            }
+            [Symbol.iterator]() {
+                return this.entries();
+            }
+        }
+
+        class Response {
+            constructor(body = null, init = {}) {
+                this[internal] = { body: toBytes(body), used: false, url: '' };
+                this.status = init.status === undefined ? 200 : init.status;
+                this.statusText = init.statusText === undefined ? '' : String(init.statusText);
+                this.headers = new Headers(init.headers);
+            }
+            get ok() {
+                return this.status >= 200 && this.status < 300;
+            }
+            get url() {
+                return this[internal].url;
+            }
+            get bodyUsed() {
+                return this[internal].used;
+            }
+            async arrayBuffer() {
+                const bytes = consume(this);
+                return bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength);
+            }
+            async text() {
+                return decode(consume(this));
+            }
+            async json() {
+                return JSON.parse(decode(consume(this)));
+            }
+            clone() {
+                if (this[internal].used) {
+                    throw new TypeError('Body has already been consumed');
+                }
+                const { body, url } = this[internal];
+                const response = new Response(body && body.slice(), this);
+                response[internal].url = url;
+                return response;
+            }
+        }
+        const consume = (response) => {
+            const state = response[internal];
+            if (state.used) {
+                throw new TypeError('Body has already been consumed');
+            }
+            state.used = true;
+            return state.body || new Uint8Array(0);
+        };
+
+        globalThis.Headers = Headers;
+        globalThis.Response = Response;
+        globalThis.fetch = async (input, init = {}) => {
+            const request = typeof input === 'object' && input !== null && 'url' in input ? input : {};
+            const url = 'url' in request ? String(request.url) : String(input);
+            const method = String(init.method || request.method || 'GET').toUpperCase();
+            const headers = new Headers(init.headers || request.headers);
+            const body = init.body === undefined ? null : init.body;
+            if (body !== null && (method === 'GET' || method === 'HEAD')) {
+                throw new TypeError(`Request with ${method} method cannot have a body`);
+            }
+            if (typeof body === 'string' && !headers.has('content-type')) {
+                headers.set('content-type', 'text/plain;charset=UTF-8');
+            }
+
+            const flat = [];
+            headers.forEach((value, name) => flat.push(name, value));
+            let result;
+            try {
+                result = send(method, url, flat, toBytes(body));
+            } catch (e) {
+                // Errors of the handler are thrown as strings.
+                const message = e instanceof Error ? e.message : e;
+                throw new TypeError(`Failed to fetch ${url}: ${message}`);
+            }
+            const response = new Response(result.body, result);
+            response[internal].url = url;
+            return response;
+        };
+    }
+"#;
+
+fn utf8_bytes(value: JsValue) -> Result<Vec<u8>, String> {
+    match value {
+        JsValue::Bytes(bytes) => Ok(bytes),
+        JsValue::String(string) => Ok(string.into_bytes()),
+        _ => Err("Expected a string or bytes".into()),
+    }
+}
+
+fn header_pairs(flat: Vec<JsValue>) -> Result<Vec<(String, String)>, String> {
+    flat.chunks(2)
+        .map(|pair| match pair {
+            [JsValue::String(name), JsValue::String(value)] => Ok((name.clone(), value.clone())),
+            _ => Err("Invalid headers".to_string()),
+        })
+        .collect()
+}
+
+impl ContextWrapper {
+    /// Install `fetch`, `Headers` and `Response`, sending requests with the
+    /// given handler.
+    pub fn set_http_handler(&self, handler: Rc<dyn HttpHandler>) -> Result<(), ExecutionError> {
+        let factory = self.eval_raw(FETCH, "fetch.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        if !factory.is_object() {
+            return Err(ExecutionError::Internal(
+                "Could not compile fetch.js".into(),
+            ));
+        }
+
+        let send = self.create_callback(
+            move |method: String, url: String, headers: JsValue, body: JsValue| {
+                let headers = match headers {
+                    JsValue::Array(flat) => header_pairs(flat)?,
+                    _ => return Err("Invalid headers".to_string()),
+                };
+                let body = match body {
+                    JsValue::Null | JsValue::Undefined => None,
+                    body => Some(utf8_bytes(body)?),
+                };
+                let response = handler.fetch(Request {
+                    method,
+                    url,
+                    headers,
+                    body,
+                })?;
+
+                let headers = response
+                    .headers
+                    .into_iter()
+                    .map(|(name, value)| JsValue::Array(vec![name.into(), value.into()]))
+                    .collect();
+                let mut result = HashMap::new();
+                result.insert("status".to_string(), JsValue::Int(response.status.into()));
+                result.insert("statusText".to_string(), response.status_text.into());
+                result.insert("headers".to_string(), JsValue::Array(headers));
+                result.insert("body".to_string(), JsValue::Bytes(response.body));
+                Ok(JsValue::Object(result))
+            },
+        )?;
+        let send = OwnedValueRef::new(self, send);
+        let encode = self.create_callback(|text: String| JsValue::Bytes(text.into_bytes()))?;
+        let encode = OwnedValueRef::new(self, encode);
+        let decode = self.create_callback(|bytes: JsValue| {
+            utf8_bytes(bytes).map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        })?;
+        let decode = OwnedValueRef::new(self, decode);
+
+        let mut args = vec![send.value, encode.value, decode.value];
+        let result = unsafe {
+            q::JS_Call(
+                self.context,
+                factory.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        self.resolve_value(OwnedValueRef::new(self, result))?;
+        Ok(())
+    }
+}
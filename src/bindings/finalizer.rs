@@ -0,0 +1,109 @@
+//! Rust callbacks that run when Javascript objects are garbage collected.
+//!
+//! A finalizer is owned by a sentinel object, which is only reachable from
+//! a `WeakMap` entry keyed by the target. The sentinel is freed together
+//! with the target, which also works with quickjs versions that do not
+//! implement `FinalizationRegistry`.
+
+use std::{collections::VecDeque, rc::Rc, sync::Mutex};
+
+use libquickjs_sys as q;
+
+use super::{class::instance, js_null_value, ContextWrapper, OwnedValueRef, RuntimeWrapper};
+use crate::ExecutionError;
+
+type FinalizerCallback = Box<dyn FnOnce()>;
+
+/// The finalizers of a runtime whose target was collected, and which wait
+/// to be run as jobs.
+pub type FinalizerQueue = Rc<Mutex<VecDeque<FinalizerCallback>>>;
+
+/// Attaches a sentinel to a target object.
+const FINALIZER_REGISTRY: &str = r#"
+    (() => {
+        const sentinels = new WeakMap();
+        return (target, sentinel) => {
+            const list = sentinels.get(target);
+            if (list) {
+                list.push(sentinel);
+            } else {
+                sentinels.set(target, [sentinel]);
+            }
+        };
+    })()
+"#;
+
+/// The Rust value of a sentinel object.
+struct Sentinel {
+    callback: Option<FinalizerCallback>,
+    queue: FinalizerQueue,
+}
+
+impl Drop for Sentinel {
+    fn drop(&mut self) {
+        // Sentinels are dropped during garbage collection, where the
+        // callback can not safely use the runtime.
+        if let Some(callback) = self.callback.take() {
+            self.queue.lock().unwrap().push_back(callback);
+        }
+    }
+}
+
+impl RuntimeWrapper {
+    /// Take the next finalizer that is ready to run.
+    ///
+    /// The lock is released before the finalizer is run, since running it
+    /// may collect more objects.
+    pub fn take_finalizer(&self) -> Option<FinalizerCallback> {
+        self.finalizers.lock().unwrap().pop_front()
+    }
+
+    /// Check if finalizers are ready to run.
+    pub fn has_pending_finalizers(&self) -> bool {
+        !self.finalizers.lock().unwrap().is_empty()
+    }
+}
+
+impl ContextWrapper {
+    /// Run `callback` once the object `target` is garbage collected.
+    pub fn register_finalizer(
+        &self,
+        target: &OwnedValueRef<'_>,
+        callback: FinalizerCallback,
+    ) -> Result<(), ExecutionError> {
+        let register = self.helper_function(
+            &self.finalizer_registry,
+            FINALIZER_REGISTRY,
+            "finalizer_registry.js",
+        )?;
+
+        let class_id = self.define_class::<Sentinel>("FinalizerSentinel")?;
+        let sentinel = Sentinel {
+            callback: Some(callback),
+            queue: self.runtime.finalizers.clone(),
+        };
+        let sentinel = self.new_instance(class_id, None, sentinel)?;
+
+        let mut args = vec![target.value, sentinel.value];
+        let result = unsafe {
+            q::JS_Call(
+                self.context,
+                register.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let result = OwnedValueRef::new(self, result);
+        if result.is_exception() {
+            // Do not run the callback when the sentinel is dropped.
+            if let Ok(sentinel) = unsafe { instance::<Sentinel>(sentinel.value) } {
+                sentinel.borrow_mut().callback = None;
+            }
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! Javascript iterators backed by Rust iterators, and Javascript iterators
+//! driven from Rust.
+
+use libquickjs_sys as q;
+
+use super::{
+    class::{borrow_error, instance},
+    dup_value, free_value, js_null_value, ContextWrapper, OwnedValueRef,
+};
+use crate::{ExecutionError, JsValue};
+
+/// Creates the function wrapping a host in an iterator, from the methods of
+/// the host.
+///
+/// `next` returns an empty array when the iterator is done, since `undefined`
+/// is a valid item. The iterators inherit from
+/// `%IteratorPrototype%`, so they are iterable themselves.
+const RUST_ITERATOR: &str = r#"
+    (next, close) => {
+        const { apply } = Reflect;
+        const { create, getPrototypeOf } = Object;
+        const IteratorPrototype = getPrototypeOf(getPrototypeOf([][Symbol.iterator]()));
+        return (host) => create(IteratorPrototype, {
+            next: {
+                value() {
+                    const result = apply(next, host, []);
+                    return result.length
+                        ? { value: result[0], done: false }
+                        : { value: undefined, done: true };
+                },
+                writable: true,
+                configurable: true,
+            },
+            return: {
+                value(value) {
+                    apply(close, host, []);
+                    return { value, done: true };
+                },
+                writable: true,
+                configurable: true,
+            },
+        });
+    }
+"#;
+
+/// Opens the iterator of an iterable, and returns a function stepping it.
+///
+/// The function returns an array with the next value, or an empty array
+/// when the iterator is done. When called with `true`, it closes the
+/// iterator instead.
+const ITERATOR_STEPPER: &str = r#"
+    (iterable) => {
+        const { apply } = Reflect;
+        const iterator = iterable[Symbol.iterator]();
+        const next = iterator.next;
+        return (close) => {
+            if (close) {
+                const method = iterator.return;
+                if (method !== undefined && method !== null) {
+                    apply(method, iterator, []);
+                }
+                return [];
+            }
+            const result = apply(next, iterator, []);
+            if (Object(result) !== result) {
+                throw new TypeError('Iterator result ' + String(result) + ' is not an object');
+            }
+            return result.done ? [] : [result.value];
+        };
+    }
+"#;
+
+/// The Rust value of a host. The iterator is dropped once it is done.
+struct Host(Option<Box<dyn Iterator<Item = JsValue>>>);
+
+impl ContextWrapper {
+    /// Get the function wrapping hosts, creating it on first use.
+    fn iterator_factory(&self) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        if let Some(factory) = *self.iterator_factory.lock().unwrap() {
+            return Ok(OwnedValueRef::new(self, unsafe { dup_value(factory) }));
+        }
+
+        let make = self.eval_raw(RUST_ITERATOR, "rust_iterator.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        let next = OwnedValueRef::new(
+            self,
+            self.create_callback_function(0, |this, _args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let mut host = host.try_borrow_mut().map_err(|_| borrow_error())?;
+                let value = host.0.as_mut().and_then(|iter| iter.next());
+                if value.is_none() {
+                    host.0 = None;
+                }
+                Ok(JsValue::Array(value.into_iter().collect()))
+            })?,
+        );
+        let close = OwnedValueRef::new(
+            self,
+            self.create_callback_function(0, |this, _args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let mut host = host.try_borrow_mut().map_err(|_| borrow_error())?;
+                host.0 = None;
+                Ok(JsValue::Null)
+            })?,
+        );
+
+        let mut args = vec![next.value, close.value];
+        let factory = unsafe {
+            q::JS_Call(
+                self.context,
+                make.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let factory = OwnedValueRef::new(self, factory);
+        if factory.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+
+        let previous = self
+            .iterator_factory
+            .lock()
+            .unwrap()
+            .replace(unsafe { dup_value(factory.value) });
+        if let Some(previous) = previous {
+            unsafe { free_value(self.context, previous) };
+        }
+        Ok(factory)
+    }
+
+    /// Create a Javascript iterator yielding the items of `iter`.
+    pub fn create_iterator(
+        &self,
+        iter: Box<dyn Iterator<Item = JsValue>>,
+    ) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let factory = self.iterator_factory()?;
+        let class_id = self.define_class::<Host>("RustIterator")?;
+        let host = self.new_instance(class_id, None, Host(Some(iter)))?;
+
+        let mut args = vec![host.value];
+        let iterator = unsafe {
+            q::JS_Call(
+                self.context,
+                factory.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let iterator = OwnedValueRef::new(self, iterator);
+        if iterator.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        Ok(iterator)
+    }
+
+    /// Open the iterator of `iterable`, and return a function stepping it.
+    pub fn open_iterator<'a>(
+        &'a self,
+        iterable: &OwnedValueRef<'_>,
+    ) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        let open = self.helper_function(
+            &self.iterator_stepper,
+            ITERATOR_STEPPER,
+            "iterator_stepper.js",
+        )?;
+        let iterable = OwnedValueRef::new(self, unsafe { dup_value(iterable.value) });
+        self.call_function(open, vec![iterable])
+    }
+
+    /// Get the next value of an iterator opened with
+    /// [open_iterator](ContextWrapper::open_iterator), or close it.
+    ///
+    /// Returns `None` once the iterator is done.
+    pub fn step_iterator<'a>(
+        &'a self,
+        stepper: OwnedValueRef<'a>,
+        close: bool,
+    ) -> Result<Option<JsValue>, ExecutionError> {
+        let close = self.serialize_value(JsValue::Bool(close))?;
+        match self.call_function(stepper, vec![close])?.to_value()? {
+            JsValue::Array(values) => Ok(values.into_iter().next()),
+            _ => Err(ExecutionError::Internal("Invalid iterator step".into())),
+        }
+    }
+}
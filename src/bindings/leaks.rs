@@ -0,0 +1,98 @@
+//! Diagnostics for values that are never freed, enabled with the
+//! `leak-tracking` feature.
+//!
+//! Handles to Javascript values keep their context alive, so a context is
+//! only freed after its [Context](crate::Context) and all handles were
+//! dropped. Handles that are kept by mistake, eg in a callback of the same
+//! context, are reported with the backtrace of their creation when the
+//! `Context` is dropped. The backtraces are only captured if enabled with
+//! `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`, since capturing one for every
+//! handle is slow. Objects that are still alive when the runtime is
+//! freed are reported as well, since they are leaked by a missing free of the
+//! wrapper code.
+
+use std::{
+    backtrace::Backtrace,
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fmt::Write,
+    rc::Rc,
+};
+
+use libquickjs_sys as q;
+
+use super::{ContextWrapper, RuntimeWrapper};
+
+/// The live handles of a context, with the backtraces of their creation.
+#[derive(Default)]
+pub struct HandleTracker {
+    handles: RefCell<BTreeMap<u64, Backtrace>>,
+    next_id: Cell<u64>,
+}
+
+impl HandleTracker {
+    /// Record a new handle, and return its id.
+    pub fn track(&self) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.handles.borrow_mut().insert(id, Backtrace::capture());
+        id
+    }
+
+    /// Record that the handle with the given id was dropped.
+    pub fn untrack(&self, id: u64) {
+        self.handles.borrow_mut().remove(&id);
+    }
+}
+
+impl ContextWrapper {
+    /// Describe the handles keeping this context alive after its last
+    /// [Context](crate::Context) is dropped.
+    ///
+    /// Returns `None` if the context is freed, or is still used by other
+    /// `Context`s.
+    pub fn leaked_handles(self: &Rc<Self>) -> Option<String> {
+        let handles = self.handles.handles.borrow();
+        // The handles and the dropped `Context` are the only references.
+        if handles.is_empty() || Rc::strong_count(self) != handles.len() + 1 {
+            return None;
+        }
+        let mut report = format!(
+            "quick-js: context dropped while {} handle(s) to its values are alive, \
+             which keep it from being freed",
+            handles.len()
+        );
+        for backtrace in handles.values() {
+            let _ = write!(report, "\n\nhandle created at:\n{}", backtrace);
+        }
+        Some(report)
+    }
+}
+
+impl RuntimeWrapper {
+    /// Describe the objects that are still alive, once all contexts of the
+    /// runtime were freed.
+    ///
+    /// The objects can not be freed anymore, since their reference count was
+    /// not decremented by the code owning them.
+    pub fn leaked_objects(&self) -> Option<String> {
+        let mut usage = std::mem::MaybeUninit::<q::JSMemoryUsage>::uninit();
+        let usage = unsafe {
+            // Pending jobs still own their values.
+            if q::JS_IsJobPending(self.runtime) != 0 {
+                return None;
+            }
+            q::JS_RunGC(self.runtime);
+            q::JS_ComputeMemoryUsage(self.runtime, usage.as_mut_ptr());
+            usage.assume_init()
+        };
+        if usage.obj_count == 0 {
+            return None;
+        }
+        Some(format!(
+            "quick-js: {} object(s) were never freed, eg because of a missing \
+             JS_FreeValue",
+            usage.obj_count
+        ))
+    }
+}
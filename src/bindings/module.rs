@@ -0,0 +1,435 @@
+//! Native modules, whose exports are backed by Rust functions and values,
+//! JSON and binary modules returned by the module loader, and the bytecode
+//! cache for compiled modules.
+
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    os::raw::{c_char, c_int},
+    panic::AssertUnwindSafe,
+    rc::Rc,
+    sync::Mutex,
+};
+
+use libquickjs_sys as q;
+
+use super::{
+    context_wrapper, deserialize_value, free_value, make_cstring, serialize_value, throw_error,
+    throw_reference_error, ContextWrapper, OwnedObjectRef, OwnedValueRef, TAG_MODULE, TAG_OBJECT,
+};
+use crate::{
+    bytecode,
+    module::{source_hash, Export, ImportMeta, ModuleBuilder, ModuleGraph, ModuleLoader},
+    ExecutionError, JsError, JsValue,
+};
+
+/// The native modules registered in a context, or the data modules loaded
+/// in a context, by name.
+pub type NativeModules = Mutex<HashMap<String, Rc<ModuleBuilder>>>;
+
+/// The number of times each module was invalidated in a context, by name.
+pub type ModuleVersions = Mutex<HashMap<String, u32>>;
+
+/// Separates the name of an invalidated module from its version in the name
+/// the module is registered with in QuickJS.
+const VERSION_SEPARATOR: &str = "?v=";
+
+impl ContextWrapper {
+    /// Register a native module, which can be imported under `name`.
+    pub fn register_module(
+        &self,
+        name: &str,
+        module: Rc<ModuleBuilder>,
+    ) -> Result<(), ExecutionError> {
+        let mut modules = self.native_modules.lock().unwrap();
+        if modules.contains_key(name) {
+            return Err(ExecutionError::Internal(format!(
+                "Module {} is already registered",
+                name
+            )));
+        }
+        modules.insert(name.to_string(), module);
+        drop(modules);
+        self.runtime.enable_module_loader();
+        Ok(())
+    }
+
+    /// Import a module like a dynamic `import()` in a script, and return
+    /// its namespace object once it is evaluated.
+    pub fn import_module(&self, specifier: &str) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let _execution = self.start_execution()?;
+        let import = self.eval_raw(
+            "(specifier) => import(specifier)",
+            "import.js",
+            q::JS_EVAL_TYPE_GLOBAL,
+        )?;
+        let import = self.resolve_value(import)?;
+        let specifier = self.serialize_value(JsValue::String(specifier.to_string()))?;
+        self.call_function(import, vec![specifier])
+    }
+
+    /// The modules loaded in this context and their imports.
+    pub fn module_graph(&self) -> ModuleGraph {
+        self.module_graph.lock().unwrap().clone()
+    }
+
+    /// Invalidate a module and the modules importing it, so they are loaded
+    /// again when they are imported the next time.
+    ///
+    /// QuickJS can not unload modules, so the new versions are registered
+    /// under a different name. Returns the names of the invalidated modules.
+    pub fn invalidate_module(&self, name: &str) -> Vec<String> {
+        let mut graph = self.module_graph.lock().unwrap();
+        let mut invalidated = vec![name.to_string()];
+        invalidated.extend(graph.dependents(name).into_iter().map(String::from));
+        let mut versions = self.module_versions.lock().unwrap();
+        for name in &invalidated {
+            graph.remove(name);
+            *versions.entry(name.clone()).or_insert(0) += 1;
+        }
+        invalidated
+    }
+
+    /// The name a module is registered with in QuickJS, with the version
+    /// appended if it was invalidated.
+    pub(super) fn versioned_module_name(&self, name: &str) -> String {
+        match self.module_versions.lock().unwrap().get(name) {
+            Some(version) => format!("{}{}{}", name, VERSION_SEPARATOR, version),
+            None => name.to_string(),
+        }
+    }
+
+    /// The name of a module without the version appended by
+    /// [versioned_module_name](ContextWrapper::versioned_module_name).
+    pub(super) fn unversioned_module_name<'a>(&self, name: &'a str) -> &'a str {
+        let index = match name.rfind(VERSION_SEPARATOR) {
+            Some(index) => index,
+            None => return name,
+        };
+        let version = &name[index + VERSION_SEPARATOR.len()..];
+        let unversioned = &name[..index];
+        let versioned = !version.is_empty()
+            && version.bytes().all(|b| b.is_ascii_digit())
+            && self
+                .module_versions
+                .lock()
+                .unwrap()
+                .contains_key(unversioned);
+        if versioned {
+            unversioned
+        } else {
+            name
+        }
+    }
+
+    fn native_module(&self, name: &str) -> Option<Rc<ModuleBuilder>> {
+        self.native_modules.lock().unwrap().get(name).cloned()
+    }
+
+    /// Create the value of an export in this context.
+    fn export_value(&self, export: &Export) -> Result<q::JSValue, ExecutionError> {
+        match export {
+            Export::Value(value) => {
+                Ok(unsafe { self.serialize_value(value.clone())?.into_inner() })
+            }
+            Export::Function(function, argument_count) => {
+                let function = function.clone();
+                self.create_args_callback_function(*argument_count as i32, move |args| {
+                    function(args)
+                })
+            }
+            Export::Json(json) => Ok(unsafe { self.json_parse(json)?.into_inner() }),
+        }
+    }
+}
+
+/// Declare the native module with the given name, if it is registered in
+/// the context.
+///
+/// Returns `Some(null)` if the module could not be created, with an
+/// exception thrown in the context.
+pub(super) unsafe fn load_native_module(
+    ctx: *mut q::JSContext,
+    module_name: *const c_char,
+    name: &str,
+) -> Option<*mut q::JSModuleDef> {
+    let module = context_wrapper(ctx).native_module(name)?;
+    Some(declare_module(ctx, module_name, name, &module))
+}
+
+/// Declare a module with a single default export, like a JSON module, that
+/// is kept in the context until it is evaluated.
+///
+/// Returns null if the module could not be created, with an exception
+/// thrown in the context.
+pub(super) unsafe fn load_data_module(
+    ctx: *mut q::JSContext,
+    module_name: *const c_char,
+    name: &str,
+    export: Export,
+) -> *mut q::JSModuleDef {
+    let module = Rc::new(ModuleBuilder {
+        exports: vec![("default".to_string(), export)],
+    });
+    let m = declare_module(ctx, module_name, name, &module);
+    if !m.is_null() {
+        let wrapper = context_wrapper(ctx);
+        wrapper
+            .data_modules
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), module);
+    }
+    m
+}
+
+/// Declare a module with the exports of `module`.
+unsafe fn declare_module(
+    ctx: *mut q::JSContext,
+    module_name: *const c_char,
+    name: &str,
+    module: &ModuleBuilder,
+) -> *mut q::JSModuleDef {
+    // The exports are declared now, and set by the init function once the
+    // module is evaluated.
+    let m = q::JS_NewCModule(ctx, module_name, Some(init_native_module));
+    if m.is_null() {
+        return m;
+    }
+    for (export, _) in &module.exports {
+        let export = match make_cstring(export.as_str()) {
+            Ok(export) => export,
+            Err(_) => {
+                throw_reference_error(
+                    ctx,
+                    &format!(
+                        "could not load module '{}': export name contains zero byte",
+                        name
+                    ),
+                );
+                return std::ptr::null_mut();
+            }
+        };
+        if q::JS_AddModuleExport(ctx, m, export.as_ptr()) < 0 {
+            return std::ptr::null_mut();
+        }
+    }
+    m
+}
+
+/// Compile the source code of a module returned by the module loader, or
+/// read it from the bytecode cache of the runtime.
+///
+/// Returns `None` if an exception was thrown.
+pub(super) unsafe fn compile_module(
+    ctx: *mut q::JSContext,
+    module_name: *const c_char,
+    name: &str,
+    code: &str,
+) -> Option<*mut q::JSModuleDef> {
+    let wrapper = context_wrapper(ctx);
+    // Bytecode contains the module name, so modules reloaded under a
+    // versioned name are not cached.
+    let cache = wrapper
+        .runtime
+        .bytecode_cache()
+        .filter(|_| CStr::from_ptr(module_name).to_bytes() == name.as_bytes());
+    let hash = source_hash(code);
+
+    if let Some(cache) = &cache {
+        let cached = std::panic::catch_unwind(|| cache.get(name, hash)).unwrap_or_else(|_| {
+            wrapper.caught_panic("Bytecode cache panicked!");
+            None
+        });
+        // Bytecode of other versions is compiled again, and replaced.
+        if let Some(bytecode) = cached.as_deref().and_then(|b| bytecode::untag(b).ok()) {
+            let value = q::JS_ReadObject(
+                ctx,
+                bytecode.as_ptr(),
+                bytecode.len() as _,
+                q::JS_READ_OBJ_BYTECODE as i32,
+            );
+            if value.tag == TAG_MODULE {
+                // The module is owned by the context.
+                let module = value.u.ptr as *mut q::JSModuleDef;
+                free_value(ctx, value);
+                return Some(module);
+            }
+            // Fall back to compiling the module.
+            free_value(ctx, value);
+            wrapper.get_exception();
+        }
+    }
+
+    let code_c = match make_cstring(code) {
+        Ok(c) => c,
+        Err(_) => {
+            throw_reference_error(
+                ctx,
+                &format!("could not load module '{}': code contains zero byte", name),
+            );
+            return None;
+        }
+    };
+
+    // Compile the module. Evaluation is done by quickjs once all
+    // dependencies are loaded.
+    let value = q::JS_Eval(
+        ctx,
+        code_c.as_ptr(),
+        code.len() as _,
+        module_name,
+        (q::JS_EVAL_TYPE_MODULE | q::JS_EVAL_FLAG_COMPILE_ONLY) as i32,
+    );
+    // An exception was raised while compiling.
+    let value = OwnedValueRef::new(&wrapper, value);
+    if value.value.tag != TAG_MODULE {
+        return None;
+    }
+
+    // Failing to write the bytecode does not fail loading the module.
+    if let Some(cache) = cache {
+        if let Ok(compiled) = wrapper.write_bytecode(&value, q::JS_WRITE_OBJ_BYTECODE) {
+            let compiled = bytecode::tag(&compiled);
+            std::panic::catch_unwind(|| cache.put(name, hash, compiled)).unwrap_or_else(|_| {
+                wrapper.caught_panic("Bytecode cache panicked!");
+            });
+        }
+    }
+
+    // The module is owned by the context, the value is not needed anymore.
+    Some(value.value.u.ptr as *mut q::JSModuleDef)
+}
+
+/// Set the exports of a native module.
+unsafe extern "C" fn init_native_module(ctx: *mut q::JSContext, m: *mut q::JSModuleDef) -> c_int {
+    let wrapper = context_wrapper(ctx);
+    let name = match module_def_name(&wrapper, m) {
+        Ok(name) => name,
+        Err(_) => return -1,
+    };
+    let data_module = || wrapper.data_modules.lock().unwrap().remove(&name);
+    let module = match wrapper.native_module(&name).or_else(data_module) {
+        Some(module) => module,
+        None => {
+            throw_reference_error(ctx, &format!("could not load module '{}'", name));
+            return -1;
+        }
+    };
+    for (export, value) in &module.exports {
+        let value = match wrapper.export_value(value) {
+            Ok(value) => value,
+            Err(e) => {
+                throw_reference_error(ctx, &format!("could not load module '{}': {}", name, e));
+                return -1;
+            }
+        };
+        // Names were checked when the module was declared.
+        let export = make_cstring(export.as_str()).unwrap();
+        // Takes ownership of the value.
+        if q::JS_SetModuleExport(ctx, m, export.as_ptr(), value) < 0 {
+            return -1;
+        }
+    }
+    0
+}
+
+/// Set `import.meta` of a compiled module, with the properties returned by
+/// the loader and `import.meta.resolve`.
+pub(super) unsafe fn set_import_meta(
+    ctx: *mut q::JSContext,
+    m: *mut q::JSModuleDef,
+    loader: Option<&dyn ModuleLoader>,
+) -> Result<(), ExecutionError> {
+    let wrapper = context_wrapper(ctx);
+    let name = module_def_name(&wrapper, m)?;
+
+    let meta = match loader {
+        Some(loader) => std::panic::catch_unwind(|| loader.import_meta(&name))
+            .map_err(|_| wrapper.caught_panic("Module loader panicked!"))?,
+        None => ImportMeta::new(name.as_str()),
+    };
+
+    let object = OwnedValueRef::new(&wrapper, q::JS_GetImportMeta(ctx, m));
+    if object.is_exception() {
+        return Err(wrapper
+            .get_exception()
+            .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+    }
+    let object = OwnedObjectRef::new(object)?;
+    if let Some(url) = meta.url {
+        object.set_property("url", JsValue::String(url))?;
+    }
+    // The function keeps the module name in its data, so it is freed with
+    // `import.meta` instead of being kept by the context.
+    let name = wrapper.serialize_value(JsValue::String(name))?;
+    let mut data = [name.value];
+    let resolve =
+        q::JS_NewCFunctionData(ctx, Some(import_meta_resolve), 1, 0, 1, data.as_mut_ptr());
+    if resolve.tag != TAG_OBJECT {
+        return Err(ExecutionError::Internal(
+            "Could not create import.meta.resolve".into(),
+        ));
+    }
+    object.set_property_raw("resolve", resolve)?;
+    for (key, value) in meta.properties {
+        object.set_property(&key, value)?;
+    }
+    Ok(())
+}
+
+/// `import.meta.resolve`, which resolves a specifier relative to the module
+/// whose name is the data of the function.
+unsafe extern "C" fn import_meta_resolve(
+    ctx: *mut q::JSContext,
+    _this: q::JSValue,
+    argc: c_int,
+    argv: *mut q::JSValue,
+    _magic: c_int,
+    data: *mut q::JSValue,
+) -> q::JSValue {
+    let wrapper = context_wrapper(ctx);
+    let specifier = match argc {
+        0 => None,
+        _ => deserialize_value(ctx, &*argv).ok(),
+    };
+    let specifier = match specifier {
+        Some(JsValue::String(specifier)) => specifier,
+        _ => {
+            let error = JsError::type_error("import.meta.resolve expects a string");
+            return throw_error(ctx, &error);
+        }
+    };
+    let name = match deserialize_value(ctx, &*data) {
+        Ok(JsValue::String(name)) => name,
+        _ => return throw_error(ctx, &JsError::error("Invalid import.meta.resolve")),
+    };
+
+    let resolve = || wrapper.runtime.resolve_module(&name, &specifier);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(resolve)).unwrap_or_else(|_| {
+        wrapper.caught_panic("Module loader panicked!");
+        Err("Module loader panicked!".to_string())
+    });
+    match result {
+        Ok(resolved) => serialize_value(ctx, JsValue::String(resolved))
+            .unwrap_or_else(|e| throw_error(ctx, &ExecutionError::from(e).into())),
+        Err(e) => throw_error(
+            ctx,
+            &JsError::new(
+                "ReferenceError",
+                format!("could not resolve module '{}': {}", specifier, e),
+            ),
+        ),
+    }
+}
+
+/// The name of a module, without the version of invalidated modules.
+unsafe fn module_def_name(
+    wrapper: &ContextWrapper,
+    m: *mut q::JSModuleDef,
+) -> Result<String, ExecutionError> {
+    let atom = q::JS_GetModuleName(wrapper.context, m);
+    let name = OwnedValueRef::new(wrapper, q::JS_AtomToString(wrapper.context, atom)).to_string();
+    q::JS_FreeAtom(wrapper.context, atom);
+    Ok(wrapper.unversioned_module_name(&name?).to_string())
+}
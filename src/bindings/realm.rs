@@ -0,0 +1,88 @@
+//! Restricting the globals and capabilities of a context.
+
+use libquickjs_sys as q;
+
+use super::{ContextWrapper, ERROR_FACTORY};
+use crate::{ExecutionError, JsValue};
+
+/// Replaces `eval` and the constructors of all function kinds with functions
+/// throwing an `EvalError`.
+///
+/// The constructors are reachable through the `constructor` property of
+/// every function, so the prototypes are patched in addition to the global
+/// `Function`.
+const DISABLE_CODE_GENERATION: &str = r#"
+    (() => {
+        const BaseEvalError = EvalError;
+        const { defineProperty, getPrototypeOf } = Object;
+        const deny = (name) => {
+            const denied = function () {
+                throw new BaseEvalError('Code generation from strings is disabled');
+            };
+            defineProperty(denied, 'name', { value: name });
+            return denied;
+        };
+        const block = (prototype) => {
+            const blocked = deny(prototype.constructor.name);
+            defineProperty(blocked, 'prototype', { value: prototype });
+            defineProperty(prototype, 'constructor', { value: blocked });
+            return blocked;
+        };
+        globalThis.Function = block(Function.prototype);
+        block(getPrototypeOf(async function () {}));
+        block(getPrototypeOf(function* () {}));
+        block(getPrototypeOf(async function* () {}));
+        globalThis.eval = deny('eval');
+    })()
+"#;
+
+/// Deletes all global properties that are not in the given list of names.
+const RESTRICT_GLOBALS: &str = r#"
+    (allowed) => {
+        const global = globalThis;
+        for (const name of Object.getOwnPropertyNames(global)) {
+            if (!allowed.includes(name)) {
+                delete global[name];
+            }
+        }
+    }
+"#;
+
+impl ContextWrapper {
+    /// Disable `eval`, the `Function` constructor and the constructors of
+    /// async and generator functions.
+    ///
+    /// Code evaluated from Rust is not affected.
+    pub fn disable_code_generation(&self) -> Result<(), ExecutionError> {
+        // The error factory captures the original `eval`.
+        self.helper_function(&self.error_factory, ERROR_FACTORY, "error.js")?;
+
+        let result = self.eval_raw(DISABLE_CODE_GENERATION, "realm.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        if result.is_exception() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Delete all global properties except the given ones.
+    ///
+    /// Non-configurable properties, like `undefined`, are kept.
+    pub fn restrict_globals(&self, allowed: &[String]) -> Result<(), ExecutionError> {
+        // The error factory uses the global `Error` and `Object`.
+        self.helper_function(&self.error_factory, ERROR_FACTORY, "error.js")?;
+
+        let restrict = self.eval_raw(RESTRICT_GLOBALS, "realm.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        if !restrict.is_object() {
+            return Err(ExecutionError::Internal(
+                "Could not compile realm.js".into(),
+            ));
+        }
+        let allowed = allowed.iter().cloned().map(JsValue::String).collect();
+        let allowed = self.serialize_value(JsValue::Array(allowed))?;
+        self.call_function(restrict, vec![allowed])?;
+        Ok(())
+    }
+}
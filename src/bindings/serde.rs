@@ -0,0 +1,901 @@
+//! Serde support: (de)serialization of Rust values directly from/into JS
+//! values, without going through [JsValue].
+
+use std::{convert::TryFrom, fmt::Display};
+
+use libquickjs_sys as q;
+use serde::{
+    de::{self, DeserializeSeed, IntoDeserializer, Visitor},
+    forward_to_deserialize_any,
+    ser::{self, Serialize},
+};
+
+use super::{
+    deserialize_array_buffer, deserialize_uint8_array, deserialize_value, free_value,
+    is_big_int_tag, js_clear_exception, js_is_instance_of, js_new_string, js_new_uint8_array,
+    make_cstring, serialize_value, ContextWrapper, OwnedValueRef, TAG_BOOL, TAG_EXCEPTION,
+    TAG_FLOAT64, TAG_INT, TAG_NULL, TAG_OBJECT, TAG_STRING, TAG_UNDEFINED,
+};
+use crate::value::{unsafe_integer_error, MAX_SAFE_INTEGER};
+use crate::{IntegerPolicy, JsValue, NonePolicy, ValueError};
+
+impl ser::Error for ValueError {
+    fn custom<T: Display>(msg: T) -> Self {
+        ValueError::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for ValueError {
+    fn custom<T: Display>(msg: T) -> Self {
+        ValueError::Custom(msg.to_string())
+    }
+}
+
+/// Define a property on an object, taking ownership of the value.
+fn define_property(
+    object: &OwnedValueRef<'_>,
+    name: &str,
+    value: OwnedValueRef<'_>,
+) -> Result<(), ValueError> {
+    let cname = make_cstring(name)?;
+    let ret = unsafe {
+        q::JS_DefinePropertyValueStr(
+            object.context.context,
+            object.value,
+            cname.as_ptr(),
+            value.into_inner(),
+            q::JS_PROP_C_W_E as i32,
+        )
+    };
+    if ret < 0 {
+        Err(ValueError::Internal(
+            "Could not add property to object".into(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Serializes Rust values into JS values.
+///
+/// Integers are serialized as JS numbers, integers outside of the safe
+/// integer range are converted according to the [IntegerPolicy], and `None`
+/// according to the [NonePolicy]. Maps and
+/// structs are serialized as objects, sequences and tuples as arrays, and
+/// byte buffers as `Uint8Array`. Enums use the externally tagged
+/// representation.
+#[derive(Clone, Copy)]
+pub struct Serializer<'a> {
+    context: &'a ContextWrapper,
+    integers: IntegerPolicy,
+    none: NonePolicy,
+}
+
+impl<'a> Serializer<'a> {
+    /// Create a serializer using the integer and `None` policies of the
+    /// context.
+    pub fn new(context: &'a ContextWrapper) -> Self {
+        Self {
+            context,
+            integers: context.integer_policy(),
+            none: context.none_policy(),
+        }
+    }
+
+    pub fn with_integer_policy(mut self, integers: IntegerPolicy) -> Self {
+        self.integers = integers;
+        self
+    }
+
+    fn value(&self, value: q::JSValue) -> Result<OwnedValueRef<'a>, ValueError> {
+        if value.tag == TAG_EXCEPTION {
+            Err(ValueError::Internal(
+                "Could not create value in runtime".into(),
+            ))
+        } else {
+            Ok(OwnedValueRef::new(self.context, value))
+        }
+    }
+
+    /// Serialize an integer that does not fit into an `i32`.
+    fn integer(self, v: impl Into<i128>) -> Result<OwnedValueRef<'a>, ValueError> {
+        let value = self.integers.convert(v)?;
+        self.value(serialize_value(self.context.context, value)?)
+    }
+
+    /// Wrap a value into an object with the variant name as the only key.
+    fn variant(
+        &self,
+        variant: &'static str,
+        value: OwnedValueRef<'a>,
+    ) -> Result<OwnedValueRef<'a>, ValueError> {
+        let object = self.value(unsafe { q::JS_NewObject(self.context.context) })?;
+        define_property(&object, variant, value)?;
+        Ok(object)
+    }
+}
+
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Ok = OwnedValueRef<'a>;
+    type Error = ValueError;
+
+    type SerializeSeq = ArraySerializer<'a>;
+    type SerializeTuple = ArraySerializer<'a>;
+    type SerializeTupleStruct = ArraySerializer<'a>;
+    type SerializeTupleVariant = VariantSerializer<'a, ArraySerializer<'a>>;
+    type SerializeMap = ObjectSerializer<'a>;
+    type SerializeStruct = ObjectSerializer<'a>;
+    type SerializeStructVariant = VariantSerializer<'a, ObjectSerializer<'a>>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.value(q::JSValue {
+            u: q::JSValueUnion {
+                int32: if v { 1 } else { 0 },
+            },
+            tag: TAG_BOOL,
+        })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.value(q::JSValue {
+            u: q::JSValueUnion { int32: v },
+            tag: TAG_INT,
+        })
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        match i32::try_from(v) {
+            Ok(v) => self.serialize_i32(v),
+            Err(_) => self.integer(v),
+        }
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        match i32::try_from(v) {
+            Ok(v) => self.serialize_i32(v),
+            Err(_) => self.integer(v),
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        match i32::try_from(v) {
+            Ok(v) => self.serialize_i32(v),
+            Err(_) => self.integer(v),
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        match i128::try_from(v) {
+            Ok(v) => self.serialize_i128(v),
+            Err(_) => match self.integers {
+                IntegerPolicy::Lossy => self.serialize_f64(v as f64),
+                IntegerPolicy::Error => Err(unsafe_integer_error(v)),
+                IntegerPolicy::BigInt => {
+                    self.value(serialize_value(self.context.context, JsValue::from(v))?)
+                }
+                IntegerPolicy::Clamp => self.serialize_f64(MAX_SAFE_INTEGER as f64),
+            },
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.value(q::JSValue {
+            u: q::JSValueUnion { float64: v },
+            tag: TAG_FLOAT64,
+        })
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        let value = js_new_string(self.context.context, v)?;
+        self.value(value)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        let value = js_new_uint8_array(self.context.context, v)?;
+        self.value(value)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.value(serialize_value(self.context.context, self.none.value())?)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.value(q::JSValue {
+            u: q::JSValueUnion { int32: 0 },
+            tag: TAG_NULL,
+        })
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let value = value.serialize(self)?;
+        self.variant(variant, value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        let array = self.value(unsafe { q::JS_NewArray(self.context.context) })?;
+        Ok(ArraySerializer {
+            serializer: self,
+            array,
+            index: 0,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(VariantSerializer {
+            serializer: self,
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        let object = self.value(unsafe { q::JS_NewObject(self.context.context) })?;
+        Ok(ObjectSerializer {
+            serializer: self,
+            object,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(VariantSerializer {
+            serializer: self,
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+/// Serializes sequences and tuples into a JS array.
+pub struct ArraySerializer<'a> {
+    serializer: Serializer<'a>,
+    array: OwnedValueRef<'a>,
+    index: u32,
+}
+
+impl<'a> ser::SerializeSeq for ArraySerializer<'a> {
+    type Ok = OwnedValueRef<'a>;
+    type Error = ValueError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let value = value.serialize(self.serializer)?;
+        let ret = unsafe {
+            q::JS_DefinePropertyValueUint32(
+                self.serializer.context.context,
+                self.array.value,
+                self.index,
+                value.into_inner(),
+                q::JS_PROP_C_W_E as i32,
+            )
+        };
+        if ret < 0 {
+            return Err(ValueError::Internal(
+                "Could not append element to array".into(),
+            ));
+        }
+        self.index += 1;
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.array)
+    }
+}
+
+impl<'a> ser::SerializeTuple for ArraySerializer<'a> {
+    type Ok = OwnedValueRef<'a>;
+    type Error = ValueError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for ArraySerializer<'a> {
+    type Ok = OwnedValueRef<'a>;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Serializes maps and structs into a JS object.
+pub struct ObjectSerializer<'a> {
+    serializer: Serializer<'a>,
+    object: OwnedValueRef<'a>,
+    /// Key of the current map entry, see `SerializeMap::serialize_key`.
+    key: Option<OwnedValueRef<'a>>,
+}
+
+impl<'a> ser::SerializeMap for ObjectSerializer<'a> {
+    type Ok = OwnedValueRef<'a>;
+    type Error = ValueError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(key.serialize(self.serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self.key.take().ok_or_else(|| {
+            ValueError::Internal("serialize_value called before serialize_key".into())
+        })?;
+        let value = value.serialize(self.serializer)?;
+
+        // Non-string keys are converted like JS property keys, eg `1` => "1".
+        let context = self.serializer.context.context;
+        let atom = unsafe { q::JS_ValueToAtom(context, key.value) };
+        if atom == q::JS_ATOM_NULL {
+            js_clear_exception(context);
+            return Err(ValueError::Internal(
+                "Could not convert map key to a property name".into(),
+            ));
+        }
+        let ret = unsafe {
+            let ret = q::JS_DefinePropertyValue(
+                context,
+                self.object.value,
+                atom,
+                value.into_inner(),
+                q::JS_PROP_C_W_E as i32,
+            );
+            q::JS_FreeAtom(context, atom);
+            ret
+        };
+        if ret < 0 {
+            return Err(ValueError::Internal(
+                "Could not add property to object".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.object)
+    }
+}
+
+impl<'a> ser::SerializeStruct for ObjectSerializer<'a> {
+    type Ok = OwnedValueRef<'a>;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        let value = value.serialize(self.serializer)?;
+        define_property(&self.object, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.object)
+    }
+}
+
+/// Serializes tuple and struct variants into an object with the variant name
+/// as the only key.
+pub struct VariantSerializer<'a, S> {
+    serializer: Serializer<'a>,
+    variant: &'static str,
+    inner: S,
+}
+
+impl<'a> ser::SerializeTupleVariant for VariantSerializer<'a, ArraySerializer<'a>> {
+    type Ok = OwnedValueRef<'a>;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        let value = ser::SerializeSeq::end(self.inner)?;
+        self.serializer.variant(self.variant, value)
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for VariantSerializer<'a, ObjectSerializer<'a>> {
+    type Ok = OwnedValueRef<'a>;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        let value = ser::SerializeStruct::end(self.inner)?;
+        self.serializer.variant(self.variant, value)
+    }
+}
+
+/// Convert a JS string value to a Rust string.
+fn to_string(value: &OwnedValueRef<'_>) -> Result<String, ValueError> {
+    match deserialize_value(value.context.context, &value.value)? {
+        JsValue::String(s) => Ok(s),
+        JsValue::Utf16(s) => Err(s.not_unicode_error()),
+        _ => Err(ValueError::UnexpectedType),
+    }
+}
+
+/// Convert a JS Date to an ISO 8601 string, like `JSON.stringify` does.
+fn date_to_iso_string(date: &OwnedValueRef<'_>) -> Result<String, ValueError> {
+    let context = date.context.context;
+    let name = make_cstring("toISOString")?;
+    let function = unsafe { q::JS_GetPropertyStr(context, date.value, name.as_ptr()) };
+    let function = OwnedValueRef::new(date.context, function);
+    let iso_string = OwnedValueRef::new(date.context, unsafe {
+        q::JS_Call(context, function.value, date.value, 0, std::ptr::null_mut())
+    });
+    if iso_string.value.tag != TAG_STRING {
+        js_clear_exception(context);
+        return Err(ValueError::Internal("Invalid Date".into()));
+    }
+    to_string(&iso_string)
+}
+
+/// The enumerable string keys of an object.
+struct PropertyNames {
+    context: *mut q::JSContext,
+    properties: *mut q::JSPropertyEnum,
+    count: u32,
+}
+
+impl PropertyNames {
+    fn new(object: &OwnedValueRef<'_>) -> Result<Self, ValueError> {
+        let context = object.context.context;
+        let mut properties: *mut q::JSPropertyEnum = std::ptr::null_mut();
+        let mut count: u32 = 0;
+
+        let flags = (q::JS_GPN_STRING_MASK | q::JS_GPN_ENUM_ONLY) as i32;
+        let ret = unsafe {
+            q::JS_GetOwnPropertyNames(context, &mut properties, &mut count, object.value, flags)
+        };
+        if ret != 0 {
+            return Err(ValueError::Internal(
+                "Could not get object properties".into(),
+            ));
+        }
+        Ok(Self {
+            context,
+            properties,
+            count,
+        })
+    }
+
+    fn atom(&self, index: u32) -> q::JSAtom {
+        assert!(index < self.count);
+        unsafe { (*self.properties.offset(index as isize)).atom }
+    }
+
+    fn name(&self, index: u32) -> Result<String, ValueError> {
+        let name = unsafe { q::JS_AtomToString(self.context, self.atom(index)) };
+        if name.tag == TAG_EXCEPTION {
+            return Err(ValueError::Internal(
+                "Could not get object property name".into(),
+            ));
+        }
+        let s = match deserialize_value(self.context, &name) {
+            Ok(JsValue::String(s)) => Ok(s),
+            Ok(_) => Err(ValueError::Internal("Could not get property name".into())),
+            Err(e) => Err(e),
+        };
+        unsafe { free_value(self.context, name) };
+        s
+    }
+}
+
+impl Drop for PropertyNames {
+    fn drop(&mut self) {
+        for index in 0..self.count {
+            unsafe { q::JS_FreeAtom(self.context, self.atom(index)) };
+        }
+        unsafe {
+            q::js_free(self.context, self.properties as *mut std::ffi::c_void);
+        }
+    }
+}
+
+/// Get a property of an object by atom.
+fn property<'a>(
+    object: &OwnedValueRef<'a>,
+    atom: q::JSAtom,
+) -> Result<OwnedValueRef<'a>, ValueError> {
+    let context = object.context.context;
+    let value = unsafe { q::JS_GetProperty(context, object.value, atom) };
+    if value.tag == TAG_EXCEPTION {
+        js_clear_exception(context);
+        return Err(ValueError::Internal("Could not get object property".into()));
+    }
+    Ok(OwnedValueRef::new(object.context, value))
+}
+
+/// Deserializes Rust values from JS values.
+///
+/// Objects are deserialized as maps, arrays as sequences, `Uint8Array` and
+/// `ArrayBuffer` as byte buffers and `Date` as an ISO 8601 string.
+/// `undefined` is treated like `null`.
+pub struct Deserializer<'a> {
+    value: OwnedValueRef<'a>,
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(value: OwnedValueRef<'a>) -> Self {
+        Self { value }
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Deserializer<'a> {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let context = self.value.context.context;
+        let value = &self.value.value;
+
+        match value.tag {
+            TAG_INT => visitor.visit_i32(unsafe { value.u.int32 }),
+            TAG_BOOL => visitor.visit_bool(unsafe { value.u.int32 } != 0),
+            TAG_NULL | TAG_UNDEFINED => visitor.visit_unit(),
+            TAG_FLOAT64 => {
+                let v = unsafe { value.u.float64 };
+                // Results of arithmetic are often stored as floats, so
+                // visit integral numbers as integers to allow deserializing
+                // them into integer types.
+                if v.fract() == 0.0
+                    && v.abs() <= MAX_SAFE_INTEGER as f64
+                    && v.to_bits() != (-0f64).to_bits()
+                {
+                    visitor.visit_i64(v as i64)
+                } else {
+                    visitor.visit_f64(v)
+                }
+            }
+            TAG_STRING => visitor.visit_string(to_string(&self.value)?),
+            tag if is_big_int_tag(tag) => match deserialize_value(context, value)? {
+                JsValue::BigInt(int) => {
+                    if let Some(v) = int.as_i64() {
+                        visitor.visit_i64(v)
+                    } else if let Some(v) = int.as_u64() {
+                        visitor.visit_u64(v)
+                    } else if let Some(v) = int.as_i128() {
+                        visitor.visit_i128(v)
+                    } else if let Some(v) = int.as_u128() {
+                        visitor.visit_u128(v)
+                    } else {
+                        Err(ValueError::Custom(format!(
+                            "BigInt value out of range: {}",
+                            int
+                        )))
+                    }
+                }
+                _ => Err(ValueError::UnexpectedType),
+            },
+            TAG_OBJECT => {
+                if unsafe { q::JS_IsArray(context, *value) } > 0 {
+                    visitor.visit_seq(SeqAccess::new(self.value)?)
+                } else if js_is_instance_of(context, value, b"Uint8Array\0") {
+                    match deserialize_uint8_array(context, value)? {
+                        JsValue::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+                        _ => Err(ValueError::UnexpectedType),
+                    }
+                } else if js_is_instance_of(context, value, b"ArrayBuffer\0") {
+                    match deserialize_array_buffer(context, value, 0, None)? {
+                        JsValue::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+                        _ => Err(ValueError::UnexpectedType),
+                    }
+                } else if js_is_instance_of(context, value, b"Date\0") {
+                    visitor.visit_string(date_to_iso_string(&self.value)?)
+                } else {
+                    visitor.visit_map(MapAccess::new(self.value)?)
+                }
+            }
+            _ => Err(ValueError::UnexpectedType),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value.value.tag {
+            TAG_NULL | TAG_UNDEFINED => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value.value.tag {
+            TAG_STRING => {
+                let variant: String = to_string(&self.value)?;
+                visitor.visit_enum(IntoDeserializer::<ValueError>::into_deserializer(variant))
+            }
+            TAG_OBJECT => {
+                let names = PropertyNames::new(&self.value)?;
+                if names.count != 1 {
+                    return Err(ValueError::Custom(
+                        "Expected an object with a single key for an enum variant".into(),
+                    ));
+                }
+                visitor.visit_enum(EnumAccess {
+                    variant: names.name(0)?,
+                    value: property(&self.value, names.atom(0))?,
+                })
+            }
+            _ => Err(ValueError::UnexpectedType),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// Deserializes the elements of a JS array.
+struct SeqAccess<'a> {
+    array: OwnedValueRef<'a>,
+    index: u32,
+    length: u32,
+}
+
+impl<'a> SeqAccess<'a> {
+    fn new(array: OwnedValueRef<'a>) -> Result<Self, ValueError> {
+        let name = make_cstring("length")?;
+        let length = OwnedValueRef::new(array.context, unsafe {
+            q::JS_GetPropertyStr(array.context.context, array.value, name.as_ptr())
+        });
+        let length = match length.value.tag {
+            TAG_INT => unsafe { length.value.u.int32 as u32 },
+            _ => {
+                return Err(ValueError::Internal(
+                    "Could not determine array length".into(),
+                ));
+            }
+        };
+        Ok(Self {
+            array,
+            index: 0,
+            length,
+        })
+    }
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for SeqAccess<'a> {
+    type Error = ValueError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        if self.index >= self.length {
+            return Ok(None);
+        }
+        let context = self.array.context.context;
+        let value = unsafe { q::JS_GetPropertyUint32(context, self.array.value, self.index) };
+        if value.tag == TAG_EXCEPTION {
+            js_clear_exception(context);
+            return Err(ValueError::Internal("Could not get array element".into()));
+        }
+        self.index += 1;
+        let value = OwnedValueRef::new(self.array.context, value);
+        seed.deserialize(Deserializer::new(value)).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.length - self.index) as usize)
+    }
+}
+
+/// Deserializes the enumerable string properties of a JS object.
+struct MapAccess<'a> {
+    object: OwnedValueRef<'a>,
+    names: PropertyNames,
+    index: u32,
+}
+
+impl<'a> MapAccess<'a> {
+    fn new(object: OwnedValueRef<'a>) -> Result<Self, ValueError> {
+        let names = PropertyNames::new(&object)?;
+        Ok(Self {
+            object,
+            names,
+            index: 0,
+        })
+    }
+}
+
+impl<'de, 'a> de::MapAccess<'de> for MapAccess<'a> {
+    type Error = ValueError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        if self.index >= self.names.count {
+            return Ok(None);
+        }
+        let name = self.names.name(self.index)?;
+        seed.deserialize(IntoDeserializer::<ValueError>::into_deserializer(name))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = property(&self.object, self.names.atom(self.index))?;
+        self.index += 1;
+        seed.deserialize(Deserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.names.count - self.index) as usize)
+    }
+}
+
+/// Deserializes an externally tagged enum variant: `{ "variant": value }`.
+struct EnumAccess<'a> {
+    variant: String,
+    value: OwnedValueRef<'a>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumAccess<'a> {
+    type Error = ValueError;
+    type Variant = Deserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(IntoDeserializer::<ValueError>::into_deserializer(
+            self.variant,
+        ))?;
+        Ok((variant, Deserializer::new(self.value)))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for Deserializer<'a> {
+    type Error = ValueError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
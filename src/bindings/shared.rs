@@ -0,0 +1,144 @@
+//! Reference counted memory for SharedArrayBuffers.
+//!
+//! By default QuickJS allocates SharedArrayBuffers with the allocator of the
+//! runtime, so they can not outlive it. With the functions installed here,
+//! the memory is reference counted instead, which allows sharing it with
+//! Rust and with other runtimes, possibly on other threads.
+
+use std::{
+    alloc::{self, Layout},
+    os::raw::c_void,
+    ptr::NonNull,
+    sync::atomic::{fence, AtomicUsize, Ordering},
+};
+
+use libquickjs_sys as q;
+
+use super::{ContextWrapper, OwnedObjectRef, OwnedValueRef, RuntimeWrapper};
+use crate::ExecutionError;
+
+/// Header in front of the data of every shared allocation.
+#[repr(C, align(16))]
+struct Header {
+    ref_count: AtomicUsize,
+    size: usize,
+}
+
+const HEADER_SIZE: usize = std::mem::size_of::<Header>();
+
+fn layout(size: usize) -> Layout {
+    Layout::from_size_align(HEADER_SIZE + size, std::mem::align_of::<Header>())
+        .expect("Invalid SharedArrayBuffer size")
+}
+
+/// Allocate zeroed shared memory with a reference count of one.
+///
+/// Returns a pointer to the data, or `None` if the allocation failed.
+pub fn alloc(size: usize) -> Option<NonNull<u8>> {
+    // Zero sized allocations are not allowed, and QuickJS never asks for one.
+    let size = size.max(1);
+    unsafe {
+        let header = alloc::alloc_zeroed(layout(size)) as *mut Header;
+        if header.is_null() {
+            return None;
+        }
+        header.write(Header {
+            ref_count: AtomicUsize::new(1),
+            size,
+        });
+        NonNull::new((header as *mut u8).add(HEADER_SIZE))
+    }
+}
+
+unsafe fn header<'a>(data: *mut u8) -> &'a Header {
+    &*(data.sub(HEADER_SIZE) as *const Header)
+}
+
+/// Add a reference to shared memory.
+///
+/// Unsafe because `data` must have been returned by [alloc] and not be freed.
+pub unsafe fn dup(data: *mut u8) {
+    header(data).ref_count.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Release a reference to shared memory, and free it if it was the last one.
+///
+/// Unsafe because `data` must have been returned by [alloc] and not be freed.
+pub unsafe fn free(data: *mut u8) {
+    let header = header(data);
+    if header.ref_count.fetch_sub(1, Ordering::Release) == 1 {
+        fence(Ordering::Acquire);
+        let size = header.size;
+        alloc::dealloc(data.sub(HEADER_SIZE), layout(size));
+    }
+}
+
+unsafe extern "C" fn sab_alloc(_opaque: *mut c_void, size: q::size_t) -> *mut c_void {
+    alloc(size as usize).map_or(std::ptr::null_mut(), |data| data.as_ptr() as *mut c_void)
+}
+
+unsafe extern "C" fn sab_free(_opaque: *mut c_void, data: *mut c_void) {
+    free(data as *mut u8);
+}
+
+unsafe extern "C" fn sab_dup(_opaque: *mut c_void, data: *mut c_void) {
+    dup(data as *mut u8);
+}
+
+impl RuntimeWrapper {
+    /// Allocate SharedArrayBuffers with reference counted memory, and allow
+    /// `Atomics.wait` to block.
+    pub(super) fn enable_atomics(&self) {
+        let functions = q::JSSharedArrayBufferFunctions {
+            sab_alloc: Some(sab_alloc),
+            sab_free: Some(sab_free),
+            sab_dup: Some(sab_dup),
+            sab_opaque: std::ptr::null_mut(),
+        };
+        unsafe {
+            q::JS_SetSharedArrayBufferFunctions(self.runtime, &functions);
+            q::JS_SetCanBlock(self.runtime, 1);
+        }
+    }
+}
+
+impl ContextWrapper {
+    /// Create a SharedArrayBuffer for shared memory returned by [alloc].
+    ///
+    /// Unsafe because `data` must be valid for `size` bytes. The buffer adds
+    /// its own reference to the memory.
+    pub unsafe fn new_shared_array_buffer(
+        &self,
+        data: *mut u8,
+        size: usize,
+    ) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let raw =
+            q::JS_NewArrayBuffer(self.context, data, size as _, None, std::ptr::null_mut(), 1);
+        let value = OwnedValueRef::new(self, raw);
+        if value.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        Ok(value)
+    }
+
+    /// Get the memory of a SharedArrayBuffer, with an added reference.
+    ///
+    /// Returns `None` if the value is not a SharedArrayBuffer.
+    pub fn shared_array_buffer_data(&self, value: OwnedValueRef) -> Option<(*mut u8, usize)> {
+        let object = OwnedObjectRef::new(value).ok()?;
+        let constructor = self.global().ok()?.property("SharedArrayBuffer").ok()?;
+        if !object.is_instance_of(&constructor).ok()? {
+            return None;
+        }
+
+        let mut size = 0;
+        let data = unsafe { q::JS_GetArrayBuffer(self.context, &mut size, object.value.value) };
+        if data.is_null() {
+            return None;
+        }
+        unsafe { dup(data) };
+        Some((data, size as usize))
+    }
+}
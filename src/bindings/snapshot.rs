@@ -0,0 +1,39 @@
+//! Recording scripts for a [Snapshot](crate::snapshot::Snapshot).
+
+use libquickjs_sys as q;
+
+use super::{ContextWrapper, OwnedValueRef};
+use crate::ExecutionError;
+
+impl ContextWrapper {
+    /// Compile and execute a script or module, and return its bytecode.
+    ///
+    /// The compiled function is executed itself, so a module is not
+    /// registered twice.
+    pub fn eval_recorded(
+        &self,
+        code: &str,
+        filename: &str,
+        module: bool,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        let _execution = self.start_execution()?;
+        let flags = if module {
+            q::JS_EVAL_TYPE_MODULE
+        } else {
+            q::JS_EVAL_TYPE_GLOBAL
+        };
+        let function = self.eval_raw(code, filename, flags | q::JS_EVAL_FLAG_COMPILE_ONLY)?;
+        if function.is_exception() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+            return Err(err);
+        }
+        let bytecode = self.write_bytecode(&function, q::JS_WRITE_OBJ_BYTECODE)?;
+
+        // Takes ownership of the function.
+        let value = unsafe { q::JS_EvalFunction(self.context, function.into_inner()) };
+        self.resolve_value(OwnedValueRef::new(self, value))?;
+        Ok(bytecode)
+    }
+}
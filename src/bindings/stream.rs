@@ -0,0 +1,284 @@
+//! Javascript async iterators backed by Rust streams, and Javascript async
+//! iterators driven from Rust.
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context as TaskContext, Poll},
+};
+
+use futures_core::Stream;
+use libquickjs_sys as q;
+
+use super::{
+    class::{borrow_error, instance},
+    context_wrapper, deserialize_promise, dup_value, free_value, js_null_value, ContextWrapper,
+    OwnedValueRef,
+};
+use crate::{ExecutionError, JsError, JsPromise, JsValue};
+
+/// Creates the function wrapping a host in an async iterator, from the
+/// methods of the host.
+///
+/// `next` returns a promise for an array with the next item, or an empty
+/// array when the stream is done. Requests are queued, like the requests of
+/// async generators, so the stream is never polled for two items at once.
+const RUST_ASYNC_ITERATOR: &str = r#"
+    (next, close) => {
+        const { apply } = Reflect;
+        const { create, getPrototypeOf } = Object;
+        const AsyncIteratorPrototype = getPrototypeOf(
+            getPrototypeOf((async function* () {}).prototype),
+        );
+        const resolved = Promise.resolve();
+        return (host) => {
+            let queue = resolved;
+            return create(AsyncIteratorPrototype, {
+                next: {
+                    value() {
+                        const step = queue.then(() => apply(next, host, []));
+                        queue = step.then(() => {}, () => {});
+                        return step.then((result) => result.length
+                            ? { value: result[0], done: false }
+                            : { value: undefined, done: true });
+                    },
+                    writable: true,
+                    configurable: true,
+                },
+                return: {
+                    value(value) {
+                        apply(close, host, []);
+                        return resolved.then(() => ({ value, done: true }));
+                    },
+                    writable: true,
+                    configurable: true,
+                },
+            });
+        };
+    }
+"#;
+
+/// Opens the async iterator of an iterable, and returns an async function
+/// stepping it.
+///
+/// Iterables without `Symbol.asyncIterator` are iterated with their
+/// iterator, and their values are awaited, like with `for await...of`.
+/// The function resolves to an array with the next value, or an empty array
+/// when the iterator is done. When called with `true`, it closes the
+/// iterator instead.
+const ASYNC_ITERATOR_STEPPER: &str = r#"
+    (iterable) => {
+        const { apply } = Reflect;
+        const method = iterable[Symbol.asyncIterator];
+        const isAsync = method !== undefined && method !== null;
+        const iterator = isAsync ? apply(method, iterable, []) : iterable[Symbol.iterator]();
+        const next = iterator.next;
+        return async (close) => {
+            if (close) {
+                const method = iterator.return;
+                if (method !== undefined && method !== null) {
+                    await apply(method, iterator, []);
+                }
+                return [];
+            }
+            const result = await apply(next, iterator, []);
+            if (Object(result) !== result) {
+                throw new TypeError('Iterator result ' + String(result) + ' is not an object');
+            }
+            if (result.done) {
+                return [];
+            }
+            return [isAsync ? result.value : await result.value];
+        };
+    }
+"#;
+
+type SharedStream = Rc<RefCell<Option<Pin<Box<dyn Stream<Item = JsValue>>>>>>;
+
+/// The Rust value of a host. The stream is dropped once it is done.
+struct Host(SharedStream);
+
+/// Converts the items of a stream.
+struct Items<S>(Pin<Box<S>>);
+
+impl<S: Stream> Stream for Items<S>
+where
+    S::Item: Into<JsValue>,
+{
+    type Item = JsValue;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<JsValue>> {
+        self.0
+            .as_mut()
+            .poll_next(cx)
+            .map(|item| item.map(Into::into))
+    }
+}
+
+/// Resolves to an array with the next item of a stream, or an empty array
+/// when the stream is done.
+struct NextItem(SharedStream);
+
+impl Future for NextItem {
+    type Output = Result<JsValue, JsError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        let mut stream = self.0.borrow_mut();
+        let item = match stream.as_mut() {
+            Some(stream) => match stream.as_mut().poll_next(cx) {
+                Poll::Ready(item) => item,
+                Poll::Pending => return Poll::Pending,
+            },
+            None => None,
+        };
+        if item.is_none() {
+            *stream = None;
+        }
+        Poll::Ready(Ok(JsValue::Array(item.into_iter().collect())))
+    }
+}
+
+impl ContextWrapper {
+    /// Get the function wrapping hosts, creating it on first use.
+    fn async_iterator_factory(&self) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        if let Some(factory) = *self.async_iterator_factory.lock().unwrap() {
+            return Ok(OwnedValueRef::new(self, unsafe { dup_value(factory) }));
+        }
+
+        let make = self.eval_raw(
+            RUST_ASYNC_ITERATOR,
+            "rust_async_iterator.js",
+            q::JS_EVAL_TYPE_GLOBAL,
+        )?;
+        let context = self.context;
+        let next = OwnedValueRef::new(
+            self,
+            self.create_raw_callback_function(0, move |this, _args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let stream = host.try_borrow().map_err(|_| borrow_error())?.0.clone();
+                let wrapper = unsafe { context_wrapper(context) };
+                let promise = wrapper.spawn_task(Box::pin(NextItem(stream)))?;
+                Ok(unsafe { promise.into_inner() })
+            })?,
+        );
+        let close = OwnedValueRef::new(
+            self,
+            self.create_callback_function(0, |this, _args| {
+                let host = unsafe { instance::<Host>(this)? };
+                let host = host.try_borrow().map_err(|_| borrow_error())?;
+                // A pending item is still polled, and resolves as done.
+                host.0.try_borrow_mut().map_err(|_| borrow_error())?.take();
+                Ok(JsValue::Null)
+            })?,
+        );
+
+        let mut args = vec![next.value, close.value];
+        let factory = unsafe {
+            q::JS_Call(
+                self.context,
+                make.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let factory = OwnedValueRef::new(self, factory);
+        if factory.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+
+        let previous = self
+            .async_iterator_factory
+            .lock()
+            .unwrap()
+            .replace(unsafe { dup_value(factory.value) });
+        if let Some(previous) = previous {
+            unsafe { free_value(self.context, previous) };
+        }
+        Ok(factory)
+    }
+
+    /// Create a Javascript async iterator yielding the items of `stream`.
+    pub fn create_async_iterator<S>(&self, stream: S) -> Result<OwnedValueRef<'_>, ExecutionError>
+    where
+        S: Stream + 'static,
+        S::Item: Into<JsValue>,
+    {
+        let factory = self.async_iterator_factory()?;
+        let class_id = self.define_class::<Host>("RustAsyncIterator")?;
+        let stream: Pin<Box<dyn Stream<Item = JsValue>>> = Box::pin(Items(Box::pin(stream)));
+        let host = Host(Rc::new(RefCell::new(Some(stream))));
+        let host = self.new_instance(class_id, None, host)?;
+
+        let mut args = vec![host.value];
+        let iterator = unsafe {
+            q::JS_Call(
+                self.context,
+                factory.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let iterator = OwnedValueRef::new(self, iterator);
+        if iterator.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        Ok(iterator)
+    }
+
+    /// Open the async iterator of `iterable`, and return a function stepping
+    /// it.
+    pub fn open_async_iterator<'a>(
+        &'a self,
+        iterable: &OwnedValueRef<'_>,
+    ) -> Result<OwnedValueRef<'a>, ExecutionError> {
+        let open = self.helper_function(
+            &self.async_iterator_stepper,
+            ASYNC_ITERATOR_STEPPER,
+            "async_iterator_stepper.js",
+        )?;
+        let iterable = OwnedValueRef::new(self, unsafe { dup_value(iterable.value) });
+        self.call_function(open, vec![iterable])
+    }
+
+    /// Request the next value of an async iterator opened with
+    /// [open_async_iterator](ContextWrapper::open_async_iterator), or close
+    /// it.
+    ///
+    /// The promise resolves to an array with the value, or an empty array
+    /// once the iterator is done.
+    pub fn step_async_iterator(
+        &self,
+        stepper: OwnedValueRef<'_>,
+        close: bool,
+    ) -> Result<JsPromise, ExecutionError> {
+        let close = self.serialize_value(JsValue::Bool(close))?;
+        let mut args = vec![close.value];
+        let step = unsafe {
+            q::JS_Call(
+                self.context,
+                stepper.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let step = OwnedValueRef::new(self, step);
+        if step.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        match deserialize_promise(self.context, &step.value)? {
+            JsValue::Promise(promise) => Ok(promise),
+            _ => Err(ExecutionError::Internal("Invalid iterator step".into())),
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Rust futures that settle Javascript promises.
+//!
+//! Tasks are polled as jobs by [ContextWrapper::execute_pending_job], once
+//! they were woken. While the event loop waits for a task, the thread is
+//! parked, or the waker of the polled [JsPromise](crate::JsPromise) is woken
+//! together with the task.
+
+use std::{
+    cell::Cell,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex},
+    task::{Context as TaskContext, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+use libquickjs_sys as q;
+
+use super::{context_wrapper, free_value, js_null_value, ContextWrapper, OwnedValueRef, TimerTime};
+use crate::{
+    callback::{AsyncCallback, CallbackArgs, CallbackFuture},
+    ExecutionError, JsError, JsValue,
+};
+
+struct Task {
+    id: u64,
+    future: CallbackFuture,
+    /// The context of the promise, which frees the pending tasks when it is
+    /// dropped.
+    context: *mut q::JSContext,
+    /// The resolving functions of the promise.
+    resolve: q::JSValue,
+    reject: q::JSValue,
+}
+
+/// The tasks that were woken, and the wakers of the hosts waiting for them.
+#[derive(Default)]
+struct Wakeups {
+    woken: Mutex<Vec<u64>>,
+    hosts: Mutex<Vec<Waker>>,
+}
+
+impl Wakeups {
+    fn wake(&self, id: u64) {
+        self.woken.lock().unwrap().push(id);
+        for waker in self.hosts.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+struct TaskWaker {
+    id: u64,
+    wakeups: Arc<Wakeups>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wakeups.wake(self.id);
+    }
+}
+
+/// Unparks the thread blocked in [RuntimeWrapper::park_until_woken].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// The pending tasks of a runtime.
+#[derive(Default)]
+pub struct TaskQueue {
+    tasks: Mutex<Vec<Task>>,
+    next_id: Cell<u64>,
+    wakeups: Arc<Wakeups>,
+}
+
+impl TaskQueue {
+    /// Take the next woken task that is still pending.
+    fn take_woken(&self) -> Option<Task> {
+        loop {
+            let id = {
+                let mut woken = self.wakeups.woken.lock().unwrap();
+                if woken.is_empty() {
+                    return None;
+                }
+                woken.remove(0)
+            };
+            let mut tasks = self.tasks.lock().unwrap();
+            if let Some(index) = tasks.iter().position(|task| task.id == id) {
+                return Some(tasks.remove(index));
+            }
+        }
+    }
+}
+
+impl super::RuntimeWrapper {
+    /// Check if tasks were woken, and wait to be polled.
+    pub fn has_woken_tasks(&self) -> bool {
+        !self.tasks.wakeups.woken.lock().unwrap().is_empty()
+    }
+
+    /// Check if there are tasks whose future is not complete yet.
+    pub fn has_pending_tasks(&self) -> bool {
+        !self.tasks.tasks.lock().unwrap().is_empty()
+    }
+
+    /// Wake `waker` once a task is woken.
+    pub fn wake_on_task(&self, waker: &Waker) {
+        // The lock is held while checking for woken tasks, so a task can not
+        // be woken in between.
+        let mut hosts = self.tasks.wakeups.hosts.lock().unwrap();
+        if self.has_woken_tasks() {
+            waker.wake_by_ref();
+        } else if !hosts.iter().any(|host| host.will_wake(waker)) {
+            hosts.push(waker.clone());
+        }
+    }
+
+    /// Block the thread until a task is woken, or `timeout` elapsed.
+    fn park_until_woken(&self, timeout: Option<Duration>) {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        self.wake_on_task(&waker);
+        // Wakeups between the check and parking are not lost, since they
+        // make `park` return immediately.
+        if !self.has_woken_tasks() {
+            match timeout {
+                Some(timeout) => thread::park_timeout(timeout),
+                None => thread::park(),
+            }
+        }
+        let mut hosts = self.tasks.wakeups.hosts.lock().unwrap();
+        hosts.retain(|host| !host.will_wake(&waker));
+    }
+}
+
+impl ContextWrapper {
+    /// Create a promise that is settled with the output of `future`.
+    ///
+    /// The future is first polled by the next job.
+    pub fn spawn_task(&self, future: CallbackFuture) -> Result<OwnedValueRef<'_>, ExecutionError> {
+        let mut resolving = [js_null_value(), js_null_value()];
+        let promise = unsafe { q::JS_NewPromiseCapability(self.context, resolving.as_mut_ptr()) };
+        let promise = OwnedValueRef::new(self, promise);
+        if promise.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+
+        let tasks = &self.runtime.tasks;
+        let id = tasks.next_id.get();
+        tasks.next_id.set(id + 1);
+        tasks.tasks.lock().unwrap().push(Task {
+            id,
+            future,
+            context: self.context,
+            resolve: resolving[0],
+            reject: resolving[1],
+        });
+        tasks.wakeups.wake(id);
+        Ok(promise)
+    }
+
+    /// Create a JS function that is backed by a Rust function or closure
+    /// returning a future, and returns a promise settled by the future.
+    ///
+    /// Invalid arguments reject the promise, like in async functions.
+    pub fn create_async_callback<F, C>(
+        &self,
+        callback: Arc<C>,
+    ) -> Result<q::JSValue, ExecutionError>
+    where
+        C: AsyncCallback<F> + 'static,
+    {
+        let argcount = callback.argument_count() as i32;
+        let context = self.context;
+        self.create_native_function(argcount, move |_, args| {
+            let future = match callback.call(CallbackArgs::new(context, args)) {
+                Ok(Ok(future)) => future,
+                Ok(Err(e)) => Box::pin(std::future::ready(Err(e))),
+                Err(e) => Box::pin(std::future::ready(Err(ExecutionError::from(e).into()))),
+            };
+            let wrapper = unsafe { context_wrapper(context) };
+            let promise = wrapper.spawn_task(future)?;
+            Ok(unsafe { promise.into_inner() })
+        })
+    }
+
+    /// Add a global JS function that is backed by a Rust function or closure
+    /// returning a future.
+    pub fn add_async_callback<F, C>(
+        &self,
+        name: &str,
+        callback: Arc<C>,
+    ) -> Result<(), ExecutionError>
+    where
+        C: AsyncCallback<F> + 'static,
+    {
+        let cfunc = self.create_async_callback(callback)?;
+        let global = self.global()?;
+        unsafe { global.set_property_raw(name, cfunc) }
+    }
+
+    /// Poll the next woken task of the runtime.
+    ///
+    /// Returns `false` if no task was woken.
+    pub fn poll_task(&self) -> Result<bool, ExecutionError> {
+        let tasks = &self.runtime.tasks;
+        let mut task = match tasks.take_woken() {
+            Some(task) => task,
+            None => return Ok(false),
+        };
+
+        let waker = Waker::from(Arc::new(TaskWaker {
+            id: task.id,
+            wakeups: tasks.wakeups.clone(),
+        }));
+        let mut cx = TaskContext::from_waker(&waker);
+        // The task may belong to any context in the runtime.
+        let context = unsafe { context_wrapper(task.context) };
+        let poll =
+            std::panic::catch_unwind(AssertUnwindSafe(|| task.future.as_mut().poll(&mut cx)))
+                .unwrap_or_else(|_| {
+                    Poll::Ready(Err(context.caught_panic("Future panicked!").into()))
+                });
+        match poll {
+            Poll::Pending => {
+                tasks.tasks.lock().unwrap().push(task);
+                Ok(true)
+            }
+            Poll::Ready(result) => {
+                let settled = context.settle_task(&task, result);
+                unsafe {
+                    free_value(task.context, task.resolve);
+                    free_value(task.context, task.reject);
+                }
+                settled.map(|_| true)
+            }
+        }
+    }
+
+    /// Resolve or reject the promise of a completed task.
+    fn settle_task(
+        &self,
+        task: &Task,
+        result: Result<JsValue, JsError>,
+    ) -> Result<(), ExecutionError> {
+        let settled = match result {
+            Ok(value) => self
+                .serialize_value(value)
+                .map(|value| (task.resolve, value)),
+            Err(error) => self.create_error(&error).map(|error| (task.reject, error)),
+        };
+        let (function, value) = match settled {
+            Ok(settled) => settled,
+            // Reject with the reason of the failure instead.
+            Err(e) => (task.reject, self.create_error(&e.into())?),
+        };
+
+        let mut args = vec![value.value];
+        let result = unsafe {
+            q::JS_Call(
+                self.context,
+                function,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let result = OwnedValueRef::new(self, result);
+        if result.is_exception() {
+            return Err(self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+        }
+        Ok(())
+    }
+
+    /// Drop the pending tasks of this context, which is being freed.
+    pub(super) fn drop_tasks(&self) {
+        let tasks = {
+            let mut tasks = self.runtime.tasks.tasks.lock().unwrap();
+            let (own, other) = tasks
+                .drain(..)
+                .partition::<Vec<_>, _>(|task| task.context == self.context);
+            *tasks = other;
+            own
+        };
+        for task in tasks {
+            unsafe {
+                free_value(self.context, task.resolve);
+                free_value(self.context, task.reject);
+            }
+        }
+    }
+
+    /// Block until a task is woken or the next timer is due, and run the
+    /// timers that are due.
+    pub fn wait_for_tasks(&self) -> Result<(), ExecutionError> {
+        // Only the monotonic clock is waited for, a custom clock is moved by
+        // the host.
+        let mut timeout = match self.next_timer_due() {
+            Some(TimerTime::Monotonic(deadline)) => {
+                Some(deadline.saturating_duration_since(Instant::now()))
+            }
+            _ => None,
+        };
+        if let Some(end) = self.runtime.interrupt.deadline.get() {
+            let remaining = end.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                return Err(ExecutionError::TimedOut);
+            }
+            timeout = Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)));
+        }
+        self.runtime.park_until_woken(timeout);
+        let now = self.timer_now();
+        if matches!(self.next_timer_due(), Some(deadline) if deadline <= now) {
+            self.run_due_timers(now)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+//! `Date` backed by a [TimeSource].
+
+use std::rc::Rc;
+
+use libquickjs_sys as q;
+
+use super::{js_null_value, system_time_to_millis, ContextWrapper, OwnedValueRef};
+use crate::{time::TimeSource, ExecutionError, JsValue};
+
+/// Replaces `Date` with a subclass that gets the current time from the host
+/// with `hostNow()`, both for `Date.now()` and when called without arguments.
+///
+/// The prototype is shared with the native `Date`, so existing dates and
+/// `instanceof` keep working.
+const DATE: &str = r#"
+    (hostNow) => {
+        const NativeDate = Date;
+        const HostDate = function Date(...args) {
+            if (new.target === undefined) {
+                return new NativeDate(hostNow()).toString();
+            }
+            return Reflect.construct(NativeDate, args.length === 0 ? [hostNow()] : args, new.target);
+        };
+        Object.setPrototypeOf(HostDate, NativeDate);
+        Object.defineProperty(HostDate, 'length', { value: NativeDate.length });
+        Object.defineProperty(HostDate, 'prototype', { value: NativeDate.prototype, writable: false });
+        Object.defineProperty(HostDate, 'now', {
+            value: function now() {
+                return hostNow();
+            },
+            writable: true,
+            configurable: true,
+        });
+        Object.defineProperty(NativeDate.prototype, 'constructor', {
+            value: HostDate,
+            writable: true,
+            configurable: true,
+        });
+        globalThis.Date = HostDate;
+    }
+"#;
+
+impl ContextWrapper {
+    /// Use the given clock for `Date` and timers.
+    pub fn set_time_source(&self, source: Rc<dyn TimeSource>) -> Result<(), ExecutionError> {
+        let factory = self.eval_raw(DATE, "date.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        if !factory.is_object() {
+            return Err(ExecutionError::Internal("Could not compile date.js".into()));
+        }
+
+        let now = self.create_callback({
+            let source = source.clone();
+            move || JsValue::Float(system_time_to_millis(source.now()))
+        })?;
+        let now = OwnedValueRef::new(self, now);
+
+        let mut args = vec![now.value];
+        let result = unsafe {
+            q::JS_Call(
+                self.context,
+                factory.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        self.resolve_value(OwnedValueRef::new(self, result))?;
+
+        *self.time_source.borrow_mut() = Some(source);
+        Ok(())
+    }
+}
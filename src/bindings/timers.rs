@@ -0,0 +1,387 @@
+//! `setTimeout` and `setInterval`, driven by the host.
+
+use std::{
+    ops::Add,
+    sync::{Arc, Mutex},
+    task::Waker,
+    time::{Duration, Instant, SystemTime},
+};
+
+use libquickjs_sys as q;
+
+use super::{context_wrapper, free_value, js_null_value, ContextWrapper, OwnedValueRef};
+use crate::{ExecutionError, JsValue};
+
+/// Installs the timer functions, and returns a function that runs the
+/// callback of a timer.
+///
+/// Callbacks are kept in Javascript, the host only schedules timer ids with
+/// `schedule(id, delay, repeat)` and `cancel(id)`.
+const TIMERS: &str = r#"
+    (schedule, cancel) => {
+        const callbacks = new Map();
+        let nextId = 1;
+        const add = (repeat, callback, delay, args) => {
+            if (typeof callback !== 'function') {
+                throw new TypeError('Timer callback must be a function');
+            }
+            const id = nextId++;
+            callbacks.set(id, () => callback(...args));
+            delay = Math.min(Math.max(Math.trunc(Number(delay)) || 0, 0), 2147483647);
+            schedule(id, delay, repeat);
+            return id;
+        };
+        const clear = (id) => {
+            if (callbacks.delete(id)) {
+                cancel(id);
+            }
+        };
+        globalThis.setTimeout = (callback, delay, ...args) => add(false, callback, delay, args);
+        globalThis.setInterval = (callback, delay, ...args) => add(true, callback, delay, args);
+        globalThis.clearTimeout = clear;
+        globalThis.clearInterval = clear;
+        return (id, done) => {
+            const callback = callbacks.get(id);
+            if (done) {
+                callbacks.delete(id);
+            }
+            if (callback) {
+                callback();
+            }
+        };
+    }
+"#;
+
+/// The time timers are scheduled in.
+///
+/// With the default clock, timers are scheduled on the monotonic clock, so
+/// changes of the system time do not affect them. With a custom
+/// [TimeSource](crate::time::TimeSource), they are scheduled in its time,
+/// which is moved by the host.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum TimerTime {
+    Monotonic(Instant),
+    Source(SystemTime),
+}
+
+impl Add<Duration> for TimerTime {
+    type Output = Self;
+
+    fn add(self, duration: Duration) -> Self {
+        match self {
+            TimerTime::Monotonic(instant) => TimerTime::Monotonic(instant + duration),
+            TimerTime::Source(time) => TimerTime::Source(time + duration),
+        }
+    }
+}
+
+struct Timer {
+    id: i32,
+    deadline: TimerTime,
+    interval: Option<Duration>,
+}
+
+/// The scheduled timers of a context.
+pub struct TimerQueue {
+    timers: Vec<Timer>,
+    /// The wakers of the promises waiting for the next timer.
+    wakers: Vec<Waker>,
+    /// When the thread waking them is due, if one is running.
+    wakeup: Option<Instant>,
+    /// The same point in time on the monotonic and the system clock, for
+    /// converting between them.
+    anchor: (Instant, SystemTime),
+}
+
+impl Default for TimerQueue {
+    fn default() -> Self {
+        Self {
+            timers: Vec::new(),
+            wakers: Vec::new(),
+            wakeup: None,
+            anchor: (Instant::now(), SystemTime::now()),
+        }
+    }
+}
+
+impl TimerQueue {
+    /// Convert a time of the monotonic clock to the system clock.
+    fn system_time(&self, instant: Instant) -> SystemTime {
+        let (anchor, system_anchor) = self.anchor;
+        match instant.checked_duration_since(anchor) {
+            Some(elapsed) => system_anchor + elapsed,
+            None => system_anchor - anchor.duration_since(instant),
+        }
+    }
+
+    /// Convert a time of the system clock to the monotonic clock.
+    fn instant(&self, time: SystemTime) -> Instant {
+        let (anchor, system_anchor) = self.anchor;
+        match time.duration_since(system_anchor) {
+            Ok(elapsed) => anchor + elapsed,
+            Err(e) => anchor.checked_sub(e.duration()).unwrap_or(anchor),
+        }
+    }
+
+    fn schedule(&mut self, id: i32, now: TimerTime, delay: Duration, repeat: bool) {
+        self.timers.push(Timer {
+            id,
+            deadline: now + delay,
+            interval: if repeat { Some(delay) } else { None },
+        });
+    }
+
+    fn cancel(&mut self, id: i32) {
+        self.timers.retain(|timer| timer.id != id);
+    }
+
+    fn next_deadline(&self) -> Option<TimerTime> {
+        self.timers.iter().map(|timer| timer.deadline).min()
+    }
+
+    /// Take the timers that are due at `now`, in the order they are due.
+    ///
+    /// Intervals are rescheduled relative to `now`. Returns the timer ids,
+    /// and if the timer is done.
+    fn take_due(&mut self, now: TimerTime) -> Vec<(i32, bool)> {
+        let mut due = self
+            .timers
+            .iter()
+            .filter(|timer| timer.deadline <= now)
+            .map(|timer| (timer.deadline, timer.id, timer.interval.is_none()))
+            .collect::<Vec<_>>();
+        due.sort();
+
+        self.timers
+            .retain(|timer| timer.deadline > now || timer.interval.is_some());
+        for timer in &mut self.timers {
+            if let (true, Some(interval)) = (timer.deadline <= now, timer.interval) {
+                timer.deadline = now + interval;
+            }
+        }
+        due.into_iter().map(|(_, id, done)| (id, done)).collect()
+    }
+
+    /// Take the wakers of the promises waiting for the next timer.
+    fn take_wakers(&mut self) -> Vec<Waker> {
+        std::mem::take(&mut self.wakers)
+    }
+}
+
+impl ContextWrapper {
+    /// Install `setTimeout`, `setInterval`, `clearTimeout` and
+    /// `clearInterval`.
+    pub fn enable_timers(&self) -> Result<(), ExecutionError> {
+        let factory = self.eval_raw(TIMERS, "timers.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        if !factory.is_object() {
+            return Err(ExecutionError::Internal(
+                "Could not compile timers.js".into(),
+            ));
+        }
+
+        let queue = self.timer_queue.clone();
+        let context = self.context;
+        let schedule = self.create_callback(move |id: i32, delay: i32, repeat: bool| {
+            let now = unsafe { context_wrapper(context) }.timer_now();
+            let delay = Duration::from_millis(delay as u64);
+            queue.lock().unwrap().schedule(id, now, delay, repeat);
+            JsValue::Null
+        })?;
+        let schedule = OwnedValueRef::new(self, schedule);
+        let queue = self.timer_queue.clone();
+        let cancel = self.create_callback(move |id: i32| {
+            queue.lock().unwrap().cancel(id);
+            JsValue::Null
+        })?;
+        let cancel = OwnedValueRef::new(self, cancel);
+
+        let mut args = vec![schedule.value, cancel.value];
+        let runner = unsafe {
+            q::JS_Call(
+                self.context,
+                factory.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        let runner = self.resolve_value(OwnedValueRef::new(self, runner))?;
+
+        // The runner is freed when the context is dropped.
+        let runner = unsafe { runner.into_inner() };
+        let previous = self.timer_runner.lock().unwrap().replace(runner);
+        if let Some(previous) = previous {
+            unsafe { free_value(self.context, previous) };
+        }
+        Ok(())
+    }
+
+    /// The current time of the clock timers are scheduled with.
+    pub fn timer_now(&self) -> TimerTime {
+        match &*self.time_source.borrow() {
+            Some(source) => TimerTime::Source(source.now()),
+            None => TimerTime::Monotonic(Instant::now()),
+        }
+    }
+
+    /// The time the next timer is due, if any timer is scheduled.
+    pub fn next_timer(&self) -> Option<SystemTime> {
+        let queue = self.timer_queue.lock().unwrap();
+        queue.next_deadline().map(|deadline| match deadline {
+            TimerTime::Monotonic(instant) => queue.system_time(instant),
+            TimerTime::Source(time) => time,
+        })
+    }
+
+    /// The time the next timer is due, on the clock it is scheduled with.
+    pub fn next_timer_due(&self) -> Option<TimerTime> {
+        self.timer_queue.lock().unwrap().next_deadline()
+    }
+
+    /// Wake `waker` once `deadline` passed, or when the host runs the timers
+    /// if no deadline is given.
+    ///
+    /// A single thread waits for the earliest deadline, so promises polled
+    /// again before it passed do not start another one.
+    pub fn wake_on_timer(&self, deadline: Option<Instant>, waker: &Waker) {
+        let mut queue = self.timer_queue.lock().unwrap();
+        if !queue.wakers.iter().any(|other| other.will_wake(waker)) {
+            queue.wakers.push(waker.clone());
+        }
+        let deadline = match deadline {
+            Some(deadline) => deadline,
+            None => return,
+        };
+        if matches!(queue.wakeup, Some(wakeup) if wakeup <= deadline) {
+            return;
+        }
+        queue.wakeup = Some(deadline);
+        drop(queue);
+        spawn_wakeup(self.timer_queue.clone(), deadline);
+    }
+
+    /// Ask a custom clock to advance to `deadline`, and return whether it
+    /// got there.
+    pub fn advance_clock(&self, deadline: SystemTime) -> bool {
+        let source = match self.time_source.borrow().clone() {
+            Some(source) => source,
+            None => return false,
+        };
+        source.advance_to(deadline);
+        source.now() >= deadline
+    }
+
+    /// Block until the timer due at `deadline` can run.
+    ///
+    /// With the default clock, the thread sleeps until then. A custom clock
+    /// is moved by the host, so this fails if it does not advance to the
+    /// deadline when asked to.
+    pub fn wait_for_timer(&self, deadline: TimerTime) -> Result<(), ExecutionError> {
+        match deadline {
+            TimerTime::Monotonic(deadline) => {
+                let execution_deadline = self.runtime.interrupt.deadline.get();
+                if matches!(execution_deadline, Some(end) if end < deadline) {
+                    return Err(ExecutionError::TimedOut);
+                }
+                std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                Ok(())
+            }
+            TimerTime::Source(deadline) if self.advance_clock(deadline) => Ok(()),
+            TimerTime::Source(_) => Err(ExecutionError::Internal(
+                "Promise can not be settled: the clock does not advance to the next timer".into(),
+            )),
+        }
+    }
+
+    /// Run the callbacks of all timers that are due at `now`, and the jobs
+    /// they enqueue, then wake the promises waiting for a timer.
+    ///
+    /// With the default clock, `now` is converted to the monotonic clock the
+    /// timers are scheduled with.
+    ///
+    /// Returns the time the next timer is due.
+    pub fn run_timers(&self, now: SystemTime) -> Result<Option<SystemTime>, ExecutionError> {
+        let now = match self.timer_now() {
+            TimerTime::Monotonic(_) => {
+                TimerTime::Monotonic(self.timer_queue.lock().unwrap().instant(now))
+            }
+            TimerTime::Source(_) => TimerTime::Source(now),
+        };
+        let result = self.run_due_timers(now);
+        let wakers = self.timer_queue.lock().unwrap().take_wakers();
+        for waker in wakers {
+            waker.wake();
+        }
+        result?;
+        Ok(self.next_timer())
+    }
+
+    /// Run the callbacks of all timers that are due at `now`, and the jobs
+    /// they enqueue.
+    pub fn run_due_timers(&self, now: TimerTime) -> Result<(), ExecutionError> {
+        let runner = match *self.timer_runner.lock().unwrap() {
+            Some(runner) => OwnedValueRef::new(self, unsafe { super::dup_value(runner) }),
+            None => return Ok(()),
+        };
+        let _execution = self.start_execution()?;
+
+        let due = self.timer_queue.lock().unwrap().take_due(now);
+        for (id, done) in due {
+            let id = self.serialize_value(JsValue::Int(id))?;
+            let done = self.serialize_value(JsValue::Bool(done))?;
+            let mut args = vec![id.value, done.value];
+            let result = unsafe {
+                q::JS_Call(
+                    self.context,
+                    runner.value,
+                    js_null_value(),
+                    args.len() as i32,
+                    args.as_mut_ptr(),
+                )
+            };
+            let result = OwnedValueRef::new(self, result);
+            if result.is_exception() {
+                return Err(self
+                    .get_exception()
+                    .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into())));
+            }
+            self.execute_pending_jobs(None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shared between a context and its `schedule` and `cancel` callbacks.
+pub type SharedTimerQueue = Arc<Mutex<TimerQueue>>;
+
+/// Wake the waiting promises once `deadline` passed.
+#[cfg(not(target_os = "wasi"))]
+fn spawn_wakeup(queue: SharedTimerQueue, deadline: Instant) {
+    std::thread::spawn(move || {
+        std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        let wakers = {
+            let mut queue = queue.lock().unwrap();
+            if queue.wakeup == Some(deadline) {
+                queue.wakeup = None;
+            }
+            queue.take_wakers()
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    });
+}
+
+/// WASI has no threads, so the promises are polled again until the timer is
+/// due.
+#[cfg(target_os = "wasi")]
+fn spawn_wakeup(queue: SharedTimerQueue, _deadline: Instant) {
+    let wakers = {
+        let mut queue = queue.lock().unwrap();
+        queue.wakeup = None;
+        queue.take_wakers()
+    };
+    for waker in wakers {
+        waker.wake();
+    }
+}
@@ -0,0 +1,337 @@
+//! Top-level `await` in modules.
+//!
+//! The embedded QuickJS version does not support top-level `await`. Modules
+//! that fail to compile because of it are rewritten to run their body in an
+//! async function, with the import declarations kept at the top level.
+//!
+//! quickjs-ng supports top-level `await`, and evaluating a module returns a
+//! promise that settles once the module finished executing.
+
+use libquickjs_sys as q;
+
+use super::{deserialize_promise, ContextWrapper, OwnedValueRef};
+use crate::{ExecutionError, JsValue};
+
+/// The global symbol under which the rewritten module stores its promise.
+const PROMISE_KEY: &str = "Symbol.for('quick_js.module')";
+
+/// Words after which a `/` starts a regular expression instead of being a
+/// division.
+const REGEX_KEYWORDS: &[&str] = &[
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "of",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+    "await",
+];
+
+/// The last significant token seen by the scanner.
+#[derive(Clone, Copy, PartialEq)]
+enum Token<'a> {
+    None,
+    Punct(u8),
+    Word(&'a str),
+    Value,
+}
+
+impl Token<'_> {
+    fn regex_allowed(self) -> bool {
+        match self {
+            Token::None => true,
+            Token::Punct(c) => !matches!(c, b')' | b']' | b'}'),
+            Token::Word(word) => REGEX_KEYWORDS.contains(&word),
+            Token::Value => false,
+        }
+    }
+}
+
+fn is_word_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80
+}
+
+/// Skip a string literal starting at the quote at `start`.
+fn skip_string(code: &[u8], start: usize) -> usize {
+    let quote = code[start];
+    let mut i = start + 1;
+    while i < code.len() && code[i] != quote {
+        i += if code[i] == b'\\' { 2 } else { 1 };
+    }
+    i + 1
+}
+
+/// Skip the characters of a template literal, starting after the backtick
+/// or the `}` of a substitution. Returns the index after the closing
+/// backtick, and whether a substitution (`${`) was entered instead.
+fn skip_template(code: &[u8], mut i: usize) -> (usize, bool) {
+    while i < code.len() {
+        match code[i] {
+            b'\\' => i += 2,
+            b'`' => return (i + 1, false),
+            b'$' if code.get(i + 1) == Some(&b'{') => return (i + 2, true),
+            _ => i += 1,
+        }
+    }
+    (i, false)
+}
+
+/// Skip a regular expression literal starting at the `/` at `start`.
+fn skip_regex(code: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    let mut in_class = false;
+    while i < code.len() {
+        match code[i] {
+            b'\\' => i += 1,
+            b'[' => in_class = true,
+            b']' => in_class = false,
+            b'/' if !in_class => break,
+            b'\n' => return i,
+            _ => {}
+        }
+        i += 1;
+    }
+    i += 1;
+    while i < code.len() && is_word_byte(code[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// Skip whitespace and comments.
+fn skip_trivia(code: &[u8], mut i: usize) -> usize {
+    while i < code.len() {
+        if code[i].is_ascii_whitespace() {
+            i += 1;
+        } else if code[i..].starts_with(b"//") {
+            while i < code.len() && code[i] != b'\n' {
+                i += 1;
+            }
+        } else if code[i..].starts_with(b"/*") {
+            i += 2;
+            while i < code.len() && !code[i..].starts_with(b"*/") {
+                i += 1;
+            }
+            i += 2;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+/// Find the end of the import declaration starting at `start`, which ends
+/// after the module specifier and an optional semicolon.
+fn import_end(code: &[u8], start: usize) -> usize {
+    let mut i = start + "import".len();
+    while i < code.len() {
+        i = skip_trivia(code, i);
+        match code.get(i) {
+            Some(b'\'') | Some(b'"') => {
+                i = skip_string(code, i);
+                let end = skip_trivia(code, i);
+                return if code.get(end) == Some(&b';') {
+                    end + 1
+                } else {
+                    i
+                };
+            }
+            Some(_) => i += 1,
+            None => break,
+        }
+    }
+    code.len()
+}
+
+/// Find the byte ranges of the top-level import declarations of a module.
+fn find_imports(code: &str) -> Vec<(usize, usize)> {
+    let code = code.as_bytes();
+    let mut imports = Vec::new();
+    let mut depth = 0;
+    // The depths at which template substitutions were entered.
+    let mut templates = Vec::new();
+    let mut last = Token::None;
+
+    let mut i = 0;
+    while i < code.len() {
+        let start = skip_trivia(code, i);
+        if start >= code.len() {
+            break;
+        }
+        i = start;
+        let c = code[i];
+        match c {
+            b'\'' | b'"' => {
+                i = skip_string(code, i);
+                last = Token::Value;
+            }
+            b'`' => {
+                let (end, substitution) = skip_template(code, i + 1);
+                if substitution {
+                    depth += 1;
+                    templates.push(depth);
+                    last = Token::Punct(b'{');
+                } else {
+                    last = Token::Value;
+                }
+                i = end;
+            }
+            b'/' if last.regex_allowed() => {
+                i = skip_regex(code, i);
+                last = Token::Value;
+            }
+            b'(' | b'[' | b'{' => {
+                depth += 1;
+                last = Token::Punct(c);
+                i += 1;
+            }
+            b'}' if templates.last() == Some(&depth) => {
+                templates.pop();
+                depth -= 1;
+                let (end, substitution) = skip_template(code, i + 1);
+                if substitution {
+                    depth += 1;
+                    templates.push(depth);
+                    last = Token::Punct(b'{');
+                } else {
+                    last = Token::Value;
+                }
+                i = end;
+            }
+            b')' | b']' | b'}' => {
+                depth = if depth > 0 { depth - 1 } else { 0 };
+                last = Token::Punct(c);
+                i += 1;
+            }
+            c if is_word_byte(c) => {
+                let mut end = i + 1;
+                while end < code.len() && is_word_byte(code[end]) {
+                    end += 1;
+                }
+                let word = std::str::from_utf8(&code[i..end]).unwrap_or("");
+                let next = skip_trivia(code, end);
+                let is_declaration = word == "import"
+                    && depth == 0
+                    && last != Token::Punct(b'.')
+                    && !matches!(code.get(next), Some(b'(') | Some(b'.'));
+                if is_declaration {
+                    end = import_end(code, i);
+                    imports.push((i, end));
+                    last = Token::Punct(b';');
+                } else {
+                    last = Token::Word(word);
+                }
+                i = end;
+            }
+            _ => {
+                last = Token::Punct(c);
+                i += 1;
+            }
+        }
+    }
+    imports
+}
+
+/// Rewrite a module to run its body in an async function, storing the
+/// returned promise under [PROMISE_KEY].
+///
+/// Import declarations are moved after the function, which does not change
+/// their meaning since they are hoisted. Line numbers are kept by blanking
+/// the declarations and opening the function on the first line.
+fn wrap_module(code: &str) -> String {
+    let imports = find_imports(code);
+
+    let mut body = code.as_bytes().to_vec();
+    for &(start, end) in &imports {
+        for c in &mut body[start..end] {
+            if *c != b'\n' {
+                *c = b' ';
+            }
+        }
+    }
+    // Only whole characters were blanked.
+    let body = String::from_utf8(body).unwrap();
+
+    let mut wrapped = format!("globalThis[{}] = (async () => {{", PROMISE_KEY);
+    wrapped.push_str(&body);
+    wrapped.push_str("\n})();\n");
+    for &(start, end) in &imports {
+        wrapped.push_str(&code[start..end]);
+        wrapped.push('\n');
+    }
+    wrapped
+}
+
+impl ContextWrapper {
+    /// Evaluate javascript code as an ES module, without running the event
+    /// loop.
+    ///
+    /// Modules using top-level `await` return a `JsValue::Promise` that
+    /// settles once the module finished executing, other modules return
+    /// `JsValue::Undefined`. With quickjs-ng, all modules return a promise.
+    pub fn eval_module_unresolved(
+        &self,
+        code: &str,
+        name: &str,
+    ) -> Result<JsValue, ExecutionError> {
+        let _execution = self.start_execution()?;
+        let flags = q::JS_EVAL_TYPE_MODULE | q::JS_EVAL_FLAG_COMPILE_ONLY;
+
+        let mut module = self.eval_raw(code, name, flags)?;
+        let mut wrapped = false;
+        if module.is_exception() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+            match &err {
+                ExecutionError::Exception(e)
+                    if e.name == "SyntaxError" && e.message.contains("await") => {}
+                _ => return Err(err),
+            }
+            module = self.eval_raw(&wrap_module(code), name, flags)?;
+            if module.is_exception() {
+                let err = self
+                    .get_exception()
+                    .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+                return Err(err);
+            }
+            wrapped = true;
+        }
+
+        // Takes ownership of the module.
+        let value = unsafe { q::JS_EvalFunction(self.context, module.into_inner()) };
+        let value = OwnedValueRef::new(self, value);
+        if value.is_exception() {
+            let err = self
+                .get_exception()
+                .unwrap_or_else(|| ExecutionError::Exception("Unknown exception".into()));
+            return Err(err);
+        }
+        #[cfg(feature = "ng")]
+        if value.is_object() {
+            return Ok(deserialize_promise(self.context, &value.value)?);
+        }
+        if !wrapped {
+            return Ok(JsValue::Undefined);
+        }
+
+        let take = format!(
+            "(() => {{ const key = {0}; const promise = globalThis[key]; delete globalThis[key]; return promise; }})()",
+            PROMISE_KEY
+        );
+        let promise = self.eval_raw(&take, "module.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        if !promise.is_object() {
+            return Err(ExecutionError::Internal(
+                "Could not get the promise of the module".into(),
+            ));
+        }
+        Ok(deserialize_promise(self.context, &promise.value)?)
+    }
+}
@@ -0,0 +1,392 @@
+//! `URL` and `URLSearchParams`, backed by the `url` crate.
+
+use std::{cell::RefCell, convert::TryFrom, rc::Rc};
+
+use ::url::{form_urlencoded, quirks, Url};
+use libquickjs_sys as q;
+
+use super::{
+    class::{borrow_error, instance, UNDEFINED},
+    context_wrapper, dup_value, js_null_value, ContextWrapper, OwnedObjectRef, OwnedValueRef,
+    PersistentValue,
+};
+use crate::{
+    callback::{Arguments, JsError},
+    class::ClassBuilder,
+    value::format_number,
+    ExecutionError, JsValue, OwnedJsValue, ValueError,
+};
+
+/// Adapts the native classes to the conventions of Javascript.
+///
+/// Constructor arguments are converted to strings or lists of pairs before
+/// they reach Rust, which also allows passing `URL` and `URLSearchParams`
+/// objects. The iteration methods of `URLSearchParams` wrap the list of
+/// entries returned by the native `entries`.
+const URL_GLUE: &str = r#"
+    (URL, URLSearchParams) => {
+        const toInit = (init) => {
+            if (init === undefined || init === null) {
+                return '';
+            } else if (init instanceof URLSearchParams) {
+                return init.toString();
+            } else if (typeof init === 'object' || typeof init === 'function') {
+                const pairs = typeof init[Symbol.iterator] === 'function'
+                    ? Array.from(init, (pair) => Array.from(pair))
+                    : Object.entries(init);
+                return pairs.map((pair) => {
+                    if (pair.length !== 2) {
+                        throw new TypeError('Each pair must have exactly two elements');
+                    }
+                    return pair.map(String);
+                });
+            }
+            return String(init);
+        };
+        globalThis.URL = new Proxy(URL, {
+            construct: (target, [url, base], newTarget) => {
+                const args = base === undefined ? [String(url)] : [String(url), String(base)];
+                return Reflect.construct(target, args, newTarget);
+            },
+        });
+        globalThis.URLSearchParams = new Proxy(URLSearchParams, {
+            construct: (target, [init], newTarget) =>
+                Reflect.construct(target, [toInit(init)], newTarget),
+        });
+
+        const proto = URLSearchParams.prototype;
+        const pairs = proto.entries;
+        const define = (name, value) =>
+            Object.defineProperty(proto, name, { value, writable: true, configurable: true });
+        define('entries', function entries() {
+            return pairs.call(this).values();
+        });
+        define('keys', function keys() {
+            return pairs.call(this).map(([name]) => name).values();
+        });
+        define('values', function values() {
+            return pairs.call(this).map(([, value]) => value).values();
+        });
+        define('forEach', function forEach(callback, thisArg) {
+            for (const [name, value] of pairs.call(this)) {
+                callback.call(thisArg, value, name, this);
+            }
+        });
+        define(Symbol.iterator, proto.entries);
+    }
+"#;
+
+/// A string argument, converted from primitives like in Javascript.
+struct UsvString(String);
+
+impl TryFrom<JsValue> for UsvString {
+    type Error = ValueError;
+
+    fn try_from(value: JsValue) -> Result<Self, ValueError> {
+        match value {
+            JsValue::String(value) => Ok(UsvString(value)),
+            JsValue::Int(value) => Ok(UsvString(value.to_string())),
+            JsValue::Float(value) => Ok(UsvString(format_number(value))),
+            JsValue::Bool(value) => Ok(UsvString(value.to_string())),
+            _ => Err(ValueError::UnexpectedType),
+        }
+    }
+}
+
+/// Convert up to `max` arguments to strings.
+///
+/// Optional arguments that are `undefined` or `null` are omitted.
+fn string_args(args: Arguments, required: usize, max: usize) -> Result<Vec<String>, JsError> {
+    let args = args.into_vec();
+    if args.len() < required {
+        return Err(JsError::type_error(format!(
+            "{} argument(s) required, but only {} present",
+            required,
+            args.len()
+        )));
+    }
+    args.into_iter()
+        .take(max)
+        .enumerate()
+        .filter(|(index, arg)| *index < required || !arg.is_nullish())
+        .map(|(_, arg)| {
+            UsvString::try_from(arg)
+                .map(|arg| arg.0)
+                .map_err(|e| JsError::type_error(e.to_string()))
+        })
+        .collect()
+}
+
+/// The Rust value of a `URL` instance.
+///
+/// The url is shared with its `searchParams`.
+struct JsUrl {
+    url: Rc<RefCell<Url>>,
+}
+
+/// The Rust value of a `URLSearchParams` instance.
+enum SearchParams {
+    Detached(Vec<(String, String)>),
+    /// The query of a `URL`.
+    Url(Rc<RefCell<Url>>),
+}
+
+impl SearchParams {
+    fn parse(query: &str) -> Vec<(String, String)> {
+        let query = query.strip_prefix('?').unwrap_or(query);
+        form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    fn pairs(&self) -> Vec<(String, String)> {
+        match self {
+            SearchParams::Detached(pairs) => pairs.clone(),
+            SearchParams::Url(url) => url.borrow().query_pairs().into_owned().collect(),
+        }
+    }
+
+    fn update(&mut self, update: impl FnOnce(&mut Vec<(String, String)>)) {
+        let mut pairs = self.pairs();
+        update(&mut pairs);
+        match self {
+            SearchParams::Detached(current) => *current = pairs,
+            SearchParams::Url(url) => {
+                let mut url = url.borrow_mut();
+                if pairs.is_empty() {
+                    url.set_query(None);
+                } else {
+                    url.query_pairs_mut().clear().extend_pairs(&pairs);
+                }
+            }
+        }
+    }
+
+    fn serialize(&self) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(self.pairs())
+            .finish()
+    }
+}
+
+fn invalid_url(input: &str) -> JsError {
+    JsError::type_error(format!("Invalid URL: {}", input))
+}
+
+fn url_property(
+    class: ClassBuilder<JsUrl>,
+    name: &str,
+    getter: fn(&Url) -> &str,
+    setter: fn(&mut Url, &str),
+) -> ClassBuilder<JsUrl> {
+    class.property(
+        name,
+        move |this: &JsUrl| getter(&this.url.borrow()).to_string(),
+        move |this: &mut JsUrl, value: UsvString| setter(&mut this.url.borrow_mut(), &value.0),
+    )
+}
+
+fn url_class() -> ClassBuilder<JsUrl> {
+    let class = ClassBuilder::new("URL")
+        .constructor(|args: Arguments| {
+            let mut args = string_args(args, 1, 2)?.into_iter();
+            let input = args.next().unwrap_or_default();
+            let url = match args.next() {
+                Some(base) => Url::parse(&base).and_then(|base| base.join(&input)),
+                None => Url::parse(&input),
+            };
+            let url = url.map_err(|_| invalid_url(&input))?;
+            Ok(JsUrl {
+                url: Rc::new(RefCell::new(url)),
+            })
+        })
+        .property(
+            "href",
+            |this: &JsUrl| quirks::href(&this.url.borrow()).to_string(),
+            |this: &mut JsUrl, href: UsvString| {
+                quirks::set_href(&mut this.url.borrow_mut(), &href.0)
+                    .map_err(|_| invalid_url(&href.0))
+            },
+        )
+        .getter("origin", |this: &JsUrl| quirks::origin(&this.url.borrow()))
+        .method("toString", |this: &mut JsUrl, _args: Arguments| {
+            quirks::href(&this.url.borrow()).to_string()
+        })
+        .method("toJSON", |this: &mut JsUrl, _args: Arguments| {
+            quirks::href(&this.url.borrow()).to_string()
+        });
+
+    // Invalid values are ignored by all setters except `href`.
+    let class = url_property(class, "protocol", quirks::protocol, |url, value| {
+        let _ = quirks::set_protocol(url, value);
+    });
+    let class = url_property(class, "username", quirks::username, |url, value| {
+        let _ = quirks::set_username(url, value);
+    });
+    let class = url_property(class, "password", quirks::password, |url, value| {
+        let _ = quirks::set_password(url, value);
+    });
+    let class = url_property(class, "host", quirks::host, |url, value| {
+        let _ = quirks::set_host(url, value);
+    });
+    let class = url_property(class, "hostname", quirks::hostname, |url, value| {
+        let _ = quirks::set_hostname(url, value);
+    });
+    let class = url_property(class, "port", quirks::port, |url, value| {
+        let _ = quirks::set_port(url, value);
+    });
+    let class = url_property(class, "pathname", quirks::pathname, quirks::set_pathname);
+    let class = url_property(class, "search", quirks::search, quirks::set_search);
+    url_property(class, "hash", quirks::hash, quirks::set_hash)
+}
+
+fn search_params_class() -> ClassBuilder<SearchParams> {
+    ClassBuilder::new("URLSearchParams")
+        .constructor(|args: Arguments| match args.into_vec().into_iter().next() {
+            None | Some(JsValue::Null | JsValue::Undefined) => {
+                Ok(SearchParams::Detached(Vec::new()))
+            }
+            Some(JsValue::String(query)) => Ok(SearchParams::Detached(SearchParams::parse(&query))),
+            Some(JsValue::Array(pairs)) => pairs
+                .into_iter()
+                .map(|pair| match pair {
+                    JsValue::Array(pair) if pair.len() == 2 => {
+                        let mut pair = string_args(Arguments::new(pair), 2, 2)?.into_iter();
+                        Ok((pair.next().unwrap(), pair.next().unwrap()))
+                    }
+                    _ => Err(JsError::type_error("Invalid pair")),
+                })
+                .collect::<Result<_, _>>()
+                .map(SearchParams::Detached),
+            Some(_) => Err(JsError::type_error("Invalid URLSearchParams init")),
+        })
+        .getter("size", |params: &SearchParams| params.pairs().len() as i32)
+        .method("append", |params: &mut SearchParams, args: Arguments| {
+            let mut args = string_args(args, 2, 2)?.into_iter();
+            let (name, value) = (args.next().unwrap(), args.next().unwrap());
+            params.update(|pairs| pairs.push((name, value)));
+            Ok::<_, JsError>(JsValue::Undefined)
+        })
+        .method("delete", |params: &mut SearchParams, args: Arguments| {
+            let args = string_args(args, 1, 2)?;
+            params.update(|pairs| {
+                pairs.retain(|(name, value)| {
+                    name != &args[0] || (args.len() > 1 && &args[1] != value)
+                })
+            });
+            Ok::<_, JsError>(JsValue::Undefined)
+        })
+        .method("get", |params: &mut SearchParams, args: Arguments| {
+            let name = string_args(args, 1, 1)?.remove(0);
+            let value = params
+                .pairs()
+                .into_iter()
+                .find(|(key, _)| key == &name)
+                .map(|(_, value)| value);
+            Ok::<_, JsError>(value)
+        })
+        .method("getAll", |params: &mut SearchParams, args: Arguments| {
+            let name = string_args(args, 1, 1)?.remove(0);
+            let values = params
+                .pairs()
+                .into_iter()
+                .filter(|(key, _)| key == &name)
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>();
+            Ok::<_, JsError>(values)
+        })
+        .method("has", |params: &mut SearchParams, args: Arguments| {
+            let args = string_args(args, 1, 2)?;
+            let found = params
+                .pairs()
+                .iter()
+                .any(|(name, value)| name == &args[0] && (args.len() < 2 || &args[1] == value));
+            Ok::<_, JsError>(found)
+        })
+        .method("set", |params: &mut SearchParams, args: Arguments| {
+            let mut args = string_args(args, 2, 2)?.into_iter();
+            let (name, value) = (args.next().unwrap(), args.next().unwrap());
+            // Replace the first pair with the name, and remove the others.
+            params.update(
+                |pairs| match pairs.iter().position(|(key, _)| key == &name) {
+                    Some(first) => {
+                        pairs[first].1 = value;
+                        let mut index = 0;
+                        pairs.retain(|(key, _)| {
+                            index += 1;
+                            index - 1 <= first || key != &name
+                        });
+                    }
+                    None => pairs.push((name, value)),
+                },
+            );
+            Ok::<_, JsError>(JsValue::Undefined)
+        })
+        .method("sort", |params: &mut SearchParams, _args: Arguments| {
+            // Names are compared by UTF-16 code units, and the sort is stable.
+            params.update(|pairs| pairs.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16())));
+            JsValue::Undefined
+        })
+        .method("toString", |params: &mut SearchParams, _args: Arguments| {
+            params.serialize()
+        })
+        .method("entries", |params: &mut SearchParams, _args: Arguments| {
+            params
+                .pairs()
+                .into_iter()
+                .map(|(name, value)| JsValue::Array(vec![name.into(), value.into()]))
+                .collect::<Vec<_>>()
+        })
+}
+
+impl ContextWrapper {
+    /// Install the `URL` and `URLSearchParams` classes.
+    pub fn enable_url(&self) -> Result<(), ExecutionError> {
+        self.register_class(url_class())?;
+        self.register_class(search_params_class())?;
+
+        let global = self.global()?;
+        let url = OwnedObjectRef::new(global.property("URL")?)?;
+        let search_params = global.property("URLSearchParams")?;
+
+        // `searchParams` needs the context to create the instance, which
+        // getters of a `ClassBuilder` can not access.
+        let context = self.context;
+        let getter = self.create_callback_function(0, move |this, _args| {
+            let url = unsafe { instance::<JsUrl>(this)? }
+                .try_borrow()
+                .map_err(|_| borrow_error())?
+                .url
+                .clone();
+            let wrapper = unsafe { context_wrapper(context) };
+            let params = wrapper.create_instance(SearchParams::Url(url))?;
+
+            // Cache the instance, so `url.searchParams === url.searchParams`.
+            let this = OwnedValueRef::new(&wrapper, unsafe { dup_value(this) });
+            let this = OwnedObjectRef::new(this).map_err(ExecutionError::from)?;
+            unsafe { this.define_property_raw("searchParams", dup_value(params.value))? };
+
+            let params = OwnedJsValue::new(PersistentValue::from_ref(params));
+            Ok(JsValue::Ref(params))
+        })?;
+        let proto = OwnedObjectRef::new(url.property("prototype")?)?;
+        unsafe { proto.define_accessor_raw("searchParams", getter, UNDEFINED)? };
+
+        let glue = self.eval_raw(URL_GLUE, "url.js", q::JS_EVAL_TYPE_GLOBAL)?;
+        if !glue.is_object() {
+            return Err(ExecutionError::Internal("Could not compile url.js".into()));
+        }
+        let mut args = vec![url.value.value, search_params.value];
+        let result = unsafe {
+            q::JS_Call(
+                self.context,
+                glue.value,
+                js_null_value(),
+                args.len() as i32,
+                args.as_mut_ptr(),
+            )
+        };
+        self.resolve_value(OwnedValueRef::new(self, result))?;
+        Ok(())
+    }
+}
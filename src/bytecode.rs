@@ -0,0 +1,124 @@
+//! Version tagging of serialized bytecode.
+//!
+//! The bytecode format of QuickJS changes between versions, and reading
+//! bytecode of another version can crash. All bytecode handed out by
+//! quick-js, by [Context::compile](crate::Context::compile) or to a
+//! [BytecodeCache](crate::module::BytecodeCache), starts with a header
+//! containing the [version] it was created with, and is only executed if
+//! the version matches. Stored bytecode can be checked with
+//! [is_compatible], eg to discard a cache after an upgrade.
+
+use std::{convert::TryInto, error, fmt};
+
+/// Identifies bytecode created by quick-js.
+const MAGIC: &[u8] = b"quick-js bytecode\0";
+
+/// Error on reading bytecode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BytecodeError {
+    /// The data is not bytecode created by quick-js.
+    Invalid,
+    /// The bytecode was created by a different version of quick-js, or with
+    /// a different engine or features.
+    VersionMismatch {
+        /// The version of this build, see [version].
+        expected: String,
+        /// The version the bytecode was created with.
+        found: String,
+    },
+    #[doc(hidden)]
+    __NonExhaustive,
+}
+
+impl fmt::Display for BytecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use BytecodeError::*;
+        match self {
+            Invalid => write!(f, "Invalid bytecode"),
+            VersionMismatch { expected, found } => write!(
+                f,
+                "Bytecode was created by quick-js {}, expected {}",
+                found, expected
+            ),
+            __NonExhaustive => unreachable!(),
+        }
+    }
+}
+
+impl error::Error for BytecodeError {}
+
+/// The version of the bytecode format: the version of quick-js, the engine
+/// and the features changing the bytecode.
+///
+/// ```rust
+/// let version = quick_js::bytecode::version();
+/// assert!(version.starts_with(env!("CARGO_PKG_VERSION")));
+/// ```
+pub fn version() -> String {
+    let engine = if cfg!(feature = "ng") {
+        "quickjs-ng"
+    } else {
+        "quickjs"
+    };
+    let mut version = format!("{} {}", env!("CARGO_PKG_VERSION"), engine);
+    if cfg!(feature = "bignum") {
+        version.push_str(" bignum");
+    }
+    version
+}
+
+/// Whether bytecode was created by quick-js with the same [version], so it
+/// can be executed.
+///
+/// ```rust
+/// use quick_js::{bytecode, Context};
+///
+/// let context = Context::new().unwrap();
+/// let bytecode = context.compile("1 + 2", "add.js").unwrap();
+/// assert!(bytecode::is_compatible(&bytecode));
+/// assert!(!bytecode::is_compatible(b"garbage"));
+/// ```
+pub fn is_compatible(bytecode: &[u8]) -> bool {
+    untag(bytecode).is_ok()
+}
+
+/// Prepend the version header to raw QuickJS bytecode.
+pub(crate) fn tag(bytecode: &[u8]) -> Vec<u8> {
+    let version = version();
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&(version.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(version.as_bytes());
+    bytes.extend_from_slice(bytecode);
+    bytes
+}
+
+/// Check the version header of bytecode, and strip it.
+pub(crate) fn untag(bytes: &[u8]) -> Result<&[u8], BytecodeError> {
+    let mut rest = bytes.strip_prefix(MAGIC).ok_or(BytecodeError::Invalid)?;
+    let version_len = take_len(&mut rest).ok_or(BytecodeError::Invalid)?;
+    let found = take(&mut rest, version_len).ok_or(BytecodeError::Invalid)?;
+    let expected = version();
+    if found != expected.as_bytes() {
+        return Err(BytecodeError::VersionMismatch {
+            expected,
+            found: String::from_utf8_lossy(found).into_owned(),
+        });
+    }
+    Ok(rest)
+}
+
+/// Split off the first `len` bytes.
+pub(crate) fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Some(head)
+}
+
+/// Split off a little endian `u32` length.
+pub(crate) fn take_len(bytes: &mut &[u8]) -> Option<usize> {
+    let len = take(bytes, 4)?.try_into().ok()?;
+    Some(u32::from_le_bytes(len) as usize)
+}
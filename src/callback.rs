@@ -1,26 +1,165 @@
-use std::{convert::TryFrom, marker::PhantomData, panic::RefUnwindSafe};
+use std::{
+    any::Any, collections::HashMap, convert::TryFrom, future::Future, marker::PhantomData,
+    panic::RefUnwindSafe, pin::Pin,
+};
+
+use libquickjs_sys as q;
+
+use crate::{
+    bindings::{
+        borrow_string, deserialize_value, is_nullish, stack_overflow_error, PersistentValue,
+    },
+    value::{JsValue, OwnedJsValue, ValueError},
+    Context, ExecutionError, JsException, JsStr,
+};
+
+/// An error that is thrown as a Javascript exception by a callback.
+///
+/// Callbacks returning `Result<T, JsError>` throw a proper `Error` instance,
+/// which can be checked with `instanceof` in Javascript. Exceptions of
+/// nested executions returned as `ExecutionError` are rethrown as is, and
+/// other error types are thrown as a string of their message.
+///
+/// ```rust
+/// use quick_js::{Context, JsError, JsValue};
+/// let context = Context::new().unwrap();
+///
+/// context
+///     .add_callback("parsePort", |port: String| {
+///         port.parse::<u16>().map(i32::from).map_err(|e| {
+///             JsError::range_error(e.to_string()).with_property("input", port.as_str())
+///         })
+///     })
+///     .unwrap();
+///
+/// let value = context.eval(r#"
+///     try { parsePort("http") } catch (e) { e instanceof RangeError && e.input }
+/// "#);
+/// assert_eq!(value, Ok(JsValue::from("http")));
+/// ```
+// Note: JsError must not implement Display, since the IntoCallbackResult
+// impls would overlap otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsError {
+    repr: ErrorRepr,
+}
 
-use crate::value::{JsValue, ValueError};
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ErrorRepr {
+    /// A value that is thrown as is.
+    Value(JsValue),
+    /// An `Error` instance.
+    Error {
+        name: String,
+        message: String,
+        properties: HashMap<String, JsValue>,
+    },
+}
+
+impl JsError {
+    /// Create an error of the class with the given name.
+    ///
+    /// If a global constructor with this name extends `Error`, like the
+    /// built-in `TypeError` or a class defined by a script, the error is
+    /// created with it. Otherwise, a plain `Error` with the given `name` is
+    /// created.
+    pub fn new(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            repr: ErrorRepr::Error {
+                name: name.into(),
+                message: message.into(),
+                properties: HashMap::new(),
+            },
+        }
+    }
+
+    /// Create an `Error`.
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new("Error", message)
+    }
+
+    /// Create a `TypeError`.
+    pub fn type_error(message: impl Into<String>) -> Self {
+        Self::new("TypeError", message)
+    }
+
+    /// Create a `RangeError`.
+    pub fn range_error(message: impl Into<String>) -> Self {
+        Self::new("RangeError", message)
+    }
+
+    /// Add a property to the error object.
+    ///
+    /// Has no effect on errors converted from an `ExecutionError`, which are
+    /// thrown as is.
+    pub fn with_property(mut self, name: impl Into<String>, value: impl Into<JsValue>) -> Self {
+        if let ErrorRepr::Error { properties, .. } = &mut self.repr {
+            properties.insert(name.into(), value.into());
+        }
+        self
+    }
+
+    pub(crate) fn repr(&self) -> &ErrorRepr {
+        &self.repr
+    }
+
+    pub(crate) fn from_message(message: String) -> Self {
+        Self {
+            repr: ErrorRepr::Value(JsValue::String(message)),
+        }
+    }
+}
+
+/// Exceptions are rethrown as is, and stack overflows as the error of the
+/// engine, so both propagate through nested executions. Other errors are
+/// thrown as a string of their message.
+impl From<ExecutionError> for JsError {
+    fn from(error: ExecutionError) -> Self {
+        match error {
+            ExecutionError::Exception(exception) => Self {
+                repr: ErrorRepr::Value(exception.value),
+            },
+            ExecutionError::StackOverflow => stack_overflow_error(),
+            other => Self::from_message(other.to_string()),
+        }
+    }
+}
 
 pub trait IntoCallbackResult {
-    fn into_callback_res(self) -> Result<JsValue, String>;
+    fn into_callback_res(self) -> Result<JsValue, JsError>;
 }
 
 impl<T: Into<JsValue>> IntoCallbackResult for T {
-    fn into_callback_res(self) -> Result<JsValue, String> {
+    fn into_callback_res(self) -> Result<JsValue, JsError> {
         Ok(self.into())
     }
 }
 
-impl<T: Into<JsValue>, E: std::fmt::Display> IntoCallbackResult for Result<T, E> {
-    fn into_callback_res(self) -> Result<JsValue, String> {
+impl<T: Into<JsValue>, E: std::fmt::Display + 'static> IntoCallbackResult for Result<T, E> {
+    fn into_callback_res(self) -> Result<JsValue, JsError> {
         match self {
             Ok(v) => Ok(v.into()),
-            Err(e) => Err(e.to_string()),
+            Err(e) => {
+                // Errors of nested executions are rethrown like with
+                // `From<ExecutionError>`, instead of as their message.
+                let mut error = Some(e);
+                if let Some(error) =
+                    (&mut error as &mut dyn Any).downcast_mut::<Option<ExecutionError>>()
+                {
+                    return Err(error.take().unwrap().into());
+                }
+                Err(JsError::from_message(error.unwrap().to_string()))
+            }
         }
     }
 }
 
+impl<T: Into<JsValue>> IntoCallbackResult for Result<T, JsError> {
+    fn into_callback_res(self) -> Result<JsValue, JsError> {
+        self.map(Into::into)
+    }
+}
+
 /// The Callback trait is implemented for functions/closures that can be
 /// used as callbacks in the JS runtime.
 pub trait Callback<F>: RefUnwindSafe {
@@ -33,7 +172,185 @@ pub trait Callback<F>: RefUnwindSafe {
     ///   - Ok(Err(_)) if an error ocurred while processing.
     ///       The given error will be raised as a JS exception.
     ///   - Ok(Ok(result)) when execution succeeded.
-    fn call(&self, args: Vec<JsValue>) -> Result<Result<JsValue, String>, ValueError>;
+    fn call(&self, args: CallbackArgs<'_>) -> Result<Result<JsValue, JsError>, ValueError>;
+}
+
+/// The future returned by an [AsyncCallback], which settles the promise
+/// returned to Javascript.
+pub type CallbackFuture = Pin<Box<dyn Future<Output = Result<JsValue, JsError>>>>;
+
+/// The AsyncCallback trait is implemented for functions/closures that return
+/// a `Future`, and can be used as async callbacks in the JS runtime.
+///
+/// See [Context::add_async_callback](crate::Context::add_async_callback).
+pub trait AsyncCallback<F>: RefUnwindSafe {
+    /// The number of JS arguments required.
+    fn argument_count(&self) -> usize;
+    /// Execute the callback, and return the future producing its result.
+    ///
+    /// Should return:
+    ///   - Err(_) if the JS values could not be converted
+    ///   - Ok(Err(_)) if the arguments are invalid
+    ///   - Ok(Ok(future)) otherwise.
+    fn call(&self, args: CallbackArgs<'_>) -> Result<Result<CallbackFuture, JsError>, ValueError>;
+}
+
+/// Check the number of arguments passed to a callback.
+fn check_argument_count(
+    count: usize,
+    required: usize,
+    max: usize,
+    variadic: bool,
+) -> Result<(), JsError> {
+    if count >= required && (variadic || count <= max) {
+        return Ok(());
+    }
+    let expected = if variadic {
+        format!("at least {}", required)
+    } else if required < max {
+        format!("{} to {}", required, max)
+    } else {
+        required.to_string()
+    };
+    Err(JsError::from_message(format!(
+        "Invalid argument count: Expected {}, got {}",
+        expected, count
+    )))
+}
+
+/// The arguments of a callback call, which the parameters of the callback
+/// are converted from.
+///
+/// The arguments are converted to [JsValue]s one at a time, when the
+/// parameter they are passed to is converted, so parameters like [JsStr] can
+/// use the values of the engine directly.
+pub struct CallbackArgs<'a> {
+    context: *mut q::JSContext,
+    values: std::slice::Iter<'a, q::JSValue>,
+}
+
+impl<'a> CallbackArgs<'a> {
+    pub(crate) fn new(context: *mut q::JSContext, values: &'a [q::JSValue]) -> Self {
+        Self {
+            context,
+            values: values.iter(),
+        }
+    }
+
+    /// The number of remaining arguments.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether all arguments have been converted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Convert the next argument, or return `None` if there are no arguments
+    /// left.
+    pub fn next_value(&mut self) -> Option<Result<JsValue, ValueError>> {
+        let value = self.values.next()?;
+        Some(deserialize_value(self.context, value))
+    }
+
+    /// Convert the remaining arguments.
+    pub fn into_values(self) -> Result<Vec<JsValue>, ValueError> {
+        let context = self.context;
+        self.values
+            .map(|value| deserialize_value(context, value))
+            .collect()
+    }
+
+    fn next_string(&mut self) -> Option<Result<JsStr<'static>, ValueError>> {
+        let value = self.values.next()?;
+        Some(unsafe { borrow_string(self.context, value) })
+    }
+}
+
+/// A parameter type of callback functions.
+///
+/// Implemented for all types that can be converted from a [JsValue], for
+/// `Option<T>` of those, which makes the parameter optional, for [JsStr],
+/// and for [Arguments], which collects all remaining arguments.
+///
+/// Parameters can not borrow from the arguments, since a closure taking a
+/// reference implements `Fn` for every lifetime, which the generic callback
+/// impls can not express. String parameters are either `String`s, which
+/// copy the string out of the engine, or [JsStr]s, which do not.
+pub trait CallbackArg: Sized {
+    /// The number of JS arguments required by the parameter.
+    const REQUIRED: usize = 1;
+    /// Whether the parameter accepts any number of arguments.
+    const VARIADIC: bool = false;
+
+    /// Convert the next argument(s).
+    fn from_args(args: &mut CallbackArgs<'_>) -> Result<Self, ValueError>;
+}
+
+fn missing_arg() -> ValueError {
+    // Only happens if a required parameter follows `Arguments`.
+    ValueError::Internal("Missing callback argument".into())
+}
+
+impl<T: TryFrom<JsValue, Error = ValueError>> CallbackArg for T {
+    fn from_args(args: &mut CallbackArgs<'_>) -> Result<Self, ValueError> {
+        T::try_from(args.next_value().ok_or_else(missing_arg)??)
+    }
+}
+
+impl CallbackArg for JsValue {
+    fn from_args(args: &mut CallbackArgs<'_>) -> Result<Self, ValueError> {
+        args.next_value().ok_or_else(missing_arg)?
+    }
+}
+
+/// Missing arguments, `null` and `undefined` are converted to `None`.
+impl<T: TryFrom<JsValue, Error = ValueError>> CallbackArg for Option<T> {
+    const REQUIRED: usize = 0;
+
+    fn from_args(args: &mut CallbackArgs<'_>) -> Result<Self, ValueError> {
+        match args.next_value().transpose()? {
+            None | Some(JsValue::Null | JsValue::Undefined) => Ok(None),
+            Some(value) => T::try_from(value).map(Some),
+        }
+    }
+}
+
+/// Borrows the string from the engine instead of copying it, see [JsStr].
+impl CallbackArg for JsStr<'static> {
+    fn from_args(args: &mut CallbackArgs<'_>) -> Result<Self, ValueError> {
+        args.next_string().ok_or_else(missing_arg)?
+    }
+}
+
+impl CallbackArg for Option<JsStr<'static>> {
+    const REQUIRED: usize = 0;
+
+    fn from_args(args: &mut CallbackArgs<'_>) -> Result<Self, ValueError> {
+        match args.values.as_slice().first() {
+            None => Ok(None),
+            Some(value) if is_nullish(value) => {
+                args.values.next();
+                Ok(None)
+            }
+            Some(_) => args.next_string().transpose(),
+        }
+    }
+}
+
+impl CallbackArg for Arguments {
+    const REQUIRED: usize = 0;
+    const VARIADIC: bool = true;
+
+    fn from_args(args: &mut CallbackArgs<'_>) -> Result<Self, ValueError> {
+        let context = args.context;
+        let values = args.values.by_ref();
+        values
+            .map(|value| deserialize_value(context, value))
+            .collect::<Result<_, _>>()
+            .map(Arguments)
+    }
 }
 
 macro_rules! impl_callback {
@@ -43,10 +360,10 @@ macro_rules! impl_callback {
 
     (@call $len:literal $self:ident $args:ident $( $arg:ident ),* ) => {
         {
-            let mut iter = $args.into_iter();
+            let mut args = $args;
             $self(
                 $(
-                    $arg::try_from(iter.next().unwrap())?,
+                    $arg::from_args(&mut args)?,
                 )*
             )
         }
@@ -65,27 +382,54 @@ macro_rules! impl_callback {
                 &F,
             )>> for F
             where
-                $( $arg: TryFrom<JsValue, Error = ValueError>, )*
+                $( $arg: CallbackArg, )*
                 R: IntoCallbackResult,
                 F: Fn( $( $arg, )*  ) -> R + Sized + RefUnwindSafe,
             {
                 fn argument_count(&self) -> usize {
-                    $len
+                    0 $( + $arg::REQUIRED )*
                 }
 
-                fn call(&self, args: Vec<JsValue>) -> Result<Result<JsValue, String>, ValueError> {
-                    if args.len() != $len {
-                        return Ok(Err(format!(
-                            "Invalid argument count: Expected {}, got {}",
-                            self.argument_count(),
-                            args.len()
-                        )));
+                fn call(&self, args: CallbackArgs<'_>) -> Result<Result<JsValue, JsError>, ValueError> {
+                    let variadic = false $( || $arg::VARIADIC )*;
+                    if let Err(e) = check_argument_count(args.len(), self.argument_count(), $len, variadic) {
+                        return Ok(Err(e));
                     }
 
                     let res = impl_callback!(@call $len self args $($arg),* );
                     Ok(res.into_callback_res())
                 }
             }
+
+            impl<
+                $( $arg, )*
+                Fut,
+                F,
+            > AsyncCallback<PhantomData<(
+                $( &$arg, )*
+                &Fut,
+                &F,
+            )>> for F
+            where
+                $( $arg: CallbackArg, )*
+                Fut: Future + 'static,
+                Fut::Output: IntoCallbackResult,
+                F: Fn( $( $arg, )*  ) -> Fut + Sized + RefUnwindSafe,
+            {
+                fn argument_count(&self) -> usize {
+                    0 $( + $arg::REQUIRED )*
+                }
+
+                fn call(&self, args: CallbackArgs<'_>) -> Result<Result<CallbackFuture, JsError>, ValueError> {
+                    let variadic = false $( || $arg::VARIADIC )*;
+                    if let Err(e) = check_argument_count(args.len(), self.argument_count(), $len, variadic) {
+                        return Ok(Err(e));
+                    }
+
+                    let future = impl_callback!(@call $len self args $($arg),* );
+                    Ok(Ok(Box::pin(async move { future.await.into_callback_res() })))
+                }
+            }
         )*
     };
 }
@@ -102,16 +446,23 @@ impl_callback![
 /// A wrapper around Vec<JsValue>, used for vararg callbacks.
 ///
 /// To create a callback with a variable number of arguments, a callback closure
-/// must take a single `Arguments` argument.
+/// must take an `Arguments` argument as its last parameter. It collects all
+/// remaining arguments.
 pub struct Arguments(Vec<JsValue>);
 
 impl Arguments {
+    pub(crate) fn new(args: Vec<JsValue>) -> Self {
+        Self(args)
+    }
+
     /// Unpack the arguments into a Vec.
     pub fn into_vec(self) -> Vec<JsValue> {
         self.0
     }
 }
 
+// Callbacks taking `Arguments` and returning a value are covered by
+// impl_callback.
 impl<F> Callback<PhantomData<(&Arguments, &F)>> for F
 where
     F: Fn(Arguments) + Sized + RefUnwindSafe,
@@ -120,24 +471,65 @@ where
         0
     }
 
-    fn call(&self, args: Vec<JsValue>) -> Result<Result<JsValue, String>, ValueError> {
-        (self)(Arguments(args));
-        Ok(Ok(JsValue::Null))
+    fn call(&self, args: CallbackArgs<'_>) -> Result<Result<JsValue, JsError>, ValueError> {
+        (self)(Arguments(args.into_values()?));
+        Ok(Ok(JsValue::Undefined))
     }
 }
 
-impl<F, R> Callback<PhantomData<(&Arguments, &F, &R)>> for F
-where
-    R: IntoCallbackResult,
-    F: Fn(Arguments) -> R + Sized + RefUnwindSafe,
-{
-    fn argument_count(&self) -> usize {
-        0
+/// A handle to the context a callback is executed in.
+///
+/// Passed to callbacks registered with
+/// [Context::add_callback_with_context](crate::Context::add_callback_with_context).
+/// It allows callbacks to interact with the engine while they are executed.
+pub struct CallbackContext {
+    context: Context,
+}
+
+impl CallbackContext {
+    pub(crate) fn new(context: Context) -> Self {
+        Self { context }
+    }
+
+    /// The [Context] the callback is executed in.
+    ///
+    /// Code executed with it, like `eval` or `call_function`, runs nested
+    /// inside the current callback, and may call callbacks again. Nesting is
+    /// only limited by the maximum stack size: exceeding it fails with
+    /// `ExecutionError::StackOverflow`, which is propagated to the outer
+    /// executions when returned from the callback.
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Get a property of the global object.
+    ///
+    /// Missing properties are returned as `JsValue::Undefined`.
+    pub fn get_global(&self, name: &str) -> Result<JsValue, ExecutionError> {
+        self.context.wrapper.get_global(name)
+    }
+
+    /// Set a property of the global object.
+    pub fn set_global(&self, name: &str, value: impl Into<JsValue>) -> Result<(), ExecutionError> {
+        self.context.wrapper.set_global(name, value.into())
     }
 
-    fn call(&self, args: Vec<JsValue>) -> Result<Result<JsValue, String>, ValueError> {
-        let res = (self)(Arguments(args));
-        Ok(res.into_callback_res())
+    /// Create an error that throws the given value as a Javascript exception
+    /// when returned from the callback.
+    pub fn throw(&self, value: impl Into<JsValue>) -> ExecutionError {
+        ExecutionError::Exception(Box::new(JsException::from(value.into())))
+    }
+
+    /// Create an error that throws the given [JsError] as a Javascript
+    /// exception when returned from the callback.
+    pub fn throw_error(&self, error: JsError) -> ExecutionError {
+        match self.context.wrapper.create_error(&error) {
+            Ok(value) => {
+                let value = OwnedJsValue::new(PersistentValue::from_ref(value));
+                ExecutionError::Exception(JsValue::Ref(value).into())
+            }
+            Err(e) => e,
+        }
     }
 }
 
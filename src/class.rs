@@ -0,0 +1,217 @@
+//! Exposing Rust types as Javascript classes.
+
+use std::{convert::TryFrom, panic::RefUnwindSafe, rc::Rc};
+
+use crate::{
+    callback::IntoCallbackResult,
+    value::{JsValue, ValueError},
+    Arguments, JsError,
+};
+
+pub(crate) type Constructor<T> = Box<dyn Fn(Arguments) -> Result<T, JsError> + RefUnwindSafe>;
+pub(crate) type Method<T> =
+    Box<dyn Fn(&mut T, Arguments) -> Result<JsValue, JsError> + RefUnwindSafe>;
+pub(crate) type Getter<T> = Box<dyn Fn(&T) -> Result<JsValue, JsError> + RefUnwindSafe>;
+pub(crate) type Setter<T> = Box<dyn Fn(&mut T, JsValue) -> Result<(), JsError> + RefUnwindSafe>;
+pub(crate) type Finalizer<T> = Rc<dyn Fn(T)>;
+
+pub(crate) struct Property<T> {
+    pub name: String,
+    pub getter: Getter<T>,
+    pub setter: Option<Setter<T>>,
+}
+
+/// The result of a property setter, see [ClassBuilder::property].
+pub trait IntoSetterResult {
+    fn into_setter_res(self) -> Result<(), JsError>;
+}
+
+impl IntoSetterResult for () {
+    fn into_setter_res(self) -> Result<(), JsError> {
+        Ok(())
+    }
+}
+
+impl<E: std::fmt::Display> IntoSetterResult for Result<(), E> {
+    fn into_setter_res(self) -> Result<(), JsError> {
+        self.map_err(|e| JsError::from_message(e.to_string()))
+    }
+}
+
+impl IntoSetterResult for Result<(), JsError> {
+    fn into_setter_res(self) -> Result<(), JsError> {
+        self
+    }
+}
+
+/// Builder for exposing a Rust type as a Javascript class.
+///
+/// Every instance of the class owns a value of type `T`, which is dropped
+/// when the instance is garbage collected, or passed to the
+/// [finalizer](ClassBuilder::finalizer) of the class. Methods and accessors are defined
+/// on the prototype and receive the value of the instance they are called on.
+///
+/// The class is registered with
+/// [Context::register_class](crate::Context::register_class), which makes the
+/// constructor available as a global with the name of the class. Instances
+/// can also be created from Rust with
+/// [Context::create_instance](crate::Context::create_instance).
+///
+/// ```rust
+/// use quick_js::{Arguments, ClassBuilder, Context, JsError, JsValue};
+///
+/// struct Counter {
+///     count: i32,
+/// }
+///
+/// let context = Context::new().unwrap();
+///
+/// let class = ClassBuilder::new("Counter")
+///     .constructor(|args: Arguments| {
+///         let count = match args.into_vec().first() {
+///             Some(JsValue::Int(start)) => *start,
+///             Some(_) => return Err(JsError::type_error("start must be an integer")),
+///             None => 0,
+///         };
+///         Ok(Counter { count })
+///     })
+///     .method("increment", |counter: &mut Counter, _args: Arguments| {
+///         counter.count += 1;
+///         counter.count
+///     })
+///     .property(
+///         "count",
+///         |counter: &Counter| counter.count,
+///         |counter: &mut Counter, count: i32| counter.count = count,
+///     );
+/// context.register_class(class).unwrap();
+///
+/// let value = context.eval(r#"
+///     const counter = new Counter(10);
+///     counter.increment();
+///     counter.count += 5;
+///     counter.count
+/// "#).unwrap();
+/// assert_eq!(value, JsValue::Int(16));
+/// ```
+pub struct ClassBuilder<T> {
+    pub(crate) name: String,
+    pub(crate) constructor: Option<Constructor<T>>,
+    pub(crate) methods: Vec<(String, Method<T>)>,
+    pub(crate) properties: Vec<Property<T>>,
+    pub(crate) finalizer: Option<Finalizer<T>>,
+}
+
+impl<T: 'static> ClassBuilder<T> {
+    /// Create a builder for a class with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            constructor: None,
+            methods: Vec::new(),
+            properties: Vec::new(),
+            finalizer: None,
+        }
+    }
+
+    /// Set the function that creates the Rust value when the class is
+    /// instantiated with `new` in Javascript.
+    ///
+    /// Without a constructor, `new` throws a `TypeError` and instances can
+    /// only be created with
+    /// [Context::create_instance](crate::Context::create_instance).
+    pub fn constructor<F>(mut self, constructor: F) -> Self
+    where
+        F: Fn(Arguments) -> Result<T, JsError> + RefUnwindSafe + 'static,
+    {
+        self.constructor = Some(Box::new(constructor));
+        self
+    }
+
+    /// Add a method.
+    ///
+    /// The return value is handled like the return value of callbacks, see
+    /// [Context::add_callback](crate::Context::add_callback).
+    pub fn method<F, R>(mut self, name: impl Into<String>, method: F) -> Self
+    where
+        F: Fn(&mut T, Arguments) -> R + RefUnwindSafe + 'static,
+        R: IntoCallbackResult,
+    {
+        let method = move |this: &mut T, args: Arguments| method(this, args).into_callback_res();
+        self.methods.push((name.into(), Box::new(method)));
+        self
+    }
+
+    /// Add a read-only property backed by a getter.
+    pub fn getter<G, R>(mut self, name: impl Into<String>, getter: G) -> Self
+    where
+        G: Fn(&T) -> R + RefUnwindSafe + 'static,
+        R: IntoCallbackResult,
+    {
+        self.properties.push(Property {
+            name: name.into(),
+            getter: Box::new(move |this: &T| getter(this).into_callback_res()),
+            setter: None,
+        });
+        self
+    }
+
+    /// Add a property backed by a getter and a setter.
+    ///
+    /// Assigning a value that can not be converted to `V` throws a
+    /// `TypeError`. The setter can return a `Result` to throw an error.
+    pub fn property<G, R, S, V, U>(mut self, name: impl Into<String>, getter: G, setter: S) -> Self
+    where
+        G: Fn(&T) -> R + RefUnwindSafe + 'static,
+        R: IntoCallbackResult,
+        S: Fn(&mut T, V) -> U + RefUnwindSafe + 'static,
+        V: TryFrom<JsValue, Error = ValueError>,
+        U: IntoSetterResult,
+    {
+        let setter = move |this: &mut T, value: JsValue| {
+            let value = V::try_from(value).map_err(|e| JsError::type_error(e.to_string()))?;
+            setter(this, value).into_setter_res()
+        };
+        self.properties.push(Property {
+            name: name.into(),
+            getter: Box::new(move |this: &T| getter(this).into_callback_res()),
+            setter: Some(Box::new(setter)),
+        });
+        self
+    }
+
+    /// Set a function that receives the Rust value of each instance once the
+    /// instance is garbage collected, instead of dropping it.
+    ///
+    /// Like the callbacks of
+    /// [Context::register_finalizer](crate::Context::register_finalizer),
+    /// the finalizer runs as a pending job, so it can use the context. The
+    /// finalizers of instances that are still alive when the context is
+    /// freed run once they are collected, which is at the latest when the
+    /// [Runtime](crate::Runtime) is dropped.
+    ///
+    /// ```rust
+    /// use quick_js::{ClassBuilder, Context};
+    /// use std::{cell::RefCell, rc::Rc};
+    ///
+    /// struct Handle(u32);
+    ///
+    /// let closed = Rc::new(RefCell::new(Vec::new()));
+    /// let log = closed.clone();
+    /// let context = Context::new().unwrap();
+    /// let class = ClassBuilder::new("Handle")
+    ///     .finalizer(move |handle: Handle| log.borrow_mut().push(handle.0));
+    /// context.register_class(class).unwrap();
+    ///
+    /// drop(context.create_instance(Handle(7)).unwrap());
+    /// context.execute_pending_jobs(None).unwrap();
+    /// assert_eq!(*closed.borrow(), vec![7]);
+    /// ```
+    pub fn finalizer<F>(mut self, finalizer: F) -> Self
+    where
+        F: Fn(T) + 'static,
+    {
+        self.finalizer = Some(Rc::new(finalizer));
+        self
+    }
+}
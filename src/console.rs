@@ -1,7 +1,7 @@
 //! Javascript console integration.
 //! See the [ConsoleBackend] trait for more info.
 
-use super::JsValue;
+use super::{value::format_number, JsValue};
 
 /// Log level of a log message sent via the console.
 /// These levels represent the different functions defined in the spec:
@@ -35,9 +35,11 @@ impl std::fmt::Display for Level {
 /// A console backend that handles console messages sent from JS via
 /// console.{log,debug,trace,...} functions.
 ///
-/// A backend has to be registered via the `ContextBuilder::console` method.
-///
-/// A backend that forwads to the `log` crate is available with the `log` feature.
+/// A backend can be registered via the `ContextBuilder::console` method.
+/// By default, messages are forwarded to the `log` crate with `LogConsole`
+/// if the `log` feature is enabled, and discarded with [NullConsole]
+/// otherwise. Backends can use [format] to format the arguments like
+/// browsers do.
 ///
 /// Note that any closure of type `Fn(Level, Vec<JsValue>)` implements this trait.
 ///
@@ -68,46 +70,171 @@ where
     }
 }
 
+/// A console backend that discards all messages.
+///
+/// Used by default if the `log` feature is disabled, so that scripts can use
+/// the console without checking if it exists.
+pub struct NullConsole;
+
+impl ConsoleBackend for NullConsole {
+    fn log(&self, _level: Level, _values: Vec<JsValue>) {}
+}
+
+/// The backend used if none is set with `ContextBuilder::console`.
+#[cfg(feature = "log")]
+pub(crate) fn default_backend() -> Box<dyn ConsoleBackend> {
+    Box::new(LogConsole)
+}
+
+/// The backend used if none is set with `ContextBuilder::console`.
+#[cfg(not(feature = "log"))]
+pub(crate) fn default_backend() -> Box<dyn ConsoleBackend> {
+    Box::new(NullConsole)
+}
+
+fn print_value(value: JsValue) -> String {
+    match value {
+        JsValue::Null => "null".to_string(),
+        JsValue::Undefined => "undefined".to_string(),
+        JsValue::Bool(v) => v.to_string(),
+        JsValue::Int(v) => v.to_string(),
+        JsValue::Float(v) => format_number(v),
+        JsValue::String(v) => v,
+        JsValue::Array(values) => {
+            let parts = values
+                .into_iter()
+                .map(print_value)
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("[{}]", parts)
+        }
+        JsValue::Object(map) => {
+            let parts = map
+                .into_iter()
+                .map(|(key, value)| format!("{}: {}", key, print_value(value)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{{{}}}", parts)
+        }
+        #[cfg(feature = "chrono")]
+        JsValue::Date(v) => chrono::DateTime::<chrono::Utc>::from(v).to_string(),
+        #[cfg(not(feature = "chrono"))]
+        JsValue::Date(v) => format!("{:?}", v),
+        JsValue::BigInt(v) => v.to_string(),
+        JsValue::Map(entries) => {
+            let size = entries.len();
+            let parts = entries
+                .into_iter()
+                .map(|(key, value)| format!("{} => {}", print_value(key), print_value(value)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("Map({}) {{{}}}", size, parts)
+        }
+        JsValue::Set(values) => {
+            let size = values.len();
+            let parts = values
+                .into_iter()
+                .map(print_value)
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("Set({}) {{{}}}", size, parts)
+        }
+        JsValue::Bytes(v) => format!("Uint8Array {:?}", v),
+        JsValue::Promise(_) => "[object Promise]".to_string(),
+        JsValue::Function(_) => "[Function]".to_string(),
+        JsValue::Symbol(s) => format!("{:?}", s),
+        JsValue::Utf16(s) => s.to_string_lossy(),
+        JsValue::Ref(v) => format!("{:?}", v),
+        JsValue::__NonExhaustive => unreachable!(),
+    }
+}
+
+/// Format the arguments of a console call into a message, like browsers do.
+///
+/// If the first argument is a string, it is used as a format string:
+/// `%s`, `%d`/`%i`, `%f` and `%o`/`%O` are replaced by the next argument,
+/// `%c` consumes an argument without output, and `%%` is replaced by `%`.
+/// The remaining arguments are appended, separated by spaces.
+///
+/// ```rust
+/// use quick_js::{console, JsValue};
+///
+/// let message = console::format(vec![
+///     JsValue::from("%s has %d items"),
+///     JsValue::from("list"),
+///     JsValue::Float(3.7),
+///     JsValue::Array(vec![JsValue::Int(1)]),
+/// ]);
+/// assert_eq!(message, "list has 3 items [1]");
+/// ```
+pub fn format(values: Vec<JsValue>) -> String {
+    let mut values = values.into_iter();
+    let mut parts = Vec::new();
+
+    match values.next() {
+        Some(JsValue::String(template)) => {
+            let mut message = String::new();
+            let mut chars = template.chars();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    message.push(c);
+                    continue;
+                }
+                match chars.clone().next() {
+                    Some('%') => message.push('%'),
+                    Some(spec) if "sdifoOc".contains(spec) => match values.next() {
+                        Some(value) => message.push_str(&format_specifier(spec, value)),
+                        None => {
+                            message.push('%');
+                            message.push(spec);
+                        }
+                    },
+                    _ => {
+                        message.push('%');
+                        continue;
+                    }
+                }
+                chars.next();
+            }
+            parts.push(message);
+        }
+        Some(value) => parts.push(print_value(value)),
+        None => {}
+    }
+
+    parts.extend(values.map(print_value));
+    parts.join(" ")
+}
+
+/// Format a single value for a format specifier.
+fn format_specifier(spec: char, value: JsValue) -> String {
+    match spec {
+        'c' => String::new(),
+        'd' | 'i' => match value {
+            JsValue::Int(v) => v.to_string(),
+            JsValue::Float(v) if v.is_finite() => (v.trunc() as i64).to_string(),
+            JsValue::BigInt(v) => v.to_string(),
+            _ => "NaN".to_string(),
+        },
+        'f' => match value {
+            JsValue::Int(v) => v.to_string(),
+            JsValue::Float(v) => format_number(v),
+            _ => "NaN".to_string(),
+        },
+        _ => print_value(value),
+    }
+}
+
 #[cfg(feature = "log")]
 mod log {
     use super::{JsValue, Level};
 
     /// A console implementation that logs messages via the `log` crate.
     ///
-    /// Only available with the `log` feature.
+    /// Only available with the `log` feature. Messages are formatted with
+    /// [format](super::format).
     pub struct LogConsole;
 
-    fn print_value(value: JsValue) -> String {
-        match value {
-            JsValue::Null => "null".to_string(),
-            JsValue::Bool(v) => v.to_string(),
-            JsValue::Int(v) => v.to_string(),
-            JsValue::Float(v) => v.to_string(),
-            JsValue::String(v) => v,
-            JsValue::Array(values) => {
-                let parts = values
-                    .into_iter()
-                    .map(print_value)
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                format!("[{}]", parts)
-            }
-            JsValue::Object(map) => {
-                let parts = map
-                    .into_iter()
-                    .map(|(key, value)| format!("{}: {}", key, print_value(value)))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                format!("{{{}}}", parts)
-            }
-            #[cfg(feature = "chrono")]
-            JsValue::Date(v) => v.to_string(),
-            #[cfg(feature = "bigint")]
-            JsValue::BigInt(v) => v.to_string(),
-            JsValue::__NonExhaustive => unreachable!(),
-        }
-    }
-
     impl super::ConsoleBackend for LogConsole {
         fn log(&self, level: Level, values: Vec<JsValue>) {
             if values.is_empty() {
@@ -122,13 +249,7 @@ mod log {
                 Level::Error => log::Level::Error,
             };
 
-            let msg = values
-                .into_iter()
-                .map(print_value)
-                .collect::<Vec<_>>()
-                .join(" ");
-
-            log::log!(log_level, "{}", msg);
+            log::log!(log_level, "{}", super::format(values));
         }
     }
 }
@@ -0,0 +1,111 @@
+//! Helpers for the code generated by the `IntoJsValue` and `FromJsValue`
+//! derive macros.
+
+use std::collections::HashMap;
+
+use crate::{FromJsValue, JsValue, ValueError};
+
+/// Get the properties of an object.
+pub fn object(value: JsValue) -> Result<HashMap<String, JsValue>, ValueError> {
+    match value {
+        JsValue::Object(object) => Ok(object),
+        _ => Err(ValueError::UnexpectedType),
+    }
+}
+
+/// Convert the property `name`, taking it out of the object.
+///
+/// Missing properties are converted like `undefined`.
+pub fn property<T: FromJsValue>(
+    object: &mut HashMap<String, JsValue>,
+    name: &str,
+) -> Result<T, ValueError> {
+    match object.remove(name) {
+        Some(value) => T::from_js_value(value).map_err(|e| invalid_property(name, e)),
+        None => T::from_js_value(JsValue::Undefined).map_err(|_| missing_property(name)),
+    }
+}
+
+/// Convert the property `name`, taking it out of the object, or use the
+/// default if it is missing, `null` or `undefined`.
+pub fn property_or_default<T: FromJsValue + Default>(
+    object: &mut HashMap<String, JsValue>,
+    name: &str,
+) -> Result<T, ValueError> {
+    match object.remove(name) {
+        None | Some(JsValue::Null | JsValue::Undefined) => Ok(T::default()),
+        Some(value) => T::from_js_value(value).map_err(|e| invalid_property(name, e)),
+    }
+}
+
+fn missing_property(name: &str) -> ValueError {
+    ValueError::Custom(format!("Missing property '{}'", name))
+}
+
+fn invalid_property(name: &str, error: ValueError) -> ValueError {
+    ValueError::Custom(format!("Invalid property '{}': {}", name, error))
+}
+
+/// Get the items of an array with `len` items.
+pub fn tuple(value: JsValue, len: usize) -> Result<std::vec::IntoIter<JsValue>, ValueError> {
+    match value {
+        JsValue::Array(items) if items.len() == len => Ok(items.into_iter()),
+        _ => Err(ValueError::UnexpectedType),
+    }
+}
+
+/// Convert the next item of an array returned by [tuple].
+pub fn element<T: FromJsValue>(items: &mut std::vec::IntoIter<JsValue>) -> Result<T, ValueError> {
+    T::from_js_value(items.next().unwrap_or(JsValue::Undefined))
+}
+
+/// Check that the value of a unit variant of an untagged enum is `null` or
+/// `undefined`.
+pub fn unit(value: JsValue) -> Result<(), ValueError> {
+    match value {
+        JsValue::Null | JsValue::Undefined => Ok(()),
+        _ => Err(ValueError::UnexpectedType),
+    }
+}
+
+/// Wrap the content of a variant of an externally tagged enum.
+pub fn tagged(name: &str, content: JsValue) -> JsValue {
+    let mut object = HashMap::new();
+    object.insert(name.to_string(), content);
+    JsValue::Object(object)
+}
+
+/// Get the name and content of a variant of an externally tagged enum.
+///
+/// Unit variants are strings, other variants objects with a single property.
+pub fn variant(value: JsValue) -> Result<(String, Option<JsValue>), ValueError> {
+    match value {
+        JsValue::String(name) => Ok((name, None)),
+        JsValue::Object(object) if object.len() == 1 => {
+            let (name, content) = object.into_iter().next().unwrap();
+            Ok((name, Some(content)))
+        }
+        _ => Err(ValueError::UnexpectedType),
+    }
+}
+
+/// Take the name of the variant of an internally tagged enum out of the
+/// object.
+pub fn tag(object: &mut HashMap<String, JsValue>, tag: &str) -> Result<String, ValueError> {
+    match object.remove(tag) {
+        Some(JsValue::String(name)) => Ok(name),
+        None | Some(JsValue::Null | JsValue::Undefined) => Err(missing_property(tag)),
+        Some(_) => Err(invalid_property(tag, ValueError::UnexpectedType)),
+    }
+}
+
+/// The error for a tag that names no variant.
+pub fn unknown_variant(name: &str) -> ValueError {
+    ValueError::Custom(format!("Unknown variant '{}'", name))
+}
+
+/// The error for a value that matches no variant of the untagged enum
+/// `name`.
+pub fn no_matching_variant(name: &str) -> ValueError {
+    ValueError::Custom(format!("Value does not match any variant of {}", name))
+}
@@ -0,0 +1,78 @@
+//! Host objects with properties computed by Rust.
+
+use crate::{JsError, JsValue};
+
+/// An object whose properties are looked up by Rust when Javascript accesses
+/// them, instead of being copied into the context up front.
+///
+/// Dynamic objects are created with
+/// [Context::create_dynamic_object](crate::Context::create_dynamic_object),
+/// which wraps them in a `Proxy`. Only string keys are forwarded, properties
+/// keyed by symbols are always missing.
+///
+/// ```rust
+/// use quick_js::{Context, DynamicObject, JsError, JsValue};
+/// use std::collections::HashMap;
+///
+/// struct Settings(HashMap<String, JsValue>);
+///
+/// impl DynamicObject for Settings {
+///     fn get(&self, name: &str) -> Option<JsValue> {
+///         self.0.get(name).cloned()
+///     }
+///
+///     fn set(&mut self, name: &str, value: JsValue) -> Result<(), JsError> {
+///         self.0.insert(name.to_string(), value);
+///         Ok(())
+///     }
+///
+///     fn keys(&self) -> Vec<String> {
+///         self.0.keys().cloned().collect()
+///     }
+/// }
+///
+/// let context = Context::new().unwrap();
+/// let settings = context.create_dynamic_object(Settings(HashMap::new())).unwrap();
+/// context.global().unwrap().set("settings", settings).unwrap();
+///
+/// context.eval("settings.theme = 'dark'").unwrap();
+/// assert_eq!(
+///     context.eval("[settings.theme, 'theme' in settings, settings.missing].join()"),
+///     Ok(JsValue::from("dark,true,"))
+/// );
+/// ```
+pub trait DynamicObject: 'static {
+    /// Get the value of a property, or `None` if the object does not have
+    /// the property.
+    fn get(&self, name: &str) -> Option<JsValue>;
+
+    /// Set the value of a property.
+    ///
+    /// The default implementation throws a `TypeError`, which makes the
+    /// object read-only.
+    fn set(&mut self, name: &str, _value: JsValue) -> Result<(), JsError> {
+        Err(JsError::type_error(format!(
+            "Cannot set property {} of a read-only object",
+            name
+        )))
+    }
+
+    /// The names of the properties, used for enumerating the object, like
+    /// with `Object.keys` or `for...in`.
+    fn keys(&self) -> Vec<String>;
+
+    /// Check if the object has a property.
+    ///
+    /// The default implementation checks if [get](DynamicObject::get)
+    /// returns a value.
+    fn has(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Delete a property, returning `false` if it can not be deleted.
+    ///
+    /// The default implementation does not allow deleting properties.
+    fn delete(&mut self, _name: &str) -> Result<bool, JsError> {
+        Ok(false)
+    }
+}
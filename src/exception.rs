@@ -0,0 +1,212 @@
+use std::fmt;
+
+use crate::JsValue;
+
+/// A frame of the stack trace of a [JsException].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct StackFrame {
+    /// The name of the function, eg `<eval>` for the top level of a script
+    /// or `<anonymous>` for anonymous functions.
+    ///
+    /// Empty for the location of a syntax error.
+    pub function: String,
+    /// The filename passed to eval, or `None` for native functions.
+    pub file: Option<String>,
+    /// The line number, starting at 1.
+    ///
+    /// QuickJS does not always know the line of the innermost frame.
+    pub line: Option<u32>,
+    /// The column number, starting at 1, if reported by the engine.
+    pub column: Option<u32>,
+}
+
+impl StackFrame {
+    /// Parse a line of a QuickJS backtrace, eg `    at foo (script.js:3)`.
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let line = line.strip_prefix("at ")?;
+
+        let (function, location) = match line.find(" (") {
+            Some(index) if line.ends_with(')') => {
+                (&line[..index], &line[index + 2..line.len() - 1])
+            }
+            _ => ("", line),
+        };
+
+        let mut frame = StackFrame {
+            function: function.to_string(),
+            file: None,
+            line: None,
+            column: None,
+        };
+        if location == "native" {
+            return Some(frame);
+        }
+
+        let mut file = location;
+        let mut numbers = Vec::new();
+        while numbers.len() < 2 {
+            match file.rfind(':') {
+                Some(index) => match file[index + 1..].parse::<u32>() {
+                    Ok(number) => {
+                        numbers.push(number);
+                        file = &file[..index];
+                    }
+                    Err(_) => break,
+                },
+                None => break,
+            }
+        }
+        match numbers.as_slice() {
+            // Stripped functions report line 0.
+            [0] | [_, 0] => {}
+            [line] => frame.line = Some(*line),
+            [column, line] => {
+                frame.line = Some(*line);
+                frame.column = Some(*column);
+            }
+            _ => {}
+        }
+        frame.file = Some(file.to_string());
+        Some(frame)
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.function.is_empty() {
+            write!(f, "{} (", self.function)?;
+        }
+        match &self.file {
+            Some(file) => {
+                write!(f, "{}", file)?;
+                if let Some(line) = self.line {
+                    write!(f, ":{}", line)?;
+                }
+                if let Some(column) = self.column {
+                    write!(f, ":{}", column)?;
+                }
+            }
+            None => write!(f, "native")?,
+        }
+        if !self.function.is_empty() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// A Javascript exception, returned as [ExecutionError::Exception](crate::ExecutionError::Exception).
+///
+/// For thrown `Error` objects, the name, message and stack trace are
+/// captured. Other thrown values only have a `value`.
+///
+/// ```rust
+/// use quick_js::{Context, ExecutionError};
+///
+/// let context = Context::new().unwrap();
+/// let err = context.eval("function fail() { throw new TypeError('bad') }\nfail()").unwrap_err();
+/// match err {
+///     ExecutionError::Exception(e) => {
+///         assert_eq!(e.name, "TypeError");
+///         assert_eq!(e.message, "bad");
+///         assert_eq!(e.stack[0].function, "fail");
+///         assert_eq!(e.stack[1].function, "<eval>");
+///         assert_eq!(e.stack[1].file.as_deref(), Some("script.js"));
+///         assert_eq!(e.stack[1].line, Some(2));
+///     }
+///     _ => panic!("expected an exception"),
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct JsException {
+    /// The thrown value. Errors are converted to a string of the form
+    /// `"name: message"`.
+    pub value: JsValue,
+    /// The name of the error, eg `TypeError`. Empty if the thrown value is
+    /// not an error.
+    pub name: String,
+    /// The message of the error. If the thrown value is not an error, this is
+    /// the value converted to a string.
+    pub message: String,
+    /// The stack trace, innermost frame first.
+    pub stack: Vec<StackFrame>,
+}
+
+impl JsException {
+    /// Parse a QuickJS backtrace, as found in the `stack` property of errors.
+    pub(crate) fn parse_stack(stack: &str) -> Vec<StackFrame> {
+        stack.lines().filter_map(StackFrame::parse).collect()
+    }
+}
+
+/// Exceptions are equal if their values are equal.
+///
+/// The stack trace is not compared, so exceptions thrown from different
+/// locations compare equal.
+impl PartialEq for JsException {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl From<JsValue> for JsException {
+    fn from(value: JsValue) -> Self {
+        let message = match &value {
+            JsValue::String(message) => message.clone(),
+            _ => String::new(),
+        };
+        Self {
+            value,
+            name: String::new(),
+            message,
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl From<String> for JsException {
+    fn from(message: String) -> Self {
+        JsValue::String(message).into()
+    }
+}
+
+impl<'a> From<&'a str> for JsException {
+    fn from(message: &'a str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<JsValue> for Box<JsException> {
+    fn from(value: JsValue) -> Self {
+        Box::new(value.into())
+    }
+}
+
+impl From<String> for Box<JsException> {
+    fn from(message: String) -> Self {
+        Box::new(message.into())
+    }
+}
+
+impl<'a> From<&'a str> for Box<JsException> {
+    fn from(message: &'a str) -> Self {
+        Box::new(message.into())
+    }
+}
+
+/// Formats the exception like QuickJS, as `name: message` followed by the
+/// stack trace.
+impl fmt::Display for JsException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "{}", self.message)?;
+        } else {
+            write!(f, "{}: {}", self.name, self.message)?;
+        }
+        for frame in &self.stack {
+            write!(f, "\n    at {}", frame)?;
+        }
+        Ok(())
+    }
+}
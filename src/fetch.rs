@@ -0,0 +1,109 @@
+//! `fetch()` integration, with requests sent by the host.
+//! See the [HttpHandler] trait for more info.
+//!
+//! Requires the `fetch` feature.
+
+/// A request made by Javascript with `fetch()`.
+///
+/// Header names are lowercase, and the body is `None` if `fetch()` was
+/// called without one.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Request {
+    /// The uppercase method, eg `GET`.
+    pub method: String,
+    /// The url as passed to `fetch()`.
+    pub url: String,
+    /// The request headers, sorted by name.
+    pub headers: Vec<(String, String)>,
+    /// The request body. String bodies are UTF-8 encoded.
+    pub body: Option<Vec<u8>>,
+}
+
+impl Request {
+    /// Get the value of a header, with a case-insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The response to a [Request], returned by an [HttpHandler].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Response {
+    /// The status code, eg `200`.
+    pub status: u16,
+    /// The status message, eg `OK`.
+    pub status_text: String,
+    /// The response headers.
+    pub headers: Vec<(String, String)>,
+    /// The response body.
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Create a response without headers and status text.
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            status_text: String::new(),
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Add a header to the response.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// A handler that sends the requests made with `fetch()` in Javascript.
+///
+/// A handler is registered via the `ContextBuilder::http_handler` method,
+/// which installs `fetch`, `Headers` and `Response`. Without a handler,
+/// `fetch` is not defined, so all network access of scripts goes through the
+/// embedder.
+///
+/// The handler is called synchronously when `fetch()` is called, and the
+/// returned promise settles when pending jobs are executed. If the handler
+/// returns an error, the promise is rejected with a `TypeError` containing
+/// the error message, like browsers do for network errors. HTTP error
+/// statuses are not errors.
+///
+/// Note that any closure of type `Fn(Request) -> Result<Response, E>`
+/// implements this trait, where `E` implements `Display`.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue, fetch::{Request, Response}};
+///
+/// let context = Context::builder()
+///     .http_handler(|request: Request| match request.url.as_str() {
+///         "https://example.com/answer" => Ok(Response::new(200, r#"{"answer": "yes"}"#)
+///             .header("content-type", "application/json")),
+///         _ => Err(format!("{} is not allowed", request.url)),
+///     })
+///     .build()
+///     .unwrap();
+///
+/// let value = context
+///     .eval("fetch('https://example.com/answer').then(r => r.json()).then(v => v.answer)")
+///     .unwrap();
+/// assert_eq!(value, JsValue::from("yes"));
+/// ```
+pub trait HttpHandler: std::panic::RefUnwindSafe + 'static {
+    /// Send a request, and return the response.
+    fn fetch(&self, request: Request) -> Result<Response, String>;
+}
+
+impl<F, E> HttpHandler for F
+where
+    F: Fn(Request) -> Result<Response, E> + std::panic::RefUnwindSafe + 'static,
+    E: std::fmt::Display,
+{
+    fn fetch(&self, request: Request) -> Result<Response, String> {
+        (self)(request).map_err(|e| e.to_string())
+    }
+}
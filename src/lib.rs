@@ -5,7 +5,9 @@
 //!
 //! ## Limitations
 //!
-//! * Windows is not supported yet
+//! * On Windows, the bundled build requires the `x86_64-pc-windows-gnu` target with
+//!   [MSYS2](https://www.msys2.org/). MSVC targets are only supported with a system installation
+//!   of quickjs, eg from vcpkg
 //!
 //! ## Quickstart:
 //!
@@ -35,16 +37,114 @@
 #![deny(missing_docs)]
 
 mod bindings;
+pub mod bytecode;
 mod callback;
+mod class;
 pub mod console;
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod derive;
 mod droppable_value;
+mod dynamic;
+mod exception;
+#[cfg(feature = "fetch")]
+pub mod fetch;
+pub mod module;
+pub mod pool;
+pub mod realm;
+pub mod snapshot;
+pub mod time;
 mod value;
+// Requires threads, which WASI does not support.
+#[cfg(not(target_os = "wasi"))]
+pub mod worker;
 
-use std::{convert::TryFrom, error, fmt};
+use std::{
+    alloc::{GlobalAlloc, System},
+    convert::TryFrom,
+    error, fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 
-pub use callback::{Arguments, Callback};
+pub use callback::{
+    Arguments, AsyncCallback, Callback, CallbackArgs, CallbackContext, CallbackFuture, JsError,
+};
+pub use class::ClassBuilder;
+pub use dynamic::DynamicObject;
+pub use exception::{JsException, StackFrame};
 pub use value::*;
 
+/// Derive macros for converting structs from and to Javascript objects,
+/// with the `derive` feature.
+///
+/// `IntoJsValue` converts a struct to a `JsValue::Object`, and `FromJsValue`
+/// implements `TryFrom<JsValue>` for it, which also implements the
+/// [FromJsValue](trait@FromJsValue) trait. The fields can be customized
+/// with `#[js(rename = "name")]`, `#[js(default)]` and `#[js(skip)]`.
+///
+/// Enums are externally tagged by default, like with serde, or internally
+/// tagged with `#[js(tag = "type")]`, or untagged with `#[js(untagged)]`:
+///
+/// ```rust
+/// use quick_js::{Context, FromJsValue, IntoJsValue};
+///
+/// #[derive(IntoJsValue, FromJsValue, PartialEq, Debug)]
+/// #[js(tag = "type")]
+/// enum Event {
+///     Click { x: i32, y: i32 },
+///     #[js(rename = "close")]
+///     Close,
+/// }
+///
+/// let context = Context::new().unwrap();
+/// let event: Event = context.eval_as("({ type: 'Click', x: 1, y: 2 })").unwrap();
+/// assert_eq!(event, Event::Click { x: 1, y: 2 });
+/// let event: Event = context.eval_as("({ type: 'close' })").unwrap();
+/// assert_eq!(event, Event::Close);
+/// ```
+///
+/// ```rust
+/// use quick_js::{Context, FromJsValue, IntoJsValue, JsValue};
+///
+/// #[derive(IntoJsValue, FromJsValue, PartialEq, Debug)]
+/// struct User {
+///     name: String,
+///     #[js(rename = "isAdmin")]
+///     is_admin: bool,
+///     #[js(default)]
+///     tags: Vec<String>,
+///     email: Option<String>,
+///     #[js(skip)]
+///     cached: Option<i32>,
+/// }
+///
+/// let context = Context::new().unwrap();
+/// context.add_callback("promote", |mut user: User| {
+///     user.is_admin = true;
+///     user
+/// }).unwrap();
+///
+/// let user: User = context.eval_as("promote({ name: 'Ada', isAdmin: false })").unwrap();
+/// assert_eq!(user, User {
+///     name: "Ada".into(),
+///     is_admin: true,
+///     tags: vec![],
+///     email: None,
+///     cached: None,
+/// });
+/// ```
+#[cfg(feature = "derive")]
+pub use quick_js_derive::{FromJsValue, IntoJsValue};
+
+// The derive macros refer to the crate by name.
+#[cfg(feature = "derive")]
+extern crate self as quick_js;
+
 /// Error on Javascript execution.
 #[derive(PartialEq, Debug)]
 pub enum ExecutionError {
@@ -55,9 +155,29 @@ pub enum ExecutionError {
     /// Internal error.
     Internal(String),
     /// JS Exception was thrown.
-    Exception(JsValue),
+    ///
+    /// See [JsException] for the name, message and stack trace of errors.
+    Exception(Box<JsException>),
     /// JS Runtime exceeded the memory limit.
     OutOfMemory,
+    /// Execution was aborted by the interrupt handler.
+    /// See [Runtime::set_interrupt_handler](Runtime::set_interrupt_handler).
+    Interrupted,
+    /// Execution took longer than the configured timeout.
+    /// See [ContextBuilder::execution_timeout](ContextBuilder::execution_timeout).
+    TimedOut,
+    /// Execution was cancelled with a [CancellationHandle](CancellationHandle).
+    Cancelled,
+    /// Execution exceeded the fuel limit.
+    /// See [EvalOptions::max_instructions](EvalOptions::max_instructions).
+    OutOfFuel,
+    /// Execution exceeded the maximum stack size, eg because of deep
+    /// recursion.
+    /// See [Runtime::set_max_stack_size](Runtime::set_max_stack_size).
+    StackOverflow,
+    /// Bytecode could not be executed, eg because it was compiled by a
+    /// different version of quick-js.
+    Bytecode(bytecode::BytecodeError),
     #[doc(hidden)]
     __NonExhaustive,
 }
@@ -69,8 +189,14 @@ impl fmt::Display for ExecutionError {
             InputWithZeroBytes => write!(f, "Invalid script input: code contains zero byte (\\0)"),
             Conversion(e) => e.fmt(f),
             Internal(e) => write!(f, "Internal error: {}", e),
-            Exception(e) => write!(f, "{:?}", e),
+            Exception(e) => write!(f, "{:?}", e.value),
             OutOfMemory => write!(f, "Out of memory: runtime memory limit exceeded"),
+            Interrupted => write!(f, "Execution was interrupted"),
+            TimedOut => write!(f, "Execution timed out"),
+            Cancelled => write!(f, "Execution was cancelled"),
+            OutOfFuel => write!(f, "Execution ran out of fuel"),
+            StackOverflow => write!(f, "Stack overflow: maximum stack size exceeded"),
+            Bytecode(e) => e.fmt(f),
             __NonExhaustive => unreachable!(),
         }
     }
@@ -84,6 +210,12 @@ impl From<ValueError> for ExecutionError {
     }
 }
 
+impl From<bytecode::BytecodeError> for ExecutionError {
+    fn from(e: bytecode::BytecodeError) -> Self {
+        ExecutionError::Bytecode(e)
+    }
+}
+
 /// Error on context creation.
 #[derive(Debug)]
 pub enum ContextError {
@@ -111,814 +243,7964 @@ impl fmt::Display for ContextError {
 
 impl error::Error for ContextError {}
 
-/// A builder for [Context](Context).
-///
-/// Create with [Context::builder](Context::builder).
-pub struct ContextBuilder {
-    memory_limit: Option<usize>,
-    console_backend: Option<Box<dyn console::ConsoleBackend>>,
+/// Error returned by [Context::check_syntax](Context::check_syntax).
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SyntaxError {
+    /// The error message, eg `unexpected token in expression: ')'`.
+    pub message: String,
+    /// The line of the error, starting at 1.
+    pub line: Option<u32>,
+    /// The column of the error, starting at 1.
+    ///
+    /// Only available if reported by the engine.
+    pub column: Option<u32>,
 }
 
-impl ContextBuilder {
-    fn new() -> Self {
-        Self {
-            memory_limit: None,
-            console_backend: None,
+impl SyntaxError {
+    fn from_execution_error(error: ExecutionError) -> Self {
+        match error {
+            ExecutionError::Exception(e) => {
+                let frame = e.stack.iter().find(|frame| frame.line.is_some());
+                Self {
+                    message: if e.name.is_empty() || e.name == "SyntaxError" {
+                        e.message.clone()
+                    } else {
+                        format!("{}: {}", e.name, e.message)
+                    },
+                    line: frame.and_then(|frame| frame.line),
+                    column: frame.and_then(|frame| frame.column),
+                }
+            }
+            other => Self {
+                message: other.to_string(),
+                line: None,
+                column: None,
+            },
         }
     }
+}
 
-    /// Sets the memory limit of the Javascript runtime (in bytes).
-    ///
-    /// If the limit is exceeded, methods like `eval` will return
-    /// a `Err(ExecutionError::Exception(JsValue::Null))`
-    // TODO: investigate why we don't get a proper exception message here.
-    pub fn memory_limit(self, max_bytes: usize) -> Self {
-        let mut s = self;
-        s.memory_limit = Some(max_bytes);
-        s
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SyntaxError: {}", self.message)?;
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, " at {}:{}", line, column),
+            (Some(line), None) => write!(f, " at line {}", line),
+            _ => Ok(()),
+        }
     }
+}
 
-    /// Set a console handler that will proxy `console.{log,trace,debug,...}`
-    /// calls.
+impl error::Error for SyntaxError {}
+
+/// Memory usage statistics of a [Runtime](Runtime).
+///
+/// Returned by [Runtime::memory_usage](Runtime::memory_usage). Sizes are in
+/// bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MemoryUsage {
+    /// Total size of all allocations, including allocator overhead.
+    pub malloc_size: i64,
+    /// The memory limit, or `-1` if unlimited.
+    pub malloc_limit: i64,
+    /// Size of all allocations, excluding allocator overhead.
+    pub memory_used_size: i64,
+    /// Number of allocations.
+    pub malloc_count: i64,
+    /// Number of allocations, including internal bookkeeping.
+    pub memory_used_count: i64,
+    /// Number of atoms (interned strings like property names).
+    pub atom_count: i64,
+    /// Size of all atoms.
+    pub atom_size: i64,
+    /// Number of strings.
+    pub str_count: i64,
+    /// Size of all strings.
+    pub str_size: i64,
+    /// Number of objects.
+    pub obj_count: i64,
+    /// Size of all objects.
+    pub obj_size: i64,
+    /// Number of object properties.
+    pub prop_count: i64,
+    /// Size of all object properties.
+    pub prop_size: i64,
+    /// Number of object shapes.
+    pub shape_count: i64,
+    /// Size of all object shapes.
+    pub shape_size: i64,
+    /// Number of Javascript functions.
+    pub js_func_count: i64,
+    /// Size of all Javascript functions.
+    pub js_func_size: i64,
+    /// Size of the bytecode of all Javascript functions.
+    pub js_func_code_size: i64,
+    /// Number of functions with line number information.
+    pub js_func_pc2line_count: i64,
+    /// Size of the line number information.
+    pub js_func_pc2line_size: i64,
+    /// Number of native functions.
+    pub c_func_count: i64,
+    /// Number of arrays.
+    pub array_count: i64,
+    /// Number of arrays using the fast (dense) representation.
+    pub fast_array_count: i64,
+    /// Number of elements in fast arrays.
+    pub fast_array_elements: i64,
+    /// Number of binary objects (`ArrayBuffer`s and typed arrays).
+    pub binary_object_count: i64,
+    /// Size of all binary objects.
+    pub binary_object_size: i64,
+}
+
+/// Statistics of the memory allocated by a runtime.
+///
+/// Returned by [Runtime::allocation_stats](Runtime::allocation_stats), and
+/// per evaluation by [Context::eval_metered](Context::eval_metered). Sizes
+/// are in bytes, and include the allocator bookkeeping of the crate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AllocationStats {
+    /// The number of bytes currently allocated.
+    pub current_bytes: usize,
+    /// The highest number of bytes allocated at the same time.
+    pub peak_bytes: usize,
+    /// The total number of bytes allocated, including memory that was freed
+    /// again.
+    pub total_bytes: u64,
+    /// The number of allocations, including reallocations.
+    pub allocation_count: u64,
+}
+
+/// A QuickJS runtime.
+///
+/// A runtime owns the memory heap and runtime-wide settings, such as the
+/// memory limit. Multiple [Context](Context)s can be created in a single
+/// runtime, which makes them share these settings.
+///
+/// Cloning a `Runtime` creates a new handle to the same runtime.
+/// The runtime is kept alive until all handles and all contexts
+/// created in it are dropped.
+///
+/// ```rust
+/// use quick_js::{JsValue, Runtime};
+///
+/// let runtime = Runtime::new().unwrap();
+/// runtime.set_memory_limit(1_000_000);
+///
+/// let context1 = runtime.new_context().unwrap();
+/// let context2 = runtime.new_context().unwrap();
+///
+/// context1.eval(" var x = 1; ").unwrap();
+/// // Each context has its own global scope.
+/// assert!(context2.eval(" x ").is_err());
+/// ```
+///
+/// # Isolation
+///
+/// Contexts are much cheaper than runtimes, so a context per tenant is a
+/// lightweight way to isolate untrusted scripts. Each context has its own
+/// global object and its own set of built-in objects like `Object` and
+/// `Array`, while the heap, the garbage collector and settings like the
+/// memory limit are shared.
+///
+/// Plain values, like numbers, strings, arrays and objects, are copied when
+/// they are passed to a context. Handles to Javascript values, like
+/// `JsValue::Function`, `JsValue::Promise`, `JsValue::Symbol` and
+/// `JsValue::Ref`, are passed by reference, and give the receiving context
+/// access to the objects of the context they belong to. Handles can never be
+/// passed to a context of a different runtime. With
+/// [set_context_isolation](Runtime::set_context_isolation), they can only be
+/// passed back to their own context, and values have to be copied with
+/// [Context::transfer](Context::transfer) instead.
+#[derive(Clone)]
+pub struct Runtime {
+    wrapper: Rc<bindings::RuntimeWrapper>,
+}
+
+impl Runtime {
+    /// Create a new runtime with default settings.
+    pub fn new() -> Result<Self, ContextError> {
+        let wrapper = bindings::RuntimeWrapper::new(Box::new(System))?;
+        Ok(Self {
+            wrapper: Rc::new(wrapper),
+        })
+    }
+
+    /// Create a new runtime that allocates its memory with a custom
+    /// allocator, instead of `malloc`.
     ///
-    /// The given argument must implement the [ConsoleBackend] trait.
+    /// Any [GlobalAlloc] can be used, like jemalloc, an arena, or a wrapper
+    /// that tracks the memory used by each tenant. The allocator must not
+    /// panic. It is dropped once the runtime is freed. The memory limit,
+    /// [memory_usage](Runtime::memory_usage) and
+    /// [allocation_stats](Runtime::allocation_stats) keep working.
     ///
-    /// A very simple logger could look like this:
-    pub fn console<B>(mut self, backend: B) -> Self
+    /// ```rust
+    /// use quick_js::{JsValue, Runtime};
+    /// use std::alloc::{GlobalAlloc, Layout, System};
+    /// use std::sync::{
+    ///     atomic::{AtomicUsize, Ordering},
+    ///     Arc,
+    /// };
+    ///
+    /// struct Tracking(Arc<AtomicUsize>);
+    ///
+    /// unsafe impl GlobalAlloc for Tracking {
+    ///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ///         self.0.fetch_add(layout.size(), Ordering::Relaxed);
+    ///         System.alloc(layout)
+    ///     }
+    ///
+    ///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    ///         self.0.fetch_sub(layout.size(), Ordering::Relaxed);
+    ///         System.dealloc(ptr, layout)
+    ///     }
+    /// }
+    ///
+    /// let used = Arc::new(AtomicUsize::new(0));
+    /// let runtime = Runtime::with_allocator(Tracking(used.clone())).unwrap();
+    /// let context = runtime.new_context().unwrap();
+    ///
+    /// let before = used.load(Ordering::Relaxed);
+    /// context.eval("var data = 'x'.repeat(100000)").unwrap();
+    /// assert!(used.load(Ordering::Relaxed) >= before + 100000);
+    ///
+    /// drop(context);
+    /// drop(runtime);
+    /// assert_eq!(used.load(Ordering::Relaxed), 0);
+    /// ```
+    pub fn with_allocator<A>(allocator: A) -> Result<Self, ContextError>
     where
-        B: console::ConsoleBackend,
+        A: GlobalAlloc + 'static,
     {
-        self.console_backend = Some(Box::new(backend));
-        self
+        let wrapper = bindings::RuntimeWrapper::new(Box::new(allocator))?;
+        Ok(Self {
+            wrapper: Rc::new(wrapper),
+        })
     }
 
-    /// Finalize the builder and build a JS Context.
-    pub fn build(self) -> Result<Context, ContextError> {
-        let wrapper = bindings::ContextWrapper::new(self.memory_limit)?;
-        if let Some(be) = self.console_backend {
-            wrapper.set_console(be).map_err(ContextError::Execution)?;
-        }
-        Ok(Context::from_wrapper(wrapper))
+    /// Sets the memory limit of the runtime (in bytes).
+    ///
+    /// The limit applies to all contexts created in this runtime combined.
+    pub fn set_memory_limit(&self, max_bytes: usize) {
+        self.wrapper.set_memory_limit(max_bytes);
     }
-}
 
-/// Context is a wrapper around a QuickJS Javascript context.
-/// It is the primary way to interact with the runtime.
-///
-/// For each `Context` instance a new instance of QuickJS
-/// runtime is created. It means that it is safe to use
-/// different contexts in different threads, but each
-/// `Context` instance must be used only from a single thread.
-pub struct Context {
-    wrapper: bindings::ContextWrapper,
-}
+    /// Sets the number of allocated bytes after which the garbage collector
+    /// runs automatically.
+    ///
+    /// Objects without reference cycles are freed immediately regardless of
+    /// this setting. The default is 256 KiB.
+    pub fn set_gc_threshold(&self, threshold: usize) {
+        self.wrapper.set_gc_threshold(threshold);
+    }
 
-impl Context {
-    fn from_wrapper(wrapper: bindings::ContextWrapper) -> Self {
-        Self { wrapper }
+    /// Run the garbage collector, freeing unreachable reference cycles.
+    ///
+    /// Useful for collecting garbage at idle points, instead of during
+    /// the next execution.
+    pub fn run_gc(&self) {
+        self.wrapper.run_gc();
     }
 
-    /// Create a `ContextBuilder` that allows customization of JS Runtime settings.
+    /// Compute the current memory usage of the runtime.
     ///
-    /// For details, see the methods on `ContextBuilder`.
+    /// The statistics cover all contexts created in this runtime.
     ///
     /// ```rust
-    /// let _context = quick_js::Context::builder()
-    ///     .memory_limit(100_000)
-    ///     .build()
-    ///     .unwrap();
+    /// use quick_js::Runtime;
+    ///
+    /// let runtime = Runtime::new().unwrap();
+    /// let context = runtime.new_context().unwrap();
+    ///
+    /// let before = runtime.memory_usage();
+    /// context.eval("var objects = Array.from({ length: 100 }, () => ({}))").unwrap();
+    /// assert!(runtime.memory_usage().obj_count >= before.obj_count + 100);
     /// ```
-    pub fn builder() -> ContextBuilder {
-        ContextBuilder::new()
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.wrapper.memory_usage()
     }
 
-    /// Create a new Javascript context with default settings.
-    pub fn new() -> Result<Self, ContextError> {
-        let wrapper = bindings::ContextWrapper::new(None)?;
-        Ok(Self::from_wrapper(wrapper))
+    /// Get the statistics of the memory allocated by the runtime, for all
+    /// contexts created in it.
+    ///
+    /// Unlike [memory_usage](Runtime::memory_usage), the statistics are
+    /// tracked while allocating, so they are cheap to get and include the
+    /// peak and total allocations.
+    ///
+    /// ```rust
+    /// use quick_js::Runtime;
+    ///
+    /// let runtime = Runtime::new().unwrap();
+    /// let context = runtime.new_context().unwrap();
+    ///
+    /// context.eval("'x'.repeat(100000).length").unwrap();
+    /// let stats = runtime.allocation_stats();
+    /// assert!(stats.peak_bytes >= stats.current_bytes + 100000);
+    /// ```
+    pub fn allocation_stats(&self) -> AllocationStats {
+        self.wrapper.allocation_stats()
     }
 
-    /// Reset the Javascript engine.
+    /// Sets the maximum stack size that Javascript code may use (in bytes).
     ///
-    /// All state and callbacks will be removed.
-    pub fn reset(self) -> Result<Self, ContextError> {
-        let wrapper = self.wrapper.reset()?;
-        Ok(Self { wrapper })
+    /// Exceeding it, for example with deep recursion, throws an error that
+    /// scripts can catch. If it is not caught, execution fails with
+    /// `ExecutionError::StackOverflow`. The stack is measured from the frame
+    /// that created the runtime, so the limit should leave room for the
+    /// thread's remaining stack. The default is 256 KiB, which is safe for
+    /// threads with the default stack size of Rust.
+    pub fn set_max_stack_size(&self, max_bytes: usize) {
+        self.wrapper.set_max_stack_size(max_bytes);
     }
 
-    /// Evaluates Javascript code and returns the value of the final expression.
+    /// Set the [ModuleLoader](module::ModuleLoader) used to resolve `import`
+    /// statements of all contexts in this runtime.
     ///
-    /// **Promises**:
-    /// If the evaluated code returns a Promise, the event loop
-    /// will be executed until the promise is finished. The final value of
-    /// the promise will be returned, or a `ExecutionError::Exception` if the
-    /// promise failed.
+    /// Replaces any previously set loader.
+    pub fn set_module_loader<L>(&self, loader: L)
+    where
+        L: module::ModuleLoader,
+    {
+        self.wrapper.set_module_loader(Box::new(loader));
+    }
+
+    /// Set the [BytecodeCache](module::BytecodeCache) for the modules
+    /// loaded by the module loader of this runtime, which skips parsing
+    /// modules whose bytecode is cached.
     ///
-    /// ```rust
-    /// use quick_js::{Context, JsValue};
-    /// let context = Context::new().unwrap();
+    /// Replaces any previously set cache.
     ///
-    /// let value = context.eval(" 1 + 2 + 3 ");
-    /// assert_eq!(
-    ///     value,
-    ///     Ok(JsValue::Int(6)),
-    /// );
+    /// # Safety
     ///
-    /// let value = context.eval(r#"
-    ///     function f() { return 55 * 3; }
-    ///     let y = f();
-    ///     var x = y.toString() + "!"
-    ///     x
-    /// "#);
-    /// assert_eq!(
-    ///     value,
-    ///     Ok(JsValue::String("165!".to_string())),
-    /// );
+    /// QuickJS does not validate bytecode, so executing malformed or
+    /// malicious bytecode can cause undefined behaviour. The cache must only
+    /// return bytecode that it was given. Bytecode of other
+    /// [versions](bytecode::version) of quick-js is detected and compiled
+    /// again.
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use quick_js::{Context, JsValue, module::{MemoryBytecodeCache, ModuleSource}};
+    ///
+    /// let cache = Arc::new(MemoryBytecodeCache::new());
+    /// for _ in 0..2 {
+    ///     let context = Context::new().unwrap();
+    ///     context.set_module_loader(|name: &str| match name {
+    ///         "answer.js" => Ok(ModuleSource::from("export const answer = 42;")),
+    ///         _ => Err("not found"),
+    ///     });
+    ///     // The cache only contains bytecode compiled by quick-js itself.
+    ///     unsafe { context.runtime().set_bytecode_cache(cache.clone()) };
+    ///     let main = "import { answer } from 'answer.js'; globalThis.x = answer;";
+    ///     context.eval_module(main, "main.js").unwrap();
+    ///     assert_eq!(context.eval("x").unwrap(), JsValue::Int(42));
+    /// }
+    /// assert_eq!(cache.len(), 1);
     /// ```
-    pub fn eval(&self, code: &str) -> Result<JsValue, ExecutionError> {
-        let value_raw = self.wrapper.eval(code)?;
-        let value = value_raw.to_value()?;
-        Ok(value)
+    pub unsafe fn set_bytecode_cache<C>(&self, cache: C)
+    where
+        C: module::BytecodeCache,
+    {
+        self.wrapper.set_bytecode_cache(Arc::new(cache));
     }
 
-    /// Evaluates Javascript code and returns the value of the final expression
-    /// as a Rust type.
+    /// Set a handler that is called periodically during the execution of
+    /// Javascript code in any context of this runtime.
     ///
-    /// **Promises**:
-    /// If the evaluated code returns a Promise, the event loop
-    /// will be executed until the promise is finished. The final value of
-    /// the promise will be returned, or a `ExecutionError::Exception` if the
-    /// promise failed.
+    /// If the handler returns `true`, execution is aborted with an
+    /// `ExecutionError::Interrupted`. The resulting exception can not be
+    /// caught by the script. A panicking handler also aborts execution.
+    ///
+    /// Replaces any previously set handler.
     ///
     /// ```rust
-    /// use quick_js::{Context};
+    /// use quick_js::{Context, ExecutionError};
+    /// use std::sync::{
+    ///     atomic::{AtomicBool, Ordering},
+    ///     Arc,
+    /// };
+    ///
     /// let context = Context::new().unwrap();
     ///
-    /// let res = context.eval_as::<bool>(" 100 > 10 ");
-    /// assert_eq!(
-    ///     res,
-    ///     Ok(true),
-    /// );
+    /// // The flag may be set from another thread.
+    /// let cancelled = Arc::new(AtomicBool::new(true));
+    /// let flag = cancelled.clone();
+    /// context.set_interrupt_handler(move || flag.load(Ordering::Relaxed));
     ///
-    /// let value: i32 = context.eval_as(" 10 + 10 ").unwrap();
-    /// assert_eq!(
-    ///     value,
-    ///     20,
-    /// );
+    /// assert_eq!(context.eval("while (true) {}"), Err(ExecutionError::Interrupted));
     /// ```
-    pub fn eval_as<R>(&self, code: &str) -> Result<R, ExecutionError>
+    pub fn set_interrupt_handler<F>(&self, handler: F)
     where
-        R: TryFrom<JsValue>,
-        R::Error: Into<ValueError>,
+        F: Fn() -> bool + std::panic::RefUnwindSafe + 'static,
     {
-        let value_raw = self.wrapper.eval(code)?;
-        let value = value_raw.to_value()?;
-        let ret = R::try_from(value).map_err(|e| e.into())?;
-        Ok(ret)
+        self.wrapper.set_interrupt_handler(Box::new(handler));
     }
 
-    /// Call a global function in the Javascript namespace.
+    /// Create a new context with default settings in this runtime.
     ///
-    /// **Promises**:
-    /// If the evaluated code returns a Promise, the event loop
-    /// will be executed until the promise is finished. The final value of
-    /// the promise will be returned, or a `ExecutionError::Exception` if the
-    /// promise failed.
+    /// To customize the context, use
+    /// [ContextBuilder::runtime](ContextBuilder::runtime).
+    pub fn new_context(&self) -> Result<Context, ContextError> {
+        let wrapper = bindings::ContextWrapper::new(self.wrapper.clone(), Default::default())?;
+        Ok(Context::from_wrapper(wrapper))
+    }
+
+    /// Get handles to the contexts of this runtime, in the order they were
+    /// created.
+    ///
+    /// A context is freed once all its handles, and all values referencing
+    /// it, like `JsValue::Function`s, are dropped, so dropped contexts may
+    /// still be listed while such values are alive.
     ///
     /// ```rust
-    /// use quick_js::{Context, JsValue};
-    /// let context = Context::new().unwrap();
+    /// use quick_js::Runtime;
     ///
-    /// let res = context.call_function("encodeURIComponent", vec!["a=b"]);
-    /// assert_eq!(
-    ///     res,
-    ///     Ok(JsValue::String("a%3Db".to_string())),
-    /// );
+    /// let runtime = Runtime::new().unwrap();
+    /// let tenant1 = runtime.new_context().unwrap();
+    /// let tenant2 = runtime.new_context().unwrap();
+    /// assert_eq!(runtime.contexts().len(), 2);
+    ///
+    /// drop(tenant1);
+    /// let contexts = runtime.contexts();
+    /// assert_eq!(contexts.len(), 1);
+    /// assert!(contexts[0].ptr_eq(&tenant2));
     /// ```
-    pub fn call_function(
-        &self,
-        function_name: &str,
-        args: impl IntoIterator<Item = impl Into<JsValue>>,
-    ) -> Result<JsValue, ExecutionError> {
-        let qargs = args
+    pub fn contexts(&self) -> Vec<Context> {
+        self.wrapper
+            .contexts()
             .into_iter()
-            .map(|arg| self.wrapper.serialize_value(arg.into()))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let global = self.wrapper.global()?;
-        let func_obj = global.property(function_name)?;
-
-        if !func_obj.is_object() {
-            return Err(ExecutionError::Internal(format!(
-                "Could not find function '{}' in global scope: does not exist, or not an object",
-                function_name
-            )));
-        }
-
-        let value = self.wrapper.call_function(func_obj, qargs)?.to_value()?;
-        Ok(value)
+            .map(Context::from_wrapper)
+            .collect()
     }
 
-    /// Add a global JS function that is backed by a Rust function or closure.
+    /// Only allow handles to Javascript values to be passed to the context
+    /// they belong to.
     ///
-    /// The callback must satisfy several requirements:
-    /// * accepts 0 - 5 arguments
-    /// * each argument must be convertible from a JsValue
-    /// * must return a value
-    /// * the return value must either:
-    ///   - be convertible to JsValue
-    ///   - be a Result<T, E> where T is convertible to JsValue
-    ///     if Err(e) is returned, a Javascript exception will be raised
+    /// Passing a handle like `JsValue::Function` to another context of the
+    /// runtime then fails with an `ExecutionError::Conversion`. See
+    /// [Isolation](Runtime#isolation).
     ///
     /// ```rust
-    /// use quick_js::{Context, JsValue};
-    /// let context = Context::new().unwrap();
+    /// use quick_js::{ExecutionError, Runtime};
     ///
-    /// // Register a closue as a callback under the "add" name.
-    /// // The 'add' function can now be called from Javascript code.
-    /// context.add_callback("add", |a: i32, b: i32| { a + b }).unwrap();
+    /// let runtime = Runtime::new().unwrap();
+    /// runtime.set_context_isolation(true);
+    /// let tenant1 = runtime.new_context().unwrap();
+    /// let tenant2 = runtime.new_context().unwrap();
     ///
-    /// // Now we try out the 'add' function via eval.
-    /// let output = context.eval_as::<i32>(" add( 3 , 4 ) ").unwrap();
-    /// assert_eq!(
-    ///     output,
-    ///     7,
-    /// );
+    /// let secret = tenant1.eval("(() => 'secret')").unwrap();
+    /// tenant2.eval("function call(f) { return f(); }").unwrap();
+    /// let res = tenant2.call_function("call", vec![secret]);
+    /// assert!(matches!(res, Err(ExecutionError::Conversion(_))));
     /// ```
-    pub fn add_callback<F>(
-        &self,
-        name: &str,
-        callback: impl Callback<F> + 'static,
-    ) -> Result<(), ExecutionError> {
-        self.wrapper.add_callback(name, callback)
+    pub fn set_context_isolation(&self, enabled: bool) {
+        self.wrapper.set_isolated(enabled);
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
-
-    use super::*;
+/// A handle for cancelling the execution of a [Context](Context) from
+/// another thread.
+///
+/// Created with [Context::cancellation_handle](Context::cancellation_handle).
+/// Calling [cancel](CancellationHandle::cancel) makes the running execution
+/// return `ExecutionError::Cancelled`. The handle is `Send` and `Sync`, and
+/// can be cloned.
+///
+/// Cancellation applies to the [Runtime](Runtime) of the context: the
+/// handles of all contexts sharing a runtime cancel whichever of them is
+/// executing.
+///
+/// ```rust
+/// use quick_js::{Context, ExecutionError};
+/// use std::{
+///     sync::{mpsc, Mutex},
+///     thread,
+/// };
+///
+/// let context = Context::new().unwrap();
+/// let handle = context.cancellation_handle();
+///
+/// // Cancel the script from another thread once it has started.
+/// let (started, receiver) = mpsc::channel();
+/// let started = Mutex::new(started);
+/// context
+///     .add_callback("started", move || started.lock().unwrap().send(()).is_ok())
+///     .unwrap();
+/// let supervisor = thread::spawn(move || {
+///     receiver.recv().unwrap();
+///     handle.cancel();
+/// });
+/// assert_eq!(
+///     context.eval("started(); while (true) {}"),
+///     Err(ExecutionError::Cancelled)
+/// );
+/// supervisor.join().unwrap();
+///
+/// // The context is still usable afterwards.
+/// assert!(context.eval("1 + 1").is_ok());
+/// ```
+#[derive(Clone, Debug)]
+pub struct CancellationHandle {
+    cancelled: Arc<AtomicBool>,
+}
 
-    // #[test]
+impl CancellationHandle {
+    /// Cancel the running execution.
+    ///
+    /// If no script is running, the request is kept until the next
+    /// execution of the runtime, which then fails with
+    /// `ExecutionError::Cancelled` without running. Since the request is
+    /// checked periodically, a running execution may continue shortly after
+    /// this returns.
+    /// Host code, such as a callback or a time source sleeping until a timer
+    /// is due, is not interrupted.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+/// A builder for [Context](Context).
+///
+/// Create with [Context::builder](Context::builder).
+pub struct ContextBuilder {
+    runtime: Option<Runtime>,
+    memory_limit: Option<usize>,
+    max_stack_size: Option<usize>,
+    gc_threshold: Option<usize>,
+    execution_timeout: Option<Duration>,
+    console_backend: Option<Box<dyn console::ConsoleBackend>>,
+    timers: bool,
+    time_source: Option<Box<dyn time::TimeSource>>,
+    #[cfg(feature = "fetch")]
+    http_handler: Option<Box<dyn fetch::HttpHandler>>,
+    disable_eval: bool,
+    abort_on_panic: bool,
+    intrinsics: IntrinsicSet,
+    integer_policy: IntegerPolicy,
+    none_policy: NonePolicy,
+}
+
+impl ContextBuilder {
+    fn new() -> Self {
+        Self {
+            runtime: None,
+            memory_limit: None,
+            max_stack_size: None,
+            gc_threshold: None,
+            execution_timeout: None,
+            console_backend: None,
+            timers: false,
+            time_source: None,
+            #[cfg(feature = "fetch")]
+            http_handler: None,
+            disable_eval: false,
+            abort_on_panic: false,
+            intrinsics: IntrinsicSet::default(),
+            integer_policy: IntegerPolicy::default(),
+            none_policy: NonePolicy::default(),
+        }
+    }
+
+    /// Create the context in an existing [Runtime](Runtime).
+    ///
+    /// By default, a new runtime is created for each context.
+    ///
+    /// Note that runtime-wide settings like `memory_limit` are applied to
+    /// the given runtime, and therefore affect all contexts created in it.
+    pub fn runtime(mut self, runtime: &Runtime) -> Self {
+        self.runtime = Some(runtime.clone());
+        self
+    }
+
+    /// Sets the memory limit of the Javascript runtime (in bytes).
+    ///
+    /// If the limit is exceeded, methods like `eval` will return
+    /// a `Err(ExecutionError::OutOfMemory)`.
+    pub fn memory_limit(self, max_bytes: usize) -> Self {
+        let mut s = self;
+        s.memory_limit = Some(max_bytes);
+        s
+    }
+
+    /// Sets the maximum stack size of the Javascript runtime (in bytes).
+    ///
+    /// See [Runtime::set_max_stack_size](Runtime::set_max_stack_size).
+    ///
+    /// ```rust
+    /// use quick_js::{Context, ExecutionError};
+    ///
+    /// let context = Context::builder().max_stack_size(64 * 1024).build().unwrap();
+    ///
+    /// let res = context.eval("function f(n) { return n && f(n - 1); } f(10000)");
+    /// assert_eq!(res, Err(ExecutionError::StackOverflow));
+    /// ```
+    pub fn max_stack_size(mut self, max_bytes: usize) -> Self {
+        self.max_stack_size = Some(max_bytes);
+        self
+    }
+
+    /// Sets the number of allocated bytes after which the garbage collector
+    /// runs automatically.
+    ///
+    /// See [Runtime::set_gc_threshold](Runtime::set_gc_threshold).
+    pub fn gc_threshold(mut self, threshold: usize) -> Self {
+        self.gc_threshold = Some(threshold);
+        self
+    }
+
+    /// Sets the maximum duration of a single `eval` or `call_function`,
+    /// including the execution of pending jobs while awaiting a promise.
+    ///
+    /// If the timeout is exceeded, execution is aborted with an
+    /// `ExecutionError::TimedOut`. The resulting exception can not be caught
+    /// by the script.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, ExecutionError};
+    /// use std::time::Duration;
+    ///
+    /// let context = Context::builder()
+    ///     .execution_timeout(Duration::from_millis(10))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(context.eval("while (true) {}"), Err(ExecutionError::TimedOut));
+    /// ```
+    pub fn execution_timeout(mut self, timeout: Duration) -> Self {
+        self.execution_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a console handler that will proxy `console.{log,trace,debug,...}`
+    /// calls.
+    ///
+    /// The given argument must implement the
+    /// [ConsoleBackend](console::ConsoleBackend) trait, see there for an
+    /// example. Without a handler, the default backend of the [console]
+    /// module is used.
+    pub fn console<B>(mut self, backend: B) -> Self
+    where
+        B: console::ConsoleBackend,
+    {
+        self.console_backend = Some(Box::new(backend));
+        self
+    }
+
+    /// Install `setTimeout`, `setInterval`, `clearTimeout` and
+    /// `clearInterval`.
+    ///
+    /// Timers are driven by the host: due callbacks run when
+    /// [Context::run_timers](Context::run_timers) is called, and while
+    /// awaiting a promise, eg with `eval` or
+    /// [JsPromise::await_result](JsPromise::await_result), which sleep until
+    /// the next timer is due if there is nothing else to do.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    ///
+    /// let context = Context::builder().timers(true).build().unwrap();
+    /// let value = context.eval("new Promise(resolve => setTimeout(resolve, 10, 'done'))");
+    /// assert_eq!(value, Ok(JsValue::from("done")));
+    /// ```
+    pub fn timers(mut self, enabled: bool) -> Self {
+        self.timers = enabled;
+        self
+    }
+
+    /// Set the clock used by `Date.now()`, `new Date()` and timers.
+    ///
+    /// The given argument must implement the
+    /// [TimeSource](time::TimeSource) trait, see there for details. By
+    /// default, the system clock is used, and timers are scheduled on the
+    /// monotonic clock, so they are not affected by changes of the system
+    /// time.
+    pub fn time_source<T>(mut self, source: T) -> Self
+    where
+        T: time::TimeSource,
+    {
+        self.time_source = Some(Box::new(source));
+        self
+    }
+
+    /// Install `fetch`, `Headers` and `Response`, sending requests with the
+    /// given handler.
+    ///
+    /// Requires the `fetch` feature. The given argument must implement the
+    /// [HttpHandler](fetch::HttpHandler) trait, see there for an example.
+    #[cfg(feature = "fetch")]
+    pub fn http_handler<H>(mut self, handler: H) -> Self
+    where
+        H: fetch::HttpHandler,
+    {
+        self.http_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Disable dynamic code generation in Javascript, eg for running
+    /// untrusted plugins.
+    ///
+    /// `eval`, the `Function` constructor and the constructors of async and
+    /// generator functions throw an `EvalError` instead of compiling code.
+    /// Code evaluated from Rust, like with [Context::eval], is not affected.
+    ///
+    /// ```rust
+    /// use quick_js::Context;
+    ///
+    /// let context = Context::builder().disable_eval(true).build().unwrap();
+    /// assert!(context.eval("eval('1 + 1')").is_err());
+    /// assert!(context.eval("new Function('return 1')").is_err());
+    /// ```
+    pub fn disable_eval(mut self, disabled: bool) -> Self {
+        self.disable_eval = disabled;
+        self
+    }
+
+    /// Abort the process when Rust code called from Javascript panics.
+    ///
+    /// Panics can not unwind through the engine, so by default panics of
+    /// callbacks, futures of async callbacks and module loaders are caught,
+    /// and thrown as a Javascript exception with the message
+    /// `Callback panicked!`, `Future panicked!` or `Module loader panicked!`.
+    /// The context stays usable afterwards. Aborting instead may be
+    /// preferable if the state of the host can not be trusted after a panic.
+    pub fn abort_on_panic(mut self, abort: bool) -> Self {
+        self.abort_on_panic = abort;
+        self
+    }
+
+    /// Select the built-in objects of the context, eg to reduce its memory
+    /// footprint. By default, all built-ins are available.
+    ///
+    /// See [IntrinsicSet](IntrinsicSet) for details.
+    pub fn intrinsics(mut self, intrinsics: IntrinsicSet) -> Self {
+        self.intrinsics = intrinsics;
+        self
+    }
+
+    /// Select how [to_js](to_js) converts integers beyond the safe integer
+    /// range of Javascript numbers. By default, they lose precision.
+    ///
+    /// See [IntegerPolicy](IntegerPolicy) for details.
+    pub fn integer_policy(mut self, policy: IntegerPolicy) -> Self {
+        self.integer_policy = policy;
+        self
+    }
+
+    /// Select whether [to_js](to_js) converts `None` to `null`, which is the
+    /// default, or to `undefined`.
+    ///
+    /// See [NonePolicy](NonePolicy) for details.
+    pub fn none_policy(mut self, policy: NonePolicy) -> Self {
+        self.none_policy = policy;
+        self
+    }
+
+    /// Finalize the builder and build a JS Context.
+    pub fn build(self) -> Result<Context, ContextError> {
+        let runtime = match self.runtime {
+            Some(runtime) => runtime,
+            None => Runtime::new()?,
+        };
+        if let Some(limit) = self.memory_limit {
+            runtime.set_memory_limit(limit);
+        }
+        if let Some(size) = self.max_stack_size {
+            runtime.set_max_stack_size(size);
+        }
+        if let Some(threshold) = self.gc_threshold {
+            runtime.set_gc_threshold(threshold);
+        }
+
+        // The settings are applied as setup steps, so they are restored by
+        // `Context::reset`.
+        let wrapper = bindings::ContextWrapper::new(runtime.wrapper, self.intrinsics)?;
+        if let Some(timeout) = self.execution_timeout {
+            wrapper
+                .setup(move |w| {
+                    w.set_execution_timeout(Some(timeout));
+                    Ok(())
+                })
+                .map_err(ContextError::Execution)?;
+        }
+        if let Some(be) = self.console_backend {
+            let be: Rc<dyn console::ConsoleBackend> = be.into();
+            wrapper
+                .setup(move |w| w.set_console(be.clone()))
+                .map_err(ContextError::Execution)?;
+        }
+        if let Some(source) = self.time_source {
+            let source: Rc<dyn time::TimeSource> = source.into();
+            wrapper
+                .setup(move |w| w.set_time_source(source.clone()))
+                .map_err(ContextError::Execution)?;
+        }
+        if self.timers {
+            wrapper
+                .setup(|w| w.enable_timers())
+                .map_err(ContextError::Execution)?;
+        }
+        #[cfg(feature = "fetch")]
+        {
+            if let Some(handler) = self.http_handler {
+                let handler: Rc<dyn fetch::HttpHandler> = handler.into();
+                wrapper
+                    .setup(move |w| w.set_http_handler(handler.clone()))
+                    .map_err(ContextError::Execution)?;
+            }
+        }
+        if self.disable_eval {
+            wrapper
+                .setup(|w| w.disable_code_generation())
+                .map_err(ContextError::Execution)?;
+        }
+        if self.abort_on_panic {
+            wrapper
+                .setup(|w| {
+                    w.set_abort_on_panic(true);
+                    Ok(())
+                })
+                .map_err(ContextError::Execution)?;
+        }
+        if self.integer_policy != IntegerPolicy::default() {
+            let policy = self.integer_policy;
+            wrapper
+                .setup(move |w| {
+                    w.set_integer_policy(policy);
+                    Ok(())
+                })
+                .map_err(ContextError::Execution)?;
+        }
+        if self.none_policy != NonePolicy::default() {
+            let policy = self.none_policy;
+            wrapper
+                .setup(move |w| {
+                    w.set_none_policy(policy);
+                    Ok(())
+                })
+                .map_err(ContextError::Execution)?;
+        }
+        Ok(Context::from_wrapper(wrapper))
+    }
+}
+
+/// The built-in objects of a context, see
+/// [ContextBuilder::intrinsics](ContextBuilder::intrinsics).
+///
+/// The base objects, like `Object`, `Array`, `Function`, `Error` and `Math`,
+/// and `Promise` are always available. Settings that rely on a built-in fail to build
+/// without it, eg [time_source](ContextBuilder::time_source) needs `Date`.
+///
+/// Use `..IntrinsicSet::minimal()` to only enable some of the built-ins.
+///
+/// ```rust
+/// use quick_js::{Context, IntrinsicSet, JsValue};
+///
+/// let context = Context::builder()
+///     .intrinsics(IntrinsicSet {
+///         json: true,
+///         ..IntrinsicSet::minimal()
+///     })
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(context.eval("JSON.stringify([1])"), Ok(JsValue::from("[1]")));
+/// assert_eq!(context.eval("typeof RegExp"), Ok(JsValue::from("undefined")));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntrinsicSet {
+    /// `Date`.
+    pub date: bool,
+    /// `String.prototype.normalize`.
+    pub string_normalize: bool,
+    /// `RegExp` and regular expression literals.
+    pub regexp: bool,
+    /// `JSON`.
+    pub json: bool,
+    /// `Proxy`.
+    pub proxy: bool,
+    /// `Map`, `Set`, `WeakMap` and `WeakSet`.
+    pub map_set: bool,
+    /// `ArrayBuffer`, the typed arrays, `DataView` and `Atomics`.
+    pub typed_arrays: bool,
+    /// `BigInt` and bigint literals.
+    ///
+    /// Requires the `bignum` feature, which is enabled by default.
+    pub big_int: bool,
+}
+
+impl IntrinsicSet {
+    /// Only the base objects.
+    pub fn minimal() -> Self {
+        Self {
+            date: false,
+            string_normalize: false,
+            regexp: false,
+            json: false,
+            proxy: false,
+            map_set: false,
+            typed_arrays: false,
+            big_int: false,
+        }
+    }
+}
+
+impl Default for IntrinsicSet {
+    /// All built-in objects.
+    fn default() -> Self {
+        Self {
+            date: true,
+            string_normalize: true,
+            regexp: true,
+            json: true,
+            proxy: true,
+            map_set: true,
+            typed_arrays: true,
+            big_int: true,
+        }
+    }
+}
+
+/// Options for [Context::eval_with_options](Context::eval_with_options).
+///
+/// Use `..Default::default()` to only set some of the options.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalOptions {
+    /// The file name used in stack traces and for resolving relative
+    /// imports. Defaults to `script.js`, like [Context::eval](Context::eval).
+    pub filename: String,
+    /// The number of lines preceding the code in the file, eg when the code
+    /// was extracted from a larger file. Line numbers in stack traces start
+    /// at `line_offset + 1`.
+    pub line_offset: u32,
+    /// Evaluate the code in strict mode, as if it started with
+    /// `"use strict"`. Modules are always strict.
+    pub strict: bool,
+    /// Evaluate the code as an ES module.
+    pub module: bool,
+    /// Strip the source and debug information from the compiled code. Saves
+    /// memory, but stack traces have no line numbers, and
+    /// `Function.prototype.toString` does not return the source.
+    pub strip: bool,
+    /// Do not include the frames of the caller in stack traces, eg when
+    /// evaluating code from a callback.
+    pub backtrace_barrier: bool,
+    /// Only compile the code, without executing it. Syntax errors are
+    /// returned, and `JsValue::Null` otherwise.
+    pub compile_only: bool,
+    /// Abort execution with `ExecutionError::OutOfFuel` once it consumed
+    /// more fuel, including the pending jobs run to resolve a returned
+    /// promise.
+    ///
+    /// QuickJS does not count single instructions. Instead, one unit of
+    /// fuel is consumed whenever the interpreter checks for interrupts,
+    /// which happens after about 10000 function calls or loop iterations.
+    /// See [Context::eval_metered](Context::eval_metered) for measuring the
+    /// consumed fuel.
+    pub max_instructions: Option<u64>,
+}
+
+/// The result of [Context::eval_metered](Context::eval_metered), with the
+/// consumed fuel and the allocations.
+#[derive(PartialEq, Debug)]
+pub struct Metered<T> {
+    /// The result of the evaluation.
+    pub result: T,
+    /// The fuel consumed by the evaluation, even if it failed. See
+    /// [EvalOptions::max_instructions](EvalOptions::max_instructions) for
+    /// the unit.
+    pub fuel: u64,
+    /// The allocations of the evaluation. `current_bytes` is the memory
+    /// allocated by the runtime afterwards, `peak_bytes` the highest memory
+    /// allocated during the evaluation, and the totals count the
+    /// allocations of the evaluation.
+    pub allocations: AllocationStats,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        Self {
+            filename: "script.js".into(),
+            line_offset: 0,
+            strict: false,
+            module: false,
+            strip: false,
+            backtrace_barrier: false,
+            compile_only: false,
+            max_instructions: None,
+        }
+    }
+}
+
+/// Context is a wrapper around a QuickJS Javascript context.
+/// It is the primary way to interact with the runtime.
+///
+/// By default, for each `Context` instance a new instance of QuickJS
+/// runtime is created. It means that it is safe to use
+/// different contexts in different threads, but each
+/// `Context` instance must be used only from a single thread.
+///
+/// Contexts that share a [Runtime](Runtime) must all be used from the same
+/// thread.
+pub struct Context {
+    wrapper: Rc<bindings::ContextWrapper>,
+}
+
+/// Reports the handles that keep the context alive, see the `leak-tracking`
+/// feature.
+#[cfg(feature = "leak-tracking")]
+impl Drop for Context {
+    fn drop(&mut self) {
+        if let Some(report) = self.wrapper.leaked_handles() {
+            eprintln!("{}", report);
+        }
+    }
+}
+
+impl Context {
+    fn from_wrapper(wrapper: Rc<bindings::ContextWrapper>) -> Self {
+        Self { wrapper }
+    }
+
+    /// Create a `ContextBuilder` that allows customization of JS Runtime settings.
+    ///
+    /// For details, see the methods on `ContextBuilder`.
+    ///
+    /// ```rust
+    /// let _context = quick_js::Context::builder()
+    ///     .memory_limit(100_000)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+
+    /// Create a new Javascript context with default settings.
+    pub fn new() -> Result<Self, ContextError> {
+        Runtime::new()?.new_context()
+    }
+
+    /// Check if both handles refer to the same context.
+    pub fn ptr_eq(&self, other: &Context) -> bool {
+        Rc::ptr_eq(&self.wrapper, &other.wrapper)
+    }
+
+    /// Get a handle to the [Runtime](Runtime) this context belongs to.
+    pub fn runtime(&self) -> Runtime {
+        Runtime {
+            wrapper: self.wrapper.runtime().clone(),
+        }
+    }
+
+    /// Reset the Javascript engine.
+    ///
+    /// The underlying Javascript context is recreated in the same runtime,
+    /// which removes all global state. The settings of the
+    /// [ContextBuilder](ContextBuilder), and the callbacks and accessors
+    /// added with the `add_callback*` and
+    /// [set_global_property_with_accessors](Context::set_global_property_with_accessors)
+    /// methods are restored, in the order they were added.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    ///
+    /// let context = Context::new().unwrap();
+    /// context.add_callback("double", |x: i32| x * 2).unwrap();
+    /// context.eval("var x = 21;").unwrap();
+    ///
+    /// let context = context.reset().unwrap();
+    /// assert_eq!(context.eval("typeof x"), Ok(JsValue::from("undefined")));
+    /// assert_eq!(context.eval("double(21)"), Ok(JsValue::Int(42)));
+    /// ```
+    pub fn reset(self) -> Result<Self, ContextError> {
+        let runtime = self.wrapper.runtime().clone();
+        let setup = self.wrapper.setup_steps();
+        let intrinsics = self.wrapper.intrinsics();
+        drop(self);
+        let wrapper = bindings::ContextWrapper::new(runtime, intrinsics)?;
+        wrapper
+            .replay_setup(setup)
+            .map_err(ContextError::Execution)?;
+        Ok(Self { wrapper })
+    }
+
+    /// Evaluates Javascript code and returns the value of the final expression.
+    ///
+    /// **Promises**:
+    /// If the evaluated code returns a Promise, the event loop
+    /// will be executed until the promise is finished. The final value of
+    /// the promise will be returned, or a `ExecutionError::Exception` if the
+    /// promise failed.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let value = context.eval(" 1 + 2 + 3 ");
+    /// assert_eq!(
+    ///     value,
+    ///     Ok(JsValue::Int(6)),
+    /// );
+    ///
+    /// let value = context.eval(r#"
+    ///     function f() { return 55 * 3; }
+    ///     let y = f();
+    ///     var x = y.toString() + "!"
+    ///     x
+    /// "#);
+    /// assert_eq!(
+    ///     value,
+    ///     Ok(JsValue::String("165!".to_string())),
+    /// );
+    /// ```
+    pub fn eval(&self, code: &str) -> Result<JsValue, ExecutionError> {
+        let value_raw = self.wrapper.eval(code)?;
+        let value = value_raw.to_value()?;
+        Ok(value)
+    }
+
+    /// Evaluates Javascript code with the given [EvalOptions](EvalOptions)
+    /// and returns the value of the final expression.
+    ///
+    /// Promises are resolved like in [eval](Context::eval). Modules have no
+    /// final expression, so evaluating a module returns `JsValue::Undefined`.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, EvalOptions, ExecutionError};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let options = EvalOptions {
+    ///     filename: "user.js".into(),
+    ///     line_offset: 10,
+    ///     ..Default::default()
+    /// };
+    /// match context.eval_with_options("throw new Error('oops')", options) {
+    ///     Err(ExecutionError::Exception(e)) => {
+    ///         assert_eq!(e.stack[0].file.as_deref(), Some("user.js"));
+    ///         assert_eq!(e.stack[0].line, Some(11));
+    ///     }
+    ///     other => panic!("unexpected result: {:?}", other),
+    /// }
+    /// ```
+    pub fn eval_with_options(
+        &self,
+        code: &str,
+        options: EvalOptions,
+    ) -> Result<JsValue, ExecutionError> {
+        let value = self.wrapper.eval_with_options(code, &options)?;
+        Ok(value.to_value()?)
+    }
+
+    /// Evaluates Javascript code like
+    /// [eval_with_options](Context::eval_with_options), and measures the
+    /// consumed fuel and the allocations, eg to find scripts using a lot of
+    /// memory.
+    ///
+    /// Fuel and allocations are measured for the [Runtime](Runtime), so they
+    /// include nested executions of other contexts in the same runtime.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, EvalOptions, ExecutionError};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let options = EvalOptions {
+    ///     max_instructions: Some(100),
+    ///     ..Default::default()
+    /// };
+    /// let metered = context.eval_metered("let x = 0; for (let i = 0; i < 1e5; i++) { x++; }", options.clone());
+    /// assert!(metered.result.is_ok());
+    /// assert!(metered.fuel > 0 && metered.fuel <= 100);
+    ///
+    /// let metered = context.eval_metered("while (true) {}", options);
+    /// assert_eq!(metered.result, Err(ExecutionError::OutOfFuel));
+    /// assert_eq!(metered.fuel, 101);
+    ///
+    /// let metered = context.eval_metered("'x'.repeat(100000).length", Default::default());
+    /// assert!(metered.allocations.peak_bytes >= metered.allocations.current_bytes + 100000);
+    /// assert!(metered.allocations.total_bytes >= 100000);
+    /// ```
+    pub fn eval_metered(
+        &self,
+        code: &str,
+        options: EvalOptions,
+    ) -> Metered<Result<JsValue, ExecutionError>> {
+        let runtime = self.wrapper.runtime();
+        let start = runtime.interrupt_ticks();
+        let (result, allocations) =
+            runtime.measure_allocations(|| self.eval_with_options(code, options));
+        Metered {
+            result,
+            fuel: runtime.interrupt_ticks() - start,
+            allocations,
+        }
+    }
+
+    /// Checks the syntax of a script, without executing it.
+    ///
+    /// To check a module, use [eval_with_options](Context::eval_with_options)
+    /// with `compile_only` and `module` set.
+    ///
+    /// ```rust
+    /// use quick_js::Context;
+    /// let context = Context::new().unwrap();
+    ///
+    /// assert!(context.check_syntax("let x = 1;").is_ok());
+    ///
+    /// let err = context.check_syntax("let x = 1;\nlet y = (x;").unwrap_err();
+    /// assert!(err.to_string().starts_with("SyntaxError: "));
+    /// #[cfg(not(feature = "ng"))]
+    /// assert_eq!(err.message, "expecting ')'");
+    /// assert_eq!(err.line, Some(2));
+    /// ```
+    pub fn check_syntax(&self, code: &str) -> Result<(), SyntaxError> {
+        let options = EvalOptions {
+            compile_only: true,
+            ..Default::default()
+        };
+        self.eval_with_options(code, options)
+            .map(|_| ())
+            .map_err(SyntaxError::from_execution_error)
+    }
+
+    /// Evaluates Javascript code and returns the value of the final expression
+    /// as a Rust type.
+    ///
+    /// **Promises**:
+    /// If the evaluated code returns a Promise, the event loop
+    /// will be executed until the promise is finished. The final value of
+    /// the promise will be returned, or a `ExecutionError::Exception` if the
+    /// promise failed.
+    ///
+    /// ```rust
+    /// use quick_js::{Context};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let res = context.eval_as::<bool>(" 100 > 10 ");
+    /// assert_eq!(
+    ///     res,
+    ///     Ok(true),
+    /// );
+    ///
+    /// let value: i32 = context.eval_as(" 10 + 10 ").unwrap();
+    /// assert_eq!(
+    ///     value,
+    ///     20,
+    /// );
+    /// ```
+    pub fn eval_as<R>(&self, code: &str) -> Result<R, ExecutionError>
+    where
+        R: TryFrom<JsValue>,
+        R::Error: Into<ValueError>,
+    {
+        let value_raw = self.wrapper.eval(code)?;
+        let value = value_raw.to_value()?;
+        let ret = R::try_from(value).map_err(|e| e.into())?;
+        Ok(ret)
+    }
+
+    /// Evaluates Javascript code and returns the value of the final expression
+    /// as an [OwnedJsValue](OwnedJsValue) handle, without converting it.
+    ///
+    /// Promises are resolved like in [eval](Context::eval).
+    pub fn eval_owned(&self, code: &str) -> Result<OwnedJsValue, ExecutionError> {
+        let value = self.wrapper.eval(code)?;
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+            value,
+        )))
+    }
+
+    /// Parses a JSON string with the native parser, like `JSON.parse`.
+    ///
+    /// This is much faster than building large values with
+    /// [eval](Context::eval) or from a `JsValue`, eg for injecting a big
+    /// payload. Invalid JSON fails with a `SyntaxError` exception.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let value = context.json_parse(r#"{"a": ["b", 2.5, null]}"#).unwrap();
+    /// assert_eq!(
+    ///     value,
+    ///     JsValue::Object(vec![(
+    ///         "a".to_string(),
+    ///         JsValue::Array(vec![JsValue::from("b"), JsValue::Float(2.5), JsValue::Null]),
+    ///     )].into_iter().collect()),
+    /// );
+    ///
+    /// assert!(context.json_parse("{a: 1}").is_err());
+    /// ```
+    pub fn json_parse(&self, json: &str) -> Result<JsValue, ExecutionError> {
+        let value = self.wrapper.json_parse(json)?;
+        Ok(value.to_value()?)
+    }
+
+    /// Converts a value to a JSON string with the native serializer, like
+    /// `JSON.stringify`.
+    ///
+    /// Values without a JSON representation, like `undefined` or functions,
+    /// fail with `ExecutionError::Conversion`.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let value = JsValue::Array(vec![JsValue::Int(1), JsValue::from("a"), JsValue::Undefined]);
+    /// assert_eq!(context.json_stringify(&value).unwrap(), r#"[1,"a",null]"#);
+    ///
+    /// assert!(context.json_stringify(&JsValue::Undefined).is_err());
+    /// ```
+    pub fn json_stringify(&self, value: &JsValue) -> Result<String, ExecutionError> {
+        let value = self.wrapper.serialize_value(value.clone())?;
+        self.wrapper
+            .json_stringify(&value)?
+            .ok_or_else(|| ValueError::Custom("Value can not be represented as JSON".into()).into())
+    }
+
+    /// Compiles Javascript code to bytecode, without executing it.
+    ///
+    /// The `name` is used as the file name in stack traces. The bytecode can
+    /// be stored and later executed with
+    /// [eval_compiled](Context::eval_compiled), skipping the parsing step.
+    /// It starts with the [version](bytecode::version) of quick-js, see
+    /// [is_compatible](bytecode::is_compatible).
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let bytecode = context.compile("1 + 2", "add.js").unwrap();
+    /// let value = unsafe { context.eval_compiled(&bytecode) };
+    /// assert_eq!(value, Ok(JsValue::Int(3)));
+    /// ```
+    pub fn compile(&self, code: &str, name: &str) -> Result<Vec<u8>, ExecutionError> {
+        let bytecode = self.wrapper.compile(code, name)?;
+        Ok(bytecode::tag(&bytecode))
+    }
+
+    /// Loads a [Snapshot](snapshot::Snapshot) by executing its scripts and
+    /// modules in order.
+    ///
+    /// Callbacks and the module loader used by the scripts must be set up
+    /// before. See [Snapshot](snapshot::Snapshot) for an example.
+    pub fn load_snapshot(&self, snapshot: &snapshot::Snapshot) -> Result<(), ExecutionError> {
+        for script in &snapshot.scripts {
+            // Snapshots are either recorded, or deserialized from a trusted
+            // source with the unsafe `Snapshot::from_bytes`.
+            unsafe { self.wrapper.eval_compiled(script)? };
+        }
+        Ok(())
+    }
+
+    /// Executes bytecode created by [compile](Context::compile) and returns
+    /// the value of the final expression.
+    ///
+    /// Promises are resolved like in [eval](Context::eval). Fails with an
+    /// `ExecutionError::Bytecode` if the bytecode was compiled by a
+    /// different [version](bytecode::version) of quick-js, without executing
+    /// it.
+    ///
+    /// # Safety
+    ///
+    /// QuickJS does not validate bytecode, so executing malformed or
+    /// malicious bytecode can cause undefined behaviour. Only execute
+    /// bytecode from a trusted source.
+    pub unsafe fn eval_compiled(&self, bytecode: &[u8]) -> Result<JsValue, ExecutionError> {
+        let bytecode = bytecode::untag(bytecode)?;
+        let value = self.wrapper.eval_compiled(bytecode)?;
+        Ok(value.to_value()?)
+    }
+
+    /// Evaluates Javascript code and returns a `Future` resolving to the
+    /// value of the final expression.
+    ///
+    /// The code is evaluated immediately. If it returns a Promise, polling
+    /// the future executes pending jobs of the runtime until the promise is
+    /// settled, yielding to the executor in between. The future resolves to
+    /// an `ExecutionError::Exception` if the promise is rejected.
+    ///
+    /// Since the future does not borrow the context, it can be spawned on a
+    /// single-threaded executor (eg `tokio::task::spawn_local`).
+    ///
+    /// ```rust
+    /// use quick_js::{Context, ExecutionError, JsValue};
+    ///
+    /// async fn run(context: &Context) -> Result<JsValue, ExecutionError> {
+    ///     context.eval_async("(async () => 1 + 2)()").await
+    /// }
+    /// ```
+    pub fn eval_async(
+        &self,
+        code: &str,
+    ) -> impl std::future::Future<Output = Result<JsValue, ExecutionError>> {
+        let evaluated = self.wrapper.eval_unresolved(code);
+        async move {
+            match evaluated? {
+                JsValue::Promise(promise) => promise.await,
+                value => Ok(value),
+            }
+        }
+    }
+
+    /// Execute pending jobs, such as promise reactions, continuations of
+    /// async functions and callbacks passed to `queueMicrotask`.
+    ///
+    /// Jobs are executed in the order they were enqueued, until the job queue
+    /// is empty, or `limit` jobs were executed. Jobs enqueued by a job run
+    /// after the jobs that were already pending. Returns the number of
+    /// executed jobs.
+    ///
+    /// Note that the job queue is shared by all contexts in the same
+    /// [Runtime](Runtime). If a job throws an exception, execution stops and
+    /// the exception is returned.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context.eval("var x = 0; Promise.resolve().then(() => { x = 1; }); 0").unwrap();
+    /// assert!(context.has_pending_jobs());
+    /// assert_eq!(context.execute_pending_jobs(None), Ok(1));
+    /// assert!(!context.has_pending_jobs());
+    /// assert_eq!(context.eval("x"), Ok(JsValue::Int(1)));
+    /// ```
+    pub fn execute_pending_jobs(&self, limit: Option<usize>) -> Result<usize, ExecutionError> {
+        self.wrapper.execute_pending_jobs(limit)
+    }
+
+    /// Run the garbage collector of the [Runtime](Runtime) of this context.
+    ///
+    /// See [Runtime::run_gc](Runtime::run_gc).
+    ///
+    /// ```rust
+    /// use quick_js::Context;
+    /// let context = Context::new().unwrap();
+    ///
+    /// context.eval("var a = {}; a.self = a; a = null;").unwrap();
+    /// context.run_gc();
+    /// ```
+    pub fn run_gc(&self) {
+        self.wrapper.runtime().run_gc();
+    }
+
+    /// Compute the current memory usage of the [Runtime](Runtime) of this
+    /// context.
+    ///
+    /// See [Runtime::memory_usage](Runtime::memory_usage).
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.wrapper.runtime().memory_usage()
+    }
+
+    /// Get the allocation statistics of the [Runtime](Runtime) of this
+    /// context.
+    ///
+    /// See [Runtime::allocation_stats](Runtime::allocation_stats).
+    pub fn allocation_stats(&self) -> AllocationStats {
+        self.wrapper.runtime().allocation_stats()
+    }
+
+    /// Assert that `f` frees all the Javascript values it creates, to catch
+    /// reference counting mistakes in tests, eg of conversions or callbacks.
+    ///
+    /// `f` is run twice, since the first run may create values that are
+    /// cached by the context. The garbage collector runs before and after the
+    /// second run, and the function panics if the number of objects,
+    /// strings, atoms, shapes, functions or allocations of the
+    /// [Runtime](Runtime) grew. `f` must not keep values alive, or leave
+    /// pending jobs behind.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context.eval("function count(values) { return values.length; }").unwrap();
+    /// context.assert_no_leaks(|| {
+    ///     let values = context.eval("[1, 'two', { three: 3 }]").unwrap();
+    ///     assert_eq!(context.call_function("count", vec![values]), Ok(JsValue::Int(3)));
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn assert_no_leaks(&self, mut f: impl FnMut()) {
+        f();
+        self.run_gc();
+        let before = self.memory_usage();
+        f();
+        self.run_gc();
+        let after = self.memory_usage();
+
+        let counts = [
+            ("objects", before.obj_count, after.obj_count),
+            ("strings", before.str_count, after.str_count),
+            ("atoms", before.atom_count, after.atom_count),
+            ("shapes", before.shape_count, after.shape_count),
+            ("functions", before.js_func_count, after.js_func_count),
+            ("allocations", before.malloc_count, after.malloc_count),
+        ];
+        let leaks = counts
+            .iter()
+            .filter(|(_, before, after)| after > before)
+            .map(|(name, before, after)| format!("{}: {} -> {}", name, before, after))
+            .collect::<Vec<_>>();
+        assert!(
+            leaks.is_empty(),
+            "values were leaked ({})",
+            leaks.join(", ")
+        );
+    }
+
+    /// Check if there are pending jobs waiting to be executed with
+    /// [execute_pending_jobs](Context::execute_pending_jobs).
+    pub fn has_pending_jobs(&self) -> bool {
+        self.wrapper.runtime().has_pending_jobs()
+    }
+
+    /// Run the callbacks of all timers that are due at `now`, including the
+    /// jobs they enqueue.
+    ///
+    /// Timers must be enabled with
+    /// [ContextBuilder::timers](ContextBuilder::timers). Timers scheduled
+    /// by the callbacks only run on the next call, even if they are already
+    /// due. Returns the time the next timer is due, if any.
+    ///
+    /// Times are on the clock of the context, see
+    /// [ContextBuilder::time_source](ContextBuilder::time_source). With a
+    /// custom clock, call this after advancing it, which also wakes the
+    /// [JsPromise](JsPromise) futures waiting for a timer.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let context = Context::builder().timers(true).build().unwrap();
+    /// context.eval("var ticks = 0; setInterval(() => ticks++, 100)").unwrap();
+    ///
+    /// let next = context.run_timers(SystemTime::now()).unwrap().unwrap();
+    /// assert_eq!(context.eval("ticks"), Ok(JsValue::Int(0)));
+    /// context.run_timers(next + Duration::from_millis(1)).unwrap();
+    /// assert_eq!(context.eval("ticks"), Ok(JsValue::Int(1)));
+    /// ```
+    pub fn run_timers(&self, now: SystemTime) -> Result<Option<SystemTime>, ExecutionError> {
+        self.wrapper.run_timers(now)
+    }
+
+    /// The time the next timer is due, if any timer is scheduled.
+    pub fn next_timer(&self) -> Option<SystemTime> {
+        self.wrapper.next_timer()
+    }
+
+    /// Evaluates Javascript code as an ES module.
+    ///
+    /// The `name` is used to resolve relative imports of the module.
+    /// Imports are loaded with the loader configured via
+    /// [set_module_loader](Context::set_module_loader).
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context.eval_module("export const x = 1; globalThis.y = x + 1;", "main.js").unwrap();
+    /// assert_eq!(context.eval("y").unwrap(), JsValue::Int(2));
+    /// ```
+    pub fn eval_module(&self, code: &str, name: &str) -> Result<(), ExecutionError> {
+        self.wrapper.eval_module(code, name)
+    }
+
+    /// Evaluates Javascript code as an ES module that may use top-level
+    /// `await`, and returns a `Future` resolving once the module finished
+    /// executing.
+    ///
+    /// The module is evaluated immediately, up to the first `await`. Like
+    /// with [eval_async](Context::eval_async), polling the future executes
+    /// pending jobs of the runtime until the module is done.
+    ///
+    /// Note that modules using top-level `await` can not have exports.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, ExecutionError};
+    ///
+    /// async fn run(context: &Context) -> Result<(), ExecutionError> {
+    ///     context
+    ///         .eval_module_async("globalThis.x = await Promise.resolve(1);", "main.js")
+    ///         .await
+    /// }
+    /// ```
+    pub fn eval_module_async(
+        &self,
+        code: &str,
+        name: &str,
+    ) -> impl std::future::Future<Output = Result<(), ExecutionError>> {
+        let evaluated = self.wrapper.eval_module_unresolved(code, name);
+        async move {
+            match evaluated? {
+                JsValue::Promise(promise) => promise.await.map(|_| ()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// Imports a module like a dynamic `import()`, and returns its namespace
+    /// object, with a property for each export.
+    ///
+    /// The module is loaded with the [ModuleLoader](module::ModuleLoader) or
+    /// the native modules of this context, and evaluated unless it was
+    /// already imported. Like with [eval](Context::eval), the event loop is
+    /// run until the module finished executing.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue, module::ModuleSource};
+    ///
+    /// let context = Context::new().unwrap();
+    /// context.set_module_loader(|name: &str| match name {
+    ///     "config" => Ok(ModuleSource::from("export const port = 8080;")),
+    ///     _ => Err("not found"),
+    /// });
+    ///
+    /// match context.import_module("config").unwrap() {
+    ///     JsValue::Object(exports) => assert_eq!(exports["port"], JsValue::Int(8080)),
+    ///     other => panic!("unexpected namespace: {:?}", other),
+    /// }
+    /// ```
+    pub fn import_module(&self, specifier: &str) -> Result<JsValue, ExecutionError> {
+        let namespace = self.wrapper.import_module(specifier)?;
+        Ok(namespace.to_value()?)
+    }
+
+    /// Set the [ModuleLoader](module::ModuleLoader) used to resolve `import`
+    /// statements.
+    ///
+    /// The loader is installed on the [Runtime](Runtime) of this context,
+    /// so it is shared with all other contexts in the same runtime.
+    pub fn set_module_loader<L>(&self, loader: L)
+    where
+        L: module::ModuleLoader,
+    {
+        self.runtime().set_module_loader(loader);
+    }
+
+    /// The modules loaded in this context and their imports, as a
+    /// [ModuleGraph](module::ModuleGraph).
+    ///
+    /// Build tools can use the graph to find the modules affected by a
+    /// change of a file, see [ModuleGraph::dependents](module::ModuleGraph::dependents).
+    /// Imports are only recorded while a module loader is set or native
+    /// modules are registered.
+    pub fn module_graph(&self) -> module::ModuleGraph {
+        self.wrapper.module_graph()
+    }
+
+    /// Invalidate a loaded module and all modules importing it, directly or
+    /// indirectly, so they are fetched from the
+    /// [ModuleLoader](module::ModuleLoader) again the next time they are
+    /// imported.
+    ///
+    /// Returns the names of the invalidated modules, `name` first and then
+    /// its [dependents](module::ModuleGraph::dependents).
+    ///
+    /// Modules that were already evaluated keep the bindings of the old
+    /// modules. The new versions are used by later imports, like evaluating
+    /// the main module again with [eval_module](Context::eval_module) or a
+    /// dynamic `import()`. QuickJS can not unload modules, so the old
+    /// versions stay in memory until the context is dropped, and the names
+    /// of the new versions have a `?v=N` suffix in stack traces.
+    /// `import.meta` and the loader see the original names.
+    ///
+    /// ```rust
+    /// use std::sync::{Arc, Mutex};
+    /// use quick_js::{Context, JsValue, module::ModuleSource};
+    ///
+    /// let answer = Arc::new(Mutex::new(1));
+    /// let source = answer.clone();
+    /// let context = Context::new().unwrap();
+    /// context.set_module_loader(move |name: &str| match name {
+    ///     "answer.js" => Ok(ModuleSource::from(format!(
+    ///         "export const answer = {};",
+    ///         source.lock().unwrap()
+    ///     ))),
+    ///     _ => Err("not found"),
+    /// });
+    ///
+    /// let main = "import { answer } from './answer.js'; globalThis.x = answer;";
+    /// context.eval_module(main, "main.js").unwrap();
+    /// assert_eq!(context.eval("x").unwrap(), JsValue::Int(1));
+    ///
+    /// *answer.lock().unwrap() = 2;
+    /// context.invalidate_module("answer.js");
+    /// context.eval_module(main, "main.js").unwrap();
+    /// assert_eq!(context.eval("x").unwrap(), JsValue::Int(2));
+    /// ```
+    pub fn invalidate_module(&self, name: &str) -> Vec<String> {
+        self.wrapper.invalidate_module(name)
+    }
+
+    /// Register a native module, whose exports are backed by Rust functions
+    /// and values, see [ModuleBuilder](module::ModuleBuilder).
+    ///
+    /// Modules evaluated in this context can import it under `name`, like
+    /// `import { readFile } from "host:fs"`. Native modules take precedence
+    /// over the module loader. Each name can only be registered once per
+    /// context.
+    pub fn register_module<F>(&self, name: &str, define: F) -> Result<(), ExecutionError>
+    where
+        F: FnOnce(&mut module::ModuleBuilder),
+    {
+        let mut module = module::ModuleBuilder::default();
+        define(&mut module);
+        let name = name.to_string();
+        let module = Rc::new(module);
+        self.wrapper
+            .setup(move |w| w.register_module(&name, module.clone()))
+    }
+
+    /// Set a handler that is called periodically during execution, and can
+    /// abort runaway scripts by returning `true`.
+    ///
+    /// The handler is installed on the [Runtime](Runtime) of this context, so
+    /// it is shared with all other contexts in the same runtime.
+    /// See [Runtime::set_interrupt_handler](Runtime::set_interrupt_handler).
+    pub fn set_interrupt_handler<F>(&self, handler: F)
+    where
+        F: Fn() -> bool + std::panic::RefUnwindSafe + 'static,
+    {
+        self.runtime().set_interrupt_handler(handler);
+    }
+
+    /// Get a [CancellationHandle](CancellationHandle) for cancelling running
+    /// scripts from another thread.
+    ///
+    /// Cancellation applies to the [Runtime](Runtime) of this context, so
+    /// it also cancels executions of other contexts in the same runtime, see
+    /// [CancellationHandle](CancellationHandle).
+    pub fn cancellation_handle(&self) -> CancellationHandle {
+        CancellationHandle {
+            cancelled: self.wrapper.runtime().cancellation_flag(),
+        }
+    }
+
+    /// Get a [JsObject](JsObject) handle to the global object.
+    ///
+    /// This allows reading, writing and deleting globals without evaluating
+    /// code.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context.eval("var config = { debug: true };").unwrap();
+    ///
+    /// let global = context.global().unwrap();
+    /// assert!(global.has("config").unwrap());
+    /// // Variables declared with `var` can not be deleted.
+    /// assert!(!global.delete("config").unwrap());
+    /// global.set("limit", 10).unwrap();
+    /// assert!(global.keys().unwrap().contains(&"limit".to_string()));
+    /// ```
+    pub fn global(&self) -> Result<JsObject, ExecutionError> {
+        let global = self.wrapper.global()?;
+        Ok(JsObject::new(bindings::PersistentValue::from_ref(
+            global.into_value(),
+        )))
+    }
+
+    /// Create a new unique symbol, like `Symbol(description)` in Javascript.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let symbol = context.new_symbol(Some("secret")).unwrap();
+    /// context.global().unwrap().set("secret", symbol).unwrap();
+    /// assert_eq!(context.eval("secret.toString()"), Ok(JsValue::from("Symbol(secret)")));
+    /// assert_eq!(context.eval("secret === Symbol('secret')"), Ok(JsValue::Bool(false)));
+    /// ```
+    pub fn new_symbol(&self, description: Option<&str>) -> Result<JsSymbol, ExecutionError> {
+        Ok(JsSymbol::try_from(self.wrapper.new_symbol(description)?)?)
+    }
+
+    /// Get the symbol for a key from the global symbol registry, like
+    /// `Symbol.for(key)` in Javascript.
+    ///
+    /// The same symbol is returned for the same key, also in other contexts
+    /// of the same runtime.
+    pub fn symbol_for(&self, key: &str) -> Result<JsSymbol, ExecutionError> {
+        Ok(JsSymbol::try_from(self.wrapper.symbol_for(key)?)?)
+    }
+
+    /// Get a well-known symbol, like `Symbol.iterator`.
+    ///
+    /// Well-known symbols can be used to make host objects iterable, see
+    /// [JsObject::set_symbol].
+    pub fn well_known_symbol(&self, symbol: WellKnownSymbol) -> Result<JsSymbol, ExecutionError> {
+        Ok(JsSymbol::try_from(
+            self.wrapper.well_known_symbol(symbol.name())?,
+        )?)
+    }
+
+    /// Call a global function in the Javascript namespace.
+    ///
+    /// **Promises**:
+    /// If the evaluated code returns a Promise, the event loop
+    /// will be executed until the promise is finished. The final value of
+    /// the promise will be returned, or a `ExecutionError::Exception` if the
+    /// promise failed.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// let res = context.call_function("encodeURIComponent", vec!["a=b"]);
+    /// assert_eq!(
+    ///     res,
+    ///     Ok(JsValue::String("a%3Db".to_string())),
+    /// );
+    /// ```
+    pub fn call_function(
+        &self,
+        function_name: &str,
+        args: impl IntoIterator<Item = impl Into<JsValue>>,
+    ) -> Result<JsValue, ExecutionError> {
+        let value = self.call_global_function(function_name, args)?.to_value()?;
+        Ok(value)
+    }
+
+    /// Call a global function in the Javascript namespace, and return the
+    /// result as an [OwnedJsValue](OwnedJsValue) handle, without converting
+    /// it.
+    ///
+    /// Promises are resolved like in [call_function](Context::call_function).
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context.eval("function counter() { return { count: 0 }; }").unwrap();
+    /// context.eval("function increment(c) { return ++c.count; }").unwrap();
+    ///
+    /// // The same object is passed to each call.
+    /// let counter = context.call_function_owned("counter", Vec::<JsValue>::new()).unwrap();
+    /// assert_eq!(context.call_function("increment", vec![counter.clone()]), Ok(JsValue::Int(1)));
+    /// assert_eq!(context.call_function("increment", vec![counter]), Ok(JsValue::Int(2)));
+    /// ```
+    pub fn call_function_owned(
+        &self,
+        function_name: &str,
+        args: impl IntoIterator<Item = impl Into<JsValue>>,
+    ) -> Result<OwnedJsValue, ExecutionError> {
+        let value = self.call_global_function(function_name, args)?;
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+            value,
+        )))
+    }
+
+    fn call_global_function(
+        &self,
+        function_name: &str,
+        args: impl IntoIterator<Item = impl Into<JsValue>>,
+    ) -> Result<bindings::OwnedValueRef<'_>, ExecutionError> {
+        let qargs = args
+            .into_iter()
+            .map(|arg| self.wrapper.serialize_value(arg.into()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let global = self.wrapper.global()?;
+        let func_obj = global.property(function_name)?;
+
+        if !func_obj.is_object() {
+            return Err(ExecutionError::Internal(format!(
+                "Could not find function '{}' in global scope: does not exist, or not an object",
+                function_name
+            )));
+        }
+
+        self.wrapper.call_function(func_obj, qargs)
+    }
+
+    /// Add a global JS function that is backed by a Rust function or closure.
+    ///
+    /// The callback must satisfy several requirements:
+    /// * accepts 0 - 5 arguments
+    /// * each argument must either:
+    ///   - be convertible from a JsValue
+    ///   - be an `Option<T>` of such a type, which makes it optional. Missing
+    ///     arguments, `null` and `undefined` are converted to `None`
+    ///   - be a [JsStr](JsStr), which borrows a string argument instead of
+    ///     copying it
+    ///   - be [Arguments](Arguments) as the last parameter, which collects
+    ///     all remaining arguments
+    /// * must return a value
+    /// * the return value must either:
+    ///   - be convertible to JsValue
+    ///   - be a Result<T, E> where T is convertible to JsValue
+    ///     if Err(e) is returned, a Javascript exception will be raised.
+    ///     Use [JsError](JsError) as the error type to throw a proper
+    ///     `Error` instance.
+    ///
+    /// Callbacks returning a `Future` are added with
+    /// [add_async_callback](Context::add_async_callback).
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// // Register a closue as a callback under the "add" name.
+    /// // The 'add' function can now be called from Javascript code.
+    /// context.add_callback("add", |a: i32, b: i32| { a + b }).unwrap();
+    ///
+    /// // Now we try out the 'add' function via eval.
+    /// let output = context.eval_as::<i32>(" add( 3 , 4 ) ").unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     7,
+    /// );
+    /// ```
+    pub fn add_callback<F>(
+        &self,
+        name: &str,
+        callback: impl Callback<F> + 'static,
+    ) -> Result<(), ExecutionError> {
+        let name = name.to_string();
+        let callback = Arc::new(callback);
+        self.wrapper
+            .setup(move |w| w.add_callback(&name, callback.clone()))
+    }
+
+    /// Add a global JS function that is backed by a Rust function or closure
+    /// returning a `Future`, like an async block.
+    ///
+    /// Calling the function returns a promise, which is settled with the
+    /// output of the future. Arguments and outputs are converted like for
+    /// [add_callback](Context::add_callback), and invalid arguments reject
+    /// the promise.
+    ///
+    /// Futures are polled as pending jobs of the runtime, so they make
+    /// progress while the event loop runs, like during [eval](Context::eval)
+    /// or while polling a [JsPromise]. When the event loop has nothing else
+    /// to do, it sleeps until a future is woken. Futures run on the thread of
+    /// the context, so they do not need to be `Send`, but they should not
+    /// block: slow work, like I/O, is best done by another thread or runtime
+    /// that wakes the future when it is done.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context
+    ///     .add_async_callback("loadRow", |id: i32| {
+    ///         let (sender, receiver) = futures::channel::oneshot::channel();
+    ///         std::thread::spawn(move || sender.send(format!("row {}", id)));
+    ///         async move { receiver.await.map_err(|e| e.to_string()) }
+    ///     })
+    ///     .unwrap();
+    ///
+    /// let row = context.eval("(async () => await loadRow(7))()");
+    /// assert_eq!(row, Ok(JsValue::from("row 7")));
+    /// ```
+    pub fn add_async_callback<F>(
+        &self,
+        name: &str,
+        callback: impl AsyncCallback<F> + 'static,
+    ) -> Result<(), ExecutionError> {
+        let name = name.to_string();
+        let callback = Arc::new(callback);
+        self.wrapper
+            .setup(move |w| w.add_async_callback(&name, callback.clone()))
+    }
+
+    /// Add a JS function that is backed by a Rust function or closure at a
+    /// dot-separated path, like `api.fs.readFile`.
+    ///
+    /// This allows grouping host functions in namespace objects instead of
+    /// adding them to the global scope. Objects along the path are created if
+    /// they don't exist yet, and existing objects are reused. An error is
+    /// returned if a path segment refers to a value that is not an object.
+    ///
+    /// The callback has the same requirements as for
+    /// [add_callback](Context::add_callback).
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context.add_callback_at("math.ops.add", |a: i32, b: i32| a + b).unwrap();
+    /// context.add_callback_at("math.ops.mul", |a: i32, b: i32| a * b).unwrap();
+    ///
+    /// let output = context.eval("math.ops.mul(math.ops.add(1, 2), 4)").unwrap();
+    /// assert_eq!(output, JsValue::Int(12));
+    /// ```
+    pub fn add_callback_at<F>(
+        &self,
+        path: &str,
+        callback: impl Callback<F> + 'static,
+    ) -> Result<(), ExecutionError> {
+        let path = path.to_string();
+        let callback = Arc::new(callback);
+        self.wrapper
+            .setup(move |w| w.add_callback_at(&path, callback.clone()))
+    }
+
+    /// Add a global JS function that is backed by a Rust closure, which
+    /// receives a [CallbackContext](CallbackContext) in addition to the
+    /// arguments.
+    ///
+    /// The context handle allows the callback to interact with the engine,
+    /// for example to evaluate code or to call Javascript functions.
+    ///
+    /// If the callback returns `Err(ExecutionError::Exception(value))`,
+    /// `value` is thrown as a Javascript exception, so exceptions of nested
+    /// executions can be propagated with `?`. Other errors are thrown as
+    /// their message.
+    ///
+    /// ```rust
+    /// use quick_js::{Arguments, CallbackContext, Context, ExecutionError, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context
+    ///     .add_callback_with_context("callNamed", |ctx: &CallbackContext, args: Arguments| {
+    ///         let mut args = args.into_vec();
+    ///         let name = match args.pop() {
+    ///             Some(JsValue::String(name)) => name,
+    ///             _ => return Err(ctx.throw("expected a function name")),
+    ///         };
+    ///         ctx.context().call_function(&name, Vec::<JsValue>::new())
+    ///     })
+    ///     .unwrap();
+    ///
+    /// context.eval("function answer() { return 42; }").unwrap();
+    /// assert_eq!(context.eval("callNamed('answer')"), Ok(JsValue::Int(42)));
+    /// assert_eq!(
+    ///     context.eval("callNamed(1)"),
+    ///     Err(ExecutionError::Exception("expected a function name".into()))
+    /// );
+    /// ```
+    pub fn add_callback_with_context<F, R>(
+        &self,
+        name: &str,
+        callback: F,
+    ) -> Result<(), ExecutionError>
+    where
+        F: Fn(&CallbackContext, Arguments) -> Result<R, ExecutionError>
+            + std::panic::RefUnwindSafe
+            + 'static,
+        R: Into<JsValue>,
+    {
+        let name = name.to_string();
+        let callback = Arc::new(callback);
+        self.wrapper.setup(move |w| {
+            let callback = callback.clone();
+            w.add_context_callback(&name, move |wrapper, args| {
+                let context = CallbackContext::new(Context::from_wrapper(wrapper));
+                callback(&context, Arguments::new(args)).map(Into::into)
+            })
+        })
+    }
+
+    /// Define a global property that is backed by a Rust getter and setter.
+    ///
+    /// The getter is called every time the property is read, and the setter
+    /// every time it is assigned, which allows exposing host values lazily
+    /// instead of copying them into the context up front.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let context = Context::new().unwrap();
+    /// let level = Arc::new(Mutex::new(String::from("info")));
+    ///
+    /// let (get_level, set_level) = (level.clone(), level.clone());
+    /// context
+    ///     .set_global_property_with_accessors(
+    ///         "logLevel",
+    ///         move || get_level.lock().unwrap().clone(),
+    ///         move |value: JsValue| {
+    ///             if let JsValue::String(value) = value {
+    ///                 *set_level.lock().unwrap() = value;
+    ///             }
+    ///         },
+    ///     )
+    ///     .unwrap();
+    ///
+    /// assert_eq!(context.eval("logLevel"), Ok(JsValue::from("info")));
+    /// context.eval("logLevel = 'debug'").unwrap();
+    /// assert_eq!(*level.lock().unwrap(), "debug");
+    /// ```
+    pub fn set_global_property_with_accessors<G, R, S>(
+        &self,
+        name: &str,
+        get: G,
+        set: S,
+    ) -> Result<(), ExecutionError>
+    where
+        G: Fn() -> R + std::panic::RefUnwindSafe + 'static,
+        R: Into<JsValue>,
+        S: Fn(JsValue) + std::panic::RefUnwindSafe + 'static,
+    {
+        let name = name.to_string();
+        let (get, set) = (Arc::new(get), Arc::new(set));
+        self.wrapper.setup(move |w| {
+            let (get, set) = (get.clone(), set.clone());
+            w.define_global_accessor(&name, move || get().into(), move |value| set(value))
+        })
+    }
+
+    /// Register a Rust type as a Javascript class.
+    ///
+    /// The constructor of the class is added to the global object under the
+    /// name of the class. See [ClassBuilder](ClassBuilder) for an example.
+    ///
+    /// Each type can only be registered once per context.
+    pub fn register_class<T: 'static>(&self, class: ClassBuilder<T>) -> Result<(), ExecutionError> {
+        self.wrapper.register_class(class)
+    }
+
+    /// Create an instance of a registered class from a Rust value.
+    ///
+    /// The returned handle can be passed to Javascript as a `JsValue::Ref`.
+    ///
+    /// ```rust
+    /// use quick_js::{Arguments, ClassBuilder, Context, JsValue};
+    ///
+    /// struct Point(i32, i32);
+    ///
+    /// let context = Context::new().unwrap();
+    /// let class = ClassBuilder::new("Point")
+    ///     .getter("x", |point: &Point| point.0)
+    ///     .getter("y", |point: &Point| point.1);
+    /// context.register_class(class).unwrap();
+    ///
+    /// context.eval("function sum(p) { return p.x + p.y; }").unwrap();
+    /// let point = context.create_instance(Point(1, 2)).unwrap();
+    /// assert_eq!(
+    ///     context.call_function("sum", vec![JsValue::Ref(point)]),
+    ///     Ok(JsValue::Int(3))
+    /// );
+    /// ```
+    pub fn create_instance<T: 'static>(&self, value: T) -> Result<OwnedJsValue, ExecutionError> {
+        let instance = self.wrapper.create_instance(value)?;
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+            instance,
+        )))
+    }
+
+    /// Create an object whose properties are looked up by a
+    /// [DynamicObject] when they are accessed.
+    ///
+    /// The object is a `Proxy`, so the context needs the `Proxy` built-in.
+    /// `object` is dropped when the proxy is garbage collected. See
+    /// [DynamicObject] for an example.
+    pub fn create_dynamic_object<O: DynamicObject>(
+        &self,
+        object: O,
+    ) -> Result<OwnedJsValue, ExecutionError> {
+        let proxy = self.wrapper.create_dynamic_object(Box::new(object))?;
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+            proxy,
+        )))
+    }
+
+    /// Create a Javascript iterator that yields the items of a Rust
+    /// iterator, so scripts can consume it with `for...of` or spread syntax
+    /// without collecting it into an array first.
+    ///
+    /// Items are produced lazily, when Javascript calls `next()`. The
+    /// iterator can only be consumed once, and it is dropped when it is done,
+    /// when the loop consuming it exits early, or when the Javascript
+    /// iterator is garbage collected. A closure producing items can be
+    /// turned into an iterator with `std::iter::from_fn`.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    ///
+    /// let context = Context::new().unwrap();
+    /// context
+    ///     .eval("function sum(items) { let s = 0; for (const x of items) s += x; return s; }")
+    ///     .unwrap();
+    ///
+    /// let items = context.create_iterator((1..=100).map(|x| x * 2)).unwrap();
+    /// assert_eq!(
+    ///     context.call_function("sum", vec![JsValue::Ref(items)]),
+    ///     Ok(JsValue::Int(10100))
+    /// );
+    /// ```
+    pub fn create_iterator<I>(&self, iter: I) -> Result<OwnedJsValue, ExecutionError>
+    where
+        I: IntoIterator,
+        I::Item: Into<JsValue> + 'static,
+        I::IntoIter: 'static,
+    {
+        let iter = iter.into_iter().map(Into::into);
+        let iterator = self.wrapper.create_iterator(Box::new(iter))?;
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+            iterator,
+        )))
+    }
+
+    /// Create a Javascript async iterator that yields the items of a Rust
+    /// `Stream`, so scripts can consume it with `for await...of`.
+    ///
+    /// The stream is polled by the pending jobs of the runtime when
+    /// Javascript calls `next()`, and the returned promise resolves once the
+    /// stream produced the next item. While the event loop waits for the
+    /// stream, like in [eval](Context::eval) or when polling a [JsPromise],
+    /// the thread sleeps until the stream wakes it. The stream is dropped
+    /// when it ends, or when the loop consuming it exits early.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    ///
+    /// let context = Context::new().unwrap();
+    /// let rows = context
+    ///     .create_async_iterator(futures::stream::iter(vec!["a", "b", "c"]))
+    ///     .unwrap();
+    /// context.global().unwrap().set("rows", rows).unwrap();
+    ///
+    /// let joined = context.eval(
+    ///     "(async () => {
+    ///         let joined = '';
+    ///         for await (const row of rows) joined += row;
+    ///         return joined;
+    ///     })()",
+    /// );
+    /// assert_eq!(joined, Ok(JsValue::from("abc")));
+    /// ```
+    #[cfg(feature = "futures")]
+    pub fn create_async_iterator<S>(&self, stream: S) -> Result<OwnedJsValue, ExecutionError>
+    where
+        S: futures_core::Stream + 'static,
+        S::Item: Into<JsValue>,
+    {
+        let iterator = self.wrapper.create_async_iterator(stream)?;
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+            iterator,
+        )))
+    }
+
+    /// Create an `ArrayBuffer` backed by Rust memory, without copying it.
+    ///
+    /// Javascript reads and writes the memory of `buffer` directly, which
+    /// avoids copying large data like `JsValue::Bytes` does. The buffer is
+    /// owned by the runtime and dropped when the `ArrayBuffer` is garbage
+    /// collected, so a `Drop` implementation can be used to get notified
+    /// when Javascript no longer uses it.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let context = Context::new().unwrap();
+    ///
+    /// context.eval("function sum(b) { return new Uint8Array(b).reduce((a, b) => a + b); }").unwrap();
+    /// let frame = context.new_array_buffer(vec![1u8; 1 << 20]).unwrap();
+    /// assert_eq!(
+    ///     context.call_function("sum", vec![JsValue::Ref(frame)]),
+    ///     Ok(JsValue::Int(1 << 20))
+    /// );
+    /// ```
+    pub fn new_array_buffer<B>(&self, buffer: B) -> Result<OwnedJsValue, ExecutionError>
+    where
+        B: AsMut<[u8]> + 'static,
+    {
+        let buffer = self.wrapper.new_array_buffer(buffer)?;
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+            buffer,
+        )))
+    }
+
+    /// Create a `SharedArrayBuffer` referring to the memory of a
+    /// [SharedBuffer].
+    ///
+    /// Requires the `atomics` feature. Changes made by Javascript are visible
+    /// through the buffer and all other `SharedArrayBuffer`s created from it.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue, SharedBuffer};
+    /// use std::convert::TryFrom;
+    /// let context = Context::new().unwrap();
+    ///
+    /// let buffer = SharedBuffer::new(8);
+    /// let value = context.new_shared_array_buffer(&buffer).unwrap();
+    /// context.eval("function store(b) { Atomics.store(new Int32Array(b), 1, 42); }").unwrap();
+    /// context.call_function("store", vec![JsValue::Ref(value)]).unwrap();
+    /// assert_eq!(buffer.to_vec(), vec![0, 0, 0, 0, 42, 0, 0, 0]);
+    ///
+    /// // SharedArrayBuffers created by Javascript can be shared as well.
+    /// let value = context.eval_owned("new SharedArrayBuffer(16)").unwrap();
+    /// assert_eq!(SharedBuffer::try_from(value).unwrap().len(), 16);
+    /// ```
+    #[cfg(feature = "atomics")]
+    pub fn new_shared_array_buffer(
+        &self,
+        buffer: &SharedBuffer,
+    ) -> Result<OwnedJsValue, ExecutionError> {
+        let buffer = unsafe {
+            self.wrapper
+                .new_shared_array_buffer(buffer.as_ptr(), buffer.len())?
+        };
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+            buffer,
+        )))
+    }
+
+    /// Deep-copy a value of this context into `target`, like
+    /// `structuredClone`.
+    ///
+    /// The value is copied with the QuickJS serializer, without a JSON round
+    /// trip. Objects, arrays, typed arrays, `ArrayBuffer`s, `Date`s, BigInts
+    /// and boxed primitives are supported, including shared and cyclic
+    /// references. With the `atomics` feature, `SharedArrayBuffer`s are
+    /// shared instead of copied. Other values, like functions, `Map`s and
+    /// `Set`s, can not be serialized by QuickJS and return an error. With the
+    /// `ng` feature, `Map`s and `Set`s are supported as well.
+    ///
+    /// The target context can belong to a different [Runtime].
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue};
+    /// let source = Context::new().unwrap();
+    /// let target = Context::new().unwrap();
+    ///
+    /// let value = source.eval_owned("const o = { date: new Date(0), bytes: new Uint8Array([1, 2]) }; o.self = o; o").unwrap();
+    /// let copy = source.transfer(&value, &target).unwrap();
+    /// target.eval("function check(o) { return o.self === o && o.date.getTime() === 0 && o.bytes[1]; }").unwrap();
+    /// assert_eq!(target.call_function("check", vec![copy]), Ok(JsValue::Int(2)));
+    /// ```
+    pub fn transfer(
+        &self,
+        value: &OwnedJsValue,
+        target: &Context,
+    ) -> Result<OwnedJsValue, ExecutionError> {
+        if !Rc::ptr_eq(value.persistent().context(), &self.wrapper) {
+            return Err(ExecutionError::Internal(
+                "Value does not belong to this context".into(),
+            ));
+        }
+        let value = value.persistent().as_value_ref();
+        let copy = self.wrapper.transfer(&value, &target.wrapper)?;
+        Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(copy)))
+    }
+
+    /// Register a callback that runs once `object` is garbage collected, eg
+    /// to invalidate Rust caches keyed by Javascript objects.
+    ///
+    /// Like the callbacks of a `FinalizationRegistry`, the callback does
+    /// not run during garbage collection, but as a pending job, see
+    /// [execute_pending_jobs](Context::execute_pending_jobs). The callback
+    /// also runs when this context is freed, since its registrations are
+    /// freed with it, or when the [Runtime](Runtime) is dropped while it is
+    /// still pending.
+    ///
+    /// `object` is not kept alive by the registration, but it is by the
+    /// [JsObject] handle itself. If `object` was part of a reference cycle,
+    /// the callback is only queued by the next garbage collection after the
+    /// one that collected the cycle. Registering requires the `WeakMap`
+    /// built-in.
+    /// With the `ng` feature, scripts can also use `WeakRef` and
+    /// `FinalizationRegistry`, which the quickjs version bundled by default
+    /// does not implement.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsObject};
+    /// use std::{cell::Cell, convert::TryFrom, rc::Rc};
+    ///
+    /// let context = Context::new().unwrap();
+    /// let object = JsObject::try_from(context.eval_owned("({})").unwrap()).unwrap();
+    ///
+    /// let collected = Rc::new(Cell::new(false));
+    /// let flag = collected.clone();
+    /// context
+    ///     .register_finalizer(&object, move || flag.set(true))
+    ///     .unwrap();
+    ///
+    /// drop(object);
+    /// context.run_gc();
+    /// assert!(!collected.get());
+    /// context.execute_pending_jobs(None).unwrap();
+    /// assert!(collected.get());
+    /// ```
+    pub fn register_finalizer<F>(
+        &self,
+        object: &JsObject,
+        callback: F,
+    ) -> Result<(), ExecutionError>
+    where
+        F: FnOnce() + 'static,
+    {
+        let object = object.persistent().value_ref_for(&self.wrapper)?;
+        self.wrapper.register_finalizer(&object, Box::new(callback))
+    }
+}
+
+/// Serialize a Rust value directly into a Javascript value of the given
+/// context.
+///
+/// Requires the `serde` feature. The returned handle can be passed to
+/// Javascript as a `JsValue::Ref`, eg as an argument of
+/// [call_function](Context::call_function).
+///
+/// Integers are converted to JS numbers. Integers outside of the safe integer
+/// range are converted according to the
+/// [integer_policy](ContextBuilder::integer_policy) of the context, so they
+/// lose precision by default. Enums use the externally tagged
+/// representation, like serde_json.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue};
+/// use std::collections::HashMap;
+///
+/// let context = Context::new().unwrap();
+/// context.eval("function sum(x) { return x.a + x.b; }").unwrap();
+///
+/// let map: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// let value = quick_js::to_js(&context, &map).unwrap();
+/// assert_eq!(context.call_function("sum", vec![value]), Ok(JsValue::Int(3)));
+/// ```
+#[cfg(feature = "serde")]
+pub fn to_js<T>(context: &Context, value: &T) -> Result<OwnedJsValue, ValueError>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let value = value.serialize(bindings::serde::Serializer::new(&context.wrapper))?;
+    Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+        value,
+    )))
+}
+
+/// Serialize a Rust value like [to_js](to_js), but with the given
+/// [IntegerPolicy](IntegerPolicy) instead of the one of the context.
+///
+/// ```rust
+/// use quick_js::{Context, IntegerPolicy};
+///
+/// let context = Context::new().unwrap();
+///
+/// assert!(quick_js::to_js_with(&context, &i64::MAX, IntegerPolicy::Error).is_err());
+/// #[cfg(feature = "bignum")]
+/// assert!(quick_js::to_js_with(&context, &i64::MAX, IntegerPolicy::BigInt).is_ok());
+/// ```
+#[cfg(feature = "serde")]
+pub fn to_js_with<T>(
+    context: &Context,
+    value: &T,
+    integers: IntegerPolicy,
+) -> Result<OwnedJsValue, ValueError>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let serializer =
+        bindings::serde::Serializer::new(&context.wrapper).with_integer_policy(integers);
+    let value = value.serialize(serializer)?;
+    Ok(OwnedJsValue::new(bindings::PersistentValue::from_ref(
+        value,
+    )))
+}
+
+/// Deserialize a Rust value directly from a Javascript value.
+///
+/// Requires the `serde` feature.
+///
+/// ```rust
+/// use quick_js::Context;
+///
+/// let context = Context::new().unwrap();
+///
+/// let value = context.eval_owned("[1, 2, 3]").unwrap();
+/// let numbers: Vec<u8> = quick_js::from_js(&value).unwrap();
+/// assert_eq!(numbers, vec![1, 2, 3]);
+/// ```
+#[cfg(feature = "serde")]
+pub fn from_js<T>(value: &OwnedJsValue) -> Result<T, ValueError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let value = value.persistent().as_value_ref();
+    T::deserialize(bindings::serde::Deserializer::new(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    // #[test]
     // fn test_global_properties() {
     //     let c = Context::new().unwrap();
 
-    //     assert_eq!(
-    //         c.global_property("lala"),
-    //         Err(ExecutionError::Exception(
-    //             "Global object does not have property 'lala'".into()
-    //         ))
-    //     );
+    //     assert_eq!(
+    //         c.global_property("lala"),
+    //         Err(ExecutionError::Exception(
+    //             "Global object does not have property 'lala'".into()
+    //         ))
+    //     );
+
+    //     c.set_global_property("testprop", true).unwrap();
+    //     assert_eq!(
+    //         c.global_property("testprop").unwrap(),
+    //         JsValue::Bool(true),
+    //     );
+    // }
+
+    #[test]
+    fn test_eval_pass() {
+        use std::iter::FromIterator;
+
+        let c = Context::new().unwrap();
+
+        let cases = vec![
+            ("null", Ok(JsValue::Null)),
+            ("undefined", Ok(JsValue::Undefined)),
+            ("true", Ok(JsValue::Bool(true))),
+            ("2 > 10", Ok(JsValue::Bool(false))),
+            ("1", Ok(JsValue::Int(1))),
+            ("1 + 1", Ok(JsValue::Int(2))),
+            ("1.1", Ok(JsValue::Float(1.1))),
+            ("2.2 * 2 + 5", Ok(JsValue::Float(9.4))),
+            ("\"abc\"", Ok(JsValue::String("abc".into()))),
+            (
+                "[1,2]",
+                Ok(JsValue::Array(vec![JsValue::Int(1), JsValue::Int(2)])),
+            ),
+        ];
+
+        for (code, res) in cases.into_iter() {
+            assert_eq!(c.eval(code), res,);
+        }
+
+        let obj_cases = vec![
+            (
+                r#" {"a": null} "#,
+                Ok(JsValue::Object(HashMap::from_iter(vec![(
+                    "a".to_string(),
+                    JsValue::Null,
+                )]))),
+            ),
+            (
+                r#" {a: 1, b: true, c: {c1: false}} "#,
+                Ok(JsValue::Object(HashMap::from_iter(vec![
+                    ("a".to_string(), JsValue::Int(1)),
+                    ("b".to_string(), JsValue::Bool(true)),
+                    (
+                        "c".to_string(),
+                        JsValue::Object(HashMap::from_iter(vec![(
+                            "c1".to_string(),
+                            JsValue::Bool(false),
+                        )])),
+                    ),
+                ]))),
+            ),
+        ];
+
+        for (index, (code, res)) in obj_cases.into_iter().enumerate() {
+            let full_code = format!(
+                "var v{index} = {code}; v{index}",
+                index = index,
+                code = code
+            );
+            assert_eq!(c.eval(&full_code), res,);
+        }
+
+        assert_eq!(c.eval_as::<bool>("true").unwrap(), true,);
+        assert_eq!(c.eval_as::<i32>("1 + 2").unwrap(), 3,);
+
+        let value: String = c.eval_as("var x = 44; x.toString()").unwrap();
+        assert_eq!(&value, "44");
+
+        #[cfg(feature = "bigint")]
+        assert_eq!(
+            c.eval_as::<num_bigint::BigInt>("1n << 100n").unwrap(),
+            num_bigint::BigInt::from(1i128 << 100)
+        );
+
+        #[cfg(feature = "bigint")]
+        assert_eq!(c.eval_as::<i64>("1 << 30").unwrap(), 1i64 << 30);
+
+        #[cfg(feature = "bigint")]
+        assert_eq!(c.eval_as::<u128>("1n << 100n").unwrap(), 1u128 << 100);
+    }
+
+    #[test]
+    fn test_eval_syntax_error() {
+        let c = Context::new().unwrap();
+        assert_eq!(
+            c.eval(
+                r#"
+                !!!!
+            "#
+            ),
+            Err(ExecutionError::Exception(
+                "SyntaxError: unexpected token in expression: \'\'".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_eval_exception() {
+        let c = Context::new().unwrap();
+        assert_eq!(
+            c.eval(
+                r#"
+                function f() {
+                    throw new Error("My Error");
+                }
+                f();
+            "#
+            ),
+            Err(ExecutionError::Exception("Error: My Error".into(),))
+        );
+    }
+
+    #[test]
+    fn eval_async() {
+        let c = Context::new().unwrap();
+
+        let value = c
+            .eval(
+                r#"
+            new Promise((resolve, _) => {
+                resolve(33);
+            })
+       "#,
+            )
+            .unwrap();
+        assert_eq!(value, JsValue::Int(33));
+
+        let res = c.eval(
+            r#"
+            new Promise((_resolve, reject) => {
+                reject("Failed...");
+            })
+       "#,
+        );
+        assert_eq!(
+            res,
+            Err(ExecutionError::Exception(
+                JsValue::String("Failed...".into()).into()
+            ))
+        );
+    }
+
+    fn first_promise(value: JsValue) -> JsPromise {
+        match value {
+            JsValue::Array(mut values) => match values.remove(0) {
+                JsValue::Promise(p) => p,
+                other => panic!("Expected a promise, got {:?}", other),
+            },
+            other => panic!("Expected an array, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn promise_handle() {
+        let c = Context::new().unwrap();
+
+        let p = first_promise(c.eval("[Promise.resolve(1).then(x => x + 1)]").unwrap());
+        assert_eq!(p.state(), PromiseState::Pending);
+        assert_eq!(p.result(), None);
+        assert_eq!(p.await_result(), Ok(JsValue::Int(2)));
+        assert_eq!(p.state(), PromiseState::Fulfilled);
+        assert_eq!(p.result(), Some(Ok(JsValue::Int(2))));
+
+        let p = first_promise(c.eval("[Promise.reject(new Error('nope'))]").unwrap());
+        assert_eq!(
+            p.await_result(),
+            Err(ExecutionError::Exception("Error: nope".into()))
+        );
+        assert_eq!(p.state(), PromiseState::Rejected);
+
+        // Settled by running the event loop during another eval.
+        let p = first_promise(c.eval("[Promise.resolve('x')]").unwrap());
+        c.eval("Promise.resolve()").unwrap();
+        assert_eq!(p.result(), Some(Ok(JsValue::String("x".into()))));
+
+        let p = first_promise(c.eval("[new Promise(() => {})]").unwrap());
+        assert!(matches!(p.await_result(), Err(ExecutionError::Internal(_))));
+    }
+
+    /// Minimal executor that polls a future until it is ready.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::task::{Context as TaskContext, Poll, RawWaker, RawWakerVTable, Waker};
+
+        fn raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+
+        let waker = unsafe { Waker::from_raw(raw_waker()) };
+        let mut cx = TaskContext::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+                return value;
+            }
+        }
+    }
+
+    #[test]
+    fn eval_async_future() {
+        let c = Context::new().unwrap();
+
+        assert_eq!(block_on(c.eval_async("1 + 2")), Ok(JsValue::Int(3)));
+        assert_eq!(
+            block_on(c.eval_async(
+                r#"
+                (async () => {
+                    let x = 0;
+                    for (let i = 0; i < 1000; i++) {
+                        x += await Promise.resolve(i);
+                    }
+                    return x;
+                })()
+                "#
+            )),
+            Ok(JsValue::Int(499500))
+        );
+        assert_eq!(
+            block_on(c.eval_async("Promise.reject('Failed...')")),
+            Err(ExecutionError::Exception("Failed...".into()))
+        );
+        assert!(block_on(c.eval_async("+")).is_err());
+
+        // The future does not borrow the context.
+        let future = c.eval_async("Promise.resolve(7)");
+        drop(c);
+        assert_eq!(block_on(future), Ok(JsValue::Int(7)));
+    }
+
+    #[test]
+    fn pending_jobs() {
+        let c = Context::new().unwrap();
+        assert!(!c.has_pending_jobs());
+        assert_eq!(c.execute_pending_jobs(None), Ok(0));
+
+        c.eval(
+            r#"
+            var log = [];
+            for (let i = 0; i < 3; i++) {
+                Promise.resolve(i).then((x) => log.push(x));
+            }
+            Promise.resolve().then(() => { throw new Error('job failed'); });
+            0
+            "#,
+        )
+        .unwrap();
+        assert!(c.has_pending_jobs());
+        assert_eq!(c.execute_pending_jobs(Some(2)), Ok(2));
+        assert_eq!(c.eval("log.length"), Ok(JsValue::Int(2)));
+        assert_eq!(c.execute_pending_jobs(Some(1)), Ok(1));
+        // Rejections are handled by the promise, not reported as job errors.
+        assert_eq!(c.execute_pending_jobs(None), Ok(1));
+        assert!(!c.has_pending_jobs());
+
+        // Jobs of other contexts in the same runtime are executed too.
+        let other = c.runtime().new_context().unwrap();
+        other.eval("Promise.resolve().then(() => {}); 0").unwrap();
+        assert!(c.has_pending_jobs());
+        assert_eq!(c.execute_pending_jobs(None), Ok(1));
+    }
+
+    #[test]
+    fn queue_microtask() {
+        let c = Context::new().unwrap();
+        c.eval(
+            r#"
+            var log = [];
+            Promise.resolve().then(() => {
+                log.push('promise 1');
+                queueMicrotask(() => log.push('nested'));
+            });
+            queueMicrotask(() => log.push('microtask 1'));
+            Promise.resolve().then(() => log.push('promise 2'));
+            queueMicrotask(() => log.push('microtask 2'));
+            0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(c.eval("log.length"), Ok(JsValue::Int(0)));
+        assert_eq!(c.execute_pending_jobs(None), Ok(5));
+        assert_eq!(
+            c.eval("log.join()"),
+            Ok("promise 1,microtask 1,promise 2,microtask 2,nested".into())
+        );
+
+        // Exceptions of microtasks are returned by the job pump.
+        c.eval("queueMicrotask(() => { throw new Error('failed'); }); 0")
+            .unwrap();
+        assert_eq!(
+            c.execute_pending_jobs(None),
+            Err(ExecutionError::Exception("Error: failed".into()))
+        );
+        assert!(!c.has_pending_jobs());
+
+        assert_eq!(
+            c.eval("try { queueMicrotask(1); } catch (e) { e instanceof TypeError }"),
+            Ok(JsValue::Bool(true))
+        );
+        assert!(!c.has_pending_jobs());
+    }
+
+    #[test]
+    fn promise_roundtrip() {
+        let c = Context::new().unwrap();
+        c.eval("var p = Promise.resolve(5);").unwrap();
+
+        let p = first_promise(c.eval("[p]").unwrap());
+        assert_eq!(p, first_promise(c.eval("[p]").unwrap()));
+        c.eval("var isP = (x) => x === p;").unwrap();
+        assert_eq!(
+            c.call_function("isP", vec![p.clone()]),
+            Ok(JsValue::Bool(true))
+        );
+
+        // Promises are passed to callbacks as handles.
+        c.add_callback("receive", |p: JsPromise| p.await_result().unwrap())
+            .unwrap();
+        assert_eq!(c.eval("receive(p)"), Ok(JsValue::Int(5)));
+
+        // The handle keeps the context alive.
+        drop(c);
+        assert_eq!(p.await_result(), Ok(JsValue::Int(5)));
+
+        let other = Context::new().unwrap();
+        assert!(other.call_function("isFinite", vec![p]).is_err());
+    }
+
+    #[test]
+    fn test_call() {
+        let c = Context::new().unwrap();
+
+        assert_eq!(
+            c.call_function("parseInt", vec!["22"]).unwrap(),
+            JsValue::Int(22),
+        );
+
+        c.eval(
+            r#"
+            function add(a, b) {
+                return a + b;
+            }
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            c.call_function("add", vec![5, 7]).unwrap(),
+            JsValue::Int(12),
+        );
+
+        c.eval(
+            r#"
+            function sumArray(arr) {
+                let sum = 0;
+                for (const value of arr) {
+                    sum += value;
+                }
+                return sum;
+            }
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            c.call_function("sumArray", vec![vec![1, 2, 3]]).unwrap(),
+            JsValue::Int(6),
+        );
+
+        c.eval(
+            r#"
+            function addObject(obj) {
+                let sum = 0;
+                for (const key of Object.keys(obj)) {
+                    sum += obj[key];
+                }
+                return sum;
+            }
+        "#,
+        )
+        .unwrap();
+        let mut obj = std::collections::HashMap::<String, i32>::new();
+        obj.insert("a".into(), 10);
+        obj.insert("b".into(), 20);
+        obj.insert("c".into(), 30);
+        assert_eq!(
+            c.call_function("addObject", vec![obj]).unwrap(),
+            JsValue::Int(60),
+        );
+    }
+
+    #[test]
+    fn test_call_large_string() {
+        let c = Context::new().unwrap();
+        c.eval(" function strLen(s) { return s.length; } ").unwrap();
+
+        let s = " ".repeat(200_000);
+        let v = c.call_function("strLen", vec![s]).unwrap();
+        assert_eq!(v, JsValue::Int(200_000));
+    }
+
+    #[test]
+    fn call_async() {
+        let c = Context::new().unwrap();
+
+        c.eval(
+            r#"
+            function asyncOk() {
+                return new Promise((resolve, _) => {
+                    resolve(33);
+                });
+            }
+
+            function asyncErr() {
+                return new Promise((_resolve, reject) => {
+                    reject("Failed...");
+                });
+            }
+        "#,
+        )
+        .unwrap();
+
+        let value = c.call_function("asyncOk", vec![true]).unwrap();
+        assert_eq!(value, JsValue::Int(33));
+
+        let res = c.call_function("asyncErr", vec![true]);
+        assert_eq!(
+            res,
+            Err(ExecutionError::Exception(
+                JsValue::String("Failed...".into()).into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_callback() {
+        let c = Context::new().unwrap();
+
+        c.add_callback("cb1", |flag: bool| !flag).unwrap();
+        assert_eq!(c.eval("cb1(true)").unwrap(), JsValue::Bool(false),);
+
+        c.add_callback("concat2", |a: String, b: String| format!("{}{}", a, b))
+            .unwrap();
+        assert_eq!(
+            c.eval(r#"concat2("abc", "def")"#).unwrap(),
+            JsValue::String("abcdef".into()),
+        );
+
+        c.add_callback("add2", |a: i32, b: i32| -> i32 { a + b })
+            .unwrap();
+        assert_eq!(c.eval("add2(5, 11)").unwrap(), JsValue::Int(16),);
+    }
+
+    #[test]
+    fn test_callback_argn_variants() {
+        macro_rules! callback_argn_tests {
+            [
+                $(
+                    $len:literal : ( $( $argn:ident : $argv:literal ),* ),
+                )*
+            ] => {
+                $(
+                   {
+                       // Test plain return type.
+                        let name = format!("cb{}", $len);
+                        let c = Context::new().unwrap();
+                        c.add_callback(&name, | $( $argn : i32 ),*| -> i32 {
+                            $( $argn + )* 0
+                        }).unwrap();
+
+                        let code = format!("{}( {} )", name, "1,".repeat($len));
+                        let v = c.eval(&code).unwrap();
+                        assert_eq!(v, JsValue::Int($len));
+
+                        // Test Result<T, E> return type with OK(_) returns.
+                        let name = format!("cbres{}", $len);
+                        c.add_callback(&name, | $( $argn : i32 ),*| -> Result<i32, String> {
+                            Ok($( $argn + )* 0)
+                        }).unwrap();
+
+                        let code = format!("{}( {} )", name, "1,".repeat($len));
+                        let v = c.eval(&code).unwrap();
+                        assert_eq!(v, JsValue::Int($len));
+
+                        // Test Result<T, E> return type with Err(_) returns.
+                        let name = format!("cbreserr{}", $len);
+                        c.add_callback(&name, #[allow(unused_variables)] | $( $argn : i32 ),*| -> Result<i32, String> {
+                            Err("error".into())
+                        }).unwrap();
+
+                        let code = format!("{}( {} )", name, "1,".repeat($len));
+                        let res = c.eval(&code);
+                        assert_eq!(res, Err(ExecutionError::Exception("error".into())));
+                   }
+                )*
+            }
+        }
+
+        callback_argn_tests![
+            1: (a : 1),
+        ]
+    }
+
+    #[test]
+    fn test_callback_varargs() {
+        let c = Context::new().unwrap();
+
+        // No return.
+        c.add_callback("cb", |args: Arguments| {
+            let args = args.into_vec();
+            assert_eq!(
+                args,
+                vec![
+                    JsValue::String("hello".into()),
+                    JsValue::Bool(true),
+                    JsValue::from(100),
+                ]
+            );
+        })
+        .unwrap();
+        c.eval(" cb('hello', true, 100) ").unwrap();
+
+        // With return.
+        c.add_callback("cb2", |args: Arguments| -> u32 {
+            let args = args.into_vec();
+            assert_eq!(
+                args,
+                vec![JsValue::from(1), JsValue::from(10), JsValue::from(100),]
+            );
+            111
+        })
+        .unwrap();
+        c.eval(
+            r#"
+           var x = cb2(1, 10, 100);
+           if (x !== 111) {
+            throw new Error('Expected 111, got ' + x);
+           }
+       "#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_callback_invalid_argcount() {
+        let c = Context::new().unwrap();
+
+        c.add_callback("cb", |a: i32, b: i32| a + b).unwrap();
+
+        assert_eq!(
+            c.eval(" cb(5) "),
+            Err(ExecutionError::Exception(
+                "Invalid argument count: Expected 2, got 1".into()
+            )),
+        );
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let c = Context::new().unwrap();
+
+        assert_eq!(
+            c.eval("new Uint8Array([1, 2, 255])"),
+            Ok(JsValue::Bytes(vec![1, 2, 255]))
+        );
+        assert_eq!(
+            c.eval("new Uint8Array([0, 1, 2, 3, 4]).subarray(1, 3)"),
+            Ok(JsValue::Bytes(vec![1, 2]))
+        );
+        assert_eq!(
+            c.eval("new Uint8Array([7, 8]).buffer"),
+            Ok(JsValue::Bytes(vec![7, 8]))
+        );
+        assert_eq!(c.eval("new ArrayBuffer(0)"), Ok(JsValue::Bytes(vec![])));
+
+        c.add_callback("invert", |args: Arguments| {
+            let bytes = args.into_vec().remove(0).into_bytes().unwrap();
+            JsValue::Bytes(bytes.into_iter().map(|b| !b).collect())
+        })
+        .unwrap();
+        assert_eq!(
+            c.eval("let x = invert(new Uint8Array([0, 255, 15])); [x instanceof Uint8Array, x[2]]"),
+            Ok(JsValue::Array(vec![JsValue::Bool(true), JsValue::Int(240)]))
+        );
+
+        let data = (0..=255).collect::<Vec<u8>>();
+        c.eval("var identity = (x) => x;").unwrap();
+        assert_eq!(
+            c.call_function("identity", vec![JsValue::Bytes(data.clone())]),
+            Ok(JsValue::Bytes(data))
+        );
+    }
+
+    #[test]
+    fn memory_limit_exceeded() {
+        // quickjs-ng needs more memory to set up a context.
+        let limit = if cfg!(feature = "ng") {
+            200_000
+        } else {
+            100_000
+        };
+        let c = Context::builder().memory_limit(limit).build().unwrap();
+        assert_eq!(
+            c.eval("  'abc'.repeat(200_000) "),
+            Err(ExecutionError::OutOfMemory),
+        );
+    }
+
+    #[test]
+    fn runtime_allocator() {
+        use std::alloc::{Layout, System};
+        use std::sync::atomic::AtomicUsize;
+
+        struct Counting(Arc<AtomicUsize>);
+
+        unsafe impl GlobalAlloc for Counting {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                System.alloc(layout)
+            }
+
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+                System.dealloc(ptr, layout)
+            }
+        }
+
+        let blocks = Arc::new(AtomicUsize::new(0));
+        let runtime = Runtime::with_allocator(Counting(blocks.clone())).unwrap();
+        let c = Context::builder().runtime(&runtime).build().unwrap();
+        assert!(blocks.load(Ordering::SeqCst) > 0);
+        assert_eq!(
+            c.eval("[1, 2, 3].map(x => x * 2).join('') + 'abc'.repeat(1000).length"),
+            Ok(JsValue::from("2463000"))
+        );
+        assert!(runtime.memory_usage().malloc_size > 0);
+
+        runtime.set_memory_limit(runtime.memory_usage().malloc_size as usize + 100_000);
+        assert_eq!(
+            c.eval("'abc'.repeat(200_000)"),
+            Err(ExecutionError::OutOfMemory)
+        );
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
+
+        drop(c);
+        drop(runtime);
+        assert_eq!(blocks.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn allocation_stats() {
+        let c = Context::new().unwrap();
+        let start = c.allocation_stats();
+        assert!(start.current_bytes > 0);
+        assert!(start.allocation_count > 0);
+
+        let metered = c.eval_metered(
+            "var kept = 'a'.repeat(50000); 'b'.repeat(200000).length",
+            Default::default(),
+        );
+        assert_eq!(metered.result, Ok(JsValue::Int(200000)));
+        let allocations = metered.allocations;
+        assert!(allocations.current_bytes >= start.current_bytes + 50000);
+        assert!(allocations.peak_bytes >= allocations.current_bytes + 200000);
+        assert!(allocations.total_bytes >= 250000);
+        assert!(allocations.allocation_count > 0);
+
+        // The freed string does not count towards the next evaluation.
+        let metered = c.eval_metered("kept.length", Default::default());
+        assert_eq!(metered.result, Ok(JsValue::Int(50000)));
+        assert!(metered.allocations.peak_bytes < allocations.peak_bytes);
+        assert!(metered.allocations.total_bytes < 50000);
+
+        let stats = c.allocation_stats();
+        assert_eq!(stats.peak_bytes, allocations.peak_bytes);
+        assert!(stats.total_bytes >= start.total_bytes + 250000);
+    }
+
+    #[test]
+    fn max_stack_size() {
+        let code = "function f(n) { return n && f(n - 1) + 1; } f(100)";
+
+        let c = Context::new().unwrap();
+        assert_eq!(c.eval(code), Ok(JsValue::Int(100)));
+
+        let c = Context::builder()
+            .max_stack_size(16 * 1024)
+            .build()
+            .unwrap();
+        assert_eq!(c.eval(code), Err(ExecutionError::StackOverflow));
+        // The context is still usable afterwards.
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
+        // Scripts can catch the error.
+        assert_eq!(
+            c.eval(&format!("try {{ {} }} catch (e) {{ 'caught' }}", code)),
+            Ok(JsValue::from("caught"))
+        );
+
+        // Deeply nested values can not overflow the stack when converted.
+        let c = Context::new().unwrap();
+        assert!(c
+            .eval("let a = []; for (let i = 0; i < 100000; i++) a = [a]; a")
+            .is_err());
+        assert!(c.eval("let o = {}; o.self = o; o").is_err());
+        let nested = c.eval("let b = []; for (let i = 0; i < 100; i++) b = [b]; b");
+        assert!(nested.is_ok());
+    }
+
+    #[test]
+    fn exception_stack() {
+        let c = Context::new().unwrap();
+        c.add_callback("fail", || -> Result<i32, String> { Err("nope".into()) })
+            .unwrap();
+
+        let e = match c.eval("function outer() {\n  [1].map(() => inner());\n}\nfunction inner() { null.x }\nouter()") {
+            Err(ExecutionError::Exception(e)) => e,
+            other => panic!("expected exception, got {:?}", other),
+        };
+        assert_eq!(
+            e.value,
+            JsValue::from("TypeError: cannot read property 'x' of null")
+        );
+        assert_eq!(e.name, "TypeError");
+        assert_eq!(e.message, "cannot read property 'x' of null");
+        let functions: Vec<_> = e.stack.iter().map(|f| f.function.as_str()).collect();
+        assert_eq!(
+            functions,
+            ["inner", "<anonymous>", "map", "outer", "<eval>"]
+        );
+        assert_eq!(
+            e.stack[2],
+            StackFrame {
+                function: "map".into(),
+                file: None,
+                line: None,
+                column: None,
+            }
+        );
+        assert_eq!(e.stack[3].line, Some(2));
+        assert_eq!(e.stack[4].file.as_deref(), Some("script.js"));
+        assert_eq!(e.stack[4].line, Some(5));
+        assert!(e
+            .to_string()
+            .starts_with("TypeError: cannot read property 'x' of null\n    at inner (script.js"));
+
+        // Syntax errors report the location without a function.
+        let e = match c.eval("1 +\n+") {
+            Err(ExecutionError::Exception(e)) => e,
+            other => panic!("expected exception, got {:?}", other),
+        };
+        assert_eq!(e.name, "SyntaxError");
+        assert_eq!(e.stack[0].function, "");
+        assert_eq!(e.stack[0].line, Some(2));
+
+        // Other values have no name or stack.
+        let e = match c.eval("fail()") {
+            Err(ExecutionError::Exception(e)) => e,
+            other => panic!("expected exception, got {:?}", other),
+        };
+        assert_eq!(e.name, "");
+        assert_eq!(e.message, "nope");
+        assert!(e.stack.is_empty());
+
+        // Rejected promises are captured too.
+        let e = match c.eval("(async () => { throw new RangeError('late') })()") {
+            Err(ExecutionError::Exception(e)) => e,
+            other => panic!("expected exception, got {:?}", other),
+        };
+        assert_eq!(e.name, "RangeError");
+        assert_eq!(e.message, "late");
+    }
+
+    #[test]
+    fn eval_with_options() {
+        let c = Context::new().unwrap();
+
+        assert_eq!(
+            c.eval_with_options("1 + 2", EvalOptions::default()),
+            Ok(JsValue::Int(3))
+        );
+
+        let options = EvalOptions {
+            filename: "lib/user.js".into(),
+            line_offset: 3,
+            ..Default::default()
+        };
+        let e = match c.eval_with_options(
+            "var a = 1;\nfunction f() { return a; }\nf(); throw new Error('x')",
+            options,
+        ) {
+            Err(ExecutionError::Exception(e)) => e,
+            other => panic!("expected exception, got {:?}", other),
+        };
+        assert_eq!(e.stack[0].file.as_deref(), Some("lib/user.js"));
+        assert_eq!(e.stack[0].line, Some(6));
+        // Declarations still end up in the global scope.
+        assert_eq!(c.eval("f()"), Ok(JsValue::Int(1)));
+
+        let strict = EvalOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert_eq!(c.eval("undeclared = 1"), Ok(JsValue::Int(1)));
+        let message = if cfg!(feature = "ng") {
+            "ReferenceError: undeclared2 is not defined"
+        } else {
+            "ReferenceError: 'undeclared2' is not defined"
+        };
+        assert_eq!(
+            c.eval_with_options("undeclared2 = 1", strict),
+            Err(ExecutionError::Exception(message.into()))
+        );
+
+        let module = EvalOptions {
+            filename: "main.js".into(),
+            module: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            c.eval_with_options("export const x = 1; globalThis.y = x + 1;", module),
+            Ok(JsValue::Undefined)
+        );
+        assert_eq!(c.eval("y"), Ok(JsValue::Int(2)));
+    }
+
+    #[test]
+    fn check_syntax() {
+        let c = Context::new().unwrap();
+
+        assert_eq!(c.check_syntax("globalThis.ran = true;"), Ok(()));
+        assert_eq!(c.eval("typeof ran"), Ok(JsValue::from("undefined")));
+
+        let err = c
+            .check_syntax("function f() {\n  return 1;\n}\nf(;")
+            .unwrap_err();
+        assert_eq!(err.line, Some(4));
+        assert!(err.to_string().starts_with("SyntaxError: "), "{}", err);
+        // quickjs-ng also reports the column.
+        #[cfg(not(feature = "ng"))]
+        {
+            assert_eq!(err.column, None);
+            assert!(err.to_string().ends_with(" at line 4"), "{}", err);
+        }
+        #[cfg(feature = "ng")]
+        {
+            assert!(err.column.is_some());
+            assert!(err.to_string().contains(" at 4:"), "{}", err);
+        }
+
+        // Runtime errors are not syntax errors.
+        assert_eq!(c.check_syntax("null.x"), Ok(()));
+
+        let err = c.check_syntax("1\0").unwrap_err();
+        assert!(err.message.contains("\\0"), "{}", err);
+        assert_eq!(err.line, None);
+    }
+
+    #[test]
+    fn eval_flags() {
+        let c = Context::new().unwrap();
+
+        let compile_only = EvalOptions {
+            compile_only: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            c.eval_with_options("globalThis.ran = true; 1", compile_only.clone()),
+            Ok(JsValue::Null)
+        );
+        assert_eq!(c.eval("typeof ran"), Ok(JsValue::from("undefined")));
+        match c.eval_with_options("1 +", compile_only.clone()) {
+            Err(ExecutionError::Exception(e)) => assert_eq!(e.name, "SyntaxError"),
+            other => panic!("expected syntax error, got {:?}", other),
+        }
+        let module = EvalOptions {
+            module: true,
+            ..compile_only
+        };
+        assert_eq!(
+            c.eval_with_options("export const x = 1;", module),
+            Ok(JsValue::Null)
+        );
+
+        let strip = EvalOptions {
+            strip: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            c.eval_with_options("(function f() { return 1; }).toString()", strip.clone()),
+            Ok(JsValue::from("function f() {\n    [native code]\n}"))
+        );
+        match c.eval_with_options("throw new Error('x')", strip) {
+            Err(ExecutionError::Exception(e)) => assert_eq!(e.stack[0].line, None),
+            other => panic!("expected exception, got {:?}", other),
+        }
+
+        c.add_callback_with_context("run", |ctx: &CallbackContext, args: Arguments| {
+            let barrier = args.into_vec().pop() == Some(JsValue::Bool(true));
+            let options = EvalOptions {
+                backtrace_barrier: barrier,
+                ..Default::default()
+            };
+            match ctx
+                .context()
+                .eval_with_options("throw new Error('x')", options)
+            {
+                Err(ExecutionError::Exception(e)) => Ok(e
+                    .stack
+                    .iter()
+                    .map(|frame| frame.function.clone())
+                    .collect::<Vec<_>>()
+                    .join(",")),
+                other => Err(ctx.throw(format!("unexpected result: {:?}", other))),
+            }
+        })
+        .unwrap();
+        // quickjs-ng includes the frame of the native callback.
+        let frames = if cfg!(feature = "ng") {
+            "<eval>,<anonymous>,outer,<eval>"
+        } else {
+            "<eval>,outer,<eval>"
+        };
+        assert_eq!(
+            c.eval("function outer(barrier) { return run(barrier); } outer(false)"),
+            Ok(JsValue::from(frames))
+        );
+        assert_eq!(c.eval("outer(true)"), Ok(JsValue::from("<eval>")));
+    }
+
+    #[test]
+    fn memory_usage() {
+        let c = Context::builder().memory_limit(10_000_000).build().unwrap();
+        let before = c.memory_usage();
+        assert_eq!(before.malloc_limit, 10_000_000);
+        assert!(before.memory_used_size > 0);
+
+        c.eval("var s = 'x'.repeat(100000) + 'y';").unwrap();
+        let after = c.memory_usage();
+        assert!(after.memory_used_size >= before.memory_used_size + 100_000);
+        assert!(after.str_size >= before.str_size + 100_000);
+    }
+
+    #[test]
+    fn run_gc() {
+        // Prevent automatic collection during the test.
+        let c = Context::builder().gc_threshold(usize::MAX).build().unwrap();
+        c.eval(
+            r#"
+            for (let i = 0; i < 1000; i++) {
+                const a = {};
+                a.b = { a };
+            }
+            undefined
+        "#,
+        )
+        .unwrap();
+
+        let before = c.memory_usage().obj_count;
+        c.run_gc();
+        assert!(c.memory_usage().obj_count + 2000 <= before);
+    }
+
+    #[test]
+    fn register_finalizer() {
+        use std::cell::RefCell;
+
+        let c = Context::builder().gc_threshold(usize::MAX).build().unwrap();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let register = |name: &'static str, code: &str| {
+            let object = JsObject::try_from(c.eval_owned(code).unwrap()).unwrap();
+            let log = log.clone();
+            c.register_finalizer(&object, move || log.borrow_mut().push(name))
+                .unwrap();
+        };
+
+        // A cycle, which is only freed by the garbage collector.
+        register("cycle", "var a = {}; a.self = a; a");
+        register("kept", "var b = {}; b");
+        c.eval("a = null").unwrap();
+        assert!(!c.has_pending_jobs());
+        // quickjs frees the sentinels of collected cycles in the next
+        // collection.
+        c.run_gc();
+        c.run_gc();
+        assert!(c.has_pending_jobs());
+        assert!(log.borrow().is_empty());
+        assert_eq!(c.execute_pending_jobs(None), Ok(1));
+        assert_eq!(*log.borrow(), vec!["cycle"]);
+
+        // Pending finalizers run when the runtime is dropped.
+        c.eval("b = null").unwrap();
+        c.run_gc();
+        drop(c);
+        assert_eq!(*log.borrow(), vec!["cycle", "kept"]);
+
+        // Registrations of a context run when it is freed.
+        let runtime = Runtime::new().unwrap();
+        let c = runtime.new_context().unwrap();
+        let object = JsObject::try_from(c.eval_owned("globalThis.x = {}").unwrap()).unwrap();
+        let context_log = log.clone();
+        c.register_finalizer(&object, move || context_log.borrow_mut().push("context"))
+            .unwrap();
+        drop(object);
+        drop(c);
+        let other = runtime.new_context().unwrap();
+        other.run_gc();
+        assert_eq!(other.execute_pending_jobs(None), Ok(1));
+        assert_eq!(*log.borrow(), vec!["cycle", "kept", "context"]);
+    }
+
+    #[cfg(feature = "ng")]
+    #[test]
+    fn weak_refs() {
+        let c = Context::builder().gc_threshold(usize::MAX).build().unwrap();
+        c.eval(
+            r#"
+            var held = [];
+            var registry = new FinalizationRegistry((value) => held.push(value));
+            var ref = (() => {
+                const target = {};
+                registry.register(target, 'target');
+                return new WeakRef(target);
+            })();
+            "#,
+        )
+        .unwrap();
+        c.run_gc();
+        c.execute_pending_jobs(None).unwrap();
+        assert_eq!(c.eval("ref.deref() === undefined"), Ok(JsValue::Bool(true)));
+        assert_eq!(c.eval("held.join()"), Ok("target".into()));
+    }
+
+    #[test]
+    fn compile_and_eval_compiled() {
+        let c = Context::new().unwrap();
+        let bytecode = c
+            .compile("var counter = (counter || 0) + 1; counter", "counter.js")
+            .unwrap();
+        assert_eq!(c.eval("typeof counter"), Ok(JsValue::from("undefined")));
+
+        // The bytecode can be executed repeatedly, and in other contexts.
+        assert_eq!(unsafe { c.eval_compiled(&bytecode) }, Ok(JsValue::Int(1)));
+        assert_eq!(unsafe { c.eval_compiled(&bytecode) }, Ok(JsValue::Int(2)));
+        let c2 = Context::new().unwrap();
+        assert_eq!(unsafe { c2.eval_compiled(&bytecode) }, Ok(JsValue::Int(1)));
+
+        let bytecode = c.compile("(async () => 'done')()", "async.js").unwrap();
+        assert_eq!(
+            unsafe { c.eval_compiled(&bytecode) },
+            Ok(JsValue::from("done"))
+        );
+
+        assert!(matches!(
+            c.compile("let x = ;", "broken.js"),
+            Err(ExecutionError::Exception(_))
+        ));
+        assert_eq!(
+            unsafe { c.eval_compiled(&[]) },
+            Err(ExecutionError::Bytecode(bytecode::BytecodeError::Invalid))
+        );
+    }
+
+    #[test]
+    fn bytecode_version() {
+        use bytecode::BytecodeError;
+
+        let c = Context::new().unwrap();
+        let compiled = c.compile("1 + 2", "add.js").unwrap();
+        assert!(bytecode::is_compatible(&compiled));
+
+        // Bytecode of another version is rejected without executing it.
+        let version = bytecode::version();
+        let other = compiled
+            .windows(version.len())
+            .position(|w| w == version.as_bytes())
+            .unwrap();
+        let mut outdated = compiled.clone();
+        outdated[other] = b'!';
+        assert!(!bytecode::is_compatible(&outdated));
+        match unsafe { c.eval_compiled(&outdated) } {
+            Err(ExecutionError::Bytecode(BytecodeError::VersionMismatch { expected, found })) => {
+                assert_eq!(expected, version);
+                assert_eq!(found, format!("!{}", &version[1..]));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Truncated headers are invalid.
+        assert!(!bytecode::is_compatible(&compiled[..other]));
+        assert_eq!(
+            unsafe { c.eval_compiled(&compiled[..other]) },
+            Err(ExecutionError::Bytecode(BytecodeError::Invalid))
+        );
+        assert_eq!(unsafe { c.eval_compiled(&compiled) }, Ok(JsValue::Int(3)));
+    }
+
+    #[cfg(feature = "leak-tracking")]
+    #[test]
+    fn leak_tracking() {
+        let c = Context::new().unwrap();
+        assert_eq!(c.wrapper.leaked_handles(), None);
+
+        // The handle would keep the context alive once `c` is dropped.
+        let f: JsFunction = c.eval_as("() => 42").unwrap();
+        let report = c.wrapper.leaked_handles().unwrap();
+        assert!(report.contains("1 handle(s)"), "{}", report);
+        assert!(report.contains("handle created at"), "{}", report);
+
+        // Handles held by other contexts sharing the wrapper are not leaks.
+        let other = Context::from_wrapper(c.wrapper.clone());
+        assert_eq!(c.wrapper.leaked_handles(), None);
+        drop(other);
+        drop(f);
+        assert_eq!(c.wrapper.leaked_handles(), None);
+
+        // Converting deeply nested values leaves no pending exception, which
+        // would keep the objects alive.
+        drop(c);
+        let runtime = Runtime::new().unwrap();
+        let c = runtime.new_context().unwrap();
+        assert!(c
+            .eval("let o = {}; for (let i = 0; i < 1000; i++) o = {x: o}; o")
+            .is_err());
+        let runtime_wrapper = c.wrapper.runtime().clone();
+        drop(c);
+        assert_eq!(runtime_wrapper.leaked_objects(), None);
+    }
+
+    #[test]
+    fn assert_no_leaks() {
+        let c = Context::new().unwrap();
+        c.add_callback("concat", |a: String, b: String| a + &b)
+            .unwrap();
+        c.add_callback("fail", || -> Result<i32, String> { Err("nope".into()) })
+            .unwrap();
+        c.eval("function identity(value) { return value; }")
+            .unwrap();
+
+        let values = c
+            .eval(
+                r#"[
+                    1, 1.5, true, null, 'text', [1, [2]], { a: { b: 'c' } },
+                    new Date(0), new Uint8Array([1, 2]), new ArrayBuffer(4),
+                    new Map([[1, 'one']]), new Set(['x']),
+                ]"#,
+            )
+            .unwrap();
+        #[cfg(feature = "bignum")]
+        let values = JsValue::Array(vec![values, c.eval("10n").unwrap()]);
+        c.assert_no_leaks(|| {
+            // Conversions in both directions.
+            assert_eq!(
+                c.call_function("identity", vec![values.clone()]),
+                Ok(values.clone())
+            );
+            assert_eq!(
+                c.call_function("concat", vec!["a", "b"]),
+                Ok(JsValue::from("ab"))
+            );
+            // Exceptions of scripts and callbacks.
+            assert!(c.eval("null.x").is_err());
+            assert!(c.eval("fail()").is_err());
+            assert!(c.eval("let x = ;").is_err());
+            // Handles and promises.
+            let f: JsFunction = c.eval_as("(a, b) => a * b").unwrap();
+            assert_eq!(f.call(vec![6, 7]), Ok(JsValue::Int(42)));
+            assert_eq!(c.eval("Promise.resolve(5)"), Ok(JsValue::Int(5)));
+        });
+
+        // Values that are kept alive are reported.
+        let mut kept = Vec::<JsFunction>::new();
+        let leaked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            c.assert_no_leaks(|| kept.push(c.eval_as("() => 1").unwrap()));
+        }));
+        assert!(leaked.is_err());
+        kept.clear();
+    }
+
+    #[test]
+    fn owned_value_handles() {
+        let c = Context::new().unwrap();
+        c.eval(
+            r#"
+            function makeCounter() {
+                let count = 0;
+                return () => ++count;
+            }
+            function callTwice(f) { f(); return f(); }
+        "#,
+        )
+        .unwrap();
+
+        // Closures keep their state between calls.
+        let counter = c
+            .call_function_owned("makeCounter", Vec::<JsValue>::new())
+            .unwrap();
+        assert_eq!(
+            c.call_function("callTwice", vec![counter.clone()]),
+            Ok(JsValue::Int(2))
+        );
+        assert_eq!(
+            c.call_function("callTwice", vec![counter.clone()]),
+            Ok(JsValue::Int(4))
+        );
+
+        // Handles survive garbage collection and are compared by identity.
+        c.run_gc();
+        let other = c
+            .call_function_owned("makeCounter", Vec::<JsValue>::new())
+            .unwrap();
+        assert_eq!(counter, counter.clone());
+        assert_ne!(counter, other);
+
+        // Handles keep the context alive.
+        drop(c);
+        let value = counter.to_value().unwrap();
+        assert!(matches!(value, JsValue::Function(_)));
+    }
+
+    #[test]
+    fn js_function() {
+        let c = Context::new().unwrap();
+
+        let counter: JsFunction = c.eval_as("let count = 0; () => ++count").unwrap();
+        assert_eq!(counter.call(Vec::<JsValue>::new()), Ok(JsValue::Int(1)));
+        assert_eq!(counter.call(Vec::<JsValue>::new()), Ok(JsValue::Int(2)));
+
+        // Functions nested in objects.
+        let obj = c
+            .eval("({ double: x => x * 2, fail: () => { throw 'nope'; } })")
+            .unwrap();
+        let mut map = match obj {
+            JsValue::Object(map) => map,
+            other => panic!("expected object, got {:?}", other),
+        };
+        let double = JsFunction::try_from(map.remove("double").unwrap()).unwrap();
+        assert_eq!(double.call(vec![21]), Ok(JsValue::Int(42)));
+        let fail = JsFunction::try_from(map.remove("fail").unwrap()).unwrap();
+        assert_eq!(
+            fail.call(Vec::<JsValue>::new()),
+            Err(ExecutionError::Exception("nope".into()))
+        );
+
+        // Functions can be passed back to JS.
+        c.eval("function apply(f, x) { return f(x); }").unwrap();
+        assert_eq!(
+            c.call_function("apply", vec![JsValue::from(double), 5.into()]),
+            Ok(JsValue::Int(10))
+        );
+
+        // Async functions are awaited.
+        let f: JsFunction = c.eval_as("async x => x + 1").unwrap();
+        drop(c);
+        assert_eq!(f.call(vec![1]), Ok(JsValue::Int(2)));
+    }
+
+    #[test]
+    fn callback_with_context() {
+        let c = Context::new().unwrap();
+        c.add_callback_with_context("evalTwice", |ctx: &CallbackContext, args: Arguments| {
+            let code = match args.into_vec().pop() {
+                Some(JsValue::String(code)) => code,
+                _ => return Err(ctx.throw("expected code")),
+            };
+            ctx.context().eval(&code)?;
+            ctx.context().eval(&code)
+        })
+        .unwrap();
+        c.add_callback_with_context("bump", |ctx: &CallbackContext, _args: Arguments| {
+            let count = match ctx.get_global("count")? {
+                JsValue::Int(count) => count,
+                _ => 0,
+            };
+            ctx.set_global("count", count + 1)?;
+            Ok(count + 1)
+        })
+        .unwrap();
+
+        // Nested eval -> callback -> eval chains.
+        assert_eq!(c.eval("evalTwice('bump()')"), Ok(JsValue::Int(2)));
+        assert_eq!(
+            c.eval("evalTwice('evalTwice(\"bump()\")')"),
+            Ok(JsValue::Int(6))
+        );
+        assert_eq!(c.eval("count"), Ok(JsValue::Int(6)));
+
+        // Exceptions of nested executions are propagated, and can be caught.
+        assert_eq!(
+            c.eval("try { evalTwice('throw \"x\"') } catch (e) { e + '!' }"),
+            Ok(JsValue::from("x!"))
+        );
+        assert_eq!(
+            c.eval("evalTwice()"),
+            Err(ExecutionError::Exception("expected code".into()))
+        );
+    }
+
+    #[test]
+    fn reentrant_callbacks() {
+        let c = Context::new().unwrap();
+        c.add_callback("apply", |f: JsFunction, n: i32| f.call(vec![n]))
+            .unwrap();
+        c.add_callback_with_context("define", |ctx: &CallbackContext, _args: Arguments| {
+            ctx.context().add_callback("double", |n: i32| n * 2)?;
+            ctx.context().eval("double(21)")
+        })
+        .unwrap();
+
+        // Callbacks calling Javascript calling callbacks.
+        c.eval("function down(n) { return n && apply(down, n - 1) + 1; }")
+            .unwrap();
+        assert_eq!(c.eval("down(20)"), Ok(JsValue::Int(20)));
+
+        // Exceptions of the innermost execution are propagated as is.
+        c.eval("function fail(n) { if (!n) throw 'bottom'; return apply(fail, n - 1); }")
+            .unwrap();
+        assert_eq!(
+            c.eval("fail(10)"),
+            Err(ExecutionError::Exception("bottom".into()))
+        );
+
+        // Unbounded nesting overflows the stack, in all nested executions.
+        c.eval("function loop() { return apply(loop, 0); }")
+            .unwrap();
+        assert_eq!(c.eval("loop()"), Err(ExecutionError::StackOverflow));
+        assert_eq!(
+            c.eval("try { loop() } catch (e) { 'caught' }"),
+            Ok(JsValue::from("caught"))
+        );
+        assert_eq!(c.eval("down(5)"), Ok(JsValue::Int(5)));
+
+        // Callbacks can be added while the context executes code.
+        assert_eq!(c.eval("define() + double(1)"), Ok(JsValue::Int(44)));
+    }
+
+    #[test]
+    fn callback_panic() {
+        use module::ModuleSource;
+
+        let c = Context::new().unwrap();
+        c.add_callback("check", |n: i32| {
+            assert!(n > 0, "invalid");
+            n
+        })
+        .unwrap();
+        c.add_callback_with_context("nested", |ctx: &CallbackContext, _args: Arguments| {
+            ctx.context().eval("check(0)")
+        })
+        .unwrap();
+        c.add_async_callback("checkLater", |n: i32| async move {
+            assert!(n > 0, "invalid");
+            n
+        })
+        .unwrap();
+        c.set_module_loader(|_: &str| -> Result<ModuleSource, String> { panic!("invalid") });
+
+        // Panics are thrown as exceptions, which scripts can catch.
+        let panicked = Err(ExecutionError::Exception(
+            "Internal error: Callback panicked!".into(),
+        ));
+        assert_eq!(c.eval("check(0)"), panicked);
+        assert_eq!(c.eval("nested()"), panicked);
+        assert_eq!(
+            c.eval("try { check(0) } catch (e) { 'caught' }"),
+            Ok(JsValue::from("caught"))
+        );
+        assert_eq!(
+            c.eval("checkLater(0)"),
+            Err(ExecutionError::Exception(
+                "Internal error: Future panicked!".into()
+            ))
+        );
+        assert_eq!(
+            c.eval_module("import 'lib';", "main.js"),
+            Err(ExecutionError::Exception(
+                "ReferenceError: could not load module 'lib': Module loader panicked!".into()
+            ))
+        );
+
+        // The context stays usable afterwards.
+        assert_eq!(c.eval("checkLater(check(1))"), Ok(JsValue::Int(1)));
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
+    }
+
+    #[test]
+    fn callback_js_error() {
+        let c = Context::new().unwrap();
+        c.eval("class ValidationError extends Error {}").unwrap();
+        c.add_callback("fail", |kind: String| -> Result<i32, JsError> {
+            Err(match kind.as_str() {
+                "type" => JsError::type_error("bad type"),
+                "custom" => {
+                    JsError::new("ValidationError", "invalid").with_property("field", "age")
+                }
+                _ => JsError::new("UnknownError", "unknown").with_property("code", 42),
+            })
+        })
+        .unwrap();
+
+        assert_eq!(
+            c.eval("try { fail('type') } catch (e) { e instanceof TypeError && e.message }"),
+            Ok(JsValue::from("bad type"))
+        );
+        assert_eq!(
+            c.eval(
+                "try { fail('custom') } catch (e) { \
+                 [e instanceof ValidationError, e instanceof Error, e.name, e.field] }"
+            ),
+            Ok(JsValue::Array(vec![
+                true.into(),
+                true.into(),
+                "ValidationError".into(),
+                "age".into()
+            ]))
+        );
+        assert_eq!(
+            c.eval(
+                "try { fail('other') } catch (e) { \
+                 [e instanceof Error, e.name, e.message, e.code, String(e)] }"
+            ),
+            Ok(JsValue::Array(vec![
+                true.into(),
+                "UnknownError".into(),
+                "unknown".into(),
+                42.into(),
+                "UnknownError: unknown".into()
+            ]))
+        );
+        assert_eq!(
+            c.eval("fail('type')"),
+            Err(ExecutionError::Exception("TypeError: bad type".into()))
+        );
+
+        // Other errors are still thrown as strings.
+        c.add_callback("failString", || -> Result<i32, String> {
+            Err("nope".into())
+        })
+        .unwrap();
+        assert_eq!(
+            c.eval("try { failString() } catch (e) { e }"),
+            Ok(JsValue::from("nope"))
+        );
+
+        // Errors can also be thrown by callbacks with a context.
+        c.add_callback_with_context("failWithContext", |ctx: &CallbackContext, _: Arguments| {
+            Err::<i32, _>(ctx.throw_error(JsError::range_error("out of range")))
+        })
+        .unwrap();
+        assert_eq!(
+            c.eval("try { failWithContext() } catch (e) { e instanceof RangeError }"),
+            Ok(JsValue::Bool(true))
+        );
+    }
+
+    #[test]
+    fn async_callback() {
+        use futures::channel::oneshot;
+        use std::time::Duration;
+
+        let c = Context::new().unwrap();
+        c.add_async_callback("double", |x: i32| async move { x * 2 })
+            .unwrap();
+        c.add_async_callback("fail", |message: String| async move {
+            Err::<i32, _>(JsError::type_error(message))
+        })
+        .unwrap();
+        // Resolved by another thread, while the event loop sleeps.
+        c.add_async_callback("later", |value: String| {
+            let (sender, receiver) = oneshot::channel();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(10));
+                sender.send(value).unwrap();
+            });
+            async move { receiver.await.map_err(|e| e.to_string()) }
+        })
+        .unwrap();
+
+        assert_eq!(c.eval("double(21)"), Ok(JsValue::Int(42)));
+        assert_eq!(
+            c.eval("double(1) instanceof Promise"),
+            Ok(JsValue::Bool(true))
+        );
+        assert_eq!(
+            c.eval("Promise.all([later('a'), double(2), later('b')]).then(r => r.join())"),
+            Ok(JsValue::from("a,4,b"))
+        );
+        assert_eq!(
+            c.eval("fail('bad').catch(e => e instanceof TypeError && e.message)"),
+            Ok(JsValue::from("bad"))
+        );
+        assert_eq!(
+            c.eval("double().catch(e => e)"),
+            Ok(JsValue::from("Invalid argument count: Expected 1, got 0"))
+        );
+        assert_eq!(
+            c.eval("double('x').catch(e => 'rejected')"),
+            Ok(JsValue::from("rejected"))
+        );
+
+        // Futures are polled as pending jobs.
+        c.eval("var result; double(5).then(r => { result = r; }); 0")
+            .unwrap();
+        assert!(c.has_pending_jobs());
+        c.execute_pending_jobs(None).unwrap();
+        assert_eq!(c.eval("result"), Ok(JsValue::Int(10)));
+
+        // Polling a promise sleeps until the future is woken.
+        let value = futures::executor::block_on(c.eval_async("later('c')"));
+        assert_eq!(value, Ok(JsValue::from("c")));
+    }
+
+    #[test]
+    fn number_fidelity() {
+        let c = Context::new().unwrap();
+        c.eval("function id(x) { return x; }").unwrap();
+        c.eval("function str(x) { return Object.is(x, -0) ? '-0' : String(x); }")
+            .unwrap();
+
+        // Values are kept as is in both directions, including integral
+        // floats and non-finite numbers.
+        let values = vec![
+            JsValue::Int(1),
+            JsValue::Float(1.0),
+            JsValue::Float(-0.0),
+            JsValue::Float(f64::INFINITY),
+            JsValue::Float(f64::NEG_INFINITY),
+        ];
+        for value in values {
+            let result = c.call_function("id", vec![value.clone()]).unwrap();
+            assert_eq!(result, value);
+            assert_eq!(
+                result.as_f64().map(f64::is_sign_negative),
+                value.as_f64().map(f64::is_sign_negative)
+            );
+        }
+        assert!(c
+            .call_function("id", vec![JsValue::Float(f64::NAN)])
+            .unwrap()
+            .is_nan());
+        assert_eq!(
+            c.call_function("str", vec![JsValue::Float(-0.0)]),
+            Ok(JsValue::from("-0"))
+        );
+        assert_eq!(c.eval("Infinity"), Ok(JsValue::Float(f64::INFINITY)));
+        assert_eq!(c.eval("-1 / 0"), Ok(JsValue::Float(f64::NEG_INFINITY)));
+        assert!(c.eval("0 / 0").unwrap().is_nan());
+        assert_eq!(c.eval("2 ** 31"), Ok(JsValue::Float(2f64.powi(31))));
+
+        // Numbers are formatted like Javascript does.
+        for &float in &[f64::NAN, f64::INFINITY, -1e21, 1.5e-7, 0.1, 123.456, 2e20] {
+            assert_eq!(
+                c.call_function("str", vec![JsValue::Float(float)]),
+                Ok(JsValue::String(value::format_number(float)))
+            );
+        }
+    }
+
+    #[test]
+    fn undefined_and_null() {
+        let c = Context::new().unwrap();
+        c.eval("function kind(x) { return x === undefined ? 'undefined' : x === null ? 'null' : typeof x; }")
+            .unwrap();
+        assert_eq!(
+            c.call_function("kind", vec![JsValue::Undefined]),
+            Ok(JsValue::from("undefined"))
+        );
+        assert_eq!(
+            c.call_function("kind", vec![JsValue::Null]),
+            Ok(JsValue::from("null"))
+        );
+        assert_eq!(
+            c.eval("[null, undefined, , {}.x]"),
+            Ok(JsValue::Array(vec![
+                JsValue::Null,
+                JsValue::Undefined,
+                JsValue::Undefined,
+                JsValue::Undefined,
+            ]))
+        );
+        assert!(JsValue::Null.is_nullish());
+        assert!(JsValue::Undefined.is_nullish());
+        assert!(!JsValue::Bool(false).is_nullish());
+
+        // Callbacks see both as `None`, and return them as is.
+        c.add_callback("isNone", |value: Option<i32>| value.is_none())
+            .unwrap();
+        c.add_callback("same", |value: JsValue| value).unwrap();
+        c.add_callback("nothing", |_: Arguments| {}).unwrap();
+        assert_eq!(
+            c.eval("[isNone(), isNone(null), isNone(undefined), isNone(0)].join()"),
+            Ok(JsValue::from("true,true,true,false"))
+        );
+        assert_eq!(
+            c.eval("[kind(same(undefined)), kind(same(null)), kind(nothing())].join()"),
+            Ok(JsValue::from("undefined,null,undefined"))
+        );
+    }
+
+    #[test]
+    fn callback_optional_and_variadic_args() {
+        let c = Context::new().unwrap();
+        c.add_callback("greet", |name: String, greeting: Option<String>| {
+            format!("{}, {}!", greeting.as_deref().unwrap_or("Hello"), name)
+        })
+        .unwrap();
+        c.add_callback("join", |sep: String, rest: Arguments| {
+            rest.into_vec()
+                .into_iter()
+                .map(|v| v.into_string().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join(&sep)
+        })
+        .unwrap();
+        c.add_callback("kind", |value: JsValue| match value {
+            JsValue::Int(_) => "int",
+            JsValue::String(_) => "string",
+            _ => "other",
+        })
+        .unwrap();
+
+        assert_eq!(c.eval("greet('Bob')"), Ok(JsValue::from("Hello, Bob!")));
+        assert_eq!(
+            c.eval("greet('Bob', undefined)"),
+            Ok(JsValue::from("Hello, Bob!"))
+        );
+        assert_eq!(c.eval("greet('Bob', 'Hi')"), Ok(JsValue::from("Hi, Bob!")));
+        assert_eq!(c.eval("greet.length"), Ok(JsValue::Int(1)));
+        assert_eq!(
+            c.eval("greet()"),
+            Err(ExecutionError::Exception(
+                "Invalid argument count: Expected 1 to 2, got 0".into()
+            ))
+        );
+        assert_eq!(
+            c.eval("greet('a', 'b', 'c')"),
+            Err(ExecutionError::Exception(
+                "Invalid argument count: Expected 1 to 2, got 3".into()
+            ))
+        );
+
+        assert_eq!(c.eval("join('-')"), Ok(JsValue::from("")));
+        assert_eq!(
+            c.eval("join('-', 'a', 'b', 'c')"),
+            Ok(JsValue::from("a-b-c"))
+        );
+        assert_eq!(
+            c.eval("join()"),
+            Err(ExecutionError::Exception(
+                "Invalid argument count: Expected at least 1, got 0".into()
+            ))
+        );
+
+        assert_eq!(c.eval("kind(1)"), Ok(JsValue::from("int")));
+        assert_eq!(c.eval("kind('a')"), Ok(JsValue::from("string")));
+    }
+
+    #[test]
+    fn define_and_freeze() {
+        let c = Context::new().unwrap();
+        let global = c.global().unwrap();
+        c.eval(
+            r#"
+            function describe(o, name) {
+                const d = Object.getOwnPropertyDescriptor(o, name);
+                return [d.value, d.writable, d.enumerable, d.configurable].join();
+            }
+            "#,
+        )
+        .unwrap();
+
+        global
+            .define("a", 1, PropertyAttributes::default())
+            .unwrap();
+        global
+            .define(
+                "b",
+                2,
+                PropertyAttributes {
+                    enumerable: false,
+                    ..PropertyAttributes::default()
+                },
+            )
+            .unwrap();
+        global
+            .define("c", 3, PropertyAttributes::constant())
+            .unwrap();
+        assert_eq!(
+            c.eval("[describe(globalThis, 'a'), describe(globalThis, 'b'), describe(globalThis, 'c')].join(' ')"),
+            Ok(JsValue::from("1,true,true,true 2,true,false,true 3,false,true,false"))
+        );
+
+        // Configurable properties can be redefined, others not, even by
+        // scripts in strict mode.
+        global
+            .define("b", 4, PropertyAttributes::constant())
+            .unwrap();
+        assert_eq!(c.eval("b"), Ok(JsValue::Int(4)));
+        assert!(global
+            .define("c", 5, PropertyAttributes::default())
+            .is_err());
+        assert!(c.eval("'use strict'; c = 5").is_err());
+        assert_eq!(global.delete("c"), Ok(false));
+        assert_eq!(c.eval("c"), Ok(JsValue::Int(3)));
+
+        // Freezing keeps accessors, and includes symbol keys.
+        let object = c
+            .eval_owned("({ value: 1, get computed() { return this.value + 1; }, [Symbol.iterator]: null })")
+            .unwrap();
+        let object = JsObject::try_from(object).unwrap();
+        object.freeze().unwrap();
+        global.set("frozen", object.clone()).unwrap();
+        assert_eq!(
+            c.eval("Object.isFrozen(frozen) && frozen.computed"),
+            Ok(JsValue::Int(2))
+        );
+        assert!(c.eval("'use strict'; frozen.value = 2").is_err());
+        assert!(c.eval("'use strict'; frozen[Symbol.iterator] = 2").is_err());
+        assert!(object.set("added", 1).is_err());
+        assert!(object
+            .define("added", 1, PropertyAttributes::default())
+            .is_err());
+        assert_eq!(object.delete("value"), Ok(false));
+
+        // Traps of proxies are called, and their errors returned.
+        let proxy = c
+            .eval_owned("new Proxy({}, { preventExtensions() { throw new Error('no'); } })")
+            .unwrap();
+        match JsObject::try_from(proxy).unwrap().freeze() {
+            Err(ExecutionError::Exception(e)) => assert_eq!(e.message, "no"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn seal_and_integrity_checks() {
+        let c = Context::new().unwrap();
+        let object = |code| JsObject::try_from(c.eval_owned(code).unwrap()).unwrap();
+
+        let sealed = object("({ value: 1 })");
+        assert_eq!(sealed.is_sealed(), Ok(false));
+        sealed.seal().unwrap();
+        assert_eq!(sealed.is_sealed(), Ok(true));
+        assert_eq!(sealed.is_frozen(), Ok(false));
+        sealed.set("value", 2).unwrap();
+        assert_eq!(sealed.get("value"), Ok(JsValue::Int(2)));
+        assert_eq!(sealed.delete("value"), Ok(false));
+        assert!(sealed.set("added", 1).is_err());
+
+        let frozen = object("({ value: 1, get computed() { return 2; } })");
+        frozen.freeze().unwrap();
+        assert_eq!(frozen.is_frozen(), Ok(true));
+        assert_eq!(frozen.is_sealed(), Ok(true));
+
+        // The checks agree with the built-in functions.
+        c.eval("function check(o) { return [Object.isSealed(o), Object.isFrozen(o)].join(); }")
+            .unwrap();
+        let cases = [
+            "({})",
+            "Object.preventExtensions({})",
+            "Object.preventExtensions({ a: 1 })",
+            "Object.preventExtensions(Object.defineProperty({}, 'a', { value: 1, writable: true }))",
+            "Object.preventExtensions(Object.defineProperty({}, 'a', { get() {} }))",
+            "Object.preventExtensions(Object.defineProperty({}, Symbol(), { value: 1, writable: true }))",
+            "Object.seal([1])",
+            "Object.freeze([1])",
+        ];
+        for code in &cases {
+            let value = object(code);
+            let expected = c.call_function("check", vec![JsValue::from(value.clone())]);
+            let actual = format!(
+                "{},{}",
+                value.is_sealed().unwrap(),
+                value.is_frozen().unwrap()
+            );
+            assert_eq!(expected, Ok(JsValue::String(actual)), "{}", code);
+        }
+    }
+
+    #[test]
+    fn types_and_prototypes() {
+        let c = Context::new().unwrap();
+        c.eval(
+            "class Custom extends Error {}; class Plain {}; function type(x) { return typeof x; }",
+        )
+        .unwrap();
+
+        let cases = [
+            ("undefined", JsType::Undefined),
+            ("null", JsType::Null),
+            ("true", JsType::Boolean),
+            ("1.5", JsType::Number),
+            ("'a'", JsType::String),
+            ("Symbol()", JsType::Symbol),
+            ("Math.max", JsType::Function),
+            ("Plain", JsType::Function),
+            ("[1]", JsType::Array),
+            ("new Custom()", JsType::Error),
+            ("new Date(0)", JsType::Date),
+            ("/a/g", JsType::RegExp),
+            ("new Map()", JsType::Map),
+            ("new Set()", JsType::Set),
+            ("new ArrayBuffer(1)", JsType::ArrayBuffer),
+            ("new Float64Array(1)", JsType::TypedArray),
+            ("new Plain()", JsType::Object),
+            ("Object.create(null)", JsType::Object),
+        ];
+        for &(code, expected) in &cases {
+            let value = c.eval_owned(code).unwrap();
+            assert_eq!(value.type_of(), expected, "{}", code);
+            assert_eq!(JsValue::Ref(value.clone()).type_of(), expected, "{}", code);
+            assert_eq!(
+                c.call_function("type", vec![value]),
+                Ok(JsValue::from(expected.type_name())),
+                "{}",
+                code
+            );
+        }
+        #[cfg(feature = "bignum")]
+        assert_eq!(c.eval_owned("1n").unwrap().type_of(), JsType::BigInt);
+
+        // Promises are resolved by eval, unless they are nested.
+        assert_eq!(
+            c.eval("[Promise.resolve()]").unwrap().type_of(),
+            JsType::Array
+        );
+        match c.eval("[Promise.resolve()]") {
+            Ok(JsValue::Array(values)) => assert_eq!(values[0].type_of(), JsType::Promise),
+            other => panic!("unexpected value: {:?}", other),
+        }
+        assert_eq!(c.eval("new Date(0)").unwrap().type_of(), JsType::Date);
+        assert_eq!(c.eval("({ a: 1 })").unwrap().type_of(), JsType::Object);
+        assert_eq!(JsValue::Bytes(vec![1]).type_of(), JsType::TypedArray);
+        assert!(JsType::Function.is_object());
+        assert!(!JsType::Null.is_object());
+
+        // Prototypes and instances of classes.
+        let error = c.eval_owned("new Custom()").unwrap();
+        let custom = match c.eval("Custom").unwrap() {
+            JsValue::Function(f) => f,
+            other => panic!("unexpected value: {:?}", other),
+        };
+        let plain = match c.eval("Plain").unwrap() {
+            JsValue::Function(f) => f,
+            other => panic!("unexpected value: {:?}", other),
+        };
+        assert_eq!(error.instance_of(&custom), Ok(true));
+        assert_eq!(error.instance_of(&plain), Ok(false));
+        assert_eq!(c.eval_owned("1").unwrap().instance_of(&plain), Ok(false));
+
+        let error = JsObject::try_from(error).unwrap();
+        let prototype = error.prototype().unwrap().unwrap();
+        assert_eq!(
+            Some(prototype.clone()),
+            JsObject::try_from(c.eval_owned("Custom.prototype").unwrap()).ok()
+        );
+        assert_eq!(
+            prototype.prototype().unwrap().unwrap().get("name"),
+            Ok(JsValue::from("Error"))
+        );
+        let proxy = c
+            .eval_owned("new Proxy({}, { getPrototypeOf() { throw new Error('no'); } })")
+            .unwrap();
+        assert!(JsObject::try_from(proxy).unwrap().prototype().is_err());
+    }
+
+    #[test]
+    fn json_parse_and_stringify() {
+        let c = Context::new().unwrap();
+
+        let value = c
+            .json_parse(r#"{"s": "a\u0000b", "b": [true, false], "n": null, "f": 0.5}"#)
+            .unwrap();
+        let expected = JsValue::Object(
+            vec![
+                ("s".to_string(), JsValue::from("a\0b")),
+                (
+                    "b".to_string(),
+                    JsValue::Array(vec![JsValue::Bool(true), JsValue::Bool(false)]),
+                ),
+                ("n".to_string(), JsValue::Null),
+                ("f".to_string(), JsValue::Float(0.5)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(value, expected);
+        assert_eq!(
+            c.json_parse(&c.json_stringify(&expected).unwrap()),
+            Ok(expected)
+        );
+
+        // Large payloads.
+        let json = format!("[{}]", vec!["\"item\""; 10000].join(","));
+        match c.json_parse(&json) {
+            Ok(JsValue::Array(items)) => assert_eq!(items.len(), 10000),
+            other => panic!("unexpected value: {:?}", other),
+        }
+
+        // Invalid JSON.
+        for json in &["", "{a: 1}", "[1,]", "'a'", "undefined"] {
+            match c.json_parse(json) {
+                Err(ExecutionError::Exception(e)) => assert_eq!(e.name, "SyntaxError", "{}", json),
+                other => panic!("unexpected result for {}: {:?}", json, other),
+            }
+        }
+        assert!(matches!(
+            c.json_parse("\"a\0\""),
+            Err(ExecutionError::Conversion(_))
+        ));
+
+        // Values are serialized like with JSON.stringify.
+        let stringify = |code: &str| c.json_stringify(&c.eval(code).unwrap());
+        assert_eq!(stringify("'a\"'"), Ok(r#""a\"""#.to_string()));
+        assert_eq!(
+            stringify("[NaN, undefined, () => 1]"),
+            Ok("[null,null,null]".into())
+        );
+        assert_eq!(
+            stringify("({ a: undefined, b: new Date(0) })"),
+            Ok(r#"{"b":"1970-01-01T00:00:00.000Z"}"#.into())
+        );
+        assert_eq!(
+            c.json_stringify(&JsValue::Ref(
+                c.eval_owned("({ toJSON() { return 'custom'; } })").unwrap()
+            )),
+            Ok(r#""custom""#.into())
+        );
+        assert!(matches!(
+            c.json_stringify(&JsValue::Undefined),
+            Err(ExecutionError::Conversion(_))
+        ));
+        match c.json_stringify(&JsValue::Ref(
+            c.eval_owned("let cyclic = {}; cyclic.self = cyclic; cyclic")
+                .unwrap(),
+        )) {
+            Err(ExecutionError::Exception(e)) => assert_eq!(e.name, "TypeError"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn global_object() {
+        let c = Context::new().unwrap();
+        let global = c.global().unwrap();
+
+        global.set("value", vec![1, 2]).unwrap();
+        assert_eq!(c.eval("value.length"), Ok(JsValue::Int(2)));
+        assert_eq!(
+            global.get("value"),
+            Ok(JsValue::Array(vec![JsValue::Int(1), JsValue::Int(2)]))
+        );
+        assert_eq!(global.get("missing"), Ok(JsValue::Undefined));
+
+        assert!(global.has("value").unwrap());
+        assert!(global.has("toString").unwrap());
+        assert!(!global.has("missing").unwrap());
+
+        let keys = global.keys().unwrap();
+        assert!(keys.contains(&"value".to_string()));
+        assert!(!keys.contains(&"Object".to_string()));
+
+        assert!(global.delete("value").unwrap());
+        assert!(!global.has("value").unwrap());
+        assert!(global.delete("missing").unwrap());
+
+        c.eval(
+            r#"
+            Object.defineProperty(globalThis, "throwing", {
+                get() { throw new Error("get") },
+                set() { throw new Error("set") },
+            });
+            "#,
+        )
+        .unwrap();
+        assert!(global.get("throwing").is_err());
+        assert!(global.set("throwing", 1).is_err());
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
+
+        // The handle can be passed back to Javascript.
+        c.eval("function isGlobal(o) { return o === globalThis; }")
+            .unwrap();
+        assert_eq!(
+            c.call_function("isGlobal", vec![global]),
+            Ok(JsValue::Bool(true))
+        );
+    }
+
+    #[test]
+    fn object_handle() {
+        use std::convert::TryFrom;
+
+        let c = Context::new().unwrap();
+        c.eval(
+            r#"
+            class Point { constructor(x) { this.x = x; } get double() { return this.x * 2; } }
+            globalThis.Point = Point;
+            var point = new Point(2);
+            Object.defineProperty(point, "hidden", { value: 1, enumerable: false });
+            "#,
+        )
+        .unwrap();
+        let point = JsObject::try_from(c.eval_owned("point").unwrap()).unwrap();
+
+        assert_eq!(point.get("double"), Ok(JsValue::Int(4)));
+        assert_eq!(point.keys(), Ok(vec!["x".to_string()]));
+        assert_eq!(
+            point.own_keys(),
+            Ok(vec!["x".to_string(), "hidden".to_string()])
+        );
+
+        point.set("x", 5).unwrap();
+        assert_eq!(c.eval("point.double"), Ok(JsValue::Int(10)));
+        let map = point.to_map().unwrap();
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get("x"), Some(&JsValue::Int(5)));
+
+        let global = c.global().unwrap();
+        let constructor = |name| match global.get(name) {
+            Ok(JsValue::Function(f)) => f,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(point.instance_of(&constructor("Point")), Ok(true));
+        assert_eq!(point.instance_of(&constructor("Object")), Ok(true));
+        assert_eq!(point.instance_of(&constructor("Array")), Ok(false));
+
+        assert_eq!(
+            JsObject::try_from(c.eval_owned("1").unwrap()),
+            Err(ValueError::UnexpectedType)
+        );
+    }
+
+    #[test]
+    fn array_handle() {
+        use std::convert::TryFrom;
+
+        let c = Context::new().unwrap();
+        let array =
+            JsArray::try_from(c.eval_owned("var array = [1, 'a', [2]]; array").unwrap()).unwrap();
+
+        assert_eq!(array.len(), Ok(3));
+        assert_eq!(array.is_empty(), Ok(false));
+        assert_eq!(array.get(1), Ok(JsValue::from("a")));
+        assert_eq!(array.get(2), Ok(JsValue::Array(vec![JsValue::Int(2)])));
+        assert_eq!(array.get(3), Ok(JsValue::Undefined));
+
+        array.set(0, 10).unwrap();
+        array.set(4, true).unwrap();
+        assert_eq!(c.eval("array[0] + array.length"), Ok(JsValue::Int(15)));
+
+        let iter = array.iter().unwrap();
+        assert_eq!(iter.len(), 5);
+        let values = iter.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(values[0], JsValue::Int(10));
+        assert_eq!(values[3], JsValue::Undefined);
+        assert_eq!(values[4], JsValue::Bool(true));
+
+        c.eval("function first(a) { return a[0]; }").unwrap();
+        assert_eq!(c.call_function("first", vec![array]), Ok(JsValue::Int(10)));
+
+        assert_eq!(
+            JsArray::try_from(c.eval_owned("({ length: 1 })").unwrap()),
+            Err(ValueError::UnexpectedType)
+        );
+        assert_eq!(
+            JsArray::try_from(c.eval_owned("'abc'").unwrap()),
+            Err(ValueError::UnexpectedType)
+        );
+    }
+
+    #[test]
+    fn js_iterator() {
+        let c = Context::new().unwrap();
+
+        let values = |code: &str| {
+            c.eval_owned(code)
+                .unwrap()
+                .iter()
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+        };
+        assert_eq!(
+            values("[1, 'a']"),
+            Ok(vec![JsValue::Int(1), JsValue::from("a")])
+        );
+        assert_eq!(
+            values("'ab'"),
+            Ok(vec![JsValue::from("a"), JsValue::from("b")])
+        );
+        assert_eq!(
+            values("new Map([['k', true]])"),
+            Ok(vec![JsValue::Array(vec![
+                JsValue::from("k"),
+                JsValue::Bool(true)
+            ])])
+        );
+        assert!(c.eval_owned("({})").unwrap().iter().is_err());
+
+        // Generators run lazily, and are closed when the iterator is dropped.
+        c.eval(
+            r#"
+            var produced = 0;
+            var closed = false;
+            function* naturals() {
+                try {
+                    for (let i = 0; ; i++) {
+                        produced++;
+                        yield i;
+                    }
+                } finally {
+                    closed = true;
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let mut naturals = c.eval_owned("naturals()").unwrap().iter().unwrap();
+        assert_eq!(naturals.nth(2), Some(Ok(JsValue::Int(2))));
+        assert_eq!(c.eval("[produced, closed].join()"), Ok("3,false".into()));
+        drop(naturals);
+        assert_eq!(c.eval("closed"), Ok(JsValue::Bool(true)));
+
+        // Exceptions end the iteration.
+        let mut failing = c
+            .eval_owned("(function* () { yield 1; throw new Error('failed'); })()")
+            .unwrap()
+            .iter()
+            .unwrap();
+        assert_eq!(failing.next(), Some(Ok(JsValue::Int(1))));
+        assert_eq!(
+            failing.next(),
+            Some(Err(ExecutionError::Exception("Error: failed".into())))
+        );
+        assert_eq!(failing.next(), None);
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn js_stream() {
+        use futures::{executor::block_on, StreamExt};
+
+        let c = Context::new().unwrap();
+        let values = |code: &str| {
+            let stream = c.eval_owned(code).unwrap().stream().unwrap();
+            block_on(stream.collect::<Vec<_>>())
+        };
+        assert_eq!(
+            values("(async function* () { yield 1; yield await Promise.resolve(2); })()"),
+            vec![Ok(JsValue::Int(1)), Ok(JsValue::Int(2))]
+        );
+        // Values of sync iterables are awaited.
+        assert_eq!(
+            values("[Promise.resolve('a'), 'b']"),
+            vec![Ok(JsValue::from("a")), Ok(JsValue::from("b"))]
+        );
+        assert_eq!(
+            values("(async function* () { yield 1; throw new Error('failed'); })()"),
+            vec![
+                Ok(JsValue::Int(1)),
+                Err(ExecutionError::Exception("Error: failed".into()))
+            ]
+        );
+        assert!(c.eval_owned("({})").unwrap().stream().is_err());
+
+        // The generator is closed when the stream is dropped.
+        let mut stream = c
+            .eval_owned(
+                r#"
+                var closed = false;
+                (async function* () {
+                    try {
+                        for (let i = 0; ; i++) yield i;
+                    } finally {
+                        closed = true;
+                    }
+                })()
+                "#,
+            )
+            .unwrap()
+            .stream()
+            .unwrap();
+        assert_eq!(block_on(stream.next()), Some(Ok(JsValue::Int(0))));
+        drop(stream);
+        c.execute_pending_jobs(None).unwrap();
+        assert_eq!(c.eval("closed"), Ok(JsValue::Bool(true)));
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn create_async_iterator() {
+        use futures::{channel::mpsc, executor::block_on, stream, StreamExt};
+
+        let c = Context::new().unwrap();
+        c.eval(
+            r#"
+            async function collect(rows) {
+                const result = [];
+                for await (const row of rows) {
+                    if (row === 'stop') break;
+                    result.push(row);
+                }
+                return result;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // The event loop sleeps until the stream is woken by another thread.
+        let (sender, receiver) = mpsc::unbounded();
+        let producer = std::thread::spawn(move || {
+            for i in 0..3 {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                sender.unbounded_send(i).unwrap();
+            }
+        });
+        let rows = c.create_async_iterator(receiver).unwrap();
+        assert_eq!(
+            c.call_function("collect", vec![JsValue::Ref(rows)]),
+            Ok(JsValue::Array(vec![
+                JsValue::Int(0),
+                JsValue::Int(1),
+                JsValue::Int(2)
+            ]))
+        );
+        producer.join().unwrap();
+
+        // Breaking out of the loop drops the stream.
+        let rows = c
+            .create_async_iterator(stream::iter(vec!["a", "stop"]).chain(stream::pending()))
+            .unwrap();
+        assert_eq!(
+            c.call_function("collect", vec![JsValue::Ref(rows)]),
+            Ok(JsValue::Array(vec![JsValue::from("a")]))
+        );
+        assert!(!c.has_pending_jobs());
+
+        // A Rust stream transformed by an async generator, consumed as a
+        // Rust stream again.
+        c.eval("async function* double(rows) { for await (const row of rows) yield row * 2; }")
+            .unwrap();
+        let rows = c.create_async_iterator(stream::iter(1..=3)).unwrap();
+        let doubled = c
+            .call_function_owned("double", vec![JsValue::Ref(rows)])
+            .unwrap()
+            .stream()
+            .unwrap();
+        assert_eq!(
+            block_on(doubled.collect::<Vec<_>>()),
+            vec![
+                Ok(JsValue::Int(2)),
+                Ok(JsValue::Int(4)),
+                Ok(JsValue::Int(6))
+            ]
+        );
+    }
+
+    #[test]
+    fn map_and_set() {
+        use std::{
+            collections::{HashMap, HashSet},
+            convert::TryFrom,
+        };
+
+        let c = Context::new().unwrap();
+
+        let map = c.eval("new Map([[1, 'a'], ['b', [2]]])").unwrap();
+        assert_eq!(
+            map,
+            JsValue::Map(vec![
+                (JsValue::Int(1), JsValue::from("a")),
+                (JsValue::from("b"), JsValue::Array(vec![JsValue::Int(2)])),
+            ])
+        );
+        let set = c.eval("new Set([3, 1, 3, 2])").unwrap();
+        assert_eq!(
+            set,
+            JsValue::Set(vec![JsValue::Int(3), JsValue::Int(1), JsValue::Int(2)])
+        );
+        assert_eq!(c.eval("new Map()"), Ok(JsValue::Map(vec![])));
+
+        c.eval("function describe(v) { return v.constructor.name + v.size; }")
+            .unwrap();
+        assert_eq!(
+            c.call_function("describe", vec![map]),
+            Ok(JsValue::from("Map2"))
+        );
+        assert_eq!(
+            c.call_function("describe", vec![set]),
+            Ok(JsValue::from("Set3"))
+        );
+
+        let map: HashMap<i32, String> = c.eval_as("new Map([[1, 'a'], [2, 'b']])").unwrap();
+        assert_eq!(map.get(&2).map(String::as_str), Some("b"));
+        let object: HashMap<String, i32> = c.eval_as("({ a: 1 })").unwrap();
+        assert_eq!(object.get("a"), Some(&1));
+        assert!(HashMap::<i32, String>::try_from(c.eval("new Map([['a', 1]])").unwrap()).is_err());
+
+        let set: HashSet<String> = c.eval_as("new Set(['a', 'b', 'a'])").unwrap();
+        assert_eq!(set.len(), 2);
+        let set: HashSet<i32> = vec![1, 2].into_iter().collect();
+        assert_eq!(
+            c.call_function("describe", vec![JsValue::from(set)]),
+            Ok(JsValue::from("Set2"))
+        );
+    }
+
+    #[test]
+    fn symbols() {
+        use std::convert::TryFrom;
+
+        let c = Context::new().unwrap();
+
+        let symbol: JsSymbol = c.eval_as("Symbol('a')").unwrap();
+        assert_eq!(symbol.description(), Ok(Some("a".to_string())));
+        assert_eq!(format!("{:?}", symbol), "Symbol(a)");
+        let empty = c.new_symbol(None).unwrap();
+        assert_eq!(empty.description(), Ok(None));
+        assert_ne!(symbol, c.new_symbol(Some("a")).unwrap());
+        assert_eq!(symbol, symbol.clone());
+        assert_eq!(c.symbol_for("key").unwrap(), c.symbol_for("key").unwrap());
+
+        let iterator = c.well_known_symbol(WellKnownSymbol::Iterator).unwrap();
+        assert_eq!(iterator, c.eval_as("Symbol.iterator").unwrap());
+        c.eval("function isIterator(s) { return s === Symbol.iterator; }")
+            .unwrap();
+        assert_eq!(
+            c.call_function("isIterator", vec![iterator.clone()]),
+            Ok(JsValue::Bool(true))
+        );
+        assert_eq!(
+            c.call_function("isIterator", vec![symbol.clone()]),
+            Ok(JsValue::Bool(false))
+        );
+
+        let object = JsObject::try_from(c.eval_owned("({})").unwrap()).unwrap();
+        assert_eq!(object.get_symbol(&iterator), Ok(JsValue::Undefined));
+        object.set_symbol(&symbol, 1).unwrap();
+        assert_eq!(object.get_symbol(&symbol), Ok(JsValue::Int(1)));
+        assert_eq!(object.keys().unwrap(), Vec::<String>::new());
+
+        let values = c.eval("(function* () { yield 'x'; yield 'y'; })").unwrap();
+        object.set_symbol(&iterator, values).unwrap();
+        c.global().unwrap().set("iterable", object).unwrap();
+        assert_eq!(c.eval("[...iterable].join()"), Ok(JsValue::from("x,y")));
+    }
+
+    #[test]
+    fn array_buffer() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        struct Frame {
+            data: Box<[u8]>,
+            drops: Arc<AtomicUsize>,
+        }
+
+        impl AsMut<[u8]> for Frame {
+            fn as_mut(&mut self) -> &mut [u8] {
+                &mut self.data
+            }
+        }
+
+        impl Drop for Frame {
+            fn drop(&mut self) {
+                self.drops.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let c = Context::new().unwrap();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let frame = Frame {
+            data: vec![1, 2, 3].into_boxed_slice(),
+            drops: drops.clone(),
+        };
+        let buffer = c.new_array_buffer(frame).unwrap();
+
+        c.eval("function fill(b) { const a = new Uint8Array(b); a[0] = 10; return b; }")
+            .unwrap();
+        assert_eq!(
+            c.call_function("fill", vec![JsValue::Ref(buffer.clone())]),
+            Ok(JsValue::Bytes(vec![10, 2, 3]))
+        );
+        assert_eq!(buffer.to_value(), Ok(JsValue::Bytes(vec![10, 2, 3])));
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(buffer);
+        c.run_gc();
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        let empty = c.new_array_buffer(Vec::new()).unwrap();
+        assert_eq!(empty.to_value(), Ok(JsValue::Bytes(vec![])));
+    }
+
+    #[cfg(feature = "atomics")]
+    #[test]
+    fn shared_array_buffer() {
+        use std::convert::TryFrom;
+
+        let buffer = SharedBuffer::new(4);
+        let worker = std::thread::spawn({
+            let buffer = buffer.clone();
+            move || {
+                let c = Context::new().unwrap();
+                c.eval("function wait(b) { return Atomics.wait(new Int32Array(b), 0, 0, 5000); }")
+                    .unwrap();
+                let value = c.new_shared_array_buffer(&buffer).unwrap();
+                c.call_function("wait", vec![JsValue::Ref(value)])
+                    .map(|result| result.into_string())
+                    .ok()
+                    .flatten()
+            }
+        });
+
+        let c = Context::new().unwrap();
+        let value = c.new_shared_array_buffer(&buffer).unwrap();
+        c.eval("function notify(b) { return Atomics.notify(new Int32Array(b), 0); }")
+            .unwrap();
+        // Notify until the worker was waiting and has been woken up.
+        while c.call_function("notify", vec![JsValue::Ref(value.clone())]) != Ok(JsValue::Int(1)) {
+            std::thread::yield_now();
+        }
+        assert_eq!(worker.join().unwrap(), Some("ok".to_string()));
+
+        c.eval("function store(b) { Atomics.store(new Uint8Array(b), 3, 7); }")
+            .unwrap();
+        c.call_function("store", vec![JsValue::Ref(value)]).unwrap();
+        assert_eq!(buffer.to_vec(), vec![0, 0, 0, 7]);
+
+        let created = c.eval_owned("new SharedArrayBuffer(3)").unwrap();
+        let created = SharedBuffer::try_from(created).unwrap();
+        assert_eq!(created.to_vec(), vec![0, 0, 0]);
+        assert_ne!(created, buffer);
+        assert!(SharedBuffer::try_from(c.eval_owned("new ArrayBuffer(3)").unwrap()).is_err());
+    }
+
+    #[cfg(feature = "bignum")]
+    #[test]
+    fn transfer() {
+        let source = Context::new().unwrap();
+        let target = Runtime::new().unwrap().new_context().unwrap();
+
+        let value = source
+            .eval_owned(
+                "const shared = { n: 1n << 70n }; \
+                 ({ list: [shared, shared], floats: new Float64Array([0.5]), s: new String('x') })",
+            )
+            .unwrap();
+        let copy = source.transfer(&value, &target).unwrap();
+        target
+            .eval(
+                "function check(o) { return o.list[0] === o.list[1] && o.list[0].n === 1n << 70n \
+                 && o.floats[0] === 0.5 && o.s instanceof String; }",
+            )
+            .unwrap();
+        assert_eq!(
+            target.call_function("check", vec![copy.clone()]),
+            Ok(JsValue::Bool(true))
+        );
+
+        // The copy is independent of the original.
+        source.eval("shared.n = 0").unwrap();
+        assert_eq!(
+            target.call_function("check", vec![copy]),
+            Ok(JsValue::Bool(true))
+        );
+
+        let function = source.eval_owned("() => 1").unwrap();
+        assert!(source.transfer(&function, &target).is_err());
+        let map = source.eval_owned("new Map([[1, 2]])").unwrap();
+        #[cfg(not(feature = "ng"))]
+        assert!(source.transfer(&map, &target).is_err());
+        #[cfg(feature = "ng")]
+        {
+            let copy = source.transfer(&map, &target).unwrap();
+            target.eval("function get(m) { return m.get(1); }").unwrap();
+            assert_eq!(target.call_function("get", vec![copy]), Ok(JsValue::Int(2)));
+        }
+        assert!(target.transfer(&value, &source).is_err());
+    }
+
+    #[test]
+    fn timers() {
+        use std::time::{Duration, SystemTime};
+
+        let c = Context::builder().timers(true).build().unwrap();
+        c.eval(
+            r#"
+            var log = [];
+            setTimeout(() => log.push('b'), 20);
+            setTimeout((x) => log.push(x), 10, 'a');
+            const cleared = setTimeout(() => log.push('never'), 10);
+            clearTimeout(cleared);
+            const interval = setInterval(() => {
+                log.push('i');
+                Promise.resolve().then(() => log.push('job'));
+            }, 30);
+        "#,
+        )
+        .unwrap();
+
+        let start = SystemTime::now();
+        assert_eq!(c.run_timers(start), Ok(c.next_timer()));
+        assert_eq!(c.eval("log.length"), Ok(JsValue::Int(0)));
+
+        let next = c.run_timers(start + Duration::from_millis(100)).unwrap();
+        assert_eq!(c.eval("log.join()"), Ok(JsValue::from("a,b,i,job")));
+        assert!(next.unwrap() > start + Duration::from_millis(100));
+
+        c.eval("clearInterval(interval)").unwrap();
+        assert_eq!(c.next_timer(), None);
+
+        // Awaiting a promise runs the timers it depends on.
+        let value =
+            c.eval("new Promise(resolve => setTimeout(() => setTimeout(resolve, 5, 42), 5))");
+        assert_eq!(value, Ok(JsValue::Int(42)));
+        // Futures are woken once the timer is due.
+        let value = futures::executor::block_on(
+            c.eval_async("new Promise(resolve => setTimeout(resolve, 20, 'later'))"),
+        );
+        assert_eq!(value, Ok(JsValue::from("later")));
+
+        c.eval("setTimeout(() => { throw new Error('fail'); }, 0)")
+            .unwrap();
+        assert!(matches!(
+            c.run_timers(SystemTime::now() + Duration::from_millis(1)),
+            Err(ExecutionError::Exception(_))
+        ));
+
+        let c = Context::builder()
+            .timers(true)
+            .execution_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        assert_eq!(
+            c.eval("new Promise(resolve => setTimeout(resolve, 60000))"),
+            Err(ExecutionError::TimedOut)
+        );
+
+        // Timers are not installed by default.
+        let c = Context::new().unwrap();
+        assert_eq!(c.eval("typeof setTimeout"), Ok(JsValue::from("undefined")));
+        assert_eq!(c.run_timers(SystemTime::now()), Ok(None));
+    }
+
+    #[test]
+    fn time_source() {
+        use crate::time::{ManualClock, TimeSource};
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let clock = ManualClock::new(start);
+        let c = Context::builder()
+            .time_source(clock.clone())
+            .timers(true)
+            .build()
+            .unwrap();
+
+        let value = c.eval(
+            r#"
+            class Later extends Date {}
+            [Date.now(), new Date().getTime(), new Date(5).getTime(), Date.UTC(1970, 0, 1, 0, 0, 1),
+             new Date() instanceof Date, new Later() instanceof Date, new Later().getTime(),
+             Date() === new Date(Date.now()).toString(), new Date().constructor === Date]
+        "#,
+        );
+        assert_eq!(
+            value,
+            Ok(JsValue::Array(vec![
+                JsValue::Float(1e9),
+                JsValue::Float(1e9),
+                JsValue::Float(5.0),
+                JsValue::Float(1000.0),
+                JsValue::Bool(true),
+                JsValue::Bool(true),
+                JsValue::Float(1e9),
+                JsValue::Bool(true),
+                JsValue::Bool(true),
+            ]))
+        );
+        assert_eq!(c.eval("new Date()"), Ok(JsValue::Date(start)));
+
+        // Timers follow the clock of the context.
+        c.eval("var fired = []; setTimeout(() => fired.push(Date.now()), 1000)")
+            .unwrap();
+        assert_eq!(c.next_timer(), Some(start + Duration::from_secs(1)));
+        clock.advance(Duration::from_millis(999));
+        c.run_timers(clock.now()).unwrap();
+        assert_eq!(c.eval("fired.length"), Ok(JsValue::Int(0)));
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(c.run_timers(clock.now()), Ok(None));
+        assert_eq!(
+            c.eval("fired"),
+            Ok(JsValue::Array(vec![JsValue::Float(1e9 + 1000.0)]))
+        );
+
+        // Any closure can be used as a clock.
+        let c = Context::builder()
+            .time_source(move || start - Duration::from_secs(2_000_000))
+            .build()
+            .unwrap();
+        assert_eq!(c.eval("Date.now()"), Ok(JsValue::Float(-1e9)));
+
+        // A clock that only the host moves is not waited for.
+        let time = std::sync::Arc::new(std::sync::Mutex::new(start));
+        let c = Context::builder()
+            .time_source({
+                let time = time.clone();
+                move || *time.lock().unwrap()
+            })
+            .timers(true)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            c.eval("new Promise(resolve => setTimeout(resolve, 1000))"),
+            Err(ExecutionError::Internal(_))
+        ));
+        let mut future =
+            Box::pin(c.eval_async("new Promise(resolve => setTimeout(resolve, 1000, 'later'))"));
+        assert_eq!(futures::FutureExt::now_or_never(future.as_mut()), None);
+        *time.lock().unwrap() += Duration::from_secs(1);
+        c.run_timers(start + Duration::from_secs(1)).unwrap();
+        assert_eq!(
+            futures::executor::block_on(future),
+            Ok(JsValue::from("later"))
+        );
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn fetch() {
+        use crate::fetch::{Request, Response};
+        use std::sync::{Arc, Mutex};
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let c = Context::builder()
+            .http_handler({
+                let requests = requests.clone();
+                move |request: Request| {
+                    let url = request.url.clone();
+                    requests.lock().unwrap().push(request);
+                    match url.as_str() {
+                        "https://example.com/json" => Ok(Response::new(200, r#"{"a": [1, 2]}"#)
+                            .header("Content-Type", "application/json")
+                            .header("X-Many", "1")
+                            .header("x-many", "2")),
+                        "https://example.com/missing" => Ok(Response {
+                            status_text: "Not Found".into(),
+                            ..Response::new(404, "nope")
+                        }),
+                        _ => Err("offline"),
+                    }
+                }
+            })
+            .build()
+            .unwrap();
+
+        // quickjs-ng parses JSON numbers as floats.
+        let numbers = if cfg!(feature = "ng") {
+            vec![JsValue::Float(1.0), JsValue::Float(2.0)]
+        } else {
+            vec![JsValue::Int(1), JsValue::Int(2)]
+        };
+        let value = c.eval(
+            r#"
+            fetch('https://example.com/json').then(async r => [
+                r.ok, r.status, r.url, r.headers.get('content-type'), r.headers.get('x-many'),
+                (await r.json()).a, r.bodyUsed,
+                await r.text().catch(e => e instanceof TypeError),
+            ])
+        "#,
+        );
+        assert_eq!(
+            value,
+            Ok(JsValue::Array(vec![
+                JsValue::Bool(true),
+                JsValue::Int(200),
+                JsValue::from("https://example.com/json"),
+                JsValue::from("application/json"),
+                JsValue::from("1, 2"),
+                JsValue::Array(numbers),
+                JsValue::Bool(true),
+                JsValue::Bool(true),
+            ]))
+        );
+
+        let value = c.eval(
+            r#"
+            fetch({ url: 'https://example.com/missing' }, {
+                method: 'post',
+                headers: { 'X-Token': 'secret' },
+                body: 'hi €',
+            }).then(async r => [r.ok, r.status, r.statusText, await r.text()])
+        "#,
+        );
+        assert_eq!(
+            value,
+            Ok(JsValue::Array(vec![
+                JsValue::Bool(false),
+                JsValue::Int(404),
+                JsValue::from("Not Found"),
+                JsValue::from("nope"),
+            ]))
+        );
+        assert_eq!(
+            requests.lock().unwrap().last(),
+            Some(&Request {
+                method: "POST".into(),
+                url: "https://example.com/missing".into(),
+                headers: vec![
+                    ("content-type".into(), "text/plain;charset=UTF-8".into()),
+                    ("x-token".into(), "secret".into()),
+                ],
+                body: Some("hi €".as_bytes().to_vec()),
+            })
+        );
+
+        let value = c.eval(
+            "fetch('https://example.org', { body: new Uint8Array([1]), method: 'PUT' }).catch(e => [e instanceof TypeError, e.message])",
+        );
+        assert_eq!(
+            value,
+            Ok(JsValue::Array(vec![
+                JsValue::Bool(true),
+                JsValue::from("Failed to fetch https://example.org: offline"),
+            ]))
+        );
+        assert_eq!(requests.lock().unwrap().last().unwrap().body, Some(vec![1]));
+
+        let value =
+            c.eval("new Response('x', { status: 201 }).arrayBuffer().then(b => b.byteLength)");
+        assert_eq!(value, Ok(JsValue::Int(1)));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        // Without a handler, scripts have no network access.
+        let c = Context::new().unwrap();
+        assert_eq!(c.eval("typeof fetch"), Ok(JsValue::from("undefined")));
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn url() {
+        let c = Context::new().unwrap();
+
+        let value = c.eval(
+            r#"
+            const url = new URL('../b/c?x=1&y=a+b#top', new URL('https://user:pw@EXAMPLE.com:8080/a/'));
+            [url.href, url.origin, url.protocol, url.username, url.host, url.hostname, url.port,
+             url.pathname, url.search, url.hash, url.searchParams.get('y'), String(url), JSON.stringify({ url })]
+        "#,
+        );
+        assert_eq!(
+            value,
+            Ok(JsValue::Array(
+                vec![
+                    "https://user:pw@example.com:8080/b/c?x=1&y=a+b#top",
+                    "https://example.com:8080",
+                    "https:",
+                    "user",
+                    "example.com:8080",
+                    "example.com",
+                    "8080",
+                    "/b/c",
+                    "?x=1&y=a+b",
+                    "#top",
+                    "a b",
+                    "https://user:pw@example.com:8080/b/c?x=1&y=a+b#top",
+                    r#"{"url":"https://user:pw@example.com:8080/b/c?x=1&y=a+b#top"}"#,
+                ]
+                .into_iter()
+                .map(JsValue::from)
+                .collect()
+            ))
+        );
+
+        // Setters, and the live link between a url and its search params.
+        let value = c.eval(
+            r#"
+            const params = url.searchParams;
+            params.append('z', 'ü &');
+            params.set('x', 2);
+            params.delete('y');
+            url.port = 'invalid';
+            url.hash = '';
+            url.protocol = 'http';
+            url.pathname = '/d e';
+            [url.href, params === url.searchParams, url instanceof URL]
+        "#,
+        );
+        assert_eq!(
+            value,
+            Ok(JsValue::Array(vec![
+                JsValue::from("http://user:pw@example.com:8080/d%20e?x=2&z=%C3%BC+%26"),
+                JsValue::Bool(true),
+                JsValue::Bool(true),
+            ]))
+        );
+        assert_eq!(
+            c.eval("url.search = '?q=1'; [...params.keys()]"),
+            Ok(JsValue::Array(vec![JsValue::from("q")]))
+        );
+
+        let value = c.eval(
+            r#"
+            const p = new URLSearchParams({ b: '2', a: 1 });
+            p.append('a', 'x');
+            p.sort();
+            const copy = new URLSearchParams(p);
+            copy.delete('a', 'x');
+            const seen = [];
+            copy.forEach((value, name) => seen.push(`${name}=${value}`));
+            [p.toString(), p.getAll('a'), p.has('a', 'x'), p.get('missing'), p.size,
+             new URLSearchParams('?c=3&c=4').getAll('c'), new URLSearchParams([['d', 5]]).toString(), seen]
+        "#,
+        );
+        assert_eq!(
+            value,
+            Ok(JsValue::Array(vec![
+                JsValue::from("a=1&a=x&b=2"),
+                JsValue::Array(vec![JsValue::from("1"), JsValue::from("x")]),
+                JsValue::Bool(true),
+                JsValue::Null,
+                JsValue::Int(3),
+                JsValue::Array(vec![JsValue::from("3"), JsValue::from("4")]),
+                JsValue::from("d=5"),
+                JsValue::Array(vec![JsValue::from("a=1"), JsValue::from("b=2")]),
+            ]))
+        );
+
+        let value = c.eval(
+            r#"
+            const errors = [];
+            for (const f of [() => new URL('/relative'), () => { url.href = 'nope'; }, () => URL('x:')]) {
+                try { f(); } catch (e) { errors.push(e instanceof TypeError); }
+            }
+            errors
+        "#,
+        );
+        assert_eq!(value, Ok(JsValue::Array(vec![JsValue::Bool(true); 3])));
+    }
+
+    #[test]
+    fn global_property_with_accessors() {
+        use std::sync::{
+            atomic::{AtomicI32, Ordering},
+            Arc,
+        };
+
+        let c = Context::new().unwrap();
+        let value = Arc::new(AtomicI32::new(1));
+        let (get, set) = (value.clone(), value.clone());
+        c.set_global_property_with_accessors(
+            "hostValue",
+            move || get.load(Ordering::SeqCst),
+            move |v: JsValue| {
+                if let JsValue::Int(v) = v {
+                    set.store(v, Ordering::SeqCst);
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(c.eval("hostValue"), Ok(JsValue::Int(1)));
+        value.store(2, Ordering::SeqCst);
+        assert_eq!(c.eval("hostValue"), Ok(JsValue::Int(2)));
+        assert_eq!(c.eval("hostValue += 40; hostValue"), Ok(JsValue::Int(42)));
+        assert_eq!(value.load(Ordering::SeqCst), 42);
+    }
+
+    #[test]
+    fn register_class() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        struct Account {
+            balance: i32,
+            drops: Arc<AtomicUsize>,
+        }
+
+        impl Drop for Account {
+            fn drop(&mut self) {
+                self.drops.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        fn class() -> ClassBuilder<Account> {
+            ClassBuilder::new("Account")
+                .method(
+                    "deposit",
+                    |account: &mut Account, args: Arguments| match args.into_vec().first() {
+                        Some(JsValue::Int(amount)) => {
+                            account.balance += amount;
+                            Ok(account.balance)
+                        }
+                        _ => Err(JsError::type_error("amount must be an integer")),
+                    },
+                )
+                .getter("balance", |account: &Account| account.balance)
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let runtime = Runtime::new().unwrap();
+        let c = runtime.new_context().unwrap();
+
+        assert!(c
+            .create_instance(Account {
+                balance: 0,
+                drops: drops.clone()
+            })
+            .is_err());
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        c.register_class(class()).unwrap();
+        assert!(c.register_class(class()).is_err());
+
+        let account = c
+            .create_instance(Account {
+                balance: 10,
+                drops: drops.clone(),
+            })
+            .unwrap();
+        c.eval("function deposit(account, amount) { return account.deposit(amount); }")
+            .unwrap();
+        assert_eq!(
+            c.call_function("deposit", vec![JsValue::Ref(account.clone()), 5.into()]),
+            Ok(JsValue::Int(15))
+        );
+        assert_eq!(
+            c.eval(
+                r#"
+                var account = globalThis.account = Object.create(Account.prototype);
+                [
+                    typeof Account,
+                    Account.name,
+                    Object.keys(globalThis).includes("Account"),
+                ].join()
+                "#
+            ),
+            Ok(JsValue::from("function,Account,false"))
+        );
+        assert_eq!(
+            c.eval(
+                r#"
+                const errors = [];
+                for (const f of [
+                    () => new Account(),
+                    () => Account(),
+                    () => Account.prototype.deposit.call({}, 1),
+                    () => Object.getOwnPropertyDescriptor(Account.prototype, "balance").get.call(1),
+                ]) {
+                    try { f() } catch (e) { errors.push(e instanceof TypeError) }
+                }
+                errors.join()
+                "#
+            ),
+            Ok(JsValue::from("true,true,true,true"))
+        );
+        assert_eq!(
+            c.call_function("deposit", vec![JsValue::Ref(account.clone()), "x".into()]),
+            Err(ExecutionError::Exception(
+                JsValue::from("TypeError: amount must be an integer").into()
+            ))
+        );
+
+        // Instances are finalized when they are garbage collected.
+        drop(account);
+        c.run_gc();
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        // Classes can be registered in multiple contexts of a runtime.
+        let other = runtime.new_context().unwrap();
+        other
+            .register_class(class().constructor(move |_args| {
+                Ok(Account {
+                    balance: 1,
+                    drops: drops.clone(),
+                })
+            }))
+            .unwrap();
+        assert_eq!(
+            other.eval(
+                r#"
+                class Savings extends Account {}
+                const savings = new Savings();
+                savings.deposit(2);
+                [savings instanceof Savings, savings instanceof Account, savings.balance].join()
+                "#
+            ),
+            Ok(JsValue::from("true,true,3"))
+        );
+    }
+
+    #[test]
+    fn class_finalizer() {
+        use std::cell::RefCell;
+
+        struct Resource(&'static str);
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let class = || {
+            let log = log.clone();
+            ClassBuilder::new("Resource")
+                .constructor(|args: Arguments| match args.into_vec().first() {
+                    Some(JsValue::String(name)) if name == "script" => Ok(Resource("script")),
+                    _ => Err(JsError::type_error("unknown resource")),
+                })
+                .finalizer(move |resource: Resource| log.borrow_mut().push(resource.0))
+        };
+
+        // Finalizers run as jobs after the instance was collected.
+        let runtime = Runtime::new().unwrap();
+        let c = runtime.new_context().unwrap();
+        c.register_class(class()).unwrap();
+        drop(c.create_instance(Resource("handle")).unwrap());
+        c.eval("new Resource('script'); 0").unwrap();
+        assert!(log.borrow().is_empty());
+        assert_eq!(c.execute_pending_jobs(None), Ok(2));
+        assert_eq!(*log.borrow(), vec!["handle", "script"]);
+
+        // Instances outliving their context are finalized when they are
+        // collected after the context was dropped.
+        let kept = c.create_instance(Resource("kept")).unwrap();
+        c.global().unwrap().set("kept", kept).unwrap();
+        drop(c);
+        log.borrow_mut().push("dropped");
+        let other = runtime.new_context().unwrap();
+        other.run_gc();
+        other.execute_pending_jobs(None).unwrap();
+        assert_eq!(*log.borrow(), vec!["handle", "script", "dropped", "kept"]);
+
+        // Instances still alive are finalized when the runtime is dropped.
+        other.register_class(class()).unwrap();
+        other.eval("var last = new Resource('script');").unwrap();
+        drop(other);
+        assert_eq!(log.borrow().len(), 4);
+        drop(runtime);
+        assert_eq!(
+            *log.borrow(),
+            vec!["handle", "script", "dropped", "kept", "script"]
+        );
+    }
+
+    #[test]
+    fn dynamic_object() {
+        use std::cell::{Cell, RefCell};
+
+        struct Env {
+            vars: HashMap<String, String>,
+            lookups: Rc<Cell<usize>>,
+        }
+
+        impl DynamicObject for Env {
+            fn get(&self, name: &str) -> Option<JsValue> {
+                self.lookups.set(self.lookups.get() + 1);
+                self.vars.get(name).cloned().map(JsValue::String)
+            }
+
+            fn set(&mut self, name: &str, value: JsValue) -> Result<(), JsError> {
+                match value {
+                    JsValue::String(value) => {
+                        self.vars.insert(name.to_string(), value);
+                        Ok(())
+                    }
+                    _ => Err(JsError::type_error("value must be a string")),
+                }
+            }
+
+            fn keys(&self) -> Vec<String> {
+                let mut keys = self.vars.keys().cloned().collect::<Vec<_>>();
+                keys.sort();
+                keys
+            }
+
+            fn delete(&mut self, name: &str) -> Result<bool, JsError> {
+                self.vars.remove(name);
+                Ok(true)
+            }
+        }
+
+        struct ReadOnly(Rc<RefCell<bool>>);
+
+        impl DynamicObject for ReadOnly {
+            fn get(&self, name: &str) -> Option<JsValue> {
+                Some(JsValue::String(name.to_uppercase()))
+            }
+
+            fn keys(&self) -> Vec<String> {
+                Vec::new()
+            }
+        }
+
+        impl Drop for ReadOnly {
+            fn drop(&mut self) {
+                *self.0.borrow_mut() = true;
+            }
+        }
+
+        let c = Context::new().unwrap();
+        let lookups = Rc::new(Cell::new(0));
+        let env = Env {
+            vars: vec![("HOME".to_string(), "/root".to_string())]
+                .into_iter()
+                .collect(),
+            lookups: lookups.clone(),
+        };
+        let env = c.create_dynamic_object(env).unwrap();
+        c.global().unwrap().set("env", env).unwrap();
+        assert_eq!(lookups.get(), 0);
+
+        assert_eq!(
+            c.eval(
+                r#"
+                env.USER = 'quickjs';
+                [env.HOME, env.USER, env.MISSING === undefined, 'HOME' in env, 'MISSING' in env].join()
+                "#
+            ),
+            Ok(JsValue::from("/root,quickjs,true,true,false"))
+        );
+        assert!(lookups.get() > 0);
+        assert_eq!(
+            c.eval("JSON.stringify(env)"),
+            Ok(JsValue::from(r#"{"HOME":"/root","USER":"quickjs"}"#))
+        );
+        assert_eq!(
+            c.eval("delete env.HOME; Object.keys(env).join()"),
+            Ok(JsValue::from("USER"))
+        );
+        assert_eq!(
+            c.eval("env[Symbol.iterator] === undefined"),
+            Ok(JsValue::Bool(true))
+        );
+        assert_eq!(
+            c.eval("try { env.USER = 1; } catch (e) { e.message }"),
+            Ok(JsValue::from("value must be a string"))
+        );
+
+        // Objects are read-only by default, and dropped with the proxy.
+        let dropped = Rc::new(RefCell::new(false));
+        let upper = c.create_dynamic_object(ReadOnly(dropped.clone())).unwrap();
+        c.global().unwrap().set("upper", upper).unwrap();
+        assert_eq!(c.eval("upper.abc"), Ok(JsValue::from("ABC")));
+        assert_eq!(
+            c.eval("try { upper.abc = 'x'; } catch (e) { e instanceof TypeError }"),
+            Ok(JsValue::Bool(true))
+        );
+        c.eval("delete globalThis.upper").unwrap();
+        c.run_gc();
+        assert!(*dropped.borrow());
+
+        // Requires the `Proxy` built-in.
+        let c = Context::builder()
+            .intrinsics(IntrinsicSet {
+                proxy: false,
+                ..IntrinsicSet::default()
+            })
+            .build()
+            .unwrap();
+        assert!(c.create_dynamic_object(ReadOnly(dropped)).is_err());
+    }
+
+    #[test]
+    fn create_iterator() {
+        use std::cell::Cell;
+
+        struct Guard(Rc<Cell<bool>>);
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let c = Context::new().unwrap();
+        c.eval(
+            r#"
+            function collect(items) { return [...items].join(); }
+            function first(items, count) {
+                const result = [];
+                for (const item of items) {
+                    if (result.length === count) break;
+                    result.push(item);
+                }
+                return result.join();
+            }
+            "#,
+        )
+        .unwrap();
+
+        let items = c.create_iterator(vec!["a", "b", "c"]).unwrap();
+        assert_eq!(
+            c.call_function("collect", vec![JsValue::Ref(items.clone())]),
+            Ok(JsValue::from("a,b,c"))
+        );
+        // Iterators can only be consumed once.
+        assert_eq!(
+            c.call_function("collect", vec![JsValue::Ref(items)]),
+            Ok(JsValue::from(""))
+        );
+
+        // Items are produced lazily, and the iterator is dropped when the
+        // loop exits early.
+        let produced = Rc::new(Cell::new(0));
+        let dropped = Rc::new(Cell::new(false));
+        let guard = Guard(dropped.clone());
+        let counter = produced.clone();
+        let items = c
+            .create_iterator(std::iter::from_fn(move || {
+                let _ = &guard;
+                counter.set(counter.get() + 1);
+                Some(counter.get())
+            }))
+            .unwrap();
+        assert_eq!(
+            c.call_function("first", vec![JsValue::Ref(items.clone()), 3.into()]),
+            Ok(JsValue::from("1,2,3"))
+        );
+        assert_eq!(produced.get(), 4);
+        assert!(dropped.get());
+
+        c.global().unwrap().set("items", items).unwrap();
+        assert_eq!(
+            c.eval(
+                r#"
+                const iterator = Object.getPrototypeOf(Object.getPrototypeOf([][Symbol.iterator]()));
+                [
+                    Object.getPrototypeOf(items) === iterator,
+                    items[Symbol.iterator]() === items,
+                    JSON.stringify(items.next()),
+                ].join()
+                "#
+            ),
+            Ok(JsValue::from(r#"true,true,{"done":true}"#))
+        );
+    }
+
+    #[test]
+    fn add_callback_at() {
+        let c = Context::new().unwrap();
+        c.eval("var api = { version: 1 }; var scalar = 5;").unwrap();
+
+        c.add_callback_at("api.fs.read", |path: String| format!("read {}", path))
+            .unwrap();
+        c.add_callback_at("api.fs.exists", |_: String| true)
+            .unwrap();
+        c.add_callback_at("top", || 1).unwrap();
+
+        assert_eq!(
+            c.eval("api.fs.read('a.txt')"),
+            Ok(JsValue::from("read a.txt"))
+        );
+        assert_eq!(c.eval("api.fs.exists('a.txt')"), Ok(JsValue::Bool(true)));
+        assert_eq!(c.eval("api.version"), Ok(JsValue::Int(1)));
+        assert_eq!(c.eval("top()"), Ok(JsValue::Int(1)));
+        assert_eq!(c.eval("typeof read"), Ok(JsValue::from("undefined")));
+
+        assert!(c.add_callback_at("scalar.f", || 1).is_err());
+        assert!(c.add_callback_at("api..f", || 1).is_err());
+        assert!(c.add_callback_at("other.", || 1).is_err());
+        assert_eq!(c.eval("typeof other"), Ok(JsValue::from("undefined")));
+    }
+
+    #[test]
+    fn eval_module_imports() {
+        use module::ModuleSource;
+
+        let c = Context::new().unwrap();
+        c.set_module_loader(|name: &str| match name {
+            "lib/a.js" => Ok(ModuleSource::from(
+                "import { b } from './b.js'; export const a = b + 1;",
+            )),
+            "lib/b.js" => Ok(ModuleSource::from("export const b = 41;")),
+            "broken" => Ok(ModuleSource::from("export const = ;")),
+            _ => Err("not found"),
+        });
+
+        c.eval_module(
+            "import { a } from 'lib/a.js'; globalThis.result = a;",
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(c.eval("result").unwrap(), JsValue::Int(42));
+
+        let err = c
+            .eval_module("import { x } from 'missing';", "main.js")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ExecutionError::Exception(
+                "ReferenceError: could not load module 'missing': not found".into()
+            )
+        );
+
+        let err = c
+            .eval_module("import { x } from 'broken';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("SyntaxError"), "{}", err);
+    }
+
+    #[test]
+    fn eval_module_without_loader() {
+        let c = Context::new().unwrap();
+        let err = c
+            .eval_module("import { x } from 'missing';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("ReferenceError"), "{}", err);
+    }
+
+    #[test]
+    fn native_modules() {
+        use module::ModuleSource;
+
+        let c = Context::new().unwrap();
+        c.register_module("host:fs", |module| {
+            module
+                .export_function("readFile", |path: String| -> Result<String, String> {
+                    match path.as_str() {
+                        "a.txt" => Ok("a".into()),
+                        _ => Err(format!("{} not found", path)),
+                    }
+                })
+                .export("separator", "/")
+                .export("limits", vec![1, 2]);
+        })
+        .unwrap();
+        c.register_module("lib", |module| {
+            module.export("name", "native");
+        })
+        .unwrap();
+
+        // Native modules are available without a loader.
+        c.eval_module(
+            r#"
+            import { readFile, separator } from 'host:fs';
+            import * as fs from 'host:fs';
+            globalThis.result = [readFile('a.txt'), separator, fs.limits, fs.readFile === readFile];
+            try { readFile('b.txt'); } catch (e) { globalThis.error = e; }
+            "#,
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(
+            c.eval("result"),
+            Ok(JsValue::Array(vec![
+                JsValue::from("a"),
+                JsValue::from("/"),
+                JsValue::Array(vec![JsValue::Int(1), JsValue::Int(2)]),
+                JsValue::Bool(true),
+            ]))
+        );
+        assert_eq!(c.eval("error"), Ok(JsValue::from("b.txt not found")));
+        assert_eq!(
+            c.eval_module("import { x } from 'missing';", "main.js"),
+            Err(ExecutionError::Exception(
+                "ReferenceError: could not load module 'missing'".into()
+            ))
+        );
+        let err = c
+            .eval_module("import { missing } from 'host:fs';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("SyntaxError"), "{}", err);
+        assert!(c.register_module("lib", |_| {}).is_err());
+
+        // They take precedence over the loader.
+        c.set_module_loader(|name: &str| match name {
+            "lib" | "other" => Ok(ModuleSource::from("export const name = 'loaded';")),
+            _ => Err("not found"),
+        });
+        c.eval_module(
+            "import { name } from 'lib'; import { name as other } from 'other'; globalThis.names = [name, other];",
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(
+            c.eval("names"),
+            Ok(JsValue::Array(vec![
+                JsValue::from("native"),
+                JsValue::from("loaded")
+            ]))
+        );
+
+        // Modules are registered per context, and kept by reset.
+        let other = Context::builder().runtime(&c.runtime()).build().unwrap();
+        other
+            .eval_module(
+                "import { name } from 'lib'; globalThis.name = name;",
+                "main.js",
+            )
+            .unwrap();
+        assert_eq!(other.eval("name"), Ok(JsValue::from("loaded")));
+
+        let c = c.reset().unwrap();
+        c.eval_module(
+            "import { separator } from 'host:fs'; globalThis.separator = separator;",
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(c.eval("separator"), Ok(JsValue::from("/")));
+    }
+
+    #[test]
+    fn eval_module_async() {
+        use module::ModuleSource;
+
+        let c = Context::new().unwrap();
+        c.set_module_loader(|name: &str| match name {
+            "lib/a.js" => Ok(ModuleSource::from("export const a = 41;")),
+            _ => Err("not found"),
+        });
+
+        // Modules without top-level await are evaluated as usual.
+        block_on(c.eval_module_async("export const x = 1; globalThis.x = x;", "x.js")).unwrap();
+        assert_eq!(c.eval("x"), Ok(JsValue::Int(1)));
+
+        let code = r#"
+            const log = [`import ${"x"}`, 'import "y"', /import '[z]'/.source];
+            import { a } from 'lib/a.js';
+            const value = await Promise.resolve(a + 1);
+            for await (const item of [Promise.resolve('done')]) {
+                log.push(item);
+            }
+            globalThis.result = [value, ...log].join(',');
+        "#;
+        block_on(c.eval_module_async(code, "main.js")).unwrap();
+        assert_eq!(
+            c.eval("result"),
+            Ok(JsValue::from("42,import x,import \"y\",import '[z]',done"))
+        );
+
+        // Errors keep their line numbers.
+        let err =
+            block_on(c.eval_module_async("await null;\n\nthrow new Error('late');", "main.js"))
+                .unwrap_err();
+        match err {
+            ExecutionError::Exception(e) => {
+                assert_eq!(e.message, "late");
+                assert_eq!(e.stack.last().unwrap().line, Some(3));
+            }
+            other => panic!("expected exception, got {:?}", other),
+        }
+
+        let err = block_on(c.eval_module_async("await null; const = 1;", "main.js")).unwrap_err();
+        assert!(err.to_string().contains("SyntaxError"), "{}", err);
+    }
+
+    #[test]
+    fn dynamic_import() {
+        use module::ModuleSource;
+
+        let c = Context::new().unwrap();
+        c.set_module_loader(|name: &str| match name {
+            "lib/a.js" => Ok(ModuleSource::from(
+                "import { b } from './b.js'; export const a = b + 1; export default 'a';",
+            )),
+            "lib/b.js" => Ok(ModuleSource::from(
+                "globalThis.loads = (globalThis.loads || 0) + 1; export const b = 41;",
+            )),
+            _ => Err("not found"),
+        });
+        c.register_module("host:env", |module| {
+            module.export("mode", "test");
+        })
+        .unwrap();
+
+        // From global scope, relative to the file name of the script.
+        assert_eq!(
+            c.eval("import('lib/a.js').then(ns => [ns.a, ns.default])"),
+            Ok(JsValue::Array(vec![JsValue::Int(42), JsValue::from("a")]))
+        );
+        let options = EvalOptions {
+            filename: "lib/main.js".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            c.eval_with_options("import('./b.js').then(ns => ns.b)", options),
+            Ok(JsValue::Int(41))
+        );
+        assert_eq!(c.eval("loads"), Ok(JsValue::Int(1)));
+        assert_eq!(
+            c.eval("import('host:env').then(ns => ns.mode)"),
+            Ok(JsValue::from("test"))
+        );
+        assert_eq!(
+            c.eval("import('missing').catch(e => e.message)"),
+            Ok(JsValue::from("could not load module 'missing': not found"))
+        );
+
+        // From a module.
+        c.eval_module(
+            "import('./b.js').then(ns => { globalThis.fromModule = ns.b; });",
+            "lib/main.js",
+        )
+        .unwrap();
+        c.execute_pending_jobs(None).unwrap();
+        assert_eq!(c.eval("fromModule"), Ok(JsValue::Int(41)));
+
+        // From Rust.
+        let namespace = c.import_module("lib/a.js").unwrap();
+        let mut expected = HashMap::new();
+        expected.insert("a".to_string(), JsValue::Int(42));
+        expected.insert("default".to_string(), JsValue::from("a"));
+        assert_eq!(namespace, JsValue::Object(expected));
+        assert_eq!(c.eval("loads"), Ok(JsValue::Int(1)));
+        assert_eq!(
+            c.import_module("missing"),
+            Err(ExecutionError::Exception(
+                "ReferenceError: could not load module 'missing': not found".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn import_meta() {
+        use module::{ImportMeta, ModuleLoader, ModuleSource};
+
+        // Without a loader, the url is the module name.
+        let c = Context::new().unwrap();
+        c.eval_module(
+            "globalThis.meta = [import.meta.url, import.meta.resolve('../x.js')];",
+            "lib/a/main.js",
+        )
+        .unwrap();
+        assert_eq!(
+            c.eval("meta"),
+            Ok(JsValue::Array(vec![
+                JsValue::from("lib/a/main.js"),
+                JsValue::from("lib/x.js")
+            ]))
+        );
+        // QuickJS looks up `import.meta` by module name, so the failed
+        // module must not share the name of the module evaluated below.
+        let err = c
+            .eval_module("import.meta.resolve(1);", "bad.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("TypeError"), "{}", err);
+
+        struct Loader;
+
+        impl ModuleLoader for Loader {
+            fn load(&self, name: &str) -> Result<ModuleSource, String> {
+                match name {
+                    "lib/data.js" => Ok(ModuleSource::from(
+                        "export const meta = [import.meta.url, import.meta.root, import.meta.resolve('./config.json')];",
+                    )),
+                    _ => Err("not found".into()),
+                }
+            }
+
+            fn import_meta(&self, name: &str) -> ImportMeta {
+                ImportMeta::new(format!("file:///app/{}", name)).property("root", "/app")
+            }
+        }
+
+        c.set_module_loader(Loader);
+        c.eval_module(
+            "import { meta } from './lib/data.js'; globalThis.meta = [...meta, import.meta.url];",
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(
+            c.eval("meta"),
+            Ok(JsValue::Array(vec![
+                JsValue::from("file:///app/lib/data.js"),
+                JsValue::from("/app"),
+                JsValue::from("lib/config.json"),
+                JsValue::from("file:///app/main.js"),
+            ]))
+        );
+
+        // Closures use the default properties.
+        c.set_module_loader(|name: &str| match name {
+            "b.js" => Ok(ModuleSource::from("export const url = import.meta.url;")),
+            _ => Err("not found"),
+        });
+        c.eval_module(
+            "import { url } from 'b.js'; globalThis.url = url;",
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(c.eval("url"), Ok(JsValue::from("b.js")));
+    }
+
+    #[test]
+    fn data_modules() {
+        use module::ModuleSource;
 
-    //     c.set_global_property("testprop", true).unwrap();
-    //     assert_eq!(
-    //         c.global_property("testprop").unwrap(),
-    //         JsValue::Bool(true),
-    //     );
-    // }
+        let c = Context::new().unwrap();
+        c.set_module_loader(|name: &str| match name {
+            "config.json" => Ok(ModuleSource::Json(
+                r#"{"port": 8080, "hosts": ["a"]}"#.into(),
+            )),
+            "broken.json" => Ok(ModuleSource::Json("{".into())),
+            "data.bin" => Ok(ModuleSource::Bytes(vec![1, 2, 3])),
+            _ => Err("not found"),
+        });
+
+        c.eval_module(
+            r#"
+            import config from './config.json';
+            import data from 'data.bin';
+            globalThis.result = [config.port, config.hosts, data instanceof Uint8Array, data];
+            "#,
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(
+            c.eval("result"),
+            Ok(JsValue::Array(vec![
+                JsValue::Int(8080),
+                JsValue::Array(vec![JsValue::from("a")]),
+                JsValue::Bool(true),
+                JsValue::Bytes(vec![1, 2, 3]),
+            ]))
+        );
+
+        // Modules are only loaded once.
+        assert_eq!(
+            c.eval("import('config.json').then(ns => ns.default.port)"),
+            Ok(JsValue::Int(8080))
+        );
+
+        let err = c
+            .eval_module("import config from 'broken.json';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("SyntaxError"), "{}", err);
+        let err = c
+            .eval_module("import { port } from 'config.json';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("SyntaxError"), "{}", err);
+    }
+
+    #[test]
+    fn module_resolve() {
+        use module::{ModuleLoader, ModuleSource};
+
+        struct Loader;
+
+        impl ModuleLoader for Loader {
+            fn load(&self, name: &str) -> Result<ModuleSource, String> {
+                match name {
+                    "src/util.js" => Ok(ModuleSource::from(
+                        "export { base } from './base.js'; export const util = 'util';",
+                    )),
+                    "src/base.js" => Ok(ModuleSource::from("export const base = 'base';")),
+                    _ => Err("not found".into()),
+                }
+            }
+
+            fn resolve(&self, base: &str, specifier: &str) -> Result<String, String> {
+                match specifier.strip_prefix("@app/") {
+                    Some(name) => Ok(format!("src/{}.js", name)),
+                    None if specifier == "forbidden" => Err("not allowed".into()),
+                    None => Ok(module::resolve(base, specifier)),
+                }
+            }
+        }
+
+        let c = Context::new().unwrap();
+        c.set_module_loader(Loader);
+        c.eval_module(
+            "import { util, base } from '@app/util'; globalThis.result = [util, base, import.meta.resolve('@app/x')];",
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(
+            c.eval("result"),
+            Ok(JsValue::Array(vec![
+                JsValue::from("util"),
+                JsValue::from("base"),
+                JsValue::from("src/x.js"),
+            ]))
+        );
+        assert_eq!(
+            c.eval_module("import 'forbidden';", "main.js"),
+            Err(ExecutionError::Exception(
+                "ReferenceError: could not resolve module 'forbidden': not allowed".into()
+            ))
+        );
+        assert_eq!(
+            c.eval("import('forbidden').catch(e => e.message)"),
+            Ok(JsValue::from(
+                "could not resolve module 'forbidden': not allowed"
+            ))
+        );
+    }
+
+    #[cfg(feature = "fs-loader")]
+    #[test]
+    fn fs_module_loader() {
+        use module::FsModuleLoader;
+
+        let dir = std::env::temp_dir().join(format!("quick-js-fs-loader-{}", std::process::id()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("lib/nested")).unwrap();
+        std::fs::write(dir.join("secret.js"), "export const secret = 1;").unwrap();
+        std::fs::write(
+            root.join("lib/index.js"),
+            "export { nested } from './nested'; export { default as config } from '../config.json';",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("lib/nested/index.mjs"),
+            "export const nested = import.meta.url;",
+        )
+        .unwrap();
+        std::fs::write(root.join("config.json"), r#"{"debug": true}"#).unwrap();
+
+        let loader = FsModuleLoader::new(&root).unwrap();
+        let url = format!(
+            "file://{}/lib/nested/index.mjs",
+            loader.root().to_string_lossy().replace('\\', "/")
+        );
+        let c = Context::new().unwrap();
+        c.set_module_loader(loader);
+        c.register_module("host:env", |module| {
+            module.export("mode", "test");
+        })
+        .unwrap();
+
+        c.eval_module(
+            r#"
+            import { nested, config } from './lib';
+            import { mode } from 'host:env';
+            globalThis.result = [nested, config.debug, mode, import.meta.resolve('./lib/nested')];
+            "#,
+            "main.js",
+        )
+        .unwrap();
+        assert_eq!(
+            c.eval("result"),
+            Ok(JsValue::Array(vec![
+                JsValue::String(url),
+                JsValue::Bool(true),
+                JsValue::from("test"),
+                JsValue::from("lib/nested/index.mjs"),
+            ]))
+        );
+
+        let err = c
+            .eval_module("import '../secret.js';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("outside of the root"), "{}", err);
+        let err = c
+            .eval_module("import '/lib/../../secret.js';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("ReferenceError"), "{}", err);
+        let err = c
+            .eval_module("import './missing.js';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("could not load module 'missing.js'"),
+            "{}",
+            err
+        );
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("secret.js"), root.join("link.js")).unwrap();
+            let err = c
+                .eval_module("import './link.js';", "main.js")
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains("outside of the root"), "{}", err);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "node-loader")]
+    #[test]
+    fn node_module_loader() {
+        use module::NodeModuleLoader;
+
+        let root =
+            std::env::temp_dir().join(format!("quick-js-node-loader-{}", std::process::id()));
+        let write = |path: &str, contents: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write(
+            "node_modules/cond/package.json",
+            r#"{
+                "exports": {
+                    ".": { "require": "./index.cjs", "import": "./index.mjs" },
+                    "./features/*.js": "./src/features/*.js",
+                    "./features/private/*": null
+                }
+            }"#,
+        );
+        write("node_modules/cond/index.mjs", "export default 'cond';");
+        write(
+            "node_modules/cond/src/features/a.js",
+            "export default 'feature a';",
+        );
+        write(
+            "node_modules/@scope/main/package.json",
+            r#"{ "main": "lib/entry" }"#,
+        );
+        write(
+            "node_modules/@scope/main/lib/entry.js",
+            "export { default } from 'nested';",
+        );
+        write(
+            "node_modules/@scope/main/node_modules/nested/index.js",
+            "export default 'nested';",
+        );
+        write("node_modules/plain/util.js", "export default 'util';");
+        write("src/local.js", "export default 'local';");
+
+        let c = Context::new().unwrap();
+        c.set_module_loader(NodeModuleLoader::new(&root).unwrap());
+        c.register_module("host:env", |module| {
+            module.export("mode", "test");
+        })
+        .unwrap();
+        c.eval_module(
+            r#"
+            import cond from 'cond';
+            import feature from 'cond/features/a.js';
+            import main from '@scope/main';
+            import util from 'plain/util';
+            import local from './local.js';
+            import { mode } from 'host:env';
+            globalThis.result = [cond, feature, main, util, local, mode];
+            "#,
+            "src/main.js",
+        )
+        .unwrap();
+        assert_eq!(
+            c.eval("result"),
+            Ok(JsValue::Array(vec![
+                JsValue::from("cond"),
+                JsValue::from("feature a"),
+                JsValue::from("nested"),
+                JsValue::from("util"),
+                JsValue::from("local"),
+                JsValue::from("test"),
+            ]))
+        );
+
+        let err = c
+            .eval_module("import 'cond/features/private/x';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("is not exported by package"), "{}", err);
+        let err = c
+            .eval_module("import 'cond/index.cjs';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("is not exported by package"), "{}", err);
+
+        // Other conditions can be matched instead.
+        let c = Context::new().unwrap();
+        c.set_module_loader(
+            NodeModuleLoader::new(&root)
+                .unwrap()
+                .conditions(vec!["require"]),
+        );
+        let err = c
+            .eval_module("import 'cond';", "main.js")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("'./index.cjs' not found"), "{}", err);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn module_graph() {
+        use module::{ModuleImport, ModuleSource, ModuleState};
+
+        let c = Context::new().unwrap();
+        c.set_module_loader(|name: &str| match name {
+            "lib/a.js" => Ok(ModuleSource::from(
+                "import { c } from './c.js'; export { b } from './b.js'; export const a = c;",
+            )),
+            "lib/b.js" => Ok(ModuleSource::from("import './c.js'; export const b = 1;")),
+            "lib/c.js" => Ok(ModuleSource::from("export const c = 2;")),
+            "broken.js" => Ok(ModuleSource::from("export const = ;")),
+            _ => Err("not found"),
+        });
+        c.register_module("host:env", |module| {
+            module.export("mode", "test");
+        })
+        .unwrap();
+
+        c.eval_module(
+            "import { a, b } from './lib/a.js'; import { mode } from 'host:env';",
+            "main.js",
+        )
+        .unwrap();
+        assert!(c.eval_module("import 'broken.js';", "other.js").is_err());
+        c.eval("import('./lib/b.js')").unwrap();
+
+        let graph = c.module_graph();
+        assert_eq!(
+            graph.modules().collect::<Vec<_>>(),
+            vec![
+                ("broken.js", ModuleState::Failed),
+                ("host:env", ModuleState::Native),
+                ("lib/a.js", ModuleState::Loaded),
+                ("lib/b.js", ModuleState::Loaded),
+                ("lib/c.js", ModuleState::Loaded),
+                ("main.js", ModuleState::Loaded),
+                ("other.js", ModuleState::Failed),
+            ]
+        );
+        assert_eq!(graph.state("missing.js"), None);
+        assert_eq!(graph.dependencies("main.js"), vec!["lib/a.js", "host:env"]);
+        assert_eq!(graph.importers("lib/c.js"), vec!["lib/a.js", "lib/b.js"]);
+        assert_eq!(
+            graph.dependents("lib/c.js"),
+            vec!["lib/a.js", "lib/b.js", "main.js", "script.js"]
+        );
+        assert!(graph.imports().contains(&ModuleImport {
+            importer: "script.js".into(),
+            specifier: "./lib/b.js".into(),
+            name: "lib/b.js".into(),
+        }));
+
+        // The graph belongs to the context.
+        let other = Context::builder().runtime(&c.runtime()).build().unwrap();
+        assert_eq!(other.module_graph(), module::ModuleGraph::default());
+    }
+
+    #[test]
+    fn hot_reload() {
+        use module::{ModuleSource, ModuleState};
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        };
+
+        let source = Arc::new(Mutex::new("export const c = 1;".to_string()));
+        let loads = Arc::new(AtomicUsize::new(0));
+        let c = Context::new().unwrap();
+        {
+            let source = source.clone();
+            let loads = loads.clone();
+            c.set_module_loader(move |name: &str| match name {
+                "lib/a.js" => Ok(ModuleSource::from("export { c, url } from './c.js';")),
+                "lib/c.js" => {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    let code = source.lock().unwrap().clone();
+                    Ok(ModuleSource::from(
+                        code + " export const url = import.meta.url;",
+                    ))
+                }
+                _ => Err("not found"),
+            });
+        }
+
+        let main = "import { c, url } from './lib/a.js'; globalThis.c = c; globalThis.url = url;";
+        c.eval_module(main, "main.js").unwrap();
+        assert_eq!(c.eval("c").unwrap(), JsValue::Int(1));
+
+        // Modules are cached until they are invalidated.
+        *source.lock().unwrap() = "export const c = 2;".to_string();
+        c.eval_module(main, "main.js").unwrap();
+        assert_eq!(c.eval("c").unwrap(), JsValue::Int(1));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            c.invalidate_module("lib/c.js"),
+            vec!["lib/c.js", "lib/a.js", "main.js"]
+        );
+        assert_eq!(c.module_graph().state("lib/c.js"), None);
+
+        c.eval_module(main, "main.js").unwrap();
+        assert_eq!(c.eval("c").unwrap(), JsValue::Int(2));
+        assert_eq!(c.eval("url").unwrap(), JsValue::from("lib/c.js"));
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            c.module_graph().state("lib/c.js"),
+            Some(ModuleState::Loaded)
+        );
+        assert_eq!(c.module_graph().dependencies("lib/a.js"), vec!["lib/c.js"]);
+
+        // Dynamic imports see the new version as well.
+        *source.lock().unwrap() = "export const c = 3;".to_string();
+        c.invalidate_module("lib/c.js");
+        assert_eq!(
+            c.eval("import('./lib/a.js').then(m => m.c)"),
+            Ok(JsValue::Int(3))
+        );
+    }
+
+    #[test]
+    fn bytecode_cache() {
+        use module::{BytecodeCache, MemoryBytecodeCache, ModuleSource};
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        };
+
+        #[derive(Default)]
+        struct Counting {
+            cache: MemoryBytecodeCache,
+            hits: AtomicUsize,
+        }
+
+        impl BytecodeCache for Counting {
+            fn get(&self, name: &str, hash: u64) -> Option<Vec<u8>> {
+                let bytecode = self.cache.get(name, hash);
+                if bytecode.is_some() {
+                    self.hits.fetch_add(1, Ordering::SeqCst);
+                }
+                bytecode
+            }
+
+            fn put(&self, name: &str, hash: u64, bytecode: Vec<u8>) {
+                self.cache.put(name, hash, bytecode);
+            }
+        }
+
+        let source = Arc::new(Mutex::new("export const b = 1;".to_string()));
+        let cache = Arc::new(Counting::default());
+        let eval = || {
+            let c = Context::new().unwrap();
+            let source = source.clone();
+            c.set_module_loader(move |name: &str| match name {
+                "lib/a.js" => Ok(ModuleSource::from(
+                    "import { b } from './b.js'; export const a = b + 1;",
+                )),
+                "lib/b.js" => Ok(ModuleSource::from(source.lock().unwrap().clone())),
+                "data.json" => Ok(ModuleSource::Json("2".into())),
+                _ => Err("not found"),
+            });
+            unsafe { c.runtime().set_bytecode_cache(cache.clone()) };
+            c.eval_module(
+                "import { a } from './lib/a.js'; import d from './data.json'; globalThis.x = a + d;",
+                "main.js",
+            )
+            .unwrap();
+            c.eval("x").unwrap()
+        };
+
+        // Only compiled modules are cached, not the evaluated main module.
+        assert_eq!(eval(), JsValue::Int(4));
+        assert_eq!(cache.cache.len(), 2);
+        assert_eq!(cache.hits.load(Ordering::SeqCst), 0);
+
+        assert_eq!(eval(), JsValue::Int(4));
+        assert_eq!(cache.hits.load(Ordering::SeqCst), 2);
+
+        // A changed module is compiled again.
+        *source.lock().unwrap() = "export const b = 10;".to_string();
+        assert_eq!(eval(), JsValue::Int(13));
+        assert_eq!(cache.hits.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.cache.len(), 2);
+
+        // Invalid bytecode is compiled again as well.
+        let hash = module::source_hash("export const b = 10;");
+        cache.put("lib/b.js", hash, Vec::new());
+        assert_eq!(eval(), JsValue::Int(13));
+        assert_eq!(cache.hits.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn snapshot() {
+        use module::ModuleSource;
+        use snapshot::Snapshot;
+
+        let loader = |name: &str| match name {
+            "lib.js" => Ok(ModuleSource::from("export const base = 40;")),
+            _ => Err("not found"),
+        };
+
+        let setup = Context::new().unwrap();
+        setup.set_module_loader(loader);
+        let mut builder = Snapshot::builder(&setup);
+        builder
+            .eval(
+                "var calls = 0; function next() { return ++calls; }",
+                "prelude.js",
+            )
+            .unwrap();
+        assert!(builder.eval("next(", "broken.js").is_err());
+        builder
+            .eval_module(
+                "import { base } from 'lib.js'; globalThis.answer = base + next();",
+                "main.js",
+            )
+            .unwrap();
+        let snapshot = builder.build();
+        // The setup context is initialized too.
+        assert_eq!(setup.eval("answer"), Ok(JsValue::Int(41)));
+
+        let bytes = snapshot.to_bytes();
+        let restored = unsafe { Snapshot::from_bytes(&bytes) }.unwrap();
+        assert_eq!(restored, snapshot);
+
+        let c = Context::new().unwrap();
+        c.set_module_loader(loader);
+        c.load_snapshot(&restored).unwrap();
+        assert_eq!(c.eval("answer"), Ok(JsValue::Int(41)));
+        assert_eq!(c.eval("next()"), Ok(JsValue::Int(2)));
+
+        // Imports are loaded again.
+        let c = Context::new().unwrap();
+        assert!(c.load_snapshot(&restored).is_err());
+
+        // Snapshots of another version are rejected like compiled bytecode.
+        let version = bytecode::version();
+        let other = bytes
+            .windows(version.len())
+            .position(|w| w == version.as_bytes())
+            .unwrap();
+        let mut outdated = bytes.clone();
+        outdated[other] = b'!';
+        match unsafe { Snapshot::from_bytes(&outdated) } {
+            Err(bytecode::BytecodeError::VersionMismatch { expected, .. }) => {
+                assert_eq!(expected, version);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(
+            unsafe { Snapshot::from_bytes(b"garbage") },
+            Err(bytecode::BytecodeError::Invalid)
+        );
+        assert_eq!(
+            unsafe { Snapshot::from_bytes(&bytes[..bytes.len() - 1]) },
+            Err(bytecode::BytecodeError::Invalid)
+        );
+    }
+
+    #[test]
+    fn context_pool() {
+        use pool::ContextPool;
+        use snapshot::Snapshot;
+
+        let setup = Context::new().unwrap();
+        setup.add_callback("double", |x: i32| x * 2).unwrap();
+        let mut builder = Snapshot::builder(&setup);
+        builder
+            .eval("var base = double(20);", "prelude.js")
+            .unwrap();
+        let snapshot = builder.build();
+
+        let pool = ContextPool::builder(2)
+            .context(|| {
+                let context = Context::new()?;
+                context
+                    .add_callback("double", |x: i32| x * 2)
+                    .map_err(ContextError::Execution)?;
+                Ok(context)
+            })
+            .snapshot(snapshot)
+            .setup(|context| {
+                context.eval("var answer = base + 2;")?;
+                Ok(())
+            })
+            .build()
+            .unwrap();
+        assert_eq!(pool.idle(), 2);
+
+        let c1 = pool.get().unwrap();
+        assert_eq!(c1.eval("answer"), Ok(JsValue::Int(42)));
+        c1.eval("answer = 0; var leaked = true;").unwrap();
+        let c2 = pool.get().unwrap();
+        assert_eq!(pool.idle(), 0);
+        // Contexts beyond the size of the pool are created on demand.
+        let c3 = pool.get().unwrap();
+        assert_eq!(c3.eval("answer"), Ok(JsValue::Int(42)));
+        drop((c1, c2, c3));
+        assert_eq!(pool.idle(), 2);
+
+        let c = pool.get().unwrap();
+        assert_eq!(c.eval("answer"), Ok(JsValue::Int(42)));
+        assert_eq!(c.eval("typeof leaked"), Ok(JsValue::from("undefined")));
+
+        let failing = ContextPool::builder(1)
+            .setup(|context| context.eval("throw 'setup failed'").map(|_| ()))
+            .build();
+        assert!(matches!(failing, Err(ContextError::Execution(_))));
+    }
+
+    #[cfg(not(target_os = "wasi"))]
+    #[test]
+    fn context_worker() {
+        use std::sync::atomic::AtomicUsize;
+        use worker::ContextWorker;
+
+        let worker = ContextWorker::spawn(|| {
+            let context = Context::new()?;
+            context
+                .eval("var greeting = 'Hello';")
+                .map_err(ContextError::Execution)?;
+            Ok(context)
+        })
+        .unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let callback = move |name: String| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            format!("{}, {}!", "Hello", name)
+        };
+        block_on(worker.add_callback("greet", callback)).unwrap();
+        block_on(worker.eval("function add(a, b) { return a + b; }")).unwrap();
+
+        // The handle can be used from other threads.
+        let handle = worker.clone();
+        let added = std::thread::spawn(move || {
+            block_on(handle.call_function("add", vec![1, 2])) == Ok(JsValue::Int(3))
+        });
+        assert!(added.join().unwrap());
+        assert_eq!(
+            block_on(worker.eval("greet('worker')")),
+            Ok(JsValue::from("Hello, worker!"))
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Handles stay on the worker thread.
+        assert!(matches!(
+            block_on(worker.eval("[add]")),
+            Err(ExecutionError::Internal(_))
+        ));
+        let sum = block_on(worker.run(|context| {
+            let add: JsFunction = context.eval_as("add")?;
+            let sum = add.call(vec![20, 22])?;
+            Ok(i32::try_from(sum)?)
+        }));
+        assert_eq!(sum, Ok(42));
+
+        let err = block_on(worker.eval("throw new TypeError('bad')")).unwrap_err();
+        assert!(matches!(err, ExecutionError::Exception(e) if e.name == "TypeError"));
+
+        let failed = ContextWorker::spawn(|| Err(ContextError::ContextCreationFailed));
+        assert!(matches!(failed, Err(ContextError::ContextCreationFailed)));
+    }
+
+    #[cfg(not(target_os = "wasi"))]
+    #[test]
+    fn context_worker_callbacks() {
+        use worker::ContextWorker;
+
+        let worker = ContextWorker::new().unwrap();
+        block_on(worker.eval("var ids = () => Object.values(plugins).map(p => p.id())")).unwrap();
+
+        // Callbacks are added from other threads while the worker is running.
+        let threads: Vec<_> = (0..4)
+            .map(|index| {
+                let handle = worker.clone();
+                std::thread::spawn(move || {
+                    let path = format!("plugins.p{}.id", index);
+                    block_on(handle.add_callback_at(path, move || index)).is_ok()
+                })
+            })
+            .collect();
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+        assert_eq!(
+            block_on(worker.eval("ids().sort().join()")),
+            Ok(JsValue::from("0,1,2,3"))
+        );
+
+        // Callbacks are replaced, but old references still work.
+        block_on(worker.eval("var old = plugins.p0.id")).unwrap();
+        block_on(worker.add_callback_at("plugins.p0.id", || 10)).unwrap();
+        assert_eq!(
+            block_on(worker.eval("[old(), plugins.p0.id()].join()")),
+            Ok(JsValue::from("0,10"))
+        );
+        assert!(matches!(
+            block_on(worker.add_callback_at("plugins..id", || 0)),
+            Err(ExecutionError::Internal(_))
+        ));
+    }
+
+    #[test]
+    fn runtime_shared_contexts() {
+        let runtime = Runtime::new().unwrap();
+        let c1 = runtime.new_context().unwrap();
+        let c2 = Context::builder().runtime(&runtime).build().unwrap();
+
+        c1.eval(" var x = 'c1'; ").unwrap();
+        c2.eval(" var x = 'c2'; ").unwrap();
+        assert_eq!(c1.eval_as::<String>(" x ").unwrap(), "c1");
+        assert_eq!(c2.eval_as::<String>(" x ").unwrap(), "c2");
+
+        // Values can be passed between contexts.
+        let value = c1.eval(" [x, 1, true] ").unwrap();
+        c2.eval(" function first(arr) { return arr[0]; } ").unwrap();
+        assert_eq!(
+            c2.call_function("first", vec![value]).unwrap(),
+            JsValue::from("c1"),
+        );
+
+        // The runtime outlives the handle it was created from.
+        drop(runtime);
+        drop(c1);
+        assert_eq!(c2.eval(" 1 + 1 ").unwrap(), JsValue::Int(2));
+    }
+
+    #[test]
+    fn runtime_context_isolation() {
+        let runtime = Runtime::new().unwrap();
+        let c1 = runtime.new_context().unwrap();
+        let c2 = Context::builder().runtime(&runtime).build().unwrap();
+        let contexts = runtime.contexts();
+        assert_eq!(contexts.len(), 2);
+        assert!(contexts[0].ptr_eq(&c1) && contexts[1].ptr_eq(&c2));
+        drop(contexts);
+
+        // Values keep their context alive.
+        let function = c1.eval("(() => 42)").unwrap();
+        drop(c1);
+        assert_eq!(runtime.contexts().len(), 2);
+
+        // Handles can be shared until the runtime is isolated.
+        c2.eval("function call(f) { return f(); }").unwrap();
+        assert_eq!(
+            c2.call_function("call", vec![function.clone()]),
+            Ok(JsValue::Int(42))
+        );
+        runtime.set_context_isolation(true);
+        assert!(matches!(
+            c2.call_function("call", vec![function.clone()]),
+            Err(ExecutionError::Conversion(_))
+        ));
+        let object = c2.global().unwrap();
+        let symbol = runtime.contexts()[0].new_symbol(None).unwrap();
+        assert!(object.set_symbol(&symbol, 1).is_err());
+
+        // Copies and plain values are allowed.
+        let owned = runtime.contexts()[0]
+            .eval_owned("({ answer: 42 })")
+            .unwrap();
+        let copy = runtime.contexts()[0].transfer(&owned, &c2).unwrap();
+        c2.eval("function answer(o) { return o.answer; }").unwrap();
+        assert_eq!(c2.call_function("answer", vec![copy]), Ok(JsValue::Int(42)));
+
+        drop((function, owned, symbol));
+        let contexts = runtime.contexts();
+        assert_eq!(contexts.len(), 1);
+        assert!(contexts[0].ptr_eq(&c2));
+    }
+
+    #[test]
+    fn realm() {
+        use realm::Realm;
+
+        let runtime = Runtime::new().unwrap();
+        let realm = Realm::builder()
+            .runtime(&runtime)
+            .intrinsics(&["Function", "Error", "eval"])
+            .callback("host.fail", || -> Result<i32, JsError> {
+                Err(JsError::type_error("failed"))
+            })
+            .build()
+            .unwrap();
+        assert_eq!(runtime.contexts().len(), 1);
+
+        assert_eq!(realm.eval("typeof Object"), Ok(JsValue::from("undefined")));
+        assert_eq!(realm.eval("typeof console"), Ok(JsValue::from("undefined")));
+        assert_eq!(realm.eval("[1, 2].map(x => x * 2)[1]"), Ok(JsValue::Int(4)));
+        assert_eq!(realm.eval("undefined === void 0"), Ok(JsValue::Bool(true)));
+        assert_eq!(
+            realm.eval("try { host.fail() } catch (e) { e instanceof Error && e.name }"),
+            Ok(JsValue::from("TypeError"))
+        );
+
+        // Code generation is disabled for all kinds of functions.
+        for code in &[
+            "eval('1')",
+            "Function('return 1')()",
+            "new Function('return 1')()",
+            "(() => {}).constructor('return 1')()",
+            "(async () => {}).constructor('return 1')()",
+            "(function* () {}).constructor('yield 1')().next().value",
+            "(async function* () {}).constructor('yield 1')",
+        ] {
+            let err = realm.eval(code).unwrap_err();
+            assert!(
+                matches!(&err, ExecutionError::Exception(e) if e.name == "EvalError"),
+                "{}: {:?}",
+                code,
+                err
+            );
+        }
+        assert_eq!(
+            realm.eval("(() => {}) instanceof Function"),
+            Ok(JsValue::Bool(true))
+        );
+
+        let realm = Realm::builder()
+            .intrinsic("eval")
+            .allow_code_generation(true)
+            .build()
+            .unwrap();
+        assert_eq!(realm.eval("eval('1 + 1')"), Ok(JsValue::Int(2)));
+        assert_eq!(
+            realm.eval("(() => {}).constructor('return 3')()"),
+            Ok(JsValue::Int(3))
+        );
+    }
 
     #[test]
-    fn test_eval_pass() {
-        use std::iter::FromIterator;
+    fn context_disable_eval() {
+        let c = Context::builder().disable_eval(true).build().unwrap();
+        c.add_callback("fail", || -> Result<i32, JsError> {
+            Err(JsError::range_error("failed"))
+        })
+        .unwrap();
 
-        let c = Context::new().unwrap();
+        let is_eval_error = |c: &Context, code: &str| matches!(c.eval(code), Err(ExecutionError::Exception(e)) if e.name == "EvalError");
+        assert!(is_eval_error(&c, "eval('1')"));
+        assert!(is_eval_error(&c, "Function('return 1')"));
+        assert!(is_eval_error(
+            &c,
+            "(async () => {}).constructor('return 1')"
+        ));
+        assert!(is_eval_error(
+            &c,
+            "(function* () {}).constructor('yield 1')"
+        ));
 
-        let cases = vec![
-            ("null", Ok(JsValue::Null)),
-            ("true", Ok(JsValue::Bool(true))),
-            ("2 > 10", Ok(JsValue::Bool(false))),
-            ("1", Ok(JsValue::Int(1))),
-            ("1 + 1", Ok(JsValue::Int(2))),
-            ("1.1", Ok(JsValue::Float(1.1))),
-            ("2.2 * 2 + 5", Ok(JsValue::Float(9.4))),
-            ("\"abc\"", Ok(JsValue::String("abc".into()))),
-            (
-                "[1,2]",
-                Ok(JsValue::Array(vec![JsValue::Int(1), JsValue::Int(2)])),
-            ),
-        ];
+        // Everything else is unaffected.
+        assert_eq!(c.eval("typeof Math.max"), Ok(JsValue::from("function")));
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
+        assert_eq!(
+            c.eval("try { fail() } catch (e) { e instanceof RangeError }"),
+            Ok(JsValue::Bool(true))
+        );
 
-        for (code, res) in cases.into_iter() {
-            assert_eq!(c.eval(code), res,);
-        }
+        // The setting survives a reset.
+        let c = c.reset().unwrap();
+        assert!(is_eval_error(&c, "eval('1')"));
 
-        let obj_cases = vec![
-            (
-                r#" {"a": null} "#,
-                Ok(JsValue::Object(HashMap::from_iter(vec![(
-                    "a".to_string(),
-                    JsValue::Null,
-                )]))),
-            ),
-            (
-                r#" {a: 1, b: true, c: {c1: false}} "#,
-                Ok(JsValue::Object(HashMap::from_iter(vec![
-                    ("a".to_string(), JsValue::Int(1)),
-                    ("b".to_string(), JsValue::Bool(true)),
-                    (
-                        "c".to_string(),
-                        JsValue::Object(HashMap::from_iter(vec![(
-                            "c1".to_string(),
-                            JsValue::Bool(false),
-                        )])),
-                    ),
-                ]))),
-            ),
-        ];
+        let c = Context::builder().disable_eval(false).build().unwrap();
+        assert_eq!(c.eval("eval('1 + 1')"), Ok(JsValue::Int(2)));
+    }
 
-        for (index, (code, res)) in obj_cases.into_iter().enumerate() {
-            let full_code = format!(
-                "var v{index} = {code}; v{index}",
-                index = index,
-                code = code
+    #[test]
+    fn context_intrinsics() {
+        use std::time::UNIX_EPOCH;
+
+        let c = Context::builder()
+            .intrinsics(IntrinsicSet {
+                json: true,
+                ..IntrinsicSet::minimal()
+            })
+            .build()
+            .unwrap();
+
+        for name in &[
+            "Date",
+            "RegExp",
+            "Proxy",
+            "Map",
+            "WeakSet",
+            "ArrayBuffer",
+            "URL",
+        ] {
+            assert_eq!(
+                c.eval(&format!("typeof {}", name)),
+                Ok(JsValue::from("undefined")),
+                "{}",
+                name
             );
-            assert_eq!(c.eval(&full_code), res,);
         }
+        assert!(c.eval("/a/").is_err());
+        // quickjs-ng always parses BigInt literals.
+        #[cfg(not(feature = "ng"))]
+        assert!(c.eval("1n").is_err());
+        #[cfg(feature = "ng")]
+        assert_eq!(c.eval("typeof BigInt"), Ok(JsValue::from("undefined")));
 
-        assert_eq!(c.eval_as::<bool>("true").unwrap(), true,);
-        assert_eq!(c.eval_as::<i32>("1 + 2").unwrap(), 3,);
+        // The base objects, `Promise` and the enabled built-ins remain.
+        assert_eq!(c.eval("JSON.stringify([1])"), Ok(JsValue::from("[1]")));
+        assert_eq!(
+            c.eval("(async () => [...'ab'].length)()"),
+            Ok(JsValue::Int(2))
+        );
 
-        let value: String = c.eval_as("var x = 44; x.toString()").unwrap();
-        assert_eq!(&value, "44");
+        // Values of missing built-ins can not be converted, but do not
+        // break the context.
+        c.eval("function identity(x) { return x; }").unwrap();
+        assert!(c
+            .call_function("identity", vec![JsValue::Date(UNIX_EPOCH)])
+            .is_err());
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
 
-        #[cfg(feature = "bigint")]
+        // Errors are created without regular expressions.
+        c.add_callback("fail", || -> Result<i32, JsError> {
+            Err(JsError::range_error("failed"))
+        })
+        .unwrap();
         assert_eq!(
-            c.eval_as::<num_bigint::BigInt>("1n << 100n").unwrap(),
-            num_bigint::BigInt::from(1i128 << 100)
+            c.eval("try { fail() } catch (e) { e instanceof RangeError }"),
+            Ok(JsValue::Bool(true))
         );
 
-        #[cfg(feature = "bigint")]
-        assert_eq!(c.eval_as::<i64>("1 << 30").unwrap(), 1i64 << 30);
+        // The intrinsics survive a reset.
+        let c = c.reset().unwrap();
+        assert_eq!(c.eval("typeof Date"), Ok(JsValue::from("undefined")));
+        assert_eq!(c.eval("typeof JSON"), Ok(JsValue::from("object")));
 
-        #[cfg(feature = "bigint")]
-        assert_eq!(c.eval_as::<u128>("1n << 100n").unwrap(), 1u128 << 100);
+        assert!(Context::builder()
+            .intrinsics(IntrinsicSet::minimal())
+            .time_source(time::SystemClock)
+            .build()
+            .is_err());
     }
 
     #[test]
-    fn test_eval_syntax_error() {
+    fn runtime_memory_limit_shared() {
+        let runtime = Runtime::new().unwrap();
+        let c1 = Context::builder()
+            .runtime(&runtime)
+            .memory_limit(1_000_000)
+            .build()
+            .unwrap();
+        let c2 = runtime.new_context().unwrap();
+
+        // The limit set via c1 also applies to c2.
+        assert_eq!(
+            c2.eval("  'abc'.repeat(1_000_000) "),
+            Err(ExecutionError::OutOfMemory),
+        );
+        assert!(c1.runtime().new_context().is_ok());
+    }
+
+    #[test]
+    fn context_reset() {
         let c = Context::new().unwrap();
+        c.eval(" var x = 123; ").unwrap();
+        c.add_callback("myCallback", || true).unwrap();
+
+        let c2 = c.reset().unwrap();
+
+        // Check it still works.
         assert_eq!(
-            c.eval(
-                r#"
-                !!!!
-            "#
-            ),
-            Err(ExecutionError::Exception(
-                "SyntaxError: unexpected token in expression: \'\'".into()
+            c2.eval_as::<String>(" 'abc'.repeat(2) ").unwrap(),
+            "abcabc".to_string(),
+        );
+
+        // Check old state is gone.
+        let err_msg = c2.eval(" x ").unwrap_err().to_string();
+        assert!(err_msg.contains("ReferenceError"));
+
+        // Check callback is restored.
+        assert_eq!(c2.eval(" myCallback() "), Ok(JsValue::Bool(true)));
+
+        // Builder settings, namespaced callbacks and accessors are restored
+        // too.
+        let c = Context::builder()
+            .timers(true)
+            .execution_timeout(Duration::from_millis(10))
+            .build()
+            .unwrap();
+        c.add_callback_at("api.double", |x: i32| x * 2).unwrap();
+        c.set_global_property_with_accessors("answer", || 42, |_| {})
+            .unwrap();
+        let c = c.reset().unwrap();
+        assert_eq!(c.eval("typeof setTimeout"), Ok(JsValue::from("function")));
+        assert_eq!(c.eval("while (true) {}"), Err(ExecutionError::TimedOut));
+        assert_eq!(c.eval("api.double(answer)"), Ok(JsValue::Int(84)));
+    }
+
+    #[inline(never)]
+    fn build_context() -> Context {
+        let ctx = Context::new().unwrap();
+        let name = "cb".to_string();
+        ctx.add_callback(&name, |a: String| a.repeat(2)).unwrap();
+
+        let code = " function f(value) { return cb(value); } ".to_string();
+        ctx.eval(&code).unwrap();
+
+        ctx
+    }
+
+    #[test]
+    fn moved_context() {
+        let c = build_context();
+        let v = c.call_function("f", vec!["test"]).unwrap();
+        assert_eq!(v, "testtest".into());
+
+        let v = c.eval(" f('la') ").unwrap();
+        assert_eq!(v, "lala".into());
+    }
+
+    #[test]
+    fn date_roundtrip() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let c = build_context();
+        c.eval(" function identity(x) { return x; } ").unwrap();
+
+        assert_eq!(
+            c.eval("new Date(1234567555)"),
+            Ok(JsValue::Date(
+                UNIX_EPOCH + Duration::from_millis(1234567555)
             ))
         );
+        assert_eq!(
+            c.eval("new Date(-1000)"),
+            Ok(JsValue::Date(UNIX_EPOCH - Duration::from_secs(1)))
+        );
+        assert!(c.eval("new Date(NaN)").is_err());
+
+        let now = SystemTime::now();
+        let millis = now.duration_since(UNIX_EPOCH).unwrap().as_millis();
+        let now_millis = UNIX_EPOCH + Duration::from_millis(millis as u64);
+        assert_eq!(
+            c.call_function("identity", vec![now]),
+            Ok(JsValue::Date(now_millis))
+        );
+        c.eval(" function getTime(d) { return d.getTime(); } ")
+            .unwrap();
+        assert_eq!(
+            c.call_function("getTime", vec![now]),
+            Ok(JsValue::Float(millis as f64))
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_serialize() {
+        let c = build_context();
+
+        c.eval(
+            "
+            function dateToTimestamp(date) {
+                return date.getTime();
+            }
+       ",
+        )
+        .unwrap();
+
+        let now = chrono::Utc::now();
+        let now_millis = now.timestamp_millis();
+
+        let timestamp = c
+            .call_function("dateToTimestamp", vec![JsValue::from(now)])
+            .unwrap();
+
+        assert_eq!(timestamp, JsValue::Float(now_millis as f64));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_deserialize() {
+        use chrono::offset::TimeZone;
+
+        let c = build_context();
+
+        let value = c.eval(" new Date(1234567555) ").unwrap();
+        let datetime = chrono::Utc.timestamp_millis(1234567555);
+
+        assert_eq!(value, JsValue::from(datetime));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_roundtrip() {
+        let c = build_context();
+
+        c.eval(" function identity(x) { return x; } ").unwrap();
+        let d = chrono::Utc::now();
+        let td = JsValue::from(d);
+        let td2 = c.call_function("identity", vec![td.clone()]).unwrap();
+        let d2 = chrono::DateTime::<chrono::Utc>::try_from(td2).expect("expected date");
+
+        assert_eq!(d.timestamp_millis(), d2.timestamp_millis());
+    }
+
+    #[cfg(feature = "bignum")]
+    #[test]
+    fn bigint_i128_roundtrip() {
+        let c = Context::new().unwrap();
+        c.eval("var identity = (x) => x;").unwrap();
+
+        assert_eq!(c.eval_as::<i64>("-(2n ** 63n)"), Ok(i64::MIN));
+        assert_eq!(c.eval_as::<u64>("2n ** 64n - 1n"), Ok(u64::MAX));
+        assert_eq!(c.eval_as::<i128>("-(2n ** 127n)"), Ok(i128::MIN));
+        assert_eq!(c.eval_as::<u128>("2n ** 100n"), Ok(1u128 << 100));
+        assert!(c.eval_as::<i64>("2n ** 63n").is_err());
+        assert!(c.eval_as::<u64>("-1n").is_err());
+
+        for i in [0, 1 << 70, i128::MIN, i128::MAX] {
+            assert_eq!(c.eval(&format!("{}n", i)), Ok(JsValue::from(i)));
+            c.eval(&format!(" function isEqual(x) {{ return x === {}n }} ", i))
+                .unwrap();
+            assert_eq!(c.call_function("isEqual", vec![i]), Ok(JsValue::Bool(true)));
+        }
+        assert_eq!(
+            c.call_function("identity", vec![u64::MAX]),
+            Ok(JsValue::BigInt(u64::MAX.into()))
+        );
+        assert_eq!(
+            c.call_function("identity", vec![u128::MAX]),
+            Ok(JsValue::from(u128::MAX))
+        );
+        assert_eq!(c.eval_as::<u128>("2n ** 128n - 1n"), Ok(u128::MAX));
+
+        #[cfg(not(feature = "bigint"))]
+        assert!(c.eval("2n ** 128n").is_err());
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_deserialize_i64() {
+        for i in vec![0, std::i64::MAX, std::i64::MIN] {
+            let c = Context::new().unwrap();
+            let value = c.eval(&format!("{}n", i)).unwrap();
+            assert_eq!(value, JsValue::BigInt(i.into()));
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_deserialize_bigint() {
+        for i in vec![
+            std::i64::MAX as i128 + 1,
+            std::i64::MIN as i128 - 1,
+            std::i128::MAX,
+            std::i128::MIN,
+        ] {
+            let c = Context::new().unwrap();
+            let value = c.eval(&format!("{}n", i)).unwrap();
+            let expected = num_bigint::BigInt::from(i);
+            assert_eq!(value, JsValue::BigInt(expected.into()));
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_serialize_i64() {
+        for i in vec![0, std::i64::MAX, std::i64::MIN] {
+            let c = Context::new().unwrap();
+            c.eval(&format!(" function isEqual(x) {{ return x === {}n }} ", i))
+                .unwrap();
+            assert_eq!(
+                c.call_function("isEqual", vec![JsValue::BigInt(i.into())])
+                    .unwrap(),
+                JsValue::Bool(true)
+            );
+        }
     }
 
+    #[cfg(feature = "bigint")]
     #[test]
-    fn test_eval_exception() {
-        let c = Context::new().unwrap();
-        assert_eq!(
-            c.eval(
-                r#"
-                function f() {
-                    throw new Error("My Error");
-                }
-                f();
-            "#
-            ),
-            Err(ExecutionError::Exception("Error: My Error".into(),))
-        );
+    fn test_bigint_serialize_bigint() {
+        for i in vec![
+            std::i64::MAX as i128 + 1,
+            std::i64::MIN as i128 - 1,
+            std::i128::MAX,
+            std::i128::MIN,
+        ] {
+            let c = Context::new().unwrap();
+            c.eval(&format!(" function isEqual(x) {{ return x === {}n }} ", i))
+                .unwrap();
+            let value = JsValue::BigInt(num_bigint::BigInt::from(i).into());
+            assert_eq!(
+                c.call_function("isEqual", vec![value]).unwrap(),
+                JsValue::Bool(true)
+            );
+        }
     }
 
     #[test]
-    fn eval_async() {
+    fn lone_surrogates() {
         let c = Context::new().unwrap();
-
-        let value = c
-            .eval(
-                r#"
-            new Promise((resolve, _) => {
-                resolve(33);
-            })
-       "#,
-            )
+        c.eval("function codes(s) { return Array.from({ length: s.length }, (_, i) => s.charCodeAt(i)); }")
             .unwrap();
-        assert_eq!(value, JsValue::Int(33));
 
-        let res = c.eval(
-            r#"
-            new Promise((_resolve, reject) => {
-                reject("Failed...");
-            })
-       "#,
-        );
+        let value = c.eval("'a\\uD800b\\uDC00\\uD83D\\uDE00'").unwrap();
+        let string = JsString::try_from(value.clone()).unwrap();
         assert_eq!(
-            res,
-            Err(ExecutionError::Exception(JsValue::String(
-                "Failed...".into()
-            )))
+            string.as_utf16(),
+            &[0x61, 0xD800, 0x62, 0xDC00, 0xD83D, 0xDE00]
         );
-    }
-
-    #[test]
-    fn test_call() {
-        let c = Context::new().unwrap();
-
         assert_eq!(
-            c.call_function("parseInt", vec!["22"]).unwrap(),
-            JsValue::Int(22),
+            c.call_function("codes", vec![value]).unwrap(),
+            JsValue::from(vec![0x61, 0xD800, 0x62, 0xDC00, 0xD83D, 0xDE00])
         );
-
-        c.eval(
-            r#"
-            function add(a, b) {
-                return a + b;
-            }
-        "#,
-        )
-        .unwrap();
+        let value = JsValue::from(JsString::from_utf16(vec![0xDBFF, 0x0]));
         assert_eq!(
-            c.call_function("add", vec![5, 7]).unwrap(),
-            JsValue::Int(12),
+            c.call_function("codes", vec![value]).unwrap(),
+            JsValue::from(vec![0xDBFF, 0])
         );
 
-        c.eval(
-            r#"
-            function sumArray(arr) {
-                let sum = 0;
-                for (const value of arr) {
-                    sum += value;
-                }
-                return sum;
-            }
-        "#,
-        )
-        .unwrap();
-        assert_eq!(
-            c.call_function("sumArray", vec![vec![1, 2, 3]]).unwrap(),
-            JsValue::Int(6),
-        );
+        // Strings with \0 are not truncated.
+        assert_eq!(c.eval("'a\\0b'"), Ok(JsValue::from("a\0b")));
 
-        c.eval(
-            r#"
-            function addObject(obj) {
-                let sum = 0;
-                for (const key of Object.keys(obj)) {
-                    sum += obj[key];
-                }
-                return sum;
+        // Strings are required for property names.
+        match c.eval("({ '\\uD800': 1 })") {
+            Err(ExecutionError::Conversion(ValueError::Custom(message))) => {
+                assert_eq!(message, "String \"\u{FFFD}\" is not valid Unicode")
             }
-        "#,
-        )
-        .unwrap();
-        let mut obj = std::collections::HashMap::<String, i32>::new();
-        obj.insert("a".into(), 10);
-        obj.insert("b".into(), 20);
-        obj.insert("c".into(), 30);
-        assert_eq!(
-            c.call_function("addObject", vec![obj]).unwrap(),
-            JsValue::Int(60),
-        );
+            other => panic!("unexpected result: {:?}", other),
+        }
     }
 
     #[test]
-    fn test_call_large_string() {
+    fn owned_string_ref() {
         let c = Context::new().unwrap();
-        c.eval(" function strLen(s) { return s.length; } ").unwrap();
 
-        let s = " ".repeat(200_000);
-        let v = c.call_function("strLen", vec![s]).unwrap();
-        assert_eq!(v, JsValue::Int(200_000));
+        let ascii = c.eval_owned("'a\\0b'.repeat(3)").unwrap();
+        assert_eq!(&*ascii.as_str_ref().unwrap(), "a\0ba\0ba\0b");
+        let unicode = c.eval_owned("'é😀'.repeat(2)").unwrap();
+        assert_eq!(unicode.as_str_ref().unwrap().to_string(), "é😀é😀");
+
+        assert_eq!(
+            c.eval_owned("1").unwrap().as_str_ref().map(|s| s.len()),
+            Err(ValueError::UnexpectedType)
+        );
+        assert!(c.eval_owned("'\\uD800'").unwrap().as_str_ref().is_err());
+
+        // The converted strings are freed.
+        c.assert_no_leaks(|| {
+            assert_eq!(unicode.as_str_ref().unwrap().len(), 12);
+        });
+
+        // The value keeps the context alive.
+        drop(c);
+        assert_eq!(ascii.as_str_ref().unwrap().len(), 9);
     }
 
     #[test]
-    fn call_async() {
-        let c = Context::new().unwrap();
-
-        c.eval(
-            r#"
-            function asyncOk() {
-                return new Promise((resolve, _) => {
-                    resolve(33);
-                });
-            }
+    fn callback_str_args() {
+        use std::{rc::Rc, sync::Mutex};
 
-            function asyncErr() {
-                return new Promise((_resolve, reject) => {
-                    reject("Failed...");
-                });
-            }
-        "#,
-        )
+        let c = Context::new().unwrap();
+        c.add_callback("shout", |s: JsStr| s.to_uppercase())
+            .unwrap();
+        c.add_callback("join", |a: JsStr, b: Option<JsStr>| match b {
+            Some(b) => format!("{}{}", a, b),
+            None => a.to_string(),
+        })
         .unwrap();
 
-        let value = c.call_function("asyncOk", vec![true]).unwrap();
-        assert_eq!(value, JsValue::Int(33));
+        assert_eq!(c.eval_as::<String>("shout('abc')"), Ok("ABC".to_string()));
+        assert_eq!(c.eval_as::<String>("shout('é😀')"), Ok("É😀".to_string()));
+        assert_eq!(c.eval_as::<String>("join('a', 'b')"), Ok("ab".to_string()));
+        assert_eq!(c.eval_as::<String>("join('a')"), Ok("a".to_string()));
+        assert!(c.eval("shout(1)").is_err());
+        assert!(c.eval("shout('\\uD800')").is_err());
 
-        let res = c.call_function("asyncErr", vec![true]);
-        assert_eq!(
-            res,
-            Err(ExecutionError::Exception(JsValue::String(
-                "Failed...".into()
-            )))
-        );
+        // The strings are freed after the call.
+        c.assert_no_leaks(|| {
+            c.eval("shout('abc'.repeat(100))").unwrap();
+        });
+
+        // Arguments kept by the callback keep their context alive.
+        let kept = Rc::new(Mutex::new(Vec::new()));
+        let keep = kept.clone();
+        c.add_callback("keep", move |s: JsStr<'static>| {
+            let mut kept = keep.lock().unwrap();
+            kept.push(s);
+            kept.len() as i32
+        })
+        .unwrap();
+        c.eval("keep('a'.repeat(3))").unwrap();
+        drop(c);
+        assert_eq!(&*kept.lock().unwrap()[0], "aaa");
     }
 
     #[test]
-    fn test_callback() {
+    fn interrupt_handler() {
+        use std::{
+            sync::atomic::{AtomicUsize, Ordering},
+            sync::Arc,
+        };
+
         let c = Context::new().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        c.set_interrupt_handler(move || counter.fetch_add(1, Ordering::Relaxed) >= 10);
 
-        c.add_callback("cb1", |flag: bool| !flag).unwrap();
-        assert_eq!(c.eval("cb1(true)").unwrap(), JsValue::Bool(false),);
+        assert_eq!(
+            c.eval("try { while (true) {} } catch (e) { 'caught' }"),
+            Err(ExecutionError::Interrupted)
+        );
+        assert_eq!(calls.load(Ordering::Relaxed), 11);
 
-        c.add_callback("concat2", |a: String, b: String| format!("{}{}", a, b))
-            .unwrap();
+        // The context is still usable afterwards.
+        c.set_interrupt_handler(|| false);
         assert_eq!(
-            c.eval(r#"concat2("abc", "def")"#).unwrap(),
-            JsValue::String("abcdef".into()),
+            c.eval("let i = 0; while (i < 100000) { i++; } i"),
+            Ok(JsValue::Int(100000))
+        );
+        assert_eq!(
+            c.eval("throw new Error('x')"),
+            Err(ExecutionError::Exception("Error: x".into()))
         );
 
-        c.add_callback("add2", |a: i32, b: i32| -> i32 { a + b })
-            .unwrap();
-        assert_eq!(c.eval("add2(5, 11)").unwrap(), JsValue::Int(16),);
+        c.set_interrupt_handler(|| panic!("handler panicked"));
+        assert_eq!(c.eval("while (true) {}"), Err(ExecutionError::Interrupted));
     }
 
     #[test]
-    fn test_callback_argn_variants() {
-        macro_rules! callback_argn_tests {
-            [
-                $(
-                    $len:literal : ( $( $argn:ident : $argv:literal ),* ),
-                )*
-            ] => {
-                $(
-                   {
-                       // Test plain return type.
-                        let name = format!("cb{}", $len);
-                        let c = Context::new().unwrap();
-                        c.add_callback(&name, | $( $argn : i32 ),*| -> i32 {
-                            $( $argn + )* 0
-                        }).unwrap();
-
-                        let code = format!("{}( {} )", name, "1,".repeat($len));
-                        let v = c.eval(&code).unwrap();
-                        assert_eq!(v, JsValue::Int($len));
-
-                        // Test Result<T, E> return type with OK(_) returns.
-                        let name = format!("cbres{}", $len);
-                        c.add_callback(&name, | $( $argn : i32 ),*| -> Result<i32, String> {
-                            Ok($( $argn + )* 0)
-                        }).unwrap();
-
-                        let code = format!("{}( {} )", name, "1,".repeat($len));
-                        let v = c.eval(&code).unwrap();
-                        assert_eq!(v, JsValue::Int($len));
-
-                        // Test Result<T, E> return type with Err(_) returns.
-                        let name = format!("cbreserr{}", $len);
-                        c.add_callback(&name, #[allow(unused_variables)] | $( $argn : i32 ),*| -> Result<i32, String> {
-                            Err("error".into())
-                        }).unwrap();
+    fn execution_timeout() {
+        let c = Context::builder()
+            .execution_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
 
-                        let code = format!("{}( {} )", name, "1,".repeat($len));
-                        let res = c.eval(&code);
-                        assert_eq!(res, Err(ExecutionError::Exception("error".into())));
-                   }
-                )*
-            }
-        }
+        assert_eq!(
+            c.eval("try { while (true) {} } catch (e) { 'caught' }"),
+            Err(ExecutionError::TimedOut)
+        );
 
-        callback_argn_tests![
-            1: (a : 1),
-        ]
+        // Each execution gets a fresh deadline.
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
+        c.eval("function spin() { while (true) {} }").unwrap();
+        assert_eq!(
+            c.call_function("spin", Vec::<JsValue>::new()),
+            Err(ExecutionError::TimedOut)
+        );
     }
 
     #[test]
-    fn test_callback_varargs() {
+    fn cancellation_handle() {
         let c = Context::new().unwrap();
+        let handle = c.cancellation_handle();
 
-        // No return.
-        c.add_callback("cb", |args: Arguments| {
-            let args = args.into_vec();
-            assert_eq!(
-                args,
-                vec![
-                    JsValue::String("hello".into()),
-                    JsValue::Bool(true),
-                    JsValue::from(100),
-                ]
-            );
-        })
-        .unwrap();
-        c.eval(" cb('hello', true, 100) ").unwrap();
+        // Cancelling while no script runs cancels the next execution once.
+        handle.cancel();
+        assert_eq!(c.eval("1 + 1"), Err(ExecutionError::Cancelled));
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
 
-        // With return.
-        c.add_callback("cb2", |args: Arguments| -> u32 {
-            let args = args.into_vec();
-            assert_eq!(
-                args,
-                vec![JsValue::from(1), JsValue::from(10), JsValue::from(100),]
-            );
-            111
-        })
-        .unwrap();
-        c.eval(
-            r#"
-           var x = cb2(1, 10, 100);
-           if (x !== 111) {
-            throw new Error('Expected 111, got ' + x);
-           }
-       "#,
-        )
+        // Cancelling from another thread, once the script has started.
+        let (started, receiver) = std::sync::mpsc::channel();
+        let started = std::sync::Mutex::new(started);
+        c.add_callback("started", move || started.lock().unwrap().send(()).is_ok())
+            .unwrap();
+        let remote = handle.clone();
+        let supervisor = std::thread::spawn(move || {
+            receiver.recv().unwrap();
+            remote.cancel();
+        });
+        assert_eq!(
+            c.eval("started(); try { while (true) {} } catch (e) { 'caught' }"),
+            Err(ExecutionError::Cancelled)
+        );
+        supervisor.join().unwrap();
+
+        // Cancelling from a callback cancels the outer execution.
+        c.add_callback("cancel", move || {
+            handle.cancel();
+            true
+        })
         .unwrap();
+        assert_eq!(
+            c.eval("cancel(); while (true) {}"),
+            Err(ExecutionError::Cancelled)
+        );
+        assert_eq!(c.eval("1 + 1"), Ok(JsValue::Int(2)));
     }
 
     #[test]
-    fn test_callback_invalid_argcount() {
+    fn fuel() {
         let c = Context::new().unwrap();
+        let limited = |fuel| EvalOptions {
+            max_instructions: Some(fuel),
+            ..Default::default()
+        };
 
-        c.add_callback("cb", |a: i32, b: i32| a + b).unwrap();
+        let metered = c.eval_metered("1 + 1", EvalOptions::default());
+        assert_eq!(metered.result, Ok(JsValue::Int(2)));
+        assert!(metered.fuel <= 1, "{}", metered.fuel);
+
+        let spin = "(() => { let i = 0; while (i < 1000000) { i++; } return i; })()";
+        let unlimited = c.eval_metered(spin, EvalOptions::default());
+        assert_eq!(unlimited.result, Ok(JsValue::Int(1000000)));
+        assert!(unlimited.fuel > 10, "{}", unlimited.fuel);
 
+        // The same code consumes about the same fuel.
+        let metered = c.eval_metered(spin, limited(unlimited.fuel + 1));
+        assert_eq!(metered.result, Ok(JsValue::Int(1000000)));
+        assert!(metered.fuel <= unlimited.fuel + 1);
+
+        let metered = c.eval_metered("try { while (true) {} } catch (e) { 'caught' }", limited(5));
+        assert_eq!(metered.result, Err(ExecutionError::OutOfFuel));
+        assert_eq!(metered.fuel, 6);
+
+        // Jobs run to resolve a returned promise are metered too.
         assert_eq!(
-            c.eval(" cb(5) "),
-            Err(ExecutionError::Exception(
-                "Invalid argument count: Expected 2, got 1".into()
-            )),
+            c.eval_with_options(
+                "Promise.resolve().then(() => { while (true) {} })",
+                limited(5)
+            ),
+            Err(ExecutionError::OutOfFuel)
         );
+
+        // The limit only applies to the evaluation.
+        assert_eq!(c.eval(spin), Ok(JsValue::Int(1000000)));
     }
 
     #[test]
-    fn memory_limit_exceeded() {
-        let c = Context::builder().memory_limit(100_000).build().unwrap();
-        assert_eq!(
-            c.eval("  'abc'.repeat(200_000) "),
-            Err(ExecutionError::OutOfMemory),
-        );
+    fn call_from_shallower_stack_frame() {
+        // Create the runtime deep in the stack, and use it from above.
+        fn deep_context(depth: usize) -> Context {
+            let padding = [0u8; 1024];
+            if depth == 0 {
+                Context::new().unwrap()
+            } else {
+                let context = deep_context(depth - 1);
+                std::hint::black_box(&padding);
+                context
+            }
+        }
+
+        let c = deep_context(64);
+        assert_eq!(c.eval("(() => 1 + 1)()"), Ok(JsValue::Int(2)));
     }
 
     #[test]
-    fn context_reset() {
+    fn owned_value_roundtrip() {
         let c = Context::new().unwrap();
-        c.eval(" var x = 123; ").unwrap();
-        c.add_callback("myCallback", || true).unwrap();
-
-        let c2 = c.reset().unwrap();
+        c.eval("function isSame(a, b) { return a === b; }").unwrap();
 
-        // Check it still works.
+        let object = c.eval_owned("globalThis.obj = { x: 1 }").unwrap();
+        assert_eq!(object.clone(), object);
         assert_eq!(
-            c2.eval_as::<String>(" 'abc'.repeat(2) ").unwrap(),
-            "abcabc".to_string(),
+            object.to_value(),
+            Ok(JsValue::Object(
+                vec![("x".to_string(), JsValue::Int(1))]
+                    .into_iter()
+                    .collect()
+            ))
         );
 
-        // Check old state is gone.
-        let err_msg = c2.eval(" x ").unwrap_err().to_string();
-        assert!(err_msg.contains("ReferenceError"));
+        let global = c.eval_owned("obj").unwrap();
+        assert_eq!(
+            c.call_function("isSame", vec![object, global]),
+            Ok(JsValue::Bool(true))
+        );
 
-        // Check callback is gone.
-        let err_msg = c2.eval(" myCallback() ").unwrap_err().to_string();
-        assert!(err_msg.contains("ReferenceError"));
+        let other = Context::new().unwrap();
+        let object = c.eval_owned("obj").unwrap();
+        assert!(other.call_function("isSame", vec![object]).is_err());
     }
 
-    #[inline(never)]
-    fn build_context() -> Context {
-        let ctx = Context::new().unwrap();
-        let name = "cb".to_string();
-        ctx.add_callback(&name, |a: String| a.repeat(2)).unwrap();
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        use serde::{Deserialize, Serialize};
 
-        let code = " function f(value) { return cb(value); } ".to_string();
-        ctx.eval(&code).unwrap();
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Rect { w: u32, h: u32 },
+        }
 
-        ctx
-    }
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Data {
+            name: String,
+            id: u64,
+            tags: Vec<String>,
+            parent: Option<i32>,
+            shapes: Vec<Shape>,
+        }
 
-    #[test]
-    fn moved_context() {
-        let c = build_context();
-        let v = c.call_function("f", vec!["test"]).unwrap();
-        assert_eq!(v, "testtest".into());
+        let data = Data {
+            name: "test".into(),
+            id: 5_000_000_000,
+            tags: vec!["a".into(), "b".into()],
+            parent: None,
+            shapes: vec![Shape::Empty, Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }],
+        };
 
-        let v = c.eval(" f('la') ").unwrap();
-        assert_eq!(v, "lala".into());
+        let c = Context::new().unwrap();
+        c.eval("function describe(d) { return JSON.stringify(d); }")
+            .unwrap();
+        c.eval("function identity(d) { return d; }").unwrap();
+
+        let value = to_js(&c, &data).unwrap();
+        assert_eq!(
+            c.call_function("describe", vec![value.clone()]),
+            Ok(JsValue::String(
+                r#"{"name":"test","id":5000000000,"tags":["a","b"],"parent":null,"shapes":["Empty",{"Circle":1.5},{"Rect":{"w":2,"h":3}}]}"#.into()
+            ))
+        );
+        assert_eq!(from_js::<Data>(&value), Ok(data));
+
+        let value = c
+            .eval_owned(
+                "({ name: 'js', id: 2 ** 40, tags: [], shapes: [{ Rect: { w: 4 / 2, h: 1 } }] })",
+            )
+            .unwrap();
+        let data = from_js::<Data>(&value).unwrap();
+        assert_eq!(data.id, 1 << 40);
+        assert_eq!(data.parent, None);
+        assert_eq!(data.shapes, [Shape::Rect { w: 2, h: 1 }]);
+
+        let value = c.eval_owned("({ name: 1 })").unwrap();
+        assert!(from_js::<Data>(&value).is_err());
     }
 
-    #[cfg(feature = "chrono")]
+    #[cfg(feature = "serde")]
     #[test]
-    fn chrono_serialize() {
-        let c = build_context();
-
-        c.eval(
-            "
-            function dateToTimestamp(date) {
-                return date.getTime();
-            }
-       ",
-        )
-        .unwrap();
+    fn serde_none_policy() {
+        let values = (None::<i32>, Some(1), ());
 
-        let now = chrono::Utc::now();
-        let now_millis = now.timestamp_millis();
+        let c = Context::new().unwrap();
+        let value = to_js(&c, &values).unwrap();
+        assert_eq!(
+            value.to_value(),
+            Ok(JsValue::Array(vec![
+                JsValue::Null,
+                JsValue::Int(1),
+                JsValue::Null
+            ]))
+        );
 
-        let timestamp = c
-            .call_function("dateToTimestamp", vec![JsValue::Date(now.clone())])
+        // Only `None` is affected by the policy, and both convert back.
+        let c = Context::builder()
+            .none_policy(NonePolicy::Undefined)
+            .build()
             .unwrap();
-
-        assert_eq!(timestamp, JsValue::Float(now_millis as f64));
+        let value = to_js(&c, &values).unwrap();
+        assert_eq!(
+            value.to_value(),
+            Ok(JsValue::Array(vec![
+                JsValue::Undefined,
+                JsValue::Int(1),
+                JsValue::Null
+            ]))
+        );
+        assert_eq!(
+            from_js::<(Option<i32>, Option<i32>, ())>(&value),
+            Ok(values)
+        );
+        let value = c.eval_owned("[null, undefined]").unwrap();
+        assert_eq!(from_js::<Vec<Option<i32>>>(&value), Ok(vec![None, None]));
     }
 
-    #[cfg(feature = "chrono")]
+    #[cfg(feature = "serde")]
     #[test]
-    fn chrono_deserialize() {
-        use chrono::offset::TimeZone;
+    fn serde_integer_policy() {
+        let big = (1i64 << 53) + 1;
+        let safe = JsValue::Float(9_007_199_254_740_991.0);
 
-        let c = build_context();
+        let c = Context::new().unwrap();
+        let value = to_js(&c, &big).unwrap();
+        assert_eq!(value.to_value(), Ok(JsValue::Float(2f64.powi(53))));
+        let value = to_js(&c, &(1i64 << 40)).unwrap();
+        assert_eq!(value.to_value(), Ok(JsValue::Float(2f64.powi(40))));
 
-        let value = c.eval(" new Date(1234567555) ").unwrap();
-        let datetime = chrono::Utc.timestamp_millis(1234567555);
+        match to_js_with(&c, &vec![0, big], IntegerPolicy::Error) {
+            Err(ValueError::Custom(message)) => assert_eq!(
+                message,
+                "Integer 9007199254740993 is outside of the safe integer range"
+            ),
+            other => panic!("unexpected result: {:?}", other.map(|v| v.to_value())),
+        }
+        let value = to_js_with(&c, &[-big, big], IntegerPolicy::Clamp).unwrap();
+        assert_eq!(
+            value.to_value(),
+            Ok(JsValue::Array(vec![
+                JsValue::Float(-9_007_199_254_740_991.0),
+                safe.clone()
+            ]))
+        );
+        let value = to_js_with(&c, &u128::MAX, IntegerPolicy::Clamp).unwrap();
+        assert_eq!(value.to_value(), Ok(safe));
+
+        // BigInts can not be created without the `bignum` feature.
+        #[cfg(not(any(feature = "bignum", feature = "ng")))]
+        assert_eq!(
+            to_js_with(&c, &big, IntegerPolicy::BigInt).map(|_| ()),
+            Err(ValueError::Custom(
+                "BigInt values require the bignum feature".into()
+            ))
+        );
 
-        assert_eq!(value, JsValue::Date(datetime));
+        // The policy of the context applies to nested values, and survives
+        // a reset.
+        #[cfg(feature = "bignum")]
+        {
+            let c = Context::builder()
+                .integer_policy(IntegerPolicy::BigInt)
+                .build()
+                .unwrap();
+            let c = c.reset().unwrap();
+            let map: HashMap<_, _> = vec![("id", u64::MAX)].into_iter().collect();
+            let value = to_js(&c, &map).unwrap();
+            c.eval("function id(x) { return typeof x.id + ' ' + x.id; }")
+                .unwrap();
+            assert_eq!(
+                c.call_function("id", vec![value]),
+                Ok(JsValue::from("bigint 18446744073709551615"))
+            );
+            let value = to_js(&c, &u128::MAX).unwrap();
+            assert_eq!(value.to_value(), Ok(JsValue::from(u128::MAX)));
+            assert_eq!(from_js::<i64>(&to_js(&c, &big).unwrap()), Ok(big));
+        }
     }
 
-    #[cfg(feature = "chrono")]
+    #[cfg(feature = "derive")]
     #[test]
-    fn chrono_roundtrip() {
-        let c = build_context();
+    fn derive_roundtrip() {
+        #[derive(IntoJsValue, FromJsValue, PartialEq, Debug)]
+        struct Point<T> {
+            x: T,
+            y: T,
+        }
 
-        c.eval(" function identity(x) { return x; } ").unwrap();
-        let d = chrono::Utc::now();
-        let td = JsValue::Date(d.clone());
-        let td2 = c.call_function("identity", vec![td.clone()]).unwrap();
-        let d2 = if let JsValue::Date(x) = td2 {
-            x
-        } else {
-            panic!("expected date")
+        #[derive(IntoJsValue, FromJsValue, PartialEq, Debug)]
+        struct Shape {
+            #[js(rename = "type")]
+            kind: String,
+            points: Vec<Point<f64>>,
+            label: Option<String>,
+            #[js(default)]
+            visible: bool,
+            #[js(skip)]
+            selected: bool,
+        }
+
+        let c = Context::new().unwrap();
+        c.eval("function identity(value) { return value; }")
+            .unwrap();
+
+        let shape = Shape {
+            kind: "line".into(),
+            points: vec![Point { x: 0.0, y: 1.5 }, Point { x: 2.0, y: 3.0 }],
+            label: Some("a".into()),
+            visible: true,
+            selected: true,
         };
+        let value = c
+            .call_function("identity", vec![JsValue::from(shape)])
+            .unwrap();
+        assert_eq!(
+            Shape::try_from(value),
+            Ok(Shape {
+                kind: "line".into(),
+                points: vec![Point { x: 0.0, y: 1.5 }, Point { x: 2.0, y: 3.0 }],
+                label: Some("a".into()),
+                visible: true,
+                selected: false,
+            })
+        );
 
-        assert_eq!(d.timestamp_millis(), d2.timestamp_millis());
-    }
+        // Optional and default properties may be missing or null.
+        assert_eq!(
+            c.eval_as::<Shape>("({ type: 'empty', points: [], visible: null, extra: 1 })"),
+            Ok(Shape {
+                kind: "empty".into(),
+                points: vec![],
+                label: None,
+                visible: false,
+                selected: false,
+            })
+        );
 
-    #[cfg(feature = "bigint")]
-    #[test]
-    fn test_bigint_deserialize_i64() {
-        for i in vec![0, std::i64::MAX, std::i64::MIN] {
-            let c = Context::new().unwrap();
-            let value = c.eval(&format!("{}n", i)).unwrap();
-            assert_eq!(value, JsValue::BigInt(i.into()));
+        // Errors name the property.
+        assert_eq!(
+            c.eval_as::<Shape>("({ points: [] })"),
+            Err(ExecutionError::Conversion(ValueError::Custom(
+                "Missing property 'type'".into()
+            )))
+        );
+        match c.eval_as::<Shape>("({ type: 'line', points: [{ x: 1 }] })") {
+            Err(ExecutionError::Conversion(ValueError::Custom(message))) => {
+                assert!(
+                    message.starts_with("Invalid property 'points'"),
+                    "{}",
+                    message
+                );
+                assert!(message.contains("Missing property 'y'"), "{}", message);
+            }
+            other => panic!("unexpected result: {:?}", other),
         }
+        assert_eq!(
+            c.eval_as::<Shape>("[]"),
+            Err(ExecutionError::Conversion(ValueError::UnexpectedType))
+        );
+
+        // Derived types can be used as callback arguments and results.
+        c.add_callback("flip", |point: Point<i32>| Point {
+            x: point.y,
+            y: point.x,
+        })
+        .unwrap();
+        assert_eq!(
+            c.eval_as::<Point<i32>>("flip({ x: 1, y: 2 })"),
+            Ok(Point { x: 2, y: 1 })
+        );
     }
 
-    #[cfg(feature = "bigint")]
+    #[cfg(feature = "derive")]
     #[test]
-    fn test_bigint_deserialize_bigint() {
-        for i in vec![
-            std::i64::MAX as i128 + 1,
-            std::i64::MIN as i128 - 1,
-            std::i128::MAX,
-            std::i128::MIN,
-        ] {
-            let c = Context::new().unwrap();
-            let value = c.eval(&format!("{}n", i)).unwrap();
-            let expected = num_bigint::BigInt::from(i);
-            assert_eq!(value, JsValue::BigInt(expected.into()));
+    fn derive_enums() {
+        #[derive(IntoJsValue, FromJsValue, PartialEq, Debug, Clone)]
+        enum External {
+            Unit,
+            #[js(rename = "new")]
+            Newtype(i32),
+            Tuple(String, bool),
+            Struct {
+                x: i32,
+                #[js(default)]
+                y: i32,
+            },
         }
-    }
 
-    #[cfg(feature = "bigint")]
-    #[test]
-    fn test_bigint_serialize_i64() {
-        for i in vec![0, std::i64::MAX, std::i64::MIN] {
-            let c = Context::new().unwrap();
-            c.eval(&format!(" function isEqual(x) {{ return x === {}n }} ", i))
-                .unwrap();
-            assert_eq!(
-                c.call_function("isEqual", vec![JsValue::BigInt(i.into())])
-                    .unwrap(),
-                JsValue::Bool(true)
-            );
+        #[derive(IntoJsValue, FromJsValue, PartialEq, Debug, Clone)]
+        #[js(tag = "kind")]
+        enum Internal {
+            Unit,
+            Struct {
+                #[js(rename = "value")]
+                x: i32,
+            },
+        }
+
+        #[derive(IntoJsValue, FromJsValue, PartialEq, Debug, Clone)]
+        #[js(untagged)]
+        enum Untagged {
+            Unit,
+            Int(i32),
+            Pair(i32, i32),
+            Named { name: String },
+            Text(String),
         }
+
+        #[derive(IntoJsValue, FromJsValue, PartialEq, Debug, Clone)]
+        enum Either<L, R> {
+            Left(L),
+            Right(R),
+        }
+
+        fn roundtrip<T>(c: &Context, value: T, js: &str)
+        where
+            T: Into<JsValue> + TryFrom<JsValue, Error = ValueError>,
+            T: Clone + PartialEq + std::fmt::Debug,
+        {
+            let expected = c.eval(&format!("({})", js)).unwrap();
+            assert_eq!(value.clone().into(), expected, "{}", js);
+            assert_eq!(T::try_from(expected), Ok(value), "{}", js);
+        }
+
+        let c = Context::new().unwrap();
+        roundtrip(&c, External::Unit, "'Unit'");
+        roundtrip(&c, External::Newtype(1), "{ new: 1 }");
+        roundtrip(
+            &c,
+            External::Tuple("a".into(), true),
+            "{ Tuple: ['a', true] }",
+        );
+        roundtrip(
+            &c,
+            External::Struct { x: 1, y: 2 },
+            "{ Struct: { x: 1, y: 2 } }",
+        );
+        assert_eq!(
+            c.eval_as::<External>("({ Struct: { x: 1 } })"),
+            Ok(External::Struct { x: 1, y: 0 })
+        );
+        assert_eq!(
+            c.eval_as::<External>("({ Unit: null })"),
+            Err(ExecutionError::Conversion(ValueError::UnexpectedType))
+        );
+        assert_eq!(
+            c.eval_as::<External>("({ Tuple: ['a'] })"),
+            Err(ExecutionError::Conversion(ValueError::UnexpectedType))
+        );
+        assert_eq!(
+            c.eval_as::<External>("'Other'"),
+            Err(ExecutionError::Conversion(ValueError::Custom(
+                "Unknown variant 'Other'".into()
+            )))
+        );
+        assert_eq!(
+            c.eval_as::<External>("({ Struct: {} })"),
+            Err(ExecutionError::Conversion(ValueError::Custom(
+                "Missing property 'x'".into()
+            )))
+        );
+
+        roundtrip(&c, Internal::Unit, "{ kind: 'Unit' }");
+        roundtrip(
+            &c,
+            Internal::Struct { x: 5 },
+            "{ kind: 'Struct', value: 5 }",
+        );
+        assert_eq!(
+            c.eval_as::<Internal>("({ value: 5 })"),
+            Err(ExecutionError::Conversion(ValueError::Custom(
+                "Missing property 'kind'".into()
+            )))
+        );
+        assert_eq!(
+            c.eval_as::<Internal>("({ kind: 'Other' })"),
+            Err(ExecutionError::Conversion(ValueError::Custom(
+                "Unknown variant 'Other'".into()
+            )))
+        );
+
+        // Untagged variants are tried in order.
+        roundtrip(&c, Untagged::Unit, "null");
+        roundtrip(&c, Untagged::Int(3), "3");
+        roundtrip(&c, Untagged::Pair(1, 2), "[1, 2]");
+        roundtrip(&c, Untagged::Named { name: "n".into() }, "{ name: 'n' }");
+        roundtrip(&c, Untagged::Text("t".into()), "'t'");
+        assert_eq!(
+            c.eval_as::<Untagged>("true"),
+            Err(ExecutionError::Conversion(ValueError::Custom(
+                "Value does not match any variant of Untagged".into()
+            )))
+        );
+
+        roundtrip(&c, Either::<i32, String>::Left(1), "{ Left: 1 }");
+        roundtrip(
+            &c,
+            Either::<i32, String>::Right("r".into()),
+            "{ Right: 'r' }",
+        );
     }
 
-    #[cfg(feature = "bigint")]
+    #[cfg(feature = "serde_json")]
     #[test]
-    fn test_bigint_serialize_bigint() {
-        for i in vec![
-            std::i64::MAX as i128 + 1,
-            std::i64::MIN as i128 - 1,
-            std::i128::MAX,
-            std::i128::MIN,
-        ] {
-            let c = Context::new().unwrap();
-            c.eval(&format!(" function isEqual(x) {{ return x === {}n }} ", i))
-                .unwrap();
-            let value = JsValue::BigInt(num_bigint::BigInt::from(i).into());
-            assert_eq!(
-                c.call_function("isEqual", vec![value]).unwrap(),
-                JsValue::Bool(true)
-            );
-        }
+    fn serde_json_value() {
+        use serde_json::json;
+
+        let c = Context::new().unwrap();
+        assert_eq!(
+            c.eval_as::<serde_json::Value>(
+                "({ a: [1, 2.5, 'x', null, undefined], b: { c: true }, d: new Date(0), e: NaN })"
+            ),
+            Ok(json!({
+                "a": [1, 2.5, "x", null, null],
+                "b": { "c": true },
+                "d": "1970-01-01T00:00:00.000Z",
+                "e": null,
+            }))
+        );
+
+        c.eval("function describe(x) { return JSON.stringify(x); }")
+            .unwrap();
+        let value = json!({ "list": [1, 5_000_000_000u64, "a", false] });
+        assert_eq!(
+            c.call_function("describe", vec![value]),
+            Ok(JsValue::String(
+                r#"{"list":[1,5000000000,"a",false]}"#.into()
+            ))
+        );
     }
 
     #[test]
     fn test_console() {
         use console::Level;
-        use std::sync::{Arc, Mutex};
+        use std::{rc::Rc, sync::Mutex};
 
-        let messages = Arc::new(Mutex::new(Vec::<(Level, Vec<JsValue>)>::new()));
+        let messages = Rc::new(Mutex::new(Vec::<(Level, Vec<JsValue>)>::new()));
 
         let m = messages.clone();
         let c = Context::builder()
@@ -932,6 +8214,7 @@ mod tests {
             r#"
             console.log("hi");
             console.error(false);
+            console.debug();
         "#,
         )
         .unwrap();
@@ -943,7 +8226,35 @@ mod tests {
             vec![
                 (Level::Log, vec![JsValue::from("hi")]),
                 (Level::Error, vec![JsValue::from(false)]),
+                (Level::Debug, vec![]),
             ]
         );
+
+        // A console is available by default.
+        let c = Context::new().unwrap();
+        assert_eq!(c.eval("console.warn('x')"), Ok(JsValue::Undefined));
+        assert_eq!(
+            c.eval("typeof __console_write"),
+            Ok(JsValue::from("undefined"))
+        );
+    }
+
+    #[test]
+    fn console_format() {
+        use console::format;
+
+        assert_eq!(format(vec![]), "");
+        assert_eq!(format(vec![JsValue::Int(1), JsValue::from("%s")]), "1 %s");
+        assert_eq!(
+            format(vec![
+                JsValue::from("%d%% %i %f %c%o %x %s"),
+                JsValue::from("12.5"),
+                JsValue::Float(-2.5),
+                JsValue::Int(3),
+                JsValue::from("color: red"),
+                JsValue::Bool(true),
+            ]),
+            "NaN% -2 3 true %x %s"
+        );
     }
 }
@@ -0,0 +1,538 @@
+//! ES module support.
+//! See the [ModuleLoader] trait for more info.
+//!
+//! Modules backed by Rust functions and values are registered with
+//! [Context::register_module](crate::Context::register_module), see
+//! [ModuleBuilder].
+//!
+//! With the `fs-loader` feature, [FsModuleLoader] loads modules from a
+//! directory, and with the `node-loader` feature, [NodeModuleLoader] also
+//! resolves packages in `node_modules`.
+//!
+//! Compiled modules can be cached with a [BytecodeCache].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    panic::RefUnwindSafe,
+    sync::{Arc, Mutex},
+};
+
+use crate::{Callback, CallbackArgs, ExecutionError, JsError, JsValue};
+
+#[cfg(feature = "fs-loader")]
+mod fs;
+#[cfg(feature = "fs-loader")]
+pub use fs::FsModuleLoader;
+#[cfg(feature = "node-loader")]
+mod node;
+#[cfg(feature = "node-loader")]
+pub use node::NodeModuleLoader;
+
+/// The source of a module, as returned by a [ModuleLoader].
+///
+/// JSON and binary modules have a single default export, and are imported
+/// like `import config from './config.json'`. The loader decides the kind of
+/// a module, eg by its extension: the embedded QuickJS versions do not parse
+/// import attributes like `with { type: "json" }`.
+#[derive(PartialEq, Clone, Debug)]
+pub enum ModuleSource {
+    /// Javascript source code.
+    Code(String),
+    /// JSON, which is parsed when the module is evaluated, and is the
+    /// default export of the module.
+    Json(String),
+    /// Binary data, which is the default export of the module as a
+    /// `Uint8Array`.
+    Bytes(Vec<u8>),
+    #[doc(hidden)]
+    __NonExhaustive,
+}
+
+impl From<String> for ModuleSource {
+    fn from(code: String) -> Self {
+        ModuleSource::Code(code)
+    }
+}
+
+impl<'a> From<&'a str> for ModuleSource {
+    fn from(code: &'a str) -> Self {
+        ModuleSource::Code(code.to_string())
+    }
+}
+
+/// A module loader resolves `import` statements in modules evaluated with
+/// [Context::eval_module](../struct.Context.html#method.eval_module).
+///
+/// The loader receives the module name that the import specifier resolves
+/// to with [resolve](ModuleLoader::resolve): by default, relative
+/// specifiers (`./x.js`, `../x.js`) are resolved against the name of the
+/// importing module, all other specifiers are passed through unchanged.
+/// Each name is only loaded once per context.
+///
+/// If the loader returns an error, the import fails with a JS
+/// `ReferenceError` containing the error message.
+///
+/// The loader also resolves dynamic `import()` expressions, including in
+/// scripts evaluated with [Context::eval](../struct.Context.html#method.eval),
+/// where relative specifiers are resolved against the file name of the
+/// script. `import()` returns a promise, and the module is loaded by a
+/// pending job of the runtime, so the loader is called once the event loop
+/// runs.
+///
+/// Note that any closure of type `Fn(&str) -> Result<ModuleSource, E>`
+/// implements this trait, where `E` implements `Display`.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue, module::ModuleSource};
+///
+/// let context = Context::new().unwrap();
+/// context.set_module_loader(|name: &str| match name {
+///     "math" => Ok(ModuleSource::from("export const double = x => x * 2;")),
+///     _ => Err(format!("unknown module {}", name)),
+/// });
+///
+/// context
+///     .eval_module("import { double } from 'math'; globalThis.x = double(21);", "main.js")
+///     .unwrap();
+/// assert_eq!(context.eval("x").unwrap(), JsValue::Int(42));
+/// ```
+pub trait ModuleLoader: std::panic::RefUnwindSafe + 'static {
+    /// Load the source of the module with the given name.
+    fn load(&self, name: &str) -> Result<ModuleSource, String>;
+
+    /// Resolve the import `specifier` of the module named `base` to the
+    /// name of the imported module.
+    ///
+    /// If this returns an error, the import fails with a JS
+    /// `ReferenceError`. The default implementation calls [resolve].
+    fn resolve(&self, base: &str, specifier: &str) -> Result<String, String> {
+        Ok(crate::module::resolve(base, specifier))
+    }
+
+    /// The properties of `import.meta` of the module with the given name.
+    ///
+    /// Called for modules loaded by this loader, and for modules evaluated
+    /// with [Context::eval_module](../struct.Context.html#method.eval_module)
+    /// in a runtime using this loader. By default, `import.meta.url` is the
+    /// name of the module.
+    ///
+    /// `import.meta.resolve(specifier)` is always available, and returns the
+    /// name the specifier resolves to when imported by the module, see
+    /// [resolve](ModuleLoader::resolve).
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue, module::{ImportMeta, ModuleLoader, ModuleSource}};
+    ///
+    /// struct Loader;
+    ///
+    /// impl ModuleLoader for Loader {
+    ///     fn load(&self, name: &str) -> Result<ModuleSource, String> {
+    ///         Err(format!("unknown module {}", name))
+    ///     }
+    ///
+    ///     fn import_meta(&self, name: &str) -> ImportMeta {
+    ///         ImportMeta::new(format!("file:///app/{}", name)).property("main", name == "src/main.js")
+    ///     }
+    /// }
+    ///
+    /// let context = Context::new().unwrap();
+    /// context.set_module_loader(Loader);
+    /// context
+    ///     .eval_module(
+    ///         "globalThis.meta = [import.meta.url, import.meta.main, import.meta.resolve('./lib.js')];",
+    ///         "src/main.js",
+    ///     )
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     context.eval("meta").unwrap(),
+    ///     JsValue::Array(vec![
+    ///         JsValue::from("file:///app/src/main.js"),
+    ///         JsValue::Bool(true),
+    ///         JsValue::from("src/lib.js"),
+    ///     ])
+    /// );
+    /// ```
+    fn import_meta(&self, name: &str) -> ImportMeta {
+        ImportMeta::new(name)
+    }
+}
+
+impl<F, E> ModuleLoader for F
+where
+    F: Fn(&str) -> Result<ModuleSource, E> + std::panic::RefUnwindSafe + 'static,
+    E: std::fmt::Display,
+{
+    fn load(&self, name: &str) -> Result<ModuleSource, String> {
+        (self)(name).map_err(|e| e.to_string())
+    }
+}
+
+/// The properties of `import.meta` of a module, as returned by
+/// [ModuleLoader::import_meta].
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct ImportMeta {
+    /// The value of `import.meta.url`, if any.
+    pub url: Option<String>,
+    /// Additional properties, set in order after `url` and `resolve`.
+    pub properties: Vec<(String, JsValue)>,
+}
+
+impl ImportMeta {
+    /// Create the properties of a module with the given `import.meta.url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: Some(url.into()),
+            properties: Vec::new(),
+        }
+    }
+
+    /// Add a property.
+    pub fn property(mut self, name: impl Into<String>, value: impl Into<JsValue>) -> Self {
+        self.properties.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// Resolve an import specifier against the name of the importing module,
+/// like QuickJS does by default before passing the name to the
+/// [ModuleLoader].
+///
+/// Relative specifiers (`./x.js`, `../x.js`) are resolved against the
+/// directory of `base`, all other specifiers are returned unchanged.
+///
+/// ```rust
+/// use quick_js::module::resolve;
+///
+/// assert_eq!(resolve("lib/a.js", "./b.js"), "lib/b.js");
+/// assert_eq!(resolve("lib/a.js", "../b.js"), "b.js");
+/// assert_eq!(resolve("lib/a.js", "b.js"), "b.js");
+/// ```
+pub fn resolve(base: &str, specifier: &str) -> String {
+    if !specifier.starts_with('.') {
+        return specifier.to_string();
+    }
+    let mut directory = match base.rfind('/') {
+        Some(index) => &base[..index],
+        None => "",
+    };
+    // Only leading `.` and `..` segments are normalized.
+    let mut rest = specifier;
+    loop {
+        if let Some(r) = rest.strip_prefix("./") {
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("../") {
+            if directory.is_empty() {
+                break;
+            }
+            let (parent, last) = match directory.rfind('/') {
+                Some(index) => (&directory[..index], &directory[index + 1..]),
+                None => ("", directory),
+            };
+            if last == "." || last == ".." {
+                break;
+            }
+            directory = parent;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+    if directory.is_empty() {
+        rest.to_string()
+    } else {
+        format!("{}/{}", directory, rest)
+    }
+}
+
+/// A cache for the bytecode of the modules returned by a [ModuleLoader], set
+/// with [Runtime::set_bytecode_cache](crate::Runtime::set_bytecode_cache).
+///
+/// Before compiling the source code of a module, the cache is asked for
+/// bytecode with the name of the module and the hash of its source code,
+/// which is stable across runs. If the cache has none, the module is
+/// compiled and its bytecode is added to the cache. The loader is still
+/// called for every module, but parsing is skipped, which speeds up cold
+/// starts with a persistent cache, or new contexts with a shared one.
+///
+/// The bytecode starts with the [version](crate::bytecode::version) of
+/// quick-js, and bytecode of other versions is compiled again, so persistent
+/// caches survive upgrades. Otherwise bytecode is not validated when it is
+/// read, so the cache must only return bytecode it was given.
+pub trait BytecodeCache: RefUnwindSafe + 'static {
+    /// The bytecode of the module with the given name and source hash.
+    fn get(&self, name: &str, hash: u64) -> Option<Vec<u8>>;
+
+    /// Add the bytecode of the module with the given name and source hash.
+    fn put(&self, name: &str, hash: u64, bytecode: Vec<u8>);
+}
+
+impl<C: BytecodeCache + ?Sized> BytecodeCache for Arc<C> {
+    fn get(&self, name: &str, hash: u64) -> Option<Vec<u8>> {
+        (**self).get(name, hash)
+    }
+
+    fn put(&self, name: &str, hash: u64, bytecode: Vec<u8>) {
+        (**self).put(name, hash, bytecode)
+    }
+}
+
+/// A [BytecodeCache] in memory, eg for sharing compiled modules between
+/// runtimes through an `Arc`.
+///
+/// Only the latest bytecode of each module is kept.
+#[derive(Debug, Default)]
+pub struct MemoryBytecodeCache {
+    modules: Mutex<HashMap<String, (u64, Vec<u8>)>>,
+}
+
+impl MemoryBytecodeCache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of cached modules.
+    pub fn len(&self) -> usize {
+        self.modules.lock().unwrap().len()
+    }
+
+    /// Whether no modules are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl BytecodeCache for MemoryBytecodeCache {
+    fn get(&self, name: &str, hash: u64) -> Option<Vec<u8>> {
+        match self.modules.lock().unwrap().get(name) {
+            Some((cached, bytecode)) if *cached == hash => Some(bytecode.clone()),
+            _ => None,
+        }
+    }
+
+    fn put(&self, name: &str, hash: u64, bytecode: Vec<u8>) {
+        let mut modules = self.modules.lock().unwrap();
+        modules.insert(name.to_string(), (hash, bytecode));
+    }
+}
+
+/// The 64 bit FNV-1a hash of the source code of a module, as passed to a
+/// [BytecodeCache].
+pub(crate) fn source_hash(code: &str) -> u64 {
+    code.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The state of a module in a [ModuleGraph].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ModuleState {
+    /// The module was loaded by the [ModuleLoader], or evaluated with
+    /// [Context::eval_module](crate::Context::eval_module).
+    Loaded,
+    /// The module is a native module, see [ModuleBuilder].
+    Native,
+    /// Loading or compiling the module failed. It is loaded again when it
+    /// is imported the next time.
+    Failed,
+}
+
+/// An import of a module in a [ModuleGraph].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ModuleImport {
+    /// The name of the importing module, or the file name of the script
+    /// for dynamic `import()` in scripts.
+    pub importer: String,
+    /// The specifier as written in the import.
+    pub specifier: String,
+    /// The name of the imported module, as resolved by
+    /// [ModuleLoader::resolve].
+    pub name: String,
+}
+
+/// The modules loaded in a context and their imports, as returned by
+/// [Context::module_graph](crate::Context::module_graph).
+///
+/// Each import is recorded once, even if it is resolved again, eg by a
+/// later dynamic `import()`.
+///
+/// ```rust
+/// use quick_js::{Context, module::{ModuleSource, ModuleState}};
+///
+/// let context = Context::new().unwrap();
+/// context.set_module_loader(|name: &str| match name {
+///     "lib/a.js" => Ok(ModuleSource::from("export { b } from './b.js';")),
+///     "lib/b.js" => Ok(ModuleSource::from("export const b = 1;")),
+///     _ => Err("not found"),
+/// });
+/// context.eval_module("import { b } from './lib/a.js';", "main.js").unwrap();
+///
+/// let graph = context.module_graph();
+/// assert_eq!(graph.state("lib/b.js"), Some(ModuleState::Loaded));
+/// assert_eq!(graph.dependencies("main.js"), vec!["lib/a.js"]);
+/// assert_eq!(graph.dependents("lib/b.js"), vec!["lib/a.js", "main.js"]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleGraph {
+    modules: BTreeMap<String, ModuleState>,
+    imports: Vec<ModuleImport>,
+}
+
+impl ModuleGraph {
+    /// The modules by name, in alphabetical order.
+    pub fn modules(&self) -> impl Iterator<Item = (&str, ModuleState)> {
+        self.modules
+            .iter()
+            .map(|(name, state)| (name.as_str(), *state))
+    }
+
+    /// The state of the module with the given name.
+    pub fn state(&self, name: &str) -> Option<ModuleState> {
+        self.modules.get(name).copied()
+    }
+
+    /// All imports, in the order they were first resolved.
+    pub fn imports(&self) -> &[ModuleImport] {
+        &self.imports
+    }
+
+    /// The names of the modules imported by the given module.
+    pub fn dependencies(&self, name: &str) -> Vec<&str> {
+        self.imports
+            .iter()
+            .filter(|import| import.importer == name)
+            .map(|import| import.name.as_str())
+            .collect()
+    }
+
+    /// The names of the modules that import the given module.
+    pub fn importers(&self, name: &str) -> Vec<&str> {
+        self.imports
+            .iter()
+            .filter(|import| import.name == name)
+            .map(|import| import.importer.as_str())
+            .collect()
+    }
+
+    /// The names of the modules that import the given module directly or
+    /// indirectly, in alphabetical order. These are the modules that are
+    /// affected by a change of the module.
+    pub fn dependents(&self, name: &str) -> Vec<&str> {
+        let mut dependents = std::collections::BTreeSet::new();
+        let mut pending = vec![name];
+        while let Some(name) = pending.pop() {
+            for importer in self.importers(name) {
+                if dependents.insert(importer) {
+                    pending.push(importer);
+                }
+            }
+        }
+        dependents.into_iter().collect()
+    }
+
+    /// Record the state of a module.
+    pub(crate) fn set_state(&mut self, name: &str, state: ModuleState) {
+        self.modules.insert(name.to_string(), state);
+    }
+
+    /// Forget a module and its imports.
+    pub(crate) fn remove(&mut self, name: &str) {
+        self.modules.remove(name);
+        self.imports.retain(|import| import.importer != name);
+    }
+
+    /// Record an import, unless it is already known.
+    pub(crate) fn add_import(&mut self, importer: &str, specifier: &str, name: &str) {
+        let known = self.imports.iter().any(|import| {
+            import.importer == importer && import.specifier == specifier && import.name == name
+        });
+        if !known {
+            self.imports.push(ModuleImport {
+                importer: importer.to_string(),
+                specifier: specifier.to_string(),
+                name: name.to_string(),
+            });
+        }
+    }
+}
+
+/// A function exported by a native module.
+pub(crate) type ExportedFunction =
+    Arc<dyn Fn(CallbackArgs<'_>) -> Result<JsValue, JsError> + RefUnwindSafe>;
+
+/// An export of a native module.
+pub(crate) enum Export {
+    Value(JsValue),
+    /// The function and its number of arguments.
+    Function(ExportedFunction, usize),
+    /// JSON, parsed when the module is evaluated.
+    Json(String),
+}
+
+/// Builder for the exports of a native module, which is backed by Rust
+/// functions and values instead of Javascript code.
+///
+/// Native modules are registered with
+/// [Context::register_module](crate::Context::register_module), and can be
+/// imported by modules evaluated in that context under the name they were
+/// registered with. They take precedence over the [ModuleLoader].
+///
+/// The exported values are converted to Javascript once, when the module is
+/// first imported. Functions are called like the callbacks of
+/// [Context::add_callback](crate::Context::add_callback).
+///
+/// ```rust
+/// use quick_js::{Context, JsValue};
+///
+/// let context = Context::new().unwrap();
+/// context
+///     .register_module("host:fs", |module| {
+///         module
+///             .export_function("readFile", |path: String| format!("contents of {}", path))
+///             .export("separator", "/");
+///     })
+///     .unwrap();
+///
+/// context
+///     .eval_module(
+///         "import { readFile, separator } from 'host:fs';
+///          globalThis.x = readFile(separator + 'a.txt');",
+///         "main.js",
+///     )
+///     .unwrap();
+/// assert_eq!(context.eval("x").unwrap(), JsValue::from("contents of /a.txt"));
+/// ```
+#[derive(Default)]
+pub struct ModuleBuilder {
+    pub(crate) exports: Vec<(String, Export)>,
+}
+
+impl ModuleBuilder {
+    /// Export a value.
+    pub fn export(&mut self, name: impl Into<String>, value: impl Into<JsValue>) -> &mut Self {
+        self.exports
+            .push((name.into(), Export::Value(value.into())));
+        self
+    }
+
+    /// Export a function backed by a Rust function or closure.
+    ///
+    /// Arguments and return values are converted like for
+    /// [Context::add_callback](crate::Context::add_callback).
+    pub fn export_function<F>(
+        &mut self,
+        name: impl Into<String>,
+        callback: impl Callback<F> + 'static,
+    ) -> &mut Self {
+        let argument_count = callback.argument_count();
+        let function = move |args: CallbackArgs<'_>| match callback.call(args) {
+            Ok(result) => result,
+            Err(e) => Err(ExecutionError::from(e).into()),
+        };
+        self.exports.push((
+            name.into(),
+            Export::Function(Arc::new(function), argument_count),
+        ));
+        self
+    }
+}
@@ -0,0 +1,214 @@
+//! Loading modules from the file system.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{ImportMeta, ModuleLoader, ModuleSource};
+
+/// The extensions that are tried by default.
+const DEFAULT_EXTENSIONS: &[&str] = &["js", "mjs", "json"];
+
+/// A [ModuleLoader] for the modules in a directory.
+///
+/// Module names are paths relative to the root directory, with `/` as the
+/// separator. Relative specifiers (`./x.js`, `../x.js`) are resolved against
+/// the importing module, all other specifiers against the root. If there is
+/// no file with the exact name, the name with each of the
+/// [extensions](FsModuleLoader::extensions) appended is tried, and then an
+/// `index` file in the directory with that name. The module name is the path
+/// of the file that was found, so relative imports of `lib/index.js` are
+/// resolved against `lib`.
+///
+/// Modules outside of the root can not be loaded: specifiers leaving the
+/// root and symbolic links pointing outside of it fail to resolve. Names
+/// without a matching file are passed on unchanged, so native modules
+/// registered with [Context::register_module](crate::Context::register_module)
+/// can still be imported.
+///
+/// Files ending in `.json` are loaded as JSON modules, all other files as
+/// Javascript. `import.meta.url` is the `file:` URL of the module.
+///
+/// Requires the `fs-loader` feature.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue, module::FsModuleLoader};
+///
+/// let root = std::env::temp_dir().join("quick-js-fs-loader-doc");
+/// std::fs::create_dir_all(root.join("lib")).unwrap();
+/// std::fs::write(root.join("lib/index.js"), "export { answer } from './answer';").unwrap();
+/// std::fs::write(root.join("lib/answer.js"), "export const answer = 42;").unwrap();
+///
+/// let context = Context::new().unwrap();
+/// context.set_module_loader(FsModuleLoader::new(&root).unwrap());
+/// context
+///     .eval_module("import { answer } from './lib'; globalThis.x = answer;", "main.js")
+///     .unwrap();
+/// assert_eq!(context.eval("x").unwrap(), JsValue::Int(42));
+///
+/// let err = context.eval_module("import '../secret.js';", "main.js").unwrap_err();
+/// assert!(err.to_string().contains("outside of the root"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct FsModuleLoader {
+    root: PathBuf,
+    extensions: Vec<String>,
+}
+
+impl FsModuleLoader {
+    /// Create a loader for the modules in the directory `root`.
+    ///
+    /// Fails if the directory does not exist.
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+        Ok(Self {
+            root,
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+        })
+    }
+
+    /// Set the extensions, without the leading `.`, that are tried in order
+    /// for names without a matching file. Defaults to `js`, `mjs` and `json`.
+    pub fn extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The canonical path of the root directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The path of a module name below the root.
+    ///
+    /// Fails if the name leaves the root, or has segments that are not file
+    /// names, like a drive prefix.
+    pub(super) fn path(&self, name: &str) -> Result<PathBuf, String> {
+        let mut segments = Vec::new();
+        for segment in name.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    if segments.pop().is_none() {
+                        return Err(format!("'{}' is outside of the root", name));
+                    }
+                }
+                segment => {
+                    let mut components = Path::new(segment).components();
+                    match (components.next(), components.next()) {
+                        (Some(Component::Normal(_)), None) => segments.push(segment),
+                        _ => return Err(format!("'{}' is not a valid module name", name)),
+                    }
+                }
+            }
+        }
+        Ok(segments
+            .into_iter()
+            .fold(self.root.clone(), |path, segment| path.join(segment)))
+    }
+
+    /// Find the file for a path below the root, trying the extensions and
+    /// index files.
+    pub(super) fn find(&self, path: &Path) -> Result<Option<PathBuf>, String> {
+        let with_extension = |path: &Path, extension: &str| {
+            let mut path = OsString::from(path);
+            path.push(".");
+            path.push(extension);
+            PathBuf::from(path)
+        };
+        let candidates = std::iter::once(path.to_path_buf())
+            .chain(self.extensions.iter().map(|e| with_extension(path, e)))
+            .chain(
+                self.extensions
+                    .iter()
+                    .map(|e| with_extension(&path.join("index"), e)),
+            );
+        for candidate in candidates {
+            if candidate.is_file() {
+                return self.canonicalize(&candidate).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Canonicalize a path, and check that it is below the root.
+    pub(super) fn canonicalize(&self, path: &Path) -> Result<PathBuf, String> {
+        let path = path.canonicalize().map_err(|e| e.to_string())?;
+        if !path.starts_with(&self.root) {
+            let name = path.display();
+            return Err(format!("'{}' is outside of the root", name));
+        }
+        Ok(path)
+    }
+
+    /// The module name of a canonical path below the root.
+    pub(super) fn name(&self, path: &Path) -> Result<String, String> {
+        let relative = path.strip_prefix(&self.root).map_err(|e| e.to_string())?;
+        let segments = relative
+            .components()
+            .map(|component| {
+                component
+                    .as_os_str()
+                    .to_str()
+                    .ok_or_else(|| format!("'{}' is not valid unicode", relative.display()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(segments.join("/"))
+    }
+}
+
+impl ModuleLoader for FsModuleLoader {
+    fn load(&self, name: &str) -> Result<ModuleSource, String> {
+        let path = self.canonicalize(&self.path(name)?)?;
+        let code = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        if path.extension() == Some(OsStr::new("json")) {
+            Ok(ModuleSource::Json(code))
+        } else {
+            Ok(ModuleSource::Code(code))
+        }
+    }
+
+    fn resolve(&self, base: &str, specifier: &str) -> Result<String, String> {
+        let relative = specifier.starts_with("./") || specifier.starts_with("../");
+        let name = super::resolve(base, specifier);
+        let path = match self.path(&name) {
+            Ok(path) => path,
+            // Other names are left to the loader, eg for native modules.
+            Err(_) if !relative => return Ok(name),
+            Err(e) => return Err(e),
+        };
+        match self.find(&path)? {
+            Some(file) => self.name(&file),
+            None => Ok(name),
+        }
+    }
+
+    fn import_meta(&self, name: &str) -> ImportMeta {
+        match self.path(name) {
+            Ok(path) => ImportMeta::new(file_url(&path)),
+            Err(_) => ImportMeta::new(name),
+        }
+    }
+}
+
+/// The `file:` URL of an absolute path.
+fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
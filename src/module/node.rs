@@ -0,0 +1,263 @@
+//! Resolving bare specifiers to packages in `node_modules`.
+
+use std::{fs, io, path::Path};
+
+use serde_json::Value;
+
+use super::{FsModuleLoader, ImportMeta, ModuleLoader, ModuleSource};
+
+/// The conditions that are matched by default.
+const DEFAULT_CONDITIONS: &[&str] = &["import", "module"];
+
+/// A [ModuleLoader] that resolves bare specifiers, like `lodash-es` or
+/// `@scope/pkg/utils.js`, to packages in `node_modules` directories, like
+/// Node.js does for ES modules.
+///
+/// The package is looked up in the `node_modules` directory next to the
+/// importing module, and then in those of its parent directories up to the
+/// root. The entry point of the package, or the file of a subpath, is taken
+/// from the `exports` of its `package.json`, including subpath patterns and
+/// conditional exports. Without `exports`, the `main` field, or the subpath
+/// is resolved like a relative import, trying the extensions and `index`
+/// files.
+///
+/// Other specifiers, and bare specifiers that match no package, are
+/// resolved like by [FsModuleLoader], so modules outside of the root can not
+/// be loaded either, and native modules registered with
+/// [Context::register_module](crate::Context::register_module) can still be
+/// imported.
+///
+/// Only ES modules can be loaded, CommonJS packages need to be bundled to
+/// ES modules first.
+///
+/// Requires the `node-loader` feature.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue, module::NodeModuleLoader};
+///
+/// let root = std::env::temp_dir().join("quick-js-node-loader-doc");
+/// let package = root.join("node_modules/greet");
+/// std::fs::create_dir_all(package.join("dist")).unwrap();
+/// std::fs::write(
+///     package.join("package.json"),
+///     r#"{ "name": "greet", "exports": { ".": "./dist/index.js" } }"#,
+/// )
+/// .unwrap();
+/// std::fs::write(
+///     package.join("dist/index.js"),
+///     "export const greet = name => `Hello ${name}`;",
+/// )
+/// .unwrap();
+///
+/// let context = Context::new().unwrap();
+/// context.set_module_loader(NodeModuleLoader::new(&root).unwrap());
+/// context
+///     .eval_module("import { greet } from 'greet'; globalThis.x = greet('you');", "main.js")
+///     .unwrap();
+/// assert_eq!(context.eval("x").unwrap(), JsValue::from("Hello you"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct NodeModuleLoader {
+    fs: FsModuleLoader,
+    conditions: Vec<String>,
+}
+
+impl NodeModuleLoader {
+    /// Create a loader for the modules in the directory `root`.
+    ///
+    /// Fails if the directory does not exist.
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            fs: FsModuleLoader::new(root)?,
+            conditions: DEFAULT_CONDITIONS.iter().map(|c| c.to_string()).collect(),
+        })
+    }
+
+    /// Set the conditions of conditional exports that are matched, in order
+    /// of preference. `default` is always matched last. Defaults to `import`
+    /// and `module`.
+    pub fn conditions<I, S>(mut self, conditions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.conditions = conditions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the extensions that are tried for names without a matching file,
+    /// see [FsModuleLoader::extensions].
+    pub fn extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fs = self.fs.extensions(extensions);
+        self
+    }
+
+    /// The canonical path of the root directory.
+    pub fn root(&self) -> &Path {
+        self.fs.root()
+    }
+
+    /// Resolve a bare specifier to a file in a package, or `None` if no
+    /// package matches.
+    fn resolve_package(&self, base: &str, specifier: &str) -> Result<Option<String>, String> {
+        // Scoped packages have two segments.
+        let segments = if specifier.starts_with('@') { 2 } else { 1 };
+        let mut parts = specifier.splitn(segments + 1, '/');
+        let package = parts.by_ref().take(segments).collect::<Vec<_>>().join("/");
+        let subpath = match parts.next() {
+            Some(rest) => format!("./{}", rest),
+            None => ".".to_string(),
+        };
+        if package.split('/').count() != segments || package.split('/').any(str::is_empty) {
+            return Ok(None);
+        }
+
+        // The directories of the importing module, innermost first.
+        let mut directories = base.split('/').collect::<Vec<_>>();
+        while directories.pop().is_some() {
+            if directories.last() == Some(&"node_modules") {
+                continue;
+            }
+            let mut name = directories.join("/");
+            if !name.is_empty() {
+                name.push('/');
+            }
+            name.push_str("node_modules/");
+            name.push_str(&package);
+            match self.fs.path(&name) {
+                Ok(path) if path.is_dir() => {
+                    return self.resolve_in_package(&name, &path, &subpath).map(Some);
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Resolve a subpath (`.` or `./x`) of the package with the given name
+    /// and path.
+    fn resolve_in_package(&self, name: &str, path: &Path, subpath: &str) -> Result<String, String> {
+        let manifest = match fs::read_to_string(path.join("package.json")) {
+            Ok(json) => serde_json::from_str::<Value>(&json)
+                .map_err(|e| format!("invalid package.json of '{}': {}", name, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Value::Null,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        if let Some(exports) = manifest.get("exports") {
+            return self.resolve_export(name, exports, subpath);
+        }
+        let target = match manifest.get("main").and_then(Value::as_str) {
+            Some(main) if subpath == "." => format!("./{}", main.trim_start_matches("./")),
+            None if subpath == "." => "./index".to_string(),
+            _ => subpath.to_string(),
+        };
+
+        let name = super::resolve(&format!("{}/package.json", name), &target);
+        let path = self.fs.path(&name)?;
+        match self.fs.find(&path)? {
+            Some(file) => self.fs.name(&file),
+            None => Err(format!("'{}' not found", name)),
+        }
+    }
+
+    /// Resolve a subpath of the package with the given name to the file
+    /// exported for it.
+    fn resolve_export(&self, name: &str, exports: &Value, subpath: &str) -> Result<String, String> {
+        let target = self
+            .exports_target(exports, subpath)
+            .ok_or_else(|| format!("'{}' is not exported by package '{}'", subpath, name))?;
+        // Targets must be files inside of the package.
+        let file = match target.strip_prefix("./") {
+            Some(file) if file.split('/').all(|s| !matches!(s, "" | "." | "..")) => file,
+            _ => return Err(format!("invalid target '{}' in package '{}'", target, name)),
+        };
+        let file = self.fs.path(&format!("{}/{}", name, file))?;
+        if !file.is_file() {
+            return Err(format!("'{}' not found in package '{}'", target, name));
+        }
+        self.fs.name(&self.fs.canonicalize(&file)?)
+    }
+
+    /// The target of a subpath in the `exports` of a package.
+    fn exports_target(&self, exports: &Value, subpath: &str) -> Option<String> {
+        // `exports` is either a map of subpaths, or the target of the main
+        // entry point.
+        let subpaths = match exports {
+            Value::Object(map) if map.keys().any(|key| key.starts_with('.')) => map,
+            target if subpath == "." => return self.target(target, None),
+            _ => return None,
+        };
+        if let Some(target) = subpaths.get(subpath) {
+            return self.target(target, None);
+        }
+
+        // Subpath patterns, where the longest prefix wins.
+        let mut best: Option<(&str, &str, &Value)> = None;
+        for (key, target) in subpaths {
+            let star = match key.find('*') {
+                Some(star) => star,
+                None => continue,
+            };
+            let (prefix, suffix) = (&key[..star], &key[star + 1..]);
+            let matches = subpath.len() >= prefix.len() + suffix.len()
+                && subpath.starts_with(prefix)
+                && subpath.ends_with(suffix);
+            let longer = match best {
+                Some((best_prefix, _, _)) => prefix.len() > best_prefix.len(),
+                None => true,
+            };
+            if matches && longer {
+                best = Some((prefix, suffix, target));
+            }
+        }
+        let (prefix, suffix, target) = best?;
+        let matched = &subpath[prefix.len()..subpath.len() - suffix.len()];
+        self.target(target, Some(matched))
+    }
+
+    /// Resolve a target of `exports`, with the part of the subpath matched
+    /// by a pattern.
+    fn target(&self, target: &Value, matched: Option<&str>) -> Option<String> {
+        match target {
+            Value::String(target) => Some(match matched {
+                Some(matched) => target.replace('*', matched),
+                None => target.clone(),
+            }),
+            Value::Array(targets) => targets.iter().find_map(|t| self.target(t, matched)),
+            Value::Object(conditions) => self
+                .conditions
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once("default"))
+                .find_map(|condition| conditions.get(condition))
+                .and_then(|target| self.target(target, matched)),
+            // `null` excludes a subpath.
+            _ => None,
+        }
+    }
+}
+
+impl ModuleLoader for NodeModuleLoader {
+    fn load(&self, name: &str) -> Result<ModuleSource, String> {
+        self.fs.load(name)
+    }
+
+    fn resolve(&self, base: &str, specifier: &str) -> Result<String, String> {
+        let bare = !specifier.starts_with('.') && !specifier.starts_with('/');
+        if bare {
+            if let Some(name) = self.resolve_package(base, specifier)? {
+                return Ok(name);
+            }
+        }
+        self.fs.resolve(base, specifier)
+    }
+
+    fn import_meta(&self, name: &str) -> ImportMeta {
+        self.fs.import_meta(name)
+    }
+}
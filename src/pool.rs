@@ -0,0 +1,190 @@
+//! Pools of pre-initialized contexts.
+//! See [ContextPool] for more info.
+
+use std::{cell::RefCell, ops::Deref};
+
+use crate::{snapshot::Snapshot, Context, ContextError, ExecutionError};
+
+type Factory = Box<dyn Fn() -> Result<Context, ContextError>>;
+type Setup = Box<dyn Fn(&Context) -> Result<(), ExecutionError>>;
+
+/// A pool of initialized contexts, eg for handling each request of a server
+/// in a clean context without paying for the initialization.
+///
+/// Contexts are handed out with [get](ContextPool::get) as a
+/// [PooledContext]. When it is dropped, the used context is discarded, and
+/// a fresh context is initialized in its place, so no global state leaks
+/// from one user to the next.
+///
+/// Like [Context], the pool can only be used from the thread it was created
+/// in.
+///
+/// ```rust
+/// use quick_js::{JsValue, pool::ContextPool};
+///
+/// let pool = ContextPool::builder(2)
+///     .setup(|context| {
+///         context.eval("var counter = 0; function count() { return ++counter; }")?;
+///         Ok(())
+///     })
+///     .build()
+///     .unwrap();
+///
+/// let context = pool.get().unwrap();
+/// assert_eq!(context.eval("count()"), Ok(JsValue::Int(1)));
+/// assert_eq!(context.eval("count()"), Ok(JsValue::Int(2)));
+/// drop(context);
+///
+/// // The counter was reset.
+/// let context = pool.get().unwrap();
+/// assert_eq!(context.eval("count()"), Ok(JsValue::Int(1)));
+/// ```
+pub struct ContextPool {
+    size: usize,
+    factory: Factory,
+    snapshot: Option<Snapshot>,
+    setup: Option<Setup>,
+    idle: RefCell<Vec<Context>>,
+}
+
+impl ContextPool {
+    /// Create a builder for a pool that keeps `size` contexts ready.
+    pub fn builder(size: usize) -> ContextPoolBuilder {
+        ContextPoolBuilder {
+            size,
+            factory: None,
+            snapshot: None,
+            setup: None,
+        }
+    }
+
+    /// Get an initialized context.
+    ///
+    /// If all contexts of the pool are in use, a new one is initialized.
+    pub fn get(&self) -> Result<PooledContext<'_>, ContextError> {
+        let context = self.idle.borrow_mut().pop();
+        let context = match context {
+            Some(context) => context,
+            None => self.initialize()?,
+        };
+        Ok(PooledContext {
+            pool: self,
+            context: Some(context),
+        })
+    }
+
+    /// The number of initialized contexts that are not in use.
+    pub fn idle(&self) -> usize {
+        self.idle.borrow().len()
+    }
+
+    /// Create and initialize a context.
+    fn initialize(&self) -> Result<Context, ContextError> {
+        let context = (self.factory)()?;
+        if let Some(snapshot) = &self.snapshot {
+            context
+                .load_snapshot(snapshot)
+                .map_err(ContextError::Execution)?;
+        }
+        if let Some(setup) = &self.setup {
+            setup(&context).map_err(ContextError::Execution)?;
+        }
+        Ok(context)
+    }
+
+    /// Replace a returned context with a fresh one.
+    fn release(&self, context: Context) {
+        drop(context);
+        if self.idle() >= self.size {
+            return;
+        }
+        // On failure, the context is initialized again by the next `get`,
+        // which can report the error.
+        if let Ok(context) = self.initialize() {
+            self.idle.borrow_mut().push(context);
+        }
+    }
+}
+
+/// A builder for [ContextPool].
+///
+/// Create with [ContextPool::builder].
+pub struct ContextPoolBuilder {
+    size: usize,
+    factory: Option<Factory>,
+    snapshot: Option<Snapshot>,
+    setup: Option<Setup>,
+}
+
+impl ContextPoolBuilder {
+    /// Create the contexts with the given function, eg to configure them
+    /// with a [ContextBuilder](crate::ContextBuilder).
+    ///
+    /// Callbacks used by the [snapshot](ContextPoolBuilder::snapshot) must be
+    /// added here. By default, [Context::new] is used.
+    pub fn context<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Result<Context, ContextError> + 'static,
+    {
+        self.factory = Some(Box::new(factory));
+        self
+    }
+
+    /// Load a [Snapshot] into each context.
+    pub fn snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Initialize each context with the given function, after loading the
+    /// snapshot.
+    pub fn setup<F>(mut self, setup: F) -> Self
+    where
+        F: Fn(&Context) -> Result<(), ExecutionError> + 'static,
+    {
+        self.setup = Some(Box::new(setup));
+        self
+    }
+
+    /// Finalize the builder, and initialize the contexts of the pool.
+    pub fn build(self) -> Result<ContextPool, ContextError> {
+        let pool = ContextPool {
+            size: self.size,
+            factory: self.factory.unwrap_or_else(|| Box::new(Context::new)),
+            snapshot: self.snapshot,
+            setup: self.setup,
+            idle: RefCell::new(Vec::new()),
+        };
+        for _ in 0..pool.size {
+            let context = pool.initialize()?;
+            pool.idle.borrow_mut().push(context);
+        }
+        Ok(pool)
+    }
+}
+
+/// A context borrowed from a [ContextPool].
+///
+/// Dereferences to [Context]. The context is discarded when this is
+/// dropped.
+pub struct PooledContext<'a> {
+    pool: &'a ContextPool,
+    context: Option<Context>,
+}
+
+impl Deref for PooledContext<'_> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        // Only taken in `drop`.
+        self.context.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledContext<'_> {
+    fn drop(&mut self) {
+        if let Some(context) = self.context.take() {
+            self.pool.release(context);
+        }
+    }
+}
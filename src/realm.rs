@@ -0,0 +1,147 @@
+//! Least privilege contexts for untrusted code.
+//! See [Realm] for more info.
+
+use std::ops::Deref;
+
+use crate::{Callback, Context, ContextError, ExecutionError, Runtime};
+
+type Setup = Box<dyn FnOnce(&Context) -> Result<(), ExecutionError>>;
+
+/// A context that only exposes the globals chosen by the embedder, eg for
+/// running untrusted plugins.
+///
+/// All global properties of a new context are removed, except for the
+/// allowed [intrinsics](RealmBuilder::intrinsic), like `Array` or `JSON`,
+/// and the host functions added with [callback](RealmBuilder::callback).
+/// Unless [allowed](RealmBuilder::allow_code_generation), `eval` and the
+/// `Function` constructor are disabled, so scripts can not generate code at
+/// runtime.
+///
+/// Removing a global does not remove the built-in itself, so objects created
+/// by literals, like `[]` or `{}`, still work. Rust methods that look up
+/// removed globals, like [Context::new_symbol] needing `Symbol`, fail.
+///
+/// A realm dereferences to its [Context]. To isolate the realms of different
+/// plugins from each other while sharing a [Runtime], see
+/// [Runtime::set_context_isolation].
+///
+/// ```rust
+/// use quick_js::{JsValue, realm::Realm};
+///
+/// let realm = Realm::builder()
+///     .intrinsics(&["Math", "JSON"])
+///     .callback("host.add", |a: i32, b: i32| a + b)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(realm.eval("Math.max(1, 2)"), Ok(JsValue::Int(2)));
+/// assert_eq!(realm.eval("host.add(1, 2)"), Ok(JsValue::Int(3)));
+/// assert_eq!(realm.eval("typeof Date"), Ok(JsValue::from("undefined")));
+/// assert!(realm.eval("[].map.constructor('return 1')()").is_err());
+/// ```
+pub struct Realm {
+    context: Context,
+}
+
+impl Realm {
+    /// Create a builder for a realm.
+    pub fn builder() -> RealmBuilder {
+        RealmBuilder {
+            runtime: None,
+            intrinsics: Vec::new(),
+            code_generation: false,
+            setup: Vec::new(),
+        }
+    }
+
+    /// Convert the realm into its context.
+    pub fn into_context(self) -> Context {
+        self.context
+    }
+}
+
+impl Deref for Realm {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        &self.context
+    }
+}
+
+/// A builder for [Realm].
+///
+/// Create with [Realm::builder].
+pub struct RealmBuilder {
+    runtime: Option<Runtime>,
+    intrinsics: Vec<String>,
+    code_generation: bool,
+    setup: Vec<Setup>,
+}
+
+impl RealmBuilder {
+    /// Create the realm in an existing [Runtime].
+    ///
+    /// By default, a new runtime is created.
+    pub fn runtime(mut self, runtime: &Runtime) -> Self {
+        self.runtime = Some(runtime.clone());
+        self
+    }
+
+    /// Keep a global property of the standard library, like `Object`,
+    /// `Promise` or `globalThis`.
+    ///
+    /// The `console` of the context can be kept too.
+    pub fn intrinsic(mut self, name: &str) -> Self {
+        self.intrinsics.push(name.to_string());
+        self
+    }
+
+    /// Keep several global properties, see
+    /// [intrinsic](RealmBuilder::intrinsic).
+    pub fn intrinsics(mut self, names: &[&str]) -> Self {
+        self.intrinsics
+            .extend(names.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /// Allow `eval` and the `Function` constructor. Disabled by default.
+    ///
+    /// Note that `eval` is only kept if it is an allowed
+    /// [intrinsic](RealmBuilder::intrinsic).
+    pub fn allow_code_generation(mut self, allow: bool) -> Self {
+        self.code_generation = allow;
+        self
+    }
+
+    /// Add a host function at a dot-separated path, like
+    /// [Context::add_callback_at].
+    pub fn callback<F>(mut self, path: &str, callback: impl Callback<F> + 'static) -> Self {
+        let path = path.to_string();
+        self.setup.push(Box::new(move |context| {
+            context.add_callback_at(&path, callback)
+        }));
+        self
+    }
+
+    /// Finalize the builder and build the realm.
+    pub fn build(self) -> Result<Realm, ContextError> {
+        let context = match self.runtime {
+            Some(runtime) => runtime.new_context()?,
+            None => Context::new()?,
+        };
+        if !self.code_generation {
+            context
+                .wrapper
+                .disable_code_generation()
+                .map_err(ContextError::Execution)?;
+        }
+        context
+            .wrapper
+            .restrict_globals(&self.intrinsics)
+            .map_err(ContextError::Execution)?;
+        for setup in self.setup {
+            setup(&context).map_err(ContextError::Execution)?;
+        }
+        Ok(Realm { context })
+    }
+}
@@ -0,0 +1,135 @@
+//! Fast context initialization from compiled scripts.
+//! See [Snapshot] for more info.
+
+use crate::{
+    bytecode::{self, take, take_len, BytecodeError},
+    Context, ExecutionError,
+};
+
+/// Identifies serialized snapshots.
+const MAGIC: &[u8] = b"quick-js snapshot\0";
+
+/// The initialization of a context, eg by a large prelude, that can be
+/// restored quickly in new contexts.
+///
+/// QuickJS can not serialize its heap, so unlike V8 snapshots, a snapshot
+/// stores the compiled bytecode of the scripts and modules that initialized
+/// a context. Loading it executes the bytecode again, skipping parsing and
+/// compilation, which usually dominates the startup time of large scripts.
+/// Since the scripts are executed again, side effects like reading the
+/// current time or calling callbacks are repeated, and callbacks and module
+/// loaders must be set up before loading the snapshot. The source code of
+/// functions is not stored, so their `toString()` does not return it.
+///
+/// A snapshot is recorded with a [SnapshotBuilder], and can be stored with
+/// [to_bytes](Snapshot::to_bytes).
+///
+/// ```rust
+/// use quick_js::{Context, JsValue, snapshot::Snapshot};
+///
+/// let setup = Context::new().unwrap();
+/// let mut builder = Snapshot::builder(&setup);
+/// builder.eval("function greet(name) { return 'Hello, ' + name; }", "prelude.js").unwrap();
+/// builder.eval_module("globalThis.answer = 42;", "config.js").unwrap();
+/// let snapshot = builder.build();
+///
+/// let context = Context::new().unwrap();
+/// context.load_snapshot(&snapshot).unwrap();
+/// assert_eq!(context.eval("greet('snapshot')"), Ok(JsValue::from("Hello, snapshot")));
+/// assert_eq!(context.eval("answer"), Ok(JsValue::Int(42)));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The bytecode of each recorded script or module, in order.
+    pub(crate) scripts: Vec<Vec<u8>>,
+}
+
+impl Snapshot {
+    /// Create a builder that records scripts evaluated in the given context.
+    pub fn builder(context: &Context) -> SnapshotBuilder<'_> {
+        SnapshotBuilder {
+            context,
+            scripts: Vec::new(),
+        }
+    }
+
+    /// Serialize the snapshot, eg for storing it in a file.
+    ///
+    /// The scripts are stored with the version header of the
+    /// [bytecode] module.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut scripts = (self.scripts.len() as u32).to_le_bytes().to_vec();
+        for script in &self.scripts {
+            scripts.extend_from_slice(&(script.len() as u32).to_le_bytes());
+            scripts.extend_from_slice(script);
+        }
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&bytecode::tag(&scripts));
+        bytes
+    }
+
+    /// Deserialize a snapshot created with [to_bytes](Snapshot::to_bytes).
+    ///
+    /// Fails with `BytecodeError::Invalid` if the bytes are not a snapshot,
+    /// and with `BytecodeError::VersionMismatch` if the snapshot was created
+    /// by a different [version](bytecode::version) of quick-js.
+    ///
+    /// # Safety
+    ///
+    /// QuickJS does not validate bytecode, so loading a malformed or
+    /// malicious snapshot can cause undefined behaviour. Only deserialize
+    /// snapshots from a trusted source, that were created with the same
+    /// features enabled.
+    pub unsafe fn from_bytes(bytes: &[u8]) -> Result<Self, BytecodeError> {
+        let tagged = bytes.strip_prefix(MAGIC).ok_or(BytecodeError::Invalid)?;
+        let mut rest = bytecode::untag(tagged)?;
+        let count = take_len(&mut rest).ok_or(BytecodeError::Invalid)?;
+        let mut scripts = Vec::new();
+        for _ in 0..count {
+            let len = take_len(&mut rest).ok_or(BytecodeError::Invalid)?;
+            scripts.push(take(&mut rest, len).ok_or(BytecodeError::Invalid)?.to_vec());
+        }
+        if !rest.is_empty() {
+            return Err(BytecodeError::Invalid);
+        }
+        Ok(Self { scripts })
+    }
+}
+
+/// Records the scripts and modules of a [Snapshot].
+///
+/// Created with [Snapshot::builder]. Each script is compiled and evaluated
+/// in the context of the builder, so it is fully initialized afterwards, and
+/// errors are reported while recording.
+pub struct SnapshotBuilder<'a> {
+    context: &'a Context,
+    scripts: Vec<Vec<u8>>,
+}
+
+impl<'a> SnapshotBuilder<'a> {
+    /// Evaluate a script, and record it. The `name` is used as the file name
+    /// in stack traces.
+    ///
+    /// Promises returned by the script are resolved like in
+    /// [Context::eval](crate::Context::eval).
+    pub fn eval(&mut self, code: &str, name: &str) -> Result<(), ExecutionError> {
+        let bytecode = self.context.wrapper.eval_recorded(code, name, false)?;
+        self.scripts.push(bytecode);
+        Ok(())
+    }
+
+    /// Evaluate an ES module, and record it. Its imports are not recorded,
+    /// and are loaded again when the snapshot is loaded.
+    pub fn eval_module(&mut self, code: &str, name: &str) -> Result<(), ExecutionError> {
+        let bytecode = self.context.wrapper.eval_recorded(code, name, true)?;
+        self.scripts.push(bytecode);
+        Ok(())
+    }
+
+    /// Finish recording.
+    pub fn build(self) -> Snapshot {
+        Snapshot {
+            scripts: self.scripts,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Clocks for `Date` and timers.
+//! See the [TimeSource] trait for more info.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// The clock of a context, used by `Date.now()` and `new Date()`, and for
+/// scheduling timers.
+///
+/// A time source is registered via the `ContextBuilder::time_source` method.
+/// By default, the system clock is used, and timers are scheduled on the
+/// monotonic clock. With a custom source, `Date` is replaced by a subclass of
+/// the native `Date` that asks the host for the current time, so
+/// `instanceof Date` and all methods keep working.
+///
+/// Timers are then scheduled in the time of the source, which is moved by
+/// the host: after it advanced, `Context::run_timers` runs the timers that
+/// are due.
+///
+/// Note that any closure of type `Fn() -> SystemTime` implements this trait.
+///
+/// See [ManualClock] for running scripts in virtual time.
+pub trait TimeSource: std::panic::RefUnwindSafe + 'static {
+    /// The current time.
+    fn now(&self) -> SystemTime;
+
+    /// Advance the clock to `deadline`, if it can.
+    ///
+    /// Called while awaiting a promise when nothing but a timer due at
+    /// `deadline` can make progress. The default implementation does
+    /// nothing, so blocking calls like `eval` fail, and futures stay pending
+    /// until the host advances the clock and calls `Context::run_timers`.
+    fn advance_to(&self, deadline: SystemTime) {
+        let _ = deadline;
+    }
+}
+
+impl<F> TimeSource for F
+where
+    F: Fn() -> SystemTime + std::panic::RefUnwindSafe + 'static,
+{
+    fn now(&self) -> SystemTime {
+        (self)()
+    }
+}
+
+/// The system clock.
+///
+/// Used by default, but timers are then scheduled on the monotonic clock.
+/// When it is set explicitly, timers are scheduled in system time, and
+/// awaiting a timer sleeps until it is due.
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn advance_to(&self, deadline: SystemTime) {
+        if let Ok(duration) = deadline.duration_since(self.now()) {
+            std::thread::sleep(duration);
+        }
+    }
+}
+
+/// A clock that only moves when it is changed by the host.
+///
+/// Clones share the same time, so a clone can be kept to control the clock
+/// of a context. While awaiting a promise that waits for a timer, the clock
+/// jumps to the time the timer is due instead of sleeping.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue, time::ManualClock};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(86400));
+/// let context = Context::builder()
+///     .time_source(clock.clone())
+///     .timers(true)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(context.eval("new Date().toISOString()"), Ok(JsValue::from("1970-01-02T00:00:00.000Z")));
+///
+/// // An hour passes instantly.
+/// let value = context.eval("new Promise(resolve => setTimeout(() => resolve(Date.now()), 3600 * 1000))");
+/// assert_eq!(value, Ok(JsValue::Float(90000.0 * 1000.0)));
+///
+/// clock.advance(Duration::from_secs(1));
+/// assert_eq!(context.eval("Date.now()"), Ok(JsValue::Float(90001.0 * 1000.0)));
+/// ```
+#[derive(Clone)]
+pub struct ManualClock {
+    time: Arc<Mutex<SystemTime>>,
+}
+
+impl ManualClock {
+    /// Create a clock starting at the given time.
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            time: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    /// Set the current time.
+    pub fn set(&self, time: SystemTime) {
+        *self.time.lock().unwrap() = time;
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, duration: std::time::Duration) {
+        *self.time.lock().unwrap() += duration;
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.time.lock().unwrap()
+    }
+
+    fn advance_to(&self, deadline: SystemTime) {
+        let mut time = self.time.lock().unwrap();
+        if *time < deadline {
+            *time = deadline;
+        }
+    }
+}
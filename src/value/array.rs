@@ -0,0 +1,143 @@
+use std::{convert::TryFrom, fmt};
+
+use crate::{
+    bindings::{OwnedObjectRef, PersistentValue},
+    ExecutionError, JsValue, OwnedJsValue, ValueError,
+};
+
+/// A handle to a Javascript array.
+///
+/// Unlike `JsValue::Array`, the array is not copied into a `Vec`. Elements
+/// are only converted when they are accessed, which allows processing large
+/// arrays one element at a time, or only looking at a part of them.
+///
+/// Arrays can be converted from an [OwnedJsValue], for example the result
+/// of [Context::eval_owned](crate::Context::eval_owned). The handle can be
+/// passed back to Javascript by converting it into a [JsValue].
+///
+/// ```rust
+/// use quick_js::{Context, JsArray, JsValue};
+/// use std::convert::TryFrom;
+///
+/// let context = Context::new().unwrap();
+///
+/// let array = context.eval_owned("Array.from({ length: 1000 }, (_, i) => i)").unwrap();
+/// let array = JsArray::try_from(array).unwrap();
+/// assert_eq!(array.len(), Ok(1000));
+/// assert_eq!(array.get(999), Ok(JsValue::Int(999)));
+///
+/// array.set(0, "first").unwrap();
+/// let first: Vec<_> = array.iter().unwrap().take(2).collect();
+/// assert_eq!(first, vec![Ok(JsValue::from("first")), Ok(JsValue::Int(1))]);
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct JsArray {
+    array: PersistentValue,
+}
+
+impl JsArray {
+    fn object_ref(&self) -> OwnedObjectRef<'_> {
+        // Only arrays are stored in a JsArray.
+        OwnedObjectRef::new(self.array.as_value_ref()).unwrap()
+    }
+
+    /// Get the length of the array.
+    pub fn len(&self) -> Result<usize, ExecutionError> {
+        Ok(self.object_ref().array_length()? as usize)
+    }
+
+    /// Check if the array is empty.
+    pub fn is_empty(&self) -> Result<bool, ExecutionError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Get the element at the given index.
+    ///
+    /// Indexes out of bounds return `JsValue::Undefined`.
+    pub fn get(&self, index: usize) -> Result<JsValue, ExecutionError> {
+        match u32::try_from(index) {
+            Ok(index) => self.object_ref().get_index(index),
+            Err(_) => Ok(JsValue::Undefined),
+        }
+    }
+
+    /// Set the element at the given index.
+    ///
+    /// Setting an index out of bounds extends the array.
+    pub fn set(&self, index: usize, value: impl Into<JsValue>) -> Result<(), ExecutionError> {
+        let index = u32::try_from(index)
+            .map_err(|_| ExecutionError::Internal("Array index out of range".into()))?;
+        self.object_ref().set_index(index, value.into())
+    }
+
+    /// Iterate over the elements of the array.
+    ///
+    /// The length is determined when the iterator is created, and each
+    /// element is converted when it is reached.
+    pub fn iter(&self) -> Result<JsArrayIter<'_>, ExecutionError> {
+        Ok(JsArrayIter {
+            array: self,
+            index: 0,
+            len: self.len()?,
+        })
+    }
+}
+
+impl fmt::Debug for JsArray {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("JsArray")
+    }
+}
+
+impl TryFrom<OwnedJsValue> for JsArray {
+    type Error = ValueError;
+
+    fn try_from(value: OwnedJsValue) -> Result<Self, ValueError> {
+        let is_array = OwnedObjectRef::new(value.persistent().as_value_ref())
+            .map(|object| object.is_array())
+            .unwrap_or(false);
+        if is_array {
+            Ok(JsArray {
+                array: value.persistent().clone(),
+            })
+        } else {
+            Err(ValueError::UnexpectedType)
+        }
+    }
+}
+
+impl From<JsArray> for JsValue {
+    fn from(array: JsArray) -> Self {
+        JsValue::Ref(OwnedJsValue::new(array.array))
+    }
+}
+
+/// Iterator over the elements of a [JsArray].
+///
+/// Created by [JsArray::iter].
+pub struct JsArrayIter<'a> {
+    array: &'a JsArray,
+    index: usize,
+    len: usize,
+}
+
+impl<'a> Iterator for JsArrayIter<'a> {
+    type Item = Result<JsValue, ExecutionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.len {
+            let value = self.array.get(self.index);
+            self.index += 1;
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for JsArrayIter<'a> {}
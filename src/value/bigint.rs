@@ -1,8 +1,18 @@
+use std::convert::TryFrom;
+
+#[cfg(feature = "bigint")]
 use num_traits::cast::ToPrimitive;
 
 #[derive(Clone, Debug)]
 pub enum BigIntOrI64 {
     Int(i64),
+    /// Values outside of the i64 range, if num-bigint is not available.
+    #[cfg(not(feature = "bigint"))]
+    Int128(i128),
+    /// Values above the i128 range, if num-bigint is not available.
+    #[cfg(not(feature = "bigint"))]
+    UInt128(u128),
+    #[cfg(feature = "bigint")]
     BigInt(num_bigint::BigInt),
 }
 
@@ -11,7 +21,17 @@ impl PartialEq for BigIntOrI64 {
         use BigIntOrI64::*;
         match (&self, &other) {
             (Int(i), Int(j)) => i == j,
+            #[cfg(not(feature = "bigint"))]
+            (Int(i), Int128(j)) | (Int128(j), Int(i)) => i128::from(*i) == *j,
+            #[cfg(not(feature = "bigint"))]
+            (Int128(i), Int128(j)) => i == j,
+            #[cfg(not(feature = "bigint"))]
+            (UInt128(i), UInt128(j)) => i == j,
+            #[cfg(not(feature = "bigint"))]
+            (UInt128(_), _) | (_, UInt128(_)) => false,
+            #[cfg(feature = "bigint")]
             (Int(i), BigInt(b)) | (BigInt(b), Int(i)) => b == &num_bigint::BigInt::from(*i),
+            #[cfg(feature = "bigint")]
             (BigInt(a), BigInt(b)) => a == b,
         }
     }
@@ -21,20 +41,89 @@ impl Eq for BigIntOrI64 {}
 
 /// A value holding JavaScript
 /// [BigInt](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/BigInt) type
+///
+/// Without the `bigint` feature, values are limited to the `i128` and `u128`
+/// ranges.
+/// With the `bigint` feature, arbitrary precision is supported via
+/// `num_bigint::BigInt`.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BigInt {
     pub(crate) inner: BigIntOrI64,
 }
 
 impl BigInt {
+    /// Parse a BigInt from its decimal string representation.
+    ///
+    /// Returns `None` if the string is invalid, or if the value does not fit
+    /// into an `i128` or `u128` without the `bigint` feature.
+    pub(crate) fn parse_decimal(value: &str) -> Option<Self> {
+        if let Ok(int) = value.parse::<i64>() {
+            return Some(int.into());
+        }
+        #[cfg(feature = "bigint")]
+        let inner = num_bigint::BigInt::parse_bytes(value.as_bytes(), 10).map(BigIntOrI64::BigInt);
+        #[cfg(not(feature = "bigint"))]
+        let inner = match value.parse::<i128>() {
+            Ok(int) => Some(BigIntOrI64::Int128(int)),
+            Err(_) => value.parse::<u128>().ok().map(BigIntOrI64::UInt128),
+        };
+        inner.map(|inner| BigInt { inner })
+    }
+
     /// Return `Some` if value fits into `i64` and `None` otherwise
     pub fn as_i64(&self) -> Option<i64> {
         match &self.inner {
             BigIntOrI64::Int(int) => Some(*int),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::Int128(int) => i64::try_from(*int).ok(),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::UInt128(_) => None,
+            #[cfg(feature = "bigint")]
             BigIntOrI64::BigInt(bigint) => bigint.to_i64(),
         }
     }
+
+    /// Return `Some` if value fits into `u64` and `None` otherwise
+    pub fn as_u64(&self) -> Option<u64> {
+        match &self.inner {
+            BigIntOrI64::Int(int) => u64::try_from(*int).ok(),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::Int128(int) => u64::try_from(*int).ok(),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::UInt128(_) => None,
+            #[cfg(feature = "bigint")]
+            BigIntOrI64::BigInt(bigint) => bigint.to_u64(),
+        }
+    }
+
+    /// Return `Some` if value fits into `i128` and `None` otherwise
+    pub fn as_i128(&self) -> Option<i128> {
+        match &self.inner {
+            BigIntOrI64::Int(int) => Some(i128::from(*int)),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::Int128(int) => Some(*int),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::UInt128(_) => None,
+            #[cfg(feature = "bigint")]
+            BigIntOrI64::BigInt(bigint) => bigint.to_i128(),
+        }
+    }
+
+    /// Return `Some` if value fits into `u128` and `None` otherwise
+    pub fn as_u128(&self) -> Option<u128> {
+        match &self.inner {
+            BigIntOrI64::Int(int) => u128::try_from(*int).ok(),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::Int128(int) => u128::try_from(*int).ok(),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::UInt128(int) => Some(*int),
+            #[cfg(feature = "bigint")]
+            BigIntOrI64::BigInt(bigint) => bigint.to_u128(),
+        }
+    }
+
     /// Convert value into `num_bigint::BigInt`
+    #[cfg(feature = "bigint")]
     pub fn into_bigint(self) -> num_bigint::BigInt {
         match self.inner {
             BigIntOrI64::Int(int) => int.into(),
@@ -47,6 +136,11 @@ impl std::fmt::Display for BigInt {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self.inner {
             BigIntOrI64::Int(i) => write!(f, "{}", i),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::Int128(i) => write!(f, "{}", i),
+            #[cfg(not(feature = "bigint"))]
+            BigIntOrI64::UInt128(i) => write!(f, "{}", i),
+            #[cfg(feature = "bigint")]
             BigIntOrI64::BigInt(ref i) => write!(f, "{}", i),
         }
     }
@@ -60,6 +154,39 @@ impl From<i64> for BigInt {
     }
 }
 
+impl From<u64> for BigInt {
+    fn from(int: u64) -> Self {
+        i128::from(int).into()
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(int: i128) -> Self {
+        if let Ok(int) = i64::try_from(int) {
+            return int.into();
+        }
+        #[cfg(feature = "bigint")]
+        let inner = BigIntOrI64::BigInt(int.into());
+        #[cfg(not(feature = "bigint"))]
+        let inner = BigIntOrI64::Int128(int);
+        BigInt { inner }
+    }
+}
+
+impl From<u128> for BigInt {
+    fn from(int: u128) -> Self {
+        if let Ok(int) = i128::try_from(int) {
+            return int.into();
+        }
+        #[cfg(feature = "bigint")]
+        let inner = BigIntOrI64::BigInt(int.into());
+        #[cfg(not(feature = "bigint"))]
+        let inner = BigIntOrI64::UInt128(int);
+        BigInt { inner }
+    }
+}
+
+#[cfg(feature = "bigint")]
 impl From<num_bigint::BigInt> for BigInt {
     fn from(bigint: num_bigint::BigInt) -> Self {
         BigInt {
@@ -81,6 +208,7 @@ mod tests {
         assert_eq!(value.as_i64(), Some(1234i64));
     }
 
+    #[cfg(feature = "bigint")]
     #[test]
     fn test_bigint_as_i64_overflow() {
         let value = BigInt {
@@ -89,6 +217,7 @@ mod tests {
         assert_eq!(value.as_i64(), None);
     }
 
+    #[cfg(feature = "bigint")]
     #[test]
     fn test_bigint_into_bigint() {
         for i in vec![
@@ -104,4 +233,31 @@ mod tests {
             assert_eq!(value.into_bigint(), num_bigint::BigInt::from(i));
         }
     }
+
+    #[test]
+    fn test_bigint_i128() {
+        for i in [
+            0,
+            i64::MAX as i128 + 1,
+            i64::MIN as i128 - 1,
+            i128::MAX,
+            i128::MIN,
+        ] {
+            let value = BigInt::from(i);
+            assert_eq!(value.as_i128(), Some(i));
+            assert_eq!(BigInt::parse_decimal(&value.to_string()), Some(value));
+        }
+        assert_eq!(BigInt::from(u64::MAX).as_u64(), Some(u64::MAX));
+        for i in [0, u128::MAX >> 1, (u128::MAX >> 1) + 1, u128::MAX] {
+            let value = BigInt::from(i);
+            assert_eq!(value.as_u128(), Some(i));
+            assert_eq!(value.to_string(), i.to_string());
+            assert_eq!(BigInt::parse_decimal(&value.to_string()), Some(value));
+        }
+        assert_eq!(BigInt::from(u128::MAX).as_i128(), None);
+        assert_eq!(BigInt::from(u128::MAX).as_u64(), None);
+        assert_ne!(BigInt::from(u128::MAX), BigInt::from(-1i64));
+        assert_eq!(BigInt::from(-1i64).as_u128(), None);
+        assert_eq!(BigInt::parse_decimal("abc"), None);
+    }
 }
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use crate::{bindings::PersistentValue, ExecutionError, JsValue};
+
+/// A handle to a Javascript function.
+///
+/// Functions are returned as `JsValue::Function`, both as top-level results
+/// and as nested values, for example in object properties. They can be
+/// called from Rust, and passed back to Javascript.
+///
+/// The handle keeps the context it belongs to alive, even if the
+/// [Context](crate::Context) itself is dropped.
+///
+/// ```rust
+/// use quick_js::{Context, JsFunction, JsValue};
+///
+/// let context = Context::new().unwrap();
+///
+/// let add: JsFunction = context.eval_as("(a, b) => a + b").unwrap();
+/// assert_eq!(add.call(vec![1, 2]), Ok(JsValue::Int(3)));
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct JsFunction {
+    function: PersistentValue,
+}
+
+impl JsFunction {
+    pub(crate) fn new(function: PersistentValue) -> Self {
+        Self { function }
+    }
+
+    pub(crate) fn persistent(&self) -> &PersistentValue {
+        &self.function
+    }
+
+    /// Call the function with the given arguments.
+    ///
+    /// `this` is `null` during the call. Like with
+    /// [Context::call_function](crate::Context::call_function), a returned
+    /// promise is resolved by running the event loop.
+    pub fn call(
+        &self,
+        args: impl IntoIterator<Item = impl Into<JsValue>>,
+    ) -> Result<JsValue, ExecutionError> {
+        let context = self.function.context();
+        let qargs = args
+            .into_iter()
+            .map(|arg| context.serialize_value(arg.into()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let value = context
+            .call_function(self.function.as_value_ref(), qargs)?
+            .to_value()?;
+        Ok(value)
+    }
+}
+
+impl fmt::Debug for JsFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("JsFunction")
+    }
+}
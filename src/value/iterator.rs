@@ -0,0 +1,95 @@
+use std::fmt;
+
+use crate::{bindings::PersistentValue, ExecutionError, JsValue, OwnedJsValue};
+
+/// A Javascript iterator, driven from Rust.
+///
+/// Created by [OwnedJsValue::iter] from any iterable, like an array, a
+/// `Map`, or a generator. Values are only produced when they are requested,
+/// so large or infinite sequences can be processed one value at a time.
+///
+/// If the Javascript iterator throws, the error is returned as the next
+/// item, and the iterator is done afterwards. Dropping an iterator that is
+/// not done closes it by calling its `return` method, like breaking out of a
+/// `for...of` loop does, which runs the `finally` blocks of generators.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue};
+///
+/// let context = Context::new().unwrap();
+/// let naturals = context
+///     .eval_owned("(function* () { for (let i = 0; ; i++) yield i; })()")
+///     .unwrap();
+///
+/// let even: Vec<_> = naturals
+///     .iter()
+///     .unwrap()
+///     .filter_map(Result::ok)
+///     .filter(|value| matches!(value, JsValue::Int(i) if i % 2 == 0))
+///     .take(3)
+///     .collect();
+/// assert_eq!(even, vec![JsValue::Int(0), JsValue::Int(2), JsValue::Int(4)]);
+/// ```
+pub struct JsIterator {
+    /// The function stepping the iterator, see `ContextWrapper::open_iterator`.
+    stepper: PersistentValue,
+    done: bool,
+}
+
+impl JsIterator {
+    fn step(&mut self, close: bool) -> Result<Option<JsValue>, ExecutionError> {
+        let context = self.stepper.context();
+        context.step_iterator(self.stepper.as_value_ref(), close)
+    }
+}
+
+impl Iterator for JsIterator {
+    type Item = Result<JsValue, ExecutionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.step(false) {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl Drop for JsIterator {
+    fn drop(&mut self) {
+        if !self.done {
+            // Errors of `return` can not be reported.
+            let _ = self.step(true);
+        }
+    }
+}
+
+impl fmt::Debug for JsIterator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("JsIterator")
+    }
+}
+
+impl OwnedJsValue {
+    /// Iterate over the values of an iterable, with the Javascript iterator
+    /// protocol. See [JsIterator].
+    ///
+    /// Fails if the value is not iterable.
+    pub fn iter(&self) -> Result<JsIterator, ExecutionError> {
+        let context = self.persistent().context();
+        let stepper = context.open_iterator(&self.persistent().as_value_ref())?;
+        Ok(JsIterator {
+            stepper: PersistentValue::from_ref(stepper),
+            done: false,
+        })
+    }
+}
@@ -1,36 +1,150 @@
-#[cfg(feature = "bigint")]
+mod array;
 pub(crate) mod bigint;
+mod function;
+mod iterator;
+mod object;
+mod owned;
+mod promise;
+#[cfg(feature = "atomics")]
+mod shared;
+#[cfg(feature = "futures")]
+mod stream;
+mod string;
+mod symbol;
 
 use std::convert::{TryFrom, TryInto};
-use std::{collections::HashMap, error, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    error, fmt,
+    hash::Hash,
+};
 
-#[cfg(feature = "bigint")]
+pub use array::{JsArray, JsArrayIter};
 pub use bigint::BigInt;
+pub use function::JsFunction;
+pub use iterator::JsIterator;
+pub use object::{JsObject, PropertyAttributes};
+pub use owned::OwnedJsValue;
+pub use promise::{JsPromise, PromiseState};
+#[cfg(feature = "atomics")]
+pub use shared::SharedBuffer;
+#[cfg(feature = "futures")]
+pub use stream::JsStream;
+pub use string::{JsStr, JsString};
+pub use symbol::{JsSymbol, WellKnownSymbol};
 
 /// A value that can be (de)serialized to/from the quickjs runtime.
 #[derive(PartialEq, Clone, Debug)]
 #[allow(missing_docs)]
 pub enum JsValue {
     Null,
+    /// JS `undefined`, which is kept distinct from `null` in both
+    /// directions. See [is_nullish](JsValue::is_nullish).
+    Undefined,
     Bool(bool),
+    /// A number stored as an integer by the engine.
     Int(i32),
+    /// Any other number, including `NaN`, `±Infinity` and `-0`.
+    /// Integral floats are kept as is, so converting a `JsValue` to
+    /// Javascript and back does not change `Int` to `Float` or vice versa.
     Float(f64),
     String(String),
     Array(Vec<JsValue>),
     Object(HashMap<String, JsValue>),
-    /// chrono::Datetime<Utc> / JS Date integration.
-    /// Only available with the optional `chrono` feature.
-    #[cfg(feature = "chrono")]
-    Date(chrono::DateTime<chrono::Utc>),
-    /// num_bigint::BigInt / JS BigInt integration
-    /// Only available with the optional `bigint` feature
-    #[cfg(feature = "bigint")]
+    /// A JS `Map`, as a list of entries in insertion order.
+    /// Can be converted from/to a `HashMap` with hashable keys.
+    Map(Vec<(JsValue, JsValue)>),
+    /// A JS `Set`, as a list of values in insertion order.
+    /// Can be converted from/to a `HashSet`.
+    Set(Vec<JsValue>),
+    /// JS Date integration.
+    /// With the optional `chrono` feature, `chrono::DateTime<Utc>` can be
+    /// converted from/to this variant.
+    Date(std::time::SystemTime),
+    /// JS BigInt integration.
+    /// Values are limited to the `i128` range, unless the optional `bigint`
+    /// feature is enabled, which adds num_bigint::BigInt support.
     BigInt(crate::BigInt),
+    /// Binary data.
+    /// Deserialized from a JS `Uint8Array` or `ArrayBuffer`, and serialized
+    /// into a `Uint8Array`. The data is copied in both directions.
+    Bytes(Vec<u8>),
+    /// A handle to a JS Promise.
+    /// See [JsPromise] for details.
+    Promise(JsPromise),
+    /// A handle to a JS function.
+    /// See [JsFunction] for details.
+    Function(JsFunction),
+    /// A handle to a JS symbol.
+    /// See [JsSymbol] for details.
+    Symbol(JsSymbol),
+    /// A JS string with lone surrogates, which is not valid Unicode.
+    /// Other strings are converted to `JsValue::String`.
+    /// See [JsString] for details.
+    Utf16(JsString),
+    /// A handle to an unconverted value.
+    /// See [OwnedJsValue] for details.
+    Ref(OwnedJsValue),
     #[doc(hidden)]
     __NonExhaustive,
 }
 
 impl JsValue {
+    /// The type of the value.
+    ///
+    /// `JsValue::Bytes` is a `TypedArray`, since it is passed to Javascript
+    /// as a `Uint8Array`. The type of a `JsValue::Ref` is determined by the
+    /// engine, see [OwnedJsValue::type_of].
+    pub fn type_of(&self) -> JsType {
+        match self {
+            JsValue::Null => JsType::Null,
+            JsValue::Undefined => JsType::Undefined,
+            JsValue::Bool(_) => JsType::Boolean,
+            JsValue::Int(_) | JsValue::Float(_) => JsType::Number,
+            JsValue::String(_) | JsValue::Utf16(_) => JsType::String,
+            JsValue::Array(_) => JsType::Array,
+            JsValue::Object(_) => JsType::Object,
+            JsValue::Map(_) => JsType::Map,
+            JsValue::Set(_) => JsType::Set,
+            JsValue::Date(_) => JsType::Date,
+            JsValue::BigInt(_) => JsType::BigInt,
+            JsValue::Bytes(_) => JsType::TypedArray,
+            JsValue::Promise(_) => JsType::Promise,
+            JsValue::Function(_) => JsType::Function,
+            JsValue::Symbol(_) => JsType::Symbol,
+            JsValue::Ref(value) => value.type_of(),
+            JsValue::__NonExhaustive => unreachable!(),
+        }
+    }
+
+    /// Check if the value is `null` or `undefined`, like the `??` operator.
+    pub fn is_nullish(&self) -> bool {
+        matches!(self, JsValue::Null | JsValue::Undefined)
+    }
+
+    /// Check if the value is the number `NaN`.
+    pub fn is_nan(&self) -> bool {
+        matches!(self, JsValue::Float(float) if float.is_nan())
+    }
+
+    /// Check if the value is a finite number, like `Number.isFinite`.
+    pub fn is_finite(&self) -> bool {
+        match self {
+            JsValue::Int(_) => true,
+            JsValue::Float(float) => float.is_finite(),
+            _ => false,
+        }
+    }
+
+    /// Get the value of a number, either an `Int` or a `Float`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            JsValue::Int(int) => Some(f64::from(int)),
+            JsValue::Float(float) => Some(float),
+            _ => None,
+        }
+    }
+
     /// Cast value to a str.
     ///
     /// Returns `Some(&str)` if value is a `JsValue::String`, None otherwise.
@@ -48,6 +162,24 @@ impl JsValue {
             _ => None,
         }
     }
+
+    /// Cast value to a byte slice.
+    ///
+    /// Returns `Some(&[u8])` if value is a `JsValue::Bytes`, None otherwise.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            JsValue::Bytes(ref b) => Some(b.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// Convert to `Vec<u8>`.
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        match self {
+            JsValue::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
 }
 
 macro_rules! value_impl_from {
@@ -93,62 +225,353 @@ macro_rules! value_impl_from {
 value_impl_from! {
     (
         bool => Bool,
-        i32 => Int,
-        f64 => Float,
         String => String,
+        JsPromise => Promise,
+        JsFunction => Function,
+        JsSymbol => Symbol,
+        OwnedJsValue => Ref,
+        std::time::SystemTime => Date,
     )
     (
+        i32 => |x| x => Int,
+        f64 => |x| x => Float,
         i8 => |x| i32::from(x) => Int,
         i16 => |x| i32::from(x) => Int,
         u8 => |x| i32::from(x) => Int,
         u16 => |x| i32::from(x) => Int,
         u32 => |x| f64::from(x) => Float,
+        f32 => |x| f64::from(x) => Float,
     )
 }
 
-#[cfg(feature = "bigint")]
 value_impl_from! {
     ()
     (
         i64 => |x| x.into() => BigInt,
-        u64 => |x| num_bigint::BigInt::from(x).into() => BigInt,
-        i128 => |x| num_bigint::BigInt::from(x).into() => BigInt,
-        u128 => |x| num_bigint::BigInt::from(x).into() => BigInt,
-        num_bigint::BigInt => |x| x.into() => BigInt,
+        u64 => |x| x.into() => BigInt,
+        i128 => |x| x.into() => BigInt,
+        u128 => |x| x.into() => BigInt,
     )
 }
 
 #[cfg(feature = "bigint")]
-impl std::convert::TryFrom<JsValue> for i64 {
+value_impl_from! {
+    ()
+    (
+        num_bigint::BigInt => |x| x.into() => BigInt,
+    )
+}
+
+/// The type of a Javascript value, see [JsValue::type_of].
+///
+/// Objects are distinguished by their built-in class, like the checks of
+/// `Array.isArray` or `instanceof`, so instances of subclasses have the type
+/// of the built-in class they extend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[allow(missing_docs)]
+pub enum JsType {
+    Undefined,
+    Null,
+    Boolean,
+    Number,
+    BigInt,
+    String,
+    Symbol,
+    Function,
+    Array,
+    /// An `Error` or an instance of a subclass, like `TypeError`.
+    Error,
+    Promise,
+    Date,
+    RegExp,
+    Map,
+    Set,
+    ArrayBuffer,
+    /// A typed array, like `Uint8Array`.
+    TypedArray,
+    /// Any other object.
+    Object,
+}
+
+impl JsType {
+    /// The result of the `typeof` operator for values of this type.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsType};
+    ///
+    /// let context = Context::new().unwrap();
+    /// let value = context.eval_owned("new Date(0)").unwrap();
+    /// assert_eq!(value.type_of(), JsType::Date);
+    /// assert_eq!(value.type_of().type_name(), "object");
+    /// ```
+    pub fn type_name(self) -> &'static str {
+        match self {
+            JsType::Undefined => "undefined",
+            JsType::Boolean => "boolean",
+            JsType::Number => "number",
+            JsType::BigInt => "bigint",
+            JsType::String => "string",
+            JsType::Symbol => "symbol",
+            JsType::Function => "function",
+            _ => "object",
+        }
+    }
+
+    /// Check if values of this type are objects, including functions.
+    pub fn is_object(self) -> bool {
+        !matches!(
+            self,
+            JsType::Undefined
+                | JsType::Null
+                | JsType::Boolean
+                | JsType::Number
+                | JsType::BigInt
+                | JsType::String
+                | JsType::Symbol
+        )
+    }
+}
+
+/// Largest integer that can be represented exactly by a JS number.
+pub(crate) const MAX_SAFE_INTEGER: i64 = 9_007_199_254_740_991;
+
+/// How integers beyond the safe integer range of Javascript numbers,
+/// `±(2^53 - 1)`, are converted to Javascript.
+///
+/// The policy of a context is set with
+/// [ContextBuilder::integer_policy](crate::ContextBuilder::integer_policy),
+/// and used by [to_js](crate::to_js). Other conversions can select a policy
+/// with [to_js_with](crate::to_js_with) or [convert](IntegerPolicy::convert).
+///
+/// Integers in the safe range are always converted to numbers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntegerPolicy {
+    /// Convert to the nearest number, which silently loses precision. This
+    /// is the default.
+    #[default]
+    Lossy,
+    /// Fail with a `ValueError::Custom`.
+    Error,
+    /// Convert to a `BigInt`.
+    ///
+    /// Requires the `bignum` feature, otherwise converting such an integer
+    /// to Javascript fails with a `ValueError::Custom`.
+    BigInt,
+    /// Convert to the nearest safe integer.
+    Clamp,
+}
+
+impl IntegerPolicy {
+    /// Convert an integer to a `JsValue::Int`, `JsValue::Float` or
+    /// `JsValue::BigInt` with this policy.
+    ///
+    /// ```rust
+    /// use quick_js::{IntegerPolicy, JsValue};
+    ///
+    /// let big = (1u64 << 53) + 1;
+    /// assert_eq!(IntegerPolicy::Lossy.convert(big), Ok(JsValue::Float(2f64.powi(53))));
+    /// assert!(IntegerPolicy::Error.convert(big).is_err());
+    /// assert_eq!(IntegerPolicy::BigInt.convert(big), Ok(JsValue::from(big)));
+    /// assert_eq!(IntegerPolicy::Clamp.convert(big), Ok(JsValue::Float(2f64.powi(53) - 1.0)));
+    /// assert_eq!(IntegerPolicy::Error.convert(1u64 << 40), Ok(JsValue::Float(2f64.powi(40))));
+    /// ```
+    pub fn convert(self, int: impl Into<i128>) -> Result<JsValue, ValueError> {
+        let int = int.into();
+        if let Ok(int) = i32::try_from(int) {
+            return Ok(JsValue::Int(int));
+        }
+        if int.unsigned_abs() <= MAX_SAFE_INTEGER as u128 {
+            return Ok(JsValue::Float(int as f64));
+        }
+        match self {
+            IntegerPolicy::Lossy => Ok(JsValue::Float(int as f64)),
+            IntegerPolicy::Error => Err(unsafe_integer_error(int)),
+            IntegerPolicy::BigInt => Ok(JsValue::BigInt(int.into())),
+            IntegerPolicy::Clamp => Ok(JsValue::Float(
+                int.signum() as f64 * MAX_SAFE_INTEGER as f64,
+            )),
+        }
+    }
+}
+
+/// Which value `None` is converted to.
+///
+/// The policy of a context is set with
+/// [ContextBuilder::none_policy](crate::ContextBuilder::none_policy), and used
+/// by [to_js](crate::to_js). `From<Option<T>>` always converts `None` to
+/// `null`, other conversions can select a policy with
+/// [convert](NonePolicy::convert).
+///
+/// Both `null` and `undefined` are converted back to `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonePolicy {
+    /// Convert to `null`. This is the default.
+    #[default]
+    Null,
+    /// Convert to `undefined`.
+    Undefined,
+}
+
+impl NonePolicy {
+    /// The value `None` is converted to.
+    pub fn value(self) -> JsValue {
+        match self {
+            NonePolicy::Null => JsValue::Null,
+            NonePolicy::Undefined => JsValue::Undefined,
+        }
+    }
+
+    /// Convert an `Option` with this policy.
+    ///
+    /// ```rust
+    /// use quick_js::{JsValue, NonePolicy};
+    ///
+    /// assert_eq!(NonePolicy::Undefined.convert(Some(1)), JsValue::Int(1));
+    /// assert_eq!(NonePolicy::Undefined.convert(None::<i32>), JsValue::Undefined);
+    /// assert_eq!(NonePolicy::Null.convert(None::<i32>), JsValue::Null);
+    /// ```
+    pub fn convert<T: Into<JsValue>>(self, value: Option<T>) -> JsValue {
+        value.map_or_else(|| self.value(), Into::into)
+    }
+}
+
+/// The error of [IntegerPolicy::Error].
+pub(crate) fn unsafe_integer_error(int: impl fmt::Display) -> ValueError {
+    ValueError::Custom(format!(
+        "Integer {} is outside of the safe integer range",
+        int
+    ))
+}
+
+/// Format a number like `Number.prototype.toString`, eg `Infinity` instead of
+/// `inf` and `1e+21` instead of `1000000000000000000000`.
+pub(crate) fn format_number(float: f64) -> String {
+    if float.is_nan() {
+        return "NaN".to_string();
+    }
+    if float.is_infinite() {
+        return if float > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if float == 0.0 {
+        return "0".to_string();
+    }
+    let sign = if float < 0.0 { "-" } else { "" };
+    // The shortest digits that round-trip, and the exponent of the first.
+    let scientific = format!("{:e}", float.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let exponent: i32 = exponent.parse().unwrap();
+    let len = digits.len() as i32;
+    let number = if exponent >= len - 1 && exponent < 21 {
+        format!("{}{}", digits, "0".repeat((exponent + 1 - len) as usize))
+    } else if (0..21).contains(&exponent) {
+        let (int, fract) = digits.split_at(exponent as usize + 1);
+        format!("{}.{}", int, fract)
+    } else if (-6..0).contains(&exponent) {
+        format!("0.{}{}", "0".repeat((-exponent - 1) as usize), digits)
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let exponent_sign = if exponent > 0 { "+" } else { "-" };
+        format!(
+            "{}{}{}e{}{}",
+            first,
+            point,
+            rest,
+            exponent_sign,
+            exponent.abs()
+        )
+    };
+    format!("{}{}", sign, number)
+}
+
+/// Convert an integral float, like a JS number beyond the `i32` range.
+fn integral_float<T: TryFrom<i128>>(float: f64) -> Option<T> {
+    // Unlike `i128::MAX as f64`, the bounds are exact.
+    let bound = 2f64.powi(127);
+    if float.fract() == 0.0 && float >= -bound && float < bound {
+        T::try_from(float as i128).ok()
+    } else {
+        None
+    }
+}
+
+macro_rules! value_int_impl_tryfrom {
+    (
+        ($($t:ty, )*)
+    ) => {
+        $(
+            impl std::convert::TryFrom<JsValue> for $t {
+                type Error = ValueError;
+
+                fn try_from(value: JsValue) -> Result<Self, Self::Error> {
+                    match value {
+                        JsValue::Int(int) => <$t>::try_from(int).map_err(|_| ValueError::UnexpectedType),
+                        JsValue::Float(float) => integral_float(float).ok_or(ValueError::UnexpectedType),
+                        _ => Err(ValueError::UnexpectedType),
+                    }
+                }
+            }
+        )*
+    }
+}
+
+value_int_impl_tryfrom! {
+    (
+        i8,
+        i16,
+        i32,
+        u8,
+        u16,
+        u32,
+    )
+}
+
+/// Converts all numbers.
+impl std::convert::TryFrom<JsValue> for f64 {
     type Error = ValueError;
 
     fn try_from(value: JsValue) -> Result<Self, Self::Error> {
         match value {
-            JsValue::Int(int) => Ok(int as i64),
-            JsValue::BigInt(bigint) => bigint.as_i64().ok_or(ValueError::UnexpectedType),
+            JsValue::Int(int) => Ok(f64::from(int)),
+            JsValue::Float(float) => Ok(float),
             _ => Err(ValueError::UnexpectedType),
         }
     }
 }
 
-#[cfg(feature = "bigint")]
+/// Converts all numbers, rounding them to the nearest `f32`.
+///
+/// Finite numbers beyond the range of `f32` fail, instead of becoming
+/// infinite.
+impl std::convert::TryFrom<JsValue> for f32 {
+    type Error = ValueError;
+
+    fn try_from(value: JsValue) -> Result<Self, Self::Error> {
+        let float = f64::try_from(value)?;
+        let rounded = float as f32;
+        if rounded.is_infinite() && float.is_finite() {
+            Err(ValueError::UnexpectedType)
+        } else {
+            Ok(rounded)
+        }
+    }
+}
+
 macro_rules! value_bigint_impl_tryfrom {
     (
-        ($($t:ty => $to_type:ident, )*)
+        ($($t:ty => $as_type:ident, )*)
     ) => {
         $(
             impl std::convert::TryFrom<JsValue> for $t {
                 type Error = ValueError;
 
                 fn try_from(value: JsValue) -> Result<Self, Self::Error> {
-                    use num_traits::ToPrimitive;
-
                     match value {
-                        JsValue::Int(int) => Ok(int as $t),
-                        JsValue::BigInt(bigint) => bigint
-                            .into_bigint()
-                            .$to_type()
-                            .ok_or(ValueError::UnexpectedType),
+                        JsValue::Int(int) => <$t>::try_from(int).map_err(|_| ValueError::UnexpectedType),
+                        JsValue::Float(float) => integral_float(float).ok_or(ValueError::UnexpectedType),
+                        JsValue::BigInt(bigint) => bigint.$as_type().ok_or(ValueError::UnexpectedType),
                         _ => Err(ValueError::UnexpectedType),
                     }
                 }
@@ -157,12 +580,12 @@ macro_rules! value_bigint_impl_tryfrom {
     }
 }
 
-#[cfg(feature = "bigint")]
 value_bigint_impl_tryfrom! {
     (
-        u64 => to_u64,
-        i128 => to_i128,
-        u128 => to_u128,
+        i64 => as_i64,
+        u64 => as_u64,
+        i128 => as_i128,
+        u128 => as_u128,
     )
 }
 
@@ -173,12 +596,135 @@ impl std::convert::TryFrom<JsValue> for num_bigint::BigInt {
     fn try_from(value: JsValue) -> Result<Self, Self::Error> {
         match value {
             JsValue::Int(int) => Ok(num_bigint::BigInt::from(int)),
+            JsValue::Float(float) if float.fract() == 0.0 => {
+                num_traits::FromPrimitive::from_f64(float).ok_or(ValueError::UnexpectedType)
+            }
             JsValue::BigInt(bigint) => Ok(bigint.into_bigint()),
             _ => Err(ValueError::UnexpectedType),
         }
     }
 }
 
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for JsValue {
+    fn from(value: chrono::DateTime<chrono::Utc>) -> Self {
+        JsValue::Date(value.into())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl std::convert::TryFrom<JsValue> for chrono::DateTime<chrono::Utc> {
+    type Error = ValueError;
+
+    fn try_from(value: JsValue) -> Result<Self, Self::Error> {
+        match value {
+            JsValue::Date(time) => Ok(time.into()),
+            _ => Err(ValueError::UnexpectedType),
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Value> for JsValue {
+    fn from(value: serde_json::Value) -> Self {
+        use serde_json::Value;
+        match value {
+            Value::Null => JsValue::Null,
+            Value::Bool(b) => JsValue::Bool(b),
+            Value::Number(n) => match n.as_i64().map(i32::try_from) {
+                Some(Ok(int)) => JsValue::Int(int),
+                _ => JsValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(s) => JsValue::String(s),
+            Value::Array(values) => JsValue::Array(values.into_iter().map(JsValue::from).collect()),
+            Value::Object(map) => {
+                JsValue::Object(map.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+/// Converts values like `JSON.stringify`: non-finite numbers become `null`
+/// and dates are formatted as ISO 8601 strings.
+/// BigInts outside of the `i64`/`u64` range, maps, sets, symbols, promises,
+/// functions and references can not be converted.
+#[cfg(feature = "serde_json")]
+impl std::convert::TryFrom<JsValue> for serde_json::Value {
+    type Error = ValueError;
+
+    fn try_from(value: JsValue) -> Result<Self, Self::Error> {
+        use serde_json::Value;
+        let value = match value {
+            JsValue::Null | JsValue::Undefined => Value::Null,
+            JsValue::Bool(b) => Value::Bool(b),
+            JsValue::Int(int) => Value::from(int),
+            JsValue::Float(f) => serde_json::Number::from_f64(f)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            JsValue::String(s) => Value::String(s),
+            JsValue::Array(values) => Value::Array(
+                values
+                    .into_iter()
+                    .map(Value::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            JsValue::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(k, v)| Ok((k, Value::try_from(v)?)))
+                    .collect::<Result<_, ValueError>>()?,
+            ),
+            JsValue::Date(time) => Value::String(format_iso_8601(time)),
+            JsValue::BigInt(int) => match (int.as_i64(), int.as_u64()) {
+                (Some(int), _) => Value::from(int),
+                (_, Some(int)) => Value::from(int),
+                _ => return Err(ValueError::UnexpectedType),
+            },
+            JsValue::Bytes(bytes) => Value::from(bytes),
+            _ => return Err(ValueError::UnexpectedType),
+        };
+        Ok(value)
+    }
+}
+
+/// Format a time like JS `Date.prototype.toISOString`.
+#[cfg(feature = "serde_json")]
+fn format_iso_8601(time: std::time::SystemTime) -> String {
+    let millis = match time.duration_since(std::time::UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as i128,
+        Err(e) => -(e.duration().as_millis() as i128),
+    };
+    let days = millis.div_euclid(86_400_000);
+    let millis = millis.rem_euclid(86_400_000);
+
+    // Convert days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let year = if (0..=9999).contains(&year) {
+        format!("{:04}", year)
+    } else {
+        format!("{:+07}", year)
+    };
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
 impl<T> From<Vec<T>> for JsValue
 where
     T: Into<JsValue>,
@@ -189,12 +735,37 @@ where
     }
 }
 
+/// Converts arrays, and the bytes of `Uint8Array`s and `ArrayBuffer`s.
+impl<T> TryFrom<JsValue> for Vec<T>
+where
+    T: TryFrom<JsValue>,
+    T::Error: Into<ValueError>,
+{
+    type Error = ValueError;
+
+    fn try_from(value: JsValue) -> Result<Self, Self::Error> {
+        let values = match value {
+            JsValue::Array(values) => values,
+            JsValue::Bytes(bytes) => bytes
+                .into_iter()
+                .map(|byte| JsValue::Int(byte.into()))
+                .collect(),
+            _ => return Err(ValueError::UnexpectedType),
+        };
+        values
+            .into_iter()
+            .map(|v| v.try_into().map_err(Into::into))
+            .collect()
+    }
+}
+
 impl<'a> From<&'a str> for JsValue {
     fn from(val: &'a str) -> Self {
         JsValue::String(val.into())
     }
 }
 
+/// Converts `None` to `null`, use [NonePolicy] to convert it to `undefined`.
 impl<T> From<Option<T>> for JsValue
 where
     T: Into<JsValue>,
@@ -219,26 +790,104 @@ where
     }
 }
 
-impl<V> TryFrom<JsValue> for HashMap<String, V>
+/// Converts both objects and `Map`s.
+impl<K, V> TryFrom<JsValue> for HashMap<K, V>
 where
+    K: TryFrom<JsValue> + Eq + Hash,
     V: TryFrom<JsValue>,
 {
     type Error = ValueError;
 
     fn try_from(value: JsValue) -> Result<Self, Self::Error> {
-        match value {
+        let entries: Vec<(JsValue, JsValue)> = match value {
             JsValue::Object(object) => object
                 .into_iter()
-                .map(|(k, v)| match v.try_into() {
-                    Ok(v) => Ok((k, v)),
-                    Err(_) => Err(ValueError::UnexpectedType),
-                })
+                .map(|(k, v)| (JsValue::String(k), v))
+                .collect(),
+            JsValue::Map(entries) => entries,
+            _ => return Err(ValueError::UnexpectedType),
+        };
+        entries
+            .into_iter()
+            .map(|(k, v)| match (k.try_into(), v.try_into()) {
+                (Ok(k), Ok(v)) => Ok((k, v)),
+                _ => Err(ValueError::UnexpectedType),
+            })
+            .collect()
+    }
+}
+
+impl<T> From<HashSet<T>> for JsValue
+where
+    T: Into<JsValue>,
+{
+    fn from(set: HashSet<T>) -> Self {
+        JsValue::Set(set.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T> TryFrom<JsValue> for HashSet<T>
+where
+    T: TryFrom<JsValue> + Eq + Hash,
+{
+    type Error = ValueError;
+
+    fn try_from(value: JsValue) -> Result<Self, Self::Error> {
+        match value {
+            JsValue::Set(values) => values
+                .into_iter()
+                .map(|v| v.try_into().map_err(|_| ValueError::UnexpectedType))
                 .collect(),
             _ => Err(ValueError::UnexpectedType),
         }
     }
 }
 
+/// Conversion from a [JsValue], with a [ValueError] on failure.
+///
+/// Implemented for all types that implement `TryFrom<JsValue>` with a
+/// `ValueError`, for [JsValue] itself, and for `Option<T>` of those, which
+/// converts `null` and `undefined` to `None`. `Option<T>` can not implement
+/// `TryFrom<JsValue>`, since it would conflict with the blanket
+/// implementations of the standard library.
+///
+/// ```rust
+/// use quick_js::{Context, FromJsValue, JsValue};
+/// let context = Context::new().unwrap();
+///
+/// let value = context.eval("[1, 2, 3]").unwrap();
+/// assert_eq!(Vec::<u8>::from_js_value(value), Ok(vec![1, 2, 3]));
+///
+/// let value = context.eval("undefined").unwrap();
+/// assert_eq!(Option::<u32>::from_js_value(value), Ok(None));
+/// assert_eq!(Option::<u32>::from_js_value(JsValue::Int(7)), Ok(Some(7)));
+/// ```
+pub trait FromJsValue: Sized {
+    /// Convert the value.
+    fn from_js_value(value: JsValue) -> Result<Self, ValueError>;
+}
+
+impl<T: TryFrom<JsValue, Error = ValueError>> FromJsValue for T {
+    fn from_js_value(value: JsValue) -> Result<Self, ValueError> {
+        T::try_from(value)
+    }
+}
+
+impl FromJsValue for JsValue {
+    fn from_js_value(value: JsValue) -> Result<Self, ValueError> {
+        Ok(value)
+    }
+}
+
+impl<T: TryFrom<JsValue, Error = ValueError>> FromJsValue for Option<T> {
+    fn from_js_value(value: JsValue) -> Result<Self, ValueError> {
+        match value {
+            JsValue::Null | JsValue::Undefined => Ok(None),
+            value => T::try_from(value).map(Some),
+        }
+    }
+}
+
 /// Error during value conversion.
 #[derive(PartialEq, Eq, Debug)]
 pub enum ValueError {
@@ -250,6 +899,8 @@ pub enum ValueError {
     Internal(String),
     /// Received an unexpected type that could not be converted.
     UnexpectedType,
+    /// Custom conversion error, eg reported by a serde implementation.
+    Custom(String),
     #[doc(hidden)]
     __NonExhaustive,
 }
@@ -274,6 +925,7 @@ impl fmt::Display for ValueError {
             StringWithZeroBytes(_) => write!(f, "String contains \\0 bytes",),
             Internal(e) => write!(f, "Value conversion failed - internal error: {}", e),
             UnexpectedType => write!(f, "Could not convert - received unexpected type"),
+            Custom(e) => write!(f, "Value conversion failed: {}", e),
             __NonExhaustive => unreachable!(),
         }
     }
@@ -286,7 +938,6 @@ mod tests {
     #[allow(unused_imports)]
     use super::*;
 
-    #[cfg(feature = "bigint")]
     #[test]
     fn test_bigint_from_i64() {
         let int = 1234i64;
@@ -298,6 +949,196 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_number_helpers() {
+        assert!(JsValue::Float(f64::NAN).is_nan());
+        assert!(!JsValue::Int(0).is_nan());
+        assert!(JsValue::Int(1).is_finite());
+        assert!(!JsValue::Float(f64::NEG_INFINITY).is_finite());
+        assert!(!JsValue::from("1").is_finite());
+        assert_eq!(JsValue::Int(2).as_f64(), Some(2.0));
+        assert_eq!(JsValue::Bool(true).as_f64(), None);
+
+        assert_eq!(format_number(f64::NAN), "NaN");
+        assert_eq!(format_number(f64::NEG_INFINITY), "-Infinity");
+        assert_eq!(format_number(-0.0), "0");
+        assert_eq!(format_number(1.0), "1");
+        assert_eq!(format_number(-1.5), "-1.5");
+        assert_eq!(format_number(1e21), "1e+21");
+        assert_eq!(format_number(1.5e-7), "1.5e-7");
+        assert_eq!(format_number(0.000001), "0.000001");
+        assert_eq!(
+            format_number(123456789012345680000.0),
+            "123456789012345680000"
+        );
+    }
+
+    #[test]
+    fn test_try_from_numbers() {
+        assert_eq!(u8::try_from(JsValue::Int(255)), Ok(255));
+        assert_eq!(
+            u8::try_from(JsValue::Int(256)),
+            Err(ValueError::UnexpectedType)
+        );
+        assert_eq!(i32::try_from(JsValue::Float(3.0)), Ok(3));
+        assert_eq!(
+            i32::try_from(JsValue::Float(3.5)),
+            Err(ValueError::UnexpectedType)
+        );
+        assert_eq!(u32::try_from(JsValue::Float(4_294_967_295.0)), Ok(u32::MAX));
+        assert_eq!(i64::try_from(JsValue::Float(2f64.powi(53))), Ok(1 << 53));
+        assert_eq!(
+            i64::try_from(JsValue::Float(2f64.powi(63))),
+            Err(ValueError::UnexpectedType)
+        );
+        assert_eq!(
+            i64::try_from(JsValue::Float(f64::NAN)),
+            Err(ValueError::UnexpectedType)
+        );
+        assert_eq!(f64::try_from(JsValue::Int(2)), Ok(2.0));
+        assert_eq!(
+            f64::try_from(JsValue::Bool(true)),
+            Err(ValueError::UnexpectedType)
+        );
+    }
+
+    #[test]
+    fn test_f32() {
+        assert_eq!(JsValue::from(0.5f32), JsValue::Float(0.5));
+        assert_eq!(f32::try_from(JsValue::Int(3)), Ok(3.0));
+        // Rounds to the nearest f32.
+        assert_eq!(f32::try_from(JsValue::Float(0.1)), Ok(0.1f32));
+        assert_eq!(
+            f32::try_from(JsValue::Float(16_777_217.0)),
+            Ok(16_777_216.0)
+        );
+        assert_eq!(
+            f32::try_from(JsValue::Float(f64::INFINITY)),
+            Ok(f32::INFINITY)
+        );
+        assert!(f32::try_from(JsValue::Float(f64::NAN)).unwrap().is_nan());
+        assert_eq!(
+            f32::try_from(JsValue::Float(1e39)),
+            Err(ValueError::UnexpectedType)
+        );
+    }
+
+    #[test]
+    fn test_u128() {
+        let value = JsValue::from(u128::MAX);
+        assert_eq!(u128::try_from(value.clone()), Ok(u128::MAX));
+        assert_eq!(i128::try_from(value), Err(ValueError::UnexpectedType));
+        assert_eq!(JsValue::from(1u128), JsValue::BigInt(1i64.into()));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_from_float() {
+        assert_eq!(
+            num_bigint::BigInt::try_from(JsValue::Float(2f64.powi(80))),
+            Ok(num_bigint::BigInt::from(1u128 << 80))
+        );
+        assert_eq!(
+            num_bigint::BigInt::try_from(JsValue::Float(0.5)),
+            Err(ValueError::UnexpectedType)
+        );
+        assert_eq!(
+            num_bigint::BigInt::try_from(JsValue::Float(f64::INFINITY)),
+            Err(ValueError::UnexpectedType)
+        );
+    }
+
+    #[test]
+    fn test_js_string_wtf8() {
+        let string = JsString::from("a\u{10000}é");
+        assert_eq!(string.as_utf16(), &[0x61, 0xD800, 0xDC00, 0xE9]);
+        assert!(string.is_well_formed());
+        assert_eq!(string.to_wtf8(), "a\u{10000}é".as_bytes());
+
+        let lone = JsString::from_utf16(vec![0xDC00, 0x61, 0xD800]);
+        assert!(!lone.is_well_formed());
+        let wtf8 = lone.to_wtf8();
+        assert_eq!(wtf8, [0xED, 0xB0, 0x80, 0x61, 0xED, 0xA0, 0x80]);
+        assert_eq!(JsString::from_wtf8(&wtf8), Ok(lone.clone()));
+        assert_eq!(lone.to_string_lossy(), "\u{FFFD}a\u{FFFD}");
+        assert_eq!(lone.clone().into_string(), Err(lone.clone()));
+        assert_eq!(JsValue::from(lone.clone()), JsValue::Utf16(lone));
+        assert_eq!(JsValue::from(string), JsValue::from("a\u{10000}é"));
+
+        // Surrogate pairs must be encoded as one code point.
+        assert!(JsString::from_wtf8(&[0xED, 0xA0, 0x80, 0xED, 0xB0, 0x80]).is_err());
+        assert!(JsString::from_wtf8(&[0xC0, 0x80]).is_err());
+        assert!(JsString::from_wtf8(&[0xE0, 0x80]).is_err());
+    }
+
+    #[test]
+    fn test_try_from_collections() {
+        let array = JsValue::Array(vec![JsValue::Int(1), JsValue::Float(2.0)]);
+        assert_eq!(Vec::<i64>::try_from(array.clone()), Ok(vec![1, 2]));
+        assert_eq!(Vec::<f64>::try_from(array.clone()), Ok(vec![1.0, 2.0]));
+        assert_eq!(
+            Vec::<JsValue>::try_from(array.clone()),
+            Ok(vec![JsValue::Int(1), JsValue::Float(2.0)])
+        );
+        assert_eq!(
+            Vec::<String>::try_from(array),
+            Err(ValueError::UnexpectedType)
+        );
+        assert_eq!(
+            Vec::<u8>::try_from(JsValue::Bytes(vec![1, 255])),
+            Ok(vec![1, 255])
+        );
+        assert_eq!(
+            Vec::<Vec<bool>>::try_from(JsValue::Array(vec![JsValue::Array(vec![true.into()])])),
+            Ok(vec![vec![true]])
+        );
+
+        let object = JsValue::Object(
+            vec![("a".to_string(), JsValue::Int(1))]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(
+            HashMap::<String, u16>::try_from(object),
+            Ok(vec![("a".to_string(), 1)].into_iter().collect())
+        );
+
+        assert_eq!(Option::<String>::from_js_value(JsValue::Null), Ok(None));
+        assert_eq!(
+            Option::<String>::from_js_value(JsValue::Undefined),
+            Ok(None)
+        );
+        assert_eq!(
+            Option::<String>::from_js_value("x".into()),
+            Ok(Some("x".to_string()))
+        );
+        assert_eq!(
+            Option::<String>::from_js_value(JsValue::Int(1)),
+            Err(ValueError::UnexpectedType)
+        );
+        assert_eq!(JsValue::from_js_value(JsValue::Int(1)), Ok(JsValue::Int(1)));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_format_iso_8601() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        assert_eq!(format_iso_8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_iso_8601(UNIX_EPOCH + Duration::from_millis(951_782_400_123)),
+            "2000-02-29T00:00:00.123Z"
+        );
+        assert_eq!(
+            format_iso_8601(UNIX_EPOCH - Duration::from_millis(1)),
+            "1969-12-31T23:59:59.999Z"
+        );
+        assert_eq!(
+            format_iso_8601(UNIX_EPOCH + Duration::from_secs(253_402_300_800)),
+            "+010000-01-01T00:00:00.000Z"
+        );
+    }
+
     #[cfg(feature = "bigint")]
     #[test]
     fn test_bigint_from_bigint() {
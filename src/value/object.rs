@@ -0,0 +1,320 @@
+use std::{collections::HashMap, convert::TryFrom, fmt};
+
+use crate::{
+    bindings::{OwnedObjectRef, PersistentValue},
+    ExecutionError, JsFunction, JsSymbol, JsValue, OwnedJsValue, ValueError,
+};
+
+/// A handle to a Javascript object.
+///
+/// Unlike `JsValue::Object`, the object is not copied into Rust data.
+/// Properties are read and written directly on the object living in the
+/// context, so large objects are only copied when asked to with
+/// [to_map](JsObject::to_map).
+///
+/// A handle to the global object is returned by
+/// [Context::global](crate::Context::global). Other objects can be
+/// converted from an [OwnedJsValue], for example the result of
+/// [Context::eval_owned](crate::Context::eval_owned). The handle can be
+/// passed back to Javascript by converting it into a [JsValue].
+///
+/// The handle keeps the context it belongs to alive, even if the
+/// [Context](crate::Context) itself is dropped.
+///
+/// ```rust
+/// use quick_js::{Context, JsObject, JsValue};
+/// use std::convert::TryFrom;
+///
+/// let context = Context::new().unwrap();
+/// let global = context.global().unwrap();
+///
+/// global.set("answer", 42).unwrap();
+/// assert_eq!(context.eval("answer"), Ok(JsValue::Int(42)));
+///
+/// let object = context.eval_owned("({ a: 1, b: [1, 2, 3] })").unwrap();
+/// let object = JsObject::try_from(object).unwrap();
+/// assert_eq!(object.get("a"), Ok(JsValue::Int(1)));
+/// object.set("c", true).unwrap();
+/// assert_eq!(object.keys().unwrap(), vec!["a", "b", "c"]);
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct JsObject {
+    object: PersistentValue,
+}
+
+impl JsObject {
+    pub(crate) fn new(object: PersistentValue) -> Self {
+        Self { object }
+    }
+
+    pub(crate) fn persistent(&self) -> &PersistentValue {
+        &self.object
+    }
+
+    fn object_ref(&self) -> OwnedObjectRef<'_> {
+        // Only objects are stored in a JsObject.
+        OwnedObjectRef::new(self.object.as_value_ref()).unwrap()
+    }
+
+    /// Get the value of a property.
+    ///
+    /// Inherited properties are included. Missing properties are returned as
+    /// `JsValue::Undefined`.
+    pub fn get(&self, name: &str) -> Result<JsValue, ExecutionError> {
+        self.object_ref().get_property(name)
+    }
+
+    /// Set the value of a property.
+    pub fn set(&self, name: &str, value: impl Into<JsValue>) -> Result<(), ExecutionError> {
+        self.object_ref().set_property(name, value.into())
+    }
+
+    /// Define a property with the given attributes, like
+    /// `Object.defineProperty`.
+    ///
+    /// Unlike [set](JsObject::set), setters are not called and read-only
+    /// properties are replaced. Fails if the property exists and is not
+    /// configurable.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsValue, PropertyAttributes};
+    ///
+    /// let context = Context::new().unwrap();
+    /// let global = context.global().unwrap();
+    ///
+    /// global.define("VERSION", "1.0", PropertyAttributes::constant()).unwrap();
+    /// context.eval("VERSION = '2.0'; delete VERSION;").unwrap();
+    /// assert_eq!(context.eval("VERSION"), Ok(JsValue::from("1.0")));
+    /// assert!(global.define("VERSION", "2.0", PropertyAttributes::default()).is_err());
+    /// ```
+    pub fn define(
+        &self,
+        name: &str,
+        value: impl Into<JsValue>,
+        attributes: PropertyAttributes,
+    ) -> Result<(), ExecutionError> {
+        self.object_ref()
+            .define_property(name, value.into(), attributes)
+    }
+
+    /// Freeze the object, like `Object.freeze`.
+    ///
+    /// Properties can no longer be added, removed or changed. Like
+    /// `Object.freeze`, this is shallow: objects stored in properties are not
+    /// frozen.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsObject, JsValue};
+    /// use std::convert::TryFrom;
+    ///
+    /// let context = Context::new().unwrap();
+    /// let config = context.eval_owned("({ debug: false })").unwrap();
+    /// let config = JsObject::try_from(config).unwrap();
+    /// config.freeze().unwrap();
+    ///
+    /// context.global().unwrap().set("config", config).unwrap();
+    /// context.eval("config.debug = true; config.extra = 1;").unwrap();
+    /// assert_eq!(context.eval("[config.debug, config.extra]"), Ok(JsValue::Array(vec![
+    ///     JsValue::Bool(false),
+    ///     JsValue::Undefined,
+    /// ])));
+    /// assert_eq!(context.eval("Object.isFrozen(config)"), Ok(JsValue::Bool(true)));
+    /// ```
+    pub fn freeze(&self) -> Result<(), ExecutionError> {
+        self.object_ref().set_integrity_level(true)
+    }
+
+    /// Seal the object, like `Object.seal`.
+    ///
+    /// Properties can no longer be added or removed, but writable properties
+    /// can still be changed.
+    pub fn seal(&self) -> Result<(), ExecutionError> {
+        self.object_ref().set_integrity_level(false)
+    }
+
+    /// Check if the object is frozen, like `Object.isFrozen`.
+    pub fn is_frozen(&self) -> Result<bool, ExecutionError> {
+        self.object_ref().test_integrity_level(true)
+    }
+
+    /// Check if the object is sealed, like `Object.isSealed`. Frozen objects
+    /// are sealed as well.
+    pub fn is_sealed(&self) -> Result<bool, ExecutionError> {
+        self.object_ref().test_integrity_level(false)
+    }
+
+    /// Get the value of a property keyed by a symbol.
+    ///
+    /// Like [get](JsObject::get), missing properties are returned as
+    /// `JsValue::Undefined`.
+    pub fn get_symbol(&self, symbol: &JsSymbol) -> Result<JsValue, ExecutionError> {
+        let symbol = symbol.persistent().value_ref_for(self.object.context())?;
+        self.object_ref().get_symbol_property(&symbol)
+    }
+
+    /// Set the value of a property keyed by a symbol.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsObject, JsValue, WellKnownSymbol};
+    /// use std::convert::TryFrom;
+    ///
+    /// let context = Context::new().unwrap();
+    /// let object = JsObject::try_from(context.eval_owned("({})").unwrap()).unwrap();
+    ///
+    /// let iterator = context.well_known_symbol(WellKnownSymbol::Iterator).unwrap();
+    /// let values = context.eval("(function* () { yield 1; yield 2; })").unwrap();
+    /// object.set_symbol(&iterator, values).unwrap();
+    ///
+    /// context.global().unwrap().set("iterable", object).unwrap();
+    /// assert_eq!(
+    ///     context.eval("[...iterable]"),
+    ///     Ok(JsValue::Array(vec![JsValue::Int(1), JsValue::Int(2)])),
+    /// );
+    /// ```
+    pub fn set_symbol(
+        &self,
+        symbol: &JsSymbol,
+        value: impl Into<JsValue>,
+    ) -> Result<(), ExecutionError> {
+        let symbol = symbol.persistent().value_ref_for(self.object.context())?;
+        self.object_ref().set_symbol_property(&symbol, value.into())
+    }
+
+    /// Delete a property.
+    ///
+    /// Returns `false` if the property is not configurable and could not be
+    /// deleted. Deleting a missing property succeeds.
+    pub fn delete(&self, name: &str) -> Result<bool, ExecutionError> {
+        self.object_ref().delete_property(name)
+    }
+
+    /// Check if the object has a property, like the `in` operator.
+    pub fn has(&self, name: &str) -> Result<bool, ExecutionError> {
+        self.object_ref().has_property(name)
+    }
+
+    /// Get the names of the own enumerable properties, like `Object.keys`.
+    pub fn keys(&self) -> Result<Vec<String>, ExecutionError> {
+        self.object_ref().property_names(true)
+    }
+
+    /// Get the names of all own properties, including non-enumerable ones,
+    /// like `Object.getOwnPropertyNames`.
+    pub fn own_keys(&self) -> Result<Vec<String>, ExecutionError> {
+        self.object_ref().property_names(false)
+    }
+
+    /// Check if the object is an instance of the given constructor, like the
+    /// `instanceof` operator.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsObject, JsValue};
+    /// use std::convert::TryFrom;
+    ///
+    /// let context = Context::new().unwrap();
+    /// let error = JsObject::try_from(context.eval_owned("new TypeError()").unwrap()).unwrap();
+    ///
+    /// let global = context.global().unwrap();
+    /// match global.get("Error").unwrap() {
+    ///     JsValue::Function(constructor) => assert!(error.instance_of(&constructor).unwrap()),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn instance_of(&self, constructor: &JsFunction) -> Result<bool, ExecutionError> {
+        let constructor = constructor
+            .persistent()
+            .value_ref_for(self.object.context())?;
+        self.object_ref().is_instance_of(&constructor)
+    }
+
+    /// Get the prototype of the object, like `Object.getPrototypeOf`.
+    ///
+    /// Returns `None` for objects without a prototype.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsObject};
+    /// use std::convert::TryFrom;
+    ///
+    /// let context = Context::new().unwrap();
+    /// let object = |code| JsObject::try_from(context.eval_owned(code).unwrap()).unwrap();
+    ///
+    /// let prototype = object("[]").prototype().unwrap().unwrap();
+    /// assert_eq!(prototype, object("Array.prototype"));
+    /// assert_eq!(object("Object.create(null)").prototype().unwrap(), None);
+    /// ```
+    pub fn prototype(&self) -> Result<Option<JsObject>, ExecutionError> {
+        let prototype = self.object_ref().prototype()?;
+        if prototype.is_null() {
+            Ok(None)
+        } else {
+            Ok(Some(JsObject::new(PersistentValue::from_ref(prototype))))
+        }
+    }
+
+    /// Copy the own enumerable properties into a map.
+    ///
+    /// Property values are converted like `JsValue::Object` properties.
+    pub fn to_map(&self) -> Result<HashMap<String, JsValue>, ExecutionError> {
+        self.object_ref().to_map()
+    }
+}
+
+impl fmt::Debug for JsObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("JsObject")
+    }
+}
+
+impl TryFrom<OwnedJsValue> for JsObject {
+    type Error = ValueError;
+
+    fn try_from(value: OwnedJsValue) -> Result<Self, ValueError> {
+        if value.persistent().as_value_ref().is_object() {
+            Ok(JsObject::new(value.persistent().clone()))
+        } else {
+            Err(ValueError::UnexpectedType)
+        }
+    }
+}
+
+impl From<JsObject> for JsValue {
+    fn from(object: JsObject) -> Self {
+        JsValue::Ref(OwnedJsValue::new(object.object))
+    }
+}
+
+/// The attributes of a property defined with [JsObject::define].
+///
+/// The default is a writable, enumerable and configurable property, like one
+/// created by an assignment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PropertyAttributes {
+    /// The value can be changed by an assignment.
+    pub writable: bool,
+    /// The property is included by `for...in` and `Object.keys`.
+    pub enumerable: bool,
+    /// The property can be deleted, and its attributes changed.
+    pub configurable: bool,
+}
+
+impl PropertyAttributes {
+    /// An enumerable property that can not be changed or deleted, like the
+    /// properties of a frozen object.
+    pub fn constant() -> Self {
+        Self {
+            writable: false,
+            enumerable: true,
+            configurable: false,
+        }
+    }
+}
+
+impl Default for PropertyAttributes {
+    fn default() -> Self {
+        Self {
+            writable: true,
+            enumerable: true,
+            configurable: true,
+        }
+    }
+}
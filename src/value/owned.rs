@@ -0,0 +1,113 @@
+use std::fmt;
+
+use std::convert::TryFrom;
+
+use crate::{
+    bindings::PersistentValue, ExecutionError, JsFunction, JsObject, JsStr, JsType, JsValue,
+    ValueError,
+};
+
+/// A handle to an arbitrary value living in a Javascript context.
+///
+/// Unlike the other [JsValue] variants, the value is not converted into Rust
+/// data. It stays in the context and can be passed back to Javascript as a
+/// `JsValue::Ref` without any conversion.
+///
+/// The handle keeps the value, and the context it belongs to, alive even if
+/// the [Context](crate::Context) itself is dropped. Cloning the handle
+/// creates a new reference to the same value.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue};
+///
+/// let context = Context::new().unwrap();
+/// context.eval("function size(set) { return set.size; }").unwrap();
+///
+/// let set = context.eval_owned("new Set([1, 2, 3])").unwrap();
+/// assert_eq!(context.call_function("size", vec![set]), Ok(JsValue::Int(3)));
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct OwnedJsValue {
+    value: PersistentValue,
+}
+
+impl OwnedJsValue {
+    pub(crate) fn new(value: PersistentValue) -> Self {
+        Self { value }
+    }
+
+    pub(crate) fn persistent(&self) -> &PersistentValue {
+        &self.value
+    }
+
+    /// Convert the value into a [JsValue], copying its contents.
+    pub fn to_value(&self) -> Result<JsValue, ValueError> {
+        self.value.as_value_ref().to_value()
+    }
+
+    /// The type of the value, as determined by the engine.
+    ///
+    /// ```rust
+    /// use quick_js::{Context, JsType};
+    ///
+    /// let context = Context::new().unwrap();
+    /// let types = ["new TypeError()", "[1]", "/a/", "({})", "null"]
+    ///     .iter()
+    ///     .map(|code| context.eval_owned(code).unwrap().type_of())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     types,
+    ///     [JsType::Error, JsType::Array, JsType::RegExp, JsType::Object, JsType::Null],
+    /// );
+    /// ```
+    pub fn type_of(&self) -> JsType {
+        self.value.as_value_ref().type_of()
+    }
+
+    /// Check if the value is an instance of the given constructor, like the
+    /// `instanceof` operator. Values that are not objects are no instances.
+    pub fn instance_of(&self, constructor: &JsFunction) -> Result<bool, ExecutionError> {
+        match JsObject::try_from(self.clone()) {
+            Ok(object) => object.instance_of(constructor),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Borrow the contents of a string, without converting it into a
+    /// `String`, eg to read a large string.
+    ///
+    /// ASCII strings are borrowed from the engine without copying them,
+    /// other strings are converted to UTF-8 once. Returns
+    /// `ValueError::UnexpectedType` for values that are not strings, and an
+    /// error for strings with lone surrogates, which can be converted with
+    /// [to_value](OwnedJsValue::to_value) instead.
+    ///
+    /// ```rust
+    /// use quick_js::Context;
+    /// use std::io::Read;
+    ///
+    /// let context = Context::new().unwrap();
+    ///
+    /// let value = context.eval_owned("'abc'.repeat(1000)").unwrap();
+    /// let string = value.as_str_ref().unwrap();
+    /// assert_eq!(string.len(), 3000);
+    /// assert!(string.starts_with("abcabc"));
+    ///
+    /// // The bytes can be streamed with `std::io::Read`.
+    /// let mut reader = string.as_bytes();
+    /// let mut buffer = [0; 4];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(&buffer, b"abca");
+    /// ```
+    pub fn as_str_ref(&self) -> Result<JsStr<'_>, ValueError> {
+        JsStr::new(self.value.to_cstring()?)
+    }
+}
+
+impl fmt::Debug for OwnedJsValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("OwnedJsValue")
+            .field(&self.value.as_value_ref())
+            .finish()
+    }
+}
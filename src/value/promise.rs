@@ -0,0 +1,184 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+};
+
+use crate::{
+    bindings::{OwnedObjectRef, PersistentValue, TimerTime},
+    ExecutionError, JsValue,
+};
+
+/// The state of a [JsPromise].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PromiseState {
+    /// The promise is neither fulfilled nor rejected yet.
+    Pending,
+    /// The promise was resolved with a value.
+    Fulfilled,
+    /// The promise was rejected.
+    Rejected,
+}
+
+/// A handle to a Javascript Promise.
+///
+/// Promises are returned as `JsValue::Promise` when they are nested in a
+/// returned value (eg `[promise]`), or passed as arguments to callbacks.
+/// Note that top-level promises returned by `eval` and `call_function` are
+/// still resolved automatically.
+///
+/// `JsPromise` implements `Future`. Polling it executes pending jobs of the
+/// runtime until the promise is settled. See
+/// [Context::eval_async](crate::Context::eval_async).
+///
+/// The handle keeps the context it belongs to alive, even if the
+/// [Context](crate::Context) itself is dropped.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue, PromiseState};
+///
+/// let context = Context::new().unwrap();
+///
+/// let value = context.eval("[new Promise(resolve => resolve(42))]").unwrap();
+/// let promise = match value {
+///     JsValue::Array(mut values) => match values.remove(0) {
+///         JsValue::Promise(promise) => promise,
+///         _ => unreachable!(),
+///     },
+///     _ => unreachable!(),
+/// };
+///
+/// assert_eq!(promise.state(), PromiseState::Pending);
+/// assert_eq!(promise.await_result(), Ok(JsValue::Int(42)));
+/// assert_eq!(promise.state(), PromiseState::Fulfilled);
+/// ```
+#[derive(Clone)]
+pub struct JsPromise {
+    promise: PersistentValue,
+    /// Object recording the outcome of the promise, see
+    /// `ContextWrapper::track_promise`.
+    tracker: PersistentValue,
+}
+
+impl JsPromise {
+    pub(crate) fn new(promise: PersistentValue, tracker: PersistentValue) -> Self {
+        Self { promise, tracker }
+    }
+
+    pub(crate) fn persistent(&self) -> &PersistentValue {
+        &self.promise
+    }
+
+    fn tracker(&self) -> OwnedObjectRef<'_> {
+        OwnedObjectRef::new(self.tracker.as_value_ref()).unwrap()
+    }
+
+    /// Get the current state of the promise.
+    ///
+    /// Note that the state only changes while pending jobs are executed,
+    /// for example by [await_result](JsPromise::await_result) or during
+    /// `eval`.
+    pub fn state(&self) -> PromiseState {
+        self.tracker
+            .context()
+            .tracked_promise_state(&self.tracker())
+            .unwrap_or(PromiseState::Pending)
+    }
+
+    /// Get the result of the promise.
+    ///
+    /// Returns `None` if the promise is still pending, the resolved value if
+    /// it was fulfilled, or a `ExecutionError::Exception` if it was rejected.
+    pub fn result(&self) -> Option<Result<JsValue, ExecutionError>> {
+        let tracker = self.tracker();
+        match self.tracker.context().tracked_promise_result(&tracker) {
+            Ok(Some(value)) => Some(value.to_value().map_err(ExecutionError::from)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Run the event loop until the promise is settled, and return its
+    /// result.
+    ///
+    /// If timers are enabled, the thread sleeps until the next timer is due
+    /// when there are no pending jobs. It also sleeps until Rust futures
+    /// awaited by Javascript, like those of async callbacks, make progress.
+    /// Returns an `ExecutionError::Internal` if the event loop runs out of
+    /// jobs and timers while the promise is still pending.
+    pub fn await_result(&self) -> Result<JsValue, ExecutionError> {
+        let tracker = self.tracker();
+        let value = self.tracker.context().await_tracked_promise(&tracker)?;
+        Ok(value.to_value()?)
+    }
+}
+
+/// Maximum number of pending jobs executed by a single poll of a
+/// [JsPromise], to avoid blocking the executor.
+const JOBS_PER_POLL: usize = 64;
+
+impl Future for JsPromise {
+    type Output = Result<JsValue, ExecutionError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        for _ in 0..JOBS_PER_POLL {
+            if let Some(result) = self.result() {
+                return Poll::Ready(result);
+            }
+            let context = self.tracker.context();
+            match context.execute_pending_job() {
+                Ok(true) => {}
+                Ok(false) => match context.next_timer_due() {
+                    Some(deadline) if deadline <= context.timer_now() => {
+                        if let Err(e) = context.run_due_timers(context.timer_now()) {
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                    Some(TimerTime::Monotonic(deadline)) => {
+                        context.wake_on_timer(Some(deadline), cx.waker());
+                        context.runtime().wake_on_task(cx.waker());
+                        return Poll::Pending;
+                    }
+                    // A custom clock that does not advance on its own is
+                    // moved by the host, which then runs the timers.
+                    Some(TimerTime::Source(deadline)) if context.advance_clock(deadline) => {}
+                    Some(TimerTime::Source(_)) => {
+                        context.wake_on_timer(None, cx.waker());
+                        context.runtime().wake_on_task(cx.waker());
+                        return Poll::Pending;
+                    }
+                    None if context.runtime().has_pending_tasks() => {
+                        context.runtime().wake_on_task(cx.waker());
+                        return Poll::Pending;
+                    }
+                    None => {
+                        return Poll::Ready(Err(ExecutionError::Internal(
+                            "Promise can not be settled: there are no pending jobs left".into(),
+                        )));
+                    }
+                },
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        // Yield to the executor, but make sure to be polled again.
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl PartialEq for JsPromise {
+    // Promises are compared by identity.
+    fn eq(&self, other: &Self) -> bool {
+        self.promise == other.promise
+    }
+}
+
+impl fmt::Debug for JsPromise {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JsPromise")
+            .field("state", &self.state())
+            .finish()
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    convert::TryFrom,
+    fmt,
+    ptr::NonNull,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{bindings::shared, OwnedJsValue, ValueError};
+
+/// Memory that is shared with Javascript as a `SharedArrayBuffer`.
+///
+/// Requires the `atomics` feature. Unlike an `ArrayBuffer`, the memory is
+/// not owned by a single runtime: every `SharedArrayBuffer` created from the
+/// same buffer refers to the same memory, also in contexts of other runtimes
+/// running on other threads. The memory is freed when the last
+/// `SharedBuffer` and `SharedArrayBuffer` referring to it are dropped.
+///
+/// A buffer is turned into a `SharedArrayBuffer` with
+/// [Context::new_shared_array_buffer](crate::Context::new_shared_array_buffer),
+/// and `SharedArrayBuffer`s created by Javascript can be converted from an
+/// [OwnedJsValue].
+///
+/// Since the memory can be modified by Javascript at any time, it is only
+/// accessible as a slice of atomic bytes. Javascript can use `Atomics` to
+/// synchronize access.
+///
+/// ```rust
+/// use quick_js::{Context, JsValue, SharedBuffer};
+///
+/// let buffer = SharedBuffer::new(4);
+/// buffer.copy_from_slice(&[1, 2, 3, 4]);
+///
+/// let worker = std::thread::spawn({
+///     let buffer = buffer.clone();
+///     move || {
+///         let context = Context::new().unwrap();
+///         context.eval("function double(b) { const a = new Uint8Array(b); a.forEach((v, i) => a[i] = v * 2); }").unwrap();
+///         let value = context.new_shared_array_buffer(&buffer).unwrap();
+///         context.call_function("double", vec![JsValue::Ref(value)]).unwrap();
+///     }
+/// });
+/// worker.join().unwrap();
+///
+/// assert_eq!(buffer.to_vec(), vec![2, 4, 6, 8]);
+/// ```
+pub struct SharedBuffer {
+    data: NonNull<u8>,
+    len: usize,
+}
+
+// The memory is reference counted atomically and only accessed atomically.
+unsafe impl Send for SharedBuffer {}
+unsafe impl Sync for SharedBuffer {}
+
+impl SharedBuffer {
+    /// Allocate a zeroed buffer of `len` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the memory could not be allocated.
+    pub fn new(len: usize) -> Self {
+        let data = shared::alloc(len).expect("Could not allocate shared memory");
+        Self { data, len }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.data.as_ptr()
+    }
+
+    /// The length of the buffer in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Access the bytes of the buffer.
+    pub fn as_slice(&self) -> &[AtomicU8] {
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr() as *const AtomicU8, self.len) }
+    }
+
+    /// Copy the bytes of the buffer into a `Vec`.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.as_slice()
+            .iter()
+            .map(|byte| byte.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    /// Copy bytes into the start of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is longer than the buffer.
+    pub fn copy_from_slice(&self, bytes: &[u8]) {
+        assert!(bytes.len() <= self.len, "Slice is longer than the buffer");
+        for (target, byte) in self.as_slice().iter().zip(bytes) {
+            target.store(*byte, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Clone for SharedBuffer {
+    fn clone(&self) -> Self {
+        unsafe { shared::dup(self.data.as_ptr()) };
+        Self {
+            data: self.data,
+            len: self.len,
+        }
+    }
+}
+
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        unsafe { shared::free(self.data.as_ptr()) };
+    }
+}
+
+impl PartialEq for SharedBuffer {
+    /// Buffers are equal if they refer to the same memory.
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl fmt::Debug for SharedBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedBuffer")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl TryFrom<OwnedJsValue> for SharedBuffer {
+    type Error = ValueError;
+
+    fn try_from(value: OwnedJsValue) -> Result<Self, ValueError> {
+        let persistent = value.persistent();
+        let (data, len) = persistent
+            .context()
+            .shared_array_buffer_data(persistent.as_value_ref())
+            .ok_or(ValueError::UnexpectedType)?;
+        Ok(Self {
+            // The data of a SharedArrayBuffer is never null.
+            data: NonNull::new(data).ok_or(ValueError::UnexpectedType)?,
+            len,
+        })
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+};
+
+use futures_core::Stream;
+
+use crate::{bindings::PersistentValue, ExecutionError, JsPromise, JsValue, OwnedJsValue};
+
+/// A Javascript async iterator, driven from Rust as a `Stream`.
+///
+/// Created by [OwnedJsValue::stream] from any async iterable, like an async
+/// generator. Like [JsPromise], polling the stream executes the pending jobs
+/// of the runtime until the next value is available.
+///
+/// If the Javascript iterator throws or rejects, the error is returned as the
+/// next item, and the stream ends afterwards. Dropping a stream that did not
+/// end closes the iterator by calling its `return` method, which runs with
+/// the next pending jobs.
+///
+/// ```rust
+/// use futures::{executor::block_on, StreamExt};
+/// use quick_js::{Context, JsValue};
+///
+/// let context = Context::new().unwrap();
+/// let rows = context
+///     .eval_owned(
+///         "(async function* () {
+///             for (const row of [1, 2, 3]) {
+///                 yield await Promise.resolve(row * 10);
+///             }
+///         })()",
+///     )
+///     .unwrap();
+///
+/// let rows: Vec<_> = block_on(rows.stream().unwrap().collect());
+/// assert_eq!(
+///     rows,
+///     vec![Ok(JsValue::Int(10)), Ok(JsValue::Int(20)), Ok(JsValue::Int(30))]
+/// );
+/// ```
+pub struct JsStream {
+    /// The function stepping the iterator, see
+    /// `ContextWrapper::open_async_iterator`.
+    stepper: PersistentValue,
+    /// The pending request for the next value.
+    step: Option<JsPromise>,
+    done: bool,
+}
+
+impl JsStream {
+    fn step(&self, close: bool) -> Result<JsPromise, ExecutionError> {
+        let context = self.stepper.context();
+        context.step_async_iterator(self.stepper.as_value_ref(), close)
+    }
+
+    fn finish(
+        &mut self,
+        item: Option<Result<JsValue, ExecutionError>>,
+    ) -> Poll<Option<Result<JsValue, ExecutionError>>> {
+        self.done = true;
+        Poll::Ready(item)
+    }
+}
+
+impl Stream for JsStream {
+    type Item = Result<JsValue, ExecutionError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        let mut step = match self.step.take() {
+            Some(step) => step,
+            None => match self.step(false) {
+                Ok(step) => step,
+                Err(error) => return self.finish(Some(Err(error))),
+            },
+        };
+        match Pin::new(&mut step).poll(cx) {
+            Poll::Pending => {
+                self.step = Some(step);
+                Poll::Pending
+            }
+            Poll::Ready(Ok(JsValue::Array(values))) => match values.into_iter().next() {
+                Some(value) => Poll::Ready(Some(Ok(value))),
+                None => self.finish(None),
+            },
+            Poll::Ready(Ok(_)) => self.finish(Some(Err(ExecutionError::Internal(
+                "Invalid iterator step".into(),
+            )))),
+            Poll::Ready(Err(error)) => self.finish(Some(Err(error))),
+        }
+    }
+}
+
+impl Drop for JsStream {
+    fn drop(&mut self) {
+        if !self.done {
+            // Errors of `return` can not be reported.
+            let _ = self.step(true);
+        }
+    }
+}
+
+impl fmt::Debug for JsStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("JsStream")
+    }
+}
+
+impl OwnedJsValue {
+    /// Iterate over the values of an async iterable as a `Stream`, with the
+    /// Javascript async iterator protocol. See [JsStream].
+    ///
+    /// Iterables that are not async, like arrays of promises, are iterated
+    /// like with `for await...of`. Fails if the value is not iterable.
+    pub fn stream(&self) -> Result<JsStream, ExecutionError> {
+        let context = self.persistent().context();
+        let stepper = context.open_async_iterator(&self.persistent().as_value_ref())?;
+        Ok(JsStream {
+            stepper: PersistentValue::from_ref(stepper),
+            step: None,
+            done: false,
+        })
+    }
+}
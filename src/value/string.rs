@@ -0,0 +1,264 @@
+use std::{convert::TryFrom, fmt, ops::Deref, rc::Rc};
+
+use super::{JsValue, ValueError};
+use crate::bindings::{CStringRef, ContextWrapper};
+
+/// A Javascript string as UTF-16 code units, which may contain lone
+/// surrogates.
+///
+/// Javascript strings are not necessarily valid Unicode, so they can not
+/// always be converted to a Rust `String`. Strings with lone surrogates are
+/// converted to a `JsValue::Utf16`, which holds a `JsString`, and convert
+/// back to the same Javascript string.
+///
+/// [WTF-8](https://simonsapin.github.io/wtf-8/) is a superset of UTF-8 that
+/// can represent lone surrogates, see [to_wtf8](JsString::to_wtf8).
+///
+/// ```rust
+/// use quick_js::{Context, JsString, JsValue};
+/// use std::convert::TryFrom;
+///
+/// let context = Context::new().unwrap();
+/// context.eval("function length(s) { return s.length; }").unwrap();
+///
+/// let value = context.eval("'a\\uD800'").unwrap();
+/// let string = JsString::try_from(value.clone()).unwrap();
+/// assert_eq!(string.as_utf16(), &[0x61, 0xD800]);
+/// assert_eq!(string.to_string_lossy(), "a\u{FFFD}");
+///
+/// assert_eq!(context.call_function("length", vec![value]), Ok(JsValue::Int(2)));
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct JsString {
+    units: Vec<u16>,
+}
+
+impl JsString {
+    /// Create a string from UTF-16 code units.
+    pub fn from_utf16(units: impl Into<Vec<u16>>) -> Self {
+        JsString {
+            units: units.into(),
+        }
+    }
+
+    /// Create a string from WTF-8 bytes.
+    ///
+    /// Returns an error if the bytes are not valid WTF-8, which includes
+    /// surrogate pairs encoded as two separate surrogates.
+    pub fn from_wtf8(bytes: &[u8]) -> Result<Self, ValueError> {
+        decode_wtf8(bytes)
+            .map(JsString::from_utf16)
+            .ok_or_else(|| ValueError::Custom("Invalid WTF-8 string".into()))
+    }
+
+    /// The UTF-16 code units of the string.
+    pub fn as_utf16(&self) -> &[u16] {
+        &self.units
+    }
+
+    /// Convert into the UTF-16 code units of the string.
+    pub fn into_utf16(self) -> Vec<u16> {
+        self.units
+    }
+
+    /// Convert to WTF-8, which is the same as UTF-8 unless the string
+    /// contains lone surrogates.
+    pub fn to_wtf8(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.units.len());
+        for c in std::char::decode_utf16(self.units.iter().copied()) {
+            match c {
+                Ok(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                Err(e) => {
+                    let unit = e.unpaired_surrogate();
+                    bytes.push(0xE0 | (unit >> 12) as u8);
+                    bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                    bytes.push(0x80 | (unit & 0x3F) as u8);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Check if the string contains no lone surrogates, like
+    /// `String.prototype.isWellFormed`.
+    pub fn is_well_formed(&self) -> bool {
+        std::char::decode_utf16(self.units.iter().copied()).all(|c| c.is_ok())
+    }
+
+    /// Convert to a `String`, replacing lone surrogates with `U+FFFD`.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(&self.units)
+    }
+
+    /// Convert to a `String`, or return the string itself if it contains
+    /// lone surrogates.
+    pub fn into_string(self) -> Result<String, Self> {
+        String::from_utf16(&self.units).map_err(|_| self)
+    }
+
+    /// The error for a string with lone surrogates where a `String` is
+    /// required, eg for property names.
+    pub(crate) fn not_unicode_error(&self) -> ValueError {
+        ValueError::Custom(format!(
+            "String {:?} is not valid Unicode",
+            self.to_string_lossy()
+        ))
+    }
+}
+
+/// Decode WTF-8 into UTF-16 code units.
+fn decode_wtf8(bytes: &[u8]) -> Option<Vec<u16>> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        let (len, min, init) = match byte {
+            0x00..=0x7F => (1, 0, u32::from(byte)),
+            0xC2..=0xDF => (2, 0x80, u32::from(byte & 0x1F)),
+            0xE0..=0xEF => (3, 0x800, u32::from(byte & 0x0F)),
+            0xF0..=0xF4 => (4, 0x10000, u32::from(byte & 0x07)),
+            _ => return None,
+        };
+        let mut c = init;
+        for &byte in bytes.get(index + 1..index + len)? {
+            if byte & 0xC0 != 0x80 {
+                return None;
+            }
+            c = (c << 6) | u32::from(byte & 0x3F);
+        }
+        if c < min || c > 0x10FFFF {
+            return None;
+        }
+        if c >= 0x10000 {
+            c -= 0x10000;
+            units.push(0xD800 | (c >> 10) as u16);
+            units.push(0xDC00 | (c & 0x3FF) as u16);
+        } else {
+            // A low surrogate after a lone high surrogate forms a pair,
+            // which must be encoded as a single code point.
+            let is_low_surrogate = (0xDC00..0xE000).contains(&c);
+            if is_low_surrogate && matches!(units.last(), Some(0xD800..=0xDBFF)) {
+                return None;
+            }
+            units.push(c as u16);
+        }
+        index += len;
+    }
+    Some(units)
+}
+
+impl From<&str> for JsString {
+    fn from(value: &str) -> Self {
+        JsString::from_utf16(value.encode_utf16().collect::<Vec<_>>())
+    }
+}
+
+impl From<String> for JsString {
+    fn from(value: String) -> Self {
+        value.as_str().into()
+    }
+}
+
+/// Converts to a `JsValue::String` if the string is valid Unicode, and to a
+/// `JsValue::Utf16` otherwise.
+impl From<JsString> for JsValue {
+    fn from(value: JsString) -> Self {
+        match value.into_string() {
+            Ok(string) => JsValue::String(string),
+            Err(string) => JsValue::Utf16(string),
+        }
+    }
+}
+
+impl TryFrom<JsValue> for JsString {
+    type Error = ValueError;
+
+    fn try_from(value: JsValue) -> Result<Self, Self::Error> {
+        match value {
+            JsValue::String(string) => Ok(string.into()),
+            JsValue::Utf16(string) => Ok(string),
+            _ => Err(ValueError::UnexpectedType),
+        }
+    }
+}
+
+/// The contents of a Javascript string, borrowed from the engine, see
+/// [OwnedJsValue::as_str_ref](crate::OwnedJsValue::as_str_ref).
+///
+/// Dereferences to a `str`, without copying the string into a `String`.
+///
+/// Callbacks can take a `JsStr` parameter to borrow a string argument
+/// instead of copying it. It keeps its context alive, and other values fail
+/// with `ValueError::UnexpectedType`.
+///
+/// ```rust
+/// use quick_js::{Context, JsStr};
+/// let context = Context::new().unwrap();
+///
+/// context
+///     .add_callback("countWords", |text: JsStr| text.split_whitespace().count() as i32)
+///     .unwrap();
+///
+/// let count = context.eval_as::<i32>("countWords('a b c')").unwrap();
+/// assert_eq!(count, 3);
+/// ```
+pub struct JsStr<'a> {
+    string: CStringRef<'a>,
+    /// The context of a callback argument, which is dropped after the string.
+    _context: Option<Rc<ContextWrapper>>,
+}
+
+impl<'a> JsStr<'a> {
+    /// Fails for strings with lone surrogates.
+    pub(crate) fn new(string: CStringRef<'a>) -> Result<Self, ValueError> {
+        if let Err(e) = std::str::from_utf8(string.as_bytes()) {
+            return Err(match JsString::from_wtf8(string.as_bytes()) {
+                Ok(string) => string.not_unicode_error(),
+                Err(_) => ValueError::InvalidString(e),
+            });
+        }
+        Ok(JsStr {
+            string,
+            _context: None,
+        })
+    }
+}
+
+impl JsStr<'static> {
+    /// Like [new](JsStr::new), but keeps the context of the string alive.
+    pub(crate) fn with_context(
+        string: CStringRef<'static>,
+        context: Rc<ContextWrapper>,
+    ) -> Result<Self, ValueError> {
+        let mut string = Self::new(string)?;
+        string._context = Some(context);
+        Ok(string)
+    }
+}
+
+impl<'a> Deref for JsStr<'a> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        // Checked by `new`.
+        unsafe { std::str::from_utf8_unchecked(self.string.as_bytes()) }
+    }
+}
+
+impl<'a> AsRef<str> for JsStr<'a> {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl<'a> fmt::Debug for JsStr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a> fmt::Display for JsStr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use crate::{bindings::PersistentValue, ExecutionError};
+
+/// A handle to a Javascript symbol.
+///
+/// Symbols are returned as `JsValue::Symbol`, both as top-level results and
+/// as nested values, and can be passed back to Javascript unchanged.
+/// New symbols are created with [Context::new_symbol](crate::Context::new_symbol),
+/// [Context::symbol_for](crate::Context::symbol_for) and
+/// [Context::well_known_symbol](crate::Context::well_known_symbol).
+///
+/// Equality mirrors `===` in Javascript: two handles are equal if they refer
+/// to the same symbol, not if their descriptions match.
+///
+/// ```rust
+/// use quick_js::{Context, JsSymbol, JsValue, WellKnownSymbol};
+///
+/// let context = Context::new().unwrap();
+///
+/// let iterator: JsSymbol = context.eval_as("Symbol.iterator").unwrap();
+/// assert_eq!(iterator, context.well_known_symbol(WellKnownSymbol::Iterator).unwrap());
+/// assert_eq!(iterator.description(), Ok(Some("Symbol.iterator".to_string())));
+///
+/// let a = context.new_symbol(Some("a")).unwrap();
+/// assert_ne!(a, context.new_symbol(Some("a")).unwrap());
+/// assert_eq!(context.symbol_for("a").unwrap(), context.symbol_for("a").unwrap());
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct JsSymbol {
+    symbol: PersistentValue,
+}
+
+impl JsSymbol {
+    pub(crate) fn new(symbol: PersistentValue) -> Self {
+        Self { symbol }
+    }
+
+    pub(crate) fn persistent(&self) -> &PersistentValue {
+        &self.symbol
+    }
+
+    /// Get the description of the symbol, like `Symbol.prototype.description`.
+    ///
+    /// Returns `None` for symbols created without a description.
+    pub fn description(&self) -> Result<Option<String>, ExecutionError> {
+        self.symbol
+            .context()
+            .symbol_description(&self.symbol.as_value_ref())
+    }
+}
+
+impl fmt::Debug for JsSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.description() {
+            Ok(Some(description)) => write!(f, "Symbol({})", description),
+            _ => f.write_str("Symbol()"),
+        }
+    }
+}
+
+/// The well-known symbols of the `Symbol` constructor, used to customize
+/// built-in behaviour of objects.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum WellKnownSymbol {
+    /// `Symbol.asyncIterator`, used by `for await...of`.
+    AsyncIterator,
+    /// `Symbol.hasInstance`, used by `instanceof`.
+    HasInstance,
+    /// `Symbol.isConcatSpreadable`, used by `Array.prototype.concat`.
+    IsConcatSpreadable,
+    /// `Symbol.iterator`, used by `for...of` and spreading.
+    Iterator,
+    /// `Symbol.match`, used by `String.prototype.match`.
+    Match,
+    /// `Symbol.matchAll`, used by `String.prototype.matchAll`.
+    MatchAll,
+    /// `Symbol.replace`, used by `String.prototype.replace`.
+    Replace,
+    /// `Symbol.search`, used by `String.prototype.search`.
+    Search,
+    /// `Symbol.species`, used to create derived objects.
+    Species,
+    /// `Symbol.split`, used by `String.prototype.split`.
+    Split,
+    /// `Symbol.toPrimitive`, used to convert objects to primitives.
+    ToPrimitive,
+    /// `Symbol.toStringTag`, used by `Object.prototype.toString`.
+    ToStringTag,
+    /// `Symbol.unscopables`, used by `with` statements.
+    Unscopables,
+}
+
+impl WellKnownSymbol {
+    /// The name of the symbol property on the `Symbol` constructor.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            WellKnownSymbol::AsyncIterator => "asyncIterator",
+            WellKnownSymbol::HasInstance => "hasInstance",
+            WellKnownSymbol::IsConcatSpreadable => "isConcatSpreadable",
+            WellKnownSymbol::Iterator => "iterator",
+            WellKnownSymbol::Match => "match",
+            WellKnownSymbol::MatchAll => "matchAll",
+            WellKnownSymbol::Replace => "replace",
+            WellKnownSymbol::Search => "search",
+            WellKnownSymbol::Species => "species",
+            WellKnownSymbol::Split => "split",
+            WellKnownSymbol::ToPrimitive => "toPrimitive",
+            WellKnownSymbol::ToStringTag => "toStringTag",
+            WellKnownSymbol::Unscopables => "unscopables",
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Using a context from multiple threads.
+//! See [ContextWorker] for more info.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context as TaskContext, Poll, Waker},
+    thread,
+};
+
+use crate::{Callback, Context, ContextError, ExecutionError, JsValue};
+
+type Job = Box<dyn FnOnce(&Context) + Send>;
+
+/// A [Context] owned by a dedicated thread.
+///
+/// `Context` can only be used from the thread it was created in. A
+/// `ContextWorker` runs a context on its own thread, and is a `Send + Sync`
+/// handle that sends it requests, eg from the tasks of an async server.
+/// Requests are executed in order, and return a [Reply] future.
+///
+/// Values are copied between the threads, so values containing handles to
+/// Javascript values, like `JsValue::Function` or `JsValue::Promise`, can not
+/// be passed to or returned from the worker. Use [run](ContextWorker::run) to
+/// work with them on the worker thread.
+///
+/// The thread exits when all handles to the worker are dropped.
+///
+/// ```rust
+/// use quick_js::{JsValue, worker::ContextWorker};
+///
+/// # async fn run() {
+/// let worker = ContextWorker::new().unwrap();
+/// worker.add_callback("double", |x: i32| x * 2).await.unwrap();
+/// worker.eval("function add(a, b) { return a + b; }").await.unwrap();
+///
+/// let sum = worker.call_function("add", vec![1, 2]).await;
+/// assert_eq!(sum, Ok(JsValue::Int(3)));
+/// assert_eq!(worker.eval("double(21)").await, Ok(JsValue::Int(42)));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ContextWorker {
+    jobs: Arc<Mutex<mpsc::Sender<Job>>>,
+}
+
+impl ContextWorker {
+    /// Start a worker with a new context with default settings.
+    pub fn new() -> Result<Self, ContextError> {
+        Self::spawn(Context::new)
+    }
+
+    /// Start a worker, creating its context on the worker thread with the
+    /// given function, eg to configure it with a
+    /// [ContextBuilder](crate::ContextBuilder).
+    ///
+    /// Blocks until the context is created.
+    pub fn spawn<F>(create: F) -> Result<Self, ContextError>
+    where
+        F: FnOnce() -> Result<Context, ContextError> + Send + 'static,
+    {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let (ready, created) = mpsc::channel();
+        thread::Builder::new()
+            .name("quick-js worker".into())
+            .spawn(move || {
+                let context = match create() {
+                    Ok(context) => context,
+                    Err(e) => {
+                        let _ = ready.send(Detached(Err(detach_context_error(e))));
+                        return;
+                    }
+                };
+                let _ = ready.send(Detached(Ok(())));
+                for job in receiver {
+                    job(&context);
+                }
+            })
+            .map_err(|_| ContextError::ContextCreationFailed)?;
+
+        match created.recv() {
+            Ok(Detached(result)) => result?,
+            // The thread panicked.
+            Err(_) => return Err(ContextError::ContextCreationFailed),
+        }
+        Ok(Self {
+            jobs: Arc::new(Mutex::new(jobs)),
+        })
+    }
+
+    /// Run a function with the context on the worker thread.
+    ///
+    /// This allows working with handles to Javascript values, which can only
+    /// be used on the worker thread. Exceptions containing handles are
+    /// returned with their message as the value.
+    pub fn run<F, R>(&self, job: F) -> Reply<R>
+    where
+        F: FnOnce(&Context) -> Result<R, ExecutionError> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.send(move |context| detach(job(context)))
+    }
+
+    /// Evaluate Javascript code, like [Context::eval].
+    ///
+    /// Fails with an `ExecutionError::Internal` if the result contains
+    /// handles to Javascript values.
+    pub fn eval(&self, code: impl Into<String>) -> Reply<JsValue> {
+        let code = code.into();
+        self.send(move |context| detach_value(context.eval(&code)))
+    }
+
+    /// Call a global function, like [Context::call_function].
+    ///
+    /// Fails with an `ExecutionError::Internal` if the arguments or the
+    /// result contain handles to Javascript values.
+    pub fn call_function(
+        &self,
+        function_name: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<JsValue>>,
+    ) -> Reply<JsValue> {
+        let function_name = function_name.into();
+        let args: Vec<JsValue> = args.into_iter().map(Into::into).collect();
+        if !args.iter().all(is_detached) {
+            return Reply::ready(Detached(Err(handle_error())));
+        }
+        let args = Detached(args);
+        self.send(move |context| {
+            let Detached(args) = args;
+            detach_value(context.call_function(&function_name, args))
+        })
+    }
+
+    /// Add a global callback, like [Context::add_callback].
+    ///
+    /// The callback is executed on the worker thread. Like all requests,
+    /// callbacks can be added from any thread while the worker is running,
+    /// and are available to the requests sent afterwards. Adding a callback
+    /// with the name of an existing one replaces it, while Javascript code
+    /// keeping a reference to the old function can still call it.
+    pub fn add_callback<F>(
+        &self,
+        name: impl Into<String>,
+        callback: impl Callback<F> + Send + 'static,
+    ) -> Reply<()> {
+        let name = name.into();
+        self.send(move |context| detach(context.add_callback(&name, callback)))
+    }
+
+    /// Add a callback at a dot-separated path, like
+    /// [Context::add_callback_at], eg to add the functions of a plugin to
+    /// its own namespace object.
+    ///
+    /// See [add_callback](ContextWorker::add_callback).
+    ///
+    /// ```rust
+    /// use quick_js::{JsValue, worker::ContextWorker};
+    ///
+    /// # async fn run() {
+    /// let worker = ContextWorker::new().unwrap();
+    ///
+    /// // Plugins can be added and replaced from any thread.
+    /// let handle = worker.clone();
+    /// std::thread::spawn(move || {
+    ///     let added = handle.add_callback_at("plugins.echo.run", |s: String| s);
+    ///     futures::executor::block_on(added).unwrap();
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// assert_eq!(worker.eval("plugins.echo.run('hi')").await, Ok(JsValue::from("hi")));
+    ///
+    /// worker
+    ///     .add_callback_at("plugins.echo.run", |s: String| s.to_uppercase())
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(worker.eval("plugins.echo.run('hi')").await, Ok(JsValue::from("HI")));
+    /// # }
+    /// ```
+    pub fn add_callback_at<F>(
+        &self,
+        path: impl Into<String>,
+        callback: impl Callback<F> + Send + 'static,
+    ) -> Reply<()> {
+        let path = path.into();
+        self.send(move |context| detach(context.add_callback_at(&path, callback)))
+    }
+
+    /// Send a job to the worker thread, and reply with its result.
+    fn send<T, F>(&self, job: F) -> Reply<T>
+    where
+        F: FnOnce(&Context) -> Detached<Result<T, ExecutionError>> + Send + 'static,
+        T: 'static,
+    {
+        let slot = Arc::new(Mutex::new(Slot {
+            result: None,
+            waker: None,
+            closed: false,
+        }));
+        let responder = Responder { slot: slot.clone() };
+        let job: Job = Box::new(move |context| responder.send(job(context)));
+        // If the worker thread stopped, the job and its responder are
+        // dropped, which closes the reply.
+        let _ = self.jobs.lock().unwrap().send(job);
+        Reply { slot }
+    }
+}
+
+/// Wraps values that were checked to be safe to send to another thread.
+///
+/// `JsValue` is not `Send`, since handles to Javascript values can only be
+/// used on the thread of their context. Values without handles are plain
+/// data, so values are only wrapped after checking that they do not contain
+/// handles, eg with [detach_value].
+struct Detached<T>(T);
+
+unsafe impl<T> Send for Detached<T> {}
+
+/// Whether a value contains no handles to Javascript values.
+fn is_detached(value: &JsValue) -> bool {
+    match value {
+        JsValue::Promise(_) | JsValue::Function(_) | JsValue::Symbol(_) | JsValue::Ref(_) => false,
+        JsValue::Array(values) | JsValue::Set(values) => values.iter().all(is_detached),
+        JsValue::Object(properties) => properties.values().all(is_detached),
+        JsValue::Map(entries) => entries
+            .iter()
+            .all(|(key, value)| is_detached(key) && is_detached(value)),
+        _ => true,
+    }
+}
+
+fn handle_error() -> ExecutionError {
+    ExecutionError::Internal(
+        "Handles to Javascript values can not be sent to another thread".into(),
+    )
+}
+
+/// Replace handles in the value of an exception with its message.
+fn detach_error(error: ExecutionError) -> ExecutionError {
+    match error {
+        ExecutionError::Exception(mut e) if !is_detached(&e.value) => {
+            e.value = JsValue::String(e.message.clone());
+            ExecutionError::Exception(e)
+        }
+        error => error,
+    }
+}
+
+fn detach_context_error(error: ContextError) -> ContextError {
+    match error {
+        ContextError::Execution(e) => ContextError::Execution(detach_error(e)),
+        error => error,
+    }
+}
+
+fn detach<T: Send>(result: Result<T, ExecutionError>) -> Detached<Result<T, ExecutionError>> {
+    Detached(result.map_err(detach_error))
+}
+
+fn detach_value(
+    result: Result<JsValue, ExecutionError>,
+) -> Detached<Result<JsValue, ExecutionError>> {
+    match result {
+        Ok(value) if !is_detached(&value) => Detached(Err(handle_error())),
+        result => Detached(result.map_err(detach_error)),
+    }
+}
+
+/// The shared state of a [Reply] and its [Responder].
+struct Slot<T> {
+    result: Option<Detached<Result<T, ExecutionError>>>,
+    waker: Option<Waker>,
+    /// Set when the responder is dropped.
+    closed: bool,
+}
+
+/// Sends the result of a job to its [Reply].
+struct Responder<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Responder<T> {
+    fn send(self, result: Detached<Result<T, ExecutionError>>) {
+        self.slot.lock().unwrap().result = Some(result);
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        let mut slot = self.slot.lock().unwrap();
+        slot.closed = true;
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The result of a request to a [ContextWorker].
+///
+/// Resolves to an `ExecutionError::Internal` if the worker thread stopped,
+/// eg because a job panicked.
+pub struct Reply<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Reply<T> {
+    fn ready(result: Detached<Result<T, ExecutionError>>) -> Self {
+        Self {
+            slot: Arc::new(Mutex::new(Slot {
+                result: Some(result),
+                waker: None,
+                closed: true,
+            })),
+        }
+    }
+}
+
+impl<T> Future for Reply<T> {
+    type Output = Result<T, ExecutionError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap();
+        if let Some(Detached(result)) = slot.result.take() {
+            return Poll::Ready(result);
+        }
+        if slot.closed {
+            return Poll::Ready(Err(ExecutionError::Internal(
+                "The context worker stopped".into(),
+            )));
+        }
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}